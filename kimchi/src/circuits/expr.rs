@@ -49,6 +49,9 @@ pub enum ExprError<Column> {
 
     #[error("runtime table not available")]
     MissingRuntime,
+
+    #[error("the lookup table with ID {0} is not available")]
+    MissingLookupTable(u32),
 }
 
 pub struct Challenges<F> {
@@ -102,6 +105,14 @@ pub trait ColumnEnvironment<'a, F: FftField> {
     /// Return the value `prod_{j != 1} (1 - omega^j)`, used for efficiently
     /// computing the evaluations of the unnormalized Lagrange basis polynomials.
     fn l0_1(&self) -> F;
+
+    /// Return the offset `g` when the columns are evaluated over the cosets
+    /// `g H_k` of the domains instead of the domains themselves, e.g. to
+    /// divide by the vanishing polynomial of `H` pointwise. The unnormalized
+    /// Lagrange basis polynomials are then evaluated over the same cosets.
+    fn coset_offset(&self) -> F {
+        F::one()
+    }
 }
 
 // In this file, we define...
@@ -1129,6 +1140,23 @@ fn unnormalized_lagrange_evals<'a, F: FftField, Environment: ColumnEnvironment<'
 
     // omega_k^0, ..., omega_k^k
     let omega_k_n_pows = pows(res_domain.group_gen.pow([n]), k);
+
+    // Over a coset g res_domain, no point is in H, and
+    // l_i(g omega_k^j) = (g^n (omega_k^n)^{j mod k} - 1) / (g omega_k^j - omega^i)
+    let offset = env.coset_offset();
+    if !offset.is_one() {
+        let mut evals: Vec<F> = res_domain
+            .elements()
+            .map(|x| offset * x - omega_i)
+            .collect();
+        ark_ff::fields::batch_inversion::<F>(&mut evals[..]);
+        let offset_n = offset.pow([n]);
+        for (j, eval) in evals.iter_mut().enumerate() {
+            *eval *= offset_n * omega_k_n_pows[j % k] - F::one();
+        }
+        return Evaluations::<F, D<F>>::from_vec_and_domain(evals, res_domain);
+    }
+
     let omega_k_pows = pows(res_domain.group_gen, k);
 
     let mut evals: Vec<F> = {
@@ -0,0 +1,52 @@
+use ark_ff::UniformRand;
+use ark_poly::Evaluations;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kimchi::circuits::domains::EvaluationDomains;
+use kimchi_msm::{
+    committer::{ColumnCommitters, ColumnDescriptor},
+    BN254G1Affine, Fp, BN254,
+};
+use poly_commitment::{pairing_proof::PairingSRS, SRS as _};
+use rand::Rng;
+
+const DOMAIN_SIZE: usize = 1 << 16;
+
+pub fn column_commit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("column_commit");
+    group.sample_size(10);
+
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+    let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), DOMAIN_SIZE);
+    srs.full_srs.add_lagrange_basis(domain.d1);
+    let committers = ColumnCommitters::<BN254G1Affine>::default()
+        .with_column(0, ColumnDescriptor::Boolean)
+        .with_column(1, ColumnDescriptor::Bits(15));
+
+    let columns = [
+        (
+            "boolean",
+            (0..DOMAIN_SIZE)
+                .map(|_| Fp::from(rng.gen_range(0..2u64)))
+                .collect::<Vec<_>>(),
+        ),
+        (
+            "bits_15",
+            (0..DOMAIN_SIZE)
+                .map(|_| Fp::from(rng.gen_range(0..1u64 << 15)))
+                .collect(),
+        ),
+    ];
+    for (column, (name, evals)) in columns.iter().enumerate() {
+        let evaluations = Evaluations::from_vec_and_domain(evals.clone(), domain.d1);
+        group.bench_function(format!("generic_{name}"), |b| {
+            b.iter(|| srs.commit_evaluations_non_hiding(domain.d1, black_box(&evaluations)))
+        });
+        group.bench_function(format!("specialized_{name}"), |b| {
+            b.iter(|| committers.commit(&srs, domain, column, black_box(evals)))
+        });
+    }
+}
+
+criterion_group!(benches, column_commit);
+criterion_main!(benches);
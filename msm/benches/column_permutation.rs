@@ -0,0 +1,92 @@
+use ark_ff::UniformRand;
+use criterion::{criterion_group, criterion_main, Criterion};
+use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+use kimchi_msm::{
+    access_patterns::analyze_access_patterns,
+    column_permutation::ColumnPermutation,
+    columns::Column,
+    config::ProtocolConfig,
+    expr::{curr_cell, NamedConstraint},
+    lookups::LookupTableIDs,
+    proof::ProofInputs,
+    prover::{prove_with_context, ProverContext},
+    witness::Witness,
+    BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+};
+use poly_commitment::pairing_proof::PairingSRS;
+
+const DOMAIN_SIZE: usize = 1 << 10;
+const N: usize = 64;
+const HALF: usize = N / 2;
+
+/// The columns read by the constraint `k`, scattered over the witness:
+/// x[HALF + k] = x[a] * x[b]
+fn operands(k: usize) -> (usize, usize) {
+    ((7 * k) % HALF, (13 * k + 5) % HALF)
+}
+
+pub fn column_permutation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("column_permutation");
+    group.sample_size(10);
+
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+    let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), DOMAIN_SIZE);
+    srs.full_srs.add_lagrange_basis(domain.d1);
+
+    let constraints: Vec<_> = (0..HALF)
+        .map(|k| {
+            let (a, b) = operands(k);
+            curr_cell(Column::X(HALF + k)) - curr_cell(Column::X(a)) * curr_cell(Column::X(b))
+        })
+        .collect();
+    let mut evaluations = Witness::<N, Vec<Fp>>::zero_vec(DOMAIN_SIZE);
+    for i in 0..HALF {
+        evaluations.cols[i] = (0..DOMAIN_SIZE).map(|_| Fp::rand(&mut rng)).collect();
+    }
+    for k in 0..HALF {
+        let (a, b) = operands(k);
+        evaluations.cols[HALF + k] = (0..DOMAIN_SIZE)
+            .map(|j| evaluations.cols[a][j] * evaluations.cols[b][j])
+            .collect();
+    }
+    let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+        evaluations,
+        mvlookups: vec![],
+    };
+
+    let report = analyze_access_patterns(&NamedConstraint::from_indexed(&constraints));
+    let permutation = ColumnPermutation::from_access_report(&report, N, 0);
+    let contexts = [("identity", None), ("access_pattern", Some(permutation))];
+    for (name, permutation) in contexts {
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap();
+        let ctx = match permutation {
+            Some(permutation) => ctx.with_column_permutation(permutation),
+            None => ctx,
+        };
+        group.bench_function(format!("prove_{name}"), |b| {
+            b.iter(|| {
+                prove_with_context::<
+                    _,
+                    OpeningProof,
+                    BaseSponge,
+                    ScalarSponge,
+                    _,
+                    N,
+                    LookupTableIDs,
+                    PointEvaluations<Fp>,
+                >(&ctx, inputs.clone(), &mut rng)
+                .unwrap()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, column_permutation);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+use ark_ff::UniformRand;
+use criterion::{criterion_group, criterion_main, Criterion};
+use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+use kimchi_msm::{
+    columns::Column,
+    config::ProtocolConfig,
+    evaluation_layout::EvaluationLayout,
+    expr::curr_cell,
+    lookups::LookupTableIDs,
+    proof::ProofInputs,
+    prover::{prove_with_context, ProverContext},
+    verifier::VerifierContext,
+    witness::Witness,
+    BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+};
+use poly_commitment::pairing_proof::PairingSRS;
+
+const DOMAIN_SIZE: usize = 1 << 4;
+const N: usize = 100;
+const HALF: usize = N / 2;
+const N_PROOFS: usize = 1000;
+
+/// The columns read by the constraint `k`, scattered over the witness:
+/// x[HALF + k] = x[a] * x[b]
+fn operands(k: usize) -> (usize, usize) {
+    ((7 * k) % HALF, (13 * k + 5) % HALF)
+}
+
+pub fn evaluation_layout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluation_layout");
+    group.sample_size(10);
+
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+    let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), DOMAIN_SIZE);
+    srs.full_srs.add_lagrange_basis(domain.d1);
+
+    let constraints: Vec<_> = (0..HALF)
+        .map(|k| {
+            let (a, b) = operands(k);
+            curr_cell(Column::X(HALF + k)) - curr_cell(Column::X(a)) * curr_cell(Column::X(b))
+        })
+        .collect();
+    let prover_ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+        domain,
+        &srs,
+        ProtocolConfig::default(),
+        &constraints,
+    )
+    .unwrap();
+    let proofs: Vec<_> = (0..N_PROOFS)
+        .map(|_| {
+            let mut evaluations = Witness::<N, Vec<Fp>>::zero_vec(DOMAIN_SIZE);
+            for i in 0..HALF {
+                evaluations.cols[i] = (0..DOMAIN_SIZE).map(|_| Fp::rand(&mut rng)).collect();
+            }
+            for k in 0..HALF {
+                let (a, b) = operands(k);
+                evaluations.cols[HALF + k] = (0..DOMAIN_SIZE)
+                    .map(|j| evaluations.cols[a][j] * evaluations.cols[b][j])
+                    .collect();
+            }
+            let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+                evaluations,
+                mvlookups: vec![],
+            };
+            prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&prover_ctx, inputs, &mut rng)
+            .unwrap()
+        })
+        .collect();
+
+    let layouts = [
+        ("per_column", EvaluationLayout::PerColumn),
+        ("flat", EvaluationLayout::Flat),
+    ];
+    for (name, layout) in layouts {
+        let ctx = VerifierContext::from_prover_context(&prover_ctx)
+            .unwrap()
+            .with_evaluation_layout(layout);
+        group.bench_function(format!("verify_{N_PROOFS}_proofs_{name}"), |b| {
+            b.iter(|| {
+                for proof in proofs.iter() {
+                    ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                        proof,
+                        Witness::zero_vec(1),
+                    )
+                    .unwrap()
+                }
+            })
+        });
+    }
+}
+
+criterion_group!(benches, evaluation_layout);
+criterion_main!(benches);
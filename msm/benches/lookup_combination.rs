@@ -0,0 +1,41 @@
+use ark_ff::UniformRand;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kimchi_msm::{
+    mvlookup::{reference, VectorCombiner},
+    Fp,
+};
+
+const N_VALUES: usize = 1 << 16;
+
+pub fn lookup_combination(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup_combination");
+    group.sample_size(10);
+
+    let mut rng = o1_utils::tests::make_test_rng();
+    let joint_combiner = Fp::rand(&mut rng);
+    for width in [1, 2, 4, 6] {
+        let values: Vec<Vec<Fp>> = (0..N_VALUES)
+            .map(|_| (0..width).map(|_| Fp::rand(&mut rng)).collect())
+            .collect();
+        group.bench_function(format!("horner_width_{width}"), |b| {
+            b.iter(|| {
+                values
+                    .iter()
+                    .map(|value| reference::combine_values(joint_combiner, black_box(value)))
+                    .collect::<Vec<_>>()
+            })
+        });
+        group.bench_function(format!("combiner_width_{width}"), |b| {
+            b.iter(|| {
+                let mut combiner = VectorCombiner::new(joint_combiner);
+                values
+                    .iter()
+                    .map(|value| combiner.combine(black_box(value)))
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, lookup_combination);
+criterion_main!(benches);
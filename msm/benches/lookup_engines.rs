@@ -0,0 +1,98 @@
+use ark_ff::UniformRand;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kimchi::circuits::domains::EvaluationDomains;
+use kimchi_msm::{
+    columns::Column,
+    lookup_engine::{
+        LookupChallenges, LookupEngine, LookupEngineKind, LookupEngines, MVLookupEngine,
+        PlookupEngine,
+    },
+    lookups::LookupTableIDs,
+    proof::ProofInputs,
+    prover::prove,
+    test::column_lookups,
+    witness::Witness,
+    BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+};
+use poly_commitment::pairing_proof::PairingSRS;
+use rand::Rng;
+use std::collections::BTreeMap;
+
+const DOMAIN_SIZE: usize = 1 << 16;
+
+/// A 4-bit range check of a single column, with the MVLookup engine and with
+/// the plookup engine
+pub fn lookup_engines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup_engines");
+    group.sample_size(10);
+
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+    let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), DOMAIN_SIZE);
+    srs.full_srs.add_lagrange_basis(domain.d1);
+
+    let cols = [(0..DOMAIN_SIZE)
+        .map(|_| Fp::from(rng.gen_range(0..16u64)))
+        .collect::<Vec<_>>()];
+    let (lookups, witness) = column_lookups(domain, 0, 16, &cols, &[0]);
+    let table_id = LookupTableIDs::Custom(0);
+    let lookups_map = BTreeMap::from([(table_id, lookups)]);
+    let witnesses = vec![witness];
+    let challenges = LookupChallenges {
+        joint_combiner: Fp::rand(&mut rng),
+        beta: Fp::rand(&mut rng),
+        gamma: Fp::rand(&mut rng),
+    };
+
+    group.bench_function("witness_mvlookup", |b| {
+        b.iter(|| {
+            let first_phase = MVLookupEngine
+                .first_phase(black_box(&witnesses), DOMAIN_SIZE)
+                .unwrap();
+            MVLookupEngine
+                .second_phase(&witnesses, &first_phase, &challenges, DOMAIN_SIZE)
+                .unwrap()
+        })
+    });
+    group.bench_function("witness_plookup", |b| {
+        b.iter(|| {
+            let first_phase = PlookupEngine
+                .first_phase(black_box(&witnesses), DOMAIN_SIZE)
+                .unwrap();
+            PlookupEngine
+                .second_phase(&witnesses, &first_phase, &challenges, DOMAIN_SIZE)
+                .unwrap()
+        })
+    });
+
+    for (name, kind) in [
+        ("prove_mvlookup", LookupEngineKind::MVLookup),
+        ("prove_plookup", LookupEngineKind::Plookup),
+    ] {
+        let constraints = LookupEngines::new()
+            .with_engine(table_id, kind)
+            .constraints(&lookups_map)
+            .unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let inputs = ProofInputs {
+                    evaluations: Witness {
+                        cols: Box::new(cols.clone()),
+                    },
+                    mvlookups: witnesses.clone(),
+                };
+                prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, 1, _>(
+                    domain,
+                    &srs,
+                    black_box(&constraints),
+                    inputs,
+                    &mut rng,
+                )
+                .unwrap()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, lookup_engines);
+criterion_main!(benches);
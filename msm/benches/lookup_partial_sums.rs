@@ -0,0 +1,47 @@
+use ark_ff::UniformRand;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kimchi::circuits::domains::EvaluationDomains;
+use kimchi_msm::{
+    lookups::LookupWitness,
+    mvlookup::{prover, reference},
+    perf::PerfCounters,
+    Fp,
+};
+
+const DOMAIN_SIZE: usize = 1 << 16;
+/// With the fixed table, 48 columns, i.e. 8 chunks
+const N_QUERIES: usize = 47;
+
+pub fn lookup_partial_sums(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup_partial_sums");
+    group.sample_size(10);
+
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+    let lookups = vec![LookupWitness::random_with_table(
+        domain,
+        0,
+        1 << 10,
+        N_QUERIES,
+    )];
+    let beta = Fp::rand(&mut rng);
+    let joint_combiner = Fp::rand(&mut rng);
+    group.bench_function("sequential_8_chunks", |b| {
+        b.iter(|| reference::partial_sums(black_box(&lookups), beta, joint_combiner, DOMAIN_SIZE))
+    });
+    group.bench_function("parallel_8_chunks", |b| {
+        b.iter(|| {
+            let perf = PerfCounters::default();
+            prover::partial_sums(
+                black_box(&lookups),
+                beta,
+                joint_combiner,
+                DOMAIN_SIZE,
+                &perf,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, lookup_partial_sums);
+criterion_main!(benches);
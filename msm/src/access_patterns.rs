@@ -0,0 +1,293 @@
+//! Static report of the columns read by each constraint, and at which row, to
+//! choose the layout of the witness in memory (row-major or column-major).
+
+use kimchi::circuits::{expr::FormattedOutput, gate::CurrOrNext};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use crate::{
+    columns::Column,
+    expr::{column_accesses, NamedConstraint},
+    mvlookup::fixed_table_read_outside_lookups,
+};
+
+/// The rows at which a constraint reads a column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RowAccess {
+    pub curr: bool,
+    pub next: bool,
+}
+
+/// The columns read by a constraint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintAccess {
+    pub name: String,
+    pub columns: BTreeMap<Column, RowAccess>,
+}
+
+/// The access patterns of a set of constraints, see
+/// [analyze_access_patterns].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessReport {
+    /// The columns read by each constraint, in the order of the constraints
+    pub constraints: Vec<ConstraintAccess>,
+    /// The columns read by at least one constraint
+    pub columns_read: BTreeSet<Column>,
+    /// The maximum number of columns read by a single constraint
+    pub max_columns_per_constraint: usize,
+    /// The witness columns which are never read, below the largest index read
+    pub columns_never_read: Vec<Column>,
+    /// The columns read both at the current and at the next row, possibly by
+    /// different constraints
+    pub columns_read_at_both_rows: Vec<Column>,
+    /// The constraints reading the column of a fixed table outside of the
+    /// lookup argument, see [fixed_table_read_outside_lookups]
+    pub fixed_table_readers: Vec<String>,
+}
+
+impl AccessReport {
+    /// Returns the indices of the `n_columns` witness columns which are not
+    /// read by any constraint, at any shift.
+    pub fn unread_witness_columns(&self, n_columns: usize) -> Vec<usize> {
+        let read: BTreeSet<usize> = self
+            .columns_read
+            .iter()
+            .filter_map(Column::witness_index)
+            .collect();
+        (0..n_columns).filter(|i| !read.contains(i)).collect()
+    }
+}
+
+/// Returns the columns read by each of `constraints`, and the aggregated
+/// statistics.
+pub fn analyze_access_patterns<F>(named_constraints: &[NamedConstraint<F>]) -> AccessReport {
+    let constraints: Vec<ConstraintAccess> = named_constraints
+        .iter()
+        .map(|NamedConstraint { name, constraint }| {
+            let mut columns: BTreeMap<Column, RowAccess> = BTreeMap::new();
+            for (col, row) in column_accesses(constraint) {
+                let access = columns.entry(col).or_default();
+                match row {
+                    CurrOrNext::Curr => access.curr = true,
+                    CurrOrNext::Next => access.next = true,
+                }
+            }
+            ConstraintAccess {
+                name: name.clone(),
+                columns,
+            }
+        })
+        .collect();
+
+    let mut rows_read: BTreeMap<Column, RowAccess> = BTreeMap::new();
+    for (col, access) in constraints.iter().flat_map(|c| c.columns.iter()) {
+        let rows = rows_read.entry(*col).or_default();
+        rows.curr |= access.curr;
+        rows.next |= access.next;
+    }
+    let columns_read: BTreeSet<Column> = rows_read.keys().copied().collect();
+
+    let witness_read: BTreeSet<usize> = columns_read
+        .iter()
+        .filter_map(Column::witness_index)
+        .collect();
+    let columns_never_read = match witness_read.last() {
+        Some(max) => (0..*max)
+            .filter(|i| !witness_read.contains(i))
+            .map(Column::X)
+            .collect(),
+        None => vec![],
+    };
+
+    AccessReport {
+        max_columns_per_constraint: constraints
+            .iter()
+            .map(|c| c.columns.len())
+            .max()
+            .unwrap_or(0),
+        columns_never_read,
+        columns_read_at_both_rows: rows_read
+            .iter()
+            .filter(|(_, rows)| rows.curr && rows.next)
+            .map(|(col, _)| *col)
+            .collect(),
+        fixed_table_readers: named_constraints
+            .iter()
+            .filter(|named| fixed_table_read_outside_lookups(&named.constraint).is_some())
+            .map(|named| named.name.clone())
+            .collect(),
+        columns_read,
+        constraints,
+    }
+}
+
+/// Renders the matrix of the accesses, one line per constraint and one column
+/// per column read. `c` (resp. `n`) means the column is read at the current
+/// (resp. next) row.
+impl fmt::Display for AccessReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column_name = |col: &Column| col.text(&mut HashMap::new());
+        let names: Vec<String> = self.columns_read.iter().map(column_name).collect();
+        let name_width = self
+            .constraints
+            .iter()
+            .map(|c| c.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let widths: Vec<usize> = names.iter().map(|n| n.chars().count().max(2)).collect();
+
+        write!(f, "{:name_width$}", "")?;
+        for (name, width) in names.iter().zip(widths.iter()) {
+            write!(f, " {name:>width$}")?;
+        }
+        writeln!(f)?;
+        for constraint in self.constraints.iter() {
+            write!(f, "{:name_width$}", constraint.name)?;
+            for (col, width) in self.columns_read.iter().zip(widths.iter()) {
+                let cell = match constraint.columns.get(col) {
+                    None => ".",
+                    Some(RowAccess {
+                        curr: true,
+                        next: true,
+                    }) => "cn",
+                    Some(RowAccess { next: true, .. }) => "n",
+                    Some(_) => "c",
+                };
+                write!(f, " {cell:>width$}")?;
+            }
+            writeln!(f)?;
+        }
+        let list = |cols: &[Column]| cols.iter().map(column_name).collect::<Vec<_>>().join(", ");
+        writeln!(
+            f,
+            "max columns per constraint: {}",
+            self.max_columns_per_constraint
+        )?;
+        writeln!(
+            f,
+            "columns never read: [{}]",
+            list(&self.columns_never_read)
+        )?;
+        writeln!(
+            f,
+            "columns read at both rows: [{}]",
+            list(&self.columns_read_at_both_rows)
+        )?;
+        write!(
+            f,
+            "fixed tables read outside of the lookups: [{}]",
+            self.fixed_table_readers.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expr::{curr_cell, shift2_cell},
+        lookups::{booleanity, LookupTableIDs},
+        mvlookup::{constraint_lookups, LookupLayout},
+        Fp,
+    };
+
+    #[test]
+    fn test_lookup_constraints_access_patterns() {
+        let lookups = BTreeMap::from([(
+            LookupTableIDs::Bit,
+            booleanity::lookups::<Fp>(&[Column::X(0), Column::X(2)]),
+        )]);
+        let layout = LookupLayout::new(&lookups);
+        let constraints: Vec<NamedConstraint<Fp>> = constraint_lookups(&lookups)
+            .into_iter()
+            .zip(["partial_sum", "aggregation"])
+            .map(|(constraint, name)| NamedConstraint::new(name, constraint))
+            .collect();
+        let report = analyze_access_patterns(&constraints);
+
+        let curr = RowAccess {
+            curr: true,
+            next: false,
+        };
+        let both = RowAccess {
+            curr: true,
+            next: true,
+        };
+        let h0 = layout.partial_sum(0).unwrap();
+        assert_eq!(
+            report.constraints,
+            vec![
+                ConstraintAccess {
+                    name: String::from("partial_sum"),
+                    columns: BTreeMap::from([
+                        (Column::X(0), curr),
+                        (Column::X(2), curr),
+                        (h0, curr),
+                        (layout.multiplicity(LookupTableIDs::Bit).unwrap(), curr),
+                        (layout.fixed_table(LookupTableIDs::Bit).unwrap(), curr),
+                    ]),
+                },
+                ConstraintAccess {
+                    name: String::from("aggregation"),
+                    columns: BTreeMap::from([(h0, curr), (layout.aggregation(), both),]),
+                },
+            ]
+        );
+        assert_eq!(report.max_columns_per_constraint, 5);
+        assert_eq!(report.columns_never_read, vec![Column::X(1)]);
+        assert_eq!(
+            report.columns_read_at_both_rows,
+            vec![Column::LookupAggregation]
+        );
+        assert_eq!(report.unread_witness_columns(4), vec![1, 3]);
+        assert!(report.fixed_table_readers.is_empty());
+
+        let expected = "            x[0] x[2] h[0] m[2]  φ t[2]
+partial_sum    c    c    c    c  .    c
+aggregation    .    .    c    . cn    .
+max columns per constraint: 5
+columns never read: [x[1]]
+columns read at both rows: [φ]
+fixed tables read outside of the lookups: []";
+        assert_eq!(report.to_string(), expected);
+    }
+
+    #[test]
+    fn test_fixed_table_readers() {
+        let lookups = BTreeMap::from([(
+            LookupTableIDs::Bit,
+            booleanity::lookups::<Fp>(&[Column::X(0)]),
+        )]);
+        let layout = LookupLayout::new(&lookups);
+        let table = layout.fixed_table(LookupTableIDs::Bit).unwrap();
+        let mut constraints: Vec<NamedConstraint<Fp>> = constraint_lookups(&lookups)
+            .into_iter()
+            .zip(["partial_sum", "aggregation"])
+            .map(|(constraint, name)| NamedConstraint::new(name, constraint))
+            .collect();
+        constraints.push(NamedConstraint::new(
+            "reads_table",
+            curr_cell(Column::X(1)) - curr_cell(table),
+        ));
+        let report = analyze_access_patterns(&constraints);
+        assert_eq!(
+            report.fixed_table_readers,
+            vec![String::from("reads_table")]
+        );
+        assert!(report
+            .to_string()
+            .ends_with("fixed tables read outside of the lookups: [reads_table]"));
+    }
+
+    #[test]
+    fn test_shifted_columns_are_read() {
+        let constraints = NamedConstraint::from_indexed(&[
+            curr_cell::<Fp>(Column::X(0)) - shift2_cell(Column::X(2))
+        ]);
+        let report = analyze_access_patterns(&constraints);
+        assert_eq!(report.columns_never_read, vec![Column::X(1)]);
+        assert_eq!(report.unread_witness_columns(3), vec![1]);
+    }
+}
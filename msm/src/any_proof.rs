@@ -0,0 +1,254 @@
+//! Proofs over a curve chosen at runtime.
+//! [AnyProof] wraps the proofs over each of the supported curves, and
+//! [verify_any] deserializes and verifies a proof with the parameters of its
+//! curve, given in [AnyProtocolConfig].
+//! The supported curves are selected with the features `curve-bn254`,
+//! `curve-pallas` and `curve-vesta`. The proofs over the Pasta curves use the
+//! IPA polynomial commitment scheme, the ones over BN254 the pairing-based one.
+//! The proofs do not have public inputs.
+
+use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
+use poly_commitment::OpenProof;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ProtocolConfig,
+    expr::E,
+    mvlookup::LookupTableID,
+    proof::Proof,
+    verifier::{VerifierContext, VerifierError},
+    witness::Witness,
+};
+
+/// The parameters to verify the proofs over the curve `G`
+pub struct CurveConfig<G: KimchiCurve, OpeningProof: OpenProof<G>> {
+    pub domain: EvaluationDomains<G::ScalarField>,
+    pub srs: OpeningProof::SRS,
+    pub config: ProtocolConfig,
+    pub constraints: Vec<E<G::ScalarField>>,
+}
+
+/// Define [AnyProof] and [AnyProtocolConfig] for the given curves. Each line
+/// gives the name of the variant, the one of the field of the configuration,
+/// the curve, the opening proof and the two sponges.
+macro_rules! any_proof {
+    ($($(#[$attr:meta])* $variant:ident, $field:ident: $curve:ty, $opening:ty, $fq_sponge:ty, $fr_sponge:ty;)*) => {
+        /// A proof over one of the supported curves
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(bound(serialize = "", deserialize = ""))]
+        pub enum AnyProof<const N: usize, ID: LookupTableID> {
+            $(
+                $(#[$attr])*
+                $variant(Proof<N, $curve, $opening, ID>),
+            )*
+        }
+
+        /// The parameters of the verifier, for each of the supported curves.
+        /// The proofs over a curve whose parameters are missing are rejected.
+        #[derive(Default)]
+        pub struct AnyProtocolConfig {
+            $(
+                $(#[$attr])*
+                pub $field: Option<CurveConfig<$curve, $opening>>,
+            )*
+        }
+
+        $(
+            $(#[$attr])*
+            impl<const N: usize, ID: LookupTableID> From<Proof<N, $curve, $opening, ID>>
+                for AnyProof<N, ID>
+            {
+                fn from(proof: Proof<N, $curve, $opening, ID>) -> Self {
+                    AnyProof::$variant(proof)
+                }
+            }
+        )*
+
+        impl<const N: usize, ID: LookupTableID> AnyProof<N, ID> {
+            /// The name of the curve of the proof
+            pub fn curve(&self) -> &'static str {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        AnyProof::$variant(_) => stringify!($field),
+                    )*
+                }
+            }
+
+            /// Verify the proof with the parameters of its curve
+            pub fn verify(&self, config: &AnyProtocolConfig) -> Result<(), VerifierError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        AnyProof::$variant(proof) => {
+                            let CurveConfig {
+                                domain,
+                                srs,
+                                config,
+                                constraints,
+                            } = config
+                                .$field
+                                .as_ref()
+                                .ok_or(VerifierError::UnsupportedCurve(stringify!($field)))?;
+                            VerifierContext::<_, $opening, ID>::new(
+                                *domain,
+                                srs,
+                                config.clone(),
+                                constraints,
+                            )?
+                            .verify::<$fq_sponge, $fr_sponge, N, 0, _>(
+                                proof,
+                                Witness { cols: Box::new([]) },
+                            )
+                        }
+                    )*
+                }
+            }
+        }
+    };
+}
+
+any_proof! {
+    #[cfg(feature = "curve-bn254")]
+    Bn254, bn254: crate::BN254G1Affine, crate::OpeningProof, crate::BaseSponge, crate::ScalarSponge;
+    #[cfg(feature = "curve-pallas")]
+    Pallas, pallas: pasta::Pallas, pasta::IpaProof<pasta::Pallas>, pasta::PallasBaseSponge, pasta::PallasScalarSponge;
+    #[cfg(feature = "curve-vesta")]
+    Vesta, vesta: pasta::Vesta, pasta::IpaProof<pasta::Vesta>, pasta::VestaBaseSponge, pasta::VestaScalarSponge;
+}
+
+/// The instantiations of the proofs over the Pasta curves
+#[cfg(any(feature = "curve-pallas", feature = "curve-vesta"))]
+pub mod pasta {
+    use crate::SpongeParams;
+    use mina_poseidon::sponge::{DefaultFqSponge, DefaultFrSponge};
+
+    pub use mina_curves::pasta::{Pallas, Vesta};
+    pub use poly_commitment::evaluation_proof::OpeningProof as IpaProof;
+
+    pub type PallasBaseSponge = DefaultFqSponge<mina_curves::pasta::PallasParameters, SpongeParams>;
+    pub type PallasScalarSponge = DefaultFrSponge<mina_curves::pasta::Fq, SpongeParams>;
+    pub type VestaBaseSponge = DefaultFqSponge<mina_curves::pasta::VestaParameters, SpongeParams>;
+    pub type VestaScalarSponge = DefaultFrSponge<mina_curves::pasta::Fp, SpongeParams>;
+}
+
+/// Deserialize a proof encoded with [AnyProof::to_bytes], and verify it with
+/// the parameters of its curve.
+pub fn verify_any<const N: usize, ID: LookupTableID>(
+    bytes: &[u8],
+    config: &AnyProtocolConfig,
+) -> Result<(), VerifierError> {
+    AnyProof::<N, ID>::from_bytes(bytes)?.verify(config)
+}
+
+impl<const N: usize, ID: LookupTableID> AnyProof<N, ID> {
+    /// Encode the proof and the tag of its curve with MessagePack
+    pub fn to_bytes(&self) -> Vec<u8> {
+        rmp_serde::to_vec(self).unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VerifierError> {
+        rmp_serde::from_slice(bytes).map_err(|err| VerifierError::Deserialization(err.to_string()))
+    }
+}
+
+#[cfg(all(test, feature = "curve-pallas", feature = "curve-vesta"))]
+mod tests {
+    use super::{
+        pasta::{
+            IpaProof, Pallas, PallasBaseSponge, PallasScalarSponge, Vesta, VestaBaseSponge,
+            VestaScalarSponge,
+        },
+        verify_any, AnyProof, AnyProtocolConfig, CurveConfig,
+    };
+    use crate::{
+        columns::Column, config::ProtocolConfig, lookups::LookupTableIDs, proof::Proof,
+        prover::prove, test::random_lookup_circuit, verifier::VerifierError,
+    };
+    use ark_ff::PrimeField;
+    use kimchi::{
+        circuits::domains::EvaluationDomains, curve::KimchiCurve, plonk_sponge::FrSponge,
+    };
+    use mina_poseidon::FqSponge;
+    use poly_commitment::{
+        commitment::{CommitmentCurve, EndoCurve},
+        srs::SRS,
+    };
+    use serde::Serialize;
+
+    const N: usize = 2;
+
+    type TestProof<G> = Proof<N, G, IpaProof<G>, LookupTableIDs>;
+
+    fn prove_over<G, EFqSponge, EFrSponge>() -> (TestProof<G>, CurveConfig<G, IpaProof<G>>)
+    where
+        G: KimchiCurve + CommitmentCurve + EndoCurve,
+        G::BaseField: PrimeField,
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+    {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let domain = EvaluationDomains::<G::ScalarField>::create(domain_size).unwrap();
+        let mut srs = SRS::<G>::create(domain_size);
+        srs.add_lagrange_basis(domain.d1);
+
+        let (constraints, inputs) = random_lookup_circuit::<N, G, _>(domain, 1, 16, 2, &mut rng);
+        let proof = prove::<_, IpaProof<G>, EFqSponge, EFrSponge, Column, _, N, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        let config = CurveConfig {
+            domain,
+            srs,
+            config: ProtocolConfig::default(),
+            constraints,
+        };
+        (proof, config)
+    }
+
+    #[test]
+    fn test_verify_any_over_pallas_and_vesta() {
+        let (pallas_proof, pallas_config) =
+            prove_over::<Pallas, PallasBaseSponge, PallasScalarSponge>();
+        let (vesta_proof, vesta_config) = prove_over::<Vesta, VestaBaseSponge, VestaScalarSponge>();
+        let config = AnyProtocolConfig {
+            pallas: Some(pallas_config),
+            vesta: Some(vesta_config),
+            ..Default::default()
+        };
+
+        let pallas_bytes = AnyProof::from(pallas_proof.clone()).to_bytes();
+        let vesta_bytes = AnyProof::from(vesta_proof).to_bytes();
+        verify_any::<N, LookupTableIDs>(&pallas_bytes, &config).unwrap();
+        verify_any::<N, LookupTableIDs>(&vesta_bytes, &config).unwrap();
+
+        // A Pallas proof tagged as a Vesta proof is rejected
+        #[derive(Serialize)]
+        #[allow(dead_code)]
+        enum Mislabeled<'a, T> {
+            Bn254(&'a T),
+            Pallas(&'a T),
+            Vesta(&'a T),
+        }
+        let mislabeled = rmp_serde::to_vec(&Mislabeled::Vesta(&pallas_proof)).unwrap();
+        assert!(matches!(
+            verify_any::<N, LookupTableIDs>(&mislabeled, &config),
+            Err(VerifierError::Deserialization(_))
+        ));
+
+        // The verifier is not configured for Pallas
+        let config = AnyProtocolConfig {
+            vesta: config.vesta,
+            ..Default::default()
+        };
+        assert_eq!(
+            verify_any::<N, LookupTableIDs>(&pallas_bytes, &config),
+            Err(VerifierError::UnsupportedCurve("pallas"))
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! The stable interface of the crate.
+//!
+//! The items re-exported here follow the semantic versioning of the crate: a
+//! breaking change to any of them is a breaking change of the crate. The
+//! other modules are internal, and are only public with the feature
+//! `unstable-internals`, without any guarantee between two versions.
+//!
+//! A circuit is defined by its constraints, written as expressions [E] over
+//! the columns [Column], and by the fixed tables it looks up into, see
+//! [CircuitDefinition]. The tables are identified by an implementation of
+//! [LookupTableID], e.g. defined with [define_lookup_tables] or
+//! [lookup_table_id]. The witness is built one row at a time with a
+//! [RowWriter], see [ProofInputs::from_step_fn], and proven with [prove] or
+//! [prove_circuit].
+
+pub use crate::{
+    circuit::{
+        prove_circuit, verify_circuit, verify_circuit_with_public, CircuitDefinition, CircuitError,
+        CircuitProverContext, CircuitVerifierContext,
+    },
+    columns::{Column, ColumnIndexer, PartialSumIdx, TableIdx},
+    config::{ConfigError, ProtocolConfig},
+    define_lookup_tables,
+    domain::create_domain,
+    expr::{NamedConstraint, E},
+    lookup_engine::{
+        LookupEngine, LookupEngineError, LookupEngineKind, LookupEngines, MVLookupEngine,
+        PlookupColumn, PlookupColumns, PlookupEngine,
+    },
+    lookup_table_id,
+    lookups::{Lookup, LookupTableIDs},
+    mvlookup::{
+        verify_table_id_impl, LookupTable, LookupTableID, MVLookup, MVLookupWitness,
+        TableIdImplError, MAX_TABLE_ID,
+    },
+    precomputed_srs::get_bn254_srs,
+    proof::{Proof, ProofInputs},
+    prover::{prove, ProverError},
+    public_schema::{PublicFieldType, PublicSchema, PublicSchemaError, PublicValue},
+    trace::{RowWriter, TraceError, TraceLayout},
+    verifier::{try_verify, verify, VerifierError},
+    witness::{Witness, WitnessError},
+    BaseSponge, Ff1, Ff2, Fp, OpeningProof, ScalarSponge, BN254,
+};
+
+/// The circuit of the foreign field additions, run by the binary `ffa`
+pub mod ffa {
+    pub use crate::ffa::{
+        columns::{FFA_NPUB_COLUMNS, FFA_N_COLUMNS},
+        constraint::ConstraintBuilderEnv,
+        interpreter::{constrain_ff_addition, ff_addition_circuit, FFAInterpreterEnv},
+        witness::WitnessBuilderEnv,
+    };
+}
+
+/// Tools for the tests of the circuits built with this crate
+pub mod testing {
+    pub use crate::test::test_completeness_generic;
+}
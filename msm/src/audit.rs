@@ -0,0 +1,141 @@
+//! Audits of the proofs before they are released by the prover, e.g. for a
+//! prover service whose proofs must be checked before leaving it.
+//!
+//! A [ProofAuditor] inspects a finished proof with the [VerifierContext] of
+//! the circuit it is created for. [crate::prover::prove_audited] runs the
+//! auditors in order, and only returns the proof if all of them accept it.
+//! The built-in [SelfVerifyAuditor] verifies the proof with
+//! [VerifierContext::verify], i.e. with the verifier of the receivers of the
+//! proof, and [MaxSizeAuditor] bounds the size of its serialization. Other
+//! policies, e.g. a deny-list of digests of proofs, see
+//! [crate::proof::StableHash], implement [ProofAuditor].
+
+use std::marker::PhantomData;
+
+use kimchi::{curve::KimchiCurve, plonk_sponge::FrSponge};
+use mina_poseidon::FqSponge;
+use poly_commitment::OpenProof;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    mvlookup::LookupTableID,
+    proof::{EvaluationContainer, Proof},
+    verifier::{VerifierContext, VerifierError},
+    witness::Witness,
+};
+
+/// The reasons for which an auditor refuses to release a proof
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AuditError {
+    #[error("the proof does not verify: {0}")]
+    Verification(VerifierError),
+
+    #[error("the proof has {size} bytes, more than the limit of {max_size}")]
+    TooLarge { size: usize, max_size: usize },
+
+    #[error("the proof is rejected by the auditor {auditor}: {reason}")]
+    Rejected { auditor: String, reason: String },
+}
+
+/// A check of a proof before it is released, see the module documentation
+pub trait ProofAuditor<
+    const N: usize,
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>
+{
+    /// Accept or reject `proof`, created for the circuit of `ctx`
+    fn audit(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        ctx: &VerifierContext<G, OpeningProof, ID>,
+    ) -> Result<(), AuditError>;
+}
+
+/// Verify the proof with [VerifierContext::verify], as its receivers would.
+/// The proofs of [crate::prover::prove_audited] have no public inputs.
+pub struct SelfVerifyAuditor<EFqSponge, EFrSponge> {
+    _sponges: PhantomData<fn() -> (EFqSponge, EFrSponge)>,
+}
+
+impl<EFqSponge, EFrSponge> SelfVerifyAuditor<EFqSponge, EFrSponge> {
+    pub fn new() -> Self {
+        SelfVerifyAuditor {
+            _sponges: PhantomData,
+        }
+    }
+}
+
+impl<EFqSponge, EFrSponge> Default for SelfVerifyAuditor<EFqSponge, EFrSponge> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+    > ProofAuditor<N, G, OpeningProof, ID, Eval> for SelfVerifyAuditor<EFqSponge, EFrSponge>
+where
+    OpeningProof::SRS: Sync,
+{
+    fn audit(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        ctx: &VerifierContext<G, OpeningProof, ID>,
+    ) -> Result<(), AuditError> {
+        ctx.verify::<EFqSponge, EFrSponge, N, 0, Eval>(proof, Witness { cols: Box::new([]) })
+            .map_err(AuditError::Verification)
+    }
+}
+
+/// Bound the size of the MessagePack serialization of the proof, which is
+/// the one of [crate::proof::StableHash]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxSizeAuditor {
+    pub max_size: usize,
+}
+
+impl MaxSizeAuditor {
+    /// Reject the proofs of more than `max_size` bytes
+    pub fn new(max_size: usize) -> Self {
+        MaxSizeAuditor { max_size }
+    }
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > ProofAuditor<N, G, OpeningProof, ID, Eval> for MaxSizeAuditor
+where
+    Proof<N, G, OpeningProof, ID, Eval>: Serialize,
+{
+    fn audit(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        _ctx: &VerifierContext<G, OpeningProof, ID>,
+    ) -> Result<(), AuditError> {
+        let size = rmp_serde::to_vec(proof)
+            .expect("The proof types can always be serialized")
+            .len();
+        if size > self.max_size {
+            Err(AuditError::TooLarge {
+                size,
+                max_size: self.max_size,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,513 @@
+//! Merkle commitments to the columns of a witness, for publishing the columns
+//! with cheap openings of single cells, e.g. for data availability.
+//!
+//! Each column is committed with a binary Merkle tree whose leaves are the
+//! cells of the column, the nodes being hashed with [hash_tuple], i.e. the
+//! Poseidon sponge of [crate::SpongeParams]. The nodes are indexed in
+//! breadth-first order: the root is the node `0`, the children of the node `i`
+//! are the nodes `2 i + 1` and `2 i + 2`, and the cell of the row `j` is the
+//! leaf `n - 1 + j`, `n` being the length of the column.
+//!
+//! The Merkle commitments are independent of the polynomial commitments of
+//! the proof. [RootBinding] constrains the root of a column of the witness to
+//! be a public input of the proof, linking both commitments. It recomputes the
+//! `n - 1` inner nodes of the tree, one per row, with [HashedLookup], and
+//! checks that each inner node is read as a child or as the root exactly once
+//! with a running sum of `± 1 / (γ + index + δ value)`, `γ` and `δ` being
+//! extra challenges coined after the columns of the tree are committed. The
+//! inverses of the terms are committed too, to keep the degree of the
+//! constraints low. It adds `2 + 55 * 3` columns for the hashes and `6`
+//! columns after the challenges, besides the public ones.
+
+use ark_ff::{One, PrimeField, Zero};
+use kimchi::circuits::expr::{ConstantExpr, ConstantTerm, ExprInner};
+use mina_poseidon::poseidon::ArithmeticSpongeParams;
+use std::collections::BTreeMap;
+
+use crate::{
+    columns::Column,
+    expr::{curr_cell, next_cell, E},
+    hashed_lookup::{hash_tuple, HashedLookup},
+    witness::Witness,
+};
+
+/// The number of public columns of a [RootBinding]
+pub const BINDING_PUBLIC_COLUMNS: usize = 4;
+
+/// The number of columns of a [RootBinding] committed after the extra
+/// challenges are coined
+pub const BINDING_SECOND_PHASE_COLUMNS: usize = 6;
+
+/// A Merkle tree over a column, see the module documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree<F> {
+    /// The nodes, in breadth-first order
+    nodes: Vec<F>,
+}
+
+/// The siblings of the nodes on the path from a leaf to the root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerklePath<F> {
+    pub row: usize,
+    /// The siblings, from the leaf to the children of the root
+    pub siblings: Vec<F>,
+}
+
+impl<F: PrimeField> MerkleTree<F> {
+    /// Build the tree of `column`, whose length must be a power of two
+    pub fn new(params: &ArithmeticSpongeParams<F>, column: &[F]) -> Self {
+        let n = column.len();
+        assert!(
+            n.is_power_of_two(),
+            "the length of the column must be a power of two"
+        );
+        let mut nodes = vec![F::zero(); n - 1];
+        nodes.extend_from_slice(column);
+        for i in (0..n - 1).rev() {
+            nodes[i] = hash_tuple(params, &[nodes[2 * i + 1], nodes[2 * i + 2]]);
+        }
+        MerkleTree { nodes }
+    }
+
+    pub fn root(&self) -> F {
+        self.nodes[0]
+    }
+
+    /// The number of leaves, i.e. the length of the column
+    pub fn n_leaves(&self) -> usize {
+        (self.nodes.len() + 1) / 2
+    }
+
+    /// The nodes, in breadth-first order
+    pub fn nodes(&self) -> &[F] {
+        &self.nodes
+    }
+
+    /// Returns the path of the cell of the row `row`, if it is in the column
+    pub fn open(&self, row: usize) -> Option<MerklePath<F>> {
+        if row >= self.n_leaves() {
+            return None;
+        }
+        let mut node = self.n_leaves() - 1 + row;
+        let mut siblings = vec![];
+        while node > 0 {
+            // The left children have odd indices
+            let sibling = if node % 2 == 1 { node + 1 } else { node - 1 };
+            siblings.push(self.nodes[sibling]);
+            node = (node - 1) / 2;
+        }
+        Some(MerklePath { row, siblings })
+    }
+}
+
+impl<F: PrimeField> MerklePath<F> {
+    /// Whether `value` is the cell of the row of the path in the column
+    /// committed by `root`
+    pub fn verify(&self, params: &ArithmeticSpongeParams<F>, root: F, value: F) -> bool {
+        let n_leaves = 1 << self.siblings.len();
+        if self.row >= n_leaves {
+            return false;
+        }
+        let mut node = n_leaves - 1 + self.row;
+        let mut hash = value;
+        for sibling in &self.siblings {
+            hash = if node % 2 == 1 {
+                hash_tuple(params, &[hash, *sibling])
+            } else {
+                hash_tuple(params, &[*sibling, hash])
+            };
+            node = (node - 1) / 2;
+        }
+        hash == root
+    }
+}
+
+/// The Merkle trees of all the columns of a witness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnTrees<F> {
+    trees: Vec<MerkleTree<F>>,
+}
+
+impl<F: PrimeField> ColumnTrees<F> {
+    pub fn new<const N: usize>(
+        params: &ArithmeticSpongeParams<F>,
+        witness: &Witness<N, Vec<F>>,
+    ) -> Self {
+        ColumnTrees {
+            trees: witness
+                .cols
+                .iter()
+                .map(|column| MerkleTree::new(params, column))
+                .collect(),
+        }
+    }
+
+    /// The roots of the trees, by column
+    pub fn roots(&self) -> Vec<F> {
+        self.trees.iter().map(MerkleTree::root).collect()
+    }
+
+    pub fn tree(&self, column: usize) -> Option<&MerkleTree<F>> {
+        self.trees.get(column)
+    }
+
+    /// Returns the path of the cell at `(column, row)`, if it is in the
+    /// witness
+    pub fn open(&self, column: usize, row: usize) -> Option<MerklePath<F>> {
+        self.trees.get(column)?.open(row)
+    }
+}
+
+/// The columns of a [RootBinding]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingLayout {
+    /// The column whose root is bound
+    pub column: usize,
+    /// The first of the [BINDING_PUBLIC_COLUMNS] public columns: the index of
+    /// the row, the selector of the rows of the inner nodes, the selector of
+    /// the first row, and the root on the first row
+    pub public: usize,
+    /// The first of the [HashedLookup] columns hashing the children of the
+    /// inner nodes
+    pub hash: usize,
+    /// The first of the [BINDING_SECOND_PHASE_COLUMNS] columns committed
+    /// after the extra challenges are coined: the running sum, and the
+    /// inverses of its terms
+    pub second_phase: usize,
+    /// The index of the extra challenge `γ`, `δ` being the next one
+    pub challenge: usize,
+}
+
+/// A gadget constraining the root of the Merkle tree of a column to be a
+/// public input, see the module documentation
+pub struct RootBinding<'a, F: PrimeField> {
+    params: &'a ArithmeticSpongeParams<F>,
+    layout: BindingLayout,
+    hash: HashedLookup<'a, F>,
+}
+
+impl<'a, F: PrimeField> RootBinding<'a, F> {
+    pub fn new(params: &'a ArithmeticSpongeParams<F>, layout: BindingLayout) -> Self {
+        RootBinding {
+            params,
+            layout,
+            hash: HashedLookup::new(params, 2, layout.hash),
+        }
+    }
+
+    pub fn layout(&self) -> &BindingLayout {
+        &self.layout
+    }
+
+    /// The number of columns of the hash of the inner nodes
+    pub fn n_hash_columns(&self) -> usize {
+        self.hash.n_columns()
+    }
+
+    /// The constraints of the gadget, for columns of length `domain_size`
+    pub fn constraints(&self, domain_size: usize) -> Vec<E<F>> {
+        let literal = |x: F| {
+            E::Atom(ExprInner::Constant(ConstantExpr::from(
+                ConstantTerm::Literal(x),
+            )))
+        };
+        let public = |i: usize| curr_cell(Column::X(self.layout.public + i));
+        let (index, inner_node, first_row, root) = (public(0), public(1), public(2), public(3));
+        let gamma = curr_cell(Column::ExtraChallenge(self.layout.challenge));
+        let delta = curr_cell(Column::ExtraChallenge(self.layout.challenge + 1));
+        let term = |index: E<F>, value: E<F>| gamma.clone() + index + delta.clone() * value;
+        let [left, right] = [0, 1].map(|i| curr_cell(self.hash.tuple_columns()[i]));
+        let two = || literal(F::from(2u64));
+
+        // The writes of the leaf and of the inner node, and the reads of the
+        // children and of the root
+        let terms = [
+            (
+                E::one(),
+                term(
+                    literal(F::from((domain_size - 1) as u64)) + index.clone(),
+                    curr_cell(Column::X(self.layout.column)),
+                ),
+            ),
+            (
+                inner_node.clone(),
+                term(index.clone(), curr_cell(self.hash.digest_column())),
+            ),
+            (
+                -inner_node.clone(),
+                term(two() * index.clone() + E::one(), left),
+            ),
+            (-inner_node, term(two() * index + two(), right)),
+            (-first_row, term(E::zero(), root)),
+        ];
+        let second_phase = |i: usize| curr_cell(Column::X(self.layout.second_phase + i));
+
+        let mut constraints = self.hash.constraints();
+        // The inverses of the terms
+        constraints.extend(
+            terms
+                .iter()
+                .enumerate()
+                .map(|(i, (_, d))| second_phase(i + 1) * d.clone() - E::one()),
+        );
+        // φ(ωX) - φ(X) = \sum_{i} n_{i} / d_{i}
+        let accumulator = Column::X(self.layout.second_phase);
+        constraints.push(terms.into_iter().enumerate().fold(
+            next_cell(accumulator) - curr_cell(accumulator),
+            |acc, (i, (n, _))| acc - n * second_phase(i + 1),
+        ));
+        constraints
+    }
+
+    /// The public columns of the gadget for the root `root`, in order, to be
+    /// given as public inputs to the verifier
+    pub fn public_inputs(&self, domain_size: usize, root: F) -> Vec<Vec<F>> {
+        let index = (0..domain_size as u64).map(F::from).collect();
+        let inner_node = (0..domain_size)
+            .map(|i| {
+                if i < domain_size - 1 {
+                    F::one()
+                } else {
+                    F::zero()
+                }
+            })
+            .collect();
+        let mut first_row = vec![F::zero(); domain_size];
+        first_row[0] = F::one();
+        let mut roots = vec![F::zero(); domain_size];
+        roots[0] = root;
+        vec![index, inner_node, first_row, roots]
+    }
+
+    /// Returns the public columns and the columns of the hashes of the inner
+    /// nodes of `tree`, by index, without the columns committed after the
+    /// extra challenges are coined, see [RootBinding::second_phase]
+    pub fn witness(&self, tree: &MerkleTree<F>) -> BTreeMap<usize, Vec<F>> {
+        let domain_size = tree.n_leaves();
+        let nodes = tree.nodes();
+        let mut columns: BTreeMap<usize, Vec<F>> = self
+            .public_inputs(domain_size, tree.root())
+            .into_iter()
+            .enumerate()
+            .map(|(i, column)| (self.layout.public + i, column))
+            .collect();
+        let mut hash_columns = vec![Vec::with_capacity(domain_size); self.hash.n_columns()];
+        for i in 0..domain_size {
+            // The last row hashes zeroes, and is not read
+            let children = if i < domain_size - 1 {
+                [nodes[2 * i + 1], nodes[2 * i + 2]]
+            } else {
+                [F::zero(); 2]
+            };
+            for (column, x) in hash_columns.iter_mut().zip(self.hash.witness(&children)) {
+                column.push(x);
+            }
+        }
+        columns.extend(
+            hash_columns
+                .into_iter()
+                .enumerate()
+                .map(|(i, column)| (self.layout.hash + i, column)),
+        );
+        columns
+    }
+
+    /// Returns the [BINDING_SECOND_PHASE_COLUMNS] columns committed after
+    /// the extra challenges are coined, for the values `gamma` and `delta` of
+    /// the challenges: the running sum of the reads and writes of the nodes of
+    /// `tree`, and the inverses of the terms. The running sum starts at zero,
+    /// and wraps around to zero if and only if each node is read once.
+    pub fn second_phase(&self, tree: &MerkleTree<F>, gamma: F, delta: F) -> Vec<Vec<F>> {
+        let n = tree.n_leaves();
+        let nodes = tree.nodes();
+        let term = |index: usize, value: F| gamma + F::from(index as u64) + delta * value;
+        // The last row hashes zeroes, see [RootBinding::witness]
+        let padding = hash_tuple(self.params, &[F::zero(); 2]);
+        // The terms of the leaf, of the inner node, of its children and of
+        // the root on each row, see [RootBinding::constraints]
+        let mut inverses: Vec<F> = (0..n)
+            .flat_map(|i| {
+                let (parent, left, right) = if i < n - 1 {
+                    (nodes[i], nodes[2 * i + 1], nodes[2 * i + 2])
+                } else {
+                    (padding, F::zero(), F::zero())
+                };
+                [
+                    term(n - 1 + i, nodes[n - 1 + i]),
+                    term(i, parent),
+                    term(2 * i + 1, left),
+                    term(2 * i + 2, right),
+                    term(0, if i == 0 { nodes[0] } else { F::zero() }),
+                ]
+            })
+            .collect();
+        ark_ff::fields::batch_inversion(&mut inverses);
+
+        let mut columns = vec![Vec::with_capacity(n); BINDING_SECOND_PHASE_COLUMNS];
+        let mut acc = F::zero();
+        for (i, row) in inverses.chunks(5).enumerate() {
+            columns[0].push(acc);
+            let inner = if i < n - 1 { F::one() } else { F::zero() };
+            let first_row = if i == 0 { F::one() } else { F::zero() };
+            acc += row[0] + inner * (row[1] - row[2] - row[3]) - first_row * row[4];
+            for (column, x) in columns[1..].iter_mut().zip(row) {
+                column.push(*x);
+            }
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BindingLayout, ColumnTrees, MerkleTree, RootBinding, BINDING_PUBLIC_COLUMNS,
+        BINDING_SECOND_PHASE_COLUMNS,
+    };
+    use crate::{
+        config::ProtocolConfig,
+        lookups::LookupTableIDs,
+        proof::ProofInputs,
+        prover::{prove_with_context_and_extra_challenges, ProverContext, ProverError},
+        verifier::VerifierContext,
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand};
+    use kimchi::{
+        circuits::domains::EvaluationDomains, curve::KimchiCurve, proof::PointEvaluations,
+    };
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+
+    #[test]
+    fn test_column_openings() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let params = BN254G1Affine::sponge_params();
+        let domain_size = 1 << 5;
+        let witness: Witness<3, Vec<Fp>> = Witness {
+            cols: Box::new(std::array::from_fn(|_| {
+                (0..domain_size).map(|_| Fp::rand(&mut rng)).collect()
+            })),
+        };
+        let trees = ColumnTrees::new(params, &witness);
+        let roots = trees.roots();
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots[1], MerkleTree::new(params, &witness.cols[1]).root());
+
+        for _ in 0..16 {
+            let column = rng.gen_range(0..3);
+            let row = rng.gen_range(0..domain_size);
+            let path = trees.open(column, row).unwrap();
+            assert_eq!(path.siblings.len(), 5);
+            let value = witness.cols[column][row];
+            assert!(path.verify(params, roots[column], value));
+
+            // Another value, another root, or another row
+            assert!(!path.verify(params, roots[column], value + Fp::one()));
+            assert!(!path.verify(params, roots[(column + 1) % 3], value));
+            let mut moved = path.clone();
+            moved.row ^= 1;
+            assert!(!moved.verify(params, roots[column], value));
+            // A tampered sibling
+            let mut tampered = path;
+            tampered.siblings[2] += Fp::one();
+            assert!(!tampered.verify(params, roots[column], value));
+        }
+        assert_eq!(trees.open(0, domain_size), None);
+        assert_eq!(trees.open(3, 0), None);
+    }
+
+    #[test]
+    fn test_root_binding() {
+        const HASH: usize = BINDING_PUBLIC_COLUMNS + 1;
+        const SECOND_PHASE: usize = HASH + 2 + 55 * 3;
+        const N: usize = SECOND_PHASE + BINDING_SECOND_PHASE_COLUMNS;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 4;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> =
+            PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let params = BN254G1Affine::sponge_params();
+        let binding = RootBinding::new(
+            params,
+            BindingLayout {
+                column: BINDING_PUBLIC_COLUMNS,
+                public: 0,
+                hash: HASH,
+                second_phase: SECOND_PHASE,
+                challenge: 0,
+            },
+        );
+        assert_eq!(HASH + binding.n_hash_columns(), SECOND_PHASE);
+        let constraints = binding.constraints(domain_size);
+        let ctx =
+            ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints).unwrap();
+        let verifier = VerifierContext::from_prover_context(&ctx)
+            .unwrap()
+            .with_public_inputs(BINDING_PUBLIC_COLUMNS);
+
+        let column: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let tree = MerkleTree::new(params, &column);
+        // The tree of the witness is `tree`, the committed column is `column`
+        let prove = |column: &[Fp], tree: &MerkleTree<Fp>, rng: &mut rand::rngs::StdRng| {
+            let mut columns = binding.witness(tree);
+            columns.insert(BINDING_PUBLIC_COLUMNS, column.to_vec());
+            let first_phase: Vec<Vec<Fp>> = columns.into_values().collect();
+            let second_phase = |[gamma, delta]: &[Fp; 2]| {
+                let mut cols = first_phase.clone();
+                cols.extend(binding.second_phase(tree, *gamma, *delta));
+                ProofInputs {
+                    evaluations: Witness {
+                        cols: Box::new(cols.try_into().unwrap()),
+                    },
+                    mvlookups: vec![],
+                }
+            };
+            prove_with_context_and_extra_challenges::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                2,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, first_phase.clone(), second_phase, rng)
+        };
+        let verify = |proof, root| {
+            let public_inputs = binding.public_inputs(domain_size, root);
+            verifier
+                .verify_with_extra_challenges::<
+                    BaseSponge,
+                    ScalarSponge,
+                    N,
+                    BINDING_PUBLIC_COLUMNS,
+                    2,
+                    PointEvaluations<Fp>,
+                >(
+                    proof,
+                    Witness {
+                        cols: Box::new(public_inputs.try_into().unwrap()),
+                    },
+                    SECOND_PHASE,
+                )
+                .is_ok()
+        };
+
+        let proof = prove(&column, &tree, &mut rng).unwrap();
+        assert!(verify(&proof, tree.root()));
+        // The root is a public input
+        assert!(!verify(&proof, tree.root() + Fp::one()));
+
+        // The tree of another column can not be bound to the committed column
+        let mut other_column = column.clone();
+        other_column[5] += Fp::one();
+        let other_tree = MerkleTree::new(params, &other_column);
+        let res = prove(&column, &other_tree, &mut rng);
+        assert!(matches!(res, Err(ProverError::ConstraintNotSatisfied(_))));
+    }
+}
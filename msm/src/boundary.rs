@@ -0,0 +1,119 @@
+//! The boundary selector of a circuit designed for a maximum number of rows,
+//! see [crate::config::ProtocolConfig::max_rows].
+//! The witness is padded to the size of d1, and the padding rows after
+//! `max_rows` do not have to satisfy the constraints of the circuit. The
+//! constraints multiplied by the selector, which is `1` on the first
+//! `max_rows` rows of d1 and `0` on the other rows, are only enforced on the
+//! rows of the circuit, see [restrict].
+//! The selector is not committed: the verifier evaluates it itself, as the
+//! sum of the Lagrange polynomials of the selected rows, or of the other
+//! rows, whichever are fewer.
+
+use ark_ff::{batch_inversion, FftField, Field};
+use ark_poly::{Evaluations, Radix2EvaluationDomain as R2D};
+use kimchi::circuits::domains::EvaluationDomains;
+
+use crate::{
+    column_env::evaluate_over_coset,
+    columns::Column,
+    expr::{curr_cell, E},
+};
+
+/// The number of rows of the selector of the first row, which enforces the
+/// initial values of the columns of the lookup engines, see
+/// [crate::lookup_engine::PlookupEngine]. It is not a boundary of the circuit,
+/// and is allowed with any [crate::config::ProtocolConfig::max_rows].
+pub const FIRST_ROW: usize = 1;
+
+/// The selector of the first `max_rows` rows
+pub fn selector<F: Field>(max_rows: usize) -> E<F> {
+    curr_cell(Column::BoundarySelector(max_rows))
+}
+
+/// Enforce `constraint` on the first `max_rows` rows only
+pub fn restrict<F: Field>(max_rows: usize, constraint: E<F>) -> E<F> {
+    selector(max_rows) * constraint
+}
+
+/// The evaluations of the selector of the first `max_rows` rows over the
+/// coset of d8 the constraints are evaluated over, see
+/// [crate::column_env::ColumnEnvironment].
+pub fn selector_evals_d8<F: FftField>(
+    max_rows: usize,
+    domain: EvaluationDomains<F>,
+) -> Evaluations<F, R2D<F>> {
+    let n = domain.d1.size as usize;
+    let evals = (0..n)
+        .map(|i| if i < max_rows { F::one() } else { F::zero() })
+        .collect();
+    let poly = Evaluations::from_vec_and_domain(evals, domain.d1).interpolate();
+    evaluate_over_coset(&poly, domain.d8)
+}
+
+/// Evaluate the selector of the first `max_rows` rows of `domain` at `x`.
+/// The Lagrange polynomial of the row `i` is
+/// `L_i(x) = ω^i (x^n - 1) / (n (x - ω^i))`, and the sum of the Lagrange
+/// polynomials of all the rows is `1`.
+pub fn evaluate_selector<F: FftField>(max_rows: usize, domain: R2D<F>, x: F) -> F {
+    let n = domain.size as usize;
+    let max_rows = max_rows.min(n);
+    // The rows whose Lagrange polynomials are summed, and the sign of the sum
+    let (rows, selected) = if max_rows <= n - max_rows {
+        (0..max_rows, true)
+    } else {
+        (max_rows..n, false)
+    };
+    let mut omega_i = domain.group_gen.pow([rows.start as u64]);
+    let mut numerators = Vec::with_capacity(rows.len());
+    let mut denominators = Vec::with_capacity(rows.len());
+    for _ in rows {
+        if x == omega_i {
+            // The point is one of the summed rows
+            return if selected { F::one() } else { F::zero() };
+        }
+        numerators.push(omega_i);
+        denominators.push(x - omega_i);
+        omega_i *= domain.group_gen;
+    }
+    batch_inversion(&mut denominators);
+    let sum: F = numerators
+        .iter()
+        .zip(denominators.iter())
+        .map(|(n, d)| *n * d)
+        .sum::<F>()
+        * (x.pow([n as u64]) - F::one())
+        * domain.size_inv;
+    if selected {
+        sum
+    } else {
+        F::one() - sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Fp;
+    use ark_ff::{One, UniformRand, Zero};
+    use ark_poly::{EvaluationDomain, Polynomial};
+
+    #[test]
+    fn test_evaluate_selector() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let x = Fp::rand(&mut rng);
+        for max_rows in [0, 3, 8, 13, 16, 20] {
+            let evals = (0..16)
+                .map(|i| if i < max_rows { Fp::one() } else { Fp::zero() })
+                .collect();
+            let poly = Evaluations::from_vec_and_domain(evals, domain.d1).interpolate();
+            assert_eq!(evaluate_selector(max_rows, domain.d1, x), poly.evaluate(&x));
+            for (i, row) in domain.d1.elements().enumerate() {
+                assert_eq!(
+                    evaluate_selector(max_rows, domain.d1, row),
+                    Fp::from(u64::from(i < max_rows))
+                );
+            }
+        }
+    }
+}
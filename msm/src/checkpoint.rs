@@ -0,0 +1,128 @@
+//! Checkpoints of the prover, to restart a long proof on another machine once
+//! the environment of the lookup argument is built, see
+//! [crate::prover::prove_until_checkpoint] and
+//! [crate::mvlookup::prover::Env::save].
+//! A checkpoint is encoded with MessagePack, the field elements and the
+//! polynomials using their canonical serialization, and it is prefixed with
+//! the Blake2b digest of its content.
+
+use ark_ec::models::SWModelParameters;
+use ark_ff::Field;
+use blake2::{Blake2b512, Digest};
+use mina_poseidon::{
+    constants::SpongeConstants,
+    poseidon::{ArithmeticSponge, ArithmeticSpongeParams, Sponge, SpongeState},
+    sponge::DefaultFqSponge,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::serde_as;
+use std::path::Path;
+use thiserror::Error;
+
+/// Errors that can arise when saving or loading a checkpoint
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("the checkpoint could not be read or written: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("the checkpoint is corrupted, its digest does not match its content")]
+    DigestMismatch,
+
+    #[error("the checkpoint could not be encoded or decoded: {0}")]
+    Encoding(String),
+
+    #[error("the checkpoint was created over a domain of size {expected}, not {got}")]
+    DomainMismatch { expected: u64, got: u64 },
+}
+
+const DIGEST_SIZE: usize = 64;
+
+/// Encode `value`, prefixed with the digest of the encoding.
+pub(crate) fn seal<T: Serialize>(value: &T) -> Result<Vec<u8>, CheckpointError> {
+    let payload =
+        rmp_serde::to_vec(value).map_err(|err| CheckpointError::Encoding(err.to_string()))?;
+    let mut bytes = Blake2b512::digest(&payload).to_vec();
+    bytes.extend(payload);
+    Ok(bytes)
+}
+
+/// Decode a value encoded with [seal], after checking its digest.
+pub(crate) fn unseal<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CheckpointError> {
+    if bytes.len() < DIGEST_SIZE {
+        return Err(CheckpointError::DigestMismatch);
+    }
+    let (digest, payload) = bytes.split_at(DIGEST_SIZE);
+    if Blake2b512::digest(payload).as_slice() != digest {
+        return Err(CheckpointError::DigestMismatch);
+    }
+    rmp_serde::from_slice(payload).map_err(|err| CheckpointError::Encoding(err.to_string()))
+}
+
+pub(crate) fn save<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+    std::fs::write(path, seal(value)?)?;
+    Ok(())
+}
+
+pub(crate) fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, CheckpointError> {
+    unseal(&std::fs::read(path)?)
+}
+
+/// A sponge whose state can be saved in a checkpoint, and restored with the
+/// same parameters.
+pub trait SpongeSnapshot<F: Field>: Sized {
+    type Snapshot: Serialize + DeserializeOwned;
+
+    fn snapshot(&self) -> Self::Snapshot;
+
+    fn restore(snapshot: Self::Snapshot, params: &'static ArithmeticSpongeParams<F>) -> Self;
+}
+
+/// The state of a [DefaultFqSponge], see [SpongeSnapshot]
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+pub struct FqSpongeSnapshot<F> {
+    #[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")]
+    state: Vec<F>,
+    /// Whether the last operation was an absorption, and the number of
+    /// elements absorbed (resp. squeezed) since the last permutation
+    absorbed: bool,
+    count: usize,
+    last_squeezed: Vec<u64>,
+}
+
+impl<P: SWModelParameters, SC: SpongeConstants> SpongeSnapshot<P::BaseField>
+    for DefaultFqSponge<P, SC>
+{
+    type Snapshot = FqSpongeSnapshot<P::BaseField>;
+
+    fn snapshot(&self) -> Self::Snapshot {
+        let (absorbed, count) = match self.sponge.sponge_state {
+            SpongeState::Absorbed(n) => (true, n),
+            SpongeState::Squeezed(n) => (false, n),
+        };
+        FqSpongeSnapshot {
+            state: self.sponge.state.clone(),
+            absorbed,
+            count,
+            last_squeezed: self.last_squeezed.clone(),
+        }
+    }
+
+    fn restore(
+        snapshot: Self::Snapshot,
+        params: &'static ArithmeticSpongeParams<P::BaseField>,
+    ) -> Self {
+        let mut sponge = ArithmeticSponge::<P::BaseField, SC>::new(params);
+        sponge.state = snapshot.state;
+        sponge.sponge_state = if snapshot.absorbed {
+            SpongeState::Absorbed(snapshot.count)
+        } else {
+            SpongeState::Squeezed(snapshot.count)
+        };
+        DefaultFqSponge {
+            sponge,
+            last_squeezed: snapshot.last_squeezed,
+        }
+    }
+}
@@ -0,0 +1,858 @@
+//! The definition of a circuit, from which the contexts of its prover and of
+//! its verifier are derived, see [CircuitDefinition].
+//!
+//! Without a definition, the circuit is whatever constraints, tables and
+//! number of columns the prover and the verifier are each given, and a
+//! mismatch is only found when a proof is rejected. The contexts derived from
+//! a definition share its constraints, its configuration and its tables, and
+//! [prove_circuit] and [verify_circuit] only take the contexts.
+
+use ark_ff::PrimeField;
+use blake2::{Blake2b512, Digest};
+use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve, plonk_sponge::FrSponge};
+use mina_poseidon::FqSponge;
+use poly_commitment::OpenProof;
+use rand::{CryptoRng, RngCore};
+use serde::Serialize;
+use serde_with::serde_as;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::{
+    columns::Column,
+    committer::ColumnDescriptor,
+    config::{ConfigError, ProtocolConfig},
+    expr::{column_accesses, encode_expr, NamedConstraint, E},
+    lookup_engine::{LookupEngineError, LookupEngineKind, LookupEngines},
+    mvlookup::{
+        generate_global_tables, verify_table_id_encoding, GlobalTableCommitments, LookupLayout,
+        LookupTable, LookupTableID, TableIdImplError,
+    },
+    proof::{Proof, ProofInputs},
+    prover::{prove_with_context, ProverContext, ProverError},
+    public_schema::{PublicSchema, PublicValue},
+    verifier::{VerifierContext, VerifierError},
+    witness::Witness,
+    MAX_SUPPORTED_DEGREE,
+};
+
+/// Errors found when checking a [CircuitDefinition]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    #[error("the circuit has neither constraints nor lookups")]
+    EmptyCircuit,
+
+    #[error("the constraint {name} has degree {degree} > allowed {max}")]
+    DegreeTooHigh { name: String, degree: u64, max: u64 },
+
+    #[error("the constraint {name} reads the witness column {column}, but the circuit has {n_columns} columns")]
+    ColumnOutOfRange {
+        name: String,
+        column: usize,
+        n_columns: usize,
+    },
+
+    #[error("the constraint {name} reads the lookup table {table_id}, which is not registered")]
+    UnregisteredTable { name: String, table_id: u32 },
+
+    #[error(
+        "the constraint {name} reads the partial sum {index}, which is not in the lookup layout"
+    )]
+    PartialSumOutOfRange { name: String, index: usize },
+
+    #[error("the partial sums of the lookup constraints are not ordered by table ID")]
+    LookupLayout,
+
+    #[error("the table {0} is registered twice")]
+    DuplicateTable(u32),
+
+    #[error("the table {table_id} has {got} entries, more than the {max} rows of the domain")]
+    TableTooLarge {
+        table_id: u32,
+        got: usize,
+        max: usize,
+    },
+
+    #[error("the implementation of the table IDs is inconsistent: {0}")]
+    TableId(TableIdImplError),
+
+    #[error("the descriptor of the column {column} is out of the {n_columns} columns")]
+    DescriptorOutOfRange { column: usize, n_columns: usize },
+
+    #[error("the circuit can not be proven with the protocol configuration: {0}")]
+    Config(ConfigError),
+
+    #[error("the constraints can not be encoded: {0}")]
+    Encoding(String),
+
+    #[error("the public schema has {n_public} columns, more than the {n_columns} columns of the circuit")]
+    PublicColumnsOutOfRange { n_public: usize, n_columns: usize },
+
+    #[error("the lookups can not be proven with the engines of their tables: {0}")]
+    LookupEngine(LookupEngineError),
+}
+
+/// A circuit of `N` witness columns: its named constraints, the fixed tables
+/// they look up, the layout of the lookup columns and the engine of each
+/// table, the descriptors of the witness columns, the schema of its public
+/// inputs, and the configuration of the protocol, including the maximum
+/// number of rows.
+/// The definition is checked when it is created, see [CircuitDefinition::new],
+/// and identified by its [CircuitDefinition::digest].
+#[derive(Debug, Clone)]
+pub struct CircuitDefinition<F: PrimeField, ID: LookupTableID, const N: usize> {
+    domain: EvaluationDomains<F>,
+    config: ProtocolConfig,
+    constraints: Vec<NamedConstraint<F>>,
+    tables: BTreeMap<ID, LookupTable<F, ID>>,
+    lookup_layout: Option<LookupLayout<ID>>,
+    lookup_engines: LookupEngines<ID>,
+    column_descriptors: BTreeMap<usize, ColumnDescriptor>,
+    public_schema: PublicSchema,
+}
+
+/// The content of a [CircuitDefinition] covered by its digest
+#[serde_as]
+#[derive(Serialize)]
+#[serde(bound = "F: PrimeField")]
+struct DigestedCircuit<'a, F: PrimeField> {
+    domain_size: u64,
+    n_columns: usize,
+    config: &'a ProtocolConfig,
+    constraints: Vec<(&'a str, Vec<crate::expr::ExprToken<F>>)>,
+    #[serde_as(as = "Vec<(_, Vec<Vec<o1_utils::serialization::SerdeAs>>)>")]
+    tables: Vec<(u32, Vec<Vec<F>>)>,
+    column_descriptors: Vec<(usize, u8, u32)>,
+    #[serde(skip_serializing_if = "PublicSchema::is_empty")]
+    public_schema: &'a PublicSchema,
+}
+
+impl<F: PrimeField, ID: LookupTableID, const N: usize> CircuitDefinition<F, ID, N> {
+    /// Check and create the circuit given by `constraints`, which do not
+    /// include the ones added by `config`, looking up the fixed tables
+    /// `tables`.
+    /// The checks are the ones the prover and the verifier would only
+    /// perform on a proof, or not at all:
+    /// - the constraints are supported by `config`, see
+    ///   [ProtocolConfig::compile_constraints], and can be serialized,
+    /// - the degree of each constraint is supported by the prover,
+    /// - the constraints only read the `N` witness columns, the registered
+    ///   tables and the partial sums of their lookup layout,
+    /// - the implementation of [LookupTableID] is consistent on the tables
+    ///   and on the ones looked up by the constraints, with IDs below
+    ///   [crate::mvlookup::MAX_TABLE_ID], see
+    ///   [crate::mvlookup::verify_table_id_impl], and the tables fit in the
+    ///   domain,
+    /// - the tables of the plookup engine, whose columns of the engine are
+    ///   read by the constraints, have a single column, and contain zero if
+    ///   they are padded, see [crate::lookup_engine::PlookupEngine].
+    pub fn new(
+        domain: EvaluationDomains<F>,
+        config: ProtocolConfig,
+        constraints: Vec<NamedConstraint<F>>,
+        tables: Vec<LookupTable<F, ID>>,
+    ) -> Result<Self, CircuitError> {
+        if constraints.is_empty() {
+            return Err(CircuitError::EmptyCircuit);
+        }
+        let compiled = config
+            .compile_constraints(
+                constraints
+                    .iter()
+                    .map(|named| named.constraint.clone())
+                    .collect(),
+            )
+            .map_err(CircuitError::Config)?;
+        for constraint in compiled.iter() {
+            encode_expr(constraint).map_err(|err| CircuitError::Encoding(err.to_string()))?;
+        }
+
+        let ids: Vec<ID> = tables.iter().map(|table| table.table_id).collect();
+        verify_table_id_encoding(&ids).map_err(CircuitError::TableId)?;
+        let domain_size = domain.d1.size as usize;
+        let mut registry = BTreeMap::new();
+        for table in tables {
+            let table_id = table.table_id.to_u32();
+            if table.entries.is_empty() {
+                return Err(CircuitError::TableId(TableIdImplError::EmptyFixedTable(
+                    table_id,
+                )));
+            }
+            if table.entries.len() > domain_size {
+                return Err(CircuitError::TableTooLarge {
+                    table_id,
+                    got: table.entries.len(),
+                    max: domain_size,
+                });
+            }
+            if registry.insert(table.table_id, table).is_some() {
+                return Err(CircuitError::DuplicateTable(table_id));
+            }
+        }
+
+        let lookup_layout = LookupLayout::of_constraints(&compiled);
+        if let Some(layout) = lookup_layout.as_ref() {
+            // The tables which are looked up without being registered, e.g.
+            // the RAM lookups
+            let ids: Vec<ID> = layout.table_ids().collect();
+            verify_table_id_encoding(&ids).map_err(CircuitError::TableId)?;
+        }
+        let n_partial_sums = lookup_layout
+            .as_ref()
+            .map_or(0, |layout| layout.n_partial_sums());
+        for named in constraints.iter() {
+            let name = || named.name.clone();
+            let degree = named.constraint.degree(1, 0);
+            if degree > MAX_SUPPORTED_DEGREE as u64 {
+                return Err(CircuitError::DegreeTooHigh {
+                    name: name(),
+                    degree,
+                    max: MAX_SUPPORTED_DEGREE as u64,
+                });
+            }
+            for (column, _) in column_accesses(&named.constraint) {
+                match column {
+                    Column::X(i) | Column::XShift2(i) if i >= N => {
+                        return Err(CircuitError::ColumnOutOfRange {
+                            name: name(),
+                            column: i,
+                            n_columns: N,
+                        })
+                    }
+                    Column::LookupFixedTable(idx) | Column::Plookup(idx, _) => {
+                        let registered = ID::try_from_u32(idx.to_u32())
+                            .map_or(false, |id| registry.contains_key(&id));
+                        if !registered {
+                            return Err(CircuitError::UnregisteredTable {
+                                name: name(),
+                                table_id: idx.to_u32(),
+                            });
+                        }
+                    }
+                    Column::LookupPartialSum(idx) => {
+                        if lookup_layout.is_none() {
+                            return Err(CircuitError::LookupLayout);
+                        }
+                        if idx.index() >= n_partial_sums {
+                            return Err(CircuitError::PartialSumOutOfRange {
+                                name: name(),
+                                index: idx.index(),
+                            });
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let lookup_engines = LookupEngines::of_constraints(&compiled);
+        for table_id in lookup_engines.tables(LookupEngineKind::Plookup) {
+            // The tables of the plookup engine read by the constraints are
+            // registered
+            let Some(table) = registry.get(&table_id) else {
+                continue;
+            };
+            let width = table.entries[0].len();
+            if width != 1 {
+                return Err(CircuitError::LookupEngine(
+                    LookupEngineError::NotSingleColumn {
+                        table_id: table_id.to_u32(),
+                        width,
+                    },
+                ));
+            }
+            if table.entries.len() < domain_size
+                && !table.entries.iter().any(|entry| entry[0].is_zero())
+            {
+                return Err(CircuitError::LookupEngine(
+                    LookupEngineError::ZeroNotInTable(table_id.to_u32()),
+                ));
+            }
+        }
+
+        Ok(CircuitDefinition {
+            domain,
+            config,
+            constraints,
+            tables: registry,
+            lookup_layout,
+            lookup_engines,
+            column_descriptors: BTreeMap::new(),
+            public_schema: PublicSchema::default(),
+        })
+    }
+
+    /// The definition of the circuit given by the unnamed `constraints`, as
+    /// given to [crate::prover::ProverContext::new] and
+    /// [crate::verifier::VerifierContext::new]. The constraints are named after
+    /// their index, see [NamedConstraint::from_indexed].
+    pub fn from_constraints(
+        domain: EvaluationDomains<F>,
+        config: ProtocolConfig,
+        constraints: &[E<F>],
+        tables: Vec<LookupTable<F, ID>>,
+    ) -> Result<Self, CircuitError> {
+        Self::new(
+            domain,
+            config,
+            NamedConstraint::from_indexed(constraints),
+            tables,
+        )
+    }
+
+    /// Describe the values of the witness column `column`, see
+    /// [ProverContext::with_column_descriptor]
+    pub fn with_column_descriptor(
+        mut self,
+        column: usize,
+        descriptor: ColumnDescriptor,
+    ) -> Result<Self, CircuitError> {
+        if column >= N {
+            return Err(CircuitError::DescriptorOutOfRange {
+                column,
+                n_columns: N,
+            });
+        }
+        self.column_descriptors.insert(column, descriptor);
+        Ok(self)
+    }
+
+    /// Lay out the public inputs of the circuit with `schema`, in its first
+    /// [PublicSchema::n_columns] columns
+    pub fn with_public_schema(mut self, schema: PublicSchema) -> Result<Self, CircuitError> {
+        if schema.n_columns() > N {
+            return Err(CircuitError::PublicColumnsOutOfRange {
+                n_public: schema.n_columns(),
+                n_columns: N,
+            });
+        }
+        self.public_schema = schema;
+        Ok(self)
+    }
+
+    pub fn domain(&self) -> EvaluationDomains<F> {
+        self.domain
+    }
+
+    pub fn config(&self) -> &ProtocolConfig {
+        &self.config
+    }
+
+    pub fn constraints(&self) -> &[NamedConstraint<F>] {
+        &self.constraints
+    }
+
+    /// The registered tables, by increasing ID
+    pub fn tables(&self) -> impl Iterator<Item = &LookupTable<F, ID>> {
+        self.tables.values()
+    }
+
+    /// The layout of the lookup columns of the constraints, `None` without
+    /// lookups
+    pub fn lookup_layout(&self) -> Option<&LookupLayout<ID>> {
+        self.lookup_layout.as_ref()
+    }
+
+    /// The engine of each table, derived from the columns read by the
+    /// constraints
+    pub fn lookup_engines(&self) -> &LookupEngines<ID> {
+        &self.lookup_engines
+    }
+
+    pub fn column_descriptors(&self) -> &BTreeMap<usize, ColumnDescriptor> {
+        &self.column_descriptors
+    }
+
+    pub fn public_schema(&self) -> &PublicSchema {
+        &self.public_schema
+    }
+
+    /// The maximum number of rows of the circuit, see
+    /// [ProtocolConfig::max_rows]
+    pub fn max_rows(&self) -> Option<usize> {
+        self.config.max_rows
+    }
+
+    fn unnamed_constraints(&self) -> Vec<E<F>> {
+        self.constraints
+            .iter()
+            .map(|named| named.constraint.clone())
+            .collect()
+    }
+
+    /// The first 32 bytes of the Blake2b digest of the MessagePack
+    /// serialization of the whole definition: the size of the domain, the
+    /// number of columns, the configuration, the names and the encodings of
+    /// the constraints, the tables, the column descriptors and the public
+    /// schema, if not empty
+    pub fn digest(&self) -> [u8; 32] {
+        let digested = DigestedCircuit {
+            domain_size: self.domain.d1.size,
+            n_columns: N,
+            config: &self.config,
+            constraints: self
+                .constraints
+                .iter()
+                .map(|named| {
+                    (
+                        named.name.as_str(),
+                        encode_expr(&named.constraint)
+                            .expect("the constraints are checked on creation"),
+                    )
+                })
+                .collect(),
+            tables: self
+                .tables
+                .iter()
+                .map(|(id, table)| (id.to_u32(), table.entries.clone()))
+                .collect(),
+            column_descriptors: self
+                .column_descriptors
+                .iter()
+                .map(|(column, descriptor)| match descriptor {
+                    ColumnDescriptor::Boolean => (*column, 0, 0),
+                    ColumnDescriptor::Bits(bits) => (*column, 1, *bits),
+                })
+                .collect(),
+            public_schema: &self.public_schema,
+        };
+        let bytes = rmp_serde::to_vec(&digested).expect("the definition can always be serialized");
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&Blake2b512::digest(bytes)[..32]);
+        digest
+    }
+
+    /// The commitments to the registered tables, `None` without tables
+    fn global_tables<G, OpeningProof>(
+        &self,
+        srs: &OpeningProof::SRS,
+    ) -> Option<GlobalTableCommitments<G, ID>>
+    where
+        G: KimchiCurve<ScalarField = F>,
+        OpeningProof: OpenProof<G>,
+    {
+        if self.tables.is_empty() {
+            return None;
+        }
+        let tables: Vec<_> = self.tables.values().cloned().collect();
+        let global_tables =
+            generate_global_tables::<G, OpeningProof, ID>(srs, self.domain, &tables)
+                .expect("the tables are checked on creation");
+        Some(global_tables)
+    }
+
+    /// The context of the prover of the circuit. The fixed tables of the
+    /// proofs are checked against the registered tables, and omitted from
+    /// the proofs, see [ProverContext::with_global_tables].
+    pub fn prover_context<'a, G, OpeningProof>(
+        &self,
+        srs: &'a OpeningProof::SRS,
+    ) -> Result<CircuitProverContext<'a, G, OpeningProof, ID, N>, ProverError>
+    where
+        G: KimchiCurve<ScalarField = F>,
+        OpeningProof: OpenProof<G>,
+    {
+        let mut ctx = ProverContext::new(
+            self.domain,
+            srs,
+            self.config.clone(),
+            &self.unnamed_constraints(),
+        )?;
+        if let Some(global_tables) = self.global_tables::<G, OpeningProof>(srs) {
+            ctx = ctx.with_global_tables(global_tables);
+        }
+        let ctx = self
+            .column_descriptors
+            .iter()
+            .fold(ctx, |ctx, (column, descriptor)| {
+                ctx.with_column_descriptor(*column, *descriptor)
+            });
+        Ok(CircuitProverContext {
+            inner: ctx,
+            digest: self.digest(),
+        })
+    }
+
+    /// The context of the verifier of the circuit, deriving the commitments
+    /// to the fixed tables from the registered tables, and the public inputs
+    /// from the public values, see [verify_circuit_with_public]
+    pub fn verifier_context<'a, G, OpeningProof>(
+        &self,
+        srs: &'a OpeningProof::SRS,
+    ) -> Result<CircuitVerifierContext<'a, G, OpeningProof, ID, N>, VerifierError>
+    where
+        G: KimchiCurve<ScalarField = F>,
+        OpeningProof: OpenProof<G>,
+        OpeningProof::SRS: Sync,
+    {
+        let mut ctx = VerifierContext::new(
+            self.domain,
+            srs,
+            self.config.clone(),
+            &self.unnamed_constraints(),
+        )?
+        .with_public_inputs(self.public_schema.n_columns());
+        if let Some(global_tables) = self.global_tables::<G, OpeningProof>(srs) {
+            ctx = ctx.with_global_tables(global_tables);
+        }
+        Ok(CircuitVerifierContext {
+            inner: ctx,
+            digest: self.digest(),
+            public_schema: self.public_schema.clone(),
+        })
+    }
+}
+
+/// The context of the prover of a [CircuitDefinition], see
+/// [CircuitDefinition::prover_context]
+pub struct CircuitProverContext<
+    'a,
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    ID: LookupTableID,
+    const N: usize,
+> {
+    inner: ProverContext<'a, G, OpeningProof, ID>,
+    digest: [u8; 32],
+}
+
+impl<'a, G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID, const N: usize>
+    CircuitProverContext<'a, G, OpeningProof, ID, N>
+{
+    /// The digest of the definition of the circuit
+    pub fn digest(&self) -> [u8; 32] {
+        self.digest
+    }
+
+    /// The underlying context, e.g. to create the verifier context of a
+    /// permutation of the columns
+    pub fn inner(&self) -> &ProverContext<'a, G, OpeningProof, ID> {
+        &self.inner
+    }
+}
+
+/// The context of the verifier of a [CircuitDefinition], see
+/// [CircuitDefinition::verifier_context]
+pub struct CircuitVerifierContext<
+    'a,
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    ID: LookupTableID,
+    const N: usize,
+> {
+    inner: VerifierContext<'a, G, OpeningProof, ID>,
+    digest: [u8; 32],
+    public_schema: PublicSchema,
+}
+
+impl<'a, G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID, const N: usize>
+    CircuitVerifierContext<'a, G, OpeningProof, ID, N>
+{
+    /// The digest of the definition of the circuit
+    pub fn digest(&self) -> [u8; 32] {
+        self.digest
+    }
+
+    pub fn inner(&self) -> &VerifierContext<'a, G, OpeningProof, ID> {
+        &self.inner
+    }
+
+    pub fn public_schema(&self) -> &PublicSchema {
+        &self.public_schema
+    }
+}
+
+/// Create a proof of the circuit of `ctx` for the witness `inputs`, which
+/// has the `N` columns of the circuit
+pub fn prove_circuit<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG: RngCore + CryptoRng,
+    ID: LookupTableID,
+    const N: usize,
+>(
+    ctx: &CircuitProverContext<G, OpeningProof, ID, N>,
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    OpeningProof: Send,
+{
+    prove_with_context::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, _>(
+        &ctx.inner, inputs, rng,
+    )
+}
+
+/// Verify a proof of the circuit of `ctx`, created with [prove_circuit], for
+/// a circuit without public inputs
+pub fn verify_circuit<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    ID: LookupTableID,
+    const N: usize,
+>(
+    ctx: &CircuitVerifierContext<G, OpeningProof, ID, N>,
+    proof: &Proof<N, G, OpeningProof, ID>,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    ctx.inner
+        .verify::<EFqSponge, EFrSponge, N, 0, _>(proof, Witness::zero_vec(0))
+}
+
+/// Verify a proof of the circuit of `ctx`, created with [prove_circuit],
+/// whose public inputs hold `values`, laid out by the public schema of the
+/// circuit. `NPUB` must be the number of public columns of the schema.
+pub fn verify_circuit_with_public<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    ID: LookupTableID,
+    const N: usize,
+    const NPUB: usize,
+>(
+    ctx: &CircuitVerifierContext<G, OpeningProof, ID, N>,
+    proof: &Proof<N, G, OpeningProof, ID>,
+    values: &[(&str, PublicValue<G::ScalarField>)],
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let domain_size = ctx.inner.domain().d1.size as usize;
+    let public_inputs = ctx
+        .public_schema
+        .public_columns::<G::ScalarField, NPUB>(values, domain_size)
+        .map_err(VerifierError::PublicSchema)?;
+    ctx.inner
+        .verify::<EFqSponge, EFrSponge, N, NPUB, _>(proof, public_inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_circuit, verify_circuit, CircuitDefinition, CircuitError};
+    use crate::{
+        columns::Column,
+        committer::ColumnDescriptor,
+        config::ProtocolConfig,
+        expr::{curr_cell, NamedConstraint, E},
+        lookup_engine::{LookupEngineError, LookupEngineKind, LookupEngines},
+        lookups::LookupTableIDs,
+        mvlookup::{
+            constraint_lookups, LookupTable, LookupTableID, MVLookup, TableIdImplError,
+            MAX_TABLE_ID,
+        },
+        test::random_lookup_circuit,
+        BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use poly_commitment::pairing_proof::PairingSRS;
+    use std::collections::BTreeMap;
+
+    const N: usize = 4;
+
+    /// The table `Custom(0)` of the values `[0, size)`
+    fn range_table(size: u64) -> LookupTable<Fp, LookupTableIDs> {
+        LookupTable {
+            table_id: LookupTableIDs::Custom(0),
+            entries: (0..size).map(|i| vec![Fp::from(i)]).collect(),
+        }
+    }
+
+    fn definition(
+        domain: EvaluationDomains<Fp>,
+        constraints: Vec<NamedConstraint<Fp>>,
+        tables: Vec<LookupTable<Fp, LookupTableIDs>>,
+    ) -> Result<CircuitDefinition<Fp, LookupTableIDs, N>, CircuitError> {
+        CircuitDefinition::new(domain, ProtocolConfig::default(), constraints, tables)
+    }
+
+    #[test]
+    fn test_prove_and_verify_circuit() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), 1 << 8);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let (constraints, inputs) = random_lookup_circuit::<N, _, _>(domain, 0, 16, 2, &mut rng);
+        let circuit = CircuitDefinition::<Fp, LookupTableIDs, N>::from_constraints(
+            domain,
+            ProtocolConfig::default(),
+            &constraints,
+            vec![range_table(16)],
+        )
+        .unwrap();
+        let prover_ctx = circuit.prover_context::<_, OpeningProof>(&srs).unwrap();
+        let verifier_ctx = circuit.verifier_context::<_, OpeningProof>(&srs).unwrap();
+        assert_eq!(prover_ctx.digest(), circuit.digest());
+        assert_eq!(verifier_ctx.digest(), circuit.digest());
+
+        let proof = prove_circuit::<_, OpeningProof, BaseSponge, ScalarSponge, _, _, N>(
+            &prover_ctx,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        verify_circuit::<_, OpeningProof, BaseSponge, ScalarSponge, _, N>(&verifier_ctx, &proof)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_circuit_checks() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+
+        assert_eq!(
+            definition(domain, vec![], vec![]).unwrap_err(),
+            CircuitError::EmptyCircuit
+        );
+        assert_eq!(
+            definition(
+                domain,
+                vec![NamedConstraint::new("wide", x(0) * x(N))],
+                vec![]
+            )
+            .unwrap_err(),
+            CircuitError::ColumnOutOfRange {
+                name: "wide".to_string(),
+                column: N,
+                n_columns: N
+            }
+        );
+        let high: E<Fp> = (1..9).fold(x(0), |acc, _| acc * x(0));
+        assert_eq!(
+            definition(domain, vec![NamedConstraint::new("high", high)], vec![]).unwrap_err(),
+            CircuitError::DegreeTooHigh {
+                name: "high".to_string(),
+                degree: 9,
+                max: 8
+            }
+        );
+
+        let (constraints, _) =
+            random_lookup_circuit::<N, crate::BN254G1Affine, _>(domain, 0, 16, 2, &mut rng);
+        let named = NamedConstraint::from_indexed(&constraints);
+        let table_id = LookupTableIDs::Custom(0).to_u32();
+        assert!(matches!(
+            definition(domain, named.clone(), vec![]).unwrap_err(),
+            CircuitError::UnregisteredTable { table_id: id, .. } if id == table_id
+        ));
+        assert_eq!(
+            definition(domain, named.clone(), vec![range_table(17)]).unwrap_err(),
+            CircuitError::TableTooLarge {
+                table_id,
+                got: 17,
+                max: 16
+            }
+        );
+        assert_eq!(
+            definition(domain, named.clone(), vec![range_table(4), range_table(4)]).unwrap_err(),
+            CircuitError::DuplicateTable(table_id)
+        );
+
+        let circuit = definition(domain, named, vec![range_table(16)]).unwrap();
+        assert_eq!(
+            circuit
+                .clone()
+                .with_column_descriptor(N, ColumnDescriptor::Boolean)
+                .unwrap_err(),
+            CircuitError::DescriptorOutOfRange {
+                column: N,
+                n_columns: N
+            }
+        );
+        // The digest covers the whole definition
+        let described = circuit
+            .clone()
+            .with_column_descriptor(2, ColumnDescriptor::Bits(4))
+            .unwrap();
+        assert_ne!(described.digest(), circuit.digest());
+        let other_table = definition(
+            domain,
+            circuit.constraints().to_vec(),
+            vec![range_table(15)],
+        )
+        .unwrap();
+        assert_ne!(other_table.digest(), circuit.digest());
+        assert_eq!(circuit.clone().digest(), circuit.digest());
+    }
+    #[test]
+    fn test_table_id_bound() {
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        // The ID of `Custom(id)` is `id + 3`
+        let table_id = LookupTableIDs::Custom(MAX_TABLE_ID - 3);
+        let lookups = BTreeMap::from([(
+            table_id,
+            vec![MVLookup::new(
+                table_id,
+                E::one(),
+                &[curr_cell::<Fp>(Column::X(0))],
+            )],
+        )]);
+        let named = NamedConstraint::from_indexed(&constraint_lookups(&lookups));
+        let table = LookupTable {
+            table_id,
+            entries: (0..16).map(|i| vec![Fp::from(i as u64)]).collect(),
+        };
+        let err = CircuitError::TableId(TableIdImplError::IdTooLarge(MAX_TABLE_ID));
+        // Whether the table is registered or only looked up
+        assert_eq!(
+            definition(domain, named.clone(), vec![table]).unwrap_err(),
+            err
+        );
+        assert_eq!(definition(domain, named, vec![]).unwrap_err(), err);
+    }
+
+    #[test]
+    fn test_plookup_table_checks() {
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let table_id = LookupTableIDs::Custom(0);
+        let lookups = BTreeMap::from([(
+            table_id,
+            vec![MVLookup::new(
+                table_id,
+                E::one(),
+                &[curr_cell::<Fp>(Column::X(0))],
+            )],
+        )]);
+        let constraints = LookupEngines::new()
+            .with_engine(table_id, LookupEngineKind::Plookup)
+            .constraints(&lookups)
+            .unwrap();
+        let named = NamedConstraint::from_indexed(&constraints);
+
+        let circuit = definition(domain, named.clone(), vec![range_table(16)]).unwrap();
+        assert_eq!(
+            circuit.lookup_engines().engine(table_id),
+            LookupEngineKind::Plookup
+        );
+        // The padding of a table shorter than the domain is a lookup of zero
+        let without_zero = LookupTable {
+            table_id,
+            entries: (1..8).map(|i| vec![Fp::from(i as u64)]).collect(),
+        };
+        assert_eq!(
+            definition(domain, named.clone(), vec![without_zero]).unwrap_err(),
+            CircuitError::LookupEngine(LookupEngineError::ZeroNotInTable(table_id.to_u32()))
+        );
+        let wide = LookupTable {
+            table_id,
+            entries: (0..8).map(|i| vec![Fp::from(i as u64); 2]).collect(),
+        };
+        assert_eq!(
+            definition(domain, named, vec![wide]).unwrap_err(),
+            CircuitError::LookupEngine(LookupEngineError::NotSingleColumn {
+                table_id: table_id.to_u32(),
+                width: 2
+            })
+        );
+    }
+}
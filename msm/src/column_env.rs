@@ -1,5 +1,9 @@
-use ark_ff::FftField;
-use ark_poly::{Evaluations, Radix2EvaluationDomain};
+use ark_ff::{FftField, Zero};
+use ark_poly::{
+    univariate::DensePolynomial, EvaluationDomain, Evaluations, Radix2EvaluationDomain,
+    UVPolynomial,
+};
+use std::collections::BTreeMap;
 
 use crate::{mvlookup, mvlookup::LookupTableID, witness::Witness};
 use kimchi::circuits::{
@@ -10,7 +14,11 @@ use kimchi::circuits::{
 /// The collection of polynomials (all in evaluation form) and constants
 /// required to evaluate an expression as a polynomial.
 ///
-/// All are evaluations.
+/// All are evaluations over the cosets `g D` of the domains, where `g` is the
+/// multiplicative generator of the field, see [evaluate_over_coset]. The
+/// vanishing polynomial of d1 does not vanish on them, and the constraint
+/// polynomial is divided by it pointwise, see
+/// [divide_by_vanishing_poly_on_coset].
 pub struct ColumnEnvironment<'a, const N: usize, F: FftField, ID: LookupTableID> {
     /// The witness column polynomials
     pub witness: &'a Witness<N, Evaluations<F, Radix2EvaluationDomain<F>>>,
@@ -25,6 +33,17 @@ pub struct ColumnEnvironment<'a, const N: usize, F: FftField, ID: LookupTableID>
     pub challenges: Challenges<F>,
     /// The domains used in the PLONK argument.
     pub domain: EvaluationDomains<F>,
+    /// The extra challenges, as constant evaluations over d8
+    pub extra_challenges: &'a [Evaluations<F, Radix2EvaluationDomain<F>>],
+    /// The base two logarithm of the size of the sub-domain, and the
+    /// evaluations over d8 of its selector, see [crate::subdomain].
+    pub subdomain_selector: Option<(u32, &'a Evaluations<F, Radix2EvaluationDomain<F>>)>,
+    /// The evaluations over d8 of the boundary selectors, indexed by their
+    /// number of rows, see [crate::boundary].
+    pub boundary_selectors: &'a BTreeMap<usize, Evaluations<F, Radix2EvaluationDomain<F>>>,
+    /// The witness columns read two rows ahead, shifted by two rows, indexed
+    /// by their column, see [crate::shifts].
+    pub shifted_witness: &'a BTreeMap<usize, Evaluations<F, Radix2EvaluationDomain<F>>>,
 
     /// Lookup specific polynomials
     // TODO: rename in additive lookup or "logup"
@@ -59,32 +78,56 @@ impl<'a, const N: usize, F: FftField, ID: LookupTableID> TColumnEnvironment<'a,
             }
             Self::Column::LookupPartialSum(i) => {
                 if let Some(ref lookup) = self.lookup {
-                    Some(&lookup.lookup_terms_evals_d8[i])
+                    Some(&lookup.lookup_terms_evals_d8[i.index()])
                 } else {
                     panic!("No lookup provided")
                 }
             }
             Self::Column::LookupAggregation => {
                 if let Some(ref lookup) = self.lookup {
-                    Some(lookup.lookup_aggregation_evals_d8)
+                    Some(lookup.lookup_aggregation_evals_d8.expect(
+                        "The aggregation of the lookup argument is not committed in the claimed mode",
+                    ))
                 } else {
                     panic!("No lookup provided")
                 }
             }
             Self::Column::LookupMultiplicity(id) => {
                 if let Some(ref lookup) = self.lookup {
-                    Some(&lookup.lookup_counters_evals_d8[&ID::from_u32(id)])
+                    Some(&lookup.lookup_counters_evals_d8[&ID::from_u32(id.to_u32())])
                 } else {
                     panic!("No lookup provided")
                 }
             }
             Self::Column::LookupFixedTable(id) => {
                 if let Some(ref lookup) = self.lookup {
-                    Some(&lookup.fixed_tables_evals_d8[&ID::from_u32(id)])
+                    Some(&lookup.fixed_tables_evals_d8[&ID::from_u32(id.to_u32())])
+                } else {
+                    panic!("No lookup provided")
+                }
+            }
+            Self::Column::Plookup(id, column) => {
+                if let Some(ref lookup) = self.lookup {
+                    Some(lookup.plookup_evals_d8[&ID::from_u32(id.to_u32())].get(column))
                 } else {
                     panic!("No lookup provided")
                 }
             }
+            Self::Column::ExtraChallenge(i) => Some(&self.extra_challenges[i]),
+            Self::Column::SubdomainSelector(log_size) => match self.subdomain_selector {
+                Some((selector_log_size, evals)) if selector_log_size == log_size => Some(evals),
+                _ => panic!("No sub-domain of size 2^{log_size} provided"),
+            },
+            Self::Column::BoundarySelector(max_rows) => {
+                match self.boundary_selectors.get(&max_rows) {
+                    Some(evals) => Some(evals),
+                    None => panic!("No boundary selector of {max_rows} rows provided"),
+                }
+            }
+            Self::Column::XShift2(i) => match self.shifted_witness.get(&i) {
+                Some(evals) => Some(evals),
+                None => panic!("The column {i} shifted by two rows is not provided"),
+            },
         }
     }
 
@@ -99,8 +142,12 @@ impl<'a, const N: usize, F: FftField, ID: LookupTableID> TColumnEnvironment<'a,
 
     fn column_domain(&self, col: &Self::Column) -> Domain {
         match *col {
-            Self::Column::X(i) => {
-                let domain_size = self.witness[i].domain().size;
+            Self::Column::X(_) | Self::Column::XShift2(_) => {
+                let domain_size = self
+                    .get_column(col)
+                    .expect("The witness columns are always provided")
+                    .domain()
+                    .size;
                 if self.domain.d1.size == domain_size {
                     Domain::D1
                 } else if self.domain.d2.size == domain_size {
@@ -116,11 +163,15 @@ impl<'a, const N: usize, F: FftField, ID: LookupTableID> TColumnEnvironment<'a,
             Self::Column::LookupAggregation
             | Self::Column::LookupFixedTable(_)
             | Self::Column::LookupMultiplicity(_)
-            | Self::Column::LookupPartialSum(_) => {
+            | Self::Column::LookupPartialSum(_)
+            | Self::Column::Plookup(..) => {
                 // When there is a lookup, we do suppose the domain is always D8
                 // and we have at leat 6 lookups per row.
                 Domain::D8
             }
+            Self::Column::ExtraChallenge(_)
+            | Self::Column::SubdomainSelector(_)
+            | Self::Column::BoundarySelector(_) => Domain::D8,
         }
     }
 
@@ -141,4 +192,170 @@ impl<'a, const N: usize, F: FftField, ID: LookupTableID> TColumnEnvironment<'a,
     fn l0_1(&self) -> F {
         self.l0_1
     }
+
+    fn coset_offset(&self) -> F {
+        F::multiplicative_generator()
+    }
+}
+
+/// The evaluations of `poly` over the coset `g domain`, where `g` is the
+/// multiplicative generator of the field. The degree of `poly` must be less
+/// than the size of `domain`.
+pub fn evaluate_over_coset<F: FftField>(
+    poly: &DensePolynomial<F>,
+    domain: Radix2EvaluationDomain<F>,
+) -> Evaluations<F, Radix2EvaluationDomain<F>> {
+    Evaluations::from_vec_and_domain(domain.coset_fft(&poly.coeffs), domain)
+}
+
+/// Divide the polynomial `C(X)` with the evaluations `evals` over the coset
+/// `g D` of their domain `D` by the vanishing polynomial `Z_H(X) = X^n - 1` of
+/// `d1`, see [evaluate_over_coset]. `Z_H` does not vanish on the coset, and
+/// the division is pointwise.
+/// Returns `None` if `C(X)` does not vanish on `d1`: the interpolation `Q(X)`
+/// of the pointwise quotient then has a degree of at least `|D| - n`, as
+/// otherwise `Q(X) Z_H(X)` would be `C(X)`. The degree of `C(X)` must be less
+/// than `|D|`.
+pub fn divide_by_vanishing_poly_on_coset<F: FftField>(
+    evals: Evaluations<F, Radix2EvaluationDomain<F>>,
+    d1: Radix2EvaluationDomain<F>,
+) -> Option<DensePolynomial<F>> {
+    let domain = evals.domain();
+    let n = d1.size;
+    // The points of the coset are g ω_D^j, hence Z_H takes the k values
+    // g^n (ω_D^n)^r - 1 for r = j mod k, where |D| = k n
+    let k = (domain.size / n) as usize;
+    let offset_n = F::multiplicative_generator().pow([n]);
+    let omega_n = domain.group_gen.pow([n]);
+    let mut vanishing_evals = Vec::with_capacity(k);
+    let mut omega_n_r = F::one();
+    for _ in 0..k {
+        vanishing_evals.push(offset_n * omega_n_r - F::one());
+        omega_n_r *= omega_n;
+    }
+    ark_ff::batch_inversion(&mut vanishing_evals);
+    let mut quotient_evals = evals.evals;
+    for (j, eval) in quotient_evals.iter_mut().enumerate() {
+        *eval *= vanishing_evals[j % k];
+    }
+    domain.coset_ifft_in_place(&mut quotient_evals);
+    let quotient = DensePolynomial::from_coefficients_vec(quotient_evals);
+    if quotient.is_zero() || quotient.coeffs.len() as u64 + n <= domain.size {
+        Some(quotient)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{divide_by_vanishing_poly_on_coset, evaluate_over_coset, ColumnEnvironment};
+    use crate::{
+        columns::Column, expr::curr_cell, lookups::LookupTableIDs, witness::Witness, BN254G1Affine,
+        Fp,
+    };
+    use ark_ff::{FftField, Field, One, Zero};
+    use ark_poly::{univariate::DensePolynomial, Polynomial, UVPolynomial};
+    use kimchi::{
+        circuits::{
+            domains::EvaluationDomains,
+            expr::{l0_1, Challenges, Constants, Expr, ExprInner, RowOffset},
+        },
+        curve::KimchiCurve,
+    };
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_divide_by_vanishing_poly_on_coset() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let n = domain.d1.size as usize;
+        // Z_H(X) = X^n - 1
+        let mut vanishing = vec![Fp::zero(); n + 1];
+        vanishing[0] = -Fp::one();
+        vanishing[n] = Fp::one();
+        let vanishing = DensePolynomial::from_coefficients_vec(vanishing);
+
+        for (eval_domain, quotient_degree) in [(domain.d4, 3 * n - 1), (domain.d8, 7 * n - 1)] {
+            let quotient = DensePolynomial::<Fp>::rand(quotient_degree, &mut rng);
+            let constraint = &quotient * &vanishing;
+            let evals = evaluate_over_coset(&constraint, eval_domain);
+            assert_eq!(
+                divide_by_vanishing_poly_on_coset(evals, domain.d1),
+                Some(quotient)
+            );
+            // A polynomial which does not vanish on a single point of H
+            let mut evals = evaluate_over_coset(&constraint, eval_domain);
+            let omega_5 = domain.d1.group_gen.pow([5]);
+            let lagrange_5 =
+                &vanishing / &DensePolynomial::from_coefficients_vec(vec![-omega_5, Fp::one()]);
+            for (eval, lagrange) in evals
+                .evals
+                .iter_mut()
+                .zip(evaluate_over_coset(&lagrange_5, eval_domain).evals)
+            {
+                *eval += lagrange;
+            }
+            assert_eq!(divide_by_vanishing_poly_on_coset(evals, domain.d1), None);
+        }
+        // A constraint of degree 1, evaluated over d1
+        let constraint = DensePolynomial::<Fp>::rand(n - 1, &mut rng);
+        let evals = evaluate_over_coset(&constraint, domain.d1);
+        assert_eq!(divide_by_vanishing_poly_on_coset(evals, domain.d1), None);
+        let evals = evaluate_over_coset(&DensePolynomial::zero(), domain.d1);
+        assert_eq!(
+            divide_by_vanishing_poly_on_coset(evals, domain.d1),
+            Some(DensePolynomial::zero())
+        );
+    }
+
+    #[test]
+    fn test_lagrange_basis_over_coset() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let n = domain.d1.size;
+        let column = DensePolynomial::<Fp>::rand(n as usize - 1, &mut rng);
+        let witness: Witness<1, _> = Witness {
+            cols: Box::new([evaluate_over_coset(&column, domain.d8)]),
+        };
+        let env = ColumnEnvironment::<1, Fp, LookupTableIDs> {
+            witness: &witness,
+            coefficients: &vec![],
+            l0_1: l0_1(domain.d1),
+            constants: Constants {
+                endo_coefficient: Fp::zero(),
+                mds: &BN254G1Affine::sponge_params().mds,
+                zk_rows: 0,
+            },
+            challenges: Challenges {
+                alpha: Fp::zero(),
+                beta: Fp::zero(),
+                gamma: Fp::zero(),
+                joint_combiner: None,
+            },
+            domain,
+            extra_challenges: &[],
+            subdomain_selector: None,
+            boundary_selectors: &BTreeMap::new(),
+            shifted_witness: &BTreeMap::new(),
+            lookup: None,
+        };
+
+        // X_{0} L_i(X) for the first, the last and another row, with
+        // L_i(X) = (X^n - 1) / (X - ω^i)
+        for offset in [0, -1, 3] {
+            let expr = Expr::Atom(ExprInner::UnnormalizedLagrangeBasis(RowOffset {
+                zk_rows: false,
+                offset,
+            })) * curr_cell(Column::X(0));
+            let evals = expr.evaluations(&env);
+            let i = (offset + n as i32) as u64 % n;
+            let omega_i = domain.d1.group_gen.pow([i]);
+            for (j, x) in ark_poly::EvaluationDomain::elements(&evals.domain()).enumerate() {
+                let x = Fp::multiplicative_generator() * x;
+                let lagrange = (x.pow([n]) - Fp::one()) / (x - omega_i);
+                assert_eq!(evals.evals[j], lagrange * column.evaluate(&x));
+            }
+        }
+    }
 }
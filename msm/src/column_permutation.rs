@@ -0,0 +1,252 @@
+//! Reordering of the witness columns, so that the columns read by the same
+//! constraints are adjacent in the witness array. The quotient is evaluated
+//! constraint by constraint, and reading neighbouring columns improves the
+//! cache behaviour of the prover on wide circuits.
+//!
+//! A [ColumnPermutation] is part of the circuit definition: it is applied to
+//! the witness and to the constraints by the prover, see
+//! [crate::prover::ProverContext::with_column_permutation], and recorded in
+//! the [crate::verifier::VerifierContext], which maps the evaluations of the
+//! proof back to the original columns. The proof format is unchanged.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use kimchi::circuits::expr::{ExprInner, Operations, Variable};
+
+use crate::{
+    access_patterns::AccessReport, columns::Column, config::ProtocolConfig, expr::E,
+    witness::Witness,
+};
+
+/// Errors that can arise when building a [ColumnPermutation]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PermutationError {
+    #[error("the positions are not a permutation of 0..{0}")]
+    NotAPermutation(usize),
+
+    #[error("the permutation has {got} columns instead of {expected}")]
+    SizeMismatch { expected: usize, got: usize },
+}
+
+/// A permutation of the witness columns, given by the new position of each
+/// column: the column `i` of the circuit is the column `positions[i]` of the
+/// permuted witness.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<usize>", into = "Vec<usize>")]
+pub struct ColumnPermutation {
+    positions: Vec<usize>,
+}
+
+impl TryFrom<Vec<usize>> for ColumnPermutation {
+    type Error = PermutationError;
+
+    fn try_from(positions: Vec<usize>) -> Result<Self, Self::Error> {
+        ColumnPermutation::from_positions(positions)
+    }
+}
+
+impl From<ColumnPermutation> for Vec<usize> {
+    fn from(permutation: ColumnPermutation) -> Self {
+        permutation.positions
+    }
+}
+
+impl ColumnPermutation {
+    /// The permutation keeping the `n_columns` columns in place
+    pub fn identity(n_columns: usize) -> Self {
+        ColumnPermutation {
+            positions: (0..n_columns).collect(),
+        }
+    }
+
+    /// The permutation moving the column `i` to `positions[i]`
+    pub fn from_positions(positions: Vec<usize>) -> Result<Self, PermutationError> {
+        let mut seen = vec![false; positions.len()];
+        for &position in positions.iter() {
+            if position >= positions.len() || seen[position] {
+                return Err(PermutationError::NotAPermutation(positions.len()));
+            }
+            seen[position] = true;
+        }
+        Ok(ColumnPermutation { positions })
+    }
+
+    /// Order the `n_columns` witness columns by first access in `report`:
+    /// the columns read by the first constraint come first, then the other
+    /// columns read by the second one, and so on. The columns which are not
+    /// read follow, in their original order. The first `n_fixed` columns,
+    /// e.g. the public inputs, are kept in place.
+    pub fn from_access_report(report: &AccessReport, n_columns: usize, n_fixed: usize) -> Self {
+        let mut order: Vec<usize> = (0..n_fixed.min(n_columns)).collect();
+        let mut placed = vec![false; n_columns];
+        order.iter().for_each(|&i| placed[i] = true);
+        let read = report
+            .constraints
+            .iter()
+            .flat_map(|constraint| constraint.columns.keys())
+            .filter_map(Column::witness_index)
+            .filter(|i| *i < n_columns);
+        for i in read.chain(0..n_columns) {
+            if !placed[i] {
+                placed[i] = true;
+                order.push(i);
+            }
+        }
+        let mut positions = vec![0; n_columns];
+        for (position, i) in order.into_iter().enumerate() {
+            positions[i] = position;
+        }
+        ColumnPermutation { positions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.positions.iter().enumerate().all(|(i, p)| i == *p)
+    }
+
+    /// The position of the column `i` of the circuit in the permuted witness
+    pub fn position(&self, i: usize) -> usize {
+        self.positions[i]
+    }
+
+    /// The column of the circuit at the position `position` of the permuted
+    /// witness
+    pub fn original(&self, position: usize) -> usize {
+        self.positions.iter().position(|p| *p == position).unwrap()
+    }
+
+    /// The permutation moving the columns back to their original position
+    pub fn inverse(&self) -> Self {
+        let mut positions = vec![0; self.positions.len()];
+        for (i, p) in self.positions.iter().enumerate() {
+            positions[*p] = i;
+        }
+        ColumnPermutation { positions }
+    }
+
+    /// Renumber the references to the witness columns in `constraint`,
+    /// including the shifted ones. The columns of the lookup argument are not
+    /// renumbered.
+    pub fn apply_to_constraint<F: Clone>(&self, constraint: &E<F>) -> E<F> {
+        let apply = |x: &E<F>| Box::new(self.apply_to_constraint(x));
+        match constraint {
+            Operations::Atom(ExprInner::Cell(Variable { col, row }))
+                if col.witness_index().is_some() =>
+            {
+                let i = col.witness_index().unwrap();
+                E::Atom(ExprInner::Cell(Variable {
+                    col: col.with_witness_index(self.positions[i]),
+                    row: *row,
+                }))
+            }
+            Operations::Atom(x) => Operations::Atom(x.clone()),
+            Operations::Pow(x, n) => Operations::Pow(apply(x), *n),
+            Operations::Add(x, y) => Operations::Add(apply(x), apply(y)),
+            Operations::Mul(x, y) => Operations::Mul(apply(x), apply(y)),
+            Operations::Sub(x, y) => Operations::Sub(apply(x), apply(y)),
+            Operations::Double(x) => Operations::Double(apply(x)),
+            Operations::Square(x) => Operations::Square(apply(x)),
+            Operations::Cache(id, x) => Operations::Cache(*id, apply(x)),
+            Operations::IfFeature(feature, x, y) => {
+                Operations::IfFeature(*feature, apply(x), apply(y))
+            }
+        }
+    }
+
+    /// Renumber the output cells of `config`, see
+    /// [ProtocolConfig::output_cells]
+    pub fn apply_to_config(&self, mut config: ProtocolConfig) -> ProtocolConfig {
+        for cell in config.output_cells.iter_mut() {
+            cell.column = self.positions[cell.column];
+        }
+        config
+    }
+
+    /// Move the columns of `witness` to their position. The permutation must
+    /// have `N` columns.
+    pub fn apply_to_witness<const N: usize, T>(
+        &self,
+        witness: Witness<N, T>,
+    ) -> Result<Witness<N, T>, PermutationError> {
+        if self.positions.len() != N {
+            return Err(PermutationError::SizeMismatch {
+                expected: N,
+                got: self.positions.len(),
+            });
+        }
+        let inverse = self.inverse();
+        let mut columns: Vec<Option<T>> = (*witness.cols).into_iter().map(Some).collect();
+        let cols =
+            std::array::from_fn(|position| columns[inverse.positions[position]].take().unwrap());
+        Ok(Witness {
+            cols: Box::new(cols),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expr::{curr_cell, shift2_cell},
+        Fp,
+    };
+
+    #[test]
+    fn test_column_permutation() {
+        assert_eq!(
+            ColumnPermutation::from_positions(vec![1, 2, 1]),
+            Err(PermutationError::NotAPermutation(3))
+        );
+        assert_eq!(
+            ColumnPermutation::from_positions(vec![0, 3, 1]),
+            Err(PermutationError::NotAPermutation(3))
+        );
+        let permutation = ColumnPermutation::from_positions(vec![2, 0, 1]).unwrap();
+        assert!(!permutation.is_identity());
+        assert!(ColumnPermutation::identity(3).is_identity());
+        assert_eq!(permutation.original(2), 0);
+        assert_eq!(permutation.inverse().position(2), 0);
+
+        let witness = Witness::<3, Vec<u32>> {
+            cols: Box::new([vec![0], vec![1], vec![2]]),
+        };
+        let permuted = permutation.apply_to_witness(witness.clone()).unwrap();
+        assert_eq!(permuted.cols, Box::new([vec![1], vec![2], vec![0]]));
+        assert_eq!(
+            permutation.inverse().apply_to_witness(permuted).unwrap(),
+            witness
+        );
+        assert_eq!(
+            ColumnPermutation::identity(2).apply_to_witness(witness),
+            Err(PermutationError::SizeMismatch {
+                expected: 3,
+                got: 2
+            })
+        );
+
+        let constraint = curr_cell::<Fp>(Column::X(0)) * curr_cell(Column::LookupAggregation);
+        assert_eq!(
+            permutation.apply_to_constraint(&constraint),
+            curr_cell::<Fp>(Column::X(2)) * curr_cell(Column::LookupAggregation)
+        );
+        assert_eq!(
+            permutation.apply_to_constraint(&shift2_cell::<Fp>(Column::X(1))),
+            shift2_cell::<Fp>(Column::X(0))
+        );
+
+        // The deserialized permutations are checked
+        let bytes = rmp_serde::to_vec(&permutation).unwrap();
+        assert_eq!(rmp_serde::from_slice(&bytes).ok(), Some(permutation));
+        let bytes = rmp_serde::to_vec(&vec![0usize, 0]).unwrap();
+        assert!(rmp_serde::from_slice::<ColumnPermutation>(&bytes).is_err());
+    }
+}
@@ -1,41 +1,224 @@
 use std::collections::HashMap;
 
 use kimchi::circuits::expr::{CacheId, FormattedOutput};
+use serde::{Deserialize, Serialize};
+
+use crate::lookup_engine::PlookupColumn;
+
+/// The index of a partial sum `h_i` of the lookup argument.
+/// It can only be obtained from a [crate::mvlookup::LookupLayout], which
+/// checks that the partial sum exists:
+/// ```compile_fail
+/// use kimchi_msm::api::{Column, PartialSumIdx};
+/// let column = Column::LookupPartialSum(PartialSumIdx(3));
+/// ```
+/// The index, like the columns, can not be deserialized either:
+/// ```compile_fail
+/// use kimchi_msm::api::{Column, PartialSumIdx};
+/// let idx: PartialSumIdx = serde_json::from_str("3").unwrap();
+/// ```
+/// ```compile_fail
+/// use kimchi_msm::api::Column;
+/// let column: Column = serde_json::from_str(r#"{"LookupPartialSum":3}"#).unwrap();
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Serialize)]
+pub struct PartialSumIdx(usize);
+
+impl PartialSumIdx {
+    pub(crate) fn new(idx: usize) -> Self {
+        PartialSumIdx(idx)
+    }
+
+    /// Create the index without checking it against a layout
+    #[cfg(feature = "legacy-columns")]
+    #[deprecated(note = "use crate::mvlookup::LookupLayout::partial_sum")]
+    pub fn new_unchecked(idx: usize) -> Self {
+        PartialSumIdx(idx)
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The ID of a fixed lookup table, as given by
+/// [crate::mvlookup::LookupTableID::to_u32], referring to its multiplicities
+/// or to its values.
+/// It can only be obtained from a [crate::mvlookup::LookupLayout], which
+/// checks that the table is used:
+/// ```compile_fail
+/// use kimchi_msm::api::{Column, TableIdx};
+/// let column = Column::LookupMultiplicity(TableIdx(3));
+/// ```
+/// ```compile_fail
+/// use kimchi_msm::api::TableIdx;
+/// let idx: TableIdx = serde_json::from_str("3").unwrap();
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Serialize)]
+pub struct TableIdx(u32);
+
+impl TableIdx {
+    pub(crate) fn new(table_id: u32) -> Self {
+        TableIdx(table_id)
+    }
+
+    /// Create the index without checking it against a layout
+    #[cfg(feature = "legacy-columns")]
+    #[deprecated(note = "use crate::mvlookup::LookupLayout::multiplicity")]
+    pub fn new_unchecked(table_id: u32) -> Self {
+        TableIdx(table_id)
+    }
+
+    pub fn to_u32(self) -> u32 {
+        self.0
+    }
+}
 
 /// Describe a generic indexed variable X_{i}.
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+/// The columns can be serialized, but only deserialized by the crate, see
+/// [ColumnDef], as the indices of the lookup columns must be checked against
+/// a layout.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Serialize)]
 pub enum Column {
     X(usize),
     // Columns related to the lookup protocol
     /// Partial sums, indexed. This corresponds to the `h_i`
-    LookupPartialSum(usize),
-    /// Multiplicities, indexed. This corresponds to the `m_i`
-    LookupMultiplicity(u32),
+    LookupPartialSum(PartialSumIdx),
+    /// Multiplicities, indexed by the ID of their table. This corresponds to
+    /// the `m_i`
+    LookupMultiplicity(TableIdx),
     /// The lookup aggregation, i.e. `phi`
     LookupAggregation,
     /// The fixed tables. The parameter is considered to the indexed table.
     /// u32 has been arbitrarily chosen as it seems to be already large enough
-    LookupFixedTable(u32),
+    LookupFixedTable(TableIdx),
+    /// An extra challenge coined by the verifier after the first phase of the
+    /// witness, see [crate::prover::prove_with_context_and_extra_challenges].
+    /// It is handled as a constant column, therefore it counts as degree one
+    /// when computing the degree of the constraints.
+    ExtraChallenge(usize),
+    /// The selector of the rows of the sub-domain of size `2^i` of d1, see
+    /// [crate::subdomain]. It is not committed, the verifier evaluates it.
+    SubdomainSelector(u32),
+    /// The selector of the first `i` rows of d1, see [crate::boundary]. It is
+    /// not committed, the verifier evaluates it.
+    BoundarySelector(usize),
+    /// The witness column `X(i)` two rows after the current row, i.e. its
+    /// evaluation at ζω², see [crate::shifts]. It is only read at the current
+    /// row, with [crate::expr::shift2_cell].
+    XShift2(usize),
+    /// A column committed by the plookup engine for a table, see
+    /// [crate::lookup_engine::PlookupEngine]
+    Plookup(TableIdx, PlookupColumn),
+}
+
+impl Column {
+    /// Whether the column is committed by the lookup argument
+    pub fn is_lookup(&self) -> bool {
+        matches!(
+            self,
+            Column::LookupPartialSum(_)
+                | Column::LookupMultiplicity(_)
+                | Column::LookupAggregation
+                | Column::LookupFixedTable(_)
+                | Column::Plookup(..)
+        )
+    }
+
+    /// The index of the witness column read by the column, i.e. `i` for
+    /// [Column::X] and [Column::XShift2]
+    pub fn witness_index(&self) -> Option<usize> {
+        match self {
+            Column::X(i) | Column::XShift2(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// The column reading the witness column `i` at the same shift as `self`.
+    /// The other columns are returned unchanged.
+    pub fn with_witness_index(self, i: usize) -> Self {
+        match self {
+            Column::X(_) => Column::X(i),
+            Column::XShift2(_) => Column::XShift2(i),
+            col => col,
+        }
+    }
+}
+
+/// The deserialization of a [Column], from its serialization. The indices of
+/// the lookup columns are not checked, it is up to the caller to check them
+/// against the layout of the circuit, see
+/// [crate::verifier::VerifierContext::from_bytes].
+#[derive(Deserialize)]
+#[serde(remote = "Column")]
+pub(crate) enum ColumnDef {
+    X(usize),
+    LookupPartialSum(#[serde(with = "PartialSumIdxDef")] PartialSumIdx),
+    LookupMultiplicity(#[serde(with = "TableIdxDef")] TableIdx),
+    LookupAggregation,
+    LookupFixedTable(#[serde(with = "TableIdxDef")] TableIdx),
+    ExtraChallenge(usize),
+    SubdomainSelector(u32),
+    BoundarySelector(usize),
+    XShift2(usize),
+    Plookup(#[serde(with = "TableIdxDef")] TableIdx, PlookupColumn),
+}
+
+#[derive(Deserialize)]
+#[serde(remote = "PartialSumIdx")]
+struct PartialSumIdxDef(usize);
+
+#[derive(Deserialize)]
+#[serde(remote = "TableIdx")]
+struct TableIdxDef(u32);
+
+#[cfg(feature = "legacy-columns")]
+#[allow(deprecated)]
+impl Column {
+    #[deprecated(note = "use crate::mvlookup::LookupLayout::partial_sum")]
+    pub fn lookup_partial_sum(idx: usize) -> Self {
+        Column::LookupPartialSum(PartialSumIdx::new_unchecked(idx))
+    }
+
+    #[deprecated(note = "use crate::mvlookup::LookupLayout::multiplicity")]
+    pub fn lookup_multiplicity(table_id: u32) -> Self {
+        Column::LookupMultiplicity(TableIdx::new_unchecked(table_id))
+    }
+
+    #[deprecated(note = "use crate::mvlookup::LookupLayout::fixed_table")]
+    pub fn lookup_fixed_table(table_id: u32) -> Self {
+        Column::LookupFixedTable(TableIdx::new_unchecked(table_id))
+    }
 }
 
 impl FormattedOutput for Column {
     fn latex(&self, _cache: &mut HashMap<CacheId, Self>) -> String {
         match self {
             Column::X(i) => format!("x_{{{i}}}"),
-            Column::LookupPartialSum(i) => format!("h_{{{i}}}"),
-            Column::LookupMultiplicity(i) => format!("m_{{{i}}}"),
-            Column::LookupFixedTable(i) => format!("t_{{{i}}}"),
+            Column::LookupPartialSum(i) => format!("h_{{{}}}", i.index()),
+            Column::LookupMultiplicity(i) => format!("m_{{{}}}", i.to_u32()),
+            Column::LookupFixedTable(i) => format!("t_{{{}}}", i.to_u32()),
             Column::LookupAggregation => String::from("φ"),
+            Column::ExtraChallenge(i) => format!("\\chi_{{{i}}}"),
+            Column::SubdomainSelector(i) => format!("s_{{{i}}}"),
+            Column::BoundarySelector(i) => format!("b_{{{i}}}"),
+            Column::XShift2(i) => format!("x_{{{i}}}^{{\\omega^2}}"),
+            Column::Plookup(i, column) => format!("\\mathrm{{{column}}}_{{{}}}", i.to_u32()),
         }
     }
 
     fn text(&self, _cache: &mut HashMap<CacheId, Self>) -> String {
         match self {
             Column::X(i) => format!("x[{i}]"),
-            Column::LookupPartialSum(i) => format!("h[{i}]"),
-            Column::LookupMultiplicity(i) => format!("m[{i}]"),
-            Column::LookupFixedTable(i) => format!("t[{i}]"),
+            Column::LookupPartialSum(i) => format!("h[{}]", i.index()),
+            Column::LookupMultiplicity(i) => format!("m[{}]", i.to_u32()),
+            Column::LookupFixedTable(i) => format!("t[{}]", i.to_u32()),
             Column::LookupAggregation => String::from("φ"),
+            Column::ExtraChallenge(i) => format!("chal[{i}]"),
+            Column::SubdomainSelector(i) => format!("sel[{i}]"),
+            Column::BoundarySelector(i) => format!("bnd[{i}]"),
+            Column::XShift2(i) => format!("shift2(x[{i}])"),
+            Column::Plookup(i, column) => format!("plookup[{}].{column}", i.to_u32()),
         }
     }
 
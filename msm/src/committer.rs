@@ -0,0 +1,265 @@
+//! Specialized commitments to the witness columns whose values are known to
+//! be small, e.g. boolean flags or limbs. The commitment to a column is the
+//! combination of the Lagrange bases by its values, which does not need any
+//! scalar multiplication when the values are bits, and a single window of
+//! buckets when they are small limbs.
+//! The commitments are the same as the generic ones, the verifier is
+//! unchanged.
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
+use poly_commitment::{commitment::PolyComm, SRS};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// What is known of the values of a witness column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColumnDescriptor {
+    /// The values are 0 or 1
+    Boolean,
+    /// The values are less than `2^k`
+    Bits(u32),
+}
+
+/// A commitment to a column in the Lagrange bases, for the columns of a
+/// [ColumnDescriptor], see [ColumnCommitters].
+pub trait ColumnCommitter<G: KimchiCurve>: Send + Sync {
+    /// Returns `\sum_i evals[i] bases[i]`, or `None` if the values do not
+    /// have the shape the committer is specialized for. The generic
+    /// commitment is then used.
+    fn commit(&self, bases: &[G], evals: &[G::ScalarField]) -> Option<G::Projective>;
+}
+
+/// The committer of the boolean columns: the sum of the bases of the rows
+/// with value 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BooleanCommitter;
+
+impl<G: KimchiCurve> ColumnCommitter<G> for BooleanCommitter {
+    fn commit(&self, bases: &[G], evals: &[G::ScalarField]) -> Option<G::Projective> {
+        let mut res = G::Projective::zero();
+        for (base, x) in bases.iter().zip(evals.iter()) {
+            if x.is_one() {
+                res.add_assign_mixed(base);
+            } else if !x.is_zero() {
+                return None;
+            }
+        }
+        Some(res)
+    }
+}
+
+/// The committer of the columns of values less than `2^bits`, with an MSM of a
+/// single window: the bases are added to the bucket of their value, and the
+/// buckets are combined with a running sum.
+/// Combining the buckets costs two additions per bucket, hence the columns
+/// with fewer rows than buckets are committed with the generic commitment.
+#[derive(Debug, Clone, Copy)]
+pub struct WindowCommitter {
+    bits: u32,
+}
+
+impl WindowCommitter {
+    /// A committer of the columns of values less than `2^bits`. It allocates
+    /// `2^bits` buckets, hence `bits` must be small.
+    pub fn new(bits: u32) -> Self {
+        assert!(bits <= 20, "a window of {bits} bits is too large");
+        WindowCommitter { bits }
+    }
+}
+
+impl<G: KimchiCurve> ColumnCommitter<G> for WindowCommitter {
+    fn commit(&self, bases: &[G], evals: &[G::ScalarField]) -> Option<G::Projective> {
+        if evals.len() < 1 << self.bits {
+            return None;
+        }
+        let mut buckets = vec![G::Projective::zero(); 1 << self.bits];
+        for (base, x) in bases.iter().zip(evals.iter()) {
+            let x = x.into_repr();
+            if x.num_bits() > self.bits {
+                return None;
+            }
+            let x = x.as_ref()[0] as usize;
+            if x != 0 {
+                buckets[x].add_assign_mixed(base);
+            }
+        }
+        // \sum_v v * B_v = \sum_v \sum_{w >= v} B_w
+        let mut running_sum = G::Projective::zero();
+        let mut res = G::Projective::zero();
+        for bucket in buckets.iter().skip(1).rev() {
+            running_sum += bucket;
+            res += &running_sum;
+        }
+        Some(res)
+    }
+}
+
+/// The committers of the witness columns with a [ColumnDescriptor]. The
+/// [BooleanCommitter] and a [WindowCommitter] of 15 bits are registered by
+/// default, for [ColumnDescriptor::Boolean] and [ColumnDescriptor::Bits] of
+/// 15 bits. The other columns, and the columns whose values do not match
+/// their descriptor, are committed with the generic commitment.
+#[derive(Clone)]
+pub struct ColumnCommitters<G: KimchiCurve> {
+    /// The descriptor of the columns, by index in the witness
+    columns: BTreeMap<usize, ColumnDescriptor>,
+    committers: BTreeMap<ColumnDescriptor, Arc<dyn ColumnCommitter<G>>>,
+}
+
+impl<G: KimchiCurve> Default for ColumnCommitters<G> {
+    fn default() -> Self {
+        let committers: BTreeMap<ColumnDescriptor, Arc<dyn ColumnCommitter<G>>> = BTreeMap::from([
+            (
+                ColumnDescriptor::Boolean,
+                Arc::new(BooleanCommitter) as Arc<dyn ColumnCommitter<G>>,
+            ),
+            (
+                ColumnDescriptor::Bits(15),
+                Arc::new(WindowCommitter::new(15)),
+            ),
+        ]);
+        ColumnCommitters {
+            columns: BTreeMap::new(),
+            committers,
+        }
+    }
+}
+
+impl<G: KimchiCurve> ColumnCommitters<G> {
+    /// Describe the values of the witness column `column`
+    pub fn with_column(mut self, column: usize, descriptor: ColumnDescriptor) -> Self {
+        self.columns.insert(column, descriptor);
+        self
+    }
+
+    /// Commit to the columns of `descriptor` with `committer`
+    pub fn with_committer(
+        mut self,
+        descriptor: ColumnDescriptor,
+        committer: Arc<dyn ColumnCommitter<G>>,
+    ) -> Self {
+        self.committers.insert(descriptor, committer);
+        self
+    }
+
+    /// The descriptor of the witness column `column`, if any
+    pub fn descriptor(&self, column: usize) -> Option<ColumnDescriptor> {
+        self.columns.get(&column).copied()
+    }
+
+    /// The commitment, without blinding, to the witness column `column` with
+    /// the evaluations `evals` over d1, if it has a descriptor with a
+    /// committer. Returns `None` if the generic commitment must be used: the
+    /// values do not match the descriptor, or the Lagrange bases of d1 are
+    /// not in the SRS, or they are chunked.
+    pub fn commit<Srs: SRS<G>>(
+        &self,
+        srs: &Srs,
+        domain: EvaluationDomains<G::ScalarField>,
+        column: usize,
+        evals: &[G::ScalarField],
+    ) -> Option<PolyComm<G>> {
+        let committer = self.committers.get(self.columns.get(&column)?)?;
+        let bases = srs.get_lagrange_basis(domain.d1.size as usize)?;
+        let bases: Vec<G> = bases
+            .iter()
+            .map(|basis| match basis.elems.as_slice() {
+                [base] => Some(*base),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        if bases.len() != evals.len() {
+            return None;
+        }
+        let comm = committer.commit(&bases, evals)?;
+        Some(PolyComm {
+            elems: vec![comm.into_affine()],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnCommitter, ColumnCommitters, ColumnDescriptor, WindowCommitter};
+    use crate::{BN254G1Affine, Fp, BN254};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{PrimeField, UniformRand, Zero};
+    use ark_poly::Evaluations;
+    use kimchi::circuits::domains::EvaluationDomains;
+    use poly_commitment::{pairing_proof::PairingSRS, SRS as _};
+    use rand::Rng;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_specialized_commitments_are_the_generic_ones() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let mut column = |bits: u32| -> Vec<Fp> {
+            (0..domain_size)
+                .map(|_| Fp::from(rng.gen_range(0..1u64 << bits)))
+                .collect()
+        };
+        let (bits, bytes, limbs) = (column(1), column(8), column(15));
+        let committers = ColumnCommitters::<BN254G1Affine>::default()
+            .with_committer(ColumnDescriptor::Bits(8), Arc::new(WindowCommitter::new(8)))
+            .with_column(0, ColumnDescriptor::Boolean)
+            .with_column(1, ColumnDescriptor::Bits(8))
+            .with_column(2, ColumnDescriptor::Bits(15))
+            .with_column(3, ColumnDescriptor::Bits(4));
+        let generic = |evals: &Vec<Fp>| {
+            let evals = Evaluations::from_vec_and_domain(evals.clone(), domain.d1);
+            let comm = srs.commit_evaluations_non_hiding(domain.d1, &evals);
+            assert_eq!(comm, srs.commit_non_hiding(&evals.interpolate(), 1));
+            comm
+        };
+
+        for (column, evals) in [(0, &bits), (1, &bytes), (1, &bits)] {
+            assert_eq!(
+                committers.commit(&srs, domain, column, evals),
+                Some(generic(evals))
+            );
+        }
+        // The bytes are not booleans, and a value of 9 bits does not fit the
+        // window
+        assert_eq!(committers.commit(&srs, domain, 0, &bytes), None);
+        let mut wide = bytes.clone();
+        wide[3] = Fp::from(1u64 << 8);
+        assert_eq!(committers.commit(&srs, domain, 1, &wide), None);
+        // The window of 15 bits has more buckets than the column has rows,
+        // and no committer is registered for the columns of 4 bits
+        assert_eq!(committers.commit(&srs, domain, 2, &limbs), None);
+        assert_eq!(committers.commit(&srs, domain, 3, &bits), None);
+    }
+
+    #[test]
+    fn test_window_commitment() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let size = 1 << 15;
+        // The bases are the multiples `(i + 1) g` of the generator, hence the
+        // commitment is `(\sum_i (i + 1) x_i) g`
+        let g = BN254G1Affine::prime_subgroup_generator();
+        let mut bases = Vec::with_capacity(size);
+        let mut base = g.into_projective();
+        for _ in 0..size {
+            bases.push(base);
+            base.add_assign_mixed(&g);
+        }
+        let bases =
+            <BN254G1Affine as AffineCurve>::Projective::batch_normalization_into_affine(&bases);
+        let limbs: Vec<Fp> = (0..size)
+            .map(|_| Fp::from(rng.gen_range(0..1u64 << 15)))
+            .collect();
+        let scalar = limbs
+            .iter()
+            .enumerate()
+            .fold(Fp::zero(), |acc, (i, x)| acc + Fp::from(i as u64 + 1) * x);
+        let comm =
+            ColumnCommitter::<BN254G1Affine>::commit(&WindowCommitter::new(15), &bases, &limbs);
+        assert_eq!(comm, Some(g.mul(scalar.into_repr())));
+    }
+}
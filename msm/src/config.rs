@@ -0,0 +1,468 @@
+use ark_ff::{FftField, Field, PrimeField};
+use ark_poly::univariate::DensePolynomial;
+use blake2::{Blake2b512, Digest};
+use kimchi::{
+    circuits::{
+        domains::EvaluationDomains,
+        expr::{ChallengeTerm, ConstantExpr, ConstantTerm, ExprInner, RowOffset},
+        gate::CurrOrNext,
+    },
+    curve::KimchiCurve,
+};
+use poly_commitment::{OpenProof, SRS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+use crate::{
+    boundary,
+    columns::Column,
+    expr::{column_accesses, curr_cell, refers_to_challenge, E},
+    mvlookup::{
+        fixed_table_read_outside_lookups, uses_lookups, AggregationDirection,
+        LookupAbsorptionOrder, LookupAggregationMode,
+    },
+    proof::PublicOutputs,
+    simplify::{simplify, ConstraintSimplification},
+    transcript::TranscriptBackend,
+};
+
+/// Errors that can arise when checking a circuit against a [ProtocolConfig]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("the constraint {0} refers to the next row, which is not opened in single-point mode")]
+    NextRowNotOpened(usize),
+
+    #[error("the lookup argument requires the evaluations at ζω, which are not opened in single-point mode")]
+    LookupsNotSupported,
+
+    #[error("the evaluations of the proof do not match the opening mode of the configuration")]
+    OpeningModeMismatch,
+
+    #[error("the constraint {0} refers to the aggregation of the lookup argument, which is not committed in the claimed mode")]
+    AggregationNotCommitted(usize),
+
+    #[error(
+        "the aggregation of the lookup argument in the proof does not match the configuration"
+    )]
+    LookupAggregationMismatch,
+
+    #[error("the SRS label of the proof does not match the configuration")]
+    SrsLabelMismatch,
+
+    #[error("the output cell {0} is not in the witness")]
+    OutputCellOutOfRange(usize),
+
+    #[error("the proof has {got} public outputs, the configuration designates {expected} cells")]
+    PublicOutputCountMismatch { expected: usize, got: usize },
+
+    #[error("the lookup table {0} is not registered in the table policy")]
+    UnregisteredTable(u32),
+
+    #[error("the lookup table {0} is mandatory, but is not active")]
+    MandatoryTableInactive(u32),
+
+    #[error("the lookup accumulator requires lookups, aggregated in the committed mode and in the forward direction")]
+    LookupAccumulatorNotSupported,
+
+    #[error("the constraint {0} refers to a boundary selector which is not the one of the maximum number of rows")]
+    BoundarySelectorMismatch(usize),
+
+    #[error("the constraint {0} reads a column shifted by two rows at the next row, which is not opened")]
+    ShiftedColumnAtNextRow(usize),
+
+    #[error(
+        "the profile {0:?} omits the fixed tables from the proof, which requires global tables"
+    )]
+    GlobalTablesRequired(Profile),
+
+    #[error("the SRS of size {srs_size} is smaller than the domain of size {domain_size}: the commitments would be chunked, and the evaluations of the chunks are not part of the proof")]
+    ChunkedCommitments { domain_size: usize, srs_size: usize },
+
+    #[error("the constraint {constraint} reads the column of the fixed table {table_id}, which is internal to the lookup argument; add the constraint to ProtocolConfig::fixed_table_readers if this is intended")]
+    FixedTableRead { constraint: usize, table_id: u32 },
+
+    #[error("the constraint {0} refers to the joint combiner, which is only coined for the circuits with lookups; use an extra challenge instead")]
+    JointCombinerWithoutLookups(usize),
+}
+
+/// A named set of parameters of the protocol, for the compatibility with
+/// another implementation of the lookup argument, see
+/// [ProtocolConfig::for_profile]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Profile {
+    /// The parameters of this implementation
+    #[default]
+    Native,
+    /// The external MVLookup implementation whose fixed tables are system
+    /// parameters: they are omitted from the proof, and given as
+    /// [crate::mvlookup::GlobalTableCommitments]. The aggregation `φ` is
+    /// absorbed before the partial sums, and accumulates the rows backward.
+    External1,
+}
+
+impl Profile {
+    /// Whether the commitments to the fixed tables are omitted from the
+    /// proofs, the prover and the verifier using global tables instead
+    pub fn omits_fixed_tables(&self) -> bool {
+        match self {
+            Profile::Native => false,
+            Profile::External1 => true,
+        }
+    }
+}
+
+/// A cell of the witness whose value is given with the proof, see
+/// [ProtocolConfig::output_cells]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutputCell {
+    /// The index of the witness column
+    pub column: usize,
+    /// The row, in the domain d1
+    pub row: usize,
+}
+
+/// The points at which the prover opens the polynomials of the proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OpeningMode {
+    /// Open at ζ and ζω. The constraints can refer to the next row.
+    #[default]
+    TwoPoints,
+    /// Open at ζ only. The proof is smaller, but the constraints can only
+    /// refer to the current row, and the lookup argument can not be used.
+    SinglePoint,
+}
+
+impl OpeningMode {
+    /// The number of evaluation points
+    pub fn n_points(&self) -> usize {
+        match self {
+            OpeningMode::TwoPoints => 2,
+            OpeningMode::SinglePoint => 1,
+        }
+    }
+
+    /// Returns the evaluation points, given the challenge `ζ`.
+    pub fn evaluation_points<F: FftField>(&self, zeta: F, domain: EvaluationDomains<F>) -> Vec<F> {
+        match self {
+            OpeningMode::TwoPoints => vec![zeta, zeta * domain.d1.group_gen],
+            OpeningMode::SinglePoint => vec![zeta],
+        }
+    }
+}
+
+/// What the prover and the verifier do when the evaluation point `ζ` coined
+/// by the sponge is degenerate, i.e. zero or in the domain d1. The vanishing
+/// polynomial is zero at the points of d1, and the evaluations of the
+/// constraints divide by it. It only happens with a negligible probability
+/// for an honest transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DegeneratePointRule {
+    /// The prover and the verifier fail
+    #[default]
+    Abort,
+    /// A new challenge is squeezed from the sponge, up to
+    /// [DegeneratePointRule::MAX_REDERIVATIONS] times
+    Rederive,
+}
+
+impl DegeneratePointRule {
+    pub const MAX_REDERIVATIONS: usize = 8;
+
+    /// Whether `zeta` is zero or in the domain d1, of size `n`, i.e.
+    /// `ζ^n = 1`. The point `ζω` is then in d1 too.
+    pub fn is_degenerate<F: FftField>(zeta: F, domain: EvaluationDomains<F>) -> bool {
+        zeta.is_zero() || zeta.pow([domain.d1.size]).is_one()
+    }
+
+    /// Derive the evaluation point from the challenges given by `squeeze`,
+    /// following the rule. Returns `None` if the point is degenerate and can
+    /// not be derived again.
+    pub fn derive_evaluation_point<F: FftField>(
+        &self,
+        domain: EvaluationDomains<F>,
+        mut squeeze: impl FnMut() -> F,
+    ) -> Option<F> {
+        let attempts = match self {
+            DegeneratePointRule::Abort => 1,
+            DegeneratePointRule::Rederive => 1 + Self::MAX_REDERIVATIONS,
+        };
+        (0..attempts)
+            .map(|_| squeeze())
+            .find(|zeta| !Self::is_degenerate(*zeta, domain))
+    }
+}
+
+/// A label identifying the SRS a proof is created with, either chosen by the
+/// caller or computed from the SRS with [SrsLabel::fingerprint]. It lets a
+/// verifier accepting proofs under several SRS select the right one, see
+/// [crate::verifier::verify_with_srs_set].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SrsLabel(pub Vec<u8>);
+
+impl SrsLabel {
+    pub fn new(label: impl AsRef<[u8]>) -> Self {
+        SrsLabel(label.as_ref().to_vec())
+    }
+
+    /// Returns a label derived from the bases of `srs`: the hash of its size
+    /// and of the commitment to the polynomial `1 + 2 X + ... + n X^{n - 1}`,
+    /// `n` being the size of the SRS, which involves all the bases.
+    pub fn fingerprint<G: KimchiCurve, OpeningProof: OpenProof<G>>(
+        srs: &OpeningProof::SRS,
+    ) -> Self {
+        let size = srs.max_poly_size();
+        let poly = DensePolynomial {
+            coeffs: (1..=size as u64).map(G::ScalarField::from).collect(),
+        };
+        let comm = srs.commit_non_hiding(&poly, 1);
+        let mut hasher = Blake2b512::new();
+        hasher.update((size as u64).to_le_bytes());
+        for elem in &comm.elems {
+            let mut bytes = vec![];
+            elem.serialize(&mut bytes).unwrap();
+            hasher.update(bytes);
+        }
+        SrsLabel(hasher.finalize()[..32].to_vec())
+    }
+
+    /// The field element absorbed at the start of the transcript
+    pub fn to_field<F: PrimeField>(&self) -> F {
+        F::from_le_bytes_mod_order(&Blake2b512::digest(&self.0))
+    }
+}
+
+/// The parameters of the protocol, which must be shared by the prover and the
+/// verifier.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolConfig {
+    /// The evaluation points, see [OpeningMode]
+    pub opening_mode: OpeningMode,
+    /// How the aggregation of the lookup argument is given, see
+    /// [LookupAggregationMode]
+    pub lookup_aggregation: LookupAggregationMode,
+    /// The order in which the running sum of the lookup argument accumulates
+    /// the rows, see [AggregationDirection]
+    pub aggregation_direction: AggregationDirection,
+    /// The label of the SRS, absorbed at the start of the transcript and
+    /// recorded in the proof. The transcript does not depend on the SRS when
+    /// it is not set.
+    pub srs_label: Option<SrsLabel>,
+    /// What to do if the evaluation point is degenerate, see
+    /// [DegeneratePointRule]
+    pub degenerate_point_rule: DegeneratePointRule,
+    /// The cells whose values are copied in the [PublicOutputs] of the proof.
+    /// The outputs are absorbed at the start of the transcript, and bound to
+    /// the cells by a constraint per cell.
+    #[serde(default)]
+    pub output_cells: Vec<OutputCell>,
+    /// The sponge coining the challenges, see [TranscriptBackend]
+    #[serde(default)]
+    pub transcript: TranscriptBackend,
+    /// The maximum number of rows of the circuit, the witness being padded to
+    /// the size of the domain. The constraints can be restricted to these
+    /// rows with the [ProtocolConfig::boundary_selector].
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+    /// The order in which the commitments of the lookup argument are
+    /// absorbed, see [LookupAbsorptionOrder]
+    #[serde(default)]
+    pub lookup_absorption: LookupAbsorptionOrder,
+    /// The implementation the parameters are compatible with, see
+    /// [ProtocolConfig::for_profile]
+    #[serde(default)]
+    pub profile: Profile,
+    /// Whether the constraints are simplified when they are compiled, see
+    /// [ConstraintSimplification]
+    #[serde(default)]
+    pub simplification: ConstraintSimplification,
+    /// The indices of the constraints allowed to read the columns of the
+    /// fixed tables outside of the lookup argument, see
+    /// [ConfigError::FixedTableRead]
+    #[serde(default)]
+    pub fixed_table_readers: BTreeSet<usize>,
+}
+
+impl ProtocolConfig {
+    /// The configuration of `profile`. The other parameters are the default
+    /// ones, and can be changed as long as they are shared with the other
+    /// implementation.
+    pub fn for_profile(profile: Profile) -> Self {
+        match profile {
+            Profile::Native => ProtocolConfig::default(),
+            Profile::External1 => ProtocolConfig {
+                aggregation_direction: AggregationDirection::Backward,
+                lookup_absorption: LookupAbsorptionOrder::AggregationFirst,
+                profile,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The configuration of the entry points taking none, e.g.
+    /// [crate::prover::prove]: the default one, with the constraints proven
+    /// as they are given.
+    pub(crate) fn unsimplified() -> Self {
+        ProtocolConfig {
+            simplification: ConstraintSimplification::Disabled,
+            ..Default::default()
+        }
+    }
+
+    /// Check that global tables are given if the profile omits the fixed
+    /// tables from the proofs, see [Profile::omits_fixed_tables]
+    pub fn check_global_tables(&self, has_global_tables: bool) -> Result<(), ConfigError> {
+        if self.profile.omits_fixed_tables() && !has_global_tables {
+            return Err(ConfigError::GlobalTablesRequired(self.profile));
+        }
+        Ok(())
+    }
+
+    /// Check that the constraints can be proven with this configuration, and
+    /// return the constraints to give to the prover and to the verifier.
+    pub fn compile_constraints<F: FftField>(
+        &self,
+        constraints: Vec<E<F>>,
+    ) -> Result<Vec<E<F>>, ConfigError> {
+        if self.opening_mode == OpeningMode::SinglePoint {
+            if let Some(i) = constraints.iter().position(refers_to_next_row) {
+                return Err(ConfigError::NextRowNotOpened(i));
+            }
+        }
+        if self.lookup_aggregation == LookupAggregationMode::Claimed {
+            if let Some(i) = constraints.iter().position(refers_to_aggregation) {
+                return Err(ConfigError::AggregationNotCommitted(i));
+            }
+        }
+        if let Some(i) = constraints
+            .iter()
+            .position(|constraint| refers_to_other_boundary(constraint, self.max_rows))
+        {
+            return Err(ConfigError::BoundarySelectorMismatch(i));
+        }
+        if let Some(i) = constraints.iter().position(refers_to_shifted_next_row) {
+            return Err(ConfigError::ShiftedColumnAtNextRow(i));
+        }
+        check_joint_combiner(&constraints)?;
+        if let Some((constraint, table_id)) = constraints
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.fixed_table_readers.contains(i))
+            .find_map(|(i, constraint)| {
+                fixed_table_read_outside_lookups(constraint).map(|table_id| (i, table_id))
+            })
+        {
+            return Err(ConfigError::FixedTableRead {
+                constraint,
+                table_id,
+            });
+        }
+        match self.simplification {
+            ConstraintSimplification::Enabled => {
+                Ok(constraints.into_iter().map(simplify).collect())
+            }
+            ConstraintSimplification::Disabled => Ok(constraints),
+        }
+    }
+
+    /// The selector of the first [ProtocolConfig::max_rows] rows, if the
+    /// maximum number of rows is set, see [crate::boundary]
+    pub fn boundary_selector<F: Field>(&self) -> Option<E<F>> {
+        self.max_rows.map(boundary::selector)
+    }
+
+    /// Returns the values of the output cells in `columns`, the witness of
+    /// size `domain_size`
+    pub fn public_outputs<F: Clone>(
+        &self,
+        columns: &[Vec<F>],
+        domain_size: usize,
+    ) -> Result<PublicOutputs<F>, ConfigError> {
+        self.output_cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if cell.row >= domain_size {
+                    return Err(ConfigError::OutputCellOutOfRange(i));
+                }
+                columns
+                    .get(cell.column)
+                    .and_then(|column| column.get(cell.row))
+                    .cloned()
+                    .ok_or(ConfigError::OutputCellOutOfRange(i))
+            })
+            .collect::<Result<_, _>>()
+            .map(PublicOutputs)
+    }
+
+    /// Returns the constraints binding the output cells to `outputs`: the
+    /// unnormalized Lagrange polynomial of the row of the cell, which is only
+    /// non-zero on this row, times the difference of the cell and the output.
+    pub fn output_constraints<F: FftField>(
+        &self,
+        outputs: &PublicOutputs<F>,
+    ) -> Result<Vec<E<F>>, ConfigError> {
+        if outputs.0.len() != self.output_cells.len() {
+            return Err(ConfigError::PublicOutputCountMismatch {
+                expected: self.output_cells.len(),
+                got: outputs.0.len(),
+            });
+        }
+        Ok(self
+            .output_cells
+            .iter()
+            .zip(outputs.0.iter())
+            .map(|(cell, output)| {
+                let row = E::Atom(ExprInner::UnnormalizedLagrangeBasis(RowOffset {
+                    zk_rows: false,
+                    offset: cell.row as i32,
+                }));
+                let output = E::Atom(ExprInner::Constant(ConstantExpr::from(
+                    ConstantTerm::Literal(*output),
+                )));
+                row * (curr_cell(Column::X(cell.column)) - output)
+            })
+            .collect())
+    }
+}
+
+fn refers_to_next_row<F>(constraint: &E<F>) -> bool {
+    column_accesses(constraint)
+        .iter()
+        .any(|(_, row)| *row == CurrOrNext::Next)
+}
+
+fn refers_to_other_boundary<F>(constraint: &E<F>, max_rows: Option<usize>) -> bool {
+    column_accesses(constraint).iter().any(|(col, _)| {
+        matches!(col, Column::BoundarySelector(rows)
+                if Some(*rows) != max_rows && *rows != boundary::FIRST_ROW)
+    })
+}
+
+fn refers_to_shifted_next_row<F>(constraint: &E<F>) -> bool {
+    column_accesses(constraint)
+        .iter()
+        .any(|(col, row)| matches!(col, Column::XShift2(_)) && *row == CurrOrNext::Next)
+}
+
+/// Check that the constraints refer to the joint combiner only if they use
+/// lookups, see [crate::mvlookup::joint_combiner]: it is not coined for a
+/// lookup-free circuit.
+pub(crate) fn check_joint_combiner<F>(constraints: &[E<F>]) -> Result<(), ConfigError> {
+    if uses_lookups(constraints) {
+        return Ok(());
+    }
+    match constraints
+        .iter()
+        .position(|constraint| refers_to_challenge(constraint, ChallengeTerm::JointCombiner))
+    {
+        Some(i) => Err(ConfigError::JointCombinerWithoutLookups(i)),
+        None => Ok(()),
+    }
+}
+
+fn refers_to_aggregation<F>(constraint: &E<F>) -> bool {
+    column_accesses(constraint)
+        .iter()
+        .any(|(col, _)| *col == Column::LookupAggregation)
+}
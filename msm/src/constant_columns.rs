@@ -0,0 +1,126 @@
+//! Elision of the witness columns which are constant over the whole domain.
+//! Committing to and opening such columns is wasted work, as the constraints
+//! can use the constant values directly. The constant columns are public data,
+//! and the verifier applies the same substitution to the constraints.
+
+use ark_ff::Field;
+use kimchi::{
+    circuits::expr::{ConstantExpr, ConstantTerm, ExprInner, Operations, Variable},
+    curve::KimchiCurve,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::BTreeMap;
+
+use crate::{
+    expr::E, mvlookup::LookupTableID, proof::ProofInputs, prover::ProverError, witness::Witness,
+};
+
+/// The witness columns which are constant over the whole domain, indexed by
+/// the index of the column in the full witness.
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+pub struct ConstantColumns<F> {
+    #[serde_as(as = "BTreeMap<_, o1_utils::serialization::SerdeAs>")]
+    pub columns: BTreeMap<usize, F>,
+}
+
+impl<F: Field> ConstantColumns<F> {
+    /// Returns the columns of `evaluations` which are constant.
+    pub fn detect<const N: usize>(evaluations: &Witness<N, Vec<F>>) -> Self {
+        let columns = evaluations
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, column)| {
+                let first = column.first()?;
+                column.iter().all(|x| x == first).then_some((i, *first))
+            })
+            .collect();
+        ConstantColumns { columns }
+    }
+
+    /// Returns the index of the column `i` in the witness without the constant
+    /// columns, or `None` if the column is constant.
+    pub fn reduced_index(&self, i: usize) -> Option<usize> {
+        if self.columns.contains_key(&i) {
+            None
+        } else {
+            Some(i - self.columns.range(..i).count())
+        }
+    }
+
+    /// Replace the references to the constant columns in `constraint` by their
+    /// value, and renumber the references to the other columns. As the
+    /// column is constant, the value on the next rows is the same, so the
+    /// shifted references are replaced too.
+    pub fn substitute(&self, constraint: &E<F>) -> E<F> {
+        let sub = |x: &E<F>| Box::new(self.substitute(x));
+        match constraint {
+            Operations::Atom(ExprInner::Cell(Variable { col, row }))
+                if col.witness_index().is_some() =>
+            {
+                let i = col.witness_index().unwrap();
+                match self.columns.get(&i) {
+                    Some(value) => E::Atom(ExprInner::Constant(ConstantExpr::from(
+                        ConstantTerm::Literal(*value),
+                    ))),
+                    None => E::Atom(ExprInner::Cell(Variable {
+                        col: col.with_witness_index(self.reduced_index(i).unwrap()),
+                        row: *row,
+                    })),
+                }
+            }
+            Operations::Atom(x) => Operations::Atom(x.clone()),
+            Operations::Pow(x, n) => Operations::Pow(sub(x), *n),
+            Operations::Add(x, y) => Operations::Add(sub(x), sub(y)),
+            Operations::Mul(x, y) => Operations::Mul(sub(x), sub(y)),
+            Operations::Sub(x, y) => Operations::Sub(sub(x), sub(y)),
+            Operations::Double(x) => Operations::Double(sub(x)),
+            Operations::Square(x) => Operations::Square(sub(x)),
+            Operations::Cache(id, x) => Operations::Cache(*id, sub(x)),
+            Operations::IfFeature(feature, x, y) => Operations::IfFeature(*feature, sub(x), sub(y)),
+        }
+    }
+
+    /// Remove the constant columns from the witness. The `N - M` columns
+    /// must be constant, with the declared value.
+    pub fn reduce_inputs<const N: usize, const M: usize, G, ID>(
+        &self,
+        inputs: ProofInputs<N, G, ID>,
+    ) -> Result<ProofInputs<M, G, ID>, ProverError>
+    where
+        G: KimchiCurve<ScalarField = F>,
+        ID: LookupTableID,
+    {
+        if M + self.columns.len() != N {
+            return Err(ProverError::Generic(
+                "the number of columns does not match the constant columns",
+            ));
+        }
+        let ProofInputs {
+            evaluations,
+            mvlookups,
+        } = inputs;
+        let mut cols = Vec::with_capacity(M);
+        for (i, column) in (*evaluations.cols).into_iter().enumerate() {
+            match self.columns.get(&i) {
+                Some(value) => {
+                    if column.iter().any(|x| x != value) {
+                        return Err(ProverError::NonConstantColumn(i));
+                    }
+                }
+                None => cols.push(column),
+            }
+        }
+        let cols: [Vec<F>; M] = cols
+            .try_into()
+            .map_err(|_| ProverError::Generic("a constant column is out of bounds"))?;
+        Ok(ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups,
+        })
+    }
+}
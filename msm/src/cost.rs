@@ -0,0 +1,497 @@
+//! Static estimate of the work performed by the verifier, to plan the
+//! verification of the proofs in a constrained environment.
+//! The estimate follows the steps of [crate::verifier::verify]: the same
+//! values are absorbed in the same order, see [transcript_profile], and the
+//! constraints are compiled to the same RPN expression.
+
+use ark_ff::FftField;
+use kimchi::{
+    circuits::expr::{Expr, PolishToken},
+    curve::KimchiCurve,
+};
+use mina_poseidon::constants::SpongeConstants;
+use poly_commitment::OpenProof;
+use std::collections::BTreeMap;
+
+use crate::{
+    config::ProtocolConfig,
+    expr::E,
+    mvlookup::{chunk_padding, LookupAggregationMode, LookupTableID},
+    proof::{max_constraint_degree, EvaluationContainer, Proof},
+    transcript::TranscriptProfile,
+    SpongeParams, MAX_SUPPORTED_DEGREE,
+};
+
+/// The number of field operations performed to evaluate an expression
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FieldOps {
+    /// The number of additions and subtractions
+    pub additions: usize,
+    /// The number of multiplications, including the ones performed by the
+    /// exponentiations
+    pub multiplications: usize,
+    /// The number of inversions, performed when evaluating the Lagrange basis
+    /// and the vanishing polynomials
+    pub inversions: usize,
+}
+
+impl FieldOps {
+    /// Count the operations performed by [PolishToken::evaluate] on `tokens`.
+    pub fn of_polish<F, Column>(tokens: &[PolishToken<F, Column>]) -> Self {
+        let mut ops = FieldOps::default();
+        for token in tokens {
+            match token {
+                PolishToken::Add | PolishToken::Sub => ops.additions += 1,
+                PolishToken::Mul => ops.multiplications += 1,
+                // Square and multiply, from the most significant bit
+                PolishToken::Pow(n) => {
+                    let bits = (64 - n.leading_zeros()) as usize;
+                    ops.multiplications += bits + n.count_ones() as usize;
+                }
+                PolishToken::UnnormalizedLagrangeBasis(_)
+                | PolishToken::VanishesOnZeroKnowledgeAndPreviousRows => ops.inversions += 1,
+                _ => (),
+            }
+        }
+        ops
+    }
+
+    /// Count the operations performed by the verifier to evaluate the
+    /// combination of `constraints` with the powers of `α`.
+    pub fn of_constraints<F: FftField>(constraints: &[E<F>]) -> Self {
+        let combined_expr =
+            Expr::combine_constraints(0..(constraints.len() as u32), constraints.to_vec());
+        Self::of_polish(&combined_expr.to_polish())
+    }
+}
+
+/// The shape of a circuit, i.e. what the cost of the verifier depends on.
+/// The commitments to the columns are assumed to be made of a single chunk,
+/// i.e. the SRS is at least as large as the domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitShape {
+    /// The size of the domain d1
+    pub domain_size: u64,
+    /// The number of witness columns
+    pub n_columns: usize,
+    /// The number of public input columns, which the verifier commits to
+    pub n_public_inputs: usize,
+    /// The number of values looked up per row, for each fixed lookup table.
+    /// Empty if the circuit does not use the lookup argument.
+    pub lookups: BTreeMap<u32, usize>,
+    /// The maximum degree of the constraints
+    pub max_degree: u64,
+    /// The operations performed to evaluate the constraints
+    pub constraint_ops: FieldOps,
+}
+
+impl CircuitShape {
+    /// The shape of a circuit with the constraints `constraints`, as given to
+    /// the verifier, and `lookups[id]` values looked up per row in the table
+    /// `id`.
+    pub fn new<F: FftField>(
+        domain_size: u64,
+        n_columns: usize,
+        n_public_inputs: usize,
+        constraints: &[E<F>],
+        lookups: BTreeMap<u32, usize>,
+    ) -> Self {
+        CircuitShape {
+            domain_size,
+            n_columns,
+            n_public_inputs,
+            max_degree: max_constraint_degree(constraints, !lookups.is_empty()),
+            constraint_ops: FieldOps::of_constraints(constraints),
+            lookups,
+        }
+    }
+
+    /// The number of partial sums of the lookup argument. The looked-up values
+    /// and the table are split in chunks of `MAX_SUPPORTED_DEGREE - 2` terms,
+    /// one partial sum per chunk.
+    pub fn n_partial_sums(&self) -> usize {
+        self.lookups
+            .values()
+            .map(|n_lookups| (n_lookups + MAX_SUPPORTED_DEGREE - 2) / (MAX_SUPPORTED_DEGREE - 2))
+            .sum()
+    }
+
+    /// The tables whose last partial sum only holds the term of the table,
+    /// wasting a committed column on a single rational term. The author can
+    /// merge the table with another one, move a looked-up value to another
+    /// table, or accept the waste by padding the chunk with
+    /// [crate::mvlookup::pad_lookups], after which the table is not reported.
+    pub fn underfull_chunks(&self) -> Vec<UnderfullChunk> {
+        self.lookups
+            .iter()
+            .filter(|(_, n_lookups)| chunk_padding(**n_lookups) > 0)
+            .map(|(table_id, n_lookups)| UnderfullChunk {
+                table_id: *table_id,
+                n_lookups: *n_lookups,
+            })
+            .collect()
+    }
+
+    /// The number of chunks of the commitment to the quotient polynomial, see
+    /// [crate::proof::expected_quotient_chunks]
+    pub fn quotient_chunks(&self) -> usize {
+        std::cmp::max(1, self.max_degree.saturating_sub(1) as usize)
+    }
+}
+
+/// A table of a [CircuitShape] whose last partial sum only holds the term of
+/// the table, see [CircuitShape::underfull_chunks]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnderfullChunk {
+    pub table_id: u32,
+    /// The number of values looked up per row in the table
+    pub n_lookups: usize,
+}
+
+impl std::fmt::Display for UnderfullChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the last partial sum of the table {} only holds the term of the table ({} values looked up per row): merge the table with another one, move a lookup, or pad it",
+            self.table_id, self.n_lookups,
+        )
+    }
+}
+
+/// The work performed by the verifier, see [estimate_verifier_cost]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifierCost {
+    /// The number of permutations of the sponge over the base field, which
+    /// absorbs the commitments
+    pub fq_sponge_permutations: usize,
+    /// The number of permutations of the sponge over the scalar field, which
+    /// absorbs the evaluations
+    pub fr_sponge_permutations: usize,
+    /// The number of MSMs, indexed by the number of bases
+    pub msms: BTreeMap<usize, usize>,
+    /// The number of single scalar multiplications of group elements
+    pub scalar_muls: usize,
+    /// The field operations performed to evaluate the constraints at `ζ`
+    pub constraint_ops: FieldOps,
+}
+
+/// The state of a sponge whose permutations are counted, following
+/// [mina_poseidon::poseidon::ArithmeticSponge].
+#[derive(Debug, Clone, Copy)]
+enum SpongeState {
+    Absorbed(usize),
+    Squeezed(usize),
+}
+
+#[derive(Debug, Clone)]
+struct SpongeModel {
+    state: SpongeState,
+    permutations: usize,
+}
+
+impl SpongeModel {
+    const RATE: usize = SpongeParams::SPONGE_RATE;
+
+    fn new() -> Self {
+        SpongeModel {
+            state: SpongeState::Absorbed(0),
+            permutations: 0,
+        }
+    }
+
+    /// Absorb `n` field elements
+    fn absorb(&mut self, n: usize) {
+        for _ in 0..n {
+            self.state = match self.state {
+                SpongeState::Absorbed(k) if k == Self::RATE => {
+                    self.permutations += 1;
+                    SpongeState::Absorbed(1)
+                }
+                SpongeState::Absorbed(k) => SpongeState::Absorbed(k + 1),
+                SpongeState::Squeezed(_) => SpongeState::Absorbed(1),
+            }
+        }
+    }
+
+    /// Squeeze one field element. A challenge uses the 128 bits of a single
+    /// field element, i.e. it is one squeeze.
+    fn squeeze(&mut self) {
+        self.state = match self.state {
+            SpongeState::Squeezed(k) if k < Self::RATE => SpongeState::Squeezed(k + 1),
+            _ => {
+                self.permutations += 1;
+                SpongeState::Squeezed(1)
+            }
+        }
+    }
+}
+
+/// The operations of a transcript, performed on a [SpongeModel] or counted in
+/// a [TranscriptProfile]
+trait TranscriptModel {
+    /// Absorb `n` points
+    fn absorb_points(&mut self, n: usize);
+    /// Absorb `n` scalars
+    fn absorb_scalars(&mut self, n: usize);
+    fn squeeze(&mut self);
+}
+
+/// A commitment is absorbed as the two coordinates of each chunk, and a
+/// scalar as a single field element
+impl TranscriptModel for SpongeModel {
+    fn absorb_points(&mut self, n: usize) {
+        self.absorb(2 * n)
+    }
+
+    fn absorb_scalars(&mut self, n: usize) {
+        self.absorb(n)
+    }
+
+    fn squeeze(&mut self) {
+        SpongeModel::squeeze(self)
+    }
+}
+
+/// The operations counted on a sponge
+#[derive(Debug, Default)]
+struct OperationCounts {
+    points: usize,
+    scalars: usize,
+    squeezes: usize,
+}
+
+impl TranscriptModel for OperationCounts {
+    fn absorb_points(&mut self, n: usize) {
+        self.points += n
+    }
+
+    fn absorb_scalars(&mut self, n: usize) {
+        self.scalars += n
+    }
+
+    fn squeeze(&mut self) {
+        self.squeezes += 1
+    }
+}
+
+/// What the messages of a transcript depend on, the commitments having a
+/// single chunk but the one to the quotient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TranscriptLayout {
+    pub srs_label: bool,
+    pub n_public_outputs: usize,
+    pub n_columns: usize,
+    pub n_extra_challenges: usize,
+    /// The number of tables with multiplicities, 0 without lookups
+    pub n_tables: usize,
+    /// The number of fixed tables whose commitments are absorbed
+    pub n_fixed_tables: usize,
+    /// The number of tables proven with the plookup engine, see
+    /// [crate::lookup_engine::PlookupEngine]
+    pub n_plookup_tables: usize,
+    pub n_partial_sums: usize,
+    pub lookup_aggregation: LookupAggregationMode,
+    /// Whether the running sum is chained with other proofs, see
+    /// [crate::mvlookup::AccumulatorBoundary]
+    pub accumulator: bool,
+    pub quotient_chunks: usize,
+    pub n_points: usize,
+    /// The number of witness columns read two rows ahead, see
+    /// [crate::shifts]
+    pub n_shifted_columns: usize,
+}
+
+impl TranscriptLayout {
+    /// The layout of the transcript of the proofs of a circuit of shape
+    /// `shape`, created with [crate::prover::prove_with_context]
+    fn of_shape(shape: &CircuitShape, config: &ProtocolConfig) -> Self {
+        TranscriptLayout {
+            srs_label: config.srs_label.is_some(),
+            n_public_outputs: config.output_cells.len(),
+            n_columns: shape.n_columns,
+            n_extra_challenges: 0,
+            n_tables: shape.lookups.len(),
+            n_fixed_tables: shape.lookups.len(),
+            n_plookup_tables: 0,
+            n_partial_sums: shape.n_partial_sums(),
+            lookup_aggregation: config.lookup_aggregation,
+            accumulator: false,
+            quotient_chunks: shape.quotient_chunks(),
+            n_points: config.opening_mode.n_points(),
+            // The shapes do not account for the columns read two rows ahead
+            n_shifted_columns: 0,
+        }
+    }
+
+    /// The layout of the transcript of `proof`, coining `n_extra_challenges`
+    /// after the first phase. The commitments to the fixed tables are
+    /// derived from the global tables for each table with multiplicities if
+    /// `global_tables`.
+    pub(crate) fn of_proof<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        n_extra_challenges: usize,
+        global_tables: bool,
+    ) -> Self {
+        let lookups = proof.proof_comms.mvlookup_comms.as_ref();
+        let n_tables = lookups.map_or(0, |lookups| lookups.m.len());
+        TranscriptLayout {
+            srs_label: proof.srs_label.is_some(),
+            n_public_outputs: proof.public_outputs.0.len(),
+            n_columns: N,
+            n_extra_challenges,
+            n_tables,
+            n_fixed_tables: match global_tables {
+                true => n_tables,
+                false => lookups.map_or(0, |lookups| lookups.fixed_tables.len()),
+            },
+            n_plookup_tables: lookups.map_or(0, |lookups| lookups.plookup.len()),
+            n_partial_sums: lookups.map_or(0, |lookups| lookups.h.len()),
+            lookup_aggregation: lookups.map_or(LookupAggregationMode::Committed, |lookups| {
+                lookups.sum.mode()
+            }),
+            accumulator: proof.accumulator.is_some(),
+            quotient_chunks: proof.proof_comms.t_comm.elems.len(),
+            n_points: Eval::OPENING_MODE.n_points(),
+            n_shifted_columns: proof.proof_evals.shifted_evals.len(),
+        }
+    }
+
+    /// Perform the operations of the transcript, in the order of
+    /// [crate::verifier::verify]
+    fn replay(&self, fq_sponge: &mut impl TranscriptModel, fr_sponge: &mut impl TranscriptModel) {
+        if self.srs_label {
+            fq_sponge.absorb_scalars(1);
+        }
+        if self.n_public_outputs > 0 {
+            fq_sponge.absorb_scalars(self.n_public_outputs);
+        }
+        fq_sponge.absorb_points(self.n_columns);
+        (0..self.n_extra_challenges).for_each(|_| fq_sponge.squeeze());
+        if self.has_lookups() {
+            fq_sponge.absorb_points(self.n_tables);
+            // The table and the sorted columns of the plookup engine
+            fq_sponge.absorb_points(3 * self.n_plookup_tables);
+            // The joint combiner, β, and γ for the plookup engine
+            fq_sponge.squeeze();
+            fq_sponge.squeeze();
+            if self.n_plookup_tables > 0 {
+                fq_sponge.squeeze();
+            }
+            fq_sponge.absorb_points(self.n_partial_sums + self.n_fixed_tables);
+            match self.lookup_aggregation {
+                LookupAggregationMode::Committed => {
+                    fq_sponge.absorb_points(1);
+                    // The incoming and outgoing accumulators
+                    if self.accumulator {
+                        fq_sponge.absorb_scalars(2);
+                    }
+                }
+                LookupAggregationMode::Claimed => fq_sponge.absorb_scalars(1),
+            }
+            // The grand products of the plookup engine
+            fq_sponge.absorb_points(self.n_plookup_tables);
+        }
+        // α
+        fq_sponge.squeeze();
+        fq_sponge.absorb_points(self.quotient_chunks);
+        // ζ, and the digest given to the sponge over the scalar field
+        fq_sponge.squeeze();
+        fq_sponge.squeeze();
+
+        fr_sponge.absorb_scalars(1);
+        fr_sponge.absorb_scalars(self.n_points * (self.n_columns + self.n_lookup_columns()));
+        // The evaluations at ζω²
+        if self.n_shifted_columns > 0 {
+            fr_sponge.absorb_scalars(self.n_shifted_columns);
+        }
+        // The evaluation of ft at ζω
+        if self.n_points == 2 {
+            fr_sponge.absorb_scalars(1);
+        }
+        // v and u
+        fr_sponge.squeeze();
+        fr_sponge.squeeze();
+    }
+
+    /// Whether the proofs have a lookup argument
+    fn has_lookups(&self) -> bool {
+        self.n_tables > 0 || self.n_plookup_tables > 0
+    }
+
+    /// The multiplicities, the partial sums, the aggregation if it is
+    /// committed, the fixed tables and the columns of the plookup engine
+    fn n_lookup_columns(&self) -> usize {
+        if !self.has_lookups() {
+            0
+        } else {
+            self.n_tables
+                + self.n_partial_sums
+                + usize::from(self.lookup_aggregation == LookupAggregationMode::Committed)
+                + self.n_fixed_tables
+                + 4 * self.n_plookup_tables
+        }
+    }
+
+    /// The profile of the transcript
+    pub(crate) fn profile(&self) -> TranscriptProfile {
+        let mut fq_sponge = OperationCounts::default();
+        let mut fr_sponge = OperationCounts::default();
+        self.replay(&mut fq_sponge, &mut fr_sponge);
+        TranscriptProfile {
+            fq_points: fq_sponge.points,
+            fq_scalars: fq_sponge.scalars,
+            fq_squeezes: fq_sponge.squeezes,
+            fr_scalars: fr_sponge.scalars,
+            fr_squeezes: fr_sponge.squeezes,
+        }
+    }
+}
+
+/// The operations performed on the sponges of the transcript of the proofs of
+/// a circuit of shape `shape`, up to the opening proof, see
+/// [TranscriptProfile]. A recursive verifier replays the same operations.
+/// The evaluation point `ζ` is squeezed once, unless it is degenerate, which
+/// only happens with a negligible probability.
+pub fn transcript_profile(shape: &CircuitShape, config: &ProtocolConfig) -> TranscriptProfile {
+    TranscriptLayout::of_shape(shape, config).profile()
+}
+
+/// Estimate the work performed by [crate::verifier::VerifierContext::verify]
+/// to verify a proof for a circuit of shape `shape`, with the sponges
+/// [crate::BaseSponge] and [crate::ScalarSponge].
+/// The MSM combining the commitments in the opening proof is counted, but not
+/// the other operations of the polynomial commitment scheme.
+pub fn estimate_verifier_cost(shape: &CircuitShape, config: &ProtocolConfig) -> VerifierCost {
+    let layout = TranscriptLayout::of_shape(shape, config);
+    let n_lookup_columns = layout.n_lookup_columns();
+    let quotient_chunks = layout.quotient_chunks;
+
+    let mut fq_sponge = SpongeModel::new();
+    let mut fr_sponge = SpongeModel::new();
+    layout.replay(&mut fq_sponge, &mut fr_sponge);
+
+    let mut msms = BTreeMap::new();
+    if shape.n_public_inputs > 0 {
+        msms.insert(shape.domain_size as usize, shape.n_public_inputs);
+    }
+    // The columns, and the chunked commitment to the quotient
+    *msms
+        .entry(shape.n_columns + n_lookup_columns + 1)
+        .or_insert(0) += 1;
+
+    VerifierCost {
+        fq_sponge_permutations: fq_sponge.permutations,
+        fr_sponge_permutations: fr_sponge.permutations,
+        msms,
+        // The masking of the public input commitments, and the chunks of the
+        // quotient
+        scalar_muls: shape.n_public_inputs + quotient_chunks + 1,
+        constraint_ops: shape.constraint_ops,
+    }
+}
@@ -0,0 +1,175 @@
+//! Creation of the evaluation domains of a circuit from its number of rows.
+//!
+//! The domain `d1` has the smallest power of two size containing the rows,
+//! and the domain `d8` is 8 times larger, hence the rows must fit in a
+//! subgroup of size `2^(TWO_ADICITY - 3)` of the field. The witness is padded
+//! with [domain_padding] rows to the size of `d1`, see
+//! [crate::proof::ProofInputs::pad].
+
+use ark_ff::{FftField, FftParameters};
+use kimchi::circuits::domains::EvaluationDomains;
+use thiserror::Error;
+
+/// Errors that can arise when creating the domains of a circuit
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DomainError {
+    #[error("the circuit has no rows")]
+    NoRows,
+
+    #[error(
+        "{requested_rows} rows need a domain d8 of size 2^{log_d8_size}, larger than the 2-adic subgroup of the field, of size 2^{two_adicity}"
+    )]
+    TooLarge {
+        requested_rows: usize,
+        log_d8_size: u32,
+        two_adicity: u32,
+    },
+
+    #[error("the domains of {requested_rows} rows can not be created: {reason}")]
+    Creation {
+        requested_rows: usize,
+        reason: String,
+    },
+}
+
+/// The largest number of rows of a circuit over the field `F`, such that its
+/// domain d8 fits in the 2-adic subgroup of `F`
+pub fn max_rows<F: FftField>() -> usize {
+    1usize
+        .checked_shl(F::FftParams::TWO_ADICITY.saturating_sub(3))
+        .unwrap_or(usize::MAX)
+}
+
+/// Create the domains of a circuit of `requested_rows` rows. The size of `d1`
+/// is the smallest power of two containing the rows, and the witness must be
+/// padded with [domain_padding] rows.
+pub fn create_domain<F: FftField>(
+    requested_rows: usize,
+) -> Result<EvaluationDomains<F>, DomainError> {
+    if requested_rows == 0 {
+        return Err(DomainError::NoRows);
+    }
+    if requested_rows > max_rows::<F>() {
+        // The size of d8 is 8 times the next power of two
+        let log_d1_size = usize::BITS - (requested_rows - 1).leading_zeros();
+        return Err(DomainError::TooLarge {
+            requested_rows,
+            log_d8_size: log_d1_size + 3,
+            two_adicity: F::FftParams::TWO_ADICITY,
+        });
+    }
+    let domain =
+        EvaluationDomains::<F>::create(requested_rows).map_err(|err| DomainError::Creation {
+            requested_rows,
+            reason: err.to_string(),
+        })?;
+    log::debug!(
+        "domain of {} rows for {requested_rows} requested rows",
+        domain.d1.size
+    );
+    Ok(domain)
+}
+
+/// The number of rows padding a witness of `requested_rows` rows to the size
+/// of `domain`, created with [create_domain]
+pub fn domain_padding<F: FftField>(domain: &EvaluationDomains<F>, requested_rows: usize) -> usize {
+    (domain.d1.size as usize).saturating_sub(requested_rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        columns::Column, expr::curr_cell, lookups::LookupTableIDs, proof::ProofInputs,
+        prover::prove, verifier::verify, witness::Witness, BN254G1Affine, BaseSponge, Fp,
+        OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::UniformRand;
+    use poly_commitment::pairing_proof::PairingSRS;
+
+    #[test]
+    fn test_create_domain_pads_the_rows() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 3;
+        let rows = 100;
+        let domain = create_domain::<Fp>(rows).unwrap();
+        assert_eq!(domain.d1.size, 128);
+        assert_eq!(domain.d8.size, 1024);
+        let padding = domain_padding(&domain, rows);
+        assert_eq!(padding, 28);
+        assert_eq!(domain_padding(&create_domain::<Fp>(64).unwrap(), 64), 0);
+
+        // x[2] = x[0] * x[1], which the zero padding rows satisfy
+        let x: Vec<Fp> = (0..rows).map(|_| Fp::rand(&mut rng)).collect();
+        let y: Vec<Fp> = (0..rows).map(|_| Fp::rand(&mut rng)).collect();
+        let z = x.iter().zip(y.iter()).map(|(x, y)| *x * y).collect();
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new([x, y, z]),
+            },
+            mvlookups: vec![],
+        }
+        .pad(padding, None)
+        .unwrap();
+        assert!(inputs
+            .evaluations
+            .cols
+            .iter()
+            .all(|column| column.len() == domain.d1.size as usize));
+
+        let constraints =
+            vec![curr_cell(Column::X(2)) - curr_cell(Column::X(0)) * curr_cell(Column::X(1))];
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), 128);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness { cols: Box::new([]) },
+        ));
+    }
+
+    #[test]
+    fn test_create_domain_errors() {
+        assert_eq!(create_domain::<Fp>(0).err(), Some(DomainError::NoRows));
+
+        // The 2-adic subgroup of the scalar field of BN254 has 2^28 elements,
+        // hence d1 has at most 2^25 rows
+        assert_eq!(max_rows::<Fp>(), 1 << 25);
+        assert_eq!(create_domain::<Fp>(1 << 25).unwrap().d8.size, 1 << 28);
+        assert_eq!(
+            create_domain::<Fp>((1 << 25) + 1).err(),
+            Some(DomainError::TooLarge {
+                requested_rows: (1 << 25) + 1,
+                log_d8_size: 29,
+                two_adicity: 28,
+            })
+        );
+        // The same number of rows fits in the 2^32 subgroup of the Pasta fields
+        assert_eq!(
+            create_domain::<mina_curves::pasta::Fp>((1 << 25) + 1)
+                .unwrap()
+                .d1
+                .size,
+            1 << 26
+        );
+    }
+}
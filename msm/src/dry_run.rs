@@ -0,0 +1,230 @@
+//! A dry run of the prover, checking a witness against its circuit as fast as
+//! possible with the code of the prover: the witness, the lookup partial sums
+//! and the aggregation, and the constraints over d1 and d8 are computed exactly
+//! like in [crate::prover::prove], and fail with the same [ProverError]s.
+//! Only the commitments and the opening proof are skipped.
+//! The prover is generic over the opening proof, hence over its SRS, which is
+//! the commitment strategy of the prover: the dry run uses the
+//! [NoopCommitter], whose commitments are placeholders computed without any
+//! MSM, and the [NoopOpeningProof], which is not computed at all. The proofs
+//! of the dry run are meaningless, and are not returned.
+
+use ark_ec::AffineCurve;
+use ark_poly::{
+    univariate::DensePolynomial, EvaluationDomain, Evaluations, Radix2EvaluationDomain as D,
+};
+use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve, plonk_sponge::FrSponge};
+use mina_poseidon::FqSponge;
+use poly_commitment::{
+    commitment::{BatchEvaluationProof, BlindedCommitment, CommitmentCurve},
+    error::CommitmentError,
+    evaluation_proof::DensePolynomialOrEvaluations,
+    OpenProof, PolyComm, SRS,
+};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{
+    config::ProtocolConfig,
+    expr::E,
+    mvlookup::LookupTableID,
+    perf::{PerfCounters, PerfStats},
+    proof::{Proof, ProofInputs},
+    prover::{check_inputs, prove_with_context, ProverContext, ProverError},
+};
+
+/// An SRS whose commitments are placeholders: each chunk of a commitment is
+/// the generator of the curve. It only knows its size, which gives the number
+/// of chunks of the commitments, like the real SRS.
+#[derive(Debug, Clone)]
+pub struct NoopCommitter<G> {
+    size: usize,
+    _marker: PhantomData<G>,
+}
+
+impl<G: CommitmentCurve> NoopCommitter<G> {
+    /// A committer to the polynomials of degree less than `size`, in chunks
+    pub fn new(size: usize) -> Self {
+        NoopCommitter {
+            size,
+            _marker: PhantomData,
+        }
+    }
+
+    fn placeholder(&self, n_coeffs: usize, num_chunks: usize) -> PolyComm<G> {
+        let n_chunks = ((n_coeffs + self.size - 1) / self.size)
+            .max(num_chunks)
+            .max(1);
+        PolyComm {
+            elems: vec![G::prime_subgroup_generator(); n_chunks],
+        }
+    }
+}
+
+impl<G: CommitmentCurve> SRS<G> for NoopCommitter<G> {
+    fn max_poly_size(&self) -> usize {
+        self.size
+    }
+
+    /// The Lagrange bases are never computed, the commitments to evaluations
+    /// are placeholders too
+    fn get_lagrange_basis(&self, _domain_size: usize) -> Option<&Vec<PolyComm<G>>> {
+        None
+    }
+
+    fn blinding_commitment(&self) -> G {
+        G::prime_subgroup_generator()
+    }
+
+    fn commit(
+        &self,
+        plnm: &DensePolynomial<G::ScalarField>,
+        num_chunks: usize,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> BlindedCommitment<G> {
+        self.mask(self.commit_non_hiding(plnm, num_chunks), rng)
+    }
+
+    fn mask_custom(
+        &self,
+        com: PolyComm<G>,
+        blinders: &PolyComm<G::ScalarField>,
+    ) -> Result<BlindedCommitment<G>, CommitmentError> {
+        if com.elems.len() != blinders.elems.len() {
+            return Err(CommitmentError::BlindersDontMatch(
+                blinders.elems.len(),
+                com.elems.len(),
+            ));
+        }
+        Ok(BlindedCommitment {
+            commitment: com,
+            blinders: blinders.clone(),
+        })
+    }
+
+    fn commit_non_hiding(
+        &self,
+        plnm: &DensePolynomial<G::ScalarField>,
+        num_chunks: usize,
+    ) -> PolyComm<G> {
+        self.placeholder(plnm.coeffs.len(), num_chunks)
+    }
+
+    fn commit_evaluations_non_hiding(
+        &self,
+        domain: D<G::ScalarField>,
+        _plnm: &Evaluations<G::ScalarField, D<G::ScalarField>>,
+    ) -> PolyComm<G> {
+        self.placeholder(domain.size(), 1)
+    }
+
+    fn commit_evaluations(
+        &self,
+        domain: D<G::ScalarField>,
+        plnm: &Evaluations<G::ScalarField, D<G::ScalarField>>,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> BlindedCommitment<G> {
+        self.mask(self.commit_evaluations_non_hiding(domain, plnm), rng)
+    }
+
+    fn create(depth: usize) -> Self {
+        NoopCommitter::new(depth)
+    }
+
+    fn add_lagrange_basis(&mut self, _domain: D<G::ScalarField>) {}
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// The opening proof of a dry run, which is not computed, and never verifies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoopOpeningProof;
+
+impl<G: CommitmentCurve> OpenProof<G> for NoopOpeningProof {
+    type SRS = NoopCommitter<G>;
+
+    fn open<EFqSponge, RNG, D: EvaluationDomain<<G as AffineCurve>::ScalarField>>(
+        _srs: &Self::SRS,
+        _group_map: &<G as CommitmentCurve>::Map,
+        _plnms: &[(
+            DensePolynomialOrEvaluations<G::ScalarField, D>,
+            PolyComm<G::ScalarField>,
+        )],
+        _elm: &[<G as AffineCurve>::ScalarField],
+        _polyscale: <G as AffineCurve>::ScalarField,
+        _evalscale: <G as AffineCurve>::ScalarField,
+        _sponge: EFqSponge,
+        _rng: &mut RNG,
+    ) -> Self
+    where
+        EFqSponge:
+            Clone + FqSponge<<G as AffineCurve>::BaseField, G, <G as AffineCurve>::ScalarField>,
+        RNG: RngCore + CryptoRng,
+    {
+        NoopOpeningProof
+    }
+
+    fn verify<EFqSponge, RNG>(
+        _srs: &Self::SRS,
+        _group_map: &G::Map,
+        _batch: &mut [BatchEvaluationProof<G, EFqSponge, Self>],
+        _rng: &mut RNG,
+    ) -> bool
+    where
+        EFqSponge: FqSponge<G::BaseField, G, G::ScalarField>,
+        RNG: RngCore + CryptoRng,
+    {
+        false
+    }
+}
+
+/// The outcome of a successful dry run, see [dry_run_prove]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRunReport {
+    /// The number of partial sums of the lookup argument
+    pub n_partial_sums: usize,
+    /// The number of chunks of the quotient polynomial
+    pub quotient_chunks: usize,
+    /// The operations performed by the dry run. The commitments are counted
+    /// as [PerfStats::placeholder_commitments], and no MSM is performed.
+    pub stats: PerfStats,
+}
+
+/// Run the prover on `inputs` for `constraints`, like [crate::prover::prove],
+/// without computing the commitments and the opening proof. Returns the
+/// errors the prover returns for the same inputs, including the constraints
+/// which are not satisfied.
+pub fn dry_run_prove<
+    G: KimchiCurve,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    inputs: ProofInputs<N, G, ID>,
+    constraints: &[E<G::ScalarField>],
+    domain: EvaluationDomains<G::ScalarField>,
+) -> Result<DryRunReport, ProverError> {
+    let srs = NoopCommitter::<G>::new(domain.d1.size as usize);
+    let perf = Arc::new(PerfCounters::with_placeholder_commitments());
+    check_inputs(constraints, &inputs, domain)?;
+    let ctx = ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), constraints)?
+        .with_perf_counters(perf.clone());
+    // The blinders do not matter, the commitments are not hiding anything
+    let mut rng = rand::rngs::StdRng::from_seed([0; 32]);
+    let proof: Proof<N, G, NoopOpeningProof, ID> =
+        prove_with_context::<G, NoopOpeningProof, EFqSponge, EFrSponge, _, N, ID, _>(
+            &ctx, inputs, &mut rng,
+        )?;
+    Ok(DryRunReport {
+        n_partial_sums: proof
+            .proof_comms
+            .mvlookup_comms
+            .as_ref()
+            .map_or(0, |lookup| lookup.h.len()),
+        quotient_chunks: proof.proof_comms.t_comm.elems.len(),
+        stats: perf.stats(),
+    })
+}
@@ -0,0 +1,155 @@
+//! Randomized check that two sets of constraints are the same polynomial
+//! identities, up to their order, e.g. before and after refactoring a gadget.
+//!
+//! The constraints are evaluated on random values of the cells they read, at
+//! both rows, and of the challenges. Two constraints are matched when they
+//! have the same value in each trial. A constraint differing from all the
+//! constraints of the other set is only matched with probability at most
+//! `(d / |F|)^trials`, `d` being the degree of the difference.
+
+use ark_ff::Field;
+use kimchi::circuits::{expr::Challenges, gate::CurrOrNext};
+use mina_poseidon::constants::SpongeConstants;
+use rand::RngCore;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    columns::Column,
+    expr::{column_accesses, evaluate_on_assignment, Assignment, E},
+    SpongeParams,
+};
+
+/// The outcome of [constraints_equivalent]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EquivalenceReport {
+    /// The indices of the constraints of the first set without a counterpart
+    /// in the second one
+    pub unmatched_a: Vec<usize>,
+    /// The indices of the constraints of the second set without a
+    /// counterpart in the first one
+    pub unmatched_b: Vec<usize>,
+}
+
+impl EquivalenceReport {
+    pub fn is_equivalent(&self) -> bool {
+        self.unmatched_a.is_empty() && self.unmatched_b.is_empty()
+    }
+}
+
+/// Returns the constraints of `a` and `b` which are not matched by a
+/// constraint of the other set, after `trials` random evaluations. The
+/// constraints are matched as multisets: a constraint given twice in `a` must
+/// be given twice in `b`. The MDS matrix is the one of [SpongeParams].
+/// The constraints which can not be evaluated on a single row, see
+/// [evaluate_on_assignment], are never matched.
+pub fn constraints_equivalent<F: Field, RNG: RngCore>(
+    a: &[E<F>],
+    b: &[E<F>],
+    trials: usize,
+    rng: &mut RNG,
+) -> EquivalenceReport {
+    let cells: BTreeSet<(Column, CurrOrNext)> =
+        a.iter().chain(b).flat_map(column_accesses).collect();
+    let assignments: Vec<Assignment<F>> = (0..trials)
+        .map(|_| Assignment {
+            cells: cells.iter().map(|cell| (*cell, F::rand(rng))).collect(),
+            challenges: Challenges {
+                alpha: F::rand(rng),
+                beta: F::rand(rng),
+                gamma: F::rand(rng),
+                joint_combiner: Some(F::rand(rng)),
+            },
+            endo_coefficient: F::rand(rng),
+            mds: (0..SpongeParams::SPONGE_WIDTH)
+                .map(|_| {
+                    (0..SpongeParams::SPONGE_WIDTH)
+                        .map(|_| F::rand(rng))
+                        .collect()
+                })
+                .collect(),
+        })
+        .collect();
+    let evaluate = |constraint: &E<F>| -> Option<Vec<F>> {
+        assignments
+            .iter()
+            .map(|assignment| evaluate_on_assignment(constraint, assignment).ok())
+            .collect()
+    };
+
+    // The constraints of b, by evaluations
+    let mut b_by_evals: BTreeMap<Vec<F>, Vec<usize>> = BTreeMap::new();
+    let mut unmatched_b = vec![];
+    for (i, constraint) in b.iter().enumerate() {
+        match evaluate(constraint) {
+            Some(evals) => b_by_evals.entry(evals).or_default().push(i),
+            None => unmatched_b.push(i),
+        }
+    }
+    let mut unmatched_a = vec![];
+    for (i, constraint) in a.iter().enumerate() {
+        let matched = evaluate(constraint)
+            .and_then(|evals| b_by_evals.get_mut(&evals))
+            .and_then(|indices| indices.pop());
+        if matched.is_none() {
+            unmatched_a.push(i);
+        }
+    }
+    unmatched_b.extend(b_by_evals.into_values().flatten());
+    unmatched_b.sort_unstable();
+    EquivalenceReport {
+        unmatched_a,
+        unmatched_b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::constraints_equivalent;
+    use crate::{
+        columns::Column,
+        expr::{curr_cell, next_cell, E},
+        Fp,
+    };
+    use ark_ff::One;
+    use kimchi::circuits::expr::{ChallengeTerm, ConstantExpr, ConstantTerm, ExprInner};
+
+    fn constraints(coefficient: u64) -> Vec<E<Fp>> {
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let alpha = E::Atom(ExprInner::Constant(ConstantExpr::from(
+            ChallengeTerm::Alpha,
+        )));
+        vec![
+            x(0) * x(1) - x(2),
+            x(0) + x(0) - next_cell(Column::X(1)),
+            E::from(ConstantTerm::Literal(Fp::from(coefficient))) * x(3) - E::one(),
+            alpha * x(2) * x(2),
+        ]
+    }
+
+    #[test]
+    fn test_constraints_equivalent() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let a = constraints(3);
+
+        // The same constraints, in another order, and written differently
+        let mut b = constraints(3);
+        b.reverse();
+        b[2] = curr_cell::<Fp>(Column::X(0)).double() - next_cell(Column::X(1));
+        let report = constraints_equivalent(&a, &b, 4, &mut rng);
+        assert!(report.is_equivalent(), "{report:?}");
+
+        // Changing one coefficient
+        let b = constraints(5);
+        let report = constraints_equivalent(&a, &b, 4, &mut rng);
+        assert!(!report.is_equivalent());
+        assert_eq!(report.unmatched_a, vec![2]);
+        assert_eq!(report.unmatched_b, vec![2]);
+
+        // The constraints are matched as multisets
+        let mut b = a.clone();
+        b.push(a[0].clone());
+        let report = constraints_equivalent(&a, &b, 4, &mut rng);
+        assert_eq!(report.unmatched_a, Vec::<usize>::new());
+        assert_eq!(report.unmatched_b.len(), 1);
+    }
+}
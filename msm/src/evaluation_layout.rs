@@ -0,0 +1,246 @@
+//! The storage of the evaluations of a proof read by the verifier when it
+//! evaluates the constraints at ζ, see [EvaluationLayout].
+//!
+//! The evaluations of the proof are stored per column, in the witness and in
+//! the [crate::mvlookup::LookupProof], and each read of a column by the
+//! constraints looks the column up in the proof. With
+//! [EvaluationLayout::Flat], the evaluations are copied once in two vectors,
+//! the evaluations at ζ and the evaluations at ζω, indexed by the dense index
+//! of the columns: the witness columns first, then the lookup columns in the
+//! order of the proof. The columns read by several constraints are then read
+//! from contiguous memory.
+//! The evaluations returned by [kimchi::circuits::expr::ColumnEvaluations] do
+//! not depend on the layout.
+
+use kimchi::proof::PointEvaluations;
+use std::collections::BTreeMap;
+
+use crate::{
+    columns::{Column, PartialSumIdx, TableIdx},
+    lookup_engine::PlookupColumn,
+    mvlookup::LookupTableID,
+    perf::PerfCounters,
+    proof::{EvaluationContainer, ProofEvaluations},
+};
+
+/// The layout of the evaluations read by the verifier, see the [module
+/// documentation](self). The outcome of the verification is the same with
+/// both layouts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvaluationLayout {
+    /// Look the evaluations of each column up in the proof, at each read
+    #[default]
+    PerColumn,
+    /// Copy the evaluations of the proof in two flat vectors before
+    /// evaluating the constraints
+    Flat,
+}
+
+/// The evaluations of a proof in the [EvaluationLayout::Flat] layout.
+pub(crate) struct FlatEvaluations<F> {
+    zeta: Vec<F>,
+    zeta_omega: Vec<F>,
+    n_witness: usize,
+    /// The dense index of the lookup columns of the proof
+    lookup_columns: BTreeMap<Column, usize>,
+}
+
+impl<F: Copy> FlatEvaluations<F> {
+    /// Copy the evaluations of `evals`. Each column is looked up once, and
+    /// counted in [crate::perf::PerfStats::evaluation_lookups].
+    pub(crate) fn new<const N: usize, ID: LookupTableID, Eval: EvaluationContainer<F>>(
+        evals: &ProofEvaluations<N, F, ID, Eval>,
+        perf: &PerfCounters,
+    ) -> Self {
+        let mut columns: Vec<(Option<Column>, &Eval)> =
+            evals.witness_evals.cols.iter().map(|e| (None, e)).collect();
+        if let Some(lookup) = &evals.mvlookup_evals {
+            let table = |id: &ID| TableIdx::new(id.to_u32());
+            columns.extend(
+                lookup
+                    .m
+                    .iter()
+                    .map(|(id, e)| (Some(Column::LookupMultiplicity(table(id))), e)),
+            );
+            columns.extend(
+                lookup
+                    .h
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (Some(Column::LookupPartialSum(PartialSumIdx::new(i))), e)),
+            );
+            columns.extend(
+                lookup
+                    .sum
+                    .committed()
+                    .map(|e| (Some(Column::LookupAggregation), e)),
+            );
+            columns.extend(
+                lookup
+                    .fixed_tables
+                    .iter()
+                    .map(|(id, e)| (Some(Column::LookupFixedTable(table(id))), e)),
+            );
+            columns.extend(lookup.plookup.iter().flat_map(|(id, evals)| {
+                PlookupColumn::ALL.into_iter().map(move |column| {
+                    (Some(Column::Plookup(table(id), column)), evals.get(column))
+                })
+            }));
+        }
+        perf.record_evaluation_lookups(columns.len());
+
+        let mut zeta = Vec::with_capacity(columns.len());
+        let mut zeta_omega = Vec::with_capacity(columns.len());
+        let mut lookup_columns = BTreeMap::new();
+        for (index, (column, e)) in columns.into_iter().enumerate() {
+            let e = e.to_point_evaluations();
+            zeta.push(e.zeta);
+            zeta_omega.push(e.zeta_omega);
+            if let Some(column) = column {
+                lookup_columns.insert(column, index);
+            }
+        }
+        FlatEvaluations {
+            zeta,
+            zeta_omega,
+            n_witness: N,
+            lookup_columns,
+        }
+    }
+
+    /// The evaluations of `col`, `None` if the column is not part of the
+    /// proof
+    pub(crate) fn get(&self, col: Column) -> Option<PointEvaluations<F>> {
+        let index = match col {
+            Column::X(i) if i < self.n_witness => i,
+            _ => *self.lookup_columns.get(&col)?,
+        };
+        Some(PointEvaluations {
+            zeta: self.zeta[index],
+            zeta_omega: self.zeta_omega[index],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "perf-counters")]
+    use crate::perf::PerfStats;
+    use crate::{
+        config::ProtocolConfig,
+        lookups::LookupTableIDs,
+        prover::prove,
+        test::random_lookup_circuit,
+        verifier::{VerifierContext, VerifierError},
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand};
+    use kimchi::circuits::{domains::EvaluationDomains, expr::ColumnEvaluations};
+    use poly_commitment::pairing_proof::PairingSRS;
+
+    const N: usize = 6;
+
+    type Ctx<'a> = VerifierContext<'a, BN254G1Affine, OpeningProof, LookupTableIDs>;
+    type TestProof = crate::proof::Proof<N, BN254G1Affine, OpeningProof, LookupTableIDs>;
+
+    fn verify(ctx: &Ctx, proof: &TestProof, perf: &PerfCounters) -> Result<(), VerifierError> {
+        ctx.verify_with_perf_counters::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            proof,
+            Witness::zero_vec(1),
+            perf,
+        )
+    }
+
+    #[test]
+    fn test_evaluation_layouts() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let mut srs: PairingSRS<BN254> =
+            PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 8, 4, &mut rng);
+        let proof: TestProof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+
+        // The flat layout returns the evaluations of the proof for each of
+        // its columns
+        let flat_evals = FlatEvaluations::new(&proof.proof_evals, &PerfCounters::default());
+        let lookup_evals = proof.proof_evals.mvlookup_evals.as_ref().unwrap();
+        let n_lookup_columns = lookup_evals.into_iter().count();
+        assert_eq!(flat_evals.zeta.len(), N + n_lookup_columns);
+        let columns = (0..N)
+            .map(Column::X)
+            .chain(flat_evals.lookup_columns.keys().copied());
+        for col in columns {
+            assert_eq!(
+                flat_evals.get(col),
+                Some(proof.proof_evals.evaluate(col).unwrap())
+            );
+        }
+        assert_eq!(flat_evals.get(Column::X(N)), None);
+        assert_eq!(
+            flat_evals.get(Column::LookupFixedTable(TableIdx::new(2))),
+            None
+        );
+
+        let contexts = [EvaluationLayout::PerColumn, EvaluationLayout::Flat].map(|layout| {
+            Ctx::new(domain, &srs, ProtocolConfig::default(), &constraints)
+                .unwrap()
+                .with_evaluation_layout(layout)
+        });
+        let outcomes = |proof: &TestProof| {
+            [&contexts[0], &contexts[1]].map(|ctx| verify(ctx, proof, &PerfCounters::default()))
+        };
+
+        // The outcomes of the verification are the same with both layouts,
+        // for a valid proof, for a wrong evaluation, and for a missing table
+        let [per_column, flat] = outcomes(&proof);
+        assert_eq!(per_column, Ok(()));
+        assert_eq!(flat, Ok(()));
+
+        let mut tampered = proof.clone();
+        tampered.proof_evals.witness_evals.cols[N - 1].zeta += Fp::one();
+        let [per_column, flat] = outcomes(&tampered);
+        assert!(per_column.is_err());
+        assert_eq!(per_column, flat);
+
+        let mut tampered = proof.clone();
+        let lookup_evals = tampered.proof_evals.mvlookup_evals.as_mut().unwrap();
+        lookup_evals.fixed_tables.clear();
+        let [per_column, flat] = outcomes(&tampered);
+        assert!(per_column.is_err());
+        assert_eq!(per_column, flat);
+
+        // The flat layout looks each column up once, instead of once per read
+        #[cfg(feature = "perf-counters")]
+        {
+            let [per_column, flat] = [&contexts[0], &contexts[1]].map(|ctx| {
+                let perf = PerfCounters::default();
+                verify(ctx, &proof, &perf).unwrap();
+                perf.stats()
+            });
+            assert_eq!(flat.evaluation_lookups, N + n_lookup_columns);
+            assert!(per_column.evaluation_lookups > flat.evaluation_lookups);
+            assert_eq!(
+                PerfStats {
+                    evaluation_lookups: 0,
+                    ..per_column
+                },
+                PerfStats {
+                    evaluation_lookups: 0,
+                    ..flat
+                }
+            );
+        }
+    }
+}
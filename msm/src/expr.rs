@@ -4,11 +4,18 @@
 
 use ark_ff::Field;
 use kimchi::circuits::{
-    expr::{ConstantExpr, Expr, ExprInner, Variable},
+    expr::{
+        ChallengeTerm, Challenges, ConstantExpr, ConstantExprInner, ConstantTerm, Expr, ExprInner,
+        FeatureFlag, Operations, RowOffset, Variable,
+    },
     gate::CurrOrNext,
 };
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::serde_as;
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
 
-use crate::columns::Column;
+use crate::columns::{Column, ColumnDef};
 
 /// An expression over /generic/ (not circuit-specific) columns
 /// defined in the msm project. To represent constraints as multi
@@ -21,8 +28,7 @@ use crate::columns::Column;
 /// ```
 /// use kimchi::circuits::expr::{ConstantExprInner, ExprInner, Operations, Variable};
 /// use kimchi::circuits::gate::CurrOrNext;
-/// use kimchi_msm::columns::Column;
-/// use kimchi_msm::expr::E;
+/// use kimchi_msm::api::{Column, E};
 /// pub type Fp = ark_bn254::Fr;
 /// let x1 = E::<Fp>::Atom(
 ///     ExprInner::<Operations<ConstantExprInner<Fp>>, Column>::Cell(Variable {
@@ -62,6 +68,483 @@ pub fn next_cell<F: Field>(col: Column) -> E<F> {
     }))
 }
 
+/// The witness column `col` two rows after the current row, see
+/// [crate::shifts]. Only the witness columns can be shifted by two rows.
+pub fn shift2_cell<F: Field>(col: Column) -> E<F> {
+    match col {
+        Column::X(i) => curr_cell(Column::XShift2(i)),
+        col => panic!("Only the witness columns can be shifted by two rows, not {col:?}"),
+    }
+}
+
+/// A constraint with a name, to refer to it in reports and error messages
+#[derive(Debug, Clone)]
+pub struct NamedConstraint<F> {
+    pub name: String,
+    pub constraint: E<F>,
+}
+
+impl<F> NamedConstraint<F> {
+    pub fn new(name: impl Into<String>, constraint: E<F>) -> Self {
+        NamedConstraint {
+            name: name.into(),
+            constraint,
+        }
+    }
+
+    /// Name the constraints after their index
+    pub fn from_indexed(constraints: &[E<F>]) -> Vec<Self>
+    where
+        F: Clone,
+    {
+        constraints
+            .iter()
+            .enumerate()
+            .map(|(i, constraint)| {
+                NamedConstraint::new(format!("constraint_{i}"), constraint.clone())
+            })
+            .collect()
+    }
+}
+
+/// Returns the columns read by `expr`, together with the row they are read at
+pub fn column_accesses<F>(expr: &E<F>) -> BTreeSet<(Column, CurrOrNext)> {
+    fn collect<F>(expr: &E<F>, acc: &mut BTreeSet<(Column, CurrOrNext)>) {
+        match expr {
+            Operations::Atom(ExprInner::Cell(Variable { col, row })) => {
+                acc.insert((*col, *row));
+            }
+            Operations::Atom(_) => (),
+            Operations::Pow(x, _)
+            | Operations::Double(x)
+            | Operations::Square(x)
+            | Operations::Cache(_, x) => collect(x, acc),
+            Operations::Add(x, y)
+            | Operations::Mul(x, y)
+            | Operations::Sub(x, y)
+            | Operations::IfFeature(_, x, y) => {
+                collect(x, acc);
+                collect(y, acc);
+            }
+        }
+    }
+    let mut acc = BTreeSet::new();
+    collect(expr, &mut acc);
+    acc
+}
+
+/// Whether `expr` refers to the challenge `term`
+pub fn refers_to_challenge<F>(expr: &E<F>, term: ChallengeTerm) -> bool {
+    fn in_constant<F>(c: &ConstantExpr<F>, term: ChallengeTerm) -> bool {
+        match c {
+            Operations::Atom(ConstantExprInner::Challenge(t)) => *t == term,
+            Operations::Atom(ConstantExprInner::Constant(_)) => false,
+            Operations::Pow(x, _)
+            | Operations::Double(x)
+            | Operations::Square(x)
+            | Operations::Cache(_, x) => in_constant(x, term),
+            Operations::Add(x, y)
+            | Operations::Mul(x, y)
+            | Operations::Sub(x, y)
+            | Operations::IfFeature(_, x, y) => in_constant(x, term) || in_constant(y, term),
+        }
+    }
+    match expr {
+        Operations::Atom(ExprInner::Constant(c)) => in_constant(c, term),
+        Operations::Atom(_) => false,
+        Operations::Pow(x, _)
+        | Operations::Double(x)
+        | Operations::Square(x)
+        | Operations::Cache(_, x) => refers_to_challenge(x, term),
+        Operations::Add(x, y)
+        | Operations::Mul(x, y)
+        | Operations::Sub(x, y)
+        | Operations::IfFeature(_, x, y) => {
+            refers_to_challenge(x, term) || refers_to_challenge(y, term)
+        }
+    }
+}
+
+/// An operation of an [ExprToken], applied to the operands on top of the
+/// stack of the decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OpToken {
+    Add,
+    Sub,
+    Mul,
+    Double,
+    Square,
+    Pow(u64),
+}
+
+/// A token of the canonical encoding of an expression, in reverse Polish
+/// notation, see [encode_expr]. Unlike [kimchi::circuits::expr::PolishToken],
+/// the operations on constants are distinguished from the ones on cells, for
+/// the expression to be decoded exactly as it was, see [decode_expr].
+/// Like the columns, the tokens are only deserialized by the crate, see
+/// [deserialize_expr_tokens].
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize")]
+pub enum ExprToken<F> {
+    Literal(#[serde_as(as = "o1_utils::serialization::SerdeAs")] F),
+    EndoCoefficient,
+    Mds {
+        row: usize,
+        col: usize,
+    },
+    Challenge(ChallengeTerm),
+    /// An operation on the constants on top of the stack, giving a constant
+    ConstantOp(OpToken),
+    Cell(Variable<Column>),
+    VanishesOnZeroKnowledgeAndPreviousRows,
+    UnnormalizedLagrangeBasis(RowOffset),
+    /// An operation on the expressions on top of the stack
+    Op(OpToken),
+}
+
+/// The deserialization of an [ExprToken], see [ColumnDef]
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(remote = "ExprToken")]
+#[serde(bound = "F: ark_serialize::CanonicalDeserialize")]
+enum ExprTokenDef<F> {
+    Literal(#[serde_as(as = "o1_utils::serialization::SerdeAs")] F),
+    EndoCoefficient,
+    Mds { row: usize, col: usize },
+    Challenge(ChallengeTerm),
+    ConstantOp(OpToken),
+    Cell(#[serde(deserialize_with = "deserialize_cell")] Variable<Column>),
+    VanishesOnZeroKnowledgeAndPreviousRows,
+    UnnormalizedLagrangeBasis(RowOffset),
+    Op(OpToken),
+}
+
+fn deserialize_cell<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Variable<Column>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(rename = "Variable")]
+    struct Cell {
+        #[serde(with = "ColumnDef")]
+        col: Column,
+        row: CurrOrNext,
+    }
+    let Cell { col, row } = Cell::deserialize(deserializer)?;
+    Ok(Variable { col, row })
+}
+
+/// Deserialize the tokens of expressions serialized with [ExprToken]. The
+/// indices of the lookup columns read by the decoded expressions, see
+/// [decode_expr], are not checked.
+pub(crate) fn deserialize_expr_tokens<'de, D, F>(
+    deserializer: D,
+) -> Result<Vec<Vec<ExprToken<F>>>, D::Error>
+where
+    D: Deserializer<'de>,
+    F: ark_serialize::CanonicalDeserialize,
+{
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    #[serde(bound = "F: ark_serialize::CanonicalDeserialize")]
+    struct Token<F>(#[serde(with = "ExprTokenDef")] ExprToken<F>);
+    let tokens = Vec::<Vec<Token<F>>>::deserialize(deserializer)?;
+    Ok(tokens
+        .into_iter()
+        .map(|tokens| tokens.into_iter().map(|Token(token)| token).collect())
+        .collect())
+}
+
+/// Errors that can arise when encoding or decoding an expression, see
+/// [encode_expr] and [decode_expr]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ExprCodecError {
+    #[error("the expressions with {0} can not be encoded")]
+    Unsupported(&'static str),
+    #[error("the token {0} has missing operands")]
+    MissingOperand(usize),
+    #[error("the token {0} applies a constant operation to a non-constant expression")]
+    NotAConstant(usize),
+    #[error("the tokens encode {0} expressions instead of one")]
+    TrailingExpressions(usize),
+}
+
+fn encode_operations<T, F>(
+    op: &Operations<T>,
+    atom: &impl Fn(&T, &mut Vec<ExprToken<F>>) -> Result<(), ExprCodecError>,
+    wrap: fn(OpToken) -> ExprToken<F>,
+    res: &mut Vec<ExprToken<F>>,
+) -> Result<(), ExprCodecError> {
+    let mut binary = |x, y, op| {
+        encode_operations(x, atom, wrap, res)?;
+        encode_operations(y, atom, wrap, res)?;
+        res.push(wrap(op));
+        Ok(())
+    };
+    match op {
+        Operations::Atom(x) => atom(x, res),
+        Operations::Add(x, y) => binary(x, y, OpToken::Add),
+        Operations::Sub(x, y) => binary(x, y, OpToken::Sub),
+        Operations::Mul(x, y) => binary(x, y, OpToken::Mul),
+        Operations::Double(x) => {
+            encode_operations(x, atom, wrap, res)?;
+            res.push(wrap(OpToken::Double));
+            Ok(())
+        }
+        Operations::Square(x) => {
+            encode_operations(x, atom, wrap, res)?;
+            res.push(wrap(OpToken::Square));
+            Ok(())
+        }
+        Operations::Pow(x, n) => {
+            encode_operations(x, atom, wrap, res)?;
+            res.push(wrap(OpToken::Pow(*n)));
+            Ok(())
+        }
+        // The identifiers of the cache can not be built outside of kimchi
+        Operations::Cache(_, _) => Err(ExprCodecError::Unsupported("cached subexpressions")),
+        Operations::IfFeature(_, _, _) => Err(ExprCodecError::Unsupported("features")),
+    }
+}
+
+/// Encode `expr` canonically, as a list of tokens in reverse Polish notation.
+/// The expressions using the cache or the features of kimchi are not
+/// supported.
+pub fn encode_expr<F: Clone>(expr: &E<F>) -> Result<Vec<ExprToken<F>>, ExprCodecError> {
+    let constant = |c: &ConstantExprInner<F>, res: &mut Vec<ExprToken<F>>| {
+        res.push(match c {
+            ConstantExprInner::Challenge(term) => ExprToken::Challenge(*term),
+            ConstantExprInner::Constant(ConstantTerm::Literal(x)) => ExprToken::Literal(x.clone()),
+            ConstantExprInner::Constant(ConstantTerm::EndoCoefficient) => {
+                ExprToken::EndoCoefficient
+            }
+            ConstantExprInner::Constant(ConstantTerm::Mds { row, col }) => ExprToken::Mds {
+                row: *row,
+                col: *col,
+            },
+        });
+        Ok(())
+    };
+    let atom = |x: &ExprInner<ConstantExpr<F>, Column>, res: &mut Vec<ExprToken<F>>| match x {
+        ExprInner::Constant(c) => encode_operations(c, &constant, ExprToken::ConstantOp, res),
+        ExprInner::Cell(v) => {
+            res.push(ExprToken::Cell(*v));
+            Ok(())
+        }
+        ExprInner::VanishesOnZeroKnowledgeAndPreviousRows => {
+            res.push(ExprToken::VanishesOnZeroKnowledgeAndPreviousRows);
+            Ok(())
+        }
+        ExprInner::UnnormalizedLagrangeBasis(offset) => {
+            res.push(ExprToken::UnnormalizedLagrangeBasis(*offset));
+            Ok(())
+        }
+    };
+    let mut res = vec![];
+    encode_operations(expr, &atom, ExprToken::Op, &mut res)?;
+    Ok(res)
+}
+
+/// An operand on the stack of the decoder of the expressions
+enum Operand<F> {
+    Constant(ConstantExpr<F>),
+    Expr(E<F>),
+}
+
+impl<F> Operand<F> {
+    fn into_expr(self) -> E<F> {
+        match self {
+            Operand::Constant(c) => E::Atom(ExprInner::Constant(c)),
+            Operand::Expr(e) => e,
+        }
+    }
+}
+
+/// Apply `op` to the operands on top of `stack`, the last one being the
+/// second operand of the binary operations
+fn apply_op<T>(
+    stack: &mut Vec<Operations<T>>,
+    op: OpToken,
+    i: usize,
+) -> Result<Operations<T>, ExprCodecError> {
+    let mut pop = || stack.pop().ok_or(ExprCodecError::MissingOperand(i));
+    let res = match op {
+        OpToken::Add | OpToken::Sub | OpToken::Mul => {
+            let y = Box::new(pop()?);
+            let x = Box::new(pop()?);
+            match op {
+                OpToken::Add => Operations::Add(x, y),
+                OpToken::Sub => Operations::Sub(x, y),
+                _ => Operations::Mul(x, y),
+            }
+        }
+        OpToken::Double => Operations::Double(Box::new(pop()?)),
+        OpToken::Square => Operations::Square(Box::new(pop()?)),
+        OpToken::Pow(n) => Operations::Pow(Box::new(pop()?), n),
+    };
+    Ok(res)
+}
+
+/// Decode an expression encoded by [encode_expr]
+pub fn decode_expr<F>(tokens: Vec<ExprToken<F>>) -> Result<E<F>, ExprCodecError> {
+    let mut stack: Vec<Operand<F>> = vec![];
+    let constant = |c: ConstantExprInner<F>| Operand::Constant(Operations::Atom(c));
+    let atom = |x: ExprInner<ConstantExpr<F>, Column>| Operand::Expr(E::Atom(x));
+    for (i, token) in tokens.into_iter().enumerate() {
+        let operand = match token {
+            ExprToken::Literal(x) => {
+                constant(ConstantExprInner::Constant(ConstantTerm::Literal(x)))
+            }
+            ExprToken::EndoCoefficient => {
+                constant(ConstantExprInner::Constant(ConstantTerm::EndoCoefficient))
+            }
+            ExprToken::Mds { row, col } => {
+                constant(ConstantExprInner::Constant(ConstantTerm::Mds { row, col }))
+            }
+            ExprToken::Challenge(term) => constant(ConstantExprInner::Challenge(term)),
+            ExprToken::Cell(v) => atom(ExprInner::Cell(v)),
+            ExprToken::VanishesOnZeroKnowledgeAndPreviousRows => {
+                atom(ExprInner::VanishesOnZeroKnowledgeAndPreviousRows)
+            }
+            ExprToken::UnnormalizedLagrangeBasis(offset) => {
+                atom(ExprInner::UnnormalizedLagrangeBasis(offset))
+            }
+            ExprToken::ConstantOp(op) => {
+                let n_operands = match op {
+                    OpToken::Add | OpToken::Sub | OpToken::Mul => 2,
+                    _ => 1,
+                };
+                if stack.len() < n_operands {
+                    return Err(ExprCodecError::MissingOperand(i));
+                }
+                let mut operands = stack
+                    .split_off(stack.len() - n_operands)
+                    .into_iter()
+                    .map(|operand| match operand {
+                        Operand::Constant(c) => Ok(c),
+                        Operand::Expr(_) => Err(ExprCodecError::NotAConstant(i)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Operand::Constant(apply_op(&mut operands, op, i)?)
+            }
+            ExprToken::Op(op) => {
+                let n_operands = match op {
+                    OpToken::Add | OpToken::Sub | OpToken::Mul => 2,
+                    _ => 1,
+                };
+                if stack.len() < n_operands {
+                    return Err(ExprCodecError::MissingOperand(i));
+                }
+                let mut operands: Vec<E<F>> = stack
+                    .split_off(stack.len() - n_operands)
+                    .into_iter()
+                    .map(Operand::into_expr)
+                    .collect();
+                Operand::Expr(apply_op(&mut operands, op, i)?)
+            }
+        };
+        stack.push(operand);
+    }
+    if stack.len() != 1 {
+        return Err(ExprCodecError::TrailingExpressions(stack.len()));
+    }
+    Ok(stack.pop().unwrap().into_expr())
+}
+
+/// Concrete values of the cells and of the constants of the expressions, to
+/// evaluate them on a single row with [evaluate_on_assignment].
+pub struct Assignment<F> {
+    /// The values of the cells, at the current and at the next row
+    pub cells: BTreeMap<(Column, CurrOrNext), F>,
+    pub challenges: Challenges<F>,
+    pub endo_coefficient: F,
+    pub mds: Vec<Vec<F>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AssignmentError {
+    #[error("the cell {0:?} at the {1:?} row is not assigned")]
+    MissingCell(Column, CurrOrNext),
+    #[error("the challenge {0:?} is not assigned")]
+    MissingChallenge(ChallengeTerm),
+    #[error("the MDS matrix has no entry ({row}, {col})")]
+    MissingMds { row: usize, col: usize },
+    #[error("the selector {0} can not be evaluated on a single row")]
+    UnsupportedSelector(&'static str),
+    #[error("the feature {0:?} is not supported")]
+    UnsupportedFeature(FeatureFlag),
+}
+
+/// Evaluate `op` bottom-up, with the atoms evaluated by `atom`. The features
+/// are not supported, like in the prover.
+fn fold_operations<T, F: Field>(
+    op: &Operations<T>,
+    atom: &impl Fn(&T) -> Result<F, AssignmentError>,
+) -> Result<F, AssignmentError> {
+    let rec = |x: &Operations<T>| fold_operations(x, atom);
+    match op {
+        Operations::Atom(x) => atom(x),
+        Operations::Pow(x, n) => Ok(rec(x)?.pow([*n])),
+        Operations::Add(x, y) => Ok(rec(x)? + rec(y)?),
+        Operations::Mul(x, y) => Ok(rec(x)? * rec(y)?),
+        Operations::Sub(x, y) => Ok(rec(x)? - rec(y)?),
+        Operations::Double(x) => Ok(rec(x)?.double()),
+        Operations::Square(x) => Ok(rec(x)?.square()),
+        Operations::Cache(_, x) => rec(x),
+        Operations::IfFeature(feature, _, _) => Err(AssignmentError::UnsupportedFeature(*feature)),
+    }
+}
+
+/// Returns the value of `expr` for the concrete values of `assignment`. The
+/// selectors defined over the whole domain, like the Lagrange basis, have no
+/// value on a single row.
+pub fn evaluate_on_assignment<F: Field>(
+    expr: &E<F>,
+    assignment: &Assignment<F>,
+) -> Result<F, AssignmentError> {
+    let challenges = &assignment.challenges;
+    let constant = |c: &ConstantExprInner<F>| match c {
+        ConstantExprInner::Challenge(term) => match term {
+            ChallengeTerm::Alpha => Ok(challenges.alpha),
+            ChallengeTerm::Beta => Ok(challenges.beta),
+            ChallengeTerm::Gamma => Ok(challenges.gamma),
+            ChallengeTerm::JointCombiner => challenges
+                .joint_combiner
+                .ok_or(AssignmentError::MissingChallenge(*term)),
+        },
+        ConstantExprInner::Constant(ConstantTerm::Literal(x)) => Ok(*x),
+        ConstantExprInner::Constant(ConstantTerm::EndoCoefficient) => {
+            Ok(assignment.endo_coefficient)
+        }
+        ConstantExprInner::Constant(ConstantTerm::Mds { row, col }) => assignment
+            .mds
+            .get(*row)
+            .and_then(|mds_row| mds_row.get(*col))
+            .copied()
+            .ok_or(AssignmentError::MissingMds {
+                row: *row,
+                col: *col,
+            }),
+    };
+    fold_operations(
+        expr,
+        &|atom: &ExprInner<ConstantExpr<F>, Column>| match atom {
+            ExprInner::Constant(c) => fold_operations(c, &constant),
+            ExprInner::Cell(Variable { col, row }) => assignment
+                .cells
+                .get(&(*col, *row))
+                .copied()
+                .ok_or(AssignmentError::MissingCell(*col, *row)),
+            ExprInner::VanishesOnZeroKnowledgeAndPreviousRows => Err(
+                AssignmentError::UnsupportedSelector("VanishesOnZeroKnowledgeAndPreviousRows"),
+            ),
+            ExprInner::UnnormalizedLagrangeBasis(_) => Err(AssignmentError::UnsupportedSelector(
+                "UnnormalizedLagrangeBasis",
+            )),
+        },
+    )
+}
+
 #[test]
 fn test_debug_can_be_called_on_expr() {
     use crate::{columns::Column::*, Fp};
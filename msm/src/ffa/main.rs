@@ -1,21 +1,16 @@
 use ark_ff::UniformRand;
 
-use kimchi::circuits::domains::EvaluationDomains;
 use poly_commitment::pairing_proof::PairingSRS;
 
-use kimchi_msm::{
-    columns::Column,
+use kimchi_msm::api::{
+    create_domain,
     ffa::{
-        columns::{FFA_NPUB_COLUMNS, FFA_N_COLUMNS},
-        constraint::ConstraintBuilderEnv as FFAConstraintBuilderEnv,
-        interpreter::{self as ffa_interpreter, FFAInterpreterEnv},
-        witness::WitnessBuilderEnv as FFAWitnessBuilderEnv,
+        self as ffa_interpreter, ConstraintBuilderEnv as FFAConstraintBuilderEnv,
+        FFAInterpreterEnv, WitnessBuilderEnv as FFAWitnessBuilderEnv, FFA_NPUB_COLUMNS,
+        FFA_N_COLUMNS,
     },
-    lookups::LookupTableIDs,
-    precomputed_srs::get_bn254_srs,
-    prover::prove,
-    verifier::verify,
-    BaseSponge, Ff1, Fp, OpeningProof, ScalarSponge, BN254,
+    get_bn254_srs, prove, verify, BaseSponge, Column, Ff1, Fp, LookupTableIDs, OpeningProof,
+    ScalarSponge, BN254,
 };
 
 pub fn main() {
@@ -24,7 +19,7 @@ pub fn main() {
 
     println!("Creating the domain and SRS");
     let domain_size = 1 << 8;
-    let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+    let domain = create_domain::<Fp>(domain_size).unwrap();
 
     let srs: PairingSRS<BN254> = get_bn254_srs(domain);
 
@@ -7,13 +7,25 @@ pub mod witness;
 mod tests {
 
     use crate::{
+        columns::{Column, ColumnIndexer},
+        config::{ConfigError, OutputCell, ProtocolConfig},
         ffa::{
+            columns::{FFAColumnIndexer, FFA_N_COLUMNS},
+            constraint::ConstraintBuilderEnv as FFAConstraintBuilderEnv,
             interpreter::{self as ffa_interpreter, FFAInterpreterEnv},
             witness::WitnessBuilderEnv as FFAWitnessBuilderEnv,
         },
-        Ff1, Fp,
+        lookups::LookupTableIDs,
+        prover::{prove_with_context, ProverContext},
+        verifier::{VerifierContext, VerifierError},
+        witness::Witness,
+        BaseSponge, Ff1, Fp, OpeningProof, ScalarSponge, BN254, LIMB_BITSIZE, N_LIMBS,
     };
-    use ark_ff::UniformRand;
+    use ark_ff::{One, UniformRand, Zero};
+    use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+    use num_bigint::BigUint;
+    use o1_utils::FieldHelpers;
+    use poly_commitment::pairing_proof::PairingSRS;
     use rand::Rng;
 
     #[test]
@@ -39,4 +51,90 @@ mod tests {
             witness_env.next_row();
         }
     }
+
+    #[test]
+    /// The limbs of the sum of the last row are given with the proof
+    pub fn test_foreign_field_addition_public_outputs() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 4;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let mut witness_env = FFAWitnessBuilderEnv::<Fp>::empty();
+        let mut constraint_env = FFAConstraintBuilderEnv::<Fp>::empty();
+        ffa_interpreter::constrain_ff_addition(&mut constraint_env);
+        let row_num = 5;
+        let (mut a, mut b) = (Ff1::zero(), Ff1::zero());
+        for _row_i in 0..row_num {
+            a = Ff1::rand(&mut rng);
+            b = Ff1::rand(&mut rng);
+            ffa_interpreter::ff_addition_circuit(&mut witness_env, a, b);
+            witness_env.next_row();
+        }
+        let inputs = witness_env.get_witness(domain_size);
+        let constraints = constraint_env.constraints;
+
+        let config = ProtocolConfig {
+            output_cells: (0..N_LIMBS)
+                .map(|i| match FFAColumnIndexer::Remainder(i).to_column() {
+                    Column::X(column) => OutputCell {
+                        column,
+                        row: row_num - 1,
+                    },
+                    _ => unreachable!(),
+                })
+                .collect(),
+            ..ProtocolConfig::default()
+        };
+        let ctx = ProverContext::new(domain, &srs, config.clone(), &constraints).unwrap();
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            FFA_N_COLUMNS,
+            LookupTableIDs,
+            PointEvaluations<_>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+        let verifier = VerifierContext::new(domain, &srs, config, &constraints).unwrap();
+        let verify = |proof| {
+            verifier.verify::<BaseSponge, ScalarSponge, FFA_N_COLUMNS, 0, _>(
+                proof,
+                Witness::zero_vec(domain_size),
+            )
+        };
+        assert_eq!(verify(&proof), Ok(()));
+
+        // The limbs of the outputs are the ones of the sum
+        let sum = proof
+            .public_outputs()
+            .0
+            .iter()
+            .rev()
+            .fold(BigUint::zero(), |acc, limb| {
+                (acc << LIMB_BITSIZE) + limb.to_biguint()
+            });
+        assert_eq!(sum, (a + b).to_biguint());
+
+        // A different output is not the one computed by the witness
+        let mut falsified = proof.clone();
+        falsified.public_outputs.0[0] += Fp::one();
+        assert!(verify(&falsified).is_err());
+
+        // The outputs must be given for each output cell
+        let mut truncated = proof.clone();
+        truncated.public_outputs.0.pop();
+        assert_eq!(
+            verify(&truncated),
+            Err(VerifierError::Config(
+                ConfigError::PublicOutputCountMismatch {
+                    expected: N_LIMBS,
+                    got: N_LIMBS - 1
+                }
+            ))
+        );
+    }
 }
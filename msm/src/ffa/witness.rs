@@ -8,7 +8,7 @@ use crate::{
     },
     lookups::LookupTableIDs,
     proof::ProofInputs,
-    witness::Witness,
+    witness::{Witness, WitnessError},
     BN254G1Affine, Fp,
 };
 
@@ -105,6 +105,22 @@ impl WitnessBuilderEnv<Fp> {
         }
     }
 
+    /// Like [Self::get_witness], for a circuit of at most `max_rows` rows,
+    /// see [crate::config::ProtocolConfig::max_rows]
+    pub fn get_witness_with_max_rows(
+        &self,
+        domain_size: usize,
+        max_rows: usize,
+    ) -> Result<ProofInputs<FFA_N_COLUMNS, BN254G1Affine, LookupTableIDs>, WitnessError> {
+        if self.witness.len() > max_rows {
+            return Err(WitnessError::TooManyRows {
+                max: max_rows,
+                got: self.witness.len(),
+            });
+        }
+        Ok(self.get_witness(domain_size))
+    }
+
     pub fn next_row(&mut self) {
         self.witness.push(Witness {
             cols: Box::new([Zero::zero(); FFA_N_COLUMNS]),
@@ -0,0 +1,474 @@
+//! Test vectors of the full proof pipeline, to check the compatibility of
+//! other implementations of the verifier.
+//!
+//! Each [FixtureConfig] describes a small circuit. From a fixed seed, it gives
+//! a [Fixture] made of the description of the circuit, the serialized
+//! [ProofInputs], the serialized [Proof] created with
+//! [crate::prover::prove_deterministic], the challenges coined by the verifier
+//! and the expected verification result. The fixtures are kept in
+//! `testdata/`, and any change of the protocol making them outdated is caught
+//! by the tests of this module.
+//!
+//! The fixtures are regenerated with
+//! `KIMCHI_MSM_UPDATE_FIXTURES=1 cargo test -p kimchi_msm -- --ignored export_fixtures`.
+//!
+//! The field elements are hex-encoded with [o1_utils::FieldHelpers::to_hex],
+//! and the inputs and the proof are hex-encoded MessagePack.
+
+use crate::{
+    columns::Column,
+    config::ProtocolConfig,
+    expr::{curr_cell, E},
+    mvlookup::{constraint_lookups, LookupTableID, MVLookup, MVLookupWitness},
+    proof::{Proof, ProofInputs},
+    prover::{prove_deterministic, ProverError},
+    verifier::{VerifierChallenges, VerifierContext, VerifierError},
+    witness::Witness,
+    BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+};
+use ark_ff::{One, UniformRand, Zero};
+use kimchi::circuits::domains::EvaluationDomains;
+use o1_utils::FieldHelpers;
+use poly_commitment::pairing_proof::PairingSRS;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The environment variable which must be set to update the fixtures
+pub const UPDATE_FIXTURES_VAR: &str = "KIMCHI_MSM_UPDATE_FIXTURES";
+
+/// The number of witness columns of the circuits of the fixtures
+pub const N_COLUMNS: usize = 3;
+
+const DOMAIN_SIZE: usize = 1 << 4;
+
+crate::define_lookup_tables! {
+    /// The fixed lookup tables of the fixtures
+    pub enum FixtureTables {
+        Bits = 1 => generate(2, |i| [i]),
+        Squares = 2 => generate(16, |i| [i, i * i]),
+    }
+}
+
+/// Errors that can arise when reading or checking a fixture
+#[derive(Error, Debug, Clone)]
+pub enum FixtureError {
+    #[error("the fixture could not be read or written: {0}")]
+    Io(String),
+
+    #[error("the fixture could not be decoded: {0}")]
+    Decoding(String),
+
+    #[error("the fixture {0} does not match any configuration")]
+    UnknownConfiguration(String),
+
+    #[error("the circuit of the fixture does not match its configuration")]
+    CircuitMismatch,
+
+    #[error("the proof of the fixture could not be created: {0}")]
+    Prover(ProverError),
+
+    #[error("the proof of the fixture has been rejected: {0}")]
+    Verifier(VerifierError),
+}
+
+/// The circuits of the fixtures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureConfig {
+    /// A multiplication `x_2 = x_0 x_1`, without lookups
+    NoLookups,
+    /// A lookup of `x_0` in [FixtureTables::Bits] and of `(x_1, x_2)` in
+    /// [FixtureTables::Squares]
+    TwoTables,
+}
+
+impl FixtureConfig {
+    pub const ALL: [FixtureConfig; 2] = [FixtureConfig::NoLookups, FixtureConfig::TwoTables];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FixtureConfig::NoLookups => "no_lookups",
+            FixtureConfig::TwoTables => "two_tables",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|config| config.name() == name)
+    }
+
+    /// The seed from which the whole fixture is derived
+    pub fn seed(&self) -> [u8; 32] {
+        match self {
+            FixtureConfig::NoLookups => [1u8; 32],
+            FixtureConfig::TwoTables => [2u8; 32],
+        }
+    }
+
+    /// The secret of the SRS of the fixture, the first value derived from its
+    /// seed
+    pub fn trapdoor(&self) -> Fp {
+        Fp::rand(&mut StdRng::from_seed(self.seed()))
+    }
+
+    /// The path of the fixture, relative to the root of the crate
+    pub fn path(&self) -> PathBuf {
+        Path::new("testdata").join(format!("fixtures_{}.json", self.name()))
+    }
+
+    pub fn domain(&self) -> EvaluationDomains<Fp> {
+        EvaluationDomains::create(DOMAIN_SIZE).unwrap()
+    }
+
+    pub fn tables(&self) -> Vec<FixtureTables> {
+        match self {
+            FixtureConfig::NoLookups => vec![],
+            FixtureConfig::TwoTables => FixtureTables::ALL.to_vec(),
+        }
+    }
+
+    pub fn constraints(&self) -> Vec<E<Fp>> {
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        match self {
+            FixtureConfig::NoLookups => vec![x(2) - x(0) * x(1)],
+            FixtureConfig::TwoTables => {
+                let lookups = BTreeMap::from([
+                    (
+                        FixtureTables::Bits,
+                        vec![MVLookup::new(FixtureTables::Bits, E::one(), &[x(0)])],
+                    ),
+                    (
+                        FixtureTables::Squares,
+                        vec![MVLookup::new(
+                            FixtureTables::Squares,
+                            E::one(),
+                            &[x(1), x(2)],
+                        )],
+                    ),
+                ]);
+                constraint_lookups(&lookups)
+            }
+        }
+    }
+
+    /// The SRS of the fixture, created from a trapdoor which is part of the
+    /// fixture
+    pub fn srs(&self, trapdoor: Fp) -> PairingSRS<BN254> {
+        let domain = self.domain();
+        let mut srs = PairingSRS::create(trapdoor, domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        srs
+    }
+
+    /// A satisfying witness of the circuit, with its lookups
+    pub fn inputs(
+        &self,
+        rng: &mut impl Rng,
+    ) -> ProofInputs<N_COLUMNS, BN254G1Affine, FixtureTables> {
+        let mut cols: [Vec<Fp>; N_COLUMNS] = std::array::from_fn(|_| vec![]);
+        // The index of the looked-up entry of each table, for each row
+        let mut indices: Vec<[usize; 2]> = vec![];
+        for _ in 0..DOMAIN_SIZE {
+            match self {
+                FixtureConfig::NoLookups => {
+                    let (a, b) = (Fp::rand(rng), Fp::rand(rng));
+                    cols[0].push(a);
+                    cols[1].push(b);
+                    cols[2].push(a * b);
+                }
+                FixtureConfig::TwoTables => {
+                    let (bit, i) = (rng.gen_range(0..2), rng.gen_range(0..16));
+                    cols[0].push(Fp::from(bit as u64));
+                    cols[1].push(Fp::from(i as u64));
+                    cols[2].push(Fp::from((i * i) as u64));
+                    indices.push([bit, i]);
+                }
+            }
+        }
+        let mvlookups = self
+            .tables()
+            .into_iter()
+            .enumerate()
+            .map(|(k, table_id)| {
+                let entries = table_id.entries::<Fp>();
+                let mut m = vec![Fp::zero(); DOMAIN_SIZE];
+                let f = indices
+                    .iter()
+                    .map(|index| {
+                        m[index[k]] += Fp::one();
+                        MVLookup::new(table_id, Fp::one(), &entries[index[k]])
+                    })
+                    .collect();
+                // The table is padded with its first entry, with a null
+                // multiplicity
+                let t = (0..DOMAIN_SIZE)
+                    .map(|i| MVLookup::new(table_id, -m[i], entries.get(i).unwrap_or(&entries[0])))
+                    .collect();
+                MVLookupWitness { f: vec![f, t], m }
+            })
+            .collect();
+        ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups,
+        }
+    }
+
+    /// Create the fixture of the configuration from its seed
+    pub fn generate(&self) -> Result<Fixture, FixtureError> {
+        let mut rng = StdRng::from_seed(self.seed());
+        let trapdoor = Fp::rand(&mut rng);
+        let inputs = self.inputs(&mut rng);
+        let prover_seed: [u8; 32] = rng.gen();
+
+        let domain = self.domain();
+        let srs = self.srs(trapdoor);
+        let constraints = self.constraints();
+        let proof = prove_deterministic::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            Column,
+            N_COLUMNS,
+            FixtureTables,
+        >(domain, &srs, &constraints, inputs.clone(), prover_seed)
+        .map_err(FixtureError::Prover)?;
+        let challenges = verify(domain, &srs, &constraints, &proof).ok();
+
+        Ok(Fixture {
+            name: self.name().to_string(),
+            prover_seed: hex::encode(prover_seed),
+            circuit: self.circuit_description(trapdoor),
+            inputs: encode(&inputs)?,
+            proof: encode(&proof)?,
+            verified: challenges.is_some(),
+            challenges: challenges.map(|challenges| FixtureChallenges::from(&challenges)),
+        })
+    }
+
+    fn circuit_description(&self, srs_trapdoor: Fp) -> CircuitDescription {
+        CircuitDescription {
+            domain_size: DOMAIN_SIZE,
+            n_columns: N_COLUMNS,
+            constraints: self
+                .constraints()
+                .iter()
+                .map(|constraint| constraint.to_string())
+                .collect(),
+            tables: self
+                .tables()
+                .into_iter()
+                .map(|table_id| FixtureTable {
+                    table_id: table_id.to_u32(),
+                    entries: table_id
+                        .entries::<Fp>()
+                        .iter()
+                        .map(|entry| entry.iter().map(|x| x.to_hex()).collect())
+                        .collect(),
+                })
+                .collect(),
+            srs_trapdoor: srs_trapdoor.to_hex(),
+        }
+    }
+}
+
+/// The description of the circuit of a fixture. The constraints are given in
+/// the textual form of the expressions, and include the constraints of the
+/// lookup argument.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitDescription {
+    pub domain_size: usize,
+    pub n_columns: usize,
+    pub constraints: Vec<String>,
+    pub tables: Vec<FixtureTable>,
+    /// The secret of the SRS, see [PairingSRS::create]
+    pub srs_trapdoor: String,
+}
+
+/// A fixed lookup table of a fixture
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixtureTable {
+    pub table_id: u32,
+    pub entries: Vec<Vec<String>>,
+}
+
+/// The challenges of the transcript of a fixture, see [VerifierChallenges]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixtureChallenges {
+    pub joint_combiner: Option<String>,
+    pub beta: String,
+    pub alpha: String,
+    pub zeta: String,
+    pub v: String,
+    pub u: String,
+}
+
+impl From<&VerifierChallenges<Fp>> for FixtureChallenges {
+    fn from(challenges: &VerifierChallenges<Fp>) -> Self {
+        FixtureChallenges {
+            joint_combiner: challenges.joint_combiner.map(|x| x.to_hex()),
+            beta: challenges.beta.to_hex(),
+            alpha: challenges.alpha.to_hex(),
+            zeta: challenges.zeta.to_hex(),
+            v: challenges.v.to_hex(),
+            u: challenges.u.to_hex(),
+        }
+    }
+}
+
+/// A complete artifact of the proof pipeline, see the [module
+/// documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fixture {
+    pub name: String,
+    /// The seed of [crate::prover::prove_deterministic]
+    pub prover_seed: String,
+    pub circuit: CircuitDescription,
+    /// The hex-encoded MessagePack of the [ProofInputs]
+    pub inputs: String,
+    /// The hex-encoded MessagePack of the [Proof]
+    pub proof: String,
+    /// The challenges of the transcript, when the proof is accepted
+    pub challenges: Option<FixtureChallenges>,
+    /// The expected verification result
+    pub verified: bool,
+}
+
+impl Fixture {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| FixtureError::Io(err.to_string()))?;
+        serde_json::from_str(&contents).map_err(|err| FixtureError::Decoding(err.to_string()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FixtureError> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|err| FixtureError::Decoding(err.to_string()))?;
+        std::fs::write(path, contents + "\n").map_err(|err| FixtureError::Io(err.to_string()))
+    }
+
+    pub fn config(&self) -> Result<FixtureConfig, FixtureError> {
+        FixtureConfig::from_name(&self.name)
+            .ok_or_else(|| FixtureError::UnknownConfiguration(self.name.clone()))
+    }
+
+    pub fn decode_inputs(
+        &self,
+    ) -> Result<ProofInputs<N_COLUMNS, BN254G1Affine, FixtureTables>, FixtureError> {
+        decode(&self.inputs)
+    }
+
+    pub fn decode_proof(
+        &self,
+    ) -> Result<Proof<N_COLUMNS, BN254G1Affine, OpeningProof, FixtureTables>, FixtureError> {
+        decode(&self.proof)
+    }
+
+    /// Verify the proof of the fixture against the circuit of its
+    /// configuration, returning the challenges of the transcript
+    pub fn verify(&self) -> Result<VerifierChallenges<Fp>, FixtureError> {
+        let config = self.config()?;
+        let trapdoor = Fp::from_hex(&self.circuit.srs_trapdoor)
+            .map_err(|err| FixtureError::Decoding(err.to_string()))?;
+        if config.circuit_description(trapdoor) != self.circuit {
+            return Err(FixtureError::CircuitMismatch);
+        }
+        verify(
+            config.domain(),
+            &config.srs(trapdoor),
+            &config.constraints(),
+            &self.decode_proof()?,
+        )
+        .map_err(FixtureError::Verifier)
+    }
+}
+
+fn verify(
+    domain: EvaluationDomains<Fp>,
+    srs: &PairingSRS<BN254>,
+    constraints: &[E<Fp>],
+    proof: &Proof<N_COLUMNS, BN254G1Affine, OpeningProof, FixtureTables>,
+) -> Result<VerifierChallenges<Fp>, VerifierError> {
+    VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?
+        .verify_with_challenges::<BaseSponge, ScalarSponge, N_COLUMNS, 0, _>(
+            proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        )
+}
+
+pub(crate) fn encode<T: Serialize>(value: &T) -> Result<String, FixtureError> {
+    rmp_serde::to_vec(value)
+        .map(hex::encode)
+        .map_err(|err| FixtureError::Decoding(err.to_string()))
+}
+
+pub(crate) fn decode<T: for<'de> Deserialize<'de>>(hex_bytes: &str) -> Result<T, FixtureError> {
+    let bytes = hex::decode(hex_bytes).map_err(|err| FixtureError::Decoding(err.to_string()))?;
+    rmp_serde::from_slice(&bytes).map_err(|err| FixtureError::Decoding(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_path(config: FixtureConfig) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(config.path())
+    }
+
+    /// Run with `KIMCHI_MSM_UPDATE_FIXTURES=1 cargo test -p kimchi_msm --
+    /// --ignored export_fixtures` to write the fixtures.
+    #[test]
+    #[ignore]
+    fn export_fixtures() {
+        assert!(
+            std::env::var_os(UPDATE_FIXTURES_VAR).is_some(),
+            "set {UPDATE_FIXTURES_VAR} to update the fixtures"
+        );
+        for config in FixtureConfig::ALL {
+            let fixture = config.generate().unwrap();
+            assert!(fixture.verified);
+            fixture.save(fixture_path(config)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_fixtures() {
+        for config in FixtureConfig::ALL {
+            let fixture = Fixture::load(fixture_path(config)).unwrap();
+            assert_eq!(fixture.config().unwrap(), config);
+
+            // The stored proof is verified with the same transcript
+            let challenges = fixture.verify();
+            assert_eq!(challenges.is_ok(), fixture.verified);
+            assert_eq!(
+                challenges.ok().as_ref().map(FixtureChallenges::from),
+                fixture.challenges
+            );
+            if config == FixtureConfig::TwoTables {
+                assert!(fixture
+                    .challenges
+                    .as_ref()
+                    .unwrap()
+                    .joint_combiner
+                    .is_some());
+            }
+
+            // The inputs are the ones of the seed
+            let mut rng = StdRng::from_seed(config.seed());
+            let _trapdoor = Fp::rand(&mut rng);
+            let inputs = config.inputs(&mut rng);
+            assert_eq!(
+                fixture.decode_inputs().unwrap().evaluations,
+                inputs.evaluations
+            );
+
+            // And the proof does not drift
+            assert_eq!(config.generate().unwrap(), fixture);
+        }
+    }
+}
@@ -0,0 +1,479 @@
+//! Entry points to fuzz the surfaces of the verifier exposed to untrusted
+//! inputs: the deserialization of the proofs, see [fuzz_deserialize_proof],
+//! and their verification, see [fuzz_verify]. They return an error for any
+//! input they reject, and never panic: a panic found by a fuzzer is a bug.
+//! The proofs are encoded with MessagePack, like in [crate::fixtures].
+//!
+//! The seeds of the corpus are the proofs of the fixtures, see
+//! [seed_corpus]. The inputs which have been found to panic are kept in
+//! `testdata/fuzz/crashers`, and are replayed by the tests of this module.
+//!
+//! With the feature `fuzz`, the evaluations of the proofs implement
+//! [arbitrary::Arbitrary], for the fuzzers to mutate them at the structure
+//! level with [fuzz_verify_evaluations].
+
+use crate::{
+    config::{ConfigError, OpeningMode, ProtocolConfig},
+    fixtures::{FixtureConfig, FixtureError},
+    mvlookup::LookupTableID,
+    proof::{EvaluationContainer, Proof, ProofEvaluations},
+    verifier::{VerifierContext, VerifierError},
+    witness::Witness,
+};
+use kimchi::{curve::KimchiCurve, plonk_sponge::FrSponge};
+use mina_poseidon::FqSponge;
+use poly_commitment::OpenProof;
+use serde::de::DeserializeOwned;
+use std::{io::Read, path::Path};
+use thiserror::Error;
+
+/// Errors that can arise when deserializing a proof, see
+/// [fuzz_deserialize_proof]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DeserializeError {
+    #[error("the proof could not be decoded: {0}")]
+    Decoding(String),
+
+    #[error("the proof does not follow the protocol configuration: {0}")]
+    Config(ConfigError),
+
+    #[error("the proof is malformed: {0}")]
+    MalformedProof(&'static str),
+
+    /// A map of the proof has duplicate or unsorted keys, see
+    /// [check_canonical_map]
+    #[error("the proof is not canonically encoded: {0}")]
+    NonCanonicalMap(String),
+}
+
+/// A value decoded in two steps: its encoding is decoded with serde, with
+/// the maps kept as the list of their entries, and then checked and
+/// converted, e.g. with [check_canonical_map]. The conversion reports the
+/// errors of the structure of the value with their type, whereas serde only
+/// carries the message of the errors of the deserializers.
+pub(crate) trait Decode: TryFrom<Self::Encoded, Error = DeserializeError> {
+    type Encoded: DeserializeOwned;
+}
+
+/// Check that the entries of a map are sorted by strictly increasing key. A
+/// map has a single encoding, and a duplicate or unsorted key is reported as
+/// [DeserializeError::NonCanonicalMap].
+pub(crate) fn check_canonical_map<K: Ord + std::fmt::Display, V>(
+    entries: &[(K, V)],
+) -> Result<(), DeserializeError> {
+    match entries.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+        Some([(previous, _), (key, _)]) => Err(DeserializeError::NonCanonicalMap(format!(
+            "the key {key} follows the key {previous}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Decode a value encoded with MessagePack, see [Decode]
+pub(crate) fn decode<T: Decode>(reader: impl Read) -> Result<T, DeserializeError> {
+    let encoded: T::Encoded = rmp_serde::decode::from_read(reader)
+        .map_err(|err| DeserializeError::Decoding(err.to_string()))?;
+    T::try_from(encoded)
+}
+
+fn decode_proof<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G> + DeserializeOwned,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    bytes: &[u8],
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, DeserializeError> {
+    decode(bytes)
+}
+
+/// Deserialize a proof, and check its structure against `config`, without
+/// the circuit: the opening mode, the SRS label, the aggregation of the
+/// lookup argument, the number of public outputs, and the pairing of the
+/// commitments and the evaluations of the lookup argument.
+pub fn fuzz_deserialize_proof<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G> + DeserializeOwned,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    bytes: &[u8],
+    config: &ProtocolConfig,
+) -> Result<(), DeserializeError> {
+    if config.opening_mode != Eval::OPENING_MODE {
+        return Err(DeserializeError::Config(ConfigError::OpeningModeMismatch));
+    }
+    let proof = decode_proof::<G, OpeningProof, N, ID, Eval>(bytes)?;
+    if proof.srs_label() != config.srs_label.as_ref() {
+        return Err(DeserializeError::Config(ConfigError::SrsLabelMismatch));
+    }
+    proof
+        .check_lookup_shape()
+        .map_err(DeserializeError::MalformedProof)?;
+    if let Some(comms) = &proof.proof_comms.mvlookup_comms {
+        if Eval::OPENING_MODE == OpeningMode::SinglePoint {
+            return Err(DeserializeError::Config(ConfigError::LookupsNotSupported));
+        }
+        if comms.aggregation_mode() != config.lookup_aggregation {
+            return Err(DeserializeError::Config(
+                ConfigError::LookupAggregationMismatch,
+            ));
+        }
+    }
+    config
+        .output_constraints::<G::ScalarField>(proof.public_outputs())
+        .map_err(DeserializeError::Config)?;
+    Ok(())
+}
+
+/// Deserialize a proof and verify it against `ctx`, without public inputs
+pub fn fuzz_verify<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G> + DeserializeOwned,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    bytes: &[u8],
+    ctx: &VerifierContext<G, OpeningProof, ID>,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let proof = decode_proof::<G, OpeningProof, N, ID, Eval>(bytes)
+        .map_err(|err| VerifierError::Deserialization(err.to_string()))?;
+    ctx.verify::<EFqSponge, EFrSponge, N, 0, Eval>(&proof, Witness { cols: Box::new([]) })
+}
+
+/// Verify `proof` against `ctx`, without public inputs, with its evaluations
+/// replaced by `evals`, e.g. generated by a fuzzer from the seed corpus.
+pub fn fuzz_verify_evaluations<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    proof: &Proof<N, G, OpeningProof, ID, Eval>,
+    evals: ProofEvaluations<N, G::ScalarField, ID, Eval>,
+    ctx: &VerifierContext<G, OpeningProof, ID>,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let proof = Proof {
+        proof_evals: evals,
+        ..proof.clone()
+    };
+    ctx.verify::<EFqSponge, EFrSponge, N, 0, Eval>(&proof, Witness { cols: Box::new([]) })
+}
+
+/// The seeds of the corpus: the encoded proof of each fixture, see
+/// [crate::fixtures], which are accepted by [fuzz_verify] with the context of
+/// their circuit.
+pub fn seed_corpus() -> Result<Vec<(FixtureConfig, Vec<u8>)>, FixtureError> {
+    FixtureConfig::ALL
+        .into_iter()
+        .map(|config| {
+            let fixture = config.generate()?;
+            let bytes = hex::decode(fixture.proof)
+                .map_err(|err| FixtureError::Decoding(err.to_string()))?;
+            Ok((config, bytes))
+        })
+        .collect()
+}
+
+/// Write the seeds of the corpus in `dir`, one file per fixture named after
+/// it, e.g. to initialize the corpus of a fuzzer.
+pub fn write_seed_corpus(dir: impl AsRef<Path>) -> Result<(), FixtureError> {
+    for (config, bytes) in seed_corpus()? {
+        std::fs::write(dir.as_ref().join(config.name()), bytes)
+            .map_err(|err| FixtureError::Io(err.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "fuzz")]
+mod generators {
+    use crate::{
+        mvlookup::{LookupAggregation, LookupProof, LookupTableID},
+        proof::{EvaluationContainer, ProofEvaluations, SinglePointEvaluations},
+        witness::Witness,
+    };
+    use arbitrary::{Arbitrary, Error, Result, Unstructured};
+    use ark_ff::PrimeField;
+    use kimchi::proof::PointEvaluations;
+    use std::{collections::BTreeMap, marker::PhantomData};
+
+    /// The maximal number of tables and of partial sums of the generated
+    /// lookup arguments
+    const MAX_LOOKUP_COLUMNS: usize = 8;
+
+    /// A field element, reduced from arbitrary bytes
+    pub fn arbitrary_field<F: PrimeField>(u: &mut Unstructured) -> Result<F> {
+        let n_bytes = (F::size_in_bits() + 7) / 8;
+        Ok(F::from_le_bytes_mod_order(u.bytes(n_bytes)?))
+    }
+
+    pub fn arbitrary_evaluations<F: PrimeField, Eval: EvaluationContainer<F>>(
+        u: &mut Unstructured,
+    ) -> Result<Eval> {
+        let points = (0..Eval::OPENING_MODE.n_points())
+            .map(|_| arbitrary_field(u))
+            .collect::<Result<_>>()?;
+        Eval::from_points(points).ok_or(Error::IncorrectFormat)
+    }
+
+    /// A table ID, decoded with [LookupTableID::try_from_u32]. The tables are
+    /// encoded with small integers in practice.
+    pub fn arbitrary_table_id<ID: LookupTableID>(u: &mut Unstructured) -> Result<ID> {
+        ID::try_from_u32(u8::arbitrary(u)?.into()).ok_or(Error::IncorrectFormat)
+    }
+
+    fn arbitrary_lookup_proof<F: PrimeField, ID: LookupTableID, Eval: EvaluationContainer<F>>(
+        u: &mut Unstructured,
+    ) -> Result<LookupProof<Eval, ID>> {
+        let mut m = BTreeMap::new();
+        let mut fixed_tables = BTreeMap::new();
+        for _ in 0..u.int_in_range(0..=MAX_LOOKUP_COLUMNS)? {
+            let id = arbitrary_table_id(u)?;
+            m.insert(id, arbitrary_evaluations(u)?);
+            if bool::arbitrary(u)? {
+                fixed_tables.insert(id, arbitrary_evaluations(u)?);
+            }
+        }
+        let h = (0..u.int_in_range(0..=MAX_LOOKUP_COLUMNS)?)
+            .map(|_| arbitrary_evaluations(u))
+            .collect::<Result<_>>()?;
+        let sum = if bool::arbitrary(u)? {
+            LookupAggregation::Committed(arbitrary_evaluations(u)?)
+        } else {
+            LookupAggregation::Claimed(PhantomData)
+        };
+        Ok(LookupProof {
+            m,
+            h,
+            sum,
+            fixed_tables,
+            plookup: BTreeMap::new(),
+        })
+    }
+
+    fn arbitrary_proof_evaluations<
+        const N: usize,
+        F: PrimeField,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<F>,
+    >(
+        u: &mut Unstructured,
+    ) -> Result<ProofEvaluations<N, F, ID, Eval>> {
+        let witness_evals: Vec<Eval> = (0..N)
+            .map(|_| arbitrary_evaluations(u))
+            .collect::<Result<_>>()?;
+        Ok(ProofEvaluations {
+            witness_evals: Witness {
+                cols: witness_evals
+                    .try_into()
+                    .map_err(|_| Error::IncorrectFormat)?,
+            },
+            mvlookup_evals: if bool::arbitrary(u)? {
+                Some(arbitrary_lookup_proof(u)?)
+            } else {
+                None
+            },
+            ft_eval1: if bool::arbitrary(u)? {
+                Some(arbitrary_field(u)?)
+            } else {
+                None
+            },
+            shifted_evals: BTreeMap::new(),
+        })
+    }
+
+    macro_rules! impl_arbitrary {
+        ($eval:ident) => {
+            impl<'a, F: PrimeField, ID: LookupTableID> Arbitrary<'a> for LookupProof<$eval<F>, ID> {
+                fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                    arbitrary_lookup_proof(u)
+                }
+            }
+
+            impl<'a, const N: usize, F: PrimeField, ID: LookupTableID> Arbitrary<'a>
+                for ProofEvaluations<N, F, ID, $eval<F>>
+            {
+                fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                    arbitrary_proof_evaluations(u)
+                }
+            }
+        };
+    }
+
+    impl_arbitrary!(PointEvaluations);
+    impl_arbitrary!(SinglePointEvaluations);
+}
+
+#[cfg(feature = "fuzz")]
+pub use generators::{arbitrary_evaluations, arbitrary_field, arbitrary_table_id};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::{FixtureTables, N_COLUMNS},
+        BN254G1Affine, BaseSponge, OpeningProof, ScalarSponge,
+    };
+    use kimchi::proof::PointEvaluations;
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::path::PathBuf;
+
+    type Eval = PointEvaluations<crate::Fp>;
+
+    fn deserialize(bytes: &[u8]) -> Result<(), DeserializeError> {
+        fuzz_deserialize_proof::<BN254G1Affine, OpeningProof, N_COLUMNS, FixtureTables, Eval>(
+            bytes,
+            &ProtocolConfig::default(),
+        )
+    }
+
+    fn verify(
+        bytes: &[u8],
+        ctx: &VerifierContext<BN254G1Affine, OpeningProof, FixtureTables>,
+    ) -> Result<(), VerifierError> {
+        fuzz_verify::<
+            BN254G1Affine,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N_COLUMNS,
+            FixtureTables,
+            Eval,
+        >(bytes, ctx)
+    }
+
+    fn srs(config: FixtureConfig) -> PairingSRS<crate::BN254> {
+        config.srs(config.trapdoor())
+    }
+
+    fn context(
+        config: FixtureConfig,
+        srs: &PairingSRS<crate::BN254>,
+    ) -> VerifierContext<BN254G1Affine, OpeningProof, FixtureTables> {
+        VerifierContext::new(
+            config.domain(),
+            srs,
+            ProtocolConfig::default(),
+            &config.constraints(),
+        )
+        .unwrap()
+    }
+
+    fn crashers() -> Vec<(PathBuf, Vec<u8>)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/fuzz/crashers");
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| {
+                let bytes = std::fs::read(&path).unwrap();
+                (path, bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_seed_corpus() {
+        let seeds = seed_corpus().unwrap();
+        for (config, bytes) in &seeds {
+            deserialize(bytes).unwrap();
+            for other in FixtureConfig::ALL {
+                let srs = srs(other);
+                let verified = verify(bytes, &context(other, &srs));
+                // The proof of a circuit is rejected by the other ones
+                assert_eq!(verified.is_ok(), other == *config, "{verified:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutated_seeds() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        for (config, bytes) in seed_corpus().unwrap() {
+            let srs = srs(config);
+            let ctx = context(config, &srs);
+            for _ in 0..16 {
+                let mut mutated = bytes.clone();
+                if rng.gen() {
+                    mutated.truncate(rng.gen_range(0..bytes.len()));
+                } else {
+                    let i = rng.gen_range(0..bytes.len());
+                    mutated[i] ^= 1 << rng.gen_range(0..8);
+                }
+                // A mutated proof may still be decoded, but is only accepted
+                // if it is a non-canonical encoding of the seed, e.g. with the
+                // coordinates of a point at infinity set
+                let _ = deserialize(&mutated);
+                if verify(&mutated, &ctx).is_ok() {
+                    let proof =
+                        decode_proof::<_, OpeningProof, N_COLUMNS, FixtureTables, Eval>(&mutated)
+                            .unwrap();
+                    assert_eq!(rmp_serde::to_vec(&proof).unwrap(), bytes);
+                }
+            }
+        }
+    }
+
+    /// The inputs found to panic, each checked against all the circuits
+    #[test]
+    fn test_replay_crashers() {
+        let crashers = crashers();
+        assert!(!crashers.is_empty());
+        for config in FixtureConfig::ALL {
+            let srs = srs(config);
+            let ctx = context(config, &srs);
+            for (path, bytes) in &crashers {
+                let _ = deserialize(bytes);
+                assert!(verify(bytes, &ctx).is_err(), "{path:?} has been accepted");
+            }
+        }
+    }
+
+    #[cfg(feature = "fuzz")]
+    #[test]
+    fn test_arbitrary_evaluations() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let config = FixtureConfig::TwoTables;
+        let srs = srs(config);
+        let ctx = context(config, &srs);
+        let fixture = config.generate().unwrap();
+        let proof = fixture.decode_proof().unwrap();
+        for _ in 0..16 {
+            let data: Vec<u8> = (0..4096).map(|_| rng.gen()).collect();
+            let mut u = Unstructured::new(&data);
+            let Ok(evals) = ProofEvaluations::arbitrary(&mut u) else {
+                continue;
+            };
+            assert!(fuzz_verify_evaluations::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                N_COLUMNS,
+                FixtureTables,
+                Eval,
+            >(&proof, evals, &ctx)
+            .is_err());
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Lookups of wide tuples through their Poseidon digest.
+//!
+//! A vector lookup of `w` values combines them with the powers of the joint
+//! combiner, adding `w` terms to the expression of the looked-up value. For
+//! wide tables, the tuple can instead be hashed in the circuit with the
+//! Poseidon permutation of [crate::SpongeParams], and the digest looked up in
+//! a table of the digests of the rows, built with [hash_table].
+//!
+//! [HashedLookup] constrains the hash of the tuple on each row. It uses its
+//! own columns: the state after each round of each permutation, i.e.
+//! `ceil(w / 2) * 55 * 3` columns, and one constraint of degree 7 for each of
+//! them.
+//!
+//! The soundness differs from the one of the combined lookups. A combination
+//! with the joint combiner, which is coined after the witness is committed,
+//! only makes two distinct rows collide with negligible probability. The
+//! digest is deterministic: the tuples of the witness can be chosen knowing
+//! the hash function, and the lookup is only sound if a collision of Poseidon
+//! can not be found, i.e. it is computationally sound, at the security level
+//! of the sponge parameters. In particular, the parameters of the sponge over
+//! BN254 are not yet the final ones.
+
+use ark_ff::{One, PrimeField, Zero};
+use kimchi::circuits::expr::ConstantTerm;
+use mina_poseidon::{
+    constants::SpongeConstants, permutation::full_round2, poseidon::ArithmeticSpongeParams,
+};
+
+use crate::{
+    columns::Column,
+    expr::{curr_cell, E},
+    mvlookup::{LookupTable, LookupTableID, MVLookup},
+    SpongeParams,
+};
+
+const RATE: usize = SpongeParams::SPONGE_RATE;
+const WIDTH: usize = SpongeParams::SPONGE_WIDTH;
+const ROUNDS: usize = SpongeParams::PERM_ROUNDS_FULL;
+
+/// Returns the Poseidon digest of `tuple`, i.e. the first element squeezed
+/// after absorbing the tuple in a [mina_poseidon::poseidon::ArithmeticSponge]
+/// with the parameters `params`.
+pub fn hash_tuple<F: PrimeField>(params: &ArithmeticSpongeParams<F>, tuple: &[F]) -> F {
+    let mut state = [F::zero(); WIDTH];
+    for block in tuple.chunks(RATE) {
+        for (x, y) in state.iter_mut().zip(block) {
+            *x += y;
+        }
+        state = (0..ROUNDS).fold(state, |state, round| {
+            full_round2::<F, SpongeParams>(params, state, round)
+        });
+    }
+    state[0]
+}
+
+/// Returns the table of the digests of the entries of `table`, with the same
+/// ID, to be looked up with [HashedLookup::lookup].
+pub fn hash_table<F: PrimeField, ID: LookupTableID>(
+    params: &ArithmeticSpongeParams<F>,
+    table: &LookupTable<F, ID>,
+) -> LookupTable<F, ID> {
+    LookupTable {
+        table_id: table.table_id,
+        entries: table
+            .entries
+            .iter()
+            .map(|entry| vec![hash_tuple(params, entry)])
+            .collect(),
+    }
+}
+
+/// A gadget constraining the Poseidon digest of a tuple of `width` columns
+/// starting at `X(first_column)`. The columns of the tuple are followed by
+/// the states of the sponge, the digest being the first element of the last
+/// state.
+pub struct HashedLookup<'a, F: PrimeField> {
+    params: &'a ArithmeticSpongeParams<F>,
+    width: usize,
+    first_column: usize,
+}
+
+impl<'a, F: PrimeField> HashedLookup<'a, F> {
+    pub fn new(params: &'a ArithmeticSpongeParams<F>, width: usize, first_column: usize) -> Self {
+        assert!(width > 0, "the tuple can not be empty");
+        HashedLookup {
+            params,
+            width,
+            first_column,
+        }
+    }
+
+    /// The number of permutations of the sponge, one for each block of
+    /// [SpongeConstants::SPONGE_RATE] values, the last one computing the
+    /// digest.
+    pub fn n_permutations(&self) -> usize {
+        (self.width + RATE - 1) / RATE
+    }
+
+    /// The number of columns used by the gadget, including the tuple
+    pub fn n_columns(&self) -> usize {
+        self.width + self.n_permutations() * ROUNDS * WIDTH
+    }
+
+    pub fn tuple_columns(&self) -> Vec<Column> {
+        (0..self.width)
+            .map(|i| Column::X(self.first_column + i))
+            .collect()
+    }
+
+    /// The column of the `i`-th element of the state after the round `round`
+    /// of the permutation `permutation`
+    fn state_column(&self, permutation: usize, round: usize, i: usize) -> Column {
+        Column::X(self.first_column + self.width + (permutation * ROUNDS + round) * WIDTH + i)
+    }
+
+    pub fn digest_column(&self) -> Column {
+        self.state_column(self.n_permutations() - 1, ROUNDS - 1, 0)
+    }
+
+    /// The values of the tuple absorbed before the permutation `permutation`
+    fn block(&self, permutation: usize) -> std::ops::Range<usize> {
+        permutation * RATE..std::cmp::min((permutation + 1) * RATE, self.width)
+    }
+
+    /// The constraints of the rounds of the permutations, of degree
+    /// [SpongeConstants::PERM_SBOX].
+    pub fn constraints(&self) -> Vec<E<F>> {
+        let mut constraints = Vec::with_capacity(self.n_permutations() * ROUNDS * WIDTH);
+        for permutation in 0..self.n_permutations() {
+            // The state after absorbing the block of the permutation
+            let mut state: Vec<E<F>> = (0..WIDTH)
+                .map(|i| {
+                    if permutation == 0 {
+                        E::zero()
+                    } else {
+                        curr_cell(self.state_column(permutation - 1, ROUNDS - 1, i))
+                    }
+                })
+                .collect();
+            for (i, j) in self.block(permutation).enumerate() {
+                state[i] = state[i].clone() + curr_cell(Column::X(self.first_column + j));
+            }
+            for round in 0..ROUNDS {
+                let sbox: Vec<E<F>> = state
+                    .iter()
+                    .map(|x| x.clone().pow(SpongeParams::PERM_SBOX as u64))
+                    .collect();
+                for i in 0..WIDTH {
+                    let next = sbox.iter().zip(self.params.mds[i].iter()).fold(
+                        E::from(ConstantTerm::Literal(self.params.round_constants[round][i])),
+                        |acc, (x, m)| acc + E::from(ConstantTerm::Literal(*m)) * x.clone(),
+                    );
+                    constraints.push(curr_cell(self.state_column(permutation, round, i)) - next);
+                }
+                state = (0..WIDTH)
+                    .map(|i| curr_cell(self.state_column(permutation, round, i)))
+                    .collect();
+            }
+        }
+        constraints
+    }
+
+    /// The lookup of the digest in the table `table_id`, built with
+    /// [hash_table]
+    pub fn lookup<ID: LookupTableID>(&self, table_id: ID) -> MVLookup<E<F>, ID> {
+        MVLookup::new(table_id, E::one(), &[curr_cell(self.digest_column())])
+    }
+
+    /// Returns the values of the [HashedLookup::n_columns] columns of the
+    /// gadget for `tuple`.
+    pub fn witness(&self, tuple: &[F]) -> Vec<F> {
+        assert_eq!(tuple.len(), self.width);
+        let mut values = tuple.to_vec();
+        let mut state = [F::zero(); WIDTH];
+        for permutation in 0..self.n_permutations() {
+            for (i, j) in self.block(permutation).enumerate() {
+                state[i] += tuple[j];
+            }
+            for round in 0..ROUNDS {
+                state = full_round2::<F, SpongeParams>(self.params, state, round);
+                values.extend(state);
+            }
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_table, hash_tuple, HashedLookup, ROUNDS, WIDTH};
+    use crate::{
+        columns::Column,
+        lookups::LookupTableIDs,
+        mvlookup::{constraint_lookups, LookupTable, MVLookup, MVLookupWitness},
+        proof::ProofInputs,
+        prover::{prove, ProverError},
+        verifier::verify,
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand, Zero};
+    use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
+    use mina_poseidon::poseidon::{ArithmeticSponge, Sponge};
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+    use std::collections::BTreeMap;
+
+    const TUPLE_WIDTH: usize = 12;
+    const N: usize = TUPLE_WIDTH + 6 * ROUNDS * WIDTH;
+
+    #[test]
+    fn test_hash_tuple_is_the_sponge_digest() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let params = BN254G1Affine::sponge_params();
+        for width in [1, 2, 3, 12] {
+            let tuple: Vec<Fp> = (0..width).map(|_| Fp::rand(&mut rng)).collect();
+            let mut sponge = ArithmeticSponge::<Fp, crate::SpongeParams>::new(params);
+            sponge.absorb(&tuple);
+            assert_eq!(hash_tuple(params, &tuple), sponge.squeeze());
+            let gadget = HashedLookup::new(params, width, 0);
+            let Column::X(digest) = gadget.digest_column() else {
+                panic!("the digest is a witness column")
+            };
+            assert_eq!(gadget.witness(&tuple)[digest], hash_tuple(params, &tuple));
+        }
+    }
+
+    #[test]
+    fn test_hashed_lookup_of_a_wide_table() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> =
+            PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let params = BN254G1Affine::sponge_params();
+        let gadget = HashedLookup::new(params, TUPLE_WIDTH, 0);
+        assert_eq!(gadget.n_columns(), N);
+        let table_id = LookupTableIDs::Custom(1);
+        let table = LookupTable {
+            table_id,
+            entries: (0..16)
+                .map(|_| (0..TUPLE_WIDTH).map(|_| Fp::rand(&mut rng)).collect())
+                .collect(),
+        };
+        let hashed_table = hash_table(params, &table);
+
+        // Each row looks up a random entry of the table
+        let mut m = vec![Fp::zero(); domain_size];
+        let mut cols: Box<[Vec<Fp>; N]> = Box::new(std::array::from_fn(|_| vec![]));
+        let mut lookups = vec![];
+        for _ in 0..domain_size {
+            let k = rng.gen_range(0..table.entries.len());
+            m[k] += Fp::one();
+            let row = gadget.witness(&table.entries[k]);
+            for (col, x) in cols.iter_mut().zip(row) {
+                col.push(x);
+            }
+            lookups.push(MVLookup::new(table_id, Fp::one(), &hashed_table.entries[k]));
+        }
+        let t = (0..domain_size)
+            .map(|i| {
+                let value = hashed_table.entries.get(i).map_or(Fp::zero(), |x| x[0]);
+                MVLookup::new(table_id, -m[i], &[value])
+            })
+            .collect();
+        let inputs: ProofInputs<N, BN254G1Affine, LookupTableIDs> = ProofInputs {
+            evaluations: Witness { cols },
+            mvlookups: vec![MVLookupWitness {
+                f: vec![lookups, t],
+                m,
+            }],
+        };
+
+        let mut constraints = gadget.constraints();
+        constraints.extend(constraint_lookups(&BTreeMap::from([(
+            table_id,
+            vec![gadget.lookup(table_id)],
+        )])));
+
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs.clone(),
+                &mut rng,
+            )
+            .unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        ));
+
+        // A tuple which is not in the table can not be given the digest of an
+        // entry
+        let mut inputs = inputs;
+        inputs.evaluations.cols[0][0] += Fp::one();
+        let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        );
+        assert!(matches!(res, Err(ProverError::ConstraintNotSatisfied(_))));
+    }
+}
@@ -0,0 +1,489 @@
+//! Inspection of the rows of a witness, e.g. to dump the row a constraint
+//! failure is attributed to, see [ProofInputs::inspect_row].
+//!
+//! A [RowSnapshot] gives the values of the witness columns at the row, the
+//! lookups issued at the row, and the multiplicities of the table rows they
+//! look up. The table rows are found with a [LookupRowIndex], which is not
+//! kept by the [ProofInputs] as their fields can be modified: it must be
+//! built again after a change of the lookups.
+
+use crate::{
+    mvlookup::{LookupLayout, LookupTableID},
+    proof::ProofInputs,
+    MAX_SUPPORTED_DEGREE,
+};
+use ark_ff::PrimeField;
+use kimchi::curve::KimchiCurve;
+use o1_utils::FieldHelpers;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+/// A lookup issued at a row, i.e. a cell of a column `f_i` of a
+/// [crate::MVLookupWitness]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowLookup<F, ID> {
+    /// The index of the lookup witness in [ProofInputs::mvlookups]
+    pub witness: usize,
+    /// The index of the column in the lookup witness
+    pub column: usize,
+    pub table_id: ID,
+    pub numerator: F,
+    pub value: Vec<F>,
+    /// Whether the column is the fixed table of the lookup witness
+    pub is_table: bool,
+    /// The index `i` of the partial sum `h_i` the term of the lookup is added
+    /// to, if it is in the layout
+    pub partial_sum: Option<usize>,
+    /// The row of the fixed table holding the looked-up value, if any
+    pub table_row: Option<usize>,
+}
+
+/// The multiplicity of a row of a fixed table looked up at the inspected row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiplicityEntry<F, ID> {
+    pub table_id: ID,
+    /// The row of the table
+    pub row: usize,
+    pub multiplicity: F,
+}
+
+/// Everything about a row of a witness, see [ProofInputs::inspect_row]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowSnapshot<F, ID> {
+    pub row: usize,
+    /// The values of the witness columns
+    pub witness: Vec<F>,
+    /// The lookups issued at the row, including the terms of the fixed
+    /// tables, by lookup witness and column
+    pub lookups: Vec<RowLookup<F, ID>>,
+    /// The multiplicities of the table rows looked up at the row, by table ID
+    /// and row
+    pub multiplicities: Vec<MultiplicityEntry<F, ID>>,
+}
+
+/// The reverse index of the values of the fixed tables of the lookup
+/// witnesses, giving the row of the table holding a looked-up value.
+#[derive(Debug, Clone)]
+pub struct LookupRowIndex<F, ID> {
+    /// For each fixed table, the index of its lookup witness and the first
+    /// row of each value
+    tables: BTreeMap<ID, (usize, HashMap<Vec<F>, usize>)>,
+}
+
+impl<F: PrimeField, ID: LookupTableID> LookupRowIndex<F, ID> {
+    /// The row of the table `table_id` holding `value`, if any. The first row
+    /// is returned when the table has duplicated rows, e.g. for padding.
+    pub fn table_row(&self, table_id: ID, value: &[F]) -> Option<usize> {
+        self.tables.get(&table_id)?.1.get(value).copied()
+    }
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofInputs<N, G, ID> {
+    /// Build the index of the rows of the fixed tables of the lookups, to
+    /// inspect several rows with [Self::inspect_row_with_index]
+    pub fn lookup_row_index(&self) -> LookupRowIndex<G::ScalarField, ID> {
+        let mut tables = BTreeMap::new();
+        for (k, lookup) in self.mvlookups.iter().enumerate() {
+            let Some(table) = lookup.fixed_table() else {
+                continue;
+            };
+            let mut rows = HashMap::new();
+            for (row, entry) in table.entries.into_iter().enumerate() {
+                rows.entry(entry).or_insert(row);
+            }
+            tables.entry(table.table_id).or_insert((k, rows));
+        }
+        LookupRowIndex { tables }
+    }
+
+    /// Everything about the row `row`: the values of the witness columns, the
+    /// lookups issued at the row with their partial sum in `layout`, and the
+    /// multiplicities of the table rows they look up. See
+    /// [Self::inspect_row_with_index] to inspect several rows.
+    ///
+    /// Panics if `row` is not a row of the witness columns.
+    pub fn inspect_row(
+        &self,
+        row: usize,
+        layout: &LookupLayout<ID>,
+    ) -> RowSnapshot<G::ScalarField, ID> {
+        self.inspect_row_with_index(row, layout, &self.lookup_row_index())
+    }
+
+    /// Same as [Self::inspect_row], with the index `index` built by
+    /// [Self::lookup_row_index] for the current lookups
+    pub fn inspect_row_with_index(
+        &self,
+        row: usize,
+        layout: &LookupLayout<ID>,
+        index: &LookupRowIndex<G::ScalarField, ID>,
+    ) -> RowSnapshot<G::ScalarField, ID> {
+        let witness = self.evaluations.cols.iter().map(|col| col[row]).collect();
+        let mut lookups = vec![];
+        let mut multiplicities = BTreeMap::new();
+        for (k, lookup) in self.mvlookups.iter().enumerate() {
+            let has_table = lookup.fixed_table().is_some();
+            for (i, f_i) in lookup.f.iter().enumerate() {
+                let Some(cell) = f_i.get(row) else {
+                    continue;
+                };
+                let is_table = has_table && i + 1 == lookup.f.len();
+                let table_row = if is_table {
+                    Some(row)
+                } else {
+                    index.table_row(cell.table_id, &cell.value)
+                };
+                if let (false, Some(table_row)) = (is_table, table_row) {
+                    let (table_witness, _) = index.tables[&cell.table_id];
+                    if let Some(m) = self.mvlookups[table_witness].m.get(table_row) {
+                        multiplicities.insert((cell.table_id, table_row), *m);
+                    }
+                }
+                lookups.push(RowLookup {
+                    witness: k,
+                    column: i,
+                    table_id: cell.table_id,
+                    numerator: cell.numerator,
+                    value: cell.value.clone(),
+                    is_table,
+                    // The columns are split in chunks row by row, like in the
+                    // constraints
+                    partial_sum: layout
+                        .partial_sum_index(cell.table_id, i / (MAX_SUPPORTED_DEGREE - 2)),
+                    table_row,
+                });
+            }
+        }
+        RowSnapshot {
+            row,
+            witness,
+            lookups,
+            multiplicities: multiplicities
+                .into_iter()
+                .map(|((table_id, row), multiplicity)| MultiplicityEntry {
+                    table_id,
+                    row,
+                    multiplicity,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A difference between an expected and an actual row, see [diff_rows]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowDifference<F, ID> {
+    Witness {
+        column: usize,
+        expected: Option<F>,
+        actual: Option<F>,
+    },
+    Lookup {
+        witness: usize,
+        column: usize,
+        expected: Option<RowLookup<F, ID>>,
+        actual: Option<RowLookup<F, ID>>,
+    },
+    Multiplicity {
+        table_id: ID,
+        row: usize,
+        expected: Option<F>,
+        actual: Option<F>,
+    },
+}
+
+/// The differences between the expected row `expected` and the actual row
+/// `actual`: the witness values, the lookups by lookup witness and column,
+/// and the multiplicities by table ID and row. The indices of the rows are
+/// not compared.
+pub fn diff_rows<F: PrimeField, ID: LookupTableID>(
+    expected: &RowSnapshot<F, ID>,
+    actual: &RowSnapshot<F, ID>,
+) -> Vec<RowDifference<F, ID>> {
+    let mut differences = vec![];
+    let n_columns = std::cmp::max(expected.witness.len(), actual.witness.len());
+    for column in 0..n_columns {
+        let (expected, actual) = (
+            expected.witness.get(column).copied(),
+            actual.witness.get(column).copied(),
+        );
+        if expected != actual {
+            differences.push(RowDifference::Witness {
+                column,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    let by_cell = |snapshot: &RowSnapshot<F, ID>| -> BTreeMap<(usize, usize), RowLookup<F, ID>> {
+        snapshot
+            .lookups
+            .iter()
+            .map(|lookup| ((lookup.witness, lookup.column), lookup.clone()))
+            .collect()
+    };
+    let (expected_lookups, actual_lookups) = (by_cell(expected), by_cell(actual));
+    let cells: BTreeSet<_> = expected_lookups
+        .keys()
+        .chain(actual_lookups.keys())
+        .copied()
+        .collect();
+    for (witness, column) in cells {
+        let (expected, actual) = (
+            expected_lookups.get(&(witness, column)).cloned(),
+            actual_lookups.get(&(witness, column)).cloned(),
+        );
+        if expected != actual {
+            differences.push(RowDifference::Lookup {
+                witness,
+                column,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    let by_row = |snapshot: &RowSnapshot<F, ID>| -> BTreeMap<(ID, usize), F> {
+        snapshot
+            .multiplicities
+            .iter()
+            .map(|entry| ((entry.table_id, entry.row), entry.multiplicity))
+            .collect()
+    };
+    let (expected_multiplicities, actual_multiplicities) = (by_row(expected), by_row(actual));
+    let rows: BTreeSet<_> = expected_multiplicities
+        .keys()
+        .chain(actual_multiplicities.keys())
+        .copied()
+        .collect();
+    for (table_id, row) in rows {
+        let (expected, actual) = (
+            expected_multiplicities.get(&(table_id, row)).copied(),
+            actual_multiplicities.get(&(table_id, row)).copied(),
+        );
+        if expected != actual {
+            differences.push(RowDifference::Multiplicity {
+                table_id,
+                row,
+                expected,
+                actual,
+            });
+        }
+    }
+    differences
+}
+
+/// A field element in decimal, negated if it is closer to the modulus
+fn field<F: PrimeField>(x: &F) -> String {
+    let (x, neg_x) = (x.to_biguint(), (-*x).to_biguint());
+    if neg_x < x {
+        format!("-{neg_x}")
+    } else {
+        x.to_string()
+    }
+}
+
+fn values<F: PrimeField>(values: &[F]) -> String {
+    let values: Vec<String> = values.iter().map(field).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn optional<F: PrimeField>(x: &Option<F>) -> String {
+    x.as_ref().map_or_else(|| "none".to_string(), field)
+}
+
+impl<F: PrimeField, ID: LookupTableID> fmt::Display for RowLookup<F, ID> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}.{}: table {}, numerator {}, value {}",
+            if self.is_table { "table" } else { "lookup" },
+            self.witness,
+            self.column,
+            self.table_id.to_u32(),
+            field(&self.numerator),
+            values(&self.value),
+        )?;
+        if let Some(i) = self.partial_sum {
+            write!(f, ", partial sum h_{i}")?;
+        }
+        match (self.is_table, self.table_row) {
+            (false, Some(row)) => write!(f, ", table row {row}"),
+            (false, None) => write!(f, ", not in the table"),
+            (true, _) => Ok(()),
+        }
+    }
+}
+
+/// Renders the witness values on one line, then one line per lookup and per
+/// multiplicity. The field elements are in decimal, and negated when they
+/// are closer to the modulus.
+impl<F: PrimeField, ID: LookupTableID> fmt::Display for RowSnapshot<F, ID> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "row {}", self.row)?;
+        let witness: Vec<String> = self
+            .witness
+            .iter()
+            .enumerate()
+            .map(|(i, x)| format!("x_{i} = {}", field(x)))
+            .collect();
+        writeln!(f, "witness: {}", witness.join(", "))?;
+        writeln!(f, "lookups:")?;
+        for lookup in self.lookups.iter() {
+            writeln!(f, "  {lookup}")?;
+        }
+        writeln!(f, "multiplicities:")?;
+        for entry in self.multiplicities.iter() {
+            writeln!(
+                f,
+                "  table {}, row {}: {}",
+                entry.table_id.to_u32(),
+                entry.row,
+                field(&entry.multiplicity)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: PrimeField, ID: LookupTableID> fmt::Display for RowDifference<F, ID> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowDifference::Witness {
+                column,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "x_{column}: expected {}, got {}",
+                optional(expected),
+                optional(actual)
+            ),
+            RowDifference::Lookup {
+                witness,
+                column,
+                expected,
+                actual,
+            } => {
+                let lookup = |lookup: &Option<RowLookup<F, ID>>| {
+                    lookup
+                        .as_ref()
+                        .map_or_else(|| "none".to_string(), |lookup| lookup.to_string())
+                };
+                write!(
+                    f,
+                    "lookup {witness}.{column}: expected {}, got {}",
+                    lookup(expected),
+                    lookup(actual)
+                )
+            }
+            RowDifference::Multiplicity {
+                table_id,
+                row,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "multiplicity of the row {row} of the table {}: expected {}, got {}",
+                table_id.to_u32(),
+                optional(expected),
+                optional(actual)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_rows, RowDifference};
+    use crate::{
+        fixtures::{FixtureConfig, FixtureTables},
+        mvlookup::LookupLayout,
+        Fp,
+    };
+    use ark_ff::One;
+    use o1_utils::FieldHelpers;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_inspect_row() {
+        let config = FixtureConfig::TwoTables;
+        let inputs = config.inputs(&mut StdRng::from_seed(config.seed()));
+        let layout = LookupLayout::of_constraints(&config.constraints()).unwrap();
+        let index = inputs.lookup_row_index();
+
+        // The row looks up a bit `b` and a square `(i, i^2)`
+        let row = 5;
+        let snapshot = inputs.inspect_row_with_index(row, &layout, &index);
+        assert_eq!(snapshot, inputs.inspect_row(row, &layout));
+        let [b, i, square] = [0, 1, 2].map(|col| inputs.evaluations.cols[col][row].to_biguint());
+        assert_eq!(square, &i * &i);
+        let rendered = snapshot.to_string();
+        for line in [
+            format!("row {row}"),
+            format!("witness: x_0 = {b}, x_1 = {i}, x_2 = {square}"),
+            format!("lookup 0.0: table 1, numerator 1, value [{b}], partial sum h_0, table row {b}"),
+            format!(
+                "lookup 1.0: table 2, numerator 1, value [{i}, {square}], partial sum h_1, table row {i}"
+            ),
+            "table 1.1: table 2".to_string(),
+            format!("table 2, row {i}: "),
+        ] {
+            assert!(rendered.contains(&line), "{line} not in\n{rendered}");
+        }
+        // The multiplicity of the looked-up row of the table, and the term of
+        // the table at the row with its negated multiplicity
+        let table_row: usize = i.to_string().parse().unwrap();
+        let squares = snapshot
+            .multiplicities
+            .iter()
+            .find(|entry| entry.table_id == FixtureTables::Squares)
+            .unwrap();
+        assert_eq!(squares.row, table_row);
+        assert_eq!(squares.multiplicity, inputs.mvlookups[1].m[table_row]);
+        let table_term = snapshot
+            .lookups
+            .iter()
+            .find(|lookup| lookup.is_table && lookup.table_id == FixtureTables::Squares)
+            .unwrap();
+        assert_eq!(table_term.numerator, -inputs.mvlookups[1].m[row]);
+
+        assert!(diff_rows(&snapshot, &snapshot).is_empty());
+        let mut wrong_inputs = inputs.clone();
+        wrong_inputs.evaluations.cols[2][row] += Fp::one();
+        wrong_inputs.mvlookups[1].f[0][row].value[1] += Fp::one();
+        let wrong = wrong_inputs.inspect_row(row, &layout);
+        let differences = diff_rows(&snapshot, &wrong);
+        assert_eq!(differences.len(), 3, "{differences:?}");
+        assert_eq!(
+            differences[0],
+            RowDifference::Witness {
+                column: 2,
+                expected: Some(inputs.evaluations.cols[2][row]),
+                actual: Some(wrong_inputs.evaluations.cols[2][row]),
+            }
+        );
+        let wrong_lookup = format!(
+            "got lookup 1.0: table 2, numerator 1, value [{i}, {}], partial sum h_1, not in the table",
+            square + 1u32
+        );
+        assert!(
+            differences[1].to_string().contains(&wrong_lookup),
+            "{}",
+            differences[1]
+        );
+        // The row of the table is not looked up anymore
+        assert_eq!(
+            differences[2],
+            RowDifference::Multiplicity {
+                table_id: FixtureTables::Squares,
+                row: table_row,
+                expected: Some(squares.multiplicity),
+                actual: None,
+            }
+        );
+    }
+}
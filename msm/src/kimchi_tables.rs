@@ -0,0 +1,285 @@
+//! Conversion of the fixed lookup tables of kimchi, like the XOR and the
+//! range check tables, into the [LookupTable]s of this crate, to look up into
+//! exactly the same tables as the kimchi circuits.
+//!
+//! A table of kimchi is given as a list of columns, identified by an `i32`,
+//! while a [LookupTable] is a list of rows, identified by a [LookupTableID].
+//! The tables of kimchi must be registered as fixed tables of the
+//! [LookupTableID] of the circuit, with their length, see [from_kimchi_table].
+
+use ark_ff::Field;
+use kimchi::circuits::lookup::tables::LookupTable as KimchiLookupTable;
+use thiserror::Error;
+
+use crate::mvlookup::{LookupTable, LookupTableID};
+
+/// Errors of the conversion of a kimchi table, see [from_kimchi_table]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum KimchiTableError {
+    #[error("the kimchi table {0} is not registered")]
+    Unregistered(i32),
+
+    #[error("the kimchi table {0} is registered as the table {1}, which is not fixed")]
+    NotFixed(i32, u32),
+
+    #[error("the kimchi table {0} does not have any column")]
+    NoColumn(i32),
+
+    #[error("the column {column} of the kimchi table {id} has {got} rows instead of {expected}")]
+    RaggedColumn {
+        id: i32,
+        column: usize,
+        expected: usize,
+        got: usize,
+    },
+
+    #[error("the kimchi table {id} has {got} rows, but the table {table_id} has {expected}")]
+    LengthMismatch {
+        id: i32,
+        table_id: u32,
+        expected: usize,
+        got: usize,
+    },
+}
+
+/// Convert the kimchi table `table` into the fixed table of `ID` it is
+/// registered as by `registry`, which maps the IDs of the kimchi tables to
+/// the tables of `ID`. The entry `i` of the result is the row `i` of the
+/// columns of `table`.
+/// The columns of `table` must have the same length, which must be the
+/// [LookupTableID::length] of the registered table.
+pub fn from_kimchi_table<F: Field, ID: LookupTableID>(
+    table: &KimchiLookupTable<F>,
+    registry: impl Fn(i32) -> Option<ID>,
+) -> Result<LookupTable<F, ID>, KimchiTableError> {
+    let id = table.id;
+    let table_id = registry(id).ok_or(KimchiTableError::Unregistered(id))?;
+    if !table_id.is_fixed() {
+        return Err(KimchiTableError::NotFixed(id, table_id.to_u32()));
+    }
+    let length = table
+        .data
+        .first()
+        .ok_or(KimchiTableError::NoColumn(id))?
+        .len();
+    if let Some((column, got)) = table
+        .data
+        .iter()
+        .map(Vec::len)
+        .enumerate()
+        .find(|(_, got)| *got != length)
+    {
+        return Err(KimchiTableError::RaggedColumn {
+            id,
+            column,
+            expected: length,
+            got,
+        });
+    }
+    if length != table_id.length() {
+        return Err(KimchiTableError::LengthMismatch {
+            id,
+            table_id: table_id.to_u32(),
+            expected: table_id.length(),
+            got: length,
+        });
+    }
+    let entries = (0..length)
+        .map(|i| table.data.iter().map(|column| column[i]).collect())
+        .collect();
+    Ok(LookupTable { table_id, entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        columns::Column,
+        expr::{curr_cell, E},
+        lookup_table_id,
+        mvlookup::{constraint_lookups, MVLookup, MVLookupWitness},
+        proof::ProofInputs,
+        prover::prove,
+        verifier::verify,
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand, Zero};
+    use kimchi::circuits::{
+        domains::EvaluationDomains,
+        lookup::tables::{range_check, xor, RANGE_CHECK_TABLE_ID, XOR_TABLE_ID},
+    };
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+    use std::collections::BTreeMap;
+
+    lookup_table_id! {
+        enum Tables {
+            Xor = fixed(xor::TABLE_SIZE),
+            RangeCheck = fixed(range_check::TABLE_SIZE),
+            Memory = ram,
+        }
+    }
+
+    /// The kimchi tables, registered as the fixed tables of [Tables]
+    fn registry(id: i32) -> Option<Tables> {
+        match id {
+            XOR_TABLE_ID => Some(Tables::Xor),
+            RANGE_CHECK_TABLE_ID => Some(Tables::RangeCheck),
+            _ => None,
+        }
+    }
+
+    /// The lookups of the rows `rows` of `table` and the column of the table
+    fn table_witness(
+        table: &LookupTable<Fp, Tables>,
+        rows: &[usize],
+    ) -> MVLookupWitness<Fp, Tables> {
+        let domain_size = rows.len();
+        let mut m = vec![Fp::zero(); domain_size];
+        for row in rows {
+            m[*row] += Fp::one();
+        }
+        let zeroes = vec![Fp::zero(); table.entries[0].len()];
+        let lookups = rows
+            .iter()
+            .map(|row| MVLookup::new(table.table_id, Fp::one(), &table.entries[*row]))
+            .collect();
+        let table_column = (0..domain_size)
+            .map(|i| {
+                let entry = table.entries.get(i).unwrap_or(&zeroes);
+                MVLookup::new(table.table_id, -m[i], entry)
+            })
+            .collect();
+        MVLookupWitness {
+            f: vec![lookups, table_column],
+            m,
+        }
+    }
+
+    #[test]
+    fn test_kimchi_tables() {
+        const N: usize = 4;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = range_check::TABLE_SIZE;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let kimchi_xor = xor::xor_table::<Fp>();
+        let kimchi_range_check = range_check::range_check_table::<Fp>();
+        let xor = from_kimchi_table(&kimchi_xor, registry).unwrap();
+        let range_check = from_kimchi_table(&kimchi_range_check, registry).unwrap();
+        for (table, kimchi_table) in [(&xor, &kimchi_xor), (&range_check, &kimchi_range_check)] {
+            assert_eq!(table.entries.len(), kimchi_table.len());
+            for (i, entry) in table.entries.iter().enumerate() {
+                assert_eq!(entry.len(), kimchi_table.width());
+                for (j, value) in entry.iter().enumerate() {
+                    assert_eq!(*value, kimchi_table.data[j][i]);
+                }
+            }
+        }
+        assert_eq!(xor.table_id, Tables::Xor);
+        assert_eq!(range_check.table_id, Tables::RangeCheck);
+
+        // x[0] is range checked, and x[3] = x[1] ^ x[2] on 4 bits
+        let cell = |i| curr_cell::<Fp>(Column::X(i));
+        let lookups = BTreeMap::from([
+            (
+                Tables::Xor,
+                vec![MVLookup::new(
+                    Tables::Xor,
+                    E::one(),
+                    &[cell(1), cell(2), cell(3)],
+                )],
+            ),
+            (
+                Tables::RangeCheck,
+                vec![MVLookup::new(Tables::RangeCheck, E::one(), &[cell(0)])],
+            ),
+        ]);
+        let constraints = constraint_lookups(&lookups);
+        let xor_rows: Vec<usize> = (0..domain_size)
+            .map(|_| rng.gen_range(0..xor.entries.len()))
+            .collect();
+        let range_check_rows: Vec<usize> = (0..domain_size)
+            .map(|_| rng.gen_range(0..range_check.entries.len()))
+            .collect();
+        let mut cols = vec![range_check_rows
+            .iter()
+            .map(|row| range_check.entries[*row][0])
+            .collect::<Vec<_>>()];
+        cols.extend((0..3).map(|j| xor_rows.iter().map(|row| xor.entries[*row][j]).collect()));
+        let inputs = ProofInputs::<N, BN254G1Affine, Tables> {
+            evaluations: Witness {
+                cols: Box::new(cols.try_into().unwrap()),
+            },
+            mvlookups: vec![
+                table_witness(&xor, &xor_rows),
+                table_witness(&range_check, &range_check_rows),
+            ],
+        };
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, Tables>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            Tables,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        ));
+    }
+
+    #[test]
+    fn test_kimchi_table_errors() {
+        let xor = xor::xor_table::<Fp>();
+        let table = |id, data| KimchiLookupTable { id, data };
+        assert_eq!(
+            from_kimchi_table(&table(-1, xor.data.clone()), registry).unwrap_err(),
+            KimchiTableError::Unregistered(-1)
+        );
+        assert_eq!(
+            from_kimchi_table(&xor, |_| Some(Tables::Memory)).unwrap_err(),
+            KimchiTableError::NotFixed(XOR_TABLE_ID, Tables::Memory.to_u32())
+        );
+        assert_eq!(
+            from_kimchi_table(&table(XOR_TABLE_ID, vec![]), registry).unwrap_err(),
+            KimchiTableError::NoColumn(XOR_TABLE_ID)
+        );
+        let mut ragged = xor.data.clone();
+        ragged[2].pop();
+        assert_eq!(
+            from_kimchi_table(&table(XOR_TABLE_ID, ragged), registry).unwrap_err(),
+            KimchiTableError::RaggedColumn {
+                id: XOR_TABLE_ID,
+                column: 2,
+                expected: xor::TABLE_SIZE,
+                got: xor::TABLE_SIZE - 1
+            }
+        );
+        // The XOR table registered as the range check table
+        assert_eq!(
+            from_kimchi_table(&table(RANGE_CHECK_TABLE_ID, xor.data), registry).unwrap_err(),
+            KimchiTableError::LengthMismatch {
+                id: RANGE_CHECK_TABLE_ID,
+                table_id: Tables::RangeCheck.to_u32(),
+                expected: range_check::TABLE_SIZE,
+                got: xor::TABLE_SIZE
+            }
+        );
+    }
+}
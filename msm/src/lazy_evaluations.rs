@@ -0,0 +1,655 @@
+//! Verification of serialized proofs without decoding all their evaluations
+//! up front, for the verifiers whose memory is bounded.
+//!
+//! [Proof::to_indexed_bytes] encodes a proof with its evaluations apart from
+//! the rest of the proof:
+//! - the length of the body, as a little-endian `u32`,
+//! - the body, i.e. the proof in MessagePack, each evaluation being replaced
+//!   by its index,
+//! - the number `k` of evaluations, as a little-endian `u32`,
+//! - the index section, i.e. the `k + 1` offsets of the evaluations in the
+//!   data section, as little-endian `u32`,
+//! - the data section, i.e. the evaluations at each point, in the canonical
+//!   encoding of the field elements.
+//!
+//! [decode_indexed_proof] decodes the body and checks the index section, the
+//! lengths of the evaluations and the absence of trailing bytes. Each
+//! evaluation is a [LazyEvaluations], decoded on its first access, e.g. by
+//! [ColumnEvaluations::evaluate], and then cached. An evaluation which is not
+//! canonically encoded is only found when it is accessed: it is replaced by
+//! zero and the error is recorded, and [LazyProof::verify] rejects the proof.
+//!
+//! [ColumnEvaluations::evaluate]: kimchi::circuits::expr::ColumnEvaluations::evaluate
+
+use crate::{
+    config::SrsLabel,
+    fuzz::DeserializeError,
+    mvlookup::{AccumulatorBoundary, EncodedLookupProof, LookupProof, LookupTableID},
+    proof::{
+        serde_shifted_evals, EncodedProofCommitments, EvaluationContainer, Proof, ProofCommitments,
+        ProofEvaluations, PublicOutputs,
+    },
+    verifier::{VerifierContext, VerifierError},
+    witness::Witness,
+};
+use ark_ec::AffineCurve;
+use ark_ff::{Field, Zero};
+use ark_serialize::CanonicalSerialize;
+use kimchi::{curve::KimchiCurve, plonk_sponge::FrSponge, proof::PointEvaluations};
+use mina_poseidon::FqSponge;
+use poly_commitment::OpenProof;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::serde_as;
+use std::{
+    cell::Cell,
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, OnceLock},
+};
+
+/// The data section of an indexed proof, and the evaluations decoded so far
+struct EvaluationSource<'a, F, Eval> {
+    data: &'a [u8],
+    /// The `k + 1` offsets of the `k` evaluations in `data`
+    offsets: Vec<u32>,
+    /// The evaluations decoded so far, boxed for the evaluations which are
+    /// not decoded to only take a pointer
+    cache: Vec<OnceLock<Box<Eval>>>,
+    /// The first error found when decoding an evaluation
+    error: OnceLock<String>,
+    _field: PhantomData<F>,
+}
+
+impl<'a, F: Field, Eval: EvaluationContainer<F>> EvaluationSource<'a, F, Eval> {
+    fn decode(&self, index: usize) -> Result<Eval, String> {
+        let (start, end) = (
+            self.offsets[index] as usize,
+            self.offsets[index + 1] as usize,
+        );
+        let mut bytes = &self.data[start..end];
+        let points = (0..Eval::OPENING_MODE.n_points())
+            .map(|_| F::deserialize(&mut bytes))
+            .collect::<Result<Vec<F>, _>>()
+            .map_err(|err| format!("the evaluation {index} is not canonical: {err}"))?;
+        if !bytes.is_empty() {
+            return Err(format!("the evaluation {index} has trailing bytes"));
+        }
+        Eval::from_points(points).ok_or_else(|| format!("the evaluation {index} is malformed"))
+    }
+
+    fn get(&self, index: usize) -> &Eval {
+        self.cache[index].get_or_init(|| {
+            Box::new(self.decode(index).unwrap_or_else(|err| {
+                self.error.get_or_init(|| err);
+                Eval::from_points(vec![F::zero(); Eval::OPENING_MODE.n_points()])
+                    .expect("the number of points is the one of the opening mode")
+            }))
+        })
+    }
+}
+
+/// The evaluations of a polynomial in an indexed proof, decoded on their
+/// first access, see the [module documentation](crate::lazy_evaluations).
+/// The evaluations are the ones of the container `Eval`, and follow its
+/// opening mode.
+pub struct LazyEvaluations<'a, F, Eval = PointEvaluations<F>> {
+    source: Arc<EvaluationSource<'a, F, Eval>>,
+    index: usize,
+}
+
+impl<'a, F, Eval> Clone for LazyEvaluations<'a, F, Eval> {
+    fn clone(&self) -> Self {
+        LazyEvaluations {
+            source: self.source.clone(),
+            index: self.index,
+        }
+    }
+}
+
+impl<'a, F, Eval: fmt::Debug> fmt::Debug for LazyEvaluations<'a, F, Eval> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source.cache[self.index].get() {
+            Some(evals) => evals.fmt(f),
+            None => write!(f, "LazyEvaluations({}, not decoded)", self.index),
+        }
+    }
+}
+
+impl<'a, F: Field, Eval: EvaluationContainer<F>> LazyEvaluations<'a, F, Eval> {
+    /// The evaluations, decoded on the first call
+    pub fn get(&self) -> &Eval {
+        self.source.get(self.index)
+    }
+
+    /// Whether the evaluations have been decoded
+    pub fn is_decoded(&self) -> bool {
+        self.source.cache[self.index].get().is_some()
+    }
+}
+
+impl<'a, F: Field, Eval: EvaluationContainer<F>> EvaluationContainer<F>
+    for LazyEvaluations<'a, F, Eval>
+{
+    const OPENING_MODE: crate::config::OpeningMode = Eval::OPENING_MODE;
+
+    /// The evaluations are already decoded
+    fn from_points(evals: Vec<F>) -> Option<Self> {
+        let cache = OnceLock::new();
+        let _ = cache.set(Box::new(Eval::from_points(evals)?));
+        Some(LazyEvaluations {
+            source: Arc::new(EvaluationSource {
+                data: &[],
+                offsets: vec![0, 0],
+                cache: vec![cache],
+                error: OnceLock::new(),
+                _field: PhantomData,
+            }),
+            index: 0,
+        })
+    }
+
+    fn to_points(&self) -> Vec<F> {
+        self.get().to_points()
+    }
+
+    fn for_each_point(&self, f: impl FnMut(&F)) {
+        self.get().for_each_point(f)
+    }
+
+    fn to_point_evaluations(&self) -> PointEvaluations<F> {
+        self.get().to_point_evaluations()
+    }
+}
+
+/// The body of an indexed proof, i.e. a [Proof] whose evaluations are
+/// replaced by their index in the data section. Its maps are kept as the
+/// list of their entries, see [crate::fuzz::Decode].
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "OpeningProof: Serialize",
+    deserialize = "OpeningProof: Deserialize<'de>"
+))]
+struct IndexedProofBody<const N: usize, G: KimchiCurve, OpeningProof: OpenProof<G>> {
+    proof_comms: EncodedProofCommitments<N, G>,
+    witness_evals: Witness<N, u32>,
+    mvlookup_evals: Option<EncodedLookupProof<u32>>,
+    #[serde_as(as = "Option<o1_utils::serialization::SerdeAs>")]
+    ft_eval1: Option<G::ScalarField>,
+    opening_proof: OpeningProof,
+    srs_label: Option<SrsLabel>,
+    public_outputs: PublicOutputs<G::ScalarField>,
+    accumulator: Option<AccumulatorBoundary<G::ScalarField>>,
+    /// The evaluations at ζω² are few, and are not indexed
+    #[serde(
+        serialize_with = "serde_shifted_evals::serialize",
+        deserialize_with = "serde_shifted_evals::deserialize::<N, _, _>"
+    )]
+    shifted_evals: Vec<(usize, G::ScalarField)>,
+    shifted_opening: Option<OpeningProof>,
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G> + Serialize,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > Proof<N, G, OpeningProof, ID, Eval>
+{
+    /// Encode the proof with its evaluations apart from the rest of the
+    /// proof, to decode them lazily, see the
+    /// [module documentation](crate::lazy_evaluations)
+    pub fn to_indexed_bytes(&self) -> Vec<u8> {
+        let mut data = vec![];
+        let mut offsets = vec![0u32];
+        let mut push = |evals: &Eval| {
+            evals.for_each_point(|x| {
+                x.serialize(&mut data)
+                    .expect("the field elements can always be serialized")
+            });
+            offsets.push(data.len() as u32);
+            offsets.len() as u32 - 2
+        };
+        let evals = &self.proof_evals;
+        let witness_evals = Witness {
+            cols: Box::new(std::array::from_fn(|i| push(&evals.witness_evals.cols[i]))),
+        };
+        // The evaluations of the lookup argument are indexed in the order of
+        // its iterator, which is the order of [LookupProof::map]
+        let mvlookup_evals = evals.mvlookup_evals.as_ref().map(|lookup| {
+            let next = Cell::new(N as u32);
+            lookup.into_iter().for_each(|evals| {
+                push(evals);
+            });
+            lookup.clone().map(|_| {
+                let index = next.get();
+                next.set(index + 1);
+                index
+            })
+        });
+        let body = IndexedProofBody::<N, G, OpeningProof> {
+            proof_comms: self.proof_comms.clone().into(),
+            witness_evals,
+            mvlookup_evals: mvlookup_evals.map(Into::into),
+            ft_eval1: evals.ft_eval1,
+            opening_proof: self.opening_proof.clone(),
+            srs_label: self.srs_label.clone(),
+            public_outputs: self.public_outputs.clone(),
+            accumulator: self.accumulator,
+            shifted_evals: evals.shifted_evals.clone().into_iter().collect(),
+            shifted_opening: self.shifted_opening.clone(),
+        };
+        let body = rmp_serde::to_vec(&body).expect("the proof types can always be serialized");
+
+        let mut bytes = Vec::with_capacity(8 + body.len() + 4 * offsets.len() + data.len());
+        bytes.extend((body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        bytes.extend((offsets.len() as u32 - 1).to_le_bytes());
+        offsets
+            .iter()
+            .for_each(|offset| bytes.extend(offset.to_le_bytes()));
+        bytes.extend(data);
+        bytes
+    }
+}
+
+/// A proof decoded by [decode_indexed_proof], whose evaluations are decoded
+/// on their first access
+pub struct LazyProof<
+    'a,
+    const N: usize,
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField> = PointEvaluations<<G as AffineCurve>::ScalarField>,
+> {
+    proof: Proof<N, G, OpeningProof, ID, LazyEvaluations<'a, G::ScalarField, Eval>>,
+    source: Arc<EvaluationSource<'a, G::ScalarField, Eval>>,
+}
+
+impl<
+        'a,
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > LazyProof<'a, N, G, OpeningProof, ID, Eval>
+where
+    OpeningProof::SRS: Sync,
+{
+    /// The proof, whose evaluations are decoded on their first access
+    pub fn proof(
+        &self,
+    ) -> &Proof<N, G, OpeningProof, ID, LazyEvaluations<'a, G::ScalarField, Eval>> {
+        &self.proof
+    }
+
+    /// The first error found when decoding the evaluations accessed so far
+    pub fn decoding_error(&self) -> Option<&str> {
+        self.source.error.get().map(String::as_str)
+    }
+
+    /// The number of evaluations decoded so far
+    pub fn decoded_evaluations(&self) -> usize {
+        self.source
+            .cache
+            .iter()
+            .filter(|evals| evals.get().is_some())
+            .count()
+    }
+
+    /// Verify the proof with `ctx`, see [VerifierContext::verify]. The proof
+    /// is rejected if one of its evaluations is not canonically encoded,
+    /// even if the verification succeeds with the evaluation replaced by
+    /// zero.
+    pub fn verify<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const NPUB: usize,
+    >(
+        &self,
+        ctx: &VerifierContext<G, OpeningProof, ID>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    ) -> Result<(), VerifierError> {
+        let result = ctx.verify::<EFqSponge, EFrSponge, N, NPUB, _>(&self.proof, public_inputs);
+        match self.decoding_error() {
+            Some(err) => Err(VerifierError::Deserialization(err.to_string())),
+            None => result,
+        }
+    }
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, DeserializeError> {
+    if bytes.len() < 4 {
+        return Err(DeserializeError::MalformedProof(
+            "the indexed proof is truncated",
+        ));
+    }
+    let (value, rest) = bytes.split_at(4);
+    *bytes = rest;
+    Ok(u32::from_le_bytes(value.try_into().unwrap()))
+}
+
+/// Decode a proof encoded by [Proof::to_indexed_bytes], without decoding its
+/// evaluations. The offsets of the evaluations are checked to cover the
+/// data section exactly, each with the size of the evaluations of the
+/// opening mode of `Eval`. The evaluations themselves are checked to be
+/// canonical on their first access, see [LazyProof::verify].
+pub fn decode_indexed_proof<
+    const N: usize,
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G> + DeserializeOwned,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    bytes: &[u8],
+) -> Result<LazyProof<'_, N, G, OpeningProof, ID, Eval>, DeserializeError> {
+    let mut bytes = bytes;
+    let body_len = read_u32(&mut bytes)? as usize;
+    if bytes.len() < body_len {
+        return Err(DeserializeError::MalformedProof(
+            "the indexed proof is truncated",
+        ));
+    }
+    let (mut body_bytes, mut bytes) = bytes.split_at(body_len);
+    let body: IndexedProofBody<N, G, OpeningProof> = rmp_serde::decode::from_read(&mut body_bytes)
+        .map_err(|err| DeserializeError::Decoding(err.to_string()))?;
+    if !body_bytes.is_empty() {
+        return Err(DeserializeError::MalformedProof(
+            "the body of the indexed proof has trailing bytes",
+        ));
+    }
+
+    let count = read_u32(&mut bytes)? as usize;
+    if bytes.len() / 4 <= count {
+        return Err(DeserializeError::MalformedProof(
+            "the indexed proof is truncated",
+        ));
+    }
+    let offsets = (0..=count)
+        .map(|_| read_u32(&mut bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data = bytes;
+    let size = Eval::OPENING_MODE.n_points() * G::ScalarField::zero().serialized_size();
+    if offsets[0] != 0
+        || offsets
+            .windows(2)
+            .any(|w| w[1].checked_sub(w[0]) != Some(size as u32))
+    {
+        return Err(DeserializeError::MalformedProof(
+            "the offsets of the evaluations do not match the opening mode",
+        ));
+    }
+    if offsets[count] as usize != data.len() {
+        return Err(DeserializeError::MalformedProof(
+            "the data section of the indexed proof has trailing bytes",
+        ));
+    }
+
+    let source = Arc::new(EvaluationSource {
+        data,
+        offsets,
+        cache: (0..count).map(|_| OnceLock::new()).collect(),
+        error: OnceLock::new(),
+        _field: PhantomData,
+    });
+    // Each evaluation is referred to exactly once by the body
+    let mut referred = vec![false; count];
+    let mut lazy = |index: u32| {
+        let index = index as usize;
+        match referred.get_mut(index) {
+            Some(seen @ false) => {
+                *seen = true;
+                Ok(LazyEvaluations {
+                    source: source.clone(),
+                    index,
+                })
+            }
+            _ => Err(DeserializeError::MalformedProof(
+                "the body of the indexed proof refers to an unknown evaluation",
+            )),
+        }
+    };
+    let witness_evals: [u32; N] = *body.witness_evals.cols;
+    let witness_evals = witness_evals
+        .into_iter()
+        .map(&mut lazy)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mvlookup_evals = match body.mvlookup_evals {
+        None => None,
+        Some(indices) => {
+            let indices = LookupProof::<u32, ID>::try_from(indices)?;
+            let lazy = std::cell::RefCell::new(&mut lazy);
+            let evals = indices.map(|index| (lazy.borrow_mut())(index));
+            if (&evals).into_iter().any(Result::is_err) {
+                return Err(DeserializeError::MalformedProof(
+                    "the body of the indexed proof refers to an unknown evaluation",
+                ));
+            }
+            Some(evals.map(Result::unwrap))
+        }
+    };
+    if referred.contains(&false) {
+        return Err(DeserializeError::MalformedProof(
+            "the indexed proof has evaluations which are not referred to",
+        ));
+    }
+
+    let proof = Proof {
+        proof_comms: ProofCommitments::try_from(body.proof_comms)?,
+        proof_evals: ProofEvaluations {
+            witness_evals: Witness {
+                cols: Box::new(witness_evals.try_into().unwrap_or_else(|_| unreachable!())),
+            },
+            mvlookup_evals,
+            ft_eval1: body.ft_eval1,
+            shifted_evals: serde_shifted_evals::into_map(body.shifted_evals)?,
+        },
+        opening_proof: body.opening_proof,
+        srs_label: body.srs_label,
+        public_outputs: body.public_outputs,
+        accumulator: body.accumulator,
+        shifted_opening: body.shifted_opening,
+    };
+    proof
+        .check_lookup_shape()
+        .map_err(DeserializeError::MalformedProof)?;
+    Ok(LazyProof { proof, source })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        columns::Column,
+        config::ProtocolConfig,
+        expr::{curr_cell, E},
+        lookups::LookupTableIDs,
+        prover::prove,
+        test::random_lookup_circuit,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::UniformRand;
+    use kimchi::circuits::domains::EvaluationDomains;
+    use poly_commitment::pairing_proof::PairingSRS;
+    use std::alloc::{GlobalAlloc, Layout, System};
+
+    /// Counts the bytes allocated by each thread, to compare the memory
+    /// high-water marks of the deserializations
+    struct CountingAllocator;
+
+    thread_local! {
+        static CURRENT: Cell<isize> = const { Cell::new(0) };
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn record(delta: isize) {
+        let _ = CURRENT.try_with(|current| {
+            current.set(current.get() + delta);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(current.get())));
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            record(layout.size() as isize);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            record(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            record(new_size as isize - layout.size() as isize);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// The result of `f`, and the highest number of bytes allocated by the
+    /// thread while running it
+    fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, isize) {
+        let base = CURRENT.with(Cell::get);
+        PEAK.with(|peak| peak.set(base));
+        let res = f();
+        (res, PEAK.with(Cell::get) - base)
+    }
+
+    type Ctx<'a> = VerifierContext<'a, BN254G1Affine, OpeningProof, LookupTableIDs>;
+
+    type TestProof<const N: usize> = Proof<N, BN254G1Affine, OpeningProof, LookupTableIDs>;
+
+    /// A proof of `N` columns, looking up its first 3 columns into a table,
+    /// with `X(4) = X(3)^2` and the other columns random
+    fn wide_proof<const N: usize>(
+        domain: EvaluationDomains<Fp>,
+    ) -> (PairingSRS<BN254>, Vec<E<Fp>>, TestProof<N>) {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let mut srs: PairingSRS<BN254> =
+            PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let (mut constraints, mut inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 8, 3, &mut rng);
+        let x3 = curr_cell::<Fp>(Column::X(3));
+        constraints.push(curr_cell::<Fp>(Column::X(4)) - x3.clone() * x3);
+        let cols = &mut inputs.evaluations.cols;
+        cols[4] = cols[3].iter().map(|x| x.square()).collect();
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        (srs, constraints, proof)
+    }
+
+    fn verify<const N: usize>(
+        ctx: &Ctx,
+        proof: &LazyProof<N, BN254G1Affine, OpeningProof, LookupTableIDs>,
+    ) -> Result<(), VerifierError> {
+        proof.verify::<BaseSponge, ScalarSponge, 0>(ctx, Witness::zero_vec(1))
+    }
+
+    #[test]
+    fn test_lazy_verification_memory() {
+        const N: usize = 200;
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let (srs, constraints, proof) = wide_proof::<N>(domain);
+        let ctx = Ctx::new(domain, &srs, ProtocolConfig::default(), &constraints).unwrap();
+
+        let bytes = rmp_serde::to_vec(&proof).unwrap();
+        let (eager, eager_peak) =
+            peak_allocation(|| rmp_serde::from_slice::<TestProof<N>>(&bytes).unwrap());
+        assert_eq!(eager, proof);
+
+        let indexed = proof.to_indexed_bytes();
+        let (lazy, lazy_peak) = peak_allocation(|| {
+            decode_indexed_proof::<
+                N,
+                BN254G1Affine,
+                OpeningProof,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&indexed)
+            .unwrap()
+        });
+        assert!(
+            lazy_peak < eager_peak,
+            "the lazy deserialization allocates {lazy_peak} bytes, the eager one {eager_peak}"
+        );
+        assert_eq!(lazy.decoded_evaluations(), 0);
+
+        // The evaluations are decoded by the verifier, and are the ones of the
+        // proof
+        verify(&ctx, &lazy).unwrap();
+        let n_evals = N
+            + (&proof.proof_evals.mvlookup_evals.unwrap())
+                .into_iter()
+                .count();
+        assert_eq!(lazy.decoded_evaluations(), n_evals);
+        assert_eq!(
+            lazy.proof().proof_evals.witness_evals.cols[1].get(),
+            &proof.proof_evals.witness_evals.cols[1]
+        );
+    }
+
+    #[test]
+    fn test_lazy_decoding_errors() {
+        const N: usize = 5;
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let (srs, constraints, proof) = wide_proof::<N>(domain);
+        let ctx = Ctx::new(domain, &srs, ProtocolConfig::default(), &constraints).unwrap();
+        let indexed = proof.to_indexed_bytes();
+        let decode = |bytes: &[u8]| {
+            decode_indexed_proof::<
+                N,
+                BN254G1Affine,
+                OpeningProof,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(bytes)
+            .map(|_| ())
+        };
+        decode(&indexed).unwrap();
+
+        // The trailing bytes and the truncations are found up front
+        let mut trailing = indexed.clone();
+        trailing.push(0);
+        assert_eq!(
+            decode(&trailing),
+            Err(DeserializeError::MalformedProof(
+                "the data section of the indexed proof has trailing bytes"
+            ))
+        );
+        assert!(decode(&indexed[..indexed.len() - 1]).is_err());
+        assert!(decode(&indexed[..3]).is_err());
+
+        // A non-canonical evaluation, i.e. not reduced modulo the order of the
+        // field, is found when it is accessed, and the proof is rejected
+        let mut non_canonical = indexed.clone();
+        let len = non_canonical.len();
+        non_canonical[len - 32..].fill(0xff);
+        let lazy = decode_indexed_proof::<
+            N,
+            BN254G1Affine,
+            OpeningProof,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&non_canonical)
+        .unwrap();
+        assert!(matches!(
+            verify(&ctx, &lazy),
+            Err(VerifierError::Deserialization(_))
+        ));
+        assert!(lazy.decoding_error().is_some());
+    }
+}
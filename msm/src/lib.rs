@@ -1,3 +1,10 @@
+// Without the feature `unstable-internals`, the items of the internal modules
+// which are not re-exported in [api] are unused outside of the tests
+#![cfg_attr(
+    not(feature = "unstable-internals"),
+    allow(dead_code, clippy::extra_unused_type_parameters)
+)]
+
 use mina_poseidon::{
     constants::PlonkSpongeConstantsKimchi,
     sponge::{DefaultFqSponge, DefaultFrSponge},
@@ -9,24 +16,256 @@ pub use mvlookup::{
     LookupTableID, MVLookup, MVLookupWitness,
 };
 
+pub mod api;
+
+// The internal modules carry no guarantee between two versions: they are
+// public with the feature `unstable-internals`, e.g. for the benches, and
+// private to the crate otherwise. The stable items are re-exported in [api].
+#[cfg(feature = "unstable-internals")]
+pub mod access_patterns;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod access_patterns;
+#[cfg(any(
+    feature = "curve-bn254",
+    feature = "curve-pallas",
+    feature = "curve-vesta"
+))]
+#[cfg(feature = "unstable-internals")]
+pub mod any_proof;
+#[cfg(any(
+    feature = "curve-bn254",
+    feature = "curve-pallas",
+    feature = "curve-vesta"
+))]
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod any_proof;
+#[cfg(feature = "unstable-internals")]
+pub mod audit;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod audit;
+#[cfg(feature = "availability")]
+#[cfg(feature = "unstable-internals")]
+pub mod availability;
+#[cfg(feature = "availability")]
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod availability;
+#[cfg(feature = "unstable-internals")]
+pub mod boundary;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod boundary;
+#[cfg(feature = "unstable-internals")]
+pub mod checkpoint;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod checkpoint;
+#[cfg(feature = "unstable-internals")]
+pub mod circuit;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod circuit;
+#[cfg(feature = "unstable-internals")]
 pub mod column_env;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod column_env;
+#[cfg(feature = "unstable-internals")]
+pub mod column_permutation;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod column_permutation;
+#[cfg(feature = "unstable-internals")]
 pub mod columns;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod columns;
+#[cfg(feature = "unstable-internals")]
+pub mod committer;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod committer;
+#[cfg(feature = "unstable-internals")]
+pub mod config;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod config;
+#[cfg(feature = "unstable-internals")]
+pub mod constant_columns;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod constant_columns;
+#[cfg(feature = "unstable-internals")]
+pub mod cost;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod cost;
+#[cfg(feature = "unstable-internals")]
+pub mod domain;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod domain;
+#[cfg(feature = "unstable-internals")]
+pub mod dry_run;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod dry_run;
+#[cfg(feature = "unstable-internals")]
+pub mod equivalence;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod equivalence;
+#[cfg(feature = "unstable-internals")]
+pub mod evaluation_layout;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod evaluation_layout;
+#[cfg(feature = "unstable-internals")]
 pub mod expr;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod expr;
+#[cfg(feature = "unstable-internals")]
+pub mod fixtures;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod fixtures;
+#[cfg(feature = "unstable-internals")]
+pub mod fuzz;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod fuzz;
+#[cfg(feature = "unstable-internals")]
+pub mod hashed_lookup;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod hashed_lookup;
+#[cfg(feature = "unstable-internals")]
+pub mod inspect;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod inspect;
+#[cfg(feature = "unstable-internals")]
 pub mod interpreter;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod interpreter;
+#[cfg(feature = "unstable-internals")]
+pub mod kimchi_tables;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod kimchi_tables;
+#[cfg(feature = "unstable-internals")]
+pub mod lazy_evaluations;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod lazy_evaluations;
+#[cfg(feature = "unstable-internals")]
+pub mod lookup_engine;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod lookup_engine;
+#[cfg(feature = "unstable-internals")]
+pub mod lookup_fingerprint;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod lookup_fingerprint;
+#[cfg(feature = "unstable-internals")]
+pub mod lookup_stats;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod lookup_stats;
+#[cfg(feature = "unstable-internals")]
+pub mod lookup_trace;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod lookup_trace;
 /// Instantiations of MVLookups for the MSM project
+#[cfg(feature = "unstable-internals")]
 pub mod lookups;
+/// Instantiations of MVLookups for the MSM project
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod lookups;
+#[cfg(feature = "unstable-internals")]
+pub mod membership;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod membership;
 /// Generic definitions of MVLookups
+#[cfg(feature = "unstable-internals")]
 pub mod mvlookup;
+/// Generic definitions of MVLookups
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod mvlookup;
+#[cfg(feature = "unstable-internals")]
+pub mod packing;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod packing;
+#[cfg(feature = "unstable-internals")]
+pub mod perf;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod perf;
+#[cfg(feature = "unstable-internals")]
 pub mod precomputed_srs;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod precomputed_srs;
+#[cfg(feature = "unstable-internals")]
 pub mod proof;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod proof;
+#[cfg(feature = "unstable-internals")]
+pub mod proof_header;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod proof_header;
+#[cfg(feature = "unstable-internals")]
 pub mod prover;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod prover;
+#[cfg(feature = "unstable-internals")]
+pub mod public_schema;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod public_schema;
+#[cfg(feature = "unstable-internals")]
+pub mod shifts;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod shifts;
+#[cfg(feature = "unstable-internals")]
+pub mod simplify;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod simplify;
+#[cfg(feature = "unstable-internals")]
+pub mod subdomain;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod subdomain;
+#[cfg(feature = "unstable-internals")]
+pub mod table_suggestions;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod table_suggestions;
+#[cfg(feature = "unstable-internals")]
+pub mod trace;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod trace;
+#[cfg(feature = "unstable-internals")]
+pub mod transcript;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod transcript;
+#[cfg(feature = "unstable-internals")]
+pub mod verification_cache;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod verification_cache;
+#[cfg(feature = "unstable-internals")]
 pub mod verifier;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod verifier;
+#[cfg(feature = "unstable-internals")]
+pub mod wide_lookup;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod wide_lookup;
+#[cfg(feature = "unstable-internals")]
+pub mod window;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod window;
+#[cfg(feature = "unstable-internals")]
 pub mod witness;
-
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod witness;
+#[cfg(feature = "unstable-internals")]
+pub mod write_once;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod write_once;
+
+#[cfg(feature = "unstable-internals")]
 pub mod fec;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod fec;
+#[cfg(feature = "unstable-internals")]
 pub mod ffa;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod ffa;
+#[cfg(feature = "unstable-internals")]
 pub mod serialization;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod serialization;
+#[cfg(feature = "unstable-internals")]
 pub mod test;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod test;
+#[cfg(feature = "unstable-internals")]
+pub mod testing;
+#[cfg(not(feature = "unstable-internals"))]
+pub(crate) mod testing;
 
 /// Define the maximum degree we support for the evaluations.
 /// For instance, it can be used to split the looked-up functions into partial
@@ -0,0 +1,1039 @@
+//! The engines of the lookup argument, proving that the values looked up are
+//! in their tables.
+//!
+//! MVLookup, see [crate::mvlookup], is the engine of the tables by default.
+//! For a small fixed table of a single column, looked up exactly once per
+//! row, the plookup-style argument of [PlookupEngine] does not invert the
+//! looked-up values: the prover sorts them in the order of the table, and
+//! proves with a single grand product that the sorted columns are
+//! permutations of the original ones.
+//!
+//! [LookupEngines] selects the engine of each table, and builds the
+//! constraints of the lookups accordingly. The prover and the verifier read
+//! the engine of each table from the columns the constraints read, see
+//! [LookupEngines::of_constraints].
+//!
+//! Each engine owns its columns, committed in two phases: the ones of
+//! [LookupEngine::first_phase] before the challenges of the lookup argument
+//! are coined, and the ones of [LookupEngine::second_phase] after. The
+//! columns of the plookup engine are given in the proofs apart from the ones
+//! of MVLookup, see [crate::mvlookup::LookupProof::plookup].
+
+use ark_ff::{batch_inversion, One, PrimeField};
+use kimchi::circuits::expr::{ChallengeTerm, ConstantExpr, ExprInner};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use thiserror::Error;
+
+use crate::{
+    boundary,
+    columns::{Column, PartialSumIdx, TableIdx},
+    expr::{column_accesses, curr_cell, next_cell, E},
+    mvlookup::{
+        constraint_lookups, is_constant_one,
+        prover::{partial_sums, unbalanced_lookups},
+        LookupLayout, LookupTableID, MVLookup, MVLookupWitness,
+    },
+    perf::PerfCounters,
+    prover::ProverError,
+};
+
+/// The lookups of a circuit, by table
+pub type LookupsMap<F, ID> = BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>;
+
+/// Errors found when building the constraints of the lookups with an engine,
+/// or when computing its columns
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LookupEngineError {
+    #[error("the table {0} of the plookup engine is not a fixed table")]
+    NotFixed(u32),
+
+    #[error("the table {table_id} of the plookup engine is looked up {got} times per row instead of once")]
+    QueryCount { table_id: u32, got: usize },
+
+    #[error("the lookups into the table {table_id} of the plookup engine have {width} values instead of one")]
+    NotSingleColumn { table_id: u32, width: usize },
+
+    #[error("the lookup into the table {0} of the plookup engine is not performed on every row")]
+    ConditionalLookup(u32),
+
+    #[error("the multiplicities of the table {0} are bounded, which the plookup engine does not support")]
+    BoundedMultiplicities(u32),
+
+    #[error("the witness of the table {0} of the plookup engine does not look up a single value on every row")]
+    WitnessShape(u32),
+
+    #[error("the table {0} of the plookup engine has no witness")]
+    MissingWitness(u32),
+
+    #[error(
+        "the table {0} of the plookup engine is padded with zero rows, but does not contain zero"
+    )]
+    ZeroNotInTable(u32),
+}
+
+/// The engine proving the lookups into a table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LookupEngineKind {
+    /// The logarithmic derivative argument, see [MVLookupEngine]
+    #[default]
+    MVLookup,
+    /// The sorted argument with a grand product, see [PlookupEngine]
+    Plookup,
+}
+
+/// The challenges of the lookup argument, coined after the columns of the
+/// first phase of the engines are committed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LookupChallenges<F> {
+    /// The combiner of the values of a vector lookup
+    pub joint_combiner: F,
+    /// The evaluation point of MVLookup, and the shift of the looked-up
+    /// values of the plookup engine
+    pub beta: F,
+    /// The shift of the values of the tables of the plookup engine
+    pub gamma: F,
+}
+
+/// An argument proving the lookups into the tables it is selected for.
+/// The engine owns its columns, the constraints on them, and the computation
+/// of their values from the witnesses of the lookups.
+pub trait LookupEngine<F: PrimeField, ID: LookupTableID> {
+    /// The engine, as selected in [LookupEngines]
+    const KIND: LookupEngineKind;
+
+    /// The columns committed for the lookups `lookups_map`, in the order of
+    /// their commitments in the proofs
+    fn columns(&self, lookups_map: &LookupsMap<F, ID>) -> Vec<Column>;
+
+    /// The constraints of the lookups `lookups_map`
+    fn constraints(&self, lookups_map: &LookupsMap<F, ID>) -> Result<Vec<E<F>>, LookupEngineError>;
+
+    /// The values over d1 of the columns committed before the challenges,
+    /// computed from the witnesses of the lookups
+    fn first_phase(
+        &self,
+        witnesses: &[MVLookupWitness<F, ID>],
+        domain_size: usize,
+    ) -> Result<Vec<(Column, Vec<F>)>, ProverError>;
+
+    /// The values over d1 of the columns committed after the challenges,
+    /// given the ones of the first phase
+    fn second_phase(
+        &self,
+        witnesses: &[MVLookupWitness<F, ID>],
+        first_phase: &[(Column, Vec<F>)],
+        challenges: &LookupChallenges<F>,
+        domain_size: usize,
+    ) -> Result<Vec<(Column, Vec<F>)>, ProverError>;
+}
+
+/// The witnesses sorted by table ID, which is the order of the columns
+fn sorted_witnesses<F: PrimeField, ID: LookupTableID>(
+    witnesses: &[MVLookupWitness<F, ID>],
+) -> Vec<&MVLookupWitness<F, ID>> {
+    let mut witnesses: Vec<_> = witnesses.iter().collect();
+    witnesses.sort_by_key(|witness| witness.table_id());
+    witnesses
+}
+
+/// The MVLookup argument, see [crate::mvlookup]. The multiplicities are
+/// committed in the first phase, the partial sums, the running sum `φ` and
+/// the combined fixed tables in the second one.
+/// The columns are the ones of the [crate::mvlookup::LookupAggregationMode::Committed]
+/// mode, in the forward direction, which [crate::mvlookup::prover::Env::create]
+/// computes along with the other modes of the configuration.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MVLookupEngine;
+
+impl<F: PrimeField, ID: LookupTableID> LookupEngine<F, ID> for MVLookupEngine {
+    const KIND: LookupEngineKind = LookupEngineKind::MVLookup;
+
+    fn columns(&self, lookups_map: &LookupsMap<F, ID>) -> Vec<Column> {
+        let layout = LookupLayout::new(lookups_map);
+        let tables: Vec<ID> = layout.table_ids().collect();
+        tables
+            .iter()
+            .filter_map(|table_id| layout.multiplicity(*table_id))
+            .chain(layout.partial_sums())
+            .chain([Column::LookupAggregation])
+            .chain(
+                tables
+                    .iter()
+                    .filter_map(|table_id| layout.fixed_table(*table_id)),
+            )
+            .collect()
+    }
+
+    fn constraints(&self, lookups_map: &LookupsMap<F, ID>) -> Result<Vec<E<F>>, LookupEngineError> {
+        Ok(constraint_lookups(lookups_map))
+    }
+
+    fn first_phase(
+        &self,
+        witnesses: &[MVLookupWitness<F, ID>],
+        _domain_size: usize,
+    ) -> Result<Vec<(Column, Vec<F>)>, ProverError> {
+        Ok(sorted_witnesses(witnesses)
+            .into_iter()
+            .filter_map(|witness| {
+                let table_id = witness.table_id().filter(ID::is_fixed)?;
+                let idx = TableIdx::new(table_id.to_u32());
+                Some((Column::LookupMultiplicity(idx), witness.m.clone()))
+            })
+            .collect())
+    }
+
+    fn second_phase(
+        &self,
+        witnesses: &[MVLookupWitness<F, ID>],
+        _first_phase: &[(Column, Vec<F>)],
+        challenges: &LookupChallenges<F>,
+        domain_size: usize,
+    ) -> Result<Vec<(Column, Vec<F>)>, ProverError> {
+        let witnesses: Vec<_> = sorted_witnesses(witnesses).into_iter().cloned().collect();
+        let (h, fixed_tables) = partial_sums(
+            &witnesses,
+            challenges.beta,
+            challenges.joint_combiner,
+            domain_size,
+            &PerfCounters::default(),
+        );
+        // φ(1) = 0, and φ(ω^{i + 1}) = φ(ω^i) + \sum_j h_j(ω^i)
+        let mut phi = Vec::with_capacity(domain_size);
+        let mut acc = F::zero();
+        for i in 0..domain_size {
+            phi.push(acc);
+            acc += h.iter().map(|h| h[i]).sum::<F>();
+        }
+        if !acc.is_zero() {
+            return Err(unbalanced_lookups(&witnesses, h.iter().map(|h| &h[..])));
+        }
+        Ok(h.into_iter()
+            .enumerate()
+            .map(|(i, h)| (Column::LookupPartialSum(PartialSumIdx::new(i)), h))
+            .chain([(Column::LookupAggregation, phi)])
+            .chain(fixed_tables.into_iter().map(|(table_id, t)| {
+                (
+                    Column::LookupFixedTable(TableIdx::new(table_id.to_u32())),
+                    t,
+                )
+            }))
+            .collect())
+    }
+}
+
+/// The columns committed by the [PlookupEngine] for each of its tables
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum PlookupColumn {
+    /// The values `t` of the table, padded with zero rows like the
+    /// commitments of [crate::mvlookup::generate_global_tables]
+    Table,
+    /// The looked-up values `a'`, sorted in the order of the table
+    SortedQuery,
+    /// The values `s'` of the table, permuted so that the first row of each
+    /// distinct value of `a'` has the same value in `s'`
+    SortedTable,
+    /// The grand product `z` of the permutations
+    Product,
+}
+
+impl PlookupColumn {
+    /// The columns, in the order of their commitments
+    pub const ALL: [PlookupColumn; 4] = [
+        PlookupColumn::Table,
+        PlookupColumn::SortedQuery,
+        PlookupColumn::SortedTable,
+        PlookupColumn::Product,
+    ];
+
+    /// The columns committed before the challenges of the lookup argument
+    pub const FIRST_PHASE: [PlookupColumn; 3] = [
+        PlookupColumn::Table,
+        PlookupColumn::SortedQuery,
+        PlookupColumn::SortedTable,
+    ];
+}
+
+impl std::fmt::Display for PlookupColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlookupColumn::Table => write!(f, "table"),
+            PlookupColumn::SortedQuery => write!(f, "sorted values"),
+            PlookupColumn::SortedTable => write!(f, "sorted table"),
+            PlookupColumn::Product => write!(f, "grand product"),
+        }
+    }
+}
+
+/// A value for each [PlookupColumn] of a table, e.g. their commitments or
+/// their evaluations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlookupColumns<T> {
+    pub table: T,
+    pub sorted_query: T,
+    pub sorted_table: T,
+    pub product: T,
+}
+
+impl<T> PlookupColumns<T> {
+    /// The value of `column`
+    pub fn get(&self, column: PlookupColumn) -> &T {
+        match column {
+            PlookupColumn::Table => &self.table,
+            PlookupColumn::SortedQuery => &self.sorted_query,
+            PlookupColumn::SortedTable => &self.sorted_table,
+            PlookupColumn::Product => &self.product,
+        }
+    }
+
+    /// The values in the order of [PlookupColumn::ALL]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        PlookupColumn::ALL
+            .into_iter()
+            .map(|column| self.get(column))
+    }
+
+    /// The values of the columns committed before the challenges
+    pub fn first_phase(&self) -> [&T; 3] {
+        [&self.table, &self.sorted_query, &self.sorted_table]
+    }
+
+    /// Apply `f` to each value, in the order of [PlookupColumn::ALL]
+    pub fn map<U, FN: FnMut(T) -> U>(self, mut f: FN) -> PlookupColumns<U> {
+        PlookupColumns {
+            table: f(self.table),
+            sorted_query: f(self.sorted_query),
+            sorted_table: f(self.sorted_table),
+            product: f(self.product),
+        }
+    }
+
+    pub fn as_ref(&self) -> PlookupColumns<&T> {
+        PlookupColumns {
+            table: &self.table,
+            sorted_query: &self.sorted_query,
+            sorted_table: &self.sorted_table,
+            product: &self.product,
+        }
+    }
+
+    /// Build the values from the values of the columns [PlookupColumn::ALL],
+    /// if they are all given
+    pub fn from_vec(values: Vec<T>) -> Option<Self> {
+        let [table, sorted_query, sorted_table, product]: [T; 4] = values.try_into().ok()?;
+        Some(PlookupColumns {
+            table,
+            sorted_query,
+            sorted_table,
+            product,
+        })
+    }
+
+    /// The values in the order of [PlookupColumn::ALL]
+    pub fn into_vec(self) -> Vec<T> {
+        vec![
+            self.table,
+            self.sorted_query,
+            self.sorted_table,
+            self.product,
+        ]
+    }
+
+    /// Group the values of the plookup columns `columns` by table. Returns
+    /// `None` if a column is not a plookup column, or if a column of a table
+    /// is missing or given twice.
+    pub fn by_table<ID: LookupTableID>(
+        columns: impl IntoIterator<Item = (Column, T)>,
+    ) -> Option<BTreeMap<ID, Self>> {
+        let mut tables: BTreeMap<ID, BTreeMap<PlookupColumn, T>> = BTreeMap::new();
+        for (column, value) in columns {
+            let Column::Plookup(idx, column) = column else {
+                return None;
+            };
+            let table = tables.entry(ID::try_from_u32(idx.to_u32())?).or_default();
+            if table.insert(column, value).is_some() {
+                return None;
+            }
+        }
+        tables
+            .into_iter()
+            .map(|(table_id, columns)| {
+                Some((table_id, Self::from_vec(columns.into_values().collect())?))
+            })
+            .collect()
+    }
+}
+
+/// The plookup-style argument for a fixed table `t` of a single column,
+/// looked up exactly once per row with the values `a`.
+/// In the first phase, the prover commits to `t`, and to the sorted columns
+/// `a'` and `s'`: `a'` is a permutation of `a` where the equal values are
+/// adjacent, in the order of the table, and `s'` is a permutation of `t`
+/// where the first row of each distinct value of `a'` has the same value. In
+/// the second phase, it commits to the grand product `z`, for the challenges
+/// `β` and `γ`:
+/// ```text
+/// z(1) = 1
+/// z(ω^{i + 1}) = z(ω^i) * (a(ω^i) + β) * (t(ω^i) + γ) / ((a'(ω^i) + β) * (s'(ω^i) + γ))
+/// ```
+/// The constraints are, with `L_0` the selector of the first row:
+/// ```text
+/// z(ωX) * (a'(X) + β) * (s'(X) + γ) - z(X) * (a(X) + β) * (t(X) + γ) = 0
+/// L_0(X) * (z(X) - 1) = 0
+/// L_0(X) * (a'(X) - s'(X)) = 0
+/// (a'(ωX) - s'(ωX)) * (a'(ωX) - a'(X)) = 0
+/// ```
+/// The first two ones prove that `a'` is a permutation of `a` and `s'` one of
+/// `t`, as `β` and `γ` are independent. The last two ones prove that each
+/// value of `a'` is either the value of `s'` on the same row, or the value of
+/// `a'` on the previous row, the first row being in the first case. On the
+/// last row, the next row is the first one, which satisfies the constraint.
+/// No value is inverted but the denominators of `z`, with a single batch
+/// inversion, and the constraints have degree 3 for values `a` of degree 1.
+///
+/// The table is padded with zero rows, like the tables committed by
+/// [crate::mvlookup::generate_global_tables]: zero is a value of the table
+/// for the argument, and the engine must only be selected for tables
+/// containing zero or filling the domain, which
+/// [crate::circuit::CircuitDefinition::new] checks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlookupEngine;
+
+/// The challenge `term` as an expression
+fn challenge<F: PrimeField>(term: ChallengeTerm) -> E<F> {
+    E::Atom(ExprInner::Constant(ConstantExpr::from(term)))
+}
+
+/// The value looked up on each row into the table `table_id` of the plookup
+/// engine, the only one of `lookups`
+fn plookup_value<F: PrimeField, ID: LookupTableID>(
+    table_id: ID,
+    lookups: &[MVLookup<E<F>, ID>],
+) -> Result<&E<F>, LookupEngineError> {
+    let id = table_id.to_u32();
+    if !table_id.is_fixed() {
+        return Err(LookupEngineError::NotFixed(id));
+    }
+    if table_id.multiplicity_bound().is_some() {
+        return Err(LookupEngineError::BoundedMultiplicities(id));
+    }
+    let [lookup] = lookups else {
+        return Err(LookupEngineError::QueryCount {
+            table_id: id,
+            got: lookups.len(),
+        });
+    };
+    let [value] = lookup.value.as_slice() else {
+        return Err(LookupEngineError::NotSingleColumn {
+            table_id: id,
+            width: lookup.value.len(),
+        });
+    };
+    if !is_constant_one(&lookup.numerator) {
+        return Err(LookupEngineError::ConditionalLookup(id));
+    }
+    Ok(value)
+}
+
+/// The table of `witness`, the values looked up on each row, and the values
+/// of the table, if the witness has the shape expected by the plookup engine:
+/// a column of values looked up unconditionally, and the column of the table
+fn plookup_witness<F: PrimeField, ID: LookupTableID>(
+    witness: &MVLookupWitness<F, ID>,
+    domain_size: usize,
+) -> Result<(ID, Vec<F>, Vec<F>), ProverError> {
+    let table_id = witness
+        .table_id()
+        .ok_or(ProverError::Internal("a lookup witness has no table"))?;
+    let shape_error =
+        || ProverError::LookupEngine(LookupEngineError::WitnessShape(table_id.to_u32()));
+    let [values, table] = witness.f.as_slice() else {
+        return Err(shape_error());
+    };
+    let single = |lookup: &MVLookup<F, ID>| match lookup.value.as_slice() {
+        [x] => Some(*x),
+        _ => None,
+    };
+    let values: Vec<F> = values
+        .iter()
+        .map(|lookup| single(lookup).filter(|_| lookup.numerator.is_one()))
+        .collect::<Option<_>>()
+        .ok_or_else(shape_error)?;
+    let table: Vec<F> = table
+        .iter()
+        .map(single)
+        .collect::<Option<_>>()
+        .ok_or_else(shape_error)?;
+    if values.len() != domain_size || table.len() != domain_size {
+        return Err(shape_error());
+    }
+    Ok((table_id, values, table))
+}
+
+/// Sort `values` in the order of `table`, returning the columns `a'` and `s'`
+/// of [PlookupEngine], or `None` if a value is not in the table
+fn sort_by_table<F: PrimeField>(values: &[F], table: &[F]) -> Option<(Vec<F>, Vec<F>)> {
+    let mut counts: HashMap<F, usize> = HashMap::new();
+    for x in values {
+        *counts.entry(*x).or_insert(0) += 1;
+    }
+    let mut sorted_query = Vec::with_capacity(values.len());
+    // The rows after the first one of each value are filled with the rows of
+    // the table which are not used, in any order
+    let mut sorted_table: Vec<Option<F>> = Vec::with_capacity(table.len());
+    let mut unused = vec![];
+    for t in table {
+        match counts.remove(t) {
+            Some(count) => {
+                sorted_query.extend(std::iter::repeat(*t).take(count));
+                sorted_table.push(Some(*t));
+                sorted_table.extend(std::iter::repeat(None).take(count - 1));
+            }
+            None => unused.push(*t),
+        }
+    }
+    if !counts.is_empty() {
+        return None;
+    }
+    let mut unused = unused.into_iter();
+    let sorted_table = sorted_table
+        .into_iter()
+        .map(|t| t.or_else(|| unused.next()))
+        .collect::<Option<_>>()?;
+    Some((sorted_query, sorted_table))
+}
+
+impl<F: PrimeField, ID: LookupTableID> LookupEngine<F, ID> for PlookupEngine {
+    const KIND: LookupEngineKind = LookupEngineKind::Plookup;
+
+    fn columns(&self, lookups_map: &LookupsMap<F, ID>) -> Vec<Column> {
+        lookups_map
+            .keys()
+            .flat_map(|table_id| {
+                let idx = TableIdx::new(table_id.to_u32());
+                PlookupColumn::ALL.map(|column| Column::Plookup(idx, column))
+            })
+            .collect()
+    }
+
+    fn constraints(&self, lookups_map: &LookupsMap<F, ID>) -> Result<Vec<E<F>>, LookupEngineError> {
+        let mut constraints = vec![];
+        for (table_id, lookups) in lookups_map {
+            let a = plookup_value(*table_id, lookups)?.clone();
+            let idx = TableIdx::new(table_id.to_u32());
+            let curr = |column| curr_cell::<F>(Column::Plookup(idx, column));
+            let next = |column| next_cell::<F>(Column::Plookup(idx, column));
+            let beta = challenge::<F>(ChallengeTerm::Beta);
+            let gamma = challenge::<F>(ChallengeTerm::Gamma);
+            let (t, sorted_query, sorted_table, z) = (
+                curr(PlookupColumn::Table),
+                curr(PlookupColumn::SortedQuery),
+                curr(PlookupColumn::SortedTable),
+                curr(PlookupColumn::Product),
+            );
+            let next_sorted_query = next(PlookupColumn::SortedQuery);
+            constraints.push(
+                next(PlookupColumn::Product)
+                    * (sorted_query.clone() + beta.clone())
+                    * (sorted_table.clone() + gamma.clone())
+                    - z.clone() * (a + beta) * (t + gamma),
+            );
+            constraints.push(boundary::restrict(boundary::FIRST_ROW, z - E::one()));
+            constraints.push(boundary::restrict(
+                boundary::FIRST_ROW,
+                sorted_query.clone() - sorted_table,
+            ));
+            constraints.push(
+                (next_sorted_query.clone() - next(PlookupColumn::SortedTable))
+                    * (next_sorted_query - sorted_query),
+            );
+        }
+        Ok(constraints)
+    }
+
+    fn first_phase(
+        &self,
+        witnesses: &[MVLookupWitness<F, ID>],
+        domain_size: usize,
+    ) -> Result<Vec<(Column, Vec<F>)>, ProverError> {
+        let mut columns = Vec::with_capacity(3 * witnesses.len());
+        for witness in sorted_witnesses(witnesses) {
+            let (table_id, values, table) = plookup_witness(witness, domain_size)?;
+            let (sorted_query, sorted_table) =
+                sort_by_table(&values, &table).ok_or(ProverError::UnbalancedLookups)?;
+            let idx = TableIdx::new(table_id.to_u32());
+            columns.extend([
+                (Column::Plookup(idx, PlookupColumn::Table), table),
+                (
+                    Column::Plookup(idx, PlookupColumn::SortedQuery),
+                    sorted_query,
+                ),
+                (
+                    Column::Plookup(idx, PlookupColumn::SortedTable),
+                    sorted_table,
+                ),
+            ]);
+        }
+        Ok(columns)
+    }
+
+    fn second_phase(
+        &self,
+        witnesses: &[MVLookupWitness<F, ID>],
+        first_phase: &[(Column, Vec<F>)],
+        challenges: &LookupChallenges<F>,
+        domain_size: usize,
+    ) -> Result<Vec<(Column, Vec<F>)>, ProverError> {
+        let first_phase: BTreeMap<Column, &Vec<F>> = first_phase
+            .iter()
+            .map(|(column, values)| (*column, values))
+            .collect();
+        let LookupChallenges { beta, gamma, .. } = *challenges;
+        let mut columns = Vec::with_capacity(witnesses.len());
+        for witness in sorted_witnesses(witnesses) {
+            let (table_id, values, _) = plookup_witness(witness, domain_size)?;
+            let idx = TableIdx::new(table_id.to_u32());
+            let column = |column| {
+                first_phase
+                    .get(&Column::Plookup(idx, column))
+                    .copied()
+                    .ok_or(ProverError::Internal(
+                        "a column of the first phase of the plookup engine is missing",
+                    ))
+            };
+            let table = column(PlookupColumn::Table)?;
+            let sorted_query = column(PlookupColumn::SortedQuery)?;
+            let sorted_table = column(PlookupColumn::SortedTable)?;
+            let mut denominators: Vec<F> = sorted_query
+                .iter()
+                .zip(sorted_table.iter())
+                .map(|(a, s)| (*a + beta) * (*s + gamma))
+                .collect();
+            batch_inversion(&mut denominators);
+            let mut product = Vec::with_capacity(domain_size);
+            let mut acc = F::one();
+            for ((a, t), denominator) in values.iter().zip(table.iter()).zip(denominators) {
+                product.push(acc);
+                acc *= (*a + beta) * (*t + gamma) * denominator;
+            }
+            // The product wraps around to z(1) = 1 if the sorted columns are
+            // permutations of the original ones
+            if !acc.is_one() {
+                return Err(ProverError::UnbalancedLookups);
+            }
+            columns.push((Column::Plookup(idx, PlookupColumn::Product), product));
+        }
+        Ok(columns)
+    }
+}
+
+/// The engine of each table, [LookupEngineKind::MVLookup] unless another one
+/// is selected. The constraints of the lookups are built by
+/// [LookupEngines::constraints], with the engine of their table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupEngines<ID: LookupTableID> {
+    engines: BTreeMap<ID, LookupEngineKind>,
+}
+
+impl<ID: LookupTableID> Default for LookupEngines<ID> {
+    fn default() -> Self {
+        LookupEngines {
+            engines: BTreeMap::new(),
+        }
+    }
+}
+
+impl<ID: LookupTableID> LookupEngines<ID> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select the engine `kind` for the table `table_id`
+    pub fn with_engine(mut self, table_id: ID, kind: LookupEngineKind) -> Self {
+        match kind {
+            LookupEngineKind::MVLookup => self.engines.remove(&table_id),
+            kind => self.engines.insert(table_id, kind),
+        };
+        self
+    }
+
+    /// The engine of the table `table_id`
+    pub fn engine(&self, table_id: ID) -> LookupEngineKind {
+        self.engines.get(&table_id).copied().unwrap_or_default()
+    }
+
+    /// The tables of the engine `kind` among the non-default ones, by
+    /// increasing ID
+    pub fn tables(&self, kind: LookupEngineKind) -> impl Iterator<Item = ID> + '_ {
+        self.engines
+            .iter()
+            .filter(move |(_, engine)| **engine == kind)
+            .map(|(table_id, _)| *table_id)
+    }
+
+    /// The engines of the tables of `constraints`: the tables whose columns
+    /// of the plookup engine are read use it, the other ones MVLookup
+    pub fn of_constraints<F>(constraints: &[E<F>]) -> Self {
+        let plookup_tables: BTreeSet<u32> = constraints
+            .iter()
+            .flat_map(column_accesses)
+            .filter_map(|(column, _)| match column {
+                Column::Plookup(idx, _) => Some(idx.to_u32()),
+                _ => None,
+            })
+            .collect();
+        LookupEngines {
+            engines: plookup_tables
+                .into_iter()
+                .filter_map(ID::try_from_u32)
+                .map(|table_id| (table_id, LookupEngineKind::Plookup))
+                .collect(),
+        }
+    }
+
+    /// Split `lookups_map` by engine
+    fn split<F>(&self, lookups_map: &LookupsMap<F, ID>) -> (LookupsMap<F, ID>, LookupsMap<F, ID>)
+    where
+        F: Clone,
+    {
+        lookups_map
+            .iter()
+            .map(|(table_id, lookups)| (*table_id, lookups.clone()))
+            .partition(|(table_id, _)| self.engine(*table_id) == LookupEngineKind::MVLookup)
+    }
+
+    /// The columns committed for the lookups `lookups_map`: the ones of
+    /// MVLookup, then the ones of the plookup engine
+    pub fn columns<F: PrimeField>(&self, lookups_map: &LookupsMap<F, ID>) -> Vec<Column> {
+        let (mvlookups, plookups) = self.split(lookups_map);
+        let mut columns = vec![];
+        if !mvlookups.is_empty() {
+            columns.extend(LookupEngine::<F, ID>::columns(&MVLookupEngine, &mvlookups));
+        }
+        columns.extend(LookupEngine::<F, ID>::columns(&PlookupEngine, &plookups));
+        columns
+    }
+
+    /// The constraints of the lookups `lookups_map`, built by the engine of
+    /// their table: the ones of MVLookup, then the ones of the plookup engine
+    pub fn constraints<F: PrimeField>(
+        &self,
+        lookups_map: &LookupsMap<F, ID>,
+    ) -> Result<Vec<E<F>>, LookupEngineError> {
+        let (mvlookups, plookups) = self.split(lookups_map);
+        let mut constraints = vec![];
+        if !mvlookups.is_empty() {
+            constraints.extend(MVLookupEngine.constraints(&mvlookups)?);
+        }
+        constraints.extend(PlookupEngine.constraints(&plookups)?);
+        Ok(constraints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        sort_by_table, LookupChallenges, LookupEngine, LookupEngineError, LookupEngineKind,
+        LookupEngines, LookupsMap, MVLookupEngine, PlookupEngine,
+    };
+    use crate::{
+        columns::Column,
+        expr::{curr_cell, E},
+        lookups::{LookupTableIDs, LookupWitness},
+        mvlookup::{constraint_lookups, LookupTableID, MVLookup},
+        proof::ProofInputs,
+        prover::{prove, ProverError},
+        test::column_lookups,
+        verifier::{try_verify, VerifierError},
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    const TABLE: LookupTableIDs = LookupTableIDs::Custom(1);
+
+    const DOMAIN_SIZE: usize = 1 << 5;
+
+    fn setup() -> (EvaluationDomains<Fp>, PairingSRS<BN254>) {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), DOMAIN_SIZE);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        (domain, srs)
+    }
+
+    /// The 4-bit range check of the single column of a circuit, with the
+    /// engine `kind`. Returns the lookups, their constraints and the inputs
+    /// of a proof.
+    #[allow(clippy::type_complexity)]
+    fn range_check(
+        domain: EvaluationDomains<Fp>,
+        kind: LookupEngineKind,
+        rng: &mut impl Rng,
+    ) -> (
+        LookupsMap<Fp, LookupTableIDs>,
+        Vec<E<Fp>>,
+        ProofInputs<1, BN254G1Affine, LookupTableIDs>,
+    ) {
+        let cols = [(0..domain.d1.size)
+            .map(|_| Fp::from(rng.gen_range(0..16u64)))
+            .collect::<Vec<_>>()];
+        let (lookups, witness) = column_lookups(domain, 1, 16, &cols, &[0]);
+        let lookups_map = BTreeMap::from([(TABLE, lookups)]);
+        let constraints = LookupEngines::new()
+            .with_engine(TABLE, kind)
+            .constraints(&lookups_map)
+            .unwrap();
+        let inputs = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups: vec![witness],
+        };
+        (lookups_map, constraints, inputs)
+    }
+
+    #[test]
+    fn test_sort_by_table() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        // The table of [0, 16), padded with zeroes
+        let table: Vec<Fp> = (0..DOMAIN_SIZE as u64)
+            .map(|i| Fp::from(if i < 16 { i } else { 0 }))
+            .collect();
+        let values: Vec<Fp> = (0..DOMAIN_SIZE)
+            .map(|_| Fp::from(rng.gen_range(0..16u64)))
+            .collect();
+        let (sorted_query, sorted_table) = sort_by_table(&values, &table).unwrap();
+
+        let sorted = |values: &[Fp]| {
+            let mut values = values.to_vec();
+            values.sort();
+            values
+        };
+        assert_eq!(sorted(&sorted_query), sorted(&values));
+        assert_eq!(sorted(&sorted_table), sorted(&table));
+        assert_eq!(sorted_query[0], sorted_table[0]);
+        for i in 1..DOMAIN_SIZE {
+            assert!(sorted_query[i] == sorted_table[i] || sorted_query[i] == sorted_query[i - 1]);
+        }
+
+        // A value which is not in the table can not be sorted
+        let mut values = values;
+        values[3] = Fp::from(16u64);
+        assert_eq!(sort_by_table(&values, &table), None);
+    }
+
+    #[test]
+    fn test_plookup_completeness() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, srs) = setup();
+        let (_, constraints, inputs) = range_check(domain, LookupEngineKind::Plookup, &mut rng);
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, 1, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        let comms = proof.proof_comms.mvlookup_comms.as_ref().unwrap();
+        assert!(comms.m.is_empty() && comms.h.is_empty());
+        assert_eq!(comms.plookup.keys().collect::<Vec<_>>(), vec![&TABLE]);
+        try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, 1, 0, _>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(0),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_mixed_engines() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, srs) = setup();
+        // The first column is looked up with MVLookup into [0, 16), the
+        // second one with the plookup engine into [0, 8)
+        let plookup_table = LookupTableIDs::Custom(2);
+        let cols: [Vec<Fp>; 2] = std::array::from_fn(|i| {
+            (0..DOMAIN_SIZE)
+                .map(|_| Fp::from(rng.gen_range(0..16u64 >> i)))
+                .collect()
+        });
+        let (mvlookups, mvlookup_witness) = column_lookups(domain, 1, 16, &cols, &[0]);
+        let (plookups, plookup_witness) = column_lookups(domain, 2, 8, &cols, &[1]);
+        let lookups_map = BTreeMap::from([(TABLE, mvlookups), (plookup_table, plookups)]);
+        let engines = LookupEngines::new().with_engine(plookup_table, LookupEngineKind::Plookup);
+        let constraints = engines.constraints(&lookups_map).unwrap();
+        assert_eq!(LookupEngines::of_constraints(&constraints), engines);
+
+        let inputs = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups: vec![plookup_witness, mvlookup_witness],
+        };
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, 2, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        let comms = proof.proof_comms.mvlookup_comms.as_ref().unwrap();
+        assert_eq!(comms.m.keys().collect::<Vec<_>>(), vec![&TABLE]);
+        assert_eq!(
+            comms.plookup.keys().collect::<Vec<_>>(),
+            vec![&plookup_table]
+        );
+        try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, 2, 0, _>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(0),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_plookup_missing_value() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, srs) = setup();
+        let (_, constraints, mut inputs) = range_check(domain, LookupEngineKind::Plookup, &mut rng);
+        let value = Fp::from(16u64);
+        inputs.evaluations.cols[0][3] = value;
+        inputs.mvlookups[0].f[0][3] = MVLookup::new(TABLE, Fp::one(), &[value]);
+        let result = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, 1, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        );
+        assert!(matches!(result, Err(ProverError::UnbalancedLookups)));
+    }
+
+    #[test]
+    fn test_plookup_tampered_evaluation() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, srs) = setup();
+        let (_, constraints, inputs) = range_check(domain, LookupEngineKind::Plookup, &mut rng);
+        let mut proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, 1, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        let evals = proof.proof_evals.mvlookup_evals.as_mut().unwrap();
+        evals.plookup.get_mut(&TABLE).unwrap().product.zeta += Fp::one();
+        assert_eq!(
+            try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, 1, 0, _>(
+                domain,
+                &srs,
+                &constraints,
+                &proof,
+                Witness::zero_vec(0),
+            ),
+            Err(VerifierError::OpeningProofFailed)
+        );
+    }
+
+    #[test]
+    fn test_plookup_constraint_errors() {
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let engines = LookupEngines::new().with_engine(TABLE, LookupEngineKind::Plookup);
+        let constraints = |lookups: Vec<MVLookup<E<Fp>, LookupTableIDs>>| {
+            engines.constraints(&BTreeMap::from([(TABLE, lookups)]))
+        };
+        let table_id = TABLE.to_u32();
+
+        assert_eq!(
+            constraints(vec![
+                MVLookup::new(TABLE, E::one(), &[x(0)]),
+                MVLookup::new(TABLE, E::one(), &[x(1)]),
+            ]),
+            Err(LookupEngineError::QueryCount { table_id, got: 2 })
+        );
+        assert_eq!(
+            constraints(vec![MVLookup::new(TABLE, E::one(), &[x(0), x(1)])]),
+            Err(LookupEngineError::NotSingleColumn { table_id, width: 2 })
+        );
+        assert_eq!(
+            constraints(vec![MVLookup::new(TABLE, x(1), &[x(0)])]),
+            Err(LookupEngineError::ConditionalLookup(table_id))
+        );
+    }
+
+    #[test]
+    fn test_default_engines() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, _) = setup();
+        let (lookups_map, constraints, _) =
+            range_check(domain, LookupEngineKind::MVLookup, &mut rng);
+        assert_eq!(constraints, constraint_lookups(&lookups_map));
+        assert_eq!(
+            LookupEngines::<LookupTableIDs>::of_constraints(&constraints),
+            LookupEngines::default()
+        );
+    }
+
+    /// The columns of `engine` are the ones its phases compute
+    fn check_columns<Engine: LookupEngine<Fp, LookupTableIDs>>(
+        engine: &Engine,
+        lookups_map: &LookupsMap<Fp, LookupTableIDs>,
+        witnesses: &[LookupWitness<Fp>],
+    ) {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let challenges = LookupChallenges {
+            joint_combiner: Fp::rand(&mut rng),
+            beta: Fp::rand(&mut rng),
+            gamma: Fp::rand(&mut rng),
+        };
+        let first_phase = engine.first_phase(witnesses, DOMAIN_SIZE).unwrap();
+        let second_phase = engine
+            .second_phase(witnesses, &first_phase, &challenges, DOMAIN_SIZE)
+            .unwrap();
+        let computed: BTreeSet<Column> = first_phase
+            .iter()
+            .chain(second_phase.iter())
+            .map(|(column, values)| {
+                assert_eq!(values.len(), DOMAIN_SIZE);
+                *column
+            })
+            .collect();
+        assert_eq!(
+            engine
+                .columns(lookups_map)
+                .into_iter()
+                .collect::<BTreeSet<_>>(),
+            computed
+        );
+    }
+
+    #[test]
+    fn test_engine_columns() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, _) = setup();
+        for kind in [LookupEngineKind::MVLookup, LookupEngineKind::Plookup] {
+            let (lookups_map, _, inputs) = range_check(domain, kind, &mut rng);
+            match kind {
+                LookupEngineKind::MVLookup => {
+                    check_columns(&MVLookupEngine, &lookups_map, &inputs.mvlookups)
+                }
+                LookupEngineKind::Plookup => {
+                    check_columns(&PlookupEngine, &lookups_map, &inputs.mvlookups)
+                }
+            }
+        }
+    }
+}
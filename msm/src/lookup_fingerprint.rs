@@ -0,0 +1,257 @@
+//! Fingerprints of the lookup witnesses, to compare two witnesses in tests,
+//! e.g. the witnesses built by two implementations of the same builder,
+//! without comparing nor printing all their values, see [WitnessFingerprint].
+//!
+//! The fingerprint of a column is made of its length and of rolling hashes of
+//! its values. The hashes are computed in the field, with fixed parameters:
+//! they are not binding, and only meant to detect accidental differences.
+
+use ark_ff::PrimeField;
+use std::fmt;
+
+use crate::mvlookup::{LookupTableID, MVLookupWitness, VectorCombiner};
+
+/// The joint combiner of the values of the vector lookups in the fingerprints
+const FINGERPRINT_COMBINER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The base of the rolling hashes of the fingerprints
+const FINGERPRINT_BASE: u64 = 0xc2b2_ae3d_27d4_eb4f;
+
+/// The fingerprint of a column `f_i` of a lookup witness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnFingerprint<F> {
+    /// The number of rows of the column
+    pub len: usize,
+    /// The rolling hash of the numerators
+    pub numerators: F,
+    /// The rolling hash of the values combined with their table ID, like the
+    /// denominators of the lookup argument, with a fixed joint combiner
+    pub values: F,
+}
+
+/// A column of a lookup witness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WitnessColumn {
+    /// The column `f_i`
+    Lookup(usize),
+    /// The multiplicities `m`
+    Multiplicities,
+}
+
+/// A column which differs between two witnesses, see
+/// [WitnessFingerprint::diff]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnDiff {
+    pub column: WitnessColumn,
+    /// The first row where the column differs. If the column is a prefix of
+    /// the other one, it is the length of the shortest column, and it is 0
+    /// if the column is missing from one of the witnesses.
+    pub first_row: usize,
+}
+
+/// The fingerprint of a lookup witness: the fingerprint of each column `f_i`,
+/// and the length and the rolling hash of the multiplicities.
+/// Two fingerprints are equal if the fingerprints of the columns are. The
+/// witness is borrowed, to locate the differences with
+/// [WitnessFingerprint::diff], and is not printed.
+#[derive(Clone)]
+pub struct WitnessFingerprint<'a, F, ID: LookupTableID> {
+    witness: &'a MVLookupWitness<F, ID>,
+    columns: Vec<ColumnFingerprint<F>>,
+    n_multiplicities: usize,
+    multiplicities: F,
+}
+
+/// The rolling hash of `values`
+fn rolling_hash<F: PrimeField>(values: impl Iterator<Item = F>) -> F {
+    let base = F::from(FINGERPRINT_BASE);
+    values.fold(F::zero(), |acc, x| acc * base + x)
+}
+
+impl<'a, F: PrimeField, ID: LookupTableID> From<&'a MVLookupWitness<F, ID>>
+    for WitnessFingerprint<'a, F, ID>
+{
+    fn from(witness: &'a MVLookupWitness<F, ID>) -> Self {
+        let mut combiner = VectorCombiner::new(F::from(FINGERPRINT_COMBINER));
+        let columns = witness
+            .f
+            .iter()
+            .map(|column| ColumnFingerprint {
+                len: column.len(),
+                numerators: rolling_hash(column.iter().map(|lookup| lookup.numerator)),
+                values: rolling_hash(column.iter().map(|lookup| {
+                    combiner.combine(&lookup.value) + lookup.table_id.to_field::<F>()
+                })),
+            })
+            .collect();
+        WitnessFingerprint {
+            witness,
+            columns,
+            n_multiplicities: witness.m.len(),
+            multiplicities: rolling_hash(witness.m.iter().copied()),
+        }
+    }
+}
+
+impl<'a, F: PrimeField, ID: LookupTableID> WitnessFingerprint<'a, F, ID> {
+    /// The fingerprints of the columns `f_i`
+    pub fn columns(&self) -> &[ColumnFingerprint<F>] {
+        &self.columns
+    }
+
+    /// The columns which differ from the ones of `other`, by increasing
+    /// index, and then the multiplicities. The columns are compared by their
+    /// fingerprints, and only the ones which differ are read again to locate
+    /// their first differing row.
+    pub fn diff(&self, other: &Self) -> Vec<ColumnDiff> {
+        let n_columns = self.columns.len().max(other.columns.len());
+        let mut diffs: Vec<ColumnDiff> = (0..n_columns)
+            .filter_map(|i| {
+                let first_row = match (self.columns.get(i), other.columns.get(i)) {
+                    (Some(left), Some(right)) if left == right => return None,
+                    (Some(_), Some(_)) => {
+                        let (left, right) = (&self.witness.f[i], &other.witness.f[i]);
+                        first_differing_row(left, right, |x, y| {
+                            x.table_id == y.table_id
+                                && x.numerator == y.numerator
+                                && x.value == y.value
+                        })
+                    }
+                    _ => 0,
+                };
+                Some(ColumnDiff {
+                    column: WitnessColumn::Lookup(i),
+                    first_row,
+                })
+            })
+            .collect();
+        if (self.n_multiplicities, self.multiplicities)
+            != (other.n_multiplicities, other.multiplicities)
+        {
+            diffs.push(ColumnDiff {
+                column: WitnessColumn::Multiplicities,
+                first_row: first_differing_row(&self.witness.m, &other.witness.m, |x, y| x == y),
+            });
+        }
+        diffs
+    }
+}
+
+/// The first row where `left` and `right` differ, or the length of the
+/// shortest one if it is a prefix of the other one
+fn first_differing_row<T>(left: &[T], right: &[T], eq: impl Fn(&T, &T) -> bool) -> usize {
+    left.iter()
+        .zip(right)
+        .position(|(x, y)| !eq(x, y))
+        .unwrap_or_else(|| left.len().min(right.len()))
+}
+
+impl<'a, F: PartialEq, ID: LookupTableID> PartialEq for WitnessFingerprint<'a, F, ID> {
+    fn eq(&self, other: &Self) -> bool {
+        self.columns == other.columns
+            && self.n_multiplicities == other.n_multiplicities
+            && self.multiplicities == other.multiplicities
+    }
+}
+
+impl<'a, F: fmt::Debug, ID: LookupTableID> fmt::Debug for WitnessFingerprint<'a, F, ID> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WitnessFingerprint")
+            .field("columns", &self.columns)
+            .field("n_multiplicities", &self.n_multiplicities)
+            .field("multiplicities", &self.multiplicities)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test::column_lookups, Fp};
+    use ark_ff::{One, UniformRand};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use rand::Rng;
+
+    #[test]
+    fn test_witness_fingerprint() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let cols: Vec<Vec<Fp>> = (0..3)
+            .map(|_| {
+                (0..domain_size)
+                    .map(|_| Fp::from(rng.gen_range(0..16u64)))
+                    .collect()
+            })
+            .collect();
+        let (_, witness) = column_lookups(domain, 1, 16, &cols, &[0, 1, 2]);
+        let fingerprint = WitnessFingerprint::from(&witness);
+        assert_eq!(fingerprint.columns().len(), 4);
+        assert!(fingerprint
+            .columns()
+            .iter()
+            .all(|column| column.len == domain_size));
+
+        // A copy has the same fingerprint
+        let copy = witness.clone();
+        assert_eq!(WitnessFingerprint::from(&copy), fingerprint);
+        assert_eq!(WitnessFingerprint::from(&copy).diff(&fingerprint), vec![]);
+
+        // A single cell differs, and the diff points to it
+        let mut changed = witness.clone();
+        changed.f[1][37].value[0] += Fp::one();
+        let changed_fingerprint = WitnessFingerprint::from(&changed);
+        assert_ne!(changed_fingerprint, fingerprint);
+        assert_eq!(changed_fingerprint.columns()[0], fingerprint.columns()[0]);
+        assert_eq!(
+            fingerprint.diff(&changed_fingerprint),
+            vec![ColumnDiff {
+                column: WitnessColumn::Lookup(1),
+                first_row: 37,
+            }]
+        );
+
+        // The numerators, the table IDs and the multiplicities are
+        // fingerprinted as well
+        let mut changed = witness.clone();
+        changed.f[3][2].numerator = Fp::rand(&mut rng);
+        changed.f[0][5].table_id = crate::lookups::LookupTableIDs::Custom(2);
+        changed.m[9] += Fp::one();
+        assert_eq!(
+            fingerprint.diff(&WitnessFingerprint::from(&changed)),
+            vec![
+                ColumnDiff {
+                    column: WitnessColumn::Lookup(0),
+                    first_row: 5,
+                },
+                ColumnDiff {
+                    column: WitnessColumn::Lookup(3),
+                    first_row: 2,
+                },
+                ColumnDiff {
+                    column: WitnessColumn::Multiplicities,
+                    first_row: 9,
+                },
+            ]
+        );
+
+        // A truncated column differs from its end, and a missing column from
+        // its first row
+        let mut changed = witness.clone();
+        changed.f[2].truncate(40);
+        changed.f.pop();
+        assert_eq!(
+            fingerprint.diff(&WitnessFingerprint::from(&changed)),
+            vec![
+                ColumnDiff {
+                    column: WitnessColumn::Lookup(2),
+                    first_row: 40,
+                },
+                ColumnDiff {
+                    column: WitnessColumn::Lookup(3),
+                    first_row: 0,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! Statistics of the lookup argument of a proving run, for telemetry.
+//!
+//! The prover computes a [LookupStats] from the lookup witnesses in
+//! [crate::mvlookup::prover::Env::create] when an observer is attached to its
+//! context, see [crate::prover::ProverContext::with_lookup_observer], and
+//! completes it with the sizes of the proof once it is finished. The same
+//! statistics can be computed offline with [LookupStats::from_witness].
+//!
+//! The statistics of a batch of proofs are aggregated with
+//! [LookupStats::merge], or with [report_usage] when the lookup witnesses are
+//! available, which also reports the most looked-up entries of each table.
+
+use crate::{
+    mvlookup::{LookupTableID, MVLookup, MVLookupWitness},
+    proof::{EvaluationContainer, Proof},
+    MAX_SUPPORTED_DEGREE,
+};
+use ark_ff::PrimeField;
+use kimchi::curve::KimchiCurve;
+use poly_commitment::OpenProof;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashSet},
+    sync::Arc,
+};
+
+/// The usage of the table of a lookup witness
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableStats {
+    pub table_id: u32,
+    /// The number of lookups into the table, i.e. the cells of the looked-up
+    /// columns with a non-zero numerator. The no-op lookups padding the
+    /// columns, see [MVLookup::noop], are not counted.
+    pub queries: usize,
+    /// The largest multiplicity of a row of the table. It saturates at
+    /// `u64::MAX`.
+    pub max_multiplicity: u64,
+    /// The number of rows of the table with a non-zero multiplicity
+    pub used_entries: usize,
+    /// The number of distinct rows of the table, i.e. its length without the
+    /// rows padding it to the domain. It is the number of multiplicities when
+    /// the table is not fixed.
+    pub entries: usize,
+    /// `used_entries / entries`, or zero for an empty table
+    pub used_fraction: f64,
+    /// The number of partial sums `h_i` of the lookup witness
+    pub partial_sums: usize,
+}
+
+impl TableStats {
+    /// The statistics of the lookup witness whose columns are `f` and whose
+    /// multiplicities are `m`
+    pub(crate) fn of_columns<F: PrimeField, ID: LookupTableID>(
+        f: &[Vec<MVLookup<F, ID>>],
+        m: &[F],
+    ) -> Option<Self> {
+        let table_id = f.last()?.first()?.table_id;
+        // The last column is the table itself when it is fixed
+        let looked_up = if table_id.is_fixed() {
+            &f[..f.len() - 1]
+        } else {
+            f
+        };
+        let queries = looked_up
+            .iter()
+            .flatten()
+            .filter(|lookup| !lookup.numerator.is_zero())
+            .count();
+        let max_multiplicity = m.iter().map(saturating_u64).max().unwrap_or(0);
+        let used_entries = m.iter().filter(|m| !m.is_zero()).count();
+        let entries = if table_id.is_fixed() {
+            f[f.len() - 1]
+                .iter()
+                .map(|lookup| &lookup.value)
+                .collect::<HashSet<_>>()
+                .len()
+        } else {
+            m.len()
+        };
+        let chunk_size = MAX_SUPPORTED_DEGREE - 2;
+        Some(TableStats {
+            table_id: table_id.to_u32(),
+            queries,
+            max_multiplicity,
+            used_entries,
+            entries,
+            used_fraction: used_fraction(used_entries, entries),
+            partial_sums: (f.len() + chunk_size - 1) / chunk_size,
+        })
+    }
+}
+
+/// `used_entries / entries`, or zero for an empty table
+fn used_fraction(used_entries: usize, entries: usize) -> f64 {
+    if entries == 0 {
+        0.
+    } else {
+        used_entries as f64 / entries as f64
+    }
+}
+
+/// The value of `x` if it fits in a `u64`, and `u64::MAX` otherwise
+fn saturating_u64<F: PrimeField>(x: &F) -> u64 {
+    let repr = x.into_repr();
+    let limbs = repr.as_ref();
+    if limbs[1..].iter().all(|limb| *limb == 0) {
+        limbs[0]
+    } else {
+        u64::MAX
+    }
+}
+
+/// The sizes of a finished proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofCounts {
+    /// The number of committed polynomials: the witness columns, the
+    /// polynomials of the lookup argument, and the quotient
+    pub commitments: usize,
+    /// The number of evaluations of the committed polynomials, at all the
+    /// evaluation points, including the evaluation of the quotient at `ζω`
+    pub evaluations: usize,
+}
+
+impl ProofCounts {
+    pub fn of_proof<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+    ) -> Self {
+        let comms = &proof.proof_comms;
+        let commitments = comms.witness_comms.cols.len()
+            + comms
+                .mvlookup_comms
+                .as_ref()
+                .map_or(0, |comms| comms.into_iter().count())
+            + 1;
+        let evals = &proof.proof_evals;
+        let mut evaluations = usize::from(evals.ft_eval1.is_some());
+        let mut count = |eval: &Eval| eval.for_each_point(|_| evaluations += 1);
+        evals.witness_evals.cols.iter().for_each(&mut count);
+        if let Some(lookup_evals) = &evals.mvlookup_evals {
+            lookup_evals.into_iter().for_each(&mut count);
+        }
+        ProofCounts {
+            commitments,
+            evaluations,
+        }
+    }
+}
+
+/// The statistics of the lookup argument of a proof
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LookupStats {
+    /// The statistics of each lookup witness, in the order of
+    /// [crate::proof::ProofInputs::mvlookups]
+    pub tables: Vec<TableStats>,
+    /// The sizes of the proof, when the statistics are emitted by the prover
+    pub proof: Option<ProofCounts>,
+}
+
+impl LookupStats {
+    /// Compute the statistics of `lookups` offline, without the sizes of the
+    /// proof
+    pub fn from_witness<F: PrimeField, ID: LookupTableID>(
+        lookups: &[MVLookupWitness<F, ID>],
+    ) -> Self {
+        let mut stats = LookupStats::default();
+        lookups.iter().for_each(|lookup| stats.record(lookup));
+        stats
+    }
+
+    /// Add the statistics of `lookup`. The witnesses without columns are
+    /// skipped.
+    pub(crate) fn record<F: PrimeField, ID: LookupTableID>(
+        &mut self,
+        lookup: &MVLookupWitness<F, ID>,
+    ) {
+        self.tables
+            .extend(TableStats::of_columns(&lookup.f, &lookup.m));
+    }
+
+    /// The total number of lookups into the tables
+    pub fn queries(&self) -> usize {
+        self.tables.iter().map(|table| table.queries).sum()
+    }
+
+    /// Add the statistics `other` of another proof to `self`, e.g. to
+    /// aggregate the usage of the tables over a batch of proofs. The
+    /// statistics of a table are merged with the ones of the same table, and
+    /// the tables which are only in `other` are appended. As the rows of the
+    /// tables are not known, the merged maximal multiplicity and number of
+    /// used entries are the largest of the proofs, i.e. lower bounds, see
+    /// [GlobalUsageReport] for exact values. The sizes of the proofs are
+    /// added up.
+    pub fn merge(&mut self, other: &LookupStats) {
+        for table in other.tables.iter() {
+            match self
+                .tables
+                .iter_mut()
+                .find(|merged| merged.table_id == table.table_id)
+            {
+                Some(merged) => {
+                    merged.queries += table.queries;
+                    merged.max_multiplicity = merged.max_multiplicity.max(table.max_multiplicity);
+                    merged.used_entries = merged.used_entries.max(table.used_entries);
+                    merged.entries = merged.entries.max(table.entries);
+                    merged.partial_sums = merged.partial_sums.max(table.partial_sums);
+                    merged.used_fraction = used_fraction(merged.used_entries, merged.entries);
+                }
+                None => self.tables.push(table.clone()),
+            }
+        }
+        self.proof = match (self.proof, other.proof) {
+            (Some(merged), Some(proof)) => Some(ProofCounts {
+                commitments: merged.commitments + proof.commitments,
+                evaluations: merged.evaluations + proof.evaluations,
+            }),
+            (merged, proof) => merged.or(proof),
+        };
+    }
+}
+
+/// A callback receiving the [LookupStats] of each proof created with a
+/// [crate::prover::ProverContext], see
+/// [crate::prover::ProverContext::with_lookup_observer]. It is called by the
+/// thread creating the proof, once the proof is finished.
+pub type LookupStatsObserver = Arc<dyn Fn(&LookupStats) + Send + Sync>;
+
+/// The number of entries of each table kept by [report_usage]
+pub const HOTTEST_ENTRIES: usize = 8;
+
+/// The number of lookups into an entry of a table, over a batch of proofs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryUsage {
+    /// The row of the entry in the column of the table
+    pub row: usize,
+    /// The sum of the multiplicities of the entry. It saturates at
+    /// `u64::MAX`.
+    pub queries: u64,
+}
+
+/// The usage of the tables over a batch of proofs, computed from their lookup
+/// witnesses, see [report_usage]. Unlike [LookupStats::merge], the
+/// multiplicities of the entries are added up over the proofs, so that the
+/// maximal multiplicity and the number of used entries of the merged
+/// statistics are exact.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GlobalUsageReport {
+    /// The number of proofs of the batch
+    pub proofs: usize,
+    /// The merged statistics of the proofs
+    pub stats: LookupStats,
+    /// The most looked-up entries of each table, by decreasing number of
+    /// lookups. The entries which are not looked up are not reported.
+    pub hottest: BTreeMap<u32, Vec<EntryUsage>>,
+}
+
+impl GlobalUsageReport {
+    /// The report of the proofs whose lookup witnesses are `witnesses`,
+    /// keeping the `top_k` most looked-up entries of each table.
+    /// The multiplicities of each table are added up in a single row per
+    /// entry, and the hottest entries are selected with a heap of `top_k`
+    /// entries, without sorting the tables.
+    pub fn from_witnesses<F: PrimeField, ID: LookupTableID>(
+        witnesses: &[&[MVLookupWitness<F, ID>]],
+        top_k: usize,
+    ) -> Self {
+        let mut stats = LookupStats::default();
+        let mut counts: BTreeMap<u32, Vec<u64>> = BTreeMap::new();
+        for proof in witnesses {
+            stats.merge(&LookupStats::from_witness(proof));
+            for lookup in proof.iter() {
+                let Some(table_id) = lookup.table_id() else {
+                    continue;
+                };
+                let table_counts = counts.entry(table_id.to_u32()).or_default();
+                if table_counts.len() < lookup.m.len() {
+                    table_counts.resize(lookup.m.len(), 0);
+                }
+                for (count, m) in table_counts.iter_mut().zip(lookup.m.iter()) {
+                    *count = count.saturating_add(saturating_u64(m));
+                }
+            }
+        }
+        for table in stats.tables.iter_mut() {
+            if let Some(table_counts) = counts.get(&table.table_id) {
+                table.max_multiplicity = table_counts.iter().copied().max().unwrap_or(0);
+                table.used_entries = table_counts.iter().filter(|count| **count > 0).count();
+                table.used_fraction = used_fraction(table.used_entries, table.entries);
+            }
+        }
+        let hottest = counts
+            .into_iter()
+            .map(|(table_id, table_counts)| (table_id, hottest_entries(&table_counts, top_k)))
+            .collect();
+        GlobalUsageReport {
+            proofs: witnesses.len(),
+            stats,
+            hottest,
+        }
+    }
+}
+
+/// The report of the usage of the tables by the proofs whose lookup
+/// witnesses are `witnesses`, with the [HOTTEST_ENTRIES] most looked-up
+/// entries of each table, see [GlobalUsageReport::from_witnesses].
+pub fn report_usage<F: PrimeField, ID: LookupTableID>(
+    witnesses: &[&[MVLookupWitness<F, ID>]],
+) -> GlobalUsageReport {
+    GlobalUsageReport::from_witnesses(witnesses, HOTTEST_ENTRIES)
+}
+
+/// The `top_k` largest non-zero counts, by decreasing count, and by
+/// increasing row for equal counts
+fn hottest_entries(counts: &[u64], top_k: usize) -> Vec<EntryUsage> {
+    // A min-heap of the hottest entries seen so far, whose top is the
+    // coldest of them
+    let mut heap: BinaryHeap<Reverse<(u64, Reverse<usize>)>> = BinaryHeap::with_capacity(top_k);
+    for (row, count) in counts.iter().enumerate() {
+        if *count == 0 {
+            continue;
+        }
+        let entry = Reverse((*count, Reverse(row)));
+        if heap.len() < top_k {
+            heap.push(entry);
+        } else if heap.peek().is_some_and(|coldest| entry < *coldest) {
+            heap.pop();
+            heap.push(entry);
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((queries, Reverse(row)))| EntryUsage { row, queries })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lookups::LookupTableIDs, Fp};
+    use ark_ff::One;
+
+    #[test]
+    fn test_lookup_stats_from_witness() {
+        let table_id = LookupTableIDs::RangeCheck16;
+        let query = |value: u64| MVLookup::new(table_id, Fp::one(), &[Fp::from(value)]);
+        // 7 looked-up columns of 4 rows, with a no-op lookup, and the table
+        let mut columns: Vec<Vec<_>> = (0..7)
+            .map(|i| (0..4).map(|j| query((i + j) % 2)).collect())
+            .collect();
+        columns[6][3] = MVLookup::noop(table_id);
+        // The table is [0, 1, 2], padded with 0. The values 0 and 1 are
+        // looked up 14 and 13 times, and 2 is not looked up.
+        let m: Vec<Fp> = [14, 13, 0, 0].into_iter().map(Fp::from).collect();
+        columns.push(
+            (0..4)
+                .map(|i| MVLookup::new(table_id, -m[i], &[Fp::from(i as u64 % 3)]))
+                .collect(),
+        );
+        let lookup = MVLookupWitness { f: columns, m };
+
+        let stats = LookupStats::from_witness(&[lookup]);
+        assert_eq!(
+            stats,
+            LookupStats {
+                tables: vec![TableStats {
+                    table_id: table_id.to_u32(),
+                    queries: 27,
+                    max_multiplicity: 14,
+                    used_entries: 2,
+                    entries: 3,
+                    used_fraction: 2. / 3.,
+                    // 8 columns, in chunks of 6
+                    partial_sums: 2,
+                }],
+                proof: None,
+            }
+        );
+        assert_eq!(stats.queries(), 27);
+
+        // The stats can be shipped
+        let bytes = rmp_serde::to_vec(&stats).unwrap();
+        assert_eq!(rmp_serde::from_slice::<LookupStats>(&bytes).unwrap(), stats);
+    }
+
+    /// A witness of 16 rows looking up `counts[i]` times the entry `i` of
+    /// the table `[0, counts.len())`
+    fn witness(table_id: LookupTableIDs, counts: &[u64]) -> MVLookupWitness<Fp, LookupTableIDs> {
+        const ROWS: usize = 16;
+        let mut queries: Vec<_> = counts
+            .iter()
+            .enumerate()
+            .flat_map(|(i, count)| {
+                (0..*count).map(move |_| MVLookup::new(table_id, Fp::one(), &[Fp::from(i as u64)]))
+            })
+            .collect();
+        queries.resize(ROWS, MVLookup::noop(table_id));
+        let m: Vec<Fp> = (0..ROWS)
+            .map(|i| Fp::from(counts.get(i).copied().unwrap_or(0)))
+            .collect();
+        let table = (0..ROWS)
+            .map(|i| {
+                let value = if i < counts.len() { i as u64 } else { 0 };
+                MVLookup::new(table_id, -m[i], &[Fp::from(value)])
+            })
+            .collect();
+        MVLookupWitness {
+            f: vec![queries, table],
+            m,
+        }
+    }
+
+    #[test]
+    fn test_global_usage_report() {
+        let (a, b) = (LookupTableIDs::Custom(0), LookupTableIDs::Custom(1));
+        let proofs = [
+            vec![witness(a, &[3, 0, 1, 0]), witness(b, &[0, 2])],
+            vec![witness(a, &[0, 0, 5, 1])],
+            vec![witness(a, &[1, 0, 0, 0])],
+        ];
+        let witnesses: Vec<&[_]> = proofs.iter().map(|proof| proof.as_slice()).collect();
+
+        // Without the rows, the merged statistics are bounded by the ones of
+        // the proofs
+        let mut merged = LookupStats::default();
+        for proof in proofs.iter() {
+            merged.merge(&LookupStats::from_witness(proof));
+        }
+        assert_eq!(merged.queries(), 13);
+        assert_eq!(merged.tables[0].table_id, a.to_u32());
+        assert_eq!(merged.tables[0].queries, 11);
+        assert_eq!(merged.tables[0].max_multiplicity, 5);
+        assert_eq!(merged.tables[0].used_entries, 2);
+
+        // With the witnesses, the multiplicities are added up: the entries
+        // of the table `a` are looked up [4, 0, 6, 1] times
+        let report = GlobalUsageReport::from_witnesses(&witnesses, 2);
+        assert_eq!(report.proofs, 3);
+        assert_eq!(report.stats.queries(), 13);
+        let table = &report.stats.tables[0];
+        assert_eq!(
+            (
+                table.queries,
+                table.max_multiplicity,
+                table.used_entries,
+                table.entries
+            ),
+            (11, 6, 3, 4)
+        );
+        assert_eq!(table.used_fraction, 3. / 4.);
+        assert_eq!(
+            report.hottest[&a.to_u32()],
+            vec![
+                EntryUsage { row: 2, queries: 6 },
+                EntryUsage { row: 0, queries: 4 }
+            ]
+        );
+        assert_eq!(
+            report.hottest[&b.to_u32()],
+            vec![EntryUsage { row: 1, queries: 2 }]
+        );
+
+        // The entries which are not looked up are not reported
+        let report = report_usage(&witnesses);
+        assert_eq!(
+            report.hottest[&a.to_u32()],
+            vec![
+                EntryUsage { row: 2, queries: 6 },
+                EntryUsage { row: 0, queries: 4 },
+                EntryUsage { row: 3, queries: 1 }
+            ]
+        );
+
+        let bytes = rmp_serde::to_vec(&report).unwrap();
+        assert_eq!(
+            rmp_serde::from_slice::<GlobalUsageReport>(&bytes).unwrap(),
+            report
+        );
+    }
+}
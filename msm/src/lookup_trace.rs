@@ -0,0 +1,634 @@
+//! Flat dumps of the lookups of a witness, e.g. for a review of a gadget
+//! against its specification.
+//!
+//! [MVLookupWitness::flatten] and [ProofInputs::lookup_trace] give one
+//! [LookupRecord] per looked-up cell, sorted by row and then by column. The
+//! columns of the fixed tables are not lookups and are left out: they are
+//! given by the table and the multiplicities, which are computed again from
+//! the records by [MVLookupWitness::from_records].
+//!
+//! The records are written one per line with [write_csv] and [write_json],
+//! and read back with [read_csv] and [read_json], without holding the whole
+//! trace. The field elements are encoded in decimal, with their canonical
+//! representative in `[0, p)`, and decoding fails on any other encoding.
+
+use crate::{
+    mvlookup::{LookupTable, LookupTableID, MVLookup, MVLookupWitness},
+    proof::ProofInputs,
+};
+use ark_ff::PrimeField;
+use kimchi::curve::KimchiCurve;
+use num_bigint::BigUint;
+use o1_utils::FieldHelpers;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
+use thiserror::Error;
+
+/// The header of the dumps written by [write_csv]
+pub const CSV_HEADER: &str = "row,column,table_id,values,numerator";
+
+/// Errors that can arise when writing, reading or ingesting a dump of lookups
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LookupTraceError {
+    #[error("the dump could not be read or written: {0}")]
+    Io(String),
+
+    #[error("the line {line} of the dump is malformed: {reason}")]
+    Malformed { line: usize, reason: String },
+
+    #[error("the line {line} of the dump refers to the unknown table {table_id}")]
+    UnknownTable { line: usize, table_id: u32 },
+
+    #[error("the record of the row {row} is not a lookup into the table {table_id}")]
+    TableMismatch { row: usize, table_id: u32 },
+
+    #[error("the row {row} of the column {column} is not in the domain")]
+    RowOutOfRange { row: usize, column: usize },
+
+    #[error("the row {row} of the column {column} is recorded twice")]
+    DuplicateRecord { row: usize, column: usize },
+
+    #[error("the row {row} of the column {column} is not recorded")]
+    MissingRecord { row: usize, column: usize },
+
+    #[error("the row {row} looks up a value which is not in the table {table_id}")]
+    NotInTable { row: usize, table_id: u32 },
+
+    #[error("the table {0} has more entries than the domain has rows")]
+    TableTooLarge(u32),
+}
+
+/// A looked-up cell of a [MVLookupWitness]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupRecord<F, ID> {
+    pub row: usize,
+    /// The index of the column in the lookup witness, or among the looked-up
+    /// columns of all the lookup witnesses for [ProofInputs::lookup_trace]
+    pub column: usize,
+    pub table_id: ID,
+    pub values: Vec<F>,
+    pub numerator: F,
+}
+
+impl<F: Clone, ID: LookupTableID> MVLookupWitness<F, ID> {
+    /// The number of columns of `f` which are looked up, i.e. without the
+    /// column of the fixed table, if any
+    fn n_looked_up_columns(&self) -> usize {
+        match self.f.last().and_then(|table| table.first()) {
+            Some(lookup) if lookup.table_id.is_fixed() => self.f.len() - 1,
+            _ => self.f.len(),
+        }
+    }
+
+    /// The looked-up cells, row by row, and by column in each row. The column
+    /// of the fixed table is left out, and the no-op lookups are kept.
+    pub fn flatten(&self) -> impl Iterator<Item = LookupRecord<F, ID>> + '_ {
+        self.records(0)
+    }
+
+    /// Same as [Self::flatten], with the columns shifted by `offset`
+    fn records(&self, offset: usize) -> impl Iterator<Item = LookupRecord<F, ID>> + '_ {
+        let columns = &self.f[..self.n_looked_up_columns()];
+        let n_rows = columns.first().map_or(0, |column| column.len());
+        (0..n_rows).flat_map(move |row| {
+            columns
+                .iter()
+                .enumerate()
+                .filter_map(move |(column, lookups)| {
+                    let lookup = lookups.get(row)?;
+                    Some(LookupRecord {
+                        row,
+                        column: offset + column,
+                        table_id: lookup.table_id,
+                        values: lookup.value.clone(),
+                        numerator: lookup.numerator.clone(),
+                    })
+                })
+        })
+    }
+}
+
+impl<F: PrimeField, ID: LookupTableID> MVLookupWitness<F, ID> {
+    /// Build the witness of the lookups `records` into `table`, over a domain
+    /// of `domain_size` rows, e.g. from a dump read by [read_csv] or
+    /// [read_json]. The records must give every row of the looked-up columns
+    /// exactly once. The multiplicities are computed from the records, adding
+    /// their numerators to the first row of the table holding their value,
+    /// and the table column is appended, padded with zero entries.
+    pub fn from_records(
+        table: &LookupTable<F, ID>,
+        domain_size: usize,
+        records: impl IntoIterator<Item = LookupRecord<F, ID>>,
+    ) -> Result<Self, LookupTraceError> {
+        let table_id = table.table_id;
+        if table.entries.len() > domain_size {
+            return Err(LookupTraceError::TableTooLarge(table_id.to_u32()));
+        }
+        let mut index = HashMap::with_capacity(table.entries.len());
+        for (row, entry) in table.entries.iter().enumerate() {
+            index.entry(entry.as_slice()).or_insert(row);
+        }
+
+        let mut m = vec![F::zero(); domain_size];
+        let mut cells: Vec<Vec<Option<MVLookup<F, ID>>>> = vec![];
+        for record in records {
+            let LookupRecord {
+                row,
+                column,
+                table_id: record_table_id,
+                values,
+                numerator,
+            } = record;
+            if record_table_id != table_id {
+                return Err(LookupTraceError::TableMismatch {
+                    row,
+                    table_id: table_id.to_u32(),
+                });
+            }
+            if row >= domain_size {
+                return Err(LookupTraceError::RowOutOfRange { row, column });
+            }
+            if column >= cells.len() {
+                cells.resize_with(column + 1, || vec![None; domain_size]);
+            }
+            if cells[column][row].is_some() {
+                return Err(LookupTraceError::DuplicateRecord { row, column });
+            }
+            // The no-op lookups do not need to be in the table
+            if !numerator.is_zero() {
+                let i = *index
+                    .get(values.as_slice())
+                    .ok_or(LookupTraceError::NotInTable {
+                        row,
+                        table_id: table_id.to_u32(),
+                    })?;
+                m[i] += numerator;
+            }
+            cells[column][row] = Some(MVLookup::new(table_id, numerator, &values));
+        }
+
+        let mut f = cells
+            .into_iter()
+            .enumerate()
+            .map(|(column, cells)| {
+                cells
+                    .into_iter()
+                    .enumerate()
+                    .map(|(row, cell)| cell.ok_or(LookupTraceError::MissingRecord { row, column }))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let width = table.entries.first().map_or(1, |entry| entry.len());
+        let padding = vec![F::zero(); width];
+        f.push(
+            (0..domain_size)
+                .map(|i| {
+                    let entry = table.entries.get(i).unwrap_or(&padding);
+                    MVLookup::new(table_id, -m[i], entry)
+                })
+                .collect(),
+        );
+        Ok(MVLookupWitness { f, m })
+    }
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofInputs<N, G, ID> {
+    /// The looked-up cells of all the lookup witnesses, row by row, and by
+    /// column in each row. The columns are numbered across the lookup
+    /// witnesses, in the order of [Self::mvlookups], see
+    /// [MVLookupWitness::flatten].
+    pub fn lookup_trace(&self) -> impl Iterator<Item = LookupRecord<G::ScalarField, ID>> + '_ {
+        let offsets: Vec<usize> = self
+            .mvlookups
+            .iter()
+            .scan(0, |offset, witness| {
+                let current = *offset;
+                *offset += witness.n_looked_up_columns();
+                Some(current)
+            })
+            .collect();
+        let mut witnesses: Vec<_> = self
+            .mvlookups
+            .iter()
+            .zip(offsets)
+            .map(|(witness, offset)| witness.records(offset).peekable())
+            .collect();
+        // The records of each witness are sorted by row, and the witnesses
+        // are interleaved row by row
+        let (mut row, mut k) = (0, 0);
+        std::iter::from_fn(move || loop {
+            if k == witnesses.len() {
+                if witnesses.iter_mut().all(|records| records.peek().is_none()) {
+                    return None;
+                }
+                row += 1;
+                k = 0;
+                continue;
+            }
+            match witnesses[k].next_if(|record| record.row == row) {
+                Some(record) => return Some(record),
+                None => k += 1,
+            }
+        })
+    }
+}
+
+fn encode_field<F: PrimeField>(x: &F) -> String {
+    x.to_biguint().to_string()
+}
+
+/// Decode the canonical encoding of a field element, see [encode_field]
+fn decode_field<F: PrimeField>(line: usize, s: &str) -> Result<F, LookupTraceError> {
+    let malformed = || LookupTraceError::Malformed {
+        line,
+        reason: format!("{s:?} is not a canonical field element"),
+    };
+    let big: BigUint = s.parse().map_err(|_| malformed())?;
+    if big.to_string() != s {
+        return Err(malformed());
+    }
+    let x = F::from_biguint(&big).map_err(|_| malformed())?;
+    if x.to_biguint() != big {
+        return Err(malformed());
+    }
+    Ok(x)
+}
+
+/// The encoding of a record in the dumps
+#[derive(Serialize, Deserialize)]
+struct EncodedRecord {
+    row: usize,
+    column: usize,
+    table_id: u32,
+    values: Vec<String>,
+    numerator: String,
+}
+
+impl EncodedRecord {
+    fn encode<F: PrimeField, ID: LookupTableID>(record: &LookupRecord<F, ID>) -> Self {
+        EncodedRecord {
+            row: record.row,
+            column: record.column,
+            table_id: record.table_id.to_u32(),
+            values: record.values.iter().map(encode_field).collect(),
+            numerator: encode_field(&record.numerator),
+        }
+    }
+
+    fn decode<F: PrimeField, ID: LookupTableID>(
+        self,
+        line: usize,
+    ) -> Result<LookupRecord<F, ID>, LookupTraceError> {
+        let table_id = ID::try_from_u32(self.table_id)
+            .filter(|id| id.to_u32() == self.table_id)
+            .ok_or(LookupTraceError::UnknownTable {
+                line,
+                table_id: self.table_id,
+            })?;
+        Ok(LookupRecord {
+            row: self.row,
+            column: self.column,
+            table_id,
+            values: self
+                .values
+                .iter()
+                .map(|value| decode_field(line, value))
+                .collect::<Result<_, _>>()?,
+            numerator: decode_field(line, &self.numerator)?,
+        })
+    }
+}
+
+fn io_error(err: std::io::Error) -> LookupTraceError {
+    LookupTraceError::Io(err.to_string())
+}
+
+/// Write `records` to `writer` as CSV, after the header [CSV_HEADER]. The
+/// values of a record are separated by spaces.
+pub fn write_csv<F: PrimeField, ID: LookupTableID>(
+    mut writer: impl Write,
+    records: impl IntoIterator<Item = LookupRecord<F, ID>>,
+) -> Result<(), LookupTraceError> {
+    writeln!(writer, "{CSV_HEADER}").map_err(io_error)?;
+    for record in records {
+        let record = EncodedRecord::encode(&record);
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            record.row,
+            record.column,
+            record.table_id,
+            record.values.join(" "),
+            record.numerator
+        )
+        .map_err(io_error)?;
+    }
+    writer.flush().map_err(io_error)
+}
+
+/// Write `records` to `writer` as JSON Lines, i.e. one JSON object per line
+pub fn write_json<F: PrimeField, ID: LookupTableID>(
+    mut writer: impl Write,
+    records: impl IntoIterator<Item = LookupRecord<F, ID>>,
+) -> Result<(), LookupTraceError> {
+    for record in records {
+        serde_json::to_writer(&mut writer, &EncodedRecord::encode(&record))
+            .map_err(|err| LookupTraceError::Io(err.to_string()))?;
+        writeln!(writer).map_err(io_error)?;
+    }
+    writer.flush().map_err(io_error)
+}
+
+/// Read the records written by [write_csv], one line at a time. The lines
+/// are numbered from 1, the header included.
+pub fn read_csv<F: PrimeField, ID: LookupTableID>(
+    reader: impl BufRead,
+) -> impl Iterator<Item = Result<LookupRecord<F, ID>, LookupTraceError>> {
+    reader.lines().enumerate().filter_map(|(i, line)| {
+        let line_number = i + 1;
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => return Some(Err(io_error(err))),
+        };
+        if i == 0 {
+            return (line != CSV_HEADER).then(|| {
+                Err(LookupTraceError::Malformed {
+                    line: line_number,
+                    reason: "missing header".to_string(),
+                })
+            });
+        }
+        Some(parse_csv_line(line_number, &line))
+    })
+}
+
+fn parse_csv_line<F: PrimeField, ID: LookupTableID>(
+    line: usize,
+    s: &str,
+) -> Result<LookupRecord<F, ID>, LookupTraceError> {
+    let malformed = |reason: &str| LookupTraceError::Malformed {
+        line,
+        reason: reason.to_string(),
+    };
+    let fields: Vec<&str> = s.split(',').collect();
+    let [row, column, table_id, values, numerator] = fields[..] else {
+        return Err(malformed("expected 5 fields"));
+    };
+    EncodedRecord {
+        row: row.parse().map_err(|_| malformed("invalid row"))?,
+        column: column.parse().map_err(|_| malformed("invalid column"))?,
+        table_id: table_id
+            .parse()
+            .map_err(|_| malformed("invalid table ID"))?,
+        values: values.split(' ').map(str::to_string).collect(),
+        numerator: numerator.to_string(),
+    }
+    .decode(line)
+}
+
+/// Read the records written by [write_json], one line at a time. The lines
+/// are numbered from 1.
+pub fn read_json<F: PrimeField, ID: LookupTableID>(
+    reader: impl BufRead,
+) -> impl Iterator<Item = Result<LookupRecord<F, ID>, LookupTraceError>> {
+    reader.lines().enumerate().map(|(i, line)| {
+        let line_number = i + 1;
+        let line = line.map_err(io_error)?;
+        let record: EncodedRecord =
+            serde_json::from_str(&line).map_err(|err| LookupTraceError::Malformed {
+                line: line_number,
+                reason: err.to_string(),
+            })?;
+        record.decode(line_number)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_csv, read_json, write_csv, write_json, LookupRecord, LookupTraceError};
+    use crate::{
+        columns::{Column, ColumnIndexer},
+        ffa::{
+            columns::{FFAColumnIndexer, FFA_N_COLUMNS},
+            interpreter::{self as ffa_interpreter, FFAInterpreterEnv},
+            witness::WitnessBuilderEnv as FFAWitnessBuilderEnv,
+        },
+        lookup_fingerprint::WitnessFingerprint,
+        lookups::LookupTableIDs,
+        mvlookup::{LookupTable, MVLookupWitness},
+        proof::ProofInputs,
+        trace::TraceLayout,
+        BN254G1Affine, Ff1, Fp, N_LIMBS,
+    };
+    use ark_ff::{One, UniformRand, Zero};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use o1_utils::FieldHelpers;
+
+    const DOMAIN_SIZE: usize = 1 << 4;
+    const N_ROWS: usize = 3;
+
+    /// The columns of the carries of the FF addition
+    fn carry_column(i: usize) -> usize {
+        match FFAColumnIndexer::Carry(i).to_column() {
+            Column::X(column) => column,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The table of the carries `{-1, 0, 1}`
+    fn carry_table() -> LookupTable<Fp, LookupTableIDs> {
+        LookupTable {
+            table_id: LookupTableIDs::Custom(0),
+            entries: vec![vec![-Fp::one()], vec![Fp::zero()], vec![Fp::one()]],
+        }
+    }
+
+    /// A few rows of FF additions, whose carries are looked up in
+    /// [carry_table], the multiplicities being computed by
+    /// [ProofInputs::from_step_fn]
+    fn ffa_inputs() -> ProofInputs<FFA_N_COLUMNS, BN254G1Affine, LookupTableIDs> {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let mut witness_env = FFAWitnessBuilderEnv::<Fp>::empty();
+        for _ in 0..N_ROWS {
+            let (a, b) = (Ff1::rand(&mut rng), Ff1::rand(&mut rng));
+            ffa_interpreter::ff_addition_circuit(&mut witness_env, a, b);
+            witness_env.next_row();
+        }
+        let cols = witness_env.get_witness(DOMAIN_SIZE).evaluations.cols;
+
+        let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+        let layout = TraceLayout::new().with_table(carry_table(), N_LIMBS - 1);
+        ProofInputs::from_step_fn(domain, &layout, |row, writer| {
+            for (i, col) in cols.iter().enumerate() {
+                writer.set(i, col[row]);
+            }
+            if row < N_ROWS {
+                for i in 0..N_LIMBS - 1 {
+                    writer.lookup(LookupTableIDs::Custom(0), &[cols[carry_column(i)][row]]);
+                }
+            }
+        })
+        .unwrap()
+    }
+
+    /// Check that `got` has the same lookups, multiplicities and table as
+    /// `expected`
+    fn assert_equivalent(
+        got: &MVLookupWitness<Fp, LookupTableIDs>,
+        expected: &MVLookupWitness<Fp, LookupTableIDs>,
+    ) {
+        let diff = WitnessFingerprint::from(got).diff(&WitnessFingerprint::from(expected));
+        assert!(diff.is_empty(), "the witnesses differ: {diff:?}");
+    }
+
+    #[test]
+    fn test_ffa_lookup_trace() {
+        let inputs = ffa_inputs();
+        let cols = &inputs.evaluations.cols;
+        let records: Vec<_> = inputs.lookup_trace().collect();
+        assert!(inputs.mvlookups[0].flatten().eq(records.iter().cloned()));
+
+        // One record per carry and per row, the rows without additions being
+        // no-op lookups
+        assert_eq!(records.len(), DOMAIN_SIZE * (N_LIMBS - 1));
+        for (k, record) in records.iter().enumerate() {
+            let (row, i) = (k / (N_LIMBS - 1), k % (N_LIMBS - 1));
+            let expected = if row < N_ROWS {
+                LookupRecord {
+                    row,
+                    column: i,
+                    table_id: LookupTableIDs::Custom(0),
+                    values: vec![cols[carry_column(i)][row]],
+                    numerator: Fp::one(),
+                }
+            } else {
+                LookupRecord {
+                    row,
+                    column: i,
+                    table_id: LookupTableIDs::Custom(0),
+                    values: vec![Fp::zero()],
+                    numerator: Fp::zero(),
+                }
+            };
+            assert_eq!(*record, expected);
+        }
+
+        let mut csv = vec![];
+        write_csv(&mut csv, inputs.lookup_trace()).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("row,column,table_id,values,numerator"));
+        assert_eq!(
+            lines.next(),
+            Some(format!("0,0,3,{},1", cols[carry_column(0)][0].to_biguint()).as_str())
+        );
+        assert_eq!(lines.last(), Some("15,15,3,0,0"));
+
+        let mut json = vec![];
+        write_json(&mut json, inputs.lookup_trace()).unwrap();
+        assert_eq!(
+            String::from_utf8(json.clone()).unwrap().lines().last(),
+            Some(r#"{"row":15,"column":15,"table_id":3,"values":["0"],"numerator":"0"}"#)
+        );
+
+        // The dumps are ingested again, with the multiplicities computed
+        // from the records
+        let from_csv = MVLookupWitness::from_records(
+            &carry_table(),
+            DOMAIN_SIZE,
+            read_csv(csv.as_bytes()).map(Result::unwrap),
+        )
+        .unwrap();
+        assert_equivalent(&from_csv, &inputs.mvlookups[0]);
+        let from_json = MVLookupWitness::from_records(
+            &carry_table(),
+            DOMAIN_SIZE,
+            read_json(json.as_slice()).map(Result::unwrap),
+        )
+        .unwrap();
+        assert_equivalent(&from_json, &inputs.mvlookups[0]);
+    }
+
+    #[test]
+    fn test_lookup_trace_errors() {
+        let read =
+            |s: &str| read_csv::<Fp, LookupTableIDs>(s.as_bytes()).collect::<Result<Vec<_>, _>>();
+        let header = "row,column,table_id,values,numerator";
+        assert!(read(&format!("{header}\n0,0,3,1,1")).is_ok());
+        // The encoding of the field elements is canonical
+        let modulus = (-Fp::one()).to_biguint() + 1u32;
+        for value in ["01", "+1", "-1", "", &modulus.to_string()] {
+            assert!(
+                matches!(
+                    read(&format!("{header}\n0,0,3,{value},1")),
+                    Err(LookupTraceError::Malformed { line: 2, .. })
+                ),
+                "{value}"
+            );
+        }
+        assert!(matches!(
+            read("0,0,3,1,1"),
+            Err(LookupTraceError::Malformed { line: 1, .. })
+        ));
+        assert!(matches!(
+            read(&format!("{header}\n0,0,3,1")),
+            Err(LookupTraceError::Malformed { line: 2, .. })
+        ));
+        assert!(matches!(
+            read_json::<Fp, LookupTableIDs>(r#"{"row":0}"#.as_bytes()).next(),
+            Some(Err(LookupTraceError::Malformed { line: 1, .. }))
+        ));
+
+        let record = |row, column, value: u64| LookupRecord {
+            row,
+            column,
+            table_id: LookupTableIDs::Custom(0),
+            values: vec![Fp::from(value)],
+            numerator: Fp::one(),
+        };
+        let bits = LookupTable {
+            table_id: LookupTableIDs::Custom(0),
+            entries: vec![vec![Fp::zero()], vec![Fp::one()]],
+        };
+        let from_records = |records: Vec<LookupRecord<Fp, LookupTableIDs>>| {
+            MVLookupWitness::from_records(&bits, 2, records).map(|_| ())
+        };
+        assert_eq!(from_records(vec![record(0, 0, 1), record(1, 0, 0)]), Ok(()));
+        assert_eq!(
+            from_records(vec![record(0, 0, 1), record(1, 0, 2)]),
+            Err(LookupTraceError::NotInTable {
+                row: 1,
+                table_id: 3
+            })
+        );
+        assert_eq!(
+            from_records(vec![record(0, 0, 1), record(0, 0, 1)]),
+            Err(LookupTraceError::DuplicateRecord { row: 0, column: 0 })
+        );
+        assert_eq!(
+            from_records(vec![record(0, 0, 1), record(2, 0, 1)]),
+            Err(LookupTraceError::RowOutOfRange { row: 2, column: 0 })
+        );
+        assert_eq!(
+            from_records(vec![record(0, 0, 1)]),
+            Err(LookupTraceError::MissingRecord { row: 1, column: 0 })
+        );
+        assert_eq!(
+            from_records(vec![LookupRecord {
+                table_id: LookupTableIDs::Bit,
+                ..record(0, 0, 1)
+            }]),
+            Err(LookupTraceError::TableMismatch {
+                row: 0,
+                table_id: 3
+            })
+        );
+        assert_eq!(
+            MVLookupWitness::from_records(&carry_table(), 2, vec![]).map(|_| ()),
+            Err(LookupTraceError::TableTooLarge(3))
+        );
+    }
+}
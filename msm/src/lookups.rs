@@ -1,18 +1,26 @@
 //! Instantiate the MVLookup protocol for the MSM project.
 
-use crate::mvlookup::{LookupTableID, MVLookup, MVLookupWitness};
-use ark_ff::FftField;
+use crate::{
+    columns::Column,
+    expr::{curr_cell, E},
+    mvlookup::{LookupTableID, MVLookup, MVLookupWitness},
+    MAX_SUPPORTED_DEGREE,
+};
+use ark_ff::{FftField, One, PrimeField};
 use kimchi::circuits::domains::EvaluationDomains;
 use rand::{seq::SliceRandom, thread_rng, Rng};
-use std::{cmp::Ord, iter};
+use std::{cmp::Ord, fmt, iter};
 
 /// Lookup tables used in the MSM project
 // TODO: Add more built-in lookup tables
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum LookupTableIDs {
     RangeCheck16,
+    /// The table `{0, 1}`, used to check the booleanity of columns, see
+    /// [booleanity].
+    Bit,
     /// Custom lookup table
-    /// The index of the table is used as the ID, padded with the number of
+    /// The index of the table is used as the ID, padded with the IDs of the
     /// built-in tables.
     Custom(u32),
 }
@@ -21,20 +29,30 @@ impl LookupTableID for LookupTableIDs {
     fn to_u32(&self) -> u32 {
         match self {
             LookupTableIDs::RangeCheck16 => 1_u32,
-            LookupTableIDs::Custom(id) => id + 1,
+            LookupTableIDs::Bit => 2_u32,
+            LookupTableIDs::Custom(id) => id + 3,
         }
     }
 
     fn from_u32(id: u32) -> Self {
         match id {
             1 => LookupTableIDs::RangeCheck16,
-            _ => LookupTableIDs::Custom(id - 1),
+            2 => LookupTableIDs::Bit,
+            _ => LookupTableIDs::Custom(id - 3),
+        }
+    }
+
+    fn try_from_u32(id: u32) -> Option<Self> {
+        match id {
+            0 => None,
+            id => Some(Self::from_u32(id)),
         }
     }
 
     fn length(&self) -> usize {
         match self {
             LookupTableIDs::RangeCheck16 => 1 << 16,
+            LookupTableIDs::Bit => 2,
             LookupTableIDs::Custom(_) => todo!(),
         }
     }
@@ -127,4 +145,151 @@ impl<F: FftField> LookupWitness<F> {
             m,
         }
     }
+
+    /// Generate `n_queries` columns of random lookups into the table
+    /// `Custom(table_id)`, which contains the values `[0, table_size)` and is
+    /// padded with zeroes up to the domain size.
+    pub fn random_with_table(
+        domain: EvaluationDomains<F>,
+        table_id: u32,
+        table_size: u64,
+        n_queries: usize,
+    ) -> Self {
+        let mut rng = thread_rng();
+        let domain_size = domain.d1.size as usize;
+        assert!(table_size as usize <= domain_size);
+        let table_id = LookupTableIDs::Custom(table_id);
+        let mut m = vec![F::zero(); domain_size];
+        let mut f: Vec<Vec<Lookup<F>>> = (0..n_queries)
+            .map(|_| {
+                (0..domain_size)
+                    .map(|_| {
+                        let v = rng.gen_range(0..table_size);
+                        m[v as usize] += F::one();
+                        Lookup {
+                            table_id,
+                            numerator: F::one(),
+                            value: vec![F::from(v)],
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let t = (0..domain_size)
+            .map(|i| Lookup {
+                table_id,
+                numerator: -m[i],
+                value: vec![if (i as u64) < table_size {
+                    F::from(i as u64)
+                } else {
+                    F::zero()
+                }],
+            })
+            .collect();
+        f.push(t);
+        LookupWitness { f, m }
+    }
+}
+
+/// Check the booleanity of witness columns with lookups into the table
+/// [LookupTableIDs::Bit], instead of one constraint `x (x - 1) = 0` per
+/// column. All the columns share the same multiplicity column.
+pub mod booleanity {
+    use super::*;
+
+    /// Returns the lookups checking that each of the `columns` is boolean.
+    /// They must be given to [crate::mvlookup::constraint_lookups] for the
+    /// table [LookupTableIDs::Bit]. The lookups are packed into partial sums
+    /// of [MAX_SUPPORTED_DEGREE] - 2 terms, the table being the last term.
+    pub fn lookups<F: PrimeField>(columns: &[Column]) -> Vec<MVLookup<E<F>, LookupTableIDs>> {
+        columns
+            .iter()
+            .map(|col| MVLookup::new(LookupTableIDs::Bit, E::one(), &[curr_cell(*col)]))
+            .collect()
+    }
+
+    /// Returns the witness of the lookups built by [lookups], given the values
+    /// of the columns. The multiplicities of `0` and `1` are accumulated over
+    /// all the columns.
+    /// Non-boolean values are not counted, and the lookup argument will not be
+    /// satisfied.
+    pub fn witness<F: PrimeField>(
+        domain: EvaluationDomains<F>,
+        columns: &[Vec<F>],
+    ) -> LookupWitness<F> {
+        let domain_size = domain.d1.size as usize;
+        let mut m = vec![F::zero(); domain_size];
+        let mut f: Vec<Vec<Lookup<F>>> = columns
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|x| {
+                        if x.is_zero() {
+                            m[0] += F::one();
+                        } else if x.is_one() {
+                            m[1] += F::one();
+                        }
+                        Lookup::new(LookupTableIDs::Bit, F::one(), &[*x])
+                    })
+                    .collect()
+            })
+            .collect();
+        // The table is padded with zeroes, with a null multiplicity.
+        let t = (0..domain_size)
+            .map(|i| {
+                let v = if i == 1 { F::one() } else { F::zero() };
+                Lookup::new(LookupTableIDs::Bit, -m[i], &[v])
+            })
+            .collect();
+        f.push(t);
+        LookupWitness { f, m }
+    }
+
+    /// The cost of checking the booleanity of a number of columns, with
+    /// constraints or with lookups.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Shape {
+        /// The number of boolean columns
+        pub n_columns: usize,
+        /// The number of constraints `x (x - 1) = 0`, of degree 2
+        pub n_constraints: usize,
+        /// The number of columns added by the lookup argument, i.e. the
+        /// partial sums, the multiplicities, the table and the aggregation
+        pub n_lookup_columns: usize,
+        /// The number of constraints of the lookup argument, i.e. one per
+        /// partial sum and the aggregation, of degree up to
+        /// [MAX_SUPPORTED_DEGREE]
+        pub n_lookup_constraints: usize,
+    }
+
+    impl Shape {
+        pub fn new(n_columns: usize) -> Self {
+            // The table is the last term
+            let n_terms = n_columns + 1;
+            let n_partial_sums = (n_terms + MAX_SUPPORTED_DEGREE - 3) / (MAX_SUPPORTED_DEGREE - 2);
+            Shape {
+                n_columns,
+                n_constraints: n_columns,
+                n_lookup_columns: n_partial_sums + 3,
+                n_lookup_constraints: n_partial_sums + 1,
+            }
+        }
+    }
+
+    impl fmt::Display for Shape {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(f, "booleanity of {} columns:", self.n_columns)?;
+            writeln!(
+                f,
+                "  constraints: 0 additional columns, {} constraints of degree 2",
+                self.n_constraints
+            )?;
+            write!(
+                f,
+                "  lookups: {} additional columns, {} constraints of degree up to {}",
+                self.n_lookup_columns, self.n_lookup_constraints, MAX_SUPPORTED_DEGREE
+            )
+        }
+    }
 }
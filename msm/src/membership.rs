@@ -0,0 +1,711 @@
+//! Standalone proofs of membership of a committed column in a public table.
+//!
+//! A [MembershipProof] shows that all the values of a single column are in a
+//! fixed table, without any other column or constraint. It uses the lookup
+//! argument of [crate::mvlookup] with a single looked-up column: the prover
+//! commits to the column `f`, the multiplicities `m`, the partial sum `h` and
+//! the running sum `φ`, and the only constraints are the one of the partial
+//! sum, see [combine_lookups],
+//! ```text
+//! h(X) * (β + t(X)) * (β + f(X)) = (β + t(X)) - m(X) * (β + f(X))
+//! ```
+//! where the denominators also include the joint combiner and the table ID,
+//! and the one of the running sum, `φ(ωX) = φ(X) + h(X)`.
+//! The constraints have degree 3, so that the quotient polynomial only has two
+//! chunks. The table is public: its commitment is computed by the verifier,
+//! and it is not part of the proof.
+
+use ark_ff::{Field, One, PrimeField, Zero};
+use ark_poly::{
+    univariate::DensePolynomial, Evaluations, Polynomial, Radix2EvaluationDomain as R2D,
+    UVPolynomial,
+};
+use kimchi::{
+    circuits::{
+        domains::EvaluationDomains,
+        expr::{Challenges, ColumnEvaluations, Constants, ExprError, PolishToken},
+    },
+    curve::KimchiCurve,
+    groupmap::GroupMap,
+    plonk_sponge::FrSponge,
+    proof::PointEvaluations,
+};
+use mina_poseidon::{sponge::ScalarChallenge, FqSponge};
+use o1_utils::ExtendedDensePolynomial;
+use poly_commitment::{
+    commitment::{absorb_commitment, BatchEvaluationProof, Evaluation, PolyComm},
+    evaluation_proof::DensePolynomialOrEvaluations,
+    OpenProof, SRS,
+};
+use rand::{thread_rng, CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::{
+    columns::{Column, PartialSumIdx, TableIdx},
+    config::{DegeneratePointRule, OpeningMode},
+    expr::{curr_cell, next_cell, E},
+    mvlookup::{combine_lookups, LookupTable, LookupTableID, MVLookup},
+    verifier::borrowed_combined_inner_product,
+};
+
+/// The number of chunks of the commitment to the quotient polynomial: the
+/// constraints have degree 3
+const QUOTIENT_CHUNKS: usize = 2;
+
+/// The number of polynomials evaluated in a [MembershipProof], see
+/// [MembershipEvaluations]
+const N_EVALUATIONS: usize = 5;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MembershipError {
+    #[error("the table must have values of width 1")]
+    TableWidth,
+
+    #[error("the table has more entries than the domain has rows")]
+    TableTooLarge,
+
+    #[error("the column has {got} rows instead of {expected}")]
+    ColumnSizeMismatch { expected: usize, got: usize },
+
+    #[error("the value of the row {0} is not in the table")]
+    NotInTable(usize),
+
+    #[error("the proof is for another table")]
+    TableMismatch,
+
+    #[error("the evaluation point is zero or in the domain")]
+    DegenerateEvaluationPoint,
+
+    #[error("the proof is malformed: {0}")]
+    MalformedProof(&'static str),
+
+    #[error("the constraints could not be evaluated on the proof: {0}")]
+    ConstraintEvaluation(String),
+
+    #[error("the opening proof could not be verified")]
+    OpeningProofFailed,
+}
+
+/// The evaluations of the polynomials of a [MembershipProof] at `ζ` and at
+/// `ζω`: the column, the multiplicities, the partial sum, the running sum and
+/// the table, in this order.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+pub struct MembershipEvaluations<F> {
+    #[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")]
+    pub(crate) zeta: Vec<F>,
+    #[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")]
+    pub(crate) zeta_omega: Vec<F>,
+}
+
+impl<F: Copy> MembershipEvaluations<F> {
+    fn point_evaluations(&self, i: usize) -> PointEvaluations<F> {
+        PointEvaluations {
+            zeta: self.zeta[i],
+            zeta_omega: self.zeta_omega[i],
+        }
+    }
+}
+
+/// The proof that all the values of a column are in a public table, see the
+/// [module documentation](crate::membership)
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "OpeningProof: Serialize",
+    deserialize = "OpeningProof: Deserialize<'de>"
+))]
+pub struct MembershipProof<G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID> {
+    /// The table of the values of the column
+    #[serde(with = "crate::mvlookup::serde_table_id")]
+    pub(crate) table_id: ID,
+    /// The commitment to the column
+    pub(crate) column_comm: PolyComm<G>,
+    /// The commitments to the multiplicities, the partial sum and the
+    /// running sum
+    pub(crate) multiplicities_comm: PolyComm<G>,
+    pub(crate) partial_sum_comm: PolyComm<G>,
+    pub(crate) aggregation_comm: PolyComm<G>,
+    /// The commitment to the quotient polynomial, in two chunks
+    pub(crate) quotient_comm: PolyComm<G>,
+    pub(crate) evals: MembershipEvaluations<G::ScalarField>,
+    /// Evaluation of Z_H(ζ) (t_0(X) + ζ^n t_1(X)) at ζω, see
+    /// [crate::proof::ProofEvaluations]
+    #[serde_as(as = "o1_utils::serialization::SerdeAs")]
+    pub(crate) ft_eval1: G::ScalarField,
+    pub(crate) opening_proof: OpeningProof,
+}
+
+impl<G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID>
+    MembershipProof<G, OpeningProof, ID>
+{
+    /// The commitment to the column whose values are in the table
+    pub fn column_comm(&self) -> &PolyComm<G> {
+        &self.column_comm
+    }
+}
+
+/// The constraints of the membership of the column `X(0)` in the table
+/// `table_id`: the one of the partial sum, see [combine_lookups], and the one
+/// of the running sum
+fn membership_constraints<F: PrimeField, ID: LookupTableID>(table_id: ID) -> [E<F>; 2] {
+    let partial_sum = Column::LookupPartialSum(PartialSumIdx::new(0));
+    let lookups = vec![
+        MVLookup::new(table_id, E::one(), &[curr_cell(Column::X(0))]),
+        MVLookup::new(
+            table_id,
+            -curr_cell(Column::LookupMultiplicity(TableIdx::new(table_id.to_u32()))),
+            &[curr_cell(Column::LookupFixedTable(TableIdx::new(
+                table_id.to_u32(),
+            )))],
+        ),
+    ];
+    [
+        combine_lookups(partial_sum, lookups),
+        next_cell(Column::LookupAggregation)
+            - curr_cell(Column::LookupAggregation)
+            - curr_cell(partial_sum),
+    ]
+}
+
+/// The evaluations of the columns of the constraints of a membership proof
+struct MembershipColumns<'a, F, ID> {
+    evals: &'a MembershipEvaluations<F>,
+    table_id: ID,
+}
+
+impl<'a, F: Copy, ID: LookupTableID> ColumnEvaluations<F> for MembershipColumns<'a, F, ID> {
+    type Column = Column;
+
+    fn evaluate(&self, col: Self::Column) -> Result<PointEvaluations<F>, ExprError<Self::Column>> {
+        let table = TableIdx::new(self.table_id.to_u32());
+        let i = match col {
+            Column::X(0) => 0,
+            Column::LookupMultiplicity(idx) if idx == table => 1,
+            Column::LookupPartialSum(idx) if idx == PartialSumIdx::new(0) => 2,
+            Column::LookupAggregation => 3,
+            Column::LookupFixedTable(idx) if idx == table => 4,
+            _ => {
+                return Err(ExprError::MissingEvaluation(
+                    col,
+                    kimchi::circuits::gate::CurrOrNext::Curr,
+                ))
+            }
+        };
+        Ok(self.evals.point_evaluations(i))
+    }
+}
+
+/// The values of the table, padded with its first entry up to the domain
+/// size, and the row of each value
+fn table_column<F: PrimeField, ID: LookupTableID>(
+    table: &LookupTable<F, ID>,
+    domain_size: usize,
+) -> Result<(Vec<F>, HashMap<F, usize>), MembershipError> {
+    if table.entries.iter().any(|entry| entry.len() != 1) {
+        return Err(MembershipError::TableWidth);
+    }
+    if table.entries.len() > domain_size {
+        return Err(MembershipError::TableTooLarge);
+    }
+    let mut rows = HashMap::with_capacity(table.entries.len());
+    for (i, entry) in table.entries.iter().enumerate() {
+        rows.entry(entry[0]).or_insert(i);
+    }
+    let mut column: Vec<F> = table.entries.iter().map(|entry| entry[0]).collect();
+    let padding = column.first().copied().unwrap_or_else(F::zero);
+    column.resize(domain_size, padding);
+    Ok((column, rows))
+}
+
+/// `β + r * x + table_id`, the denominator of the lookup of `x`, see
+/// [combine_lookups]
+fn denominator<F: Field>(beta: F, joint_combiner: F, table_id: F, x: F) -> F {
+    beta + joint_combiner * x + table_id
+}
+
+/// Prove that all the values of `column` are in `table`. The column has the
+/// size of the domain.
+pub fn prove_membership<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    ID: LookupTableID,
+    RNG: RngCore + CryptoRng,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    table: &LookupTable<G::ScalarField, ID>,
+    column: &[G::ScalarField],
+    rng: &mut RNG,
+) -> Result<MembershipProof<G, OpeningProof, ID>, MembershipError> {
+    let domain_size = domain.d1.size as usize;
+    if column.len() != domain_size {
+        return Err(MembershipError::ColumnSizeMismatch {
+            expected: domain_size,
+            got: column.len(),
+        });
+    }
+    let (table_values, rows) = table_column(table, domain_size)?;
+    let mut m = vec![G::ScalarField::zero(); domain_size];
+    for (i, x) in column.iter().enumerate() {
+        let row = rows.get(x).ok_or(MembershipError::NotInTable(i))?;
+        m[*row] += G::ScalarField::one();
+    }
+
+    let interpolate = |evals: Vec<G::ScalarField>| {
+        Evaluations::<G::ScalarField, R2D<G::ScalarField>>::from_vec_and_domain(evals, domain.d1)
+            .interpolate()
+    };
+    let commit = |poly: &DensePolynomial<G::ScalarField>| srs.commit_non_hiding(poly, 1);
+
+    let mut fq_sponge = EFqSponge::new(G::other_curve_sponge_params());
+    let column_poly = interpolate(column.to_vec());
+    let column_comm = commit(&column_poly);
+    let multiplicities_poly = interpolate(m.clone());
+    let multiplicities_comm = commit(&multiplicities_poly);
+    absorb_commitment(&mut fq_sponge, &column_comm);
+    absorb_commitment(&mut fq_sponge, &multiplicities_comm);
+
+    let joint_combiner = fq_sponge.challenge();
+    let beta = fq_sponge.challenge();
+
+    // h(ω^i) = 1 / (β + f(ω^i)) - m(ω^i) / (β + t(ω^i)), and φ(1) = 0
+    let table_id = table.table_id.to_field::<G::ScalarField>();
+    let mut denominators: Vec<G::ScalarField> = column
+        .iter()
+        .chain(table_values.iter())
+        .map(|x| denominator(beta, joint_combiner, table_id, *x))
+        .collect();
+    ark_ff::batch_inversion(&mut denominators);
+    let (column_inverses, table_inverses) = denominators.split_at(domain_size);
+    let partial_sum: Vec<G::ScalarField> = (0..domain_size)
+        .map(|i| column_inverses[i] - m[i] * table_inverses[i])
+        .collect();
+    let mut aggregation = Vec::with_capacity(domain_size);
+    let mut acc = G::ScalarField::zero();
+    for h in partial_sum.iter() {
+        aggregation.push(acc);
+        acc += h;
+    }
+    // The values are in the table, so that the sum is zero
+    debug_assert!(acc.is_zero());
+
+    let partial_sum_poly = interpolate(partial_sum);
+    let partial_sum_comm = commit(&partial_sum_poly);
+    let aggregation_poly = interpolate(aggregation);
+    let aggregation_comm = commit(&aggregation_poly);
+    absorb_commitment(&mut fq_sponge, &partial_sum_comm);
+    absorb_commitment(&mut fq_sponge, &aggregation_comm);
+
+    let (_, endo_r) = G::endos();
+    let alpha: G::ScalarField = ScalarChallenge(fq_sponge.challenge()).to_field(endo_r);
+
+    // The constraints of [membership_constraints], combined with α
+    let table_poly = interpolate(table_values);
+    let constant = |c: G::ScalarField| DensePolynomial::from_coefficients_vec(vec![c]);
+    let denominator_poly = |poly: &DensePolynomial<G::ScalarField>| {
+        &poly.scale(joint_combiner) + &constant(beta + table_id)
+    };
+    let column_denominator = denominator_poly(&column_poly);
+    let table_denominator = denominator_poly(&table_poly);
+    let partial_sum_constraint = &(&(&partial_sum_poly * &table_denominator) * &column_denominator)
+        - &(&table_denominator - &(&multiplicities_poly * &column_denominator));
+    let shifted_aggregation = {
+        let mut coeffs = aggregation_poly.coeffs.clone();
+        let mut power = G::ScalarField::one();
+        for coeff in coeffs.iter_mut() {
+            *coeff *= power;
+            power *= domain.d1.group_gen;
+        }
+        DensePolynomial::from_coefficients_vec(coeffs)
+    };
+    let aggregation_constraint = &(&shifted_aggregation - &aggregation_poly) - &partial_sum_poly;
+    let constraint = &partial_sum_constraint + &aggregation_constraint.scale(alpha);
+    let (quotient_poly, remainder) =
+        constraint
+            .divide_by_vanishing_poly(domain.d1)
+            .ok_or(MembershipError::MalformedProof(
+                "the quotient can not be computed",
+            ))?;
+    if !remainder.is_zero() {
+        return Err(MembershipError::MalformedProof(
+            "the constraints are not satisfied",
+        ));
+    }
+    let quotient_comm = srs.commit_non_hiding(&quotient_poly, QUOTIENT_CHUNKS);
+    absorb_commitment(&mut fq_sponge, &quotient_comm);
+
+    let zeta = DegeneratePointRule::default()
+        .derive_evaluation_point(domain, || {
+            ScalarChallenge(fq_sponge.challenge()).to_field(endo_r)
+        })
+        .ok_or(MembershipError::DegenerateEvaluationPoint)?;
+    let evaluation_points = OpeningMode::TwoPoints.evaluation_points(zeta, domain);
+
+    let polys = [
+        &column_poly,
+        &multiplicities_poly,
+        &partial_sum_poly,
+        &aggregation_poly,
+        &table_poly,
+    ];
+    let evals = MembershipEvaluations {
+        zeta: polys.iter().map(|poly| poly.evaluate(&zeta)).collect(),
+        zeta_omega: polys
+            .iter()
+            .map(|poly| poly.evaluate(&evaluation_points[1]))
+            .collect(),
+    };
+
+    let fq_sponge_before_evaluations = fq_sponge.clone();
+    let mut fr_sponge = EFrSponge::new(G::sponge_params());
+    fr_sponge.absorb(&fq_sponge.digest());
+    evals
+        .zeta
+        .iter()
+        .chain(evals.zeta_omega.iter())
+        .for_each(|x| fr_sponge.absorb(x));
+
+    // ft(X) = (1 - ζ^n) (t_0(X) + ζ^n t_1(X)), whose evaluation at ζ is the
+    // opposite of the one of the constraints
+    let ft = {
+        let zeta_to_domain_size = zeta.pow([domain.d1.size]);
+        quotient_poly
+            .to_chunked_polynomial(QUOTIENT_CHUNKS, domain_size)
+            .linearize(zeta_to_domain_size)
+            .scale(G::ScalarField::one() - zeta_to_domain_size)
+    };
+    let ft_eval1 = ft.evaluate(&evaluation_points[1]);
+    fr_sponge.absorb(&ft_eval1);
+
+    let v = fr_sponge.challenge().to_field(endo_r);
+    let u = fr_sponge.challenge().to_field(endo_r);
+    let non_hiding = PolyComm {
+        elems: vec![G::ScalarField::zero()],
+    };
+    let polynomials: Vec<_> = polys
+        .into_iter()
+        .chain([&ft])
+        .map(|poly| {
+            (
+                DensePolynomialOrEvaluations::DensePolynomial(poly),
+                non_hiding.clone(),
+            )
+        })
+        .collect();
+    let group_map = G::Map::setup();
+    let opening_proof = OpenProof::open::<_, _, R2D<G::ScalarField>>(
+        srs,
+        &group_map,
+        polynomials.as_slice(),
+        &evaluation_points,
+        v,
+        u,
+        fq_sponge_before_evaluations,
+        rng,
+    );
+
+    Ok(MembershipProof {
+        table_id: table.table_id,
+        column_comm,
+        multiplicities_comm,
+        partial_sum_comm,
+        aggregation_comm,
+        quotient_comm,
+        evals,
+        ft_eval1,
+        opening_proof,
+    })
+}
+
+/// Verify a proof created with [prove_membership] that all the values of the
+/// column committed in `proof` are in `table`
+pub fn verify_membership<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    table: &LookupTable<G::ScalarField, ID>,
+    proof: &MembershipProof<G, OpeningProof, ID>,
+) -> Result<(), MembershipError> {
+    let MembershipProof {
+        table_id,
+        column_comm,
+        multiplicities_comm,
+        partial_sum_comm,
+        aggregation_comm,
+        quotient_comm,
+        evals,
+        ft_eval1,
+        opening_proof,
+    } = proof;
+    if *table_id != table.table_id {
+        return Err(MembershipError::TableMismatch);
+    }
+    let domain_size = domain.d1.size as usize;
+    if evals.zeta.len() != N_EVALUATIONS || evals.zeta_omega.len() != N_EVALUATIONS {
+        return Err(MembershipError::MalformedProof(
+            "the proof does not have the evaluations of each polynomial",
+        ));
+    }
+    let column_comms = [
+        column_comm,
+        multiplicities_comm,
+        partial_sum_comm,
+        aggregation_comm,
+    ];
+    if column_comms.iter().any(|comm| comm.elems.len() != 1)
+        || quotient_comm.elems.len() != QUOTIENT_CHUNKS
+    {
+        return Err(MembershipError::MalformedProof(
+            "the commitments do not have the expected number of chunks",
+        ));
+    }
+    let (table_values, _) = table_column(table, domain_size)?;
+    let table_comm = srs.commit_evaluations_non_hiding(
+        domain.d1,
+        &Evaluations::from_vec_and_domain(table_values, domain.d1),
+    );
+
+    let mut fq_sponge = EFqSponge::new(G::other_curve_sponge_params());
+    absorb_commitment(&mut fq_sponge, column_comm);
+    absorb_commitment(&mut fq_sponge, multiplicities_comm);
+    let joint_combiner = fq_sponge.challenge();
+    let beta = fq_sponge.challenge();
+    absorb_commitment(&mut fq_sponge, partial_sum_comm);
+    absorb_commitment(&mut fq_sponge, aggregation_comm);
+    let (_, endo_r) = G::endos();
+    let alpha: G::ScalarField = ScalarChallenge(fq_sponge.challenge()).to_field(endo_r);
+    absorb_commitment(&mut fq_sponge, quotient_comm);
+    let zeta = DegeneratePointRule::default()
+        .derive_evaluation_point(domain, || {
+            ScalarChallenge(fq_sponge.challenge()).to_field(endo_r)
+        })
+        .ok_or(MembershipError::DegenerateEvaluationPoint)?;
+    let evaluation_points = OpeningMode::TwoPoints.evaluation_points(zeta, domain);
+
+    let fq_sponge_before_evaluations = fq_sponge.clone();
+    let mut fr_sponge = EFrSponge::new(G::sponge_params());
+    fr_sponge.absorb(&fq_sponge.digest());
+    evals
+        .zeta
+        .iter()
+        .chain(evals.zeta_omega.iter())
+        .for_each(|x| fr_sponge.absorb(x));
+    fr_sponge.absorb(ft_eval1);
+    let v = fr_sponge.challenge().to_field(endo_r);
+    let u = fr_sponge.challenge().to_field(endo_r);
+
+    // The evaluation of the constraints at ζ
+    let challenges = Challenges {
+        alpha,
+        beta,
+        gamma: G::ScalarField::zero(),
+        joint_combiner: Some(joint_combiner),
+    };
+    let constants = Constants {
+        endo_coefficient: *endo_r,
+        mds: &G::sponge_params().mds,
+        zk_rows: 0,
+    };
+    let columns = MembershipColumns {
+        evals,
+        table_id: table.table_id,
+    };
+    let [partial_sum_constraint, aggregation_constraint] = membership_constraints(table.table_id)
+        .map(|constraint| {
+            PolishToken::evaluate(
+                constraint.to_polish().as_slice(),
+                domain.d1,
+                zeta,
+                &columns,
+                &constants,
+                &challenges,
+            )
+            .map_err(|err| MembershipError::ConstraintEvaluation(err.to_string()))
+        });
+    let ft_eval0 = -(partial_sum_constraint? + alpha * aggregation_constraint?);
+
+    let ft_comm = {
+        let zeta_to_domain_size = zeta.pow([domain.d1.size]);
+        quotient_comm
+            .chunk_commitment(zeta_to_domain_size)
+            .scale(G::ScalarField::one() - zeta_to_domain_size)
+    };
+    let mut evaluations: Vec<Evaluation<G>> = column_comms
+        .into_iter()
+        .chain([&table_comm])
+        .enumerate()
+        .map(|(i, comm)| Evaluation {
+            commitment: comm.clone(),
+            evaluations: vec![vec![evals.zeta[i]], vec![evals.zeta_omega[i]]],
+        })
+        .collect();
+    evaluations.push(Evaluation {
+        commitment: ft_comm,
+        evaluations: vec![vec![ft_eval0], vec![*ft_eval1]],
+    });
+    let combined_inner_product = borrowed_combined_inner_product(v, u, &evaluations);
+    let batch = BatchEvaluationProof {
+        sponge: fq_sponge_before_evaluations,
+        evaluations,
+        evaluation_points,
+        polyscale: v,
+        evalscale: u,
+        opening: opening_proof,
+        combined_inner_product,
+    };
+    let group_map = G::Map::setup();
+    if OpeningProof::verify(srs, &group_map, &mut [batch], &mut thread_rng()) {
+        Ok(())
+    } else {
+        Err(MembershipError::OpeningProofFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_membership, verify_membership, MembershipError, MembershipProof};
+    use crate::{
+        config::ProtocolConfig,
+        lookups::LookupTableIDs,
+        mvlookup::{LookupTable, LookupTableID},
+        prover::{prove_with_context, ProverContext},
+        serialization,
+        test::random_lookup_circuit,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::UniformRand;
+    use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+
+    fn setup_srs(domain: EvaluationDomains<Fp>) -> PairingSRS<BN254> {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let x = Fp::rand(&mut rng);
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(x, domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        srs
+    }
+
+    /// The table of the values `[0, size)`
+    fn range_table<ID: LookupTableID>(table_id: ID, size: u64) -> LookupTable<Fp, ID> {
+        LookupTable {
+            table_id,
+            entries: (0..size).map(|i| vec![Fp::from(i)]).collect(),
+        }
+    }
+
+    fn prove<ID: LookupTableID>(
+        domain: EvaluationDomains<Fp>,
+        srs: &PairingSRS<BN254>,
+        table: &LookupTable<Fp, ID>,
+        column: &[Fp],
+    ) -> Result<MembershipProof<BN254G1Affine, OpeningProof, ID>, MembershipError> {
+        let mut rng = o1_utils::tests::make_test_rng();
+        prove_membership::<_, OpeningProof, BaseSponge, ScalarSponge, _, _>(
+            domain, srs, table, column, &mut rng,
+        )
+    }
+
+    fn verify<ID: LookupTableID>(
+        domain: EvaluationDomains<Fp>,
+        srs: &PairingSRS<BN254>,
+        table: &LookupTable<Fp, ID>,
+        proof: &MembershipProof<BN254G1Affine, OpeningProof, ID>,
+    ) -> Result<(), MembershipError> {
+        verify_membership::<_, OpeningProof, BaseSponge, ScalarSponge, _>(domain, srs, table, proof)
+    }
+
+    #[test]
+    fn test_membership_of_15_bit_values() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 15).unwrap();
+        let srs = setup_srs(domain);
+        let table = range_table(serialization::LookupTable::RangeCheck15, 1 << 15);
+        let mut column: Vec<Fp> = (0..domain.d1.size)
+            .map(|_| Fp::from(rng.gen_range(0..1u64 << 15)))
+            .collect();
+
+        let proof = prove(domain, &srs, &table, &column).unwrap();
+        assert_eq!(verify(domain, &srs, &table, &proof), Ok(()));
+
+        // The proof is bound to its table
+        let small_table = range_table(serialization::LookupTable::RangeCheck4, 1 << 4);
+        assert_eq!(
+            verify(domain, &srs, &small_table, &proof),
+            Err(MembershipError::TableMismatch)
+        );
+        let mut relabelled = proof.clone();
+        relabelled.table_id = serialization::LookupTable::RangeCheck4;
+        assert_eq!(
+            verify(domain, &srs, &small_table, &relabelled),
+            Err(MembershipError::OpeningProofFailed)
+        );
+
+        let mut tampered = proof;
+        tampered.evals.zeta[0] += Fp::from(1u64);
+        assert_eq!(
+            verify(domain, &srs, &table, &tampered),
+            Err(MembershipError::OpeningProofFailed)
+        );
+
+        // A single value out of the range
+        column[42] = Fp::from(1u64 << 15);
+        assert_eq!(
+            prove(domain, &srs, &table, &column).map(|_| ()),
+            Err(MembershipError::NotInTable(42))
+        );
+    }
+
+    #[test]
+    fn test_membership_proof_is_smaller_than_full_proof() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+        let table_size = domain.d1.size;
+
+        // The baseline: a full proof of a single column, only looked up in
+        // the table
+        let (constraints, inputs) =
+            random_lookup_circuit::<1, BN254G1Affine, _>(domain, 1, table_size, 1, &mut rng);
+        let column = inputs.evaluations.cols[0].clone();
+        let ctx =
+            ProverContext::new(domain, &srs, ProtocolConfig::default(), &constraints).unwrap();
+        let full_proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            1,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+
+        let table = range_table(LookupTableIDs::Custom(1), table_size);
+        let proof = prove(domain, &srs, &table, &column).unwrap();
+        assert_eq!(verify(domain, &srs, &table, &proof), Ok(()));
+
+        let full_size = rmp_serde::to_vec(&full_proof).unwrap().len();
+        let size = rmp_serde::to_vec(&proof).unwrap().len();
+        assert!(
+            size < full_size,
+            "{size} bytes, {full_size} for a full proof"
+        );
+        let decoded: MembershipProof<BN254G1Affine, OpeningProof, LookupTableIDs> =
+            rmp_serde::from_slice(&rmp_serde::to_vec(&proof).unwrap()).unwrap();
+        assert_eq!(decoded, proof);
+    }
+}
@@ -1,13 +1,33 @@
 //! Implement the protocol MVLookup <https://eprint.iacr.org/2022/1530.pdf>
 
-use ark_ff::{Field, PrimeField, Zero};
-use std::{collections::BTreeMap, hash::Hash};
+use ark_ff::{Field, One, PrimeField, Zero};
+use ark_poly::{Evaluations, Radix2EvaluationDomain as D};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::serde_as;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    hash::Hash,
+    marker::PhantomData,
+};
+use thiserror::Error;
 
-use kimchi::circuits::expr::{ChallengeTerm, ConstantExpr, ConstantTerm, ExprInner};
+use kimchi::{
+    circuits::{
+        domains::EvaluationDomains,
+        expr::{ChallengeTerm, ConstantExpr, ConstantTerm, ExprInner, Literal, RowOffset},
+    },
+    curve::KimchiCurve,
+};
+use poly_commitment::{commitment::PolyComm, OpenProof, SRS as _};
 
 use crate::{
-    columns::Column,
-    expr::{curr_cell, next_cell, E},
+    columns::{Column, PartialSumIdx, TableIdx},
+    config::{ConfigError, ProtocolConfig},
+    expr::{column_accesses, curr_cell, next_cell, E},
+    fuzz::{check_canonical_map, Decode, DeserializeError},
+    lookup_engine::PlookupColumns,
+    witness::Witness,
     MAX_SUPPORTED_DEGREE,
 };
 
@@ -18,10 +38,15 @@ use crate::{
 /// The table ID is added to the random linear combination formed with the
 /// values. The combiner for the random linear combination is coined during the
 /// proving phase by the prover.
-#[derive(Debug, Clone)]
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
 pub struct MVLookup<F, ID: LookupTableID> {
+    #[serde(with = "serde_table_id")]
     pub(crate) table_id: ID,
+    #[serde_as(as = "o1_utils::serialization::SerdeAs")]
     pub(crate) numerator: F,
+    #[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")]
     pub(crate) value: Vec<F>,
 }
 
@@ -41,15 +66,81 @@ where
     }
 }
 
+impl<F: Zero, ID: LookupTableID> MVLookup<F, ID> {
+    /// The canonical no-op lookup into the table `table_id`, padding the terms
+    /// of the table, see [chunk_padding]. Its numerator is zero, hence its
+    /// term `0 / (β + table_id)` is zero whatever the value and the table are:
+    /// it adds nothing to the partial sums, and the value does not need to be
+    /// in the table. The denominator is only zero if `β = -table_id`, which
+    /// happens with a negligible probability.
+    pub fn noop(table_id: ID) -> Self {
+        MVLookup {
+            table_id,
+            numerator: F::zero(),
+            value: vec![F::zero()],
+        }
+    }
+}
+
+impl<F: PrimeField, ID: LookupTableID> MVLookup<E<F>, ID> {
+    /// The term of the table `table_id` in the partial sums of the lookup
+    /// argument, i.e. `-m(X) / (β + t(X))`.
+    /// The sign convention: the looked-up values are added with their own
+    /// numerator, and the table is subtracted with its multiplicities, the
+    /// numerator of the term being `-m(X)`. The witness follows the same
+    /// convention, the numerators of the table column of a
+    /// [MVLookupWitness] being `-m`.
+    /// The value is the column [Column::LookupFixedTable], which contains the
+    /// values of the table already combined with the joint combiner and the
+    /// table ID, i.e. `t(X) = table_id + r * t_{1}(X) + ...`. The denominator
+    /// of the term is therefore `β + t(X)`, and not the one of a looked-up
+    /// value, see [combine_lookups].
+    pub fn table_term(table_id: ID) -> Self {
+        let idx = TableIdx::new(table_id.to_u32());
+        MVLookup {
+            table_id,
+            numerator: -curr_cell(Column::LookupMultiplicity(idx)),
+            value: vec![curr_cell(Column::LookupFixedTable(idx))],
+        }
+    }
+}
+
+/// The exclusive bound of the IDs of the tables, see [LookupTableID::to_u32].
+///
+/// The ID of a table is the constant term of the denominators of its lookups,
+/// `β + id + r·v_1 + … + r^k·v_k`, see [combine_lookups]. Two lookups
+/// `(id_1, v_1)` and `(id_2, v_2)` into distinct tables have the same
+/// denominator only if `r` is a root of the polynomial
+/// `(id_1 - id_2) + Σ r^i (v_{1,i} - v_{2,i})`, whose constant term is not
+/// zero: the joint combiner, coined after the values are committed, is one of
+/// its at most `k` roots with a negligible probability. The argument holds if
+/// the field elements of distinct IDs are distinct, which is enforced by
+/// [verify_table_id_impl] when the tables are registered: the encodings are
+/// injective, the field elements of the IDs are their encodings, and the
+/// encodings are below this bound, far below the characteristic of the field.
+/// The bound also rejects the encodings built from a buggy cast, e.g. of a
+/// negative integer.
+pub const MAX_TABLE_ID: u32 = 1 << 16;
+
 /// Trait for lookup table variants
 pub trait LookupTableID: Send + Sync + Copy + Hash + Eq + PartialEq + Ord + PartialOrd {
-    /// Assign a unique ID, as a u32 value
+    /// Assign a unique ID, as a u32 value below [MAX_TABLE_ID]
     fn to_u32(&self) -> u32;
 
     /// Build a value from a u32
     fn from_u32(value: u32) -> Self;
 
-    /// Assign a unique ID to the lookup tables.
+    /// Build a value from a u32, or returns `None` if `value` is not the
+    /// encoding of a table. The IDs of the proofs are decoded with it, and the
+    /// implementations whose [LookupTableID::from_u32] panics on some values
+    /// must override it for the deserialization of untrusted proofs to be
+    /// panic-free, see [crate::fuzz].
+    fn try_from_u32(value: u32) -> Option<Self> {
+        Some(Self::from_u32(value))
+    }
+
+    /// Assign a unique ID to the lookup tables. It must be the field element
+    /// of [LookupTableID::to_u32], see [MAX_TABLE_ID].
     fn to_field<F: Field>(&self) -> F {
         F::from(self.to_u32())
     }
@@ -59,7 +150,8 @@ pub trait LookupTableID: Send + Sync + Copy + Hash + Eq + PartialEq + Ord + Part
     /// like range checks.
     fn is_fixed(&self) -> bool;
 
-    /// Assign a unique ID to the lookup tables, as an expression.
+    /// Assign a unique ID to the lookup tables, as an expression. It must be
+    /// the constant [LookupTableID::to_field].
     fn to_constraint<F: Field>(&self) -> E<F> {
         let f = self.to_field();
         let f = ConstantExpr::from(ConstantTerm::Literal(f));
@@ -68,6 +160,414 @@ pub trait LookupTableID: Send + Sync + Copy + Hash + Eq + PartialEq + Ord + Part
 
     /// Returns the length of each table.
     fn length(&self) -> usize;
+
+    /// Returns the fixed table bounding the multiplicities of this table, if
+    /// any. The bounding table must contain the values `[0, length)`, and its
+    /// own multiplicities must not be bounded.
+    /// The multiplicities are then range checked with lookups into the
+    /// bounding table, added by [constraint_lookups] and
+    /// [add_multiplicity_range_checks].
+    fn multiplicity_bound(&self) -> Option<Self> {
+        None
+    }
+}
+
+/// Errors found in an implementation of [LookupTableID], see
+/// [verify_table_id_impl]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TableIdImplError {
+    #[error("the table {id} is decoded as the table {got}")]
+    RoundTrip { id: u32, got: u32 },
+
+    #[error("distinct tables are encoded with the same ID {0}")]
+    NotInjective(u32),
+
+    #[error("the field element of the table {0} is not its ID")]
+    FieldMismatch(u32),
+
+    #[error("the ID {0} of a table is not below {}", MAX_TABLE_ID)]
+    IdTooLarge(u32),
+
+    #[error("the fixed table {0} is empty")]
+    EmptyFixedTable(u32),
+}
+
+/// Check the encoding of `ids`: each ID is below [MAX_TABLE_ID] and decoded
+/// as itself, distinct IDs have distinct encodings, and their field elements
+/// and constant expressions are their encodings.
+pub(crate) fn verify_table_id_encoding<ID: LookupTableID>(
+    ids: &[ID],
+) -> Result<(), TableIdImplError> {
+    if let Some(id) = ids.iter().find(|id| id.to_u32() >= MAX_TABLE_ID) {
+        return Err(TableIdImplError::IdTooLarge(id.to_u32()));
+    }
+    // A non-injective encoding can not be decoded either, the injectivity is
+    // checked first to report the cause
+    let mut encodings: BTreeMap<u32, ID> = BTreeMap::new();
+    for id in ids {
+        let encoding = id.to_u32();
+        if encodings
+            .insert(encoding, *id)
+            .map_or(false, |other| other != *id)
+        {
+            return Err(TableIdImplError::NotInjective(encoding));
+        }
+    }
+    for id in ids {
+        let encoding = id.to_u32();
+        let decoded = ID::from_u32(encoding);
+        if decoded != *id || ID::try_from_u32(encoding) != Some(*id) {
+            return Err(TableIdImplError::RoundTrip {
+                id: encoding,
+                got: decoded.to_u32(),
+            });
+        }
+        let field = crate::Fp::from(encoding);
+        let constant = E::Atom(ExprInner::Constant(ConstantExpr::from(
+            ConstantTerm::Literal(field),
+        )));
+        if id.to_field::<crate::Fp>() != field || id.to_constraint::<crate::Fp>() != constant {
+            return Err(TableIdImplError::FieldMismatch(encoding));
+        }
+    }
+    Ok(())
+}
+
+/// Check the implementation of [LookupTableID] on `ids`: the IDs are below
+/// [MAX_TABLE_ID] and decoded as themselves, distinct IDs have distinct
+/// encodings, their field elements are their encodings, and the fixed tables
+/// are not empty. These are the invariants of the separation of the tables,
+/// see [MAX_TABLE_ID].
+/// An inconsistent implementation silently mixes the tables, which are
+/// indexed by their encodings in the proofs.
+/// An implementation generated with [crate::lookup_table_id] passes the
+/// checks.
+pub fn verify_table_id_impl<ID: LookupTableID>(ids: &[ID]) -> Result<(), TableIdImplError> {
+    verify_table_id_encoding(ids)?;
+    match ids.iter().find(|id| id.is_fixed() && id.length() == 0) {
+        Some(id) => Err(TableIdImplError::EmptyFixedTable(id.to_u32())),
+        None => Ok(()),
+    }
+}
+
+/// Define a fieldless enum of lookup tables, and implement [LookupTableID] for
+/// it. The tables are encoded with their index, starting from zero, and are
+/// either fixed tables of a given length, or RAM lookups.
+/// ```
+/// use kimchi_msm::api::{lookup_table_id, verify_table_id_impl, LookupTableID};
+///
+/// lookup_table_id! {
+///     pub enum Tables {
+///         RangeCheck8 = fixed(1 << 8),
+///         Memory = ram,
+///     }
+/// }
+///
+/// assert_eq!(Tables::from_u32(1), Tables::Memory);
+/// assert_eq!(Tables::RangeCheck8.length(), 256);
+/// assert!(verify_table_id_impl(&[Tables::RangeCheck8, Tables::Memory]).is_ok());
+/// ```
+#[macro_export]
+macro_rules! lookup_table_id {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_attr:meta])* $variant:ident = $kind:ident $(($length:expr))?),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+        $vis enum $name {
+            $($(#[$variant_attr])* $variant),*
+        }
+
+        impl $crate::api::LookupTableID for $name {
+            fn to_u32(&self) -> u32 {
+                *self as u32
+            }
+
+            fn from_u32(value: u32) -> Self {
+                Self::try_from_u32(value).unwrap_or_else(|| panic!("Invalid table ID {value}"))
+            }
+
+            fn try_from_u32(value: u32) -> Option<Self> {
+                $(
+                    if value == $name::$variant as u32 {
+                        return Some($name::$variant);
+                    }
+                )*
+                None
+            }
+
+            fn is_fixed(&self) -> bool {
+                match self {
+                    $($name::$variant => $crate::lookup_table_id!(@is_fixed $kind)),*
+                }
+            }
+
+            fn length(&self) -> usize {
+                match self {
+                    $($name::$variant => $crate::lookup_table_id!(@length $kind $(($length))?)),*
+                }
+            }
+        }
+    };
+    (@is_fixed fixed) => { true };
+    (@is_fixed ram) => { false };
+    (@length fixed($length:expr)) => { $length };
+    (@length ram) => { panic!("RAM tables do not have a fixed length") };
+}
+
+/// Define a fieldless enum of fixed lookup tables whose entries are given in
+/// Rust, and implement [LookupTableID] for it. Each table is given an explicit
+/// ID, and its entries either as a constant array of rows of `u64`,
+/// `entries(ROWS)`, or as a function of the index of the row,
+/// `generate(length, |i| row)`.
+/// Besides the [LookupTableID] implementation, the enum gets:
+/// - `ALL`, the tables in the order of their definition;
+/// - `entries::<F>()`, the entries of a table as field elements;
+/// - `tables::<F>()`, the [LookupTable] of each table, to be registered with
+///   [generate_global_tables].
+///
+/// The RAM lookups do not have entries, see [crate::lookup_table_id] instead.
+/// ```
+/// use kimchi_msm::api::{define_lookup_tables, verify_table_id_impl, Fp, LookupTableID};
+///
+/// const SBOX: [[u64; 2]; 4] = [[0, 3], [1, 0], [2, 1], [3, 2]];
+///
+/// define_lookup_tables! {
+///     pub enum Tables {
+///         SBox = 1 => entries(SBOX),
+///         Square = 2 => generate(256, |i| [i, i * i]),
+///     }
+/// }
+///
+/// assert_eq!(Tables::from_u32(2), Tables::Square);
+/// assert_eq!(Tables::Square.length(), 256);
+/// assert_eq!(Tables::SBox.entries::<Fp>()[1], vec![Fp::from(1u64), Fp::from(0u64)]);
+/// assert_eq!(Tables::tables::<Fp>().len(), 2);
+/// assert!(verify_table_id_impl(Tables::ALL).is_ok());
+/// ```
+/// Two tables can not be given the same ID:
+/// ```compile_fail
+/// use kimchi_msm::api::define_lookup_tables;
+///
+/// define_lookup_tables! {
+///     pub enum Tables {
+///         Bits = 1 => generate(2, |i| [i]),
+///         Bytes = 1 => generate(256, |i| [i]),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_lookup_tables {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_attr:meta])* $variant:ident = $id:literal => $kind:ident($($args:tt)*)),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+        #[repr(u32)]
+        $vis enum $name {
+            $($(#[$variant_attr])* $variant = $id),*
+        }
+
+        impl $name {
+            /// The tables, in the order of their definition
+            pub const ALL: &'static [$name] = &[$($name::$variant),*];
+
+            /// Returns the entries of the table
+            pub fn entries<F: ::ark_ff::Field>(&self) -> Vec<Vec<F>> {
+                match self {
+                    $($name::$variant => $crate::define_lookup_tables!(@entries $kind($($args)*))),*
+                }
+            }
+
+            /// Returns all the tables, e.g. to commit to them with
+            /// `generate_global_tables`
+            pub fn tables<F: ::ark_ff::Field>() -> Vec<$crate::api::LookupTable<F, $name>> {
+                Self::ALL
+                    .iter()
+                    .map(|table_id| $crate::api::LookupTable {
+                        table_id: *table_id,
+                        entries: table_id.entries(),
+                    })
+                    .collect()
+            }
+        }
+
+        impl $crate::api::LookupTableID for $name {
+            fn to_u32(&self) -> u32 {
+                *self as u32
+            }
+
+            fn from_u32(value: u32) -> Self {
+                Self::try_from_u32(value).unwrap_or_else(|| panic!("Invalid table ID {value}"))
+            }
+
+            fn try_from_u32(value: u32) -> Option<Self> {
+                match value {
+                    $($id => Some($name::$variant),)*
+                    _ => None,
+                }
+            }
+
+            fn is_fixed(&self) -> bool {
+                true
+            }
+
+            fn length(&self) -> usize {
+                match self {
+                    $($name::$variant => $crate::define_lookup_tables!(@length $kind($($args)*))),*
+                }
+            }
+        }
+    };
+    (@entries entries($rows:expr)) => {
+        $rows
+            .iter()
+            .map(|row| row.iter().map(|x| F::from(*x)).collect())
+            .collect()
+    };
+    (@entries generate($length:expr, $row:expr)) => {
+        (0..$length as u64)
+            .map($row)
+            .map(|row| row.into_iter().map(F::from).collect())
+            .collect()
+    };
+    (@length entries($rows:expr)) => { $rows.len() };
+    (@length generate($length:expr, $row:expr)) => { $length };
+}
+
+/// Serialize a table ID using [LookupTableID::to_u32], to be used with
+/// `#[serde(with = "serde_table_id")]`.
+pub(crate) mod serde_table_id {
+    use super::LookupTableID;
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, ID: LookupTableID>(
+        id: &ID,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        id.to_u32().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, De: Deserializer<'de>, ID: LookupTableID>(
+        deserializer: De,
+    ) -> Result<ID, De::Error> {
+        let id = u32::deserialize(deserializer)?;
+        ID::try_from_u32(id).ok_or_else(|| De::Error::custom(format!("invalid table ID {id}")))
+    }
+}
+
+/// Serialize a map indexed by table IDs as the list of its entries, sorted by
+/// strictly increasing ID, each ID being encoded with
+/// [LookupTableID::to_u32]. To be used with
+/// `#[serde(with = "serde_table_map")]`.
+///
+/// The encoding is canonical: the entries are decoded as [TableEntries], and
+/// then checked, see [TableEntries::into_map].
+pub(crate) mod serde_table_map {
+    use super::{LookupTableID, TableEntries};
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer, ID: LookupTableID, T: Serialize>(
+        map: &BTreeMap<ID, T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let entries: Vec<(u32, &T)> = map.iter().map(|(id, v)| (id.to_u32(), v)).collect();
+        entries.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, De: Deserializer<'de>, ID: LookupTableID, T: Deserialize<'de>>(
+        deserializer: De,
+    ) -> Result<BTreeMap<ID, T>, De::Error> {
+        TableEntries::deserialize(deserializer)?
+            .into_map()
+            .map_err(De::Error::custom)
+    }
+}
+
+/// The entries of a map indexed by table IDs, in their encoded order, see
+/// [serde_table_map]. Their number is bounded by [MAX_TABLE_ID], checked
+/// against the length prefix of the list before decoding them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct TableEntries<T>(pub(crate) Vec<(u32, T)>);
+
+impl<T> TableEntries<T> {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The map of the entries. A duplicate or unsorted ID is reported with
+    /// [crate::fuzz::check_canonical_map], and the IDs are decoded with
+    /// [LookupTableID::try_from_u32] and bounded by [MAX_TABLE_ID].
+    pub(crate) fn into_map<ID: LookupTableID>(self) -> Result<BTreeMap<ID, T>, DeserializeError> {
+        check_canonical_map(&self.0)?;
+        self.0
+            .into_iter()
+            .map(|(id, v)| {
+                let table_id = ID::try_from_u32(id)
+                    .filter(|_| id < MAX_TABLE_ID)
+                    .ok_or_else(|| DeserializeError::Decoding(format!("invalid table ID {id}")))?;
+                Ok((table_id, v))
+            })
+            .collect()
+    }
+}
+
+impl<ID: LookupTableID, T> From<BTreeMap<ID, T>> for TableEntries<T> {
+    fn from(map: BTreeMap<ID, T>) -> Self {
+        TableEntries(map.into_iter().map(|(id, v)| (id.to_u32(), v)).collect())
+    }
+}
+
+impl<T> Default for TableEntries<T> {
+    fn default() -> Self {
+        TableEntries(vec![])
+    }
+}
+
+struct EntriesVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for EntriesVisitor<T> {
+    type Value = TableEntries<T>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "a list of at most {MAX_TABLE_ID} entries indexed by table ID"
+        )
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        use serde::de::Error;
+        let too_many = |len: usize| {
+            A::Error::custom(format!(
+                "{len} tables are declared, above the bound {MAX_TABLE_ID}"
+            ))
+        };
+        if let Some(len) = seq.size_hint().filter(|&len| len > MAX_TABLE_ID as usize) {
+            return Err(too_many(len));
+        }
+        let mut entries = vec![];
+        while let Some(entry) = seq.next_element::<(u32, T)>()? {
+            if entries.len() == MAX_TABLE_ID as usize {
+                return Err(too_many(entries.len() + 1));
+            }
+            entries.push(entry);
+        }
+        Ok(TableEntries(entries))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for TableEntries<T> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        deserializer.deserialize_seq(EntriesVisitor(PhantomData))
+    }
 }
 
 /// A table of values that can be used for a lookup, along with the ID for the table.
@@ -79,11 +579,121 @@ pub struct LookupTable<F, ID: LookupTableID> {
     pub entries: Vec<Vec<F>>,
 }
 
+impl<F: Field, ID: LookupTableID> LookupTable<F, ID> {
+    /// Returns the columns of the table, padded with zero rows up to
+    /// `domain_size`.
+    /// The width of the table is given by its first entry.
+    pub fn padded_columns(&self, domain_size: usize) -> Vec<Vec<F>> {
+        let width = self.entries.first().map_or(0, |entry| entry.len());
+        (0..width)
+            .map(|j| {
+                let mut column: Vec<F> = self.entries.iter().map(|entry| entry[j]).collect();
+                column.resize(domain_size, F::zero());
+                column
+            })
+            .collect()
+    }
+}
+
+/// Commitments to the (raw) columns of a set of fixed lookup tables.
+/// They are computed once, for a given SRS and domain, and published
+/// out-of-band so that several circuits can share exactly the same table set.
+///
+/// The commitment to the table column used by the lookup argument is the
+/// commitment to `r * t_{1}(X) + r^2 * t_{2}(X) + ... + table_id`, where `r` is
+/// the joint combiner. As the commitment is linear, it can be derived from the
+/// commitments to the individual columns after `r` has been coined, see
+/// [GlobalTableCommitments::combined_commitment].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalTableCommitments<G: KimchiCurve, ID: LookupTableID> {
+    /// For each table, the commitments to each column of its entries.
+    pub tables: BTreeMap<ID, Vec<PolyComm<G>>>,
+}
+
+/// Commit to the columns of the given tables over `domain.d1`.
+/// The tables are padded with zero rows up to the domain size.
+/// The implementation of [LookupTableID] is checked on the IDs of the tables
+/// like [verify_table_id_impl] does, the tables themselves giving their
+/// lengths.
+pub fn generate_global_tables<G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID>(
+    srs: &OpeningProof::SRS,
+    domain: EvaluationDomains<G::ScalarField>,
+    tables: &[LookupTable<G::ScalarField, ID>],
+) -> Result<GlobalTableCommitments<G, ID>, TableIdImplError> {
+    let ids: Vec<ID> = tables.iter().map(|table| table.table_id).collect();
+    verify_table_id_encoding(&ids)?;
+    if let Some(table) = tables.iter().find(|table| table.entries.is_empty()) {
+        return Err(TableIdImplError::EmptyFixedTable(table.table_id.to_u32()));
+    }
+    let tables = tables
+        .iter()
+        .map(|table| {
+            let comms = table
+                .padded_columns(domain.d1.size as usize)
+                .into_iter()
+                .map(|column| {
+                    let evals =
+                        Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
+                            column, domain.d1,
+                        );
+                    srs.commit_evaluations_non_hiding(domain.d1, &evals)
+                })
+                .collect();
+            (table.table_id, comms)
+        })
+        .collect();
+    Ok(GlobalTableCommitments { tables })
+}
+
+impl<G: KimchiCurve, ID: LookupTableID> GlobalTableCommitments<G, ID> {
+    /// Compute the commitment to the combined table column of `table_id`,
+    /// i.e. `r * [t_{1}] + r^2 * [t_{2}] + ... + table_id * [1]`.
+    /// `unit_comm` is the commitment to the constant polynomial `1`, using the
+    /// same chunking as the table columns.
+    /// Returns `None` if the table is not part of the set.
+    pub fn combined_commitment(
+        &self,
+        table_id: &ID,
+        joint_combiner: G::ScalarField,
+        unit_comm: &PolyComm<G>,
+    ) -> Option<PolyComm<G>> {
+        self.tables.get(table_id).map(|columns| {
+            let mut scalars = Vec::with_capacity(columns.len() + 1);
+            let mut power = G::ScalarField::one();
+            for _ in columns.iter() {
+                power *= joint_combiner;
+                scalars.push(power);
+            }
+            scalars.push(table_id.to_field());
+            let comms: Vec<&PolyComm<G>> =
+                columns.iter().chain(std::iter::once(unit_comm)).collect();
+            PolyComm::multi_scalar_mul(&comms, &scalars)
+        })
+    }
+}
+
+/// The canonical serialization is the list of tables, sorted by strictly
+/// increasing ID, each ID being encoded with [LookupTableID::to_u32].
+impl<G: KimchiCurve, ID: LookupTableID> Serialize for GlobalTableCommitments<G, ID> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_table_map::serialize(&self.tables, serializer)
+    }
+}
+
+impl<'de, G: KimchiCurve, ID: LookupTableID> Deserialize<'de> for GlobalTableCommitments<G, ID> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        let tables = serde_table_map::deserialize(deserializer)?;
+        Ok(GlobalTableCommitments { tables })
+    }
+}
+
 /// Represents a witness of one instance of the lookup argument
 /// IMPROVEME: Possible to index by a generic const?
 // The parameter N is the number of functions/looked-up values per row. It is
 // used by the PlonK polynomial IOP to compute the number of partial sums.
-#[derive(Debug, Clone)]
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
 pub struct MVLookupWitness<F, ID: LookupTableID> {
     /// A list of functions/looked-up values.
     /// Invariant: for fixed lookup tables, the last value of the vector is the
@@ -101,546 +711,5519 @@ pub struct MVLookupWitness<F, ID: LookupTableID> {
     /// array
     pub(crate) f: Vec<Vec<MVLookup<F, ID>>>,
     /// The multiplicity polynomial
+    #[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")]
     pub(crate) m: Vec<F>,
 }
 
-/// Represents the proof of the lookup argument
-/// It is parametrized by the type `T` which can be either:
-/// - Polycomm<G: KimchiCurve> for the commitments
-/// - F for the evaluations at zeta (resp. zeta omega).
-/// FIXME: We should have a fixed number of m and h. Should we encode that in
-/// the type?
-#[derive(Debug, Clone)]
-pub struct LookupProof<T, ID> {
-    /// The multiplicity polynomials
-    pub(crate) m: BTreeMap<ID, T>,
-    /// The polynomial keeping the sum of each row
-    pub(crate) h: Vec<T>,
-    /// The "running-sum" over the rows, coined `φ`
-    pub(crate) sum: T,
-    /// All fixed lookup tables values, indexed by their ID
-    pub(crate) fixed_tables: BTreeMap<ID, T>,
+impl<F: Clone, ID: LookupTableID> MVLookupWitness<F, ID> {
+    /// Returns the fixed lookup table this witness is looking up into, as
+    /// given by the last column of `f`, including the padding rows.
+    /// Returns `None` if the witness does not use a fixed table.
+    pub fn fixed_table(&self) -> Option<LookupTable<F, ID>> {
+        let table = self.f.last()?;
+        let table_id = table.first()?.table_id;
+        if !table_id.is_fixed() {
+            return None;
+        }
+        Some(LookupTable {
+            table_id,
+            entries: table.iter().map(|lookup| lookup.value.clone()).collect(),
+        })
+    }
 }
 
-/// Iterator implementation to abstract the content of the structure.
-/// It can be used to iterate over the commitments (resp. the evaluations)
-/// without requiring to have a look at the inner fields.
-impl<'lt, G, ID: LookupTableID> IntoIterator for &'lt LookupProof<G, ID> {
-    type Item = &'lt G;
-    type IntoIter = std::vec::IntoIter<&'lt G>;
+/// Errors that can arise when computing or merging multiplicities
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MultiplicityError {
+    #[error("the multiplicity at row {row} of the table {table_id} is not smaller than the bound {bound}")]
+    BoundExceeded {
+        table_id: u32,
+        row: usize,
+        bound: usize,
+    },
 
-    fn into_iter(self) -> Self::IntoIter {
-        let mut iter_contents = vec![];
-        // First multiplicities
-        self.m.values().for_each(|m| iter_contents.push(m));
-        iter_contents.extend(&self.h);
-        iter_contents.push(&self.sum);
-        // Fixed tables
-        self.fixed_tables
-            .values()
-            .for_each(|t| iter_contents.push(t));
-        iter_contents.into_iter()
+    #[error("the witnesses do not look up into the same table")]
+    TableMismatch,
+}
+
+/// Returns `m` as an index if it is smaller than `bound`.
+pub(crate) fn multiplicity_index<F: PrimeField>(m: &F, bound: usize) -> Option<usize> {
+    let m = m.into_repr();
+    if m < F::BigInt::from(bound as u64) {
+        Some(m.as_ref()[0] as usize)
+    } else {
+        None
     }
 }
 
-/// Compute the following constraint:
-/// ```text
-///                     lhs
-///    |------------------------------------------|
-///    |                           denominators   |
-///    |                         /--------------\ |
-/// column * (\prod_{i = 1}^{N} (β + f_{i}(X))) =
-/// \sum_{i = 1}^{N} m_{i} * \prod_{j = 1, j \neq i}^{N} (β + f_{j}(X))
-///    |             |--------------------------------------------------|
-///    |                             Inner part of rhs                  |
-///    |                                                                |
-///    |                                                               /
-///     \                                                             /
-///      \                                                           /
-///       \---------------------------------------------------------/
-///                           rhs
-/// ```
-/// It is because h(X) (column) is defined as:
-/// ```text
-/// h(X) = \sum_{i = 1}^{n} (m_i(X) / (β + f_{i}(X))
-/// ```
-/// For instance, if i = 2, we have
-/// ```text
-/// h(X) = m_1(X) / (β + f_1(X)) + m_2(X) / (β + f_{2}(X))
-///        m_1(X) * (β + f_2(X)) + m_2(X) * (β + f_{1}(X))
-///      = ----------------------------------------------
-///                  (β + f_2(X)) * (β + f_1(X))
-/// ```
-/// which is equivalent to
-/// ```text
-/// h(X) * (β + f_2(X)) * (β + f_1(X)) = m_1(X) * (β + f_2(X)) + m_2(X) * (β + f_{1}(X))
-/// ```
-/// When we have f_1(X) a looked-up value, t(X) a fixed table and m_2(X) being
-/// the multiplicities, we have
-/// ```text
-/// h(X) * (β + t(X)) * (β + f(X)) = (β + t(X)) + m(X) * (β + f(X))
-/// ```
-pub fn combine_lookups<F: PrimeField, ID: LookupTableID>(
-    column: Column,
-    lookups: Vec<MVLookup<E<F>, ID>>,
-) -> E<F> {
-    let joint_combiner = {
-        let joint_combiner = ConstantExpr::from(ChallengeTerm::JointCombiner);
-        E::Atom(ExprInner::Constant(joint_combiner))
-    };
-    let beta = {
-        let beta = ConstantExpr::from(ChallengeTerm::Beta);
-        E::Atom(ExprInner::Constant(beta))
-    };
+impl<F: PrimeField, ID: LookupTableID> MVLookupWitness<F, ID> {
+    /// Returns the table this witness is looking up into, as given by the last
+    /// column of `f`.
+    pub(crate) fn table_id(&self) -> Option<ID> {
+        Some(self.f.last()?.first()?.table_id)
+    }
 
-    // Compute (β + f_{i}(X)) for each i.
-    // Note that f_i(X) = table_id + r * x_{1} + r^2 x_{2} + ... r^{N} x_{N}
-    let denominators = lookups
-        .iter()
-        .map(|x| {
-            // Compute r * x_{1} + r^2 x_{2} + ... r^{N} x_{N}
-            let combined_value = x
-                .value
-                .iter()
-                .rev()
-                .fold(E::zero(), |acc, y| acc * joint_combiner.clone() + y.clone())
-                * joint_combiner.clone();
-            // FIXME: sanity check for the domain, we should consider it in prover.rs.
-            // We do only support degree one constraint in the denominator.
-            assert_eq!(combined_value.degree(1, 0), 1, "Only degree one is supported in the denominator of the lookup because of the maximum degree supported (8)");
-            // add table id + evaluation point
-            beta.clone() + combined_value + x.table_id.to_constraint()
-        })
-        .collect::<Vec<_>>();
-    // Compute `column * (\prod_{i = 1}^{N} (β + f_{i}(X)))`
-    let lhs = denominators
-        .iter()
-        .fold(curr_cell(column), |acc, x| acc * x.clone());
-    let rhs = lookups
-        .into_iter()
-        .enumerate()
-        .map(|(i, x)| {
-            denominators.iter().enumerate().fold(
-                // Compute individual \sum_{j = 1, j \neq i}^{N} (β + f_{j}(X))
-                // This is the inner part of rhs. It multiplies with m_{i}
-                x.numerator,
-                |acc, (j, y)| {
-                    if i == j {
-                        acc
-                    } else {
-                        acc * y.clone()
-                    }
-                },
-            )
-        })
-        // Individual sums
-        .reduce(|x, y| x + y)
-        .unwrap_or(E::zero());
-    lhs - rhs
-}
+    /// The number of partial sums of the witness, one per chunk of
+    /// `MAX_SUPPORTED_DEGREE - 2` columns
+    pub(crate) fn n_partial_sums(&self) -> usize {
+        (self.f.len() + MAX_SUPPORTED_DEGREE - 3) / (MAX_SUPPORTED_DEGREE - 2)
+    }
 
-/// Build the constraints for the lookup protocol.
-/// The constraints are the partial sum and the aggregation of the partial sums.
-pub fn constraint_lookups<F: PrimeField, ID: LookupTableID>(
-    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
-) -> Vec<E<F>> {
-    let mut constraints: Vec<E<F>> = vec![];
-    let mut idx_partial_sum = 0;
-    lookups_map.iter().for_each(|(id, lookups)| {
-        let table_lookup = MVLookup {
-            table_id: *id,
-            numerator: curr_cell(Column::LookupMultiplicity(id.to_u32())),
-            value: vec![curr_cell(Column::LookupFixedTable(id.to_u32()))],
-        };
-        // FIXME: do not clone
-        let mut lookups = lookups.clone();
-        lookups.push(table_lookup);
-        // We split in chunks of 6 (MAX_SUPPORTED_DEGREE - 2)
-        lookups.chunks(MAX_SUPPORTED_DEGREE - 2).for_each(|chunk| {
-            constraints.push(combine_lookups(
-                Column::LookupPartialSum(idx_partial_sum),
-                chunk.to_vec(),
-            ));
-            idx_partial_sum += 1;
+    /// Returns the first row of the table column whose numerator is not the
+    /// opposite of the multiplicity, see [MVLookup::table_term], if the
+    /// witness looks up into a fixed table.
+    pub fn table_numerator_mismatch(&self) -> Option<usize> {
+        if !self.table_id()?.is_fixed() {
+            return None;
+        }
+        self.f
+            .last()?
+            .iter()
+            .zip(self.m.iter())
+            .position(|(lookup, m)| lookup.numerator != -*m)
+    }
+
+    /// Pad the looked-up values with [chunk_padding] no-op lookups, before
+    /// the column of the table, like [pad_lookups] does for the constraints.
+    pub fn pad(mut self) -> Self {
+        let Some(table_id) = self.table_id() else {
+            return self;
+        };
+        let n_rows = self.f[0].len();
+        let padding = chunk_padding(self.f.len() - 1);
+        let table = self.f.pop().unwrap();
+        self.f
+            .extend((0..padding).map(|_| vec![MVLookup::noop(table_id); n_rows]));
+        self.f.push(table);
+        self
+    }
+
+    /// Check that the multiplicities are smaller than the length of the table
+    /// given by [LookupTableID::multiplicity_bound], if any.
+    pub fn check_multiplicity_bound(&self) -> Result<(), MultiplicityError> {
+        let Some(table_id) = self.table_id() else {
+            return Ok(());
+        };
+        let Some(bound) = table_id.multiplicity_bound() else {
+            return Ok(());
+        };
+        let bound = bound.length();
+        match self
+            .m
+            .iter()
+            .position(|m| multiplicity_index(m, bound).is_none())
+        {
+            None => Ok(()),
+            Some(row) => Err(MultiplicityError::BoundExceeded {
+                table_id: table_id.to_u32(),
+                row,
+                bound,
+            }),
+        }
+    }
+
+    /// Merge the lookups of two witnesses into the same table, adding the
+    /// multiplicities. The bound on the multiplicities is checked on the
+    /// result, see [Self::check_multiplicity_bound].
+    pub fn combine(self, other: Self) -> Result<Self, MultiplicityError> {
+        let (Some(table), Some(other_table)) = (self.f.last(), other.f.last()) else {
+            return Err(MultiplicityError::TableMismatch);
+        };
+        let same_table = table.len() == other_table.len()
+            && table
+                .iter()
+                .zip(other_table.iter())
+                .all(|(x, y)| x.table_id == y.table_id && x.value == y.value);
+        if !same_table || self.m.len() != other.m.len() {
+            return Err(MultiplicityError::TableMismatch);
+        }
+        let m: Vec<F> = self
+            .m
+            .iter()
+            .zip(other.m.iter())
+            .map(|(x, y)| *x + y)
+            .collect();
+        let mut table = table.clone();
+        table
+            .iter_mut()
+            .zip(m.iter())
+            .for_each(|(lookup, m)| lookup.numerator = -*m);
+        let mut f = self.f;
+        f.pop();
+        let mut other_f = other.f;
+        other_f.pop();
+        f.extend(other_f);
+        f.push(table);
+        let res = MVLookupWitness { f, m };
+        res.check_multiplicity_bound()?;
+        Ok(res)
+    }
+}
+
+/// A column of looked-up values of a [MVLookupQueries], given either by value
+/// or by reference to a witness column.
+#[derive(Debug, Clone)]
+pub enum LookupQuery<F, ID: LookupTableID> {
+    /// The lookups of each row, for the values which are computed and are not
+    /// a witness column, and for the column of the table.
+    Value(Vec<MVLookup<F, ID>>),
+    /// The lookup of the value of the witness column at each row, with a
+    /// numerator of one, into the table of the witness, i.e. the table of its
+    /// last column. The values are read by the prover out of the witness, see
+    /// [MVLookupQueries::resolve], and can not diverge from it.
+    Column(Column),
+}
+
+/// Errors that can arise when reading the looked-up values of a
+/// [MVLookupQueries] out of the witness
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LookupQueryError {
+    #[error("the lookups refer to the column {0:?}, which is not a witness column")]
+    NotAWitnessColumn(Column),
+
+    #[error("the lookups by reference do not end with the column of their table")]
+    MissingTableColumn,
+}
+
+/// The witness of one instance of the lookup argument, like
+/// [MVLookupWitness], with the looked-up values which mirror witness cells
+/// declared by reference to their column, see [LookupQuery]. The constraints
+/// of the lookups already refer to the columns, and the witness of the lookups
+/// can not hold stale copies of their values.
+#[derive(Debug, Clone)]
+pub struct MVLookupQueries<F, ID: LookupTableID> {
+    /// The columns of looked-up values. Like for [MVLookupWitness], the last
+    /// one is the column of the table, given by value.
+    pub f: Vec<LookupQuery<F, ID>>,
+    /// The multiplicity polynomial
+    pub m: Vec<F>,
+}
+
+impl<F: Clone + One, ID: LookupTableID> MVLookupQueries<F, ID> {
+    /// Returns the witness of the lookups, with the values of the columns
+    /// given by reference read out of `witness`.
+    pub fn resolve<const N: usize>(
+        self,
+        witness: &Witness<N, Vec<F>>,
+    ) -> Result<MVLookupWitness<F, ID>, LookupQueryError> {
+        let table_id = match self.f.last() {
+            Some(LookupQuery::Value(table)) => table
+                .first()
+                .map(|lookup| lookup.table_id)
+                .ok_or(LookupQueryError::MissingTableColumn)?,
+            _ => return Err(LookupQueryError::MissingTableColumn),
+        };
+        let f = self
+            .f
+            .into_iter()
+            .map(|query| match query {
+                LookupQuery::Value(lookups) => Ok(lookups),
+                LookupQuery::Column(Column::X(i)) if i < N => Ok(witness.cols[i]
+                    .iter()
+                    .map(|x| MVLookup::new(table_id, F::one(), std::slice::from_ref(x)))
+                    .collect()),
+                LookupQuery::Column(column) => Err(LookupQueryError::NotAWitnessColumn(column)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(MVLookupWitness { f, m: self.m })
+    }
+}
+
+/// Add the lookups range checking the multiplicities of the tables with a
+/// [LookupTableID::multiplicity_bound] into their bounding table, like
+/// [constraint_lookups] does for the constraints. The range checks of a
+/// bounding table are ordered by the ID of the bounded tables, and are placed
+/// before its table column. The witness of a bounding table is created if
+/// needed.
+/// Out of range multiplicities are not counted, and the lookup argument will
+/// not be satisfied.
+pub fn add_multiplicity_range_checks<F: PrimeField, ID: LookupTableID>(
+    mut lookups: Vec<MVLookupWitness<F, ID>>,
+    domain_size: usize,
+) -> Vec<MVLookupWitness<F, ID>> {
+    // The multiplicities to range check, for each bounding table
+    let mut range_checks: BTreeMap<ID, BTreeMap<ID, Vec<F>>> = BTreeMap::new();
+    for lookup in lookups.iter() {
+        if let Some(table_id) = lookup.table_id() {
+            if let Some(bound) = table_id.multiplicity_bound() {
+                range_checks
+                    .entry(bound)
+                    .or_default()
+                    .insert(table_id, lookup.m.clone());
+            }
+        }
+    }
+    for (bound, multiplicities) in range_checks {
+        assert!(
+            bound.multiplicity_bound().is_none(),
+            "The multiplicities of a bounding table can not be bounded"
+        );
+        let idx = match lookups.iter().position(|x| x.table_id() == Some(bound)) {
+            Some(idx) => idx,
+            None => {
+                // Keep the witnesses sorted by table ID
+                let idx = lookups
+                    .iter()
+                    .position(|x| x.table_id() > Some(bound))
+                    .unwrap_or(lookups.len());
+                let table = (0..domain_size)
+                    .map(|i| {
+                        let value = if i < bound.length() { i as u64 } else { 0 };
+                        MVLookup::new(bound, F::zero(), &[F::from(value)])
+                    })
+                    .collect();
+                let witness = MVLookupWitness {
+                    f: vec![table],
+                    m: vec![F::zero(); domain_size],
+                };
+                lookups.insert(idx, witness);
+                idx
+            }
+        };
+        let witness = &mut lookups[idx];
+        let mut table = witness.f.pop().unwrap();
+        for m in multiplicities.into_values() {
+            let column = m
+                .into_iter()
+                .map(|m| {
+                    if let Some(i) = multiplicity_index(&m, bound.length()) {
+                        witness.m[i] += F::one();
+                    }
+                    MVLookup::new(bound, F::one(), &[m])
+                })
+                .collect();
+            witness.f.push(column);
+        }
+        table
+            .iter_mut()
+            .zip(witness.m.iter())
+            .for_each(|(lookup, m)| lookup.numerator = -*m);
+        witness.f.push(table);
+    }
+    lookups
+}
+
+/// Errors that can arise when packing, grouping or coalescing the witness of
+/// the lookups, see [LookupPacking::apply_to_witness],
+/// [LookupGrouping::apply_to_witness] and
+/// [LookupCoalescing::apply_to_witness].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PackingError {
+    #[error("the witness of the table {0} is missing")]
+    MissingTable(u32),
+
+    #[error("the witness of the table {0} does not have the expected number of lookups")]
+    LookupCountMismatch(u32),
+}
+
+/// A lookup which only differs from a lookup of another table by its table ID.
+/// It is placed right after its twin in the partial sums.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedLookup<ID> {
+    pub table_id: ID,
+    pub index: usize,
+    pub twin_table_id: ID,
+    pub twin_index: usize,
+}
+
+/// The lookups which are structurally identical to a lookup of a previous
+/// table, except for the table ID, for instance a limb which is both range
+/// checked and looked up in a XOR table.
+/// As the table ID is part of the combined value, they can be placed in the
+/// partial sums of any table, and the constraints are unchanged. Placing them
+/// next to their twin lets the prover compute `r * x_1 + ... + r^N x_N` once
+/// for both. The witness of the lookups must be packed like the constraints,
+/// see [LookupPacking::apply_to_witness].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupPacking<ID> {
+    pub packed: Vec<PackedLookup<ID>>,
+}
+
+impl<ID> Default for LookupPacking<ID> {
+    fn default() -> Self {
+        LookupPacking { packed: vec![] }
+    }
+}
+
+impl<ID: LookupTableID> LookupPacking<ID> {
+    /// Find the lookups of `lookups_map` which have the same numerator and
+    /// values as a lookup of a table with a smaller ID.
+    pub fn detect<F: PrimeField>(lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>) -> Self {
+        let mut packed = vec![];
+        for (i, (table_id, lookups)) in lookups_map.iter().enumerate() {
+            for (index, lookup) in lookups.iter().enumerate() {
+                let twin = lookups_map
+                    .iter()
+                    .take(i)
+                    .find_map(|(twin_table_id, twins)| {
+                        twins
+                            .iter()
+                            .enumerate()
+                            .find(|(twin_index, twin)| {
+                                twin.numerator == lookup.numerator
+                                    && twin.value == lookup.value
+                                    && !packed.iter().any(|p: &PackedLookup<ID>| {
+                                        p.table_id == *twin_table_id && p.index == *twin_index
+                                    })
+                            })
+                            .map(|(twin_index, _)| (*twin_table_id, twin_index))
+                    });
+                if let Some((twin_table_id, twin_index)) = twin {
+                    packed.push(PackedLookup {
+                        table_id: *table_id,
+                        index,
+                        twin_table_id,
+                        twin_index,
+                    })
+                }
+            }
+        }
+        LookupPacking { packed }
+    }
+
+    /// Move the packed lookups of `columns` after their twin
+    fn reorder<T: Clone>(&self, columns: &BTreeMap<ID, Vec<T>>) -> BTreeMap<ID, Vec<T>> {
+        columns
+            .iter()
+            .map(|(table_id, lookups)| {
+                let mut res = vec![];
+                for (index, lookup) in lookups.iter().enumerate() {
+                    let is_packed = self
+                        .packed
+                        .iter()
+                        .any(|p| p.table_id == *table_id && p.index == index);
+                    if is_packed {
+                        continue;
+                    }
+                    res.push(lookup.clone());
+                    for p in self
+                        .packed
+                        .iter()
+                        .filter(|p| p.twin_table_id == *table_id && p.twin_index == index)
+                    {
+                        res.push(columns[&p.table_id][p.index].clone());
+                    }
+                }
+                (*table_id, res)
+            })
+            .collect()
+    }
+
+    /// Returns the lookups of `lookups_map`, with the packed lookups placed
+    /// after their twin.
+    pub fn apply<F: Clone>(
+        &self,
+        lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+    ) -> BTreeMap<ID, Vec<MVLookup<E<F>, ID>>> {
+        self.reorder(lookups_map)
+    }
+
+    /// Move the looked-up columns of the witnesses like [LookupPacking::apply]
+    /// does for the constraints. The table column stays the last column of
+    /// the witness of its table.
+    pub fn apply_to_witness<F: PrimeField>(
+        &self,
+        witnesses: Vec<MVLookupWitness<F, ID>>,
+    ) -> Result<Vec<MVLookupWitness<F, ID>>, PackingError> {
+        if self.packed.is_empty() {
+            return Ok(witnesses);
+        }
+        // The looked-up columns of each table. Only the table column is kept
+        // in the witnesses.
+        let mut columns: BTreeMap<ID, Vec<Vec<MVLookup<F, ID>>>> = BTreeMap::new();
+        let mut witnesses: Vec<(Option<ID>, MVLookupWitness<F, ID>)> = witnesses
+            .into_iter()
+            .map(|mut witness| {
+                let table_id = witness.table_id();
+                if let Some(table_id) = table_id {
+                    let table = witness.f.pop().unwrap();
+                    columns.insert(table_id, std::mem::replace(&mut witness.f, vec![table]));
+                }
+                (table_id, witness)
+            })
+            .collect();
+        for p in self.packed.iter() {
+            for (table_id, index) in [(p.table_id, p.index), (p.twin_table_id, p.twin_index)] {
+                let lookups = columns
+                    .get(&table_id)
+                    .ok_or(PackingError::MissingTable(table_id.to_u32()))?;
+                if index >= lookups.len() {
+                    return Err(PackingError::LookupCountMismatch(table_id.to_u32()));
+                }
+            }
+        }
+        let mut columns = self.reorder(&columns);
+        for (table_id, witness) in witnesses.iter_mut() {
+            if let Some(table_id) = table_id {
+                let mut f = columns.remove(table_id).unwrap();
+                f.append(&mut witness.f);
+                witness.f = f;
+            }
+        }
+        Ok(witnesses.into_iter().map(|(_, witness)| witness).collect())
+    }
+}
+
+/// An assignment of the lookups of each table to the chunks of its partial
+/// sums, grouping the lookups of identical values. As the table ID is the
+/// same, they share the denominator `β + f(X)`, which is then only a factor
+/// once in the constraint of the chunk, see
+/// [LookupConstraintOptions::grouping], and only inverted once by the prover.
+/// The assignment only reorders the lookups of each table, hence the number
+/// of partial sums, and the layout, are unchanged. It is derived
+/// deterministically from the lookups, by the prover and by the verifier.
+/// The witness of the lookups must be reordered like the constraints, see
+/// [LookupGrouping::apply_to_witness].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupGrouping<ID> {
+    /// The indices of the lookups of each table, in their new order
+    pub order: BTreeMap<ID, Vec<usize>>,
+}
+
+impl<ID> Default for LookupGrouping<ID> {
+    fn default() -> Self {
+        LookupGrouping {
+            order: BTreeMap::new(),
+        }
+    }
+}
+
+impl<ID: LookupTableID> LookupGrouping<ID> {
+    /// Group the lookups of each table of `lookups_map` with the same values.
+    /// The groups, by decreasing size, are placed in the first chunk with
+    /// enough room left, and split over the chunks with room left otherwise.
+    /// The last chunk also holds the term of the table.
+    pub fn detect<F: PrimeField>(lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>) -> Self {
+        let chunk_size = MAX_SUPPORTED_DEGREE - 2;
+        let order = lookups_map
+            .iter()
+            .map(|(table_id, lookups)| {
+                // The groups, by first occurrence
+                let mut groups: Vec<Vec<usize>> = vec![];
+                for (index, lookup) in lookups.iter().enumerate() {
+                    match groups
+                        .iter_mut()
+                        .find(|group| lookups[group[0]].value == lookup.value)
+                    {
+                        Some(group) => group.push(index),
+                        None => groups.push(vec![index]),
+                    }
+                }
+                // The sort is stable, the order is deterministic
+                groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+
+                let n_terms = lookups.len() + 1;
+                let n_chunks = (n_terms + chunk_size - 1) / chunk_size;
+                // The room for lookups in each chunk. The chunks are full,
+                // except the last one.
+                let mut room = vec![chunk_size; n_chunks];
+                room[n_chunks - 1] = n_terms - (n_chunks - 1) * chunk_size - 1;
+                let mut chunks: Vec<Vec<usize>> = vec![vec![]; n_chunks];
+                for group in groups {
+                    match room.iter().position(|room| *room >= group.len()) {
+                        Some(chunk) => {
+                            room[chunk] -= group.len();
+                            chunks[chunk].extend(group);
+                        }
+                        None => {
+                            for index in group {
+                                // There is as much room as lookups
+                                let chunk = room.iter().position(|room| *room > 0).unwrap();
+                                room[chunk] -= 1;
+                                chunks[chunk].push(index);
+                            }
+                        }
+                    }
+                }
+                (*table_id, chunks.concat())
+            })
+            .collect();
+        LookupGrouping { order }
+    }
+
+    /// Reorder the columns of each table of `columns`
+    fn reorder<T: Clone>(&self, columns: &BTreeMap<ID, Vec<T>>) -> BTreeMap<ID, Vec<T>> {
+        columns
+            .iter()
+            .map(|(table_id, lookups)| match self.order.get(table_id) {
+                Some(order) => (
+                    *table_id,
+                    order.iter().map(|i| lookups[*i].clone()).collect(),
+                ),
+                None => (*table_id, lookups.clone()),
+            })
+            .collect()
+    }
+
+    /// Returns the lookups of `lookups_map`, in the order of the grouping.
+    /// The lookups of `lookups_map` must be the ones the grouping has been
+    /// detected on.
+    pub fn apply<F: Clone>(
+        &self,
+        lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+    ) -> BTreeMap<ID, Vec<MVLookup<E<F>, ID>>> {
+        self.reorder(lookups_map)
+    }
+
+    /// Reorder the looked-up columns of the witnesses like
+    /// [LookupGrouping::apply] does for the constraints. The table column
+    /// stays the last column of the witness of its table.
+    pub fn apply_to_witness<F: PrimeField>(
+        &self,
+        witnesses: Vec<MVLookupWitness<F, ID>>,
+    ) -> Result<Vec<MVLookupWitness<F, ID>>, PackingError> {
+        let table_ids: BTreeSet<ID> = witnesses.iter().filter_map(|w| w.table_id()).collect();
+        if let Some(table_id) = self.order.keys().find(|id| !table_ids.contains(id)) {
+            return Err(PackingError::MissingTable(table_id.to_u32()));
+        }
+        witnesses
+            .into_iter()
+            .map(|mut witness| {
+                let Some(table_id) = witness.table_id() else {
+                    return Ok(witness);
+                };
+                let Some(order) = self.order.get(&table_id) else {
+                    return Ok(witness);
+                };
+                if witness.f.len() != order.len() + 1 {
+                    return Err(PackingError::LookupCountMismatch(table_id.to_u32()));
+                }
+                let table = witness.f.pop().unwrap();
+                let columns = BTreeMap::from([(table_id, std::mem::take(&mut witness.f))]);
+                witness.f = self.reorder(&columns).remove(&table_id).unwrap();
+                witness.f.push(table);
+                Ok(witness)
+            })
+            .collect()
+    }
+}
+
+/// The lookups of each table which look up the same value on every row, for
+/// instance a limb feeding two sub-checks of a gadget, coalesced into a single
+/// lookup whose numerator is the sum of their numerators, i.e. the number of
+/// repetitions when the numerators are one. The sum of the terms is
+/// unchanged, hence the multiplicities of the table are too, but a table has
+/// fewer looked-up columns and possibly fewer partial sums.
+/// The coalescing is opt-in, as it changes the layout of the lookup columns:
+/// the constraints must be built from the coalesced lookups, see
+/// [LookupConstraintOptions::coalescing], and the witness of the lookups must
+/// be coalesced like the constraints, see [LookupCoalescing::apply_to_witness].
+/// It is derived deterministically from the lookups, by the prover and by the
+/// verifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupCoalescing<ID> {
+    /// The indices of the lookups of each table with the same value, by first
+    /// occurrence
+    pub groups: BTreeMap<ID, Vec<Vec<usize>>>,
+}
+
+impl<ID> Default for LookupCoalescing<ID> {
+    fn default() -> Self {
+        LookupCoalescing {
+            groups: BTreeMap::new(),
+        }
+    }
+}
+
+impl<ID: LookupTableID> LookupCoalescing<ID> {
+    /// Group the lookups of each table of `lookups_map` with structurally
+    /// identical values
+    pub fn detect<F: PrimeField>(lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>) -> Self {
+        let groups = lookups_map
+            .iter()
+            .map(|(table_id, lookups)| {
+                let mut groups: Vec<Vec<usize>> = vec![];
+                for (index, lookup) in lookups.iter().enumerate() {
+                    match groups
+                        .iter_mut()
+                        .find(|group| lookups[group[0]].value == lookup.value)
+                    {
+                        Some(group) => group.push(index),
+                        None => groups.push(vec![index]),
+                    }
+                }
+                (*table_id, groups)
+            })
+            .collect();
+        LookupCoalescing { groups }
+    }
+
+    /// Whether no lookups are coalesced
+    pub fn is_trivial(&self) -> bool {
+        self.groups
+            .values()
+            .all(|groups| groups.iter().all(|group| group.len() == 1))
+    }
+
+    /// The number of lookups removed from the table `table_id`
+    pub fn n_coalesced(&self, table_id: ID) -> usize {
+        self.groups
+            .get(&table_id)
+            .map_or(0, |groups| groups.iter().map(|group| group.len() - 1).sum())
+    }
+
+    /// Returns the lookups of `lookups_map`, one per group. The numerator of
+    /// a group of lookups with the numerator one is the size of the group,
+    /// and the sum of the numerators otherwise.
+    /// The lookups of `lookups_map` must be the ones the coalescing has been
+    /// detected on.
+    pub fn apply<F: PrimeField>(
+        &self,
+        lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+    ) -> BTreeMap<ID, Vec<MVLookup<E<F>, ID>>> {
+        lookups_map
+            .iter()
+            .map(|(table_id, lookups)| {
+                let Some(groups) = self.groups.get(table_id) else {
+                    return (*table_id, lookups.clone());
+                };
+                let coalesced = groups
+                    .iter()
+                    .map(|group| {
+                        let first = &lookups[group[0]];
+                        let numerator = if group.iter().all(|i| lookups[*i].numerator == E::one()) {
+                            if group.len() == 1 {
+                                E::one()
+                            } else {
+                                E::from(group.len() as u64)
+                            }
+                        } else {
+                            group[1..].iter().fold(first.numerator.clone(), |acc, i| {
+                                acc + lookups[*i].numerator.clone()
+                            })
+                        };
+                        MVLookup {
+                            table_id: *table_id,
+                            numerator,
+                            value: first.value.clone(),
+                        }
+                    })
+                    .collect();
+                (*table_id, coalesced)
+            })
+            .collect()
+    }
+
+    /// Coalesce the looked-up columns of the witnesses like
+    /// [LookupCoalescing::apply] does for the constraints: on each row, the
+    /// numerators of a group are summed. The table column, with the
+    /// multiplicities, stays the last column of the witness of its table.
+    pub fn apply_to_witness<F: PrimeField>(
+        &self,
+        witnesses: Vec<MVLookupWitness<F, ID>>,
+    ) -> Result<Vec<MVLookupWitness<F, ID>>, PackingError> {
+        let table_ids: BTreeSet<ID> = witnesses.iter().filter_map(|w| w.table_id()).collect();
+        if let Some(table_id) = self.groups.keys().find(|id| !table_ids.contains(id)) {
+            return Err(PackingError::MissingTable(table_id.to_u32()));
+        }
+        witnesses
+            .into_iter()
+            .map(|mut witness| {
+                let Some(table_id) = witness.table_id() else {
+                    return Ok(witness);
+                };
+                let Some(groups) = self.groups.get(&table_id) else {
+                    return Ok(witness);
+                };
+                let n_lookups: usize = groups.iter().map(|group| group.len()).sum();
+                if witness.f.len() != n_lookups + 1 {
+                    return Err(PackingError::LookupCountMismatch(table_id.to_u32()));
+                }
+                let table = witness.f.pop().unwrap();
+                let columns = std::mem::take(&mut witness.f);
+                witness.f = groups
+                    .iter()
+                    .map(|group| {
+                        let mut column = columns[group[0]].clone();
+                        for i in group[1..].iter() {
+                            column
+                                .iter_mut()
+                                .zip(columns[*i].iter())
+                                .for_each(|(lookup, other)| lookup.numerator += other.numerator);
+                        }
+                        column
+                    })
+                    .collect();
+                witness.f.push(table);
+                Ok(witness)
+            })
+            .collect()
+    }
+}
+
+/// How the prover gives the aggregation `φ` of the partial sums, i.e. how
+/// the telescoping of the partial sums over the domain is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LookupAggregationMode {
+    /// The prover commits to the running sum `φ`, and the verifier checks the
+    /// aggregation constraint, see [constraint_lookups].
+    #[default]
+    Committed,
+    /// The prover only gives the sum of the partial sums over the domain. It is
+    /// absorbed by the sponge, but it is not checked by the verifier: the sum
+    /// must be checked by a boundary argument outside of the proof, for
+    /// instance when folding the lookup accumulators of several proofs.
+    Claimed,
+}
+
+/// The order in which the running sum `φ` accumulates the partial sums of the
+/// rows, in the [LookupAggregationMode::Committed] mode.
+/// The proofs in both directions are equally sound, the backward direction
+/// follows the specifications accumulating from the last row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AggregationDirection {
+    /// `φ(1) = 0` and `φ(ωX) = φ(X) + \sum_{i} h_i(X)`, i.e. `φ(ω^j)` is the
+    /// sum of the partial sums of the rows before `j`
+    #[default]
+    Forward,
+    /// `φ(X) = φ(ωX) + \sum_{i} h_i(X)`, i.e. `φ(ω^j)` is the sum of the
+    /// partial sums of the rows from `j` to the last one, and `φ(ω^n) = φ(1)`
+    /// is the sum over the whole domain, which is zero.
+    Backward,
+}
+
+/// The order in which the commitments of the lookup argument following the
+/// multiplicities are absorbed by the sponge, before coining `α`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LookupAbsorptionOrder {
+    /// The partial sums `h`, the fixed tables, then the aggregation `φ`
+    #[default]
+    ColumnsFirst,
+    /// The aggregation `φ`, then the partial sums `h` and the fixed tables,
+    /// as in the specification of [crate::config::Profile::External1]
+    AggregationFirst,
+}
+
+/// The aggregation `φ` of the partial sums in a [LookupProof], see
+/// [LookupAggregationMode].
+/// It is parametrized by the type `T` of the values of the columns, and by the
+/// type `C` of the claimed sum.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize, C: ark_serialize::CanonicalSerialize",
+    deserialize = "T: Deserialize<'de>, C: ark_serialize::CanonicalDeserialize"
+))]
+pub enum LookupAggregation<T, C> {
+    /// The running sum `φ` over the rows
+    Committed(T),
+    /// The sum of the partial sums over the domain, i.e. the last value of the
+    /// accumulator. It is zero if the lookups are satisfied.
+    Claimed(#[serde_as(as = "o1_utils::serialization::SerdeAs")] C),
+}
+
+impl<T, C> LookupAggregation<T, C> {
+    pub fn mode(&self) -> LookupAggregationMode {
+        match self {
+            LookupAggregation::Committed(_) => LookupAggregationMode::Committed,
+            LookupAggregation::Claimed(_) => LookupAggregationMode::Claimed,
+        }
+    }
+
+    /// The value of the column `φ`, if it is committed
+    pub fn committed(&self) -> Option<&T> {
+        match self {
+            LookupAggregation::Committed(x) => Some(x),
+            LookupAggregation::Claimed(_) => None,
+        }
+    }
+
+    /// The claimed sum, if `φ` is not committed
+    pub fn claimed(&self) -> Option<&C> {
+        match self {
+            LookupAggregation::Committed(_) => None,
+            LookupAggregation::Claimed(sum) => Some(sum),
+        }
+    }
+
+    /// Apply `f` to the value of the column `φ`, if it is committed.
+    pub fn map<U, FN: FnOnce(T) -> U>(self, f: FN) -> LookupAggregation<U, C> {
+        match self {
+            LookupAggregation::Committed(x) => LookupAggregation::Committed(f(x)),
+            LookupAggregation::Claimed(sum) => LookupAggregation::Claimed(sum),
+        }
+    }
+}
+
+/// Represents the proof of the lookup argument
+/// It is parametrized by the type `T` which can be either:
+/// - Polycomm<G: KimchiCurve> for the commitments
+/// - F for the evaluations at zeta (resp. zeta omega).
+/// The type `C` is the type of the claimed sum of the aggregation, see
+/// [LookupAggregation]. The claim is part of the commitments, and the
+/// evaluations keep the marker [PhantomData] instead.
+/// FIXME: We should have a fixed number of m and h. Should we encode that in
+/// the type?
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    bound(
+        serialize = "T: Serialize, ID: LookupTableID, C: ark_serialize::CanonicalSerialize",
+        deserialize = "T: Deserialize<'de>, ID: LookupTableID, C: ark_serialize::CanonicalDeserialize"
+    ),
+    try_from = "EncodedLookupProof<T, C>"
+)]
+pub struct LookupProof<T, ID, C = PhantomData<()>> {
+    /// The multiplicity polynomials
+    #[serde(serialize_with = "serde_table_map::serialize")]
+    pub(crate) m: BTreeMap<ID, T>,
+    /// The polynomial keeping the sum of each row
+    pub(crate) h: Vec<T>,
+    /// The "running-sum" over the rows, coined `φ`, or the claimed sum of the
+    /// partial sums
+    pub(crate) sum: LookupAggregation<T, C>,
+    /// All fixed lookup tables values, indexed by their ID
+    #[serde(serialize_with = "serde_table_map::serialize")]
+    pub(crate) fixed_tables: BTreeMap<ID, T>,
+    /// The columns of the tables proven with the plookup engine, see
+    /// [crate::lookup_engine::PlookupEngine]. The other fields only cover
+    /// the tables proven with MVLookup.
+    #[serde(
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "serde_table_map::serialize"
+    )]
+    pub(crate) plookup: BTreeMap<ID, PlookupColumns<T>>,
+}
+
+/// The encoding of a [LookupProof], with its maps kept as the list of their
+/// entries, see [Decode]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize, C: ark_serialize::CanonicalSerialize",
+    deserialize = "T: Deserialize<'de>, C: ark_serialize::CanonicalDeserialize"
+))]
+pub(crate) struct EncodedLookupProof<T, C = PhantomData<()>> {
+    m: TableEntries<T>,
+    h: Vec<T>,
+    sum: LookupAggregation<T, C>,
+    fixed_tables: TableEntries<T>,
+    #[serde(default, skip_serializing_if = "TableEntries::is_empty")]
+    plookup: TableEntries<PlookupColumns<T>>,
+}
+
+impl<T, ID: LookupTableID, C> From<LookupProof<T, ID, C>> for EncodedLookupProof<T, C> {
+    fn from(proof: LookupProof<T, ID, C>) -> Self {
+        EncodedLookupProof {
+            m: proof.m.into(),
+            h: proof.h,
+            sum: proof.sum,
+            fixed_tables: proof.fixed_tables.into(),
+            plookup: proof.plookup.into(),
+        }
+    }
+}
+
+impl<T, ID: LookupTableID, C> TryFrom<EncodedLookupProof<T, C>> for LookupProof<T, ID, C> {
+    type Error = DeserializeError;
+
+    fn try_from(encoded: EncodedLookupProof<T, C>) -> Result<Self, Self::Error> {
+        Ok(LookupProof {
+            m: encoded.m.into_map()?,
+            h: encoded.h,
+            sum: encoded.sum,
+            fixed_tables: encoded.fixed_tables.into_map()?,
+            plookup: encoded.plookup.into_map()?,
+        })
+    }
+}
+
+impl<T, ID, C> Decode for LookupProof<T, ID, C>
+where
+    T: serde::de::DeserializeOwned,
+    ID: LookupTableID,
+    C: ark_serialize::CanonicalDeserialize,
+{
+    type Encoded = EncodedLookupProof<T, C>;
+}
+
+impl<T, ID: LookupTableID, C> LookupProof<T, ID, C> {
+    /// Apply `f` to each value of the structure. The claimed sum is kept.
+    pub fn map<U, FN: Fn(T) -> U>(self, f: FN) -> LookupProof<U, ID, C> {
+        let LookupProof {
+            m,
+            h,
+            sum,
+            fixed_tables,
+            plookup,
+        } = self;
+        LookupProof {
+            m: m.into_iter().map(|(id, v)| (id, f(v))).collect(),
+            h: h.into_iter().map(&f).collect(),
+            sum: sum.map(&f),
+            fixed_tables: fixed_tables.into_iter().map(|(id, v)| (id, f(v))).collect(),
+            plookup: plookup
+                .into_iter()
+                .map(|(id, columns)| (id, columns.map(&f)))
+                .collect(),
+        }
+    }
+
+    /// Whether `other` gives a value for the same columns, e.g. the
+    /// evaluations of the committed columns. The values are then paired in
+    /// the order of [IntoIterator].
+    pub fn has_shape_of<U, D>(&self, other: &LookupProof<U, ID, D>) -> bool {
+        self.m.keys().eq(other.m.keys())
+            && self.h.len() == other.h.len()
+            && self.aggregation_mode() == other.aggregation_mode()
+            && self.fixed_tables.keys().eq(other.fixed_tables.keys())
+            && self.plookup.keys().eq(other.plookup.keys())
+    }
+
+    /// How the aggregation of the partial sums is given
+    pub fn aggregation_mode(&self) -> LookupAggregationMode {
+        self.sum.mode()
+    }
+
+    /// The claimed sum of the partial sums, in the
+    /// [LookupAggregationMode::Claimed] mode
+    pub fn claimed_sum(&self) -> Option<&C> {
+        self.sum.claimed()
+    }
+
+    /// The IDs of the tables of the argument, in increasing order
+    pub fn tables(&self) -> impl Iterator<Item = &ID> {
+        self.m.keys()
+    }
+
+    /// The partial sum `chunk` of the table `table_id`, if both are in
+    /// `layout` and the partial sum is in the proof
+    pub fn partial_sum(&self, table_id: ID, chunk: usize, layout: &LookupLayout<ID>) -> Option<&T> {
+        layout
+            .partial_sum_index(table_id, chunk)
+            .and_then(|i| self.h.get(i))
+    }
+}
+
+/// Iterator implementation to abstract the content of the structure.
+/// It can be used to iterate over the commitments (resp. the evaluations)
+/// without requiring to have a look at the inner fields.
+/// The aggregation is skipped when it is only claimed.
+impl<'lt, G, ID: LookupTableID, C> IntoIterator for &'lt LookupProof<G, ID, C> {
+    type Item = &'lt G;
+    type IntoIter = std::vec::IntoIter<&'lt G>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut iter_contents = vec![];
+        // First multiplicities
+        self.m.values().for_each(|m| iter_contents.push(m));
+        iter_contents.extend(&self.h);
+        iter_contents.extend(self.sum.committed());
+        // Fixed tables
+        self.fixed_tables
+            .values()
+            .for_each(|t| iter_contents.push(t));
+        // The columns of the plookup engine, table by table
+        self.plookup
+            .values()
+            .for_each(|columns| iter_contents.extend(columns.iter()));
+        iter_contents.into_iter()
+    }
+}
+
+/// Compute the following constraint:
+/// ```text
+///                     lhs
+///    |------------------------------------------|
+///    |                           denominators   |
+///    |                         /--------------\ |
+/// column * (\prod_{i = 1}^{N} (β + f_{i}(X))) =
+/// \sum_{i = 1}^{N} m_{i} * \prod_{j = 1, j \neq i}^{N} (β + f_{j}(X))
+///    |             |--------------------------------------------------|
+///    |                             Inner part of rhs                  |
+///    |                                                                |
+///    |                                                               /
+///     \                                                             /
+///      \                                                           /
+///       \---------------------------------------------------------/
+///                           rhs
+/// ```
+/// It is because h(X) (column) is defined as:
+/// ```text
+/// h(X) = \sum_{i = 1}^{n} (m_i(X) / (β + f_{i}(X))
+/// ```
+/// where `f_{i}(X) = table_id + r * x_{1}(X) + ...` is the combination of the
+/// values of the lookup `i` with the joint combiner `r`, see
+/// [MVLookup::new], and `m_{i}(X)` is its numerator.
+/// For instance, if i = 2, we have
+/// ```text
+/// h(X) = m_1(X) / (β + f_1(X)) + m_2(X) / (β + f_{2}(X))
+///        m_1(X) * (β + f_2(X)) + m_2(X) * (β + f_{1}(X))
+///      = ----------------------------------------------
+///                  (β + f_2(X)) * (β + f_1(X))
+/// ```
+/// which is equivalent to
+/// ```text
+/// h(X) * (β + f_2(X)) * (β + f_1(X)) = m_1(X) * (β + f_2(X)) + m_2(X) * (β + f_{1}(X))
+/// ```
+/// When we have f_1(X) a looked-up value, whose numerator m_1(X) is one, and
+/// the term of a fixed table t(X) with the multiplicities m(X), the numerator
+/// of the table is m_2(X) = -m(X). The values of the table are already
+/// combined, see [MVLookup::table_term], and [constraint_lookups] uses the
+/// denominator β + t(X) for this term. We have
+/// ```text
+/// h(X) * (β + t(X)) * (β + f(X)) = (β + t(X)) - m(X) * (β + f(X))
+/// ```
+pub fn combine_lookups<F: PrimeField, ID: LookupTableID>(
+    column: Column,
+    lookups: Vec<MVLookup<E<F>, ID>>,
+) -> E<F> {
+    let terms = lookups
+        .into_iter()
+        .map(|x| {
+            let denominator = lookup_denominator(&x);
+            (x.numerator, denominator)
+        })
+        .collect();
+    combine_terms(column, terms)
+}
+
+/// Compute `β + f(X)` where `f(X) = table_id + r * x_{1} + r^2 x_{2} + ... r^{N} x_{N}`.
+fn lookup_denominator<F: PrimeField, ID: LookupTableID>(lookup: &MVLookup<E<F>, ID>) -> E<F> {
+    let joint_combiner = joint_combiner();
+    // Compute r * x_{1} + r^2 x_{2} + ... r^{N} x_{N}
+    let combined_value = lookup
+        .value
+        .iter()
+        .rev()
+        .fold(E::zero(), |acc, y| acc * joint_combiner.clone() + y.clone())
+        * joint_combiner.clone();
+    // FIXME: sanity check for the domain, we should consider it in prover.rs.
+    // We do only support degree one constraint in the denominator. It is
+    // constant for a no-op lookup, see [MVLookup::noop].
+    assert!(combined_value.degree(1, 0) <= 1, "Only degree one is supported in the denominator of the lookup because of the maximum degree supported (8)");
+    // add table id + evaluation point
+    beta() + combined_value + lookup.table_id.to_constraint()
+}
+
+/// The joint combiner `r` of the vector lookups, which the constraints of
+/// the circuit can also use, e.g. to check several equalities with one
+/// constraint by a random linear combination.
+///
+/// It is coined after all the witness columns, including the ones of the
+/// second phase of [crate::prover::prove_with_context_and_extra_challenges],
+/// and the multiplicities are committed, and the prover can not choose them
+/// once it is known. The columns committed after it are the ones of the lookup
+/// argument: a constraint must not use it to combine them, as they depend on
+/// it. Using the same challenge for the lookups and for the constraints adds
+/// up their soundness errors, e.g. `k / |F|` for a combination of `k + 1`
+/// terms.
+///
+/// It is only coined for the circuits with lookups, see
+/// [crate::config::ConfigError::JointCombinerWithoutLookups]. The other
+/// circuits can use an extra challenge instead, see
+/// [crate::columns::Column::ExtraChallenge].
+pub fn joint_combiner<F: Field>() -> E<F> {
+    E::Atom(ExprInner::Constant(ConstantExpr::from(
+        ChallengeTerm::JointCombiner,
+    )))
+}
+
+/// The evaluation point `β` of the rational functions
+fn beta<F: PrimeField>() -> E<F> {
+    let beta = ConstantExpr::from(ChallengeTerm::Beta);
+    E::Atom(ExprInner::Constant(beta))
+}
+
+/// Compute the constraint described in [combine_lookups], given the
+/// numerators and the denominators of the terms.
+fn combine_terms<F: PrimeField>(column: Column, terms: Vec<(E<F>, E<F>)>) -> E<F> {
+    // Compute `column * (\prod_{i = 1}^{N} (β + f_{i}(X)))`
+    let lhs = terms
+        .iter()
+        .fold(curr_cell(column), |acc, (_, x)| acc * x.clone());
+    let rhs = terms
+        .iter()
+        .enumerate()
+        .map(|(i, (numerator, _))| {
+            // Compute individual \prod_{j = 1, j \neq i}^{N} (β + f_{j}(X))
+            // This is the inner part of rhs. It multiplies with m_{i}
+            let others = terms
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (_, y))| y.clone());
+            if is_constant_one(numerator) {
+                // The numerator of the unconditional lookups is elided
+                others.reduce(|acc, y| acc * y).unwrap_or_else(E::one)
+            } else {
+                others.fold(numerator.clone(), |acc, y| acc * y)
+            }
+        })
+        // Individual sums
+        .reduce(|x, y| x + y)
+        .unwrap_or(E::zero());
+    lhs - rhs
+}
+
+/// Whether `numerator` is the literal one, the numerator of the lookups
+/// performed unconditionally on every row
+pub(crate) fn is_constant_one<F: PrimeField>(numerator: &E<F>) -> bool {
+    numerator.to_literal_ref().map_or(false, |x| x.is_one())
+}
+
+/// Merge the terms with the same denominator, summing their numerators, see
+/// [LookupConstraintOptions::grouping].
+fn merge_terms<F: PrimeField>(terms: &[(E<F>, E<F>)]) -> Vec<(E<F>, E<F>)> {
+    let mut merged: Vec<(E<F>, E<F>)> = vec![];
+    for (numerator, denominator) in terms {
+        match merged.iter_mut().find(|(_, d)| d == denominator) {
+            Some((n, _)) => *n = n.clone() + numerator.clone(),
+            None => merged.push((numerator.clone(), denominator.clone())),
+        }
+    }
+    merged
+}
+
+/// The lookup columns of a circuit: the partial sums `h_i`, the
+/// multiplicities and the values of each table, and the aggregation `φ`.
+/// The indices of the columns can only be obtained from the layout, which
+/// checks that the columns exist, see [crate::columns::PartialSumIdx].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupLayout<ID> {
+    /// The number of partial sums of each table. The partial sums are indexed
+    /// by increasing table ID.
+    partial_sums: BTreeMap<ID, usize>,
+}
+
+impl<ID: LookupTableID> LookupLayout<ID> {
+    /// The layout of the columns used by [constraint_lookups] for
+    /// `lookups_map`.
+    pub fn new<F: PrimeField>(lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>) -> Self {
+        Self::with_packing(lookups_map, &LookupPacking::detect(lookups_map))
+    }
+
+    /// The layout of the columns used by [constraint_lookups_with_config]
+    /// for `lookups_map` packed with `packing`.
+    pub fn with_packing<F: PrimeField>(
+        lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+        packing: &LookupPacking<ID>,
+    ) -> Self {
+        Self::of_prepared(&prepare_lookups(lookups_map, packing))
+    }
+
+    /// The layout of lookups already packed and including the range checks
+    /// of the multiplicities, see [prepare_lookups]. The term of the table is
+    /// added to the lookups of each table.
+    fn of_prepared<T>(lookups_map: &BTreeMap<ID, Vec<T>>) -> Self {
+        LookupLayout {
+            partial_sums: lookups_map
+                .iter()
+                .map(|(id, lookups)| {
+                    let n_terms = lookups.len() + 1;
+                    let chunk_size = MAX_SUPPORTED_DEGREE - 2;
+                    (*id, (n_terms + chunk_size - 1) / chunk_size)
+                })
+                .collect(),
+        }
+    }
+
+    /// The layout of the lookup columns read by `constraints`, built by
+    /// [constraint_lookups] or its variants. The last partial sum of each
+    /// table is the only one reading the values of the table. Returns `None`
+    /// if the partial sums are not ordered by table ID.
+    pub fn of_constraints<F>(constraints: &[E<F>]) -> Option<Self> {
+        // The last partial sum of each table
+        let mut last_partial_sums: BTreeMap<ID, usize> = BTreeMap::new();
+        for constraint in constraints {
+            let mut tables = vec![];
+            let mut partial_sums = vec![];
+            for (column, _) in column_accesses(constraint) {
+                match column {
+                    Column::LookupFixedTable(idx) => tables.push(ID::from_u32(idx.to_u32())),
+                    Column::LookupPartialSum(idx) => partial_sums.push(idx.index()),
+                    _ => (),
+                }
+            }
+            if let ([table_id], [i]) = (tables.as_slice(), partial_sums.as_slice()) {
+                if last_partial_sums.insert(*table_id, *i).is_some() {
+                    return None;
+                }
+            }
+        }
+        let mut next = 0;
+        let mut partial_sums = BTreeMap::new();
+        for (table_id, last) in last_partial_sums {
+            if last < next {
+                return None;
+            }
+            partial_sums.insert(table_id, last + 1 - next);
+            next = last + 1;
+        }
+        Some(LookupLayout { partial_sums })
+    }
+
+    /// The number of partial sums of the table `table_id`, if it is used
+    pub fn n_table_partial_sums(&self, table_id: ID) -> Option<usize> {
+        self.partial_sums.get(&table_id).copied()
+    }
+
+    /// The number of partial sums `h_i`
+    pub fn n_partial_sums(&self) -> usize {
+        self.partial_sums.values().sum()
+    }
+
+    /// The IDs of the tables, in increasing order
+    pub fn table_ids(&self) -> impl Iterator<Item = ID> + '_ {
+        self.partial_sums.keys().copied()
+    }
+
+    /// The partial sum `h_i`, if `i` is less than [Self::n_partial_sums]
+    pub fn partial_sum(&self, i: usize) -> Option<Column> {
+        (i < self.n_partial_sums()).then(|| Column::LookupPartialSum(PartialSumIdx::new(i)))
+    }
+
+    /// The index `i` of the partial sum `h_i` number `chunk` of the table
+    /// `table_id`, if the table is used and has more than `chunk` partial sums
+    pub fn partial_sum_index(&self, table_id: ID, chunk: usize) -> Option<usize> {
+        let n_chunks = *self.partial_sums.get(&table_id)?;
+        (chunk < n_chunks).then(|| {
+            self.partial_sums
+                .range(..table_id)
+                .map(|(_, n)| n)
+                .sum::<usize>()
+                + chunk
+        })
+    }
+
+    /// All the partial sums, in order
+    pub fn partial_sums(&self) -> Vec<Column> {
+        (0..self.n_partial_sums())
+            .map(|i| Column::LookupPartialSum(PartialSumIdx::new(i)))
+            .collect()
+    }
+
+    /// The multiplicities of the table `table_id`, if it is used.
+    /// The multiplicities are committed and evaluated at the same points as
+    /// the witness columns, so that the constraints of the circuit can read
+    /// them, at the current or the next row, like any other column. They have
+    /// degree 1: a constraint multiplying them by a selector has degree 2,
+    /// which is below the degree of the constraints of the partial sums.
+    pub fn multiplicity(&self, table_id: ID) -> Option<Column> {
+        self.partial_sums
+            .contains_key(&table_id)
+            .then(|| Column::LookupMultiplicity(TableIdx::new(table_id.to_u32())))
+    }
+
+    /// The multiplicities of all the tables, by increasing table ID
+    pub fn multiplicities(&self) -> Vec<Column> {
+        self.partial_sums
+            .keys()
+            .map(|id| Column::LookupMultiplicity(TableIdx::new(id.to_u32())))
+            .collect()
+    }
+
+    /// The values of the table `table_id`, if it is used
+    pub fn fixed_table(&self, table_id: ID) -> Option<Column> {
+        self.partial_sums
+            .contains_key(&table_id)
+            .then(|| Column::LookupFixedTable(TableIdx::new(table_id.to_u32())))
+    }
+
+    /// The aggregation `φ` of the partial sums
+    pub fn aggregation(&self) -> Column {
+        Column::LookupAggregation
+    }
+}
+
+/// Returns the ID of a fixed table whose column is read by `constraint` if it
+/// is not a constraint of the lookup argument, i.e. the partial sum reading
+/// the values of the table, see [LookupLayout::of_constraints]. The column of
+/// a fixed table is internal to the lookup argument: its values are combined
+/// with the joint combiner, and another constraint reading it is most likely
+/// a bug.
+pub fn fixed_table_read_outside_lookups<F>(constraint: &E<F>) -> Option<u32> {
+    let mut tables = vec![];
+    let mut n_partial_sums = 0;
+    for (column, _) in column_accesses(constraint) {
+        match column {
+            Column::LookupFixedTable(idx) => tables.push(idx.to_u32()),
+            Column::LookupPartialSum(_) => n_partial_sums += 1,
+            _ => (),
+        }
+    }
+    match (tables.as_slice(), n_partial_sums) {
+        ([_], 1) => None,
+        _ => tables.first().copied(),
+    }
+}
+
+/// The constraint that the multiplicities of the table `table_id` are zero on
+/// the padding rows of the table, i.e. after its `table_real_length` first
+/// rows, if the table is in `layout`. The values of the padding rows are
+/// copies of entries of the table, and the constraint prevents the lookups
+/// from being counted on them, e.g. for a table whose multiplicities are
+/// bounded, see [LookupTableID::multiplicity_bound].
+/// The constraint `(1 - s(X)) m(X) = 0`, where `s` is the boundary selector
+/// of the first `table_real_length` rows, see [crate::boundary], has degree 2.
+pub fn constrain_multiplicity_padding<F: Field, ID: LookupTableID>(
+    layout: &LookupLayout<ID>,
+    table_id: ID,
+    table_real_length: usize,
+) -> Option<E<F>> {
+    let m = layout.multiplicity(table_id)?;
+    Some((E::one() - crate::boundary::selector(table_real_length)) * curr_cell(m))
+}
+
+/// Pack the lookups, and add the lookups range checking the bounded
+/// multiplicities into their bounding table.
+fn prepare_lookups<F: PrimeField, ID: LookupTableID>(
+    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+    packing: &LookupPacking<ID>,
+) -> BTreeMap<ID, Vec<MVLookup<E<F>, ID>>> {
+    let mut lookups_map = packing.apply(lookups_map);
+    let bounded: Vec<(ID, ID)> = lookups_map
+        .keys()
+        .filter_map(|id| id.multiplicity_bound().map(|bound| (*id, bound)))
+        .collect();
+    for (id, bound) in bounded {
+        let m = curr_cell(Column::LookupMultiplicity(TableIdx::new(id.to_u32())));
+        lookups_map
+            .entry(bound)
+            .or_default()
+            .push(MVLookup::new(bound, E::one(), &[m]));
+    }
+    lookups_map
+}
+
+/// The number of no-op lookups, see [MVLookup::noop], to add to a table with
+/// `n_lookups` looked-up values per row so that its last partial sum does not
+/// hold a single term. The terms of a table, i.e. the looked-up values and the
+/// table itself, are split in chunks of `MAX_SUPPORTED_DEGREE - 2` terms, one
+/// committed partial sum per chunk. When the number of terms modulo the chunk
+/// size is 1, the last partial sum only holds the term of the table, see
+/// [crate::cost::CircuitShape::underfull_chunks].
+/// Returns 0 if the last chunk holds more than one term.
+pub fn chunk_padding(n_lookups: usize) -> usize {
+    let chunk_size = MAX_SUPPORTED_DEGREE - 2;
+    let n_terms = n_lookups + 1;
+    usize::from(n_terms > chunk_size && n_terms % chunk_size == 1)
+}
+
+/// Pad the lookups of each table with [chunk_padding] no-op lookups, after
+/// the looked-up values. The witness must be padded the same way with
+/// [MVLookupWitness::pad].
+pub fn pad_lookups<F: PrimeField, ID: LookupTableID>(
+    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+) -> BTreeMap<ID, Vec<MVLookup<E<F>, ID>>> {
+    lookups_map
+        .iter()
+        .map(|(id, lookups)| {
+            let mut lookups = lookups.clone();
+            lookups.extend((0..chunk_padding(lookups.len())).map(|_| MVLookup::noop(*id)));
+            (*id, lookups)
+        })
+        .collect()
+}
+
+/// Whether `constraints` read a column of the lookup argument. The circuits
+/// which do not have no lookup data in their proofs: the prover rejects their
+/// lookup witnesses, see [crate::prover::ProverError::UnusedLookups], and the
+/// verifier skips the lookup argument.
+pub fn uses_lookups<F>(constraints: &[E<F>]) -> bool {
+    constraints.iter().any(|constraint| {
+        column_accesses(constraint)
+            .iter()
+            .any(|(column, _)| column.is_lookup())
+    })
+}
+
+/// Build the constraints for the lookup protocol.
+/// The constraints are the partial sum and the aggregation of the partial sums.
+/// For each table, the term of the fixed table is added after the looked-up
+/// values, with the multiplicities as a negative numerator. The column
+/// [Column::LookupFixedTable] contains the values of the table already
+/// combined with the joint combiner and the table ID, therefore its
+/// denominator is simply `β + t(X)`.
+/// The multiplicities of the tables with a [LookupTableID::multiplicity_bound]
+/// are looked up into the bounding table.
+/// The lookups which only differ by their table ID are packed, see
+/// [LookupPacking].
+pub fn constraint_lookups<F: PrimeField, ID: LookupTableID>(
+    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+) -> Vec<E<F>> {
+    constraint_lookups_with_config(lookups_map, &LookupConstraintOptions::default())
+}
+
+/// The options of the constraints of the lookup protocol, see
+/// [constraint_lookups_with_config]. The default options are the ones of
+/// [constraint_lookups].
+#[derive(Debug, Clone, Copy)]
+pub struct LookupConstraintOptions<'a, ID> {
+    /// How the aggregation of the partial sums is given. In the
+    /// [LookupAggregationMode::Claimed] mode, the aggregation constraint is
+    /// omitted, and only the constraints on the partial sums are returned,
+    /// e.g. for a proof chained with others, see [AccumulatorBoundary].
+    pub aggregation: LookupAggregationMode,
+    pub direction: AggregationDirection,
+    /// The packing of the lookups, detected with [LookupPacking::detect] if
+    /// none. It applies to the lookups once coalesced and grouped.
+    pub packing: Option<&'a LookupPacking<ID>>,
+    /// Coalesce the lookups of identical values of each table, see
+    /// [LookupCoalescing::detect]. The layout of the lookup columns is the one
+    /// of the coalesced lookups, and the witness must be coalesced like the
+    /// lookups.
+    pub coalescing: bool,
+    /// Assign the lookups of each table to the chunks with
+    /// [LookupGrouping::detect]. In each chunk, the terms sharing a
+    /// denominator are merged into a single term, whose numerator is the sum
+    /// of their numerators. The number of partial sums is unchanged, and their
+    /// values are the same as with the lookups in the order of the grouping.
+    /// The grouping is detected before the packing of the lookups, and the
+    /// witness must be grouped, then packed, like the lookups.
+    pub grouping: bool,
+}
+
+impl<'a, ID> Default for LookupConstraintOptions<'a, ID> {
+    fn default() -> Self {
+        LookupConstraintOptions {
+            aggregation: LookupAggregationMode::Committed,
+            direction: AggregationDirection::Forward,
+            packing: None,
+            coalescing: false,
+            grouping: false,
+        }
+    }
+}
+
+impl<'a, ID> LookupConstraintOptions<'a, ID> {
+    /// The options for the aggregation mode and direction of `config`
+    pub fn of_config(config: &ProtocolConfig) -> Self {
+        LookupConstraintOptions {
+            aggregation: config.lookup_aggregation,
+            direction: config.aggregation_direction,
+            ..Default::default()
+        }
+    }
+}
+
+/// Build the constraints for the lookup protocol, like [constraint_lookups],
+/// with the options `options`.
+pub fn constraint_lookups_with_config<F: PrimeField, ID: LookupTableID>(
+    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+    options: &LookupConstraintOptions<ID>,
+) -> Vec<E<F>> {
+    let mut lookups_map = Cow::Borrowed(lookups_map);
+    if options.coalescing {
+        lookups_map = Cow::Owned(LookupCoalescing::detect(&lookups_map).apply(&lookups_map));
+    }
+    if options.grouping {
+        lookups_map = Cow::Owned(LookupGrouping::detect(&lookups_map).apply(&lookups_map));
+    }
+    let packing = match options.packing {
+        Some(packing) => Cow::Borrowed(packing),
+        None => Cow::Owned(LookupPacking::detect(&lookups_map)),
+    };
+    constraint_lookups_internal(
+        &lookups_map,
+        &packing,
+        options.aggregation,
+        options.direction,
+        options.grouping,
+    )
+}
+
+/// The boundary values of the running sum `φ` of a proof whose lookups are
+/// only satisfied across a chain of proofs, e.g. in a rollup, see
+/// [crate::prover::prove_with_context_and_accumulator]. The accumulator of the first row
+/// is the public `incoming` value, and the sum of `φ` and of the partial sums
+/// of the last row, i.e. the value `φ` would have on the next row, is given
+/// with the proof as `outgoing`.
+#[serde_as]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+pub struct AccumulatorBoundary<F> {
+    #[serde_as(as = "o1_utils::serialization::SerdeAs")]
+    pub incoming: F,
+    #[serde_as(as = "o1_utils::serialization::SerdeAs")]
+    pub outgoing: F,
+}
+
+/// Check that the accumulators of a chain of proofs telescope to zero, given
+/// the `(incoming, outgoing)` boundaries of the proofs in the order of the
+/// chain: the first proof starts from zero, each proof starts from the
+/// outgoing accumulator of the previous one, and the last one ends at zero.
+/// The lookups of the proofs are then satisfied as a whole.
+pub fn check_chain<F: Field>(boundaries: &[(F, F)]) -> bool {
+    let (Some((first_incoming, _)), Some((_, last_outgoing))) =
+        (boundaries.first(), boundaries.last())
+    else {
+        return true;
+    };
+    first_incoming.is_zero()
+        && last_outgoing.is_zero()
+        && boundaries.windows(2).all(|links| links[0].1 == links[1].0)
+}
+
+/// The constraints on the running sum `φ` of a chained proof with the columns
+/// of `layout`: `φ(ωX) = φ(X) + \sum_{i} h_i(X)` on all the rows but the last
+/// one, `φ(1) = incoming` and `φ(ω^{n - 1}) + \sum_{i} h_i(ω^{n - 1}) =
+/// outgoing`. The rows are selected with the unnormalized Lagrange
+/// polynomials, the one of the last row being normalized by its value
+/// `n ω` on the row.
+pub fn accumulator_constraints<F: PrimeField, ID: LookupTableID>(
+    layout: &LookupLayout<ID>,
+    domain: D<F>,
+    boundary: &AccumulatorBoundary<F>,
+) -> Vec<E<F>> {
+    let lagrange = |offset| {
+        E::Atom(ExprInner::UnnormalizedLagrangeBasis(RowOffset {
+            zk_rows: false,
+            offset,
+        }))
+    };
+    let literal = |x: F| {
+        E::Atom(ExprInner::Constant(ConstantExpr::from(
+            ConstantTerm::Literal(x),
+        )))
+    };
+    let phi = curr_cell(layout.aggregation());
+    let row_sum = layout
+        .partial_sums()
+        .into_iter()
+        .fold(E::zero(), |acc, h| acc + curr_cell(h));
+    let last_row_norm = (F::from(domain.size) * domain.group_gen).inverse().unwrap();
+    let not_last_row = E::one() - lagrange(-1) * literal(last_row_norm);
+    vec![
+        (next_cell(layout.aggregation()) - phi.clone() - row_sum.clone()) * not_last_row,
+        lagrange(0) * (phi.clone() - literal(boundary.incoming)),
+        lagrange(-1) * (phi + row_sum - literal(boundary.outgoing)),
+    ]
+}
+
+/// Whether a fixed table registered in a [TablePolicy] can be left out of a
+/// proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableRequirement {
+    /// The table is active in every proof
+    Mandatory,
+    /// The table can be inactive, when an execution does not use it. Its
+    /// lookups are then not checked.
+    Optional,
+}
+
+/// The fixed tables registered by a circuit, and the sets of active tables
+/// which are acceptable for a proof. Only the columns of the active tables are
+/// committed, and the constraints of the inactive tables are dropped. The
+/// active tables of a proof are the ones with multiplicities, see
+/// [crate::proof::Proof::active_tables], and the layout of the lookup columns
+/// follows from them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TablePolicy<ID> {
+    tables: BTreeMap<ID, TableRequirement>,
+}
+
+impl<ID: LookupTableID> Default for TablePolicy<ID> {
+    fn default() -> Self {
+        TablePolicy {
+            tables: BTreeMap::new(),
+        }
+    }
+}
+
+impl<ID: LookupTableID> TablePolicy<ID> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a table which must be active in every proof
+    pub fn mandatory(mut self, table_id: ID) -> Self {
+        self.tables.insert(table_id, TableRequirement::Mandatory);
+        self
+    }
+
+    /// Register a table which can be inactive
+    pub fn optional(mut self, table_id: ID) -> Self {
+        self.tables.insert(table_id, TableRequirement::Optional);
+        self
+    }
+
+    pub fn requirement(&self, table_id: ID) -> Option<TableRequirement> {
+        self.tables.get(&table_id).copied()
+    }
+
+    /// Check that `active` is an acceptable set of active tables: the tables
+    /// must be registered, and the mandatory ones must be active.
+    pub fn check(&self, active: &BTreeSet<ID>) -> Result<(), ConfigError> {
+        if let Some(table_id) = active.iter().find(|id| !self.tables.contains_key(id)) {
+            return Err(ConfigError::UnregisteredTable(table_id.to_u32()));
+        }
+        match self.tables.iter().find(|(id, requirement)| {
+            **requirement == TableRequirement::Mandatory && !active.contains(id)
+        }) {
+            Some((table_id, _)) => Err(ConfigError::MandatoryTableInactive(table_id.to_u32())),
+            None => Ok(()),
+        }
+    }
+
+    /// The constraints of a proof with the tables `active`: the constraints
+    /// `constraints` and the ones of the lookups into the active tables, see
+    /// [constraint_lookups].
+    pub fn compile_constraints<F: PrimeField>(
+        &self,
+        constraints: &[E<F>],
+        lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+        active: &BTreeSet<ID>,
+    ) -> Result<Vec<E<F>>, ConfigError> {
+        self.check(active)?;
+        let active_lookups: BTreeMap<_, _> = lookups_map
+            .iter()
+            .filter(|(id, _)| active.contains(id))
+            .map(|(id, lookups)| (*id, lookups.clone()))
+            .collect();
+        let mut compiled = constraints.to_vec();
+        compiled.extend(constraint_lookups(&active_lookups));
+        Ok(compiled)
+    }
+}
+
+fn constraint_lookups_internal<F: PrimeField, ID: LookupTableID>(
+    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>,
+    packing: &LookupPacking<ID>,
+    mode: LookupAggregationMode,
+    direction: AggregationDirection,
+    merge_denominators: bool,
+) -> Vec<E<F>> {
+    // Range check the bounded multiplicities into their bounding table
+    let lookups_map = prepare_lookups(lookups_map, packing);
+    let layout = LookupLayout::of_prepared(&lookups_map);
+    let mut partial_sums = layout.partial_sums().into_iter();
+
+    let mut constraints: Vec<E<F>> = vec![];
+    lookups_map.iter().for_each(|(id, lookups)| {
+        let mut terms: Vec<(E<F>, E<F>)> = lookups
+            .iter()
+            .map(|x| (x.numerator.clone(), lookup_denominator(x)))
+            .collect();
+        let MVLookup {
+            numerator, value, ..
+        } = MVLookup::table_term(*id);
+        terms.push((numerator, beta() + value[0].clone()));
+        // We split in chunks of 6 (MAX_SUPPORTED_DEGREE - 2)
+        terms.chunks(MAX_SUPPORTED_DEGREE - 2).for_each(|chunk| {
+            let chunk = if merge_denominators {
+                merge_terms(chunk)
+            } else {
+                chunk.to_vec()
+            };
+            constraints.push(combine_terms(partial_sums.next().unwrap(), chunk));
+        });
+    });
+
+    // Generic code over the partial sum
+    // Compute φ(ωX) - φ(X) - \sum_{i = 1}^{N} h_i(X), or
+    // φ(X) - φ(ωX) - \sum_{i = 1}^{N} h_i(X) in the backward direction
+    if mode == LookupAggregationMode::Committed {
+        let constraint = match direction {
+            AggregationDirection::Forward => {
+                next_cell(layout.aggregation()) - curr_cell(layout.aggregation())
+            }
+            AggregationDirection::Backward => {
+                curr_cell(layout.aggregation()) - next_cell(layout.aggregation())
+            }
+        };
+        let constraint = layout
+            .partial_sums()
+            .into_iter()
+            .fold(constraint, |acc, h| acc - curr_cell(h));
+        constraints.push(constraint);
+    }
+    constraints
+}
+
+/// The combination `r * x_{1} + r^2 * x_{2} + ... + r^{k} * x_{k}` of the
+/// values of the vector lookups, used in the row loop of the prover.
+/// The powers `r^1..r^k` of the joint combiner `r` are computed once, on the
+/// first lookup of width `k`, and the combination is a dot product with them,
+/// instead of the `k + 1` dependent multiplications of the Horner scheme of
+/// [reference::combine_values]. The lookups of width 1, e.g. the range checks,
+/// only cost one multiplication. The result is the same as
+/// [reference::combine_values].
+#[derive(Debug, Clone)]
+pub struct VectorCombiner<F> {
+    /// `r^1, r^2, ..., r^k` for the largest width `k` seen so far
+    powers: Vec<F>,
+    /// The number of multiplications performed by [Self::combine]
+    muls: usize,
+}
+
+impl<F: Field> VectorCombiner<F> {
+    /// Returns a combiner of the values with the joint combiner `r`
+    pub fn new(joint_combiner: F) -> Self {
+        VectorCombiner {
+            powers: vec![joint_combiner],
+            muls: 0,
+        }
+    }
+
+    /// Returns `r * x_{1} + r^2 * x_{2} + ... + r^{k} * x_{k}` for the values
+    /// `x` of width `k`. The table ID is added by the caller.
+    #[inline]
+    pub fn combine(&mut self, value: &[F]) -> F {
+        match value {
+            [] => F::zero(),
+            [x] => {
+                self.muls += 1;
+                self.powers[0] * x
+            }
+            _ => {
+                while self.powers.len() < value.len() {
+                    let next = *self.powers.last().unwrap() * self.powers[0];
+                    self.powers.push(next);
+                    self.muls += 1;
+                }
+                self.muls += value.len();
+                self.powers
+                    .iter()
+                    .zip(value)
+                    .fold(F::zero(), |acc, (power, x)| acc + *power * x)
+            }
+        }
+    }
+
+    /// Returns the number of field multiplications performed so far,
+    /// including the computation of the powers of the joint combiner.
+    pub fn muls(&self) -> usize {
+        self.muls
+    }
+}
+
+/// A reference implementation of the row sums of the lookup argument, for
+/// differential testing against other implementations of the protocol. It
+/// only depends on the field arithmetic, and computes the values of one row
+/// without the batch inversion and the sharing of the combined values and of
+/// the denominators of [prover::Env::create], which is tested against it.
+pub mod reference {
+    use crate::{
+        mvlookup::{LookupTableID, MVLookup, MVLookupWitness, VectorCombiner},
+        MAX_SUPPORTED_DEGREE,
+    };
+    use ark_ff::{Field, PrimeField};
+    use std::collections::BTreeMap;
+
+    /// Returns `r * x_{1} + r^2 * x_{2} + ... + r^{n} * x_{n}` for the values
+    /// `x` and the joint combiner `r`. The table ID is added separately.
+    pub fn combine_values<F: Field>(joint_combiner: F, value: &[F]) -> F {
+        value
+            .iter()
+            .rev()
+            .fold(F::zero(), |acc, y| acc * joint_combiner + y)
+            * joint_combiner
+    }
+
+    /// Returns the evaluations of the partial sums `h` of `lookups` and the
+    /// *combined* values of their fixed tables, like
+    /// [crate::mvlookup::prover::partial_sums], computed sequentially: the
+    /// chunks of a witness are interleaved in a single pass over the rows,
+    /// with a single batch inversion per witness.
+    pub fn partial_sums<F: PrimeField, ID: LookupTableID>(
+        lookups: &[MVLookupWitness<F, ID>],
+        beta: F,
+        vector_lookup_combiner: F,
+        domain_size: usize,
+    ) -> (Vec<Vec<F>>, BTreeMap<ID, Vec<F>>) {
+        let mut fixed_lookup_tables: BTreeMap<ID, Vec<F>> = BTreeMap::new();
+        let partial_sums = lookups
+            .iter()
+            .flat_map(|lookup| {
+                let f = &lookup.f;
+                let n = f.len();
+                let n_partial_sums = lookup.n_partial_sums();
+                let mut partial_sums = vec![Vec::<F>::with_capacity(domain_size); n_partial_sums];
+
+                let mut denominators = Vec::with_capacity(n * domain_size);
+                let mut denominator_indices = Vec::with_capacity(n * domain_size);
+                let mut combiner = VectorCombiner::new(vector_lookup_combiner);
+                for j in 0..domain_size {
+                    let mut previous: Option<(&MVLookup<_, ID>, _)> = None;
+                    for (i, f_i) in f.iter().enumerate() {
+                        let lookup = &f_i[j];
+                        let (combined_value, same_denominator) = match previous {
+                            Some((previous, combined)) if previous.value == lookup.value => {
+                                (combined, previous.table_id == lookup.table_id)
+                            }
+                            _ => (combiner.combine(&lookup.value), false),
+                        };
+                        previous = Some((lookup, combined_value));
+                        let combined_value = combined_value + lookup.table_id.to_field::<F>();
+                        if i == (n - 1) && lookup.table_id.is_fixed() {
+                            fixed_lookup_tables
+                                .entry(lookup.table_id)
+                                .or_default()
+                                .push(combined_value);
+                        }
+                        if !same_denominator {
+                            denominators.push(beta + combined_value);
+                        }
+                        denominator_indices.push(denominators.len() - 1);
+                    }
+                }
+                ark_ff::fields::batch_inversion(&mut denominators);
+
+                let mut denominator_indices = denominator_indices.into_iter();
+                for j in 0..domain_size {
+                    let mut partial_sum_idx = 0;
+                    let mut row_acc = F::zero();
+                    for (i, f_i) in f.iter().enumerate() {
+                        row_acc +=
+                            f_i[j].numerator * denominators[denominator_indices.next().unwrap()];
+                        if (i + 1) % (MAX_SUPPORTED_DEGREE - 2) == 0 {
+                            partial_sums[partial_sum_idx].push(row_acc);
+                            row_acc = F::zero();
+                            partial_sum_idx += 1;
+                        }
+                    }
+                    if n % (MAX_SUPPORTED_DEGREE - 2) != 0 {
+                        partial_sums[partial_sum_idx].push(row_acc);
+                    }
+                }
+                partial_sums
+            })
+            .collect();
+        (partial_sums, fixed_lookup_tables)
+    }
+
+    /// Returns the values of the partial sums `h_i` for one row, i.e. for
+    /// each chunk of [MAX_SUPPORTED_DEGREE] - 2 lookups of `row`, the sum of
+    /// `numerator / (β + table_id + combine_values(value))`.
+    /// The lookups are given in the order of the columns of the
+    /// [crate::mvlookup::MVLookupWitness], the last one being the table for
+    /// the fixed tables, with a numerator `-m`. The table is not treated
+    /// differently from the other lookups.
+    pub fn row_partial_sums<F: PrimeField, ID: LookupTableID>(
+        beta: F,
+        joint_combiner: F,
+        row: &[MVLookup<F, ID>],
+    ) -> Vec<F> {
+        row.chunks(MAX_SUPPORTED_DEGREE - 2)
+            .map(|chunk| {
+                chunk.iter().fold(F::zero(), |acc, lookup| {
+                    let combined = lookup.table_id.to_field::<F>()
+                        + combine_values(joint_combiner, &lookup.value);
+                    acc + lookup.numerator * (beta + combined).inverse().unwrap()
+                })
+            })
+            .collect()
+    }
+}
+
+pub mod prover {
+    use crate::{
+        checkpoint::{self, CheckpointError},
+        column_env::evaluate_over_coset,
+        columns::Column,
+        lookup_engine::{
+            LookupChallenges, LookupEngine, LookupEngineError, LookupEngineKind, LookupEngines,
+            PlookupColumns, PlookupEngine,
+        },
+        lookup_stats::LookupStats,
+        mvlookup::{
+            reference, AccumulatorBoundary, AggregationDirection, LookupAbsorptionOrder,
+            LookupAggregation, LookupAggregationMode, LookupTableID, MVLookup, MVLookupWitness,
+            VectorCombiner,
+        },
+        perf::PerfCounters,
+        prover::{validate_lookup_witnesses, ProverError, ProverWarning, ProvingMode},
+        MAX_SUPPORTED_DEGREE,
+    };
+    use ark_ff::{FftField, Field, PrimeField, Zero};
+    use ark_poly::{univariate::DensePolynomial, Evaluations, Radix2EvaluationDomain as D};
+    use ark_serialize::CanonicalSerialize;
+    use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
+    use mina_poseidon::FqSponge;
+    use o1_utils::{serialization::SerdeAs, FieldHelpers};
+    use poly_commitment::{
+        commitment::{absorb_commitment, PolyComm},
+        OpenProof, SRS as _,
+    };
+    use rayon::iter::{IntoParallelIterator, ParallelIterator};
+    use serde::{Deserialize, Serialize};
+    use serde_with::serde_as;
+    use std::{collections::BTreeMap, path::Path};
+
+    /// The error of lookups whose running sum does not end at zero, naming
+    /// the first table whose partial sums, in `partial_sums`, do not sum to
+    /// zero over the domain. The partial sums which follow the ones of the
+    /// last table are counted with it, so that the residual of the running
+    /// sum is always reported.
+    pub(crate) fn unbalanced_lookups<'a, F: PrimeField, ID: LookupTableID>(
+        lookups: &[MVLookupWitness<F, ID>],
+        mut partial_sums: impl Iterator<Item = &'a [F]>,
+    ) -> ProverError {
+        let aggregation_non_zero =
+            |table_id: ID, residual: F| ProverError::LookupAggregationNonZero {
+                table_id: table_id.to_u32(),
+                residual: residual.to_biguint().to_string(),
+            };
+        let mut last_table_id = None;
+        for lookup in lookups {
+            let residual: F = partial_sums
+                .by_ref()
+                .take(lookup.n_partial_sums())
+                .flatten()
+                .sum();
+            // A witness without column has no partial sums
+            let Some(table_id) = lookup.table_id() else {
+                continue;
+            };
+            if !residual.is_zero() {
+                return aggregation_non_zero(table_id, residual);
+            }
+            last_table_id = Some(table_id);
+        }
+        let residual: F = partial_sums.flatten().sum();
+        match last_table_id {
+            Some(table_id) => aggregation_non_zero(table_id, residual),
+            None => ProverError::Generic("there is no lookup witness"),
+        }
+    }
+
+    /// Returns the evaluations over d1 of the partial sums `h` of `lookups`,
+    /// one per chunk of (MAX_SUPPORTED_DEGREE - 2) columns of each witness,
+    /// and the *combined* values of the fixed tables, which are the last
+    /// column of their witness.
+    /// The chunks are independent: each one has its own denominators and
+    /// batch inversion, and the chunks of all the witnesses are computed in
+    /// parallel. The evaluations are the ones of [reference::partial_sums].
+    pub fn partial_sums<F: PrimeField, ID: LookupTableID>(
+        lookups: &[MVLookupWitness<F, ID>],
+        beta: F,
+        vector_lookup_combiner: F,
+        domain_size: usize,
+        perf: &PerfCounters,
+    ) -> (Vec<Vec<F>>, BTreeMap<ID, Vec<F>>) {
+        partial_sums_with_mode(
+            lookups,
+            beta,
+            vector_lookup_combiner,
+            domain_size,
+            ProvingMode::Optimized,
+            perf,
+        )
+    }
+
+    /// Returns the partial sums like [partial_sums]. In the mode
+    /// [ProvingMode::ConstantTrace], each cell has its own denominator and
+    /// its numerator is always multiplied, whatever the values of the
+    /// witness, so that the operations only depend on its shape.
+    pub fn partial_sums_with_mode<F: PrimeField, ID: LookupTableID>(
+        lookups: &[MVLookupWitness<F, ID>],
+        beta: F,
+        vector_lookup_combiner: F,
+        domain_size: usize,
+        mode: ProvingMode,
+        perf: &PerfCounters,
+    ) -> (Vec<Vec<F>>, BTreeMap<ID, Vec<F>>) {
+        let chunk_size = MAX_SUPPORTED_DEGREE - 2;
+        // The chunks of the witnesses, with whether they contain the last
+        // column of their witness
+        let chunks: Vec<_> = lookups
+            .iter()
+            .flat_map(|lookup| {
+                let n = lookup.f.len();
+                lookup
+                    .f
+                    .chunks(chunk_size)
+                    .enumerate()
+                    .map(move |(c, chunk)| (chunk, (c + 1) * chunk_size >= n))
+            })
+            .collect();
+        let (partial_sums, fixed_table_values): (Vec<_>, Vec<Vec<_>>) = chunks
+            .into_par_iter()
+            .map(|(chunk, last)| {
+                chunk_partial_sum(
+                    chunk,
+                    last,
+                    beta,
+                    vector_lookup_combiner,
+                    domain_size,
+                    mode,
+                    perf,
+                )
+            })
+            .unzip();
+
+        // The values are added in the order of the witnesses, and of the rows
+        let mut fixed_lookup_tables: BTreeMap<ID, Vec<F>> = BTreeMap::new();
+        for (table_id, combined_value) in fixed_table_values.into_iter().flatten() {
+            fixed_lookup_tables
+                .entry(table_id)
+                .or_default()
+                .push(combined_value);
+        }
+        (partial_sums, fixed_lookup_tables)
+    }
+
+    /// Returns the evaluations of the partial sum of the columns `chunk` of a
+    /// lookup witness and, if it contains the last column of the witness
+    /// (`last`), the *combined* values of the fixed tables of this column,
+    /// row by row.
+    fn chunk_partial_sum<F: PrimeField, ID: LookupTableID>(
+        chunk: &[Vec<MVLookup<F, ID>>],
+        last: bool,
+        beta: F,
+        vector_lookup_combiner: F,
+        domain_size: usize,
+        mode: ProvingMode,
+        perf: &PerfCounters,
+    ) -> (Vec<F>, Vec<(ID, F)>) {
+        let constant_trace = mode == ProvingMode::ConstantTrace;
+        let n = chunk.len();
+        let mut fixed_table_values = Vec::new();
+
+        // We compute first the denominators of the chunk. We gather them in
+        // a vector to perform a batch inversion.
+        let mut denominators = Vec::with_capacity(n * domain_size);
+        // The index in `denominators` of the denominator of each cell, row by
+        // row. The cells with the same table ID and value as the previous
+        // column of the row share its denominator, see [LookupGrouping].
+        let mut denominator_indices = Vec::with_capacity(n * domain_size);
+        let mut combiner = VectorCombiner::new(vector_lookup_combiner);
+        let mut table_ids: Vec<Option<(ID, F)>> = vec![None; n];
+        // Iterate over the rows
+        for j in 0..domain_size {
+            // The lookup of the previous column, and the combination of its
+            // values, shared with the packed lookups, see [LookupPacking].
+            let mut previous: Option<(&MVLookup<_, ID>, _)> = None;
+            // Iterate over individual columns (i.e. f_i and t)
+            for (i, f_i) in chunk.iter().enumerate() {
+                let lookup = &f_i[j];
+                let MVLookup {
+                    numerator: _,
+                    table_id,
+                    value,
+                } = lookup;
+                // Compute r * x_{1} + r^2 x_{2} + ... r^{N} x_{N}
+                let (combined_value, same_denominator) = match previous {
+                    Some((previous, combined)) if !constant_trace && previous.value == *value => {
+                        (combined, previous.table_id == *table_id)
+                    }
+                    _ => (combiner.combine(value), false),
+                };
+                previous = Some((lookup, combined_value));
+                // add table id, converted once per column as long as the
+                // column keeps the same table
+                let table_id_field = match table_ids[i] {
+                    Some((id, id_field)) if !constant_trace && id == *table_id => id_field,
+                    _ => {
+                        let id_field = table_id.to_field::<F>();
+                        table_ids[i] = Some((*table_id, id_field));
+                        id_field
+                    }
+                };
+                let combined_value = combined_value + table_id_field;
+
+                // If last element and fixed lookup tables, we keep the
+                // *combined* value of the table.
+                if last && i == (n - 1) && table_id.is_fixed() {
+                    fixed_table_values.push((*table_id, combined_value));
+                }
+
+                // β + a_{i}
+                if !same_denominator {
+                    denominators.push(beta + combined_value);
+                }
+                denominator_indices.push(denominators.len() - 1);
+            }
+        }
+
+        perf.record_combiner_muls(combiner.muls());
+        perf.record_batch_inversion(denominators.len());
+        ark_ff::fields::batch_inversion(&mut denominators);
+
+        // We only need to add the numerators now. The numerator of the
+        // unconditional lookups is one, and their inverted denominator is
+        // added without multiplying it.
+        let mut numerator_muls = 0;
+        let mut skipped_numerator_muls = 0;
+        let mut denominator_indices = denominator_indices.into_iter();
+        let partial_sum = (0..domain_size)
+            .map(|j| {
+                chunk.iter().fold(F::zero(), |acc, f_i| {
+                    let inverse = denominators[denominator_indices.next().unwrap()];
+                    let numerator = f_i[j].numerator;
+                    if !constant_trace && numerator.is_one() {
+                        skipped_numerator_muls += 1;
+                        acc + inverse
+                    } else {
+                        numerator_muls += 1;
+                        acc + numerator * inverse
+                    }
+                })
+            })
+            .collect();
+        perf.record_numerator_muls(numerator_muls, skipped_numerator_muls);
+        (partial_sum, fixed_table_values)
+    }
+
+    /// Returns the rows of the fixed tables of `lookups` which have the same
+    /// value once combined with `joint_combiner`, see
+    /// [ProverWarning::CombinedValueCollision].
+    /// The rows which are not looked up, like the padding of the tables, do
+    /// not contribute to the lookup argument and are ignored.
+    pub fn combined_value_collisions<F: Field, ID: LookupTableID>(
+        lookups: &[MVLookupWitness<F, ID>],
+        joint_combiner: F,
+    ) -> Vec<ProverWarning> {
+        // The values with their trailing zeroes removed, which do not change
+        // the combined value
+        let trimmed = |value: &[F]| {
+            let len = value
+                .iter()
+                .rposition(|x| !x.is_zero())
+                .map_or(0, |i| i + 1);
+            value[..len].to_vec()
+        };
+        let mut warnings = vec![];
+        for lookup in lookups {
+            let Some(table) = lookup.f.last() else {
+                continue;
+            };
+            let Some(table_id) = table.first().map(|row| row.table_id) else {
+                continue;
+            };
+            if !table_id.is_fixed() {
+                continue;
+            }
+            let mut rows_by_value: BTreeMap<F, Vec<usize>> = BTreeMap::new();
+            for (i, row) in table.iter().enumerate() {
+                if row.numerator.is_zero() {
+                    continue;
+                }
+                let combined = reference::combine_values(joint_combiner, &row.value);
+                rows_by_value.entry(combined).or_default().push(i);
+            }
+            // Reported in the order of the rows
+            let mut colliding_rows: Vec<Vec<usize>> = rows_by_value
+                .into_values()
+                .filter(|rows| rows.len() > 1)
+                .collect();
+            colliding_rows.sort();
+            for rows in colliding_rows {
+                let mut rows_by_raw_value: BTreeMap<Vec<F>, Vec<usize>> = BTreeMap::new();
+                for &i in &rows {
+                    rows_by_raw_value
+                        .entry(trimmed(&table[i].value))
+                        .or_default()
+                        .push(i);
+                }
+                let n_distinct = rows_by_raw_value.len();
+                warnings.extend(
+                    rows_by_raw_value
+                        .into_values()
+                        .filter(|rows| rows.len() > 1)
+                        .map(|rows| ProverWarning::CombinedValueCollision {
+                            table_id: table_id.to_u32(),
+                            rows,
+                            structural: true,
+                        }),
+                );
+                if n_distinct > 1 {
+                    warnings.push(ProverWarning::CombinedValueCollision {
+                        table_id: table_id.to_u32(),
+                        rows,
+                        structural: false,
+                    });
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Returns the evaluations over d1 of the multiplicities m(X), for each
+    /// fixed lookup table.
+    // FIXME/IMPROVEME: m(X) is only for fixed table
+    pub fn lookup_counters_evals_d1<F: PrimeField, ID: LookupTableID>(
+        lookups: &[MVLookupWitness<F, ID>],
+        domain: EvaluationDomains<F>,
+    ) -> BTreeMap<ID, Evaluations<F, D<F>>> {
+        lookups
+            .into_par_iter()
+            .filter_map(|lookup| {
+                // Does not handle RAMLookup
+                let table_id = lookup.table_id().filter(ID::is_fixed)?;
+                Some((
+                    table_id,
+                    Evaluations::<F, D<F>>::from_vec_and_domain(lookup.m.to_vec(), domain.d1),
+                ))
+            })
+            .collect()
+    }
+
+    /// Commit to the multiplicities m(X), given by their evaluations over d1.
+    pub fn commit_lookup_counters<G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID>(
+        lookup_counters_evals_d1: &BTreeMap<ID, Evaluations<G::ScalarField, D<G::ScalarField>>>,
+        domain: EvaluationDomains<G::ScalarField>,
+        srs: &OpeningProof::SRS,
+    ) -> BTreeMap<ID, PolyComm<G>>
+    where
+        OpeningProof::SRS: Sync,
+    {
+        lookup_counters_evals_d1
+            .into_par_iter()
+            .map(|(id, evals)| (*id, srs.commit_evaluations_non_hiding(domain.d1, evals)))
+            .collect()
+    }
+
+    pub struct QuotientPolynomialEnvironment<'a, F: FftField, ID: LookupTableID> {
+        pub lookup_terms_evals_d8: &'a Vec<Evaluations<F, D<F>>>,
+        /// The evaluations of `φ`, if it is committed
+        pub lookup_aggregation_evals_d8: Option<&'a Evaluations<F, D<F>>>,
+        pub lookup_counters_evals_d8: &'a BTreeMap<ID, Evaluations<F, D<F>>>,
+        pub fixed_tables_evals_d8: &'a BTreeMap<ID, Evaluations<F, D<F>>>,
+        /// The evaluations of the columns of the plookup engine
+        pub plookup_evals_d8: &'a BTreeMap<ID, PlookupColumns<Evaluations<F, D<F>>>>,
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub struct Env<G: KimchiCurve, ID: LookupTableID> {
+        pub lookup_counters_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>>,
+        pub lookup_counters_comm_d1: BTreeMap<ID, PolyComm<G>>,
+
+        pub lookup_terms_poly_d1: Vec<DensePolynomial<G::ScalarField>>,
+        pub lookup_terms_comms_d1: Vec<PolyComm<G>>,
+
+        /// The running sum `φ`, in the [LookupAggregationMode::Committed] mode
+        pub lookup_aggregation_poly_d1: Option<DensePolynomial<G::ScalarField>>,
+        /// The commitment to `φ`, or the claimed sum of the partial sums
+        pub lookup_aggregation: LookupAggregation<PolyComm<G>, G::ScalarField>,
+
+        // Evaluating over d8 for the quotient polynomial
+        pub lookup_counters_evals_d8: BTreeMap<ID, Evaluations<G::ScalarField, D<G::ScalarField>>>,
+        pub lookup_terms_evals_d8: Vec<Evaluations<G::ScalarField, D<G::ScalarField>>>,
+        pub lookup_aggregation_evals_d8: Option<Evaluations<G::ScalarField, D<G::ScalarField>>>,
+
+        pub fixed_lookup_tables_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>>,
+        pub fixed_lookup_tables_comms_d1: BTreeMap<ID, PolyComm<G>>,
+        pub fixed_lookup_tables_evals_d8:
+            BTreeMap<ID, Evaluations<G::ScalarField, D<G::ScalarField>>>,
+
+        /// The combiner used for vector lookups
+        pub joint_combiner: G::ScalarField,
+
+        /// The evaluation point used for the lookup polynomials.
+        pub beta: G::ScalarField,
+
+        /// The boundary of `φ`, when it starts from an incoming accumulator
+        pub accumulator: Option<AccumulatorBoundary<G::ScalarField>>,
+
+        /// The columns of the tables proven with the plookup engine
+        pub plookup_poly_d1: BTreeMap<ID, PlookupColumns<DensePolynomial<G::ScalarField>>>,
+        pub plookup_comms_d1: BTreeMap<ID, PlookupColumns<PolyComm<G>>>,
+        pub plookup_evals_d8:
+            BTreeMap<ID, PlookupColumns<Evaluations<G::ScalarField, D<G::ScalarField>>>>,
+
+        /// The shift of the values of the tables of the plookup engine, only
+        /// coined if there is one
+        pub gamma: Option<G::ScalarField>,
+    }
+
+    impl<G: KimchiCurve, ID: LookupTableID> Env<G, ID> {
+        /// Create an environment for the prover to create a proof for the MVLookup protocol.
+        /// The protocol does suppose that the individual lookup terms are
+        /// committed as part of the columns.
+        /// Therefore, the protocol only focus on commiting to the "grand
+        /// product sum" and the "row-accumulated" values.
+        /// In the [LookupAggregationMode::Claimed] mode, the running sum is
+        /// not committed, and the sum of the partial sums is absorbed instead.
+        /// When `incoming` is given, `φ(1)` is the incoming accumulator, and
+        /// the sum over the domain does not need to be zero, see
+        /// [AccumulatorBoundary].
+        /// The commitments are absorbed in the order `absorption`.
+        /// The statistics of the lookups are added to `stats` if given, see
+        /// [LookupStats].
+        /// The witnesses can be given in any order: they are sorted by table
+        /// ID, which is the order of the partial sums in the constraints, see
+        /// [LookupLayout].
+        ///
+        /// The witnesses are checked first by [validate_lookup_witnesses], and
+        /// the rest of the function relies on its invariants: all the columns
+        /// and the multiplicities have the size of d1, and the witness of each
+        /// fixed table ends with its column. A violation of these invariants
+        /// found later is reported as [ProverError::Internal]. Whatever the
+        /// witnesses, the function returns an error instead of panicking, e.g.
+        /// [ProverError::LookupAggregationNonZero] if a value looked up is not
+        /// in its table, or if its multiplicities are wrong.
+        /// All the tables are proven with MVLookup, see
+        /// [Self::create_with_engines] to select another engine.
+        #[allow(clippy::too_many_arguments)]
+        pub fn create<
+            OpeningProof: OpenProof<G>,
+            Sponge: FqSponge<G::BaseField, G, G::ScalarField>,
+        >(
+            lookups: Vec<MVLookupWitness<G::ScalarField, ID>>,
+            domain: EvaluationDomains<G::ScalarField>,
+            fq_sponge: &mut Sponge,
+            srs: &OpeningProof::SRS,
+            aggregation_mode: LookupAggregationMode,
+            direction: AggregationDirection,
+            absorption: LookupAbsorptionOrder,
+            incoming: Option<G::ScalarField>,
+            perf: &PerfCounters,
+            stats: Option<&mut LookupStats>,
+        ) -> Result<Self, ProverError>
+        where
+            OpeningProof::SRS: Sync,
+        {
+            Self::create_with_engines::<OpeningProof, Sponge>(
+                lookups,
+                domain,
+                fq_sponge,
+                srs,
+                aggregation_mode,
+                direction,
+                absorption,
+                incoming,
+                perf,
+                stats,
+                &LookupEngines::default(),
+                ProvingMode::Optimized,
+            )
+        }
+
+        /// Create the environment like [Self::create], proving the lookups
+        /// into each table with its engine in `engines`.
+        /// The columns of the first phase of the plookup engine are absorbed
+        /// after the multiplicities, and the challenge `γ` is coined after
+        /// `β` if there is a table of the plookup engine. The grand products
+        /// are absorbed last, after the columns of MVLookup. Without a table
+        /// of MVLookup, there is no partial sum, and `φ` is zero.
+        /// The partial sums are computed with `mode`, see
+        /// [partial_sums_with_mode].
+        #[allow(clippy::too_many_arguments)]
+        pub fn create_with_engines<
+            OpeningProof: OpenProof<G>,
+            Sponge: FqSponge<G::BaseField, G, G::ScalarField>,
+        >(
+            mut lookups: Vec<MVLookupWitness<G::ScalarField, ID>>,
+            domain: EvaluationDomains<G::ScalarField>,
+            fq_sponge: &mut Sponge,
+            srs: &OpeningProof::SRS,
+            aggregation_mode: LookupAggregationMode,
+            direction: AggregationDirection,
+            absorption: LookupAbsorptionOrder,
+            incoming: Option<G::ScalarField>,
+            perf: &PerfCounters,
+            mut stats: Option<&mut LookupStats>,
+            engines: &LookupEngines<ID>,
+            mode: ProvingMode,
+        ) -> Result<Self, ProverError>
+        where
+            OpeningProof::SRS: Sync,
+        {
+            if lookups.is_empty() {
+                return Err(ProverError::Generic("there is no lookup witness"));
+            }
+            // The table terms of the constraints are -m / (β + t), see
+            // MVLookup::table_term
+            validate_lookup_witnesses(&lookups, domain.d1.size as usize)?;
+            // The partial sums are flattened in the order of the witnesses
+            lookups.sort_by_key(|lookup| lookup.table_id());
+            if let Some(stats) = stats.as_mut() {
+                lookups.iter().for_each(|lookup| stats.record(lookup));
+            }
+            let (plookups, lookups): (Vec<_>, Vec<_>) = lookups.into_iter().partition(|lookup| {
+                lookup.table_id().map(|table_id| engines.engine(table_id))
+                    == Some(LookupEngineKind::Plookup)
+            });
+            if let Some(table_id) = engines.tables(LookupEngineKind::Plookup).find(|table_id| {
+                !plookups
+                    .iter()
+                    .any(|lookup| lookup.table_id() == Some(*table_id))
+            }) {
+                return Err(ProverError::LookupEngine(
+                    LookupEngineError::MissingWitness(table_id.to_u32()),
+                ));
+            }
+            let d1_size = domain.d1.size;
+            let d8_size = domain.d8.size;
+            let elem_size = G::ScalarField::zero().serialized_size();
+            // Interpolate over d1, and evaluate over d8
+            let interpolate = |evals: &Evaluations<G::ScalarField, D<G::ScalarField>>| {
+                perf.record_fft(d1_size);
+                evals.interpolate_by_ref()
+            };
+            // The evaluations over d8 are only used by the quotient, over the
+            // coset of d8, see [crate::column_env::ColumnEnvironment]
+            let evaluate_d8 = |poly: &DensePolynomial<G::ScalarField>| {
+                perf.record_fft(d8_size);
+                evaluate_over_coset(poly, domain.d8)
+            };
+            let commit = |evals: &Evaluations<G::ScalarField, D<G::ScalarField>>| {
+                perf.record_commitment(d1_size as usize, 1, elem_size);
+                srs.commit_evaluations_non_hiding(domain.d1, evals)
+            };
+
+            // Polynomial m(X)
+            let lookup_counters_evals_d1 = lookup_counters_evals_d1(&lookups, domain);
+
+            let lookup_counters_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>> =
+                (&lookup_counters_evals_d1)
+                    .into_par_iter()
+                    .map(|(id, evals)| (*id, interpolate(evals)))
+                    .collect();
+
+            let lookup_counters_evals_d8: BTreeMap<
+                ID,
+                Evaluations<G::ScalarField, D<G::ScalarField>>,
+            > = (&lookup_counters_poly_d1)
+                .into_par_iter()
+                .map(|(id, lookup)| (*id, evaluate_d8(lookup)))
+                .collect();
+
+            let lookup_counters_comm_d1: BTreeMap<ID, PolyComm<G>> = (&lookup_counters_evals_d1)
+                .into_par_iter()
+                .map(|(id, evals)| (*id, commit(evals)))
+                .collect();
+
+            lookup_counters_comm_d1
+                .values()
+                .for_each(|comm| absorb_commitment(fq_sponge, comm));
+            // -- end of m(X)
+
+            // The columns of the plookup engine, with their polynomials, their
+            // evaluations over d8 and their commitments
+            let plookup_columns = |columns: Vec<(Column, Vec<G::ScalarField>)>| {
+                columns
+                    .into_par_iter()
+                    .map(|(column, evals)| {
+                        let evals = Evaluations::from_vec_and_domain(evals, domain.d1);
+                        let poly = interpolate(&evals);
+                        (column, evaluate_d8(&poly), poly, commit(&evals))
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let domain_size = d1_size as usize;
+            let plookup_first_phase = PlookupEngine.first_phase(&plookups, domain_size)?;
+            let plookup_first_phase_columns = plookup_columns(plookup_first_phase.clone());
+            plookup_first_phase_columns
+                .iter()
+                .for_each(|(_, _, _, comm)| absorb_commitment(fq_sponge, comm));
+
+            // -- start computing the row sums h(X)
+            // It will be used to compute the running sum in lookup_aggregation
+            // Coin a combiner to perform vector lookup.
+            // The row sums h are defined as
+            // --           n            1                    1
+            // h(ω^i) = ∑        -------------------- - --------------
+            //            j = 0    (β + f_{j}(ω^i))      (β + t(ω^i))
+            let vector_lookup_combiner = fq_sponge.challenge();
+            // A failure of the lookup argument caused by a table with
+            // duplicated rows is hard to diagnose
+            if cfg!(debug_assertions) && mode == ProvingMode::Optimized {
+                for warning in combined_value_collisions(&lookups, vector_lookup_combiner) {
+                    log::warn!("{warning}");
+                }
+            }
+
+            // Coin an evaluation point for the rational functions
+            let beta = fq_sponge.challenge();
+            let gamma = (!plookups.is_empty()).then(|| fq_sponge.challenge());
+
+            // Contain the evalations of the h_i. We divide the looked-up values
+            // in chunks of (MAX_SUPPORTED_DEGREE - 2)
+            let (lookup_terms_evals, fixed_lookup_tables) = partial_sums_with_mode(
+                &lookups,
+                beta,
+                vector_lookup_combiner,
+                domain_size,
+                mode,
+                perf,
+            );
+
+            // Sanity check to verify that the number of evaluations is correct
+            if lookup_terms_evals
+                .iter()
+                .any(|evals| evals.len() != domain.d1.size as usize)
+            {
+                return Err(ProverError::Internal(
+                    "a partial sum does not have the size of the domain",
+                ));
+            }
+
+            // Sanity check to verify that we have all the evaluations for the
+            // fixed lookup tables, and the multiplicities of each of them
+            if fixed_lookup_tables
+                .values()
+                .any(|evals| evals.len() != domain.d1.size as usize)
+                || !fixed_lookup_tables
+                    .keys()
+                    .eq(lookup_counters_evals_d1.keys())
+            {
+                return Err(ProverError::Internal(
+                    "the fixed tables do not match the multiplicities",
+                ));
+            }
+
+            let lookup_terms_evals_d1: Vec<Evaluations<G::ScalarField, D<G::ScalarField>>> =
+                lookup_terms_evals
+                    .into_par_iter()
+                    .map(|lte| {
+                        Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
+                            lte, domain.d1,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+            let fixed_lookup_tables_evals_d1: BTreeMap<
+                ID,
+                Evaluations<G::ScalarField, D<G::ScalarField>>,
+            > = fixed_lookup_tables
+                .into_iter()
+                .map(|(id, evals)| {
+                    (
+                        id,
+                        Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
+                            evals, domain.d1,
+                        ),
+                    )
+                })
+                .collect();
+
+            let lookup_terms_poly_d1: Vec<DensePolynomial<G::ScalarField>> =
+                (&lookup_terms_evals_d1)
+                    .into_par_iter()
+                    .map(interpolate)
+                    .collect::<Vec<_>>();
+
+            let fixed_lookup_tables_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>> =
+                (&fixed_lookup_tables_evals_d1)
+                    .into_par_iter()
+                    .map(|(id, evals)| (*id, interpolate(evals)))
+                    .collect();
+
+            let lookup_terms_evals_d8: Vec<Evaluations<G::ScalarField, D<G::ScalarField>>> =
+                (&lookup_terms_poly_d1)
+                    .into_par_iter()
+                    .map(evaluate_d8)
+                    .collect::<Vec<_>>();
+
+            let fixed_lookup_tables_evals_d8: BTreeMap<
+                ID,
+                Evaluations<G::ScalarField, D<G::ScalarField>>,
+            > = (&fixed_lookup_tables_poly_d1)
+                .into_par_iter()
+                .map(|(id, poly)| (*id, evaluate_d8(poly)))
+                .collect();
+
+            let lookup_terms_comms_d1: Vec<PolyComm<G>> = (&lookup_terms_evals_d1)
+                .into_par_iter()
+                .map(commit)
+                .collect::<Vec<_>>();
+
+            let fixed_lookup_tables_comms_d1: BTreeMap<ID, PolyComm<G>> =
+                (&fixed_lookup_tables_evals_d1)
+                    .into_par_iter()
+                    .map(|(id, evals)| (*id, commit(evals)))
+                    .collect();
+
+            let absorb_columns = |fq_sponge: &mut Sponge| {
+                lookup_terms_comms_d1
+                    .iter()
+                    .for_each(|comm| absorb_commitment(fq_sponge, comm));
+
+                fixed_lookup_tables_comms_d1
+                    .values()
+                    .for_each(|comm| absorb_commitment(fq_sponge, comm));
+            };
+            if absorption == LookupAbsorptionOrder::ColumnsFirst {
+                absorb_columns(fq_sponge);
+            }
+            // -- end computing the row sums h
+
+            // -- start computing the running sum in lookup_aggregation
+            // The running sum, φ, is defined recursively over the subgroup as followed:
+            // - φ(1) = 0
+            // - φ(ω^{j + 1}) = φ(ω^j) + \
+            //                         \sum_{i = 1}^{n} (1 / (β + f_i(ω^{j + 1}))) - \
+            //                         (m(ω^{j + 1}) / (β + t(ω^{j + 1})))
+            // - φ(ω^n) = 0
+            // In the backward direction, the rows are accumulated from the last
+            // one, with φ(ω^j) = φ(ω^{j + 1}) + \sum_{i} h_i(ω^j).
+            let (lookup_aggregation_evals_d1, claimed_sum) = {
+                let n = domain.d1.size as usize;
+                let mut evals = vec![G::ScalarField::zero(); n];
+                let mut acc = incoming.unwrap_or_else(G::ScalarField::zero);
+                let row_sum = |i: usize| {
+                    lookup_terms_evals_d1
+                        .iter()
+                        .fold(G::ScalarField::zero(), |acc, lte| acc + lte[i])
+                };
+                match direction {
+                    AggregationDirection::Forward => {
+                        for (i, eval) in evals.iter_mut().enumerate() {
+                            // φ(1) = 0, or the incoming accumulator
+                            *eval = acc;
+                            acc += row_sum(i);
+                        }
+                    }
+                    AggregationDirection::Backward => {
+                        for (i, eval) in evals.iter_mut().enumerate().rev() {
+                            acc += row_sum(i);
+                            *eval = acc;
+                        }
+                    }
+                }
+                (
+                    Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
+                        evals, domain.d1,
+                    ),
+                    acc,
+                )
+            };
+
+            let (lookup_aggregation_poly_d1, lookup_aggregation_evals_d8, lookup_aggregation) =
+                match aggregation_mode {
+                    LookupAggregationMode::Committed => {
+                        // Check that the accumulator ends up being zero. The
+                        // verifier checks it with the constraints built by
+                        // [constraint_lookups].
+                        if incoming.is_none() && !claimed_sum.is_zero() {
+                            return Err(unbalanced_lookups(
+                                &lookups,
+                                lookup_terms_evals_d1.iter().map(|evals| &evals.evals[..]),
+                            ));
+                        }
+                        let poly = interpolate(&lookup_aggregation_evals_d1);
+                        let evals_d8 = evaluate_d8(&poly);
+                        let comm = commit(&lookup_aggregation_evals_d1);
+                        absorb_commitment(fq_sponge, &comm);
+                        // The boundary is bound to the running sum
+                        if let Some(incoming) = incoming {
+                            fq_sponge.absorb_fr(&[incoming, claimed_sum]);
+                        }
+                        (
+                            Some(poly),
+                            Some(evals_d8),
+                            LookupAggregation::Committed(comm),
+                        )
+                    }
+                    LookupAggregationMode::Claimed => {
+                        // The sum is checked outside of the proof
+                        fq_sponge.absorb_fr(&[claimed_sum]);
+                        (None, None, LookupAggregation::Claimed(claimed_sum))
+                    }
+                };
+            if absorption == LookupAbsorptionOrder::AggregationFirst {
+                absorb_columns(fq_sponge);
+            }
+
+            // The grand products of the plookup engine
+            let challenges = LookupChallenges {
+                joint_combiner: vector_lookup_combiner,
+                beta,
+                gamma: gamma.unwrap_or_else(G::ScalarField::zero),
+            };
+            let plookup_second_phase_columns = plookup_columns(PlookupEngine.second_phase(
+                &plookups,
+                &plookup_first_phase,
+                &challenges,
+                domain_size,
+            )?);
+            plookup_second_phase_columns
+                .iter()
+                .for_each(|(_, _, _, comm)| absorb_commitment(fq_sponge, comm));
+            let mut plookup_evals_d8 = vec![];
+            let mut plookup_poly_d1 = vec![];
+            let mut plookup_comms_d1 = vec![];
+            for (column, evals, poly, comm) in plookup_first_phase_columns
+                .into_iter()
+                .chain(plookup_second_phase_columns)
+            {
+                plookup_evals_d8.push((column, evals));
+                plookup_poly_d1.push((column, poly));
+                plookup_comms_d1.push((column, comm));
+            }
+            let missing_plookup_column =
+                ProverError::Internal("a column of the plookup engine is missing");
+
+            Ok(Self {
+                lookup_counters_poly_d1,
+                lookup_counters_comm_d1,
+
+                lookup_terms_poly_d1,
+                lookup_terms_comms_d1,
+
+                lookup_aggregation_poly_d1,
+                lookup_aggregation,
+
+                lookup_counters_evals_d8,
+                lookup_terms_evals_d8,
+                lookup_aggregation_evals_d8,
+
+                fixed_lookup_tables_poly_d1,
+                fixed_lookup_tables_comms_d1,
+                fixed_lookup_tables_evals_d8,
+
+                joint_combiner: vector_lookup_combiner,
+                beta,
+                accumulator: incoming.map(|incoming| AccumulatorBoundary {
+                    incoming,
+                    outgoing: claimed_sum,
+                }),
+
+                plookup_poly_d1: PlookupColumns::by_table(plookup_poly_d1)
+                    .ok_or(missing_plookup_column.clone())?,
+                plookup_comms_d1: PlookupColumns::by_table(plookup_comms_d1)
+                    .ok_or(missing_plookup_column.clone())?,
+                plookup_evals_d8: PlookupColumns::by_table(plookup_evals_d8)
+                    .ok_or(missing_plookup_column)?,
+                gamma,
+            })
+        }
+    }
+
+    /// Serialization helper for [Env]. The evaluations over d8 are omitted,
+    /// and recomputed when loading the environment.
+    #[serde_as]
+    #[derive(Serialize, Deserialize)]
+    #[serde(bound = "G: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+    struct SerializableEnv<G: KimchiCurve> {
+        /// The size of d8, to check the domain given when loading
+        d8_size: u64,
+        #[serde_as(as = "Vec<(_, SerdeAs)>")]
+        lookup_counters_poly_d1: Vec<(u32, DensePolynomial<G::ScalarField>)>,
+        lookup_counters_comm_d1: Vec<(u32, PolyComm<G>)>,
+        #[serde_as(as = "Vec<SerdeAs>")]
+        lookup_terms_poly_d1: Vec<DensePolynomial<G::ScalarField>>,
+        lookup_terms_comms_d1: Vec<PolyComm<G>>,
+        #[serde_as(as = "Option<SerdeAs>")]
+        lookup_aggregation_poly_d1: Option<DensePolynomial<G::ScalarField>>,
+        lookup_aggregation: LookupAggregation<PolyComm<G>, G::ScalarField>,
+        #[serde_as(as = "Vec<(_, SerdeAs)>")]
+        fixed_lookup_tables_poly_d1: Vec<(u32, DensePolynomial<G::ScalarField>)>,
+        fixed_lookup_tables_comms_d1: Vec<(u32, PolyComm<G>)>,
+        #[serde_as(as = "SerdeAs")]
+        joint_combiner: G::ScalarField,
+        #[serde_as(as = "SerdeAs")]
+        beta: G::ScalarField,
+        #[serde(default)]
+        accumulator: Option<AccumulatorBoundary<G::ScalarField>>,
+        #[serde(default)]
+        #[serde_as(as = "Vec<(_, Vec<SerdeAs>)>")]
+        plookup_poly_d1: Vec<(u32, Vec<DensePolynomial<G::ScalarField>>)>,
+        #[serde(default)]
+        plookup_comms_d1: Vec<(u32, PlookupColumns<PolyComm<G>>)>,
+        #[serde(default)]
+        #[serde_as(as = "Option<SerdeAs>")]
+        gamma: Option<G::ScalarField>,
+    }
+
+    impl<G: KimchiCurve, ID: LookupTableID> Env<G, ID> {
+        /// Encode the environment, with the digest of its content, see
+        /// [crate::checkpoint]. The evaluations over d8 are not part of the
+        /// encoding.
+        pub fn to_bytes(&self) -> Result<Vec<u8>, CheckpointError> {
+            fn by_u32<ID: LookupTableID, T: Clone>(map: &BTreeMap<ID, T>) -> Vec<(u32, T)> {
+                map.iter().map(|(id, v)| (id.to_u32(), v.clone())).collect()
+            }
+            // There is at least one partial sum, or a table of the plookup
+            // engine
+            let d8_size = self
+                .lookup_terms_evals_d8
+                .first()
+                .or_else(|| Some(&self.plookup_evals_d8.values().next()?.table))
+                .ok_or_else(|| CheckpointError::Encoding("the environment has no column".into()))?
+                .domain()
+                .size;
+            checkpoint::seal(&SerializableEnv::<G> {
+                d8_size,
+                lookup_counters_poly_d1: by_u32(&self.lookup_counters_poly_d1),
+                lookup_counters_comm_d1: by_u32(&self.lookup_counters_comm_d1),
+                lookup_terms_poly_d1: self.lookup_terms_poly_d1.clone(),
+                lookup_terms_comms_d1: self.lookup_terms_comms_d1.clone(),
+                lookup_aggregation_poly_d1: self.lookup_aggregation_poly_d1.clone(),
+                lookup_aggregation: self.lookup_aggregation.clone(),
+                fixed_lookup_tables_poly_d1: by_u32(&self.fixed_lookup_tables_poly_d1),
+                fixed_lookup_tables_comms_d1: by_u32(&self.fixed_lookup_tables_comms_d1),
+                joint_combiner: self.joint_combiner,
+                beta: self.beta,
+                accumulator: self.accumulator,
+                plookup_poly_d1: self
+                    .plookup_poly_d1
+                    .iter()
+                    .map(|(id, polys)| (id.to_u32(), polys.clone().into_vec()))
+                    .collect(),
+                plookup_comms_d1: by_u32(&self.plookup_comms_d1),
+                gamma: self.gamma,
+            })
+        }
+
+        /// Decode an environment encoded with [Self::to_bytes] over `domain`,
+        /// recomputing the evaluations over d8.
+        pub fn from_bytes(
+            bytes: &[u8],
+            domain: EvaluationDomains<G::ScalarField>,
+        ) -> Result<Self, CheckpointError> {
+            let env: SerializableEnv<G> = checkpoint::unseal(bytes)?;
+            if env.d8_size != domain.d8.size {
+                return Err(CheckpointError::DomainMismatch {
+                    expected: env.d8_size / 8,
+                    got: domain.d1.size,
+                });
+            }
+            fn by_id<ID: LookupTableID, T>(entries: Vec<(u32, T)>) -> BTreeMap<ID, T> {
+                entries
+                    .into_iter()
+                    .map(|(id, v)| (ID::from_u32(id), v))
+                    .collect()
+            }
+            let evaluate_d8 =
+                |poly: &DensePolynomial<G::ScalarField>| evaluate_over_coset(poly, domain.d8);
+            let lookup_counters_poly_d1: BTreeMap<ID, _> = by_id(env.lookup_counters_poly_d1);
+            let fixed_lookup_tables_poly_d1: BTreeMap<ID, _> =
+                by_id(env.fixed_lookup_tables_poly_d1);
+            let plookup_poly_d1: BTreeMap<ID, PlookupColumns<_>> = by_id(env.plookup_poly_d1)
+                .into_iter()
+                .map(|(id, polys)| Some((id, PlookupColumns::from_vec(polys)?)))
+                .collect::<Option<_>>()
+                .ok_or_else(|| {
+                    CheckpointError::Encoding("a column of the plookup engine is missing".into())
+                })?;
+            Ok(Env {
+                lookup_counters_evals_d8: (&lookup_counters_poly_d1)
+                    .into_par_iter()
+                    .map(|(id, poly)| (*id, evaluate_d8(poly)))
+                    .collect(),
+                lookup_terms_evals_d8: (&env.lookup_terms_poly_d1)
+                    .into_par_iter()
+                    .map(evaluate_d8)
+                    .collect(),
+                lookup_aggregation_evals_d8: env
+                    .lookup_aggregation_poly_d1
+                    .as_ref()
+                    .map(evaluate_d8),
+                fixed_lookup_tables_evals_d8: (&fixed_lookup_tables_poly_d1)
+                    .into_par_iter()
+                    .map(|(id, poly)| (*id, evaluate_d8(poly)))
+                    .collect(),
+                lookup_counters_poly_d1,
+                lookup_counters_comm_d1: by_id(env.lookup_counters_comm_d1),
+                lookup_terms_poly_d1: env.lookup_terms_poly_d1,
+                lookup_terms_comms_d1: env.lookup_terms_comms_d1,
+                lookup_aggregation_poly_d1: env.lookup_aggregation_poly_d1,
+                lookup_aggregation: env.lookup_aggregation,
+                fixed_lookup_tables_poly_d1,
+                fixed_lookup_tables_comms_d1: by_id(env.fixed_lookup_tables_comms_d1),
+                joint_combiner: env.joint_combiner,
+                beta: env.beta,
+                accumulator: env.accumulator,
+                plookup_evals_d8: plookup_poly_d1
+                    .iter()
+                    .map(|(id, polys)| (*id, polys.as_ref().map(evaluate_d8)))
+                    .collect(),
+                plookup_poly_d1,
+                plookup_comms_d1: by_id(env.plookup_comms_d1),
+                gamma: env.gamma,
+            })
+        }
+
+        /// Save the environment in the file `path`, see [Self::to_bytes].
+        pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+            std::fs::write(path, self.to_bytes()?)?;
+            Ok(())
+        }
+
+        /// Load an environment saved with [Self::save] over `domain`.
+        pub fn load(
+            path: impl AsRef<Path>,
+            domain: EvaluationDomains<G::ScalarField>,
+        ) -> Result<Self, CheckpointError> {
+            Self::from_bytes(&std::fs::read(path)?, domain)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_chain, chunk_padding, constraint_lookups, constraint_lookups_with_config,
+        fixed_table_read_outside_lookups, generate_global_tables, pad_lookups,
+        prover::{combined_value_collisions, partial_sums, Env},
+        verify_table_id_impl, AggregationDirection, GlobalTableCommitments, LookupAbsorptionOrder,
+        LookupAggregationMode, LookupCoalescing, LookupConstraintOptions, LookupGrouping,
+        LookupLayout, LookupPacking, LookupTable, LookupTableID, MVLookup, MVLookupWitness,
+        MultiplicityError, PackedLookup, PackingError, TableIdImplError, TablePolicy,
+        VectorCombiner, MAX_TABLE_ID,
+    };
+    use crate::{
+        columns::Column,
+        config::{ConfigError, Profile, ProtocolConfig},
+        cost::CircuitShape,
+        expr::{column_accesses, curr_cell, E},
+        lookups::{LookupTableIDs, LookupWitness},
+        perf::PerfCounters,
+        proof::{Proof, ProofInputs},
+        prover::{
+            prove, prove_with_context, prove_with_context_and_accumulator, ProverContext,
+            ProverError, ProverWarning,
+        },
+        verifier::{verify, VerifierContext, VerifierError},
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, PrimeField, UniformRand, Zero};
+    use kimchi::{
+        circuits::{
+            domains::EvaluationDomains,
+            expr::{ConstantExpr, ConstantTerm, ExprInner},
+        },
+        curve::KimchiCurve,
+        proof::PointEvaluations,
+    };
+    use mina_poseidon::FqSponge;
+    use poly_commitment::pairing_proof::PairingSRS;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    const N: usize = 2;
+
+    fn setup(domain_size: usize) -> (EvaluationDomains<Fp>, PairingSRS<BN254>) {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let x = Fp::rand(&mut rng);
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(x, domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        (domain, srs)
+    }
+
+    fn range_table(table_size: u64) -> LookupTable<Fp, LookupTableIDs> {
+        LookupTable {
+            table_id: LookupTableIDs::Custom(1),
+            entries: (0..table_size).map(|v| vec![Fp::from(v)]).collect(),
+        }
+    }
+
+    /// The constraints and the inputs of random lookups of the witness
+    /// columns into [range_table], see [crate::test::random_lookup_circuit]
+    fn inputs(
+        domain: EvaluationDomains<Fp>,
+        table_size: u64,
+    ) -> (Vec<E<Fp>>, ProofInputs<N, BN254G1Affine, LookupTableIDs>) {
+        let mut rng = o1_utils::tests::make_test_rng();
+        crate::test::random_lookup_circuit(domain, 1, table_size, N, &mut rng)
+    }
+
+    #[test]
+    fn test_global_tables_serialization_roundtrip() {
+        let (domain, srs) = setup(1 << 4);
+        let global_tables =
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[range_table(8)]).unwrap();
+        let bytes = rmp_serde::to_vec(&global_tables).unwrap();
+        let deserialized: GlobalTableCommitments<BN254G1Affine, LookupTableIDs> =
+            rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(global_tables, deserialized);
+    }
+
+    #[test]
+    fn test_global_tables_completeness() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let global_tables =
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[range_table(16)]).unwrap();
+        let (constraints, inputs) = inputs(domain, 16);
+
+        let ctx = ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+            .unwrap()
+            .with_global_tables(global_tables);
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+
+        // The fixed tables are omitted from the proof
+        assert!(proof
+            .proof_comms
+            .mvlookup_comms
+            .as_ref()
+            .unwrap()
+            .fixed_tables
+            .is_empty());
+
+        let res = VerifierContext::from_prover_context(&ctx)
+            .unwrap()
+            .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size),
+            );
+        assert_eq!(res, Ok(()));
+    }
+
+    #[test]
+    fn test_global_tables_drift_is_caught_by_the_prover() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        // The published table has one entry different from the one used by
+        // the witness.
+        let mut table = range_table(16);
+        table.entries[3] = vec![Fp::from(42u64)];
+        let global_tables =
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[table]).unwrap();
+        let (constraints, inputs) = inputs(domain, 16);
+
+        let ctx = ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+            .unwrap()
+            .with_global_tables(global_tables);
+        let res = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng);
+        let table_id = LookupTableIDs::Custom(1).to_u32();
+        assert!(matches!(res, Err(ProverError::GlobalTableMismatch(id)) if id == table_id));
+    }
+
+    /// A table of 16 values, whose multiplicities are bounded by the table
+    /// `[0, 64)`.
+    #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    enum BoundedTableIDs {
+        Small,
+        Bound,
+    }
+
+    impl LookupTableID for BoundedTableIDs {
+        fn to_u32(&self) -> u32 {
+            match self {
+                BoundedTableIDs::Small => 1,
+                BoundedTableIDs::Bound => 2,
+            }
+        }
+
+        fn from_u32(value: u32) -> Self {
+            Self::try_from_u32(value).expect("Invalid table ID")
+        }
+
+        fn try_from_u32(value: u32) -> Option<Self> {
+            match value {
+                1 => Some(BoundedTableIDs::Small),
+                2 => Some(BoundedTableIDs::Bound),
+                _ => None,
+            }
+        }
+
+        fn is_fixed(&self) -> bool {
+            true
+        }
+
+        fn length(&self) -> usize {
+            match self {
+                BoundedTableIDs::Small => 16,
+                BoundedTableIDs::Bound => 64,
+            }
+        }
+
+        fn multiplicity_bound(&self) -> Option<Self> {
+            match self {
+                BoundedTableIDs::Small => Some(BoundedTableIDs::Bound),
+                BoundedTableIDs::Bound => None,
+            }
+        }
+    }
+
+    /// The lookups of the values of `columns` into the table
+    /// [BoundedTableIDs::Small].
+    fn small_table_witness(columns: &[Vec<Fp>]) -> MVLookupWitness<Fp, BoundedTableIDs> {
+        let table_id = BoundedTableIDs::Small;
+        let domain_size = columns[0].len();
+        let mut m = vec![Fp::zero(); domain_size];
+        let mut f: Vec<Vec<MVLookup<Fp, BoundedTableIDs>>> = columns
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|x| {
+                        m[x.into_repr().as_ref()[0] as usize] += Fp::one();
+                        MVLookup::new(table_id, Fp::one(), &[*x])
+                    })
+                    .collect()
+            })
+            .collect();
+        let table = (0..domain_size)
+            .map(|i| {
+                let value = if i < table_id.length() { i as u64 } else { 0 };
+                MVLookup::new(table_id, -m[i], &[Fp::from(value)])
+            })
+            .collect();
+        f.push(table);
+        MVLookupWitness { f, m }
+    }
+
+    fn bounded_inputs(columns: [Vec<Fp>; N]) -> ProofInputs<N, BN254G1Affine, BoundedTableIDs> {
+        let mvlookups = vec![small_table_witness(&columns)];
+        ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(columns),
+            },
+            mvlookups,
+        }
+    }
+
+    fn bounded_constraints() -> Vec<E<Fp>> {
+        let table_id = BoundedTableIDs::Small;
+        let lookups = (0..N)
+            .map(|i| MVLookup::new(table_id, E::one(), &[curr_cell(Column::X(i))]))
+            .collect();
+        constraint_lookups(&BTreeMap::from([(table_id, lookups)]))
+    }
+
+    #[test]
+    fn test_multiplicity_bound_completeness() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        // Each value is looked up 32 times
+        let columns: [Vec<Fp>; N] = std::array::from_fn(|j| {
+            (0..domain_size)
+                .map(|i| Fp::from(((i + j) % 16) as u64))
+                .collect()
         });
-    });
+        let inputs = bounded_inputs(columns);
+        assert_eq!(inputs.mvlookups[0].check_multiplicity_bound(), Ok(()));
 
-    // Generic code over the partial sum
-    // Compute φ(ωX) - φ(X) - \sum_{i = 1}^{N} h_i(X)
-    {
-        let constraint =
-            next_cell(Column::LookupAggregation) - curr_cell(Column::LookupAggregation);
-        let constraint = (0..idx_partial_sum).fold(constraint, |acc, i| {
-            acc - curr_cell(Column::LookupPartialSum(i))
+        let constraints = bounded_constraints();
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, BoundedTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+        // The multiplicities of the small table are committed, and range
+        // checked into the bounding table
+        let mvlookup_comms = proof.proof_comms.mvlookup_comms.as_ref().unwrap();
+        assert_eq!(mvlookup_comms.m.len(), 2);
+
+        let verifies = verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, BoundedTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        );
+        assert!(verifies);
+    }
+
+    #[test]
+    fn test_multiplicity_bound_merging() {
+        let domain_size = 1 << 8;
+        let columns: [Vec<Fp>; N] = std::array::from_fn(|j| {
+            (0..domain_size)
+                .map(|i| Fp::from(((i + j) % 16) as u64))
+                .collect()
         });
-        constraints.push(constraint);
+        let witness = small_table_witness(&columns);
+        // 32 + 32 is not smaller than 64
+        let res = witness.clone().combine(witness);
+        assert_eq!(
+            res.unwrap_err(),
+            MultiplicityError::BoundExceeded {
+                table_id: BoundedTableIDs::Small.to_u32(),
+                row: 0,
+                bound: 64,
+            }
+        );
+
+        // With half of the lookups, the multiplicities are 16 + 16
+        let witness = small_table_witness(&columns[..1]);
+        let merged = witness.clone().combine(witness).unwrap();
+        assert_eq!(merged.f.len(), 3);
+        assert!(merged.m[..16].iter().all(|m| *m == Fp::from(32u64)));
     }
-    constraints
-}
 
-pub mod prover {
-    use crate::{
-        mvlookup::{LookupTableID, MVLookup, MVLookupWitness},
-        MAX_SUPPORTED_DEGREE,
-    };
-    use ark_ff::{FftField, Zero};
-    use ark_poly::{univariate::DensePolynomial, Evaluations, Radix2EvaluationDomain as D};
-    use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
-    use mina_poseidon::FqSponge;
-    use poly_commitment::{
-        commitment::{absorb_commitment, PolyComm},
-        OpenProof, SRS as _,
-    };
-    use rayon::iter::{IntoParallelIterator, ParallelIterator};
-    use std::collections::BTreeMap;
+    #[test]
+    fn test_multiplicity_bound_is_constrained() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        // The value zero is looked up 512 times
+        let columns: [Vec<Fp>; N] = std::array::from_fn(|_| vec![Fp::zero(); domain_size]);
+        let inputs = bounded_inputs(columns);
+        assert!(inputs.mvlookups[0].check_multiplicity_bound().is_err());
+        // The multiplicity can not be found in the bounding table
+        let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, BoundedTableIDs>(
+            domain,
+            &srs,
+            &bounded_constraints(),
+            inputs,
+            &mut rng,
+        );
+        assert!(matches!(
+            res,
+            Err(ProverError::LookupAggregationNonZero { .. })
+        ));
+    }
+
+    /// A wrong multiplicity unbalances the lookups of its table, which is
+    /// named by the error of the prover
+    #[test]
+    fn test_wrong_multiplicity() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let (constraints, mut inputs) = inputs(domain, 16);
+        let witness = &mut inputs.mvlookups[0];
+        witness.m[3] += Fp::one();
+        witness.f.last_mut().unwrap()[3].numerator = -witness.m[3];
+        let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        );
+        assert!(matches!(
+            res,
+            Err(ProverError::LookupAggregationNonZero { table_id, residual })
+                if table_id == LookupTableIDs::Custom(1).to_u32() && residual != "0"
+        ));
+    }
+
+    /// The witness of the lookups of `columns` into the table `Custom(id)`,
+    /// which contains the values `[0, table_size)`.
+    fn range_table_witness(
+        id: u32,
+        table_size: usize,
+        columns: &[Vec<Fp>],
+    ) -> MVLookupWitness<Fp, LookupTableIDs> {
+        let table_id = LookupTableIDs::Custom(id);
+        let domain_size = columns[0].len();
+        let mut m = vec![Fp::zero(); domain_size];
+        let mut f: Vec<Vec<MVLookup<Fp, LookupTableIDs>>> = columns
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|x| {
+                        m[x.into_repr().as_ref()[0] as usize] += Fp::one();
+                        MVLookup::new(table_id, Fp::one(), &[*x])
+                    })
+                    .collect()
+            })
+            .collect();
+        let table = (0..domain_size)
+            .map(|i| {
+                let value = if i < table_size { i as u64 } else { 0 };
+                MVLookup::new(table_id, -m[i], &[Fp::from(value)])
+            })
+            .collect();
+        f.push(table);
+        MVLookupWitness { f, m }
+    }
+
+    /// Both columns are looked up in `Custom(1)`, of size 16, and `Custom(2)`,
+    /// of size 32.
+    fn dual_table_lookups() -> BTreeMap<LookupTableIDs, Vec<MVLookup<E<Fp>, LookupTableIDs>>> {
+        (1..=2)
+            .map(|id| {
+                let table_id = LookupTableIDs::Custom(id);
+                let lookups = (0..N)
+                    .map(|i| MVLookup::new(table_id, E::one(), &[curr_cell(Column::X(i))]))
+                    .collect();
+                (table_id, lookups)
+            })
+            .collect()
+    }
+
+    fn dual_table_inputs(domain_size: usize) -> ProofInputs<N, BN254G1Affine, LookupTableIDs> {
+        let columns: [Vec<Fp>; N] = std::array::from_fn(|j| {
+            (0..domain_size)
+                .map(|i| Fp::from(((3 * i + j) % 16) as u64))
+                .collect()
+        });
+        ProofInputs {
+            mvlookups: vec![
+                range_table_witness(1, 16, &columns),
+                range_table_witness(2, 32, &columns),
+            ],
+            evaluations: Witness {
+                cols: Box::new(columns),
+            },
+        }
+    }
+
+    #[test]
+    fn test_lookup_packing_completeness() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let lookups = dual_table_lookups();
+
+        let packing = LookupPacking::detect(&lookups);
+        let custom = LookupTableIDs::Custom;
+        assert_eq!(
+            packing.packed,
+            (0..N)
+                .map(|i| PackedLookup {
+                    table_id: custom(2),
+                    index: i,
+                    twin_table_id: custom(1),
+                    twin_index: i,
+                })
+                .collect::<Vec<_>>()
+        );
+
+        let mut inputs = dual_table_inputs(domain_size);
+        inputs.mvlookups = packing.apply_to_witness(inputs.mvlookups).unwrap();
+        // The lookups of the second table are placed after their twin
+        let table_ids: Vec<Vec<LookupTableIDs>> = inputs
+            .mvlookups
+            .iter()
+            .map(|lookup| lookup.f.iter().map(|f_i| f_i[0].table_id).collect())
+            .collect();
+        assert_eq!(
+            table_ids,
+            vec![
+                vec![custom(1), custom(2), custom(1), custom(2), custom(1)],
+                vec![custom(2)]
+            ]
+        );
+
+        let constraints = constraint_lookups(&lookups);
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+        let verifies = verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        );
+        assert!(verifies);
+    }
+
+    #[test]
+    fn test_lookup_packing_is_equivalent_to_unpacked_lookups() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let lookups = dual_table_lookups();
+        let packing = LookupPacking::detect(&lookups);
+
+        // The unoptimized path
+        let unpacked_constraints = constraint_lookups_with_config(
+            &lookups,
+            &LookupConstraintOptions {
+                packing: Some(&LookupPacking::default()),
+                ..Default::default()
+            },
+        );
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &unpacked_constraints,
+                dual_table_inputs(domain_size),
+                &mut rng,
+            )
+            .unwrap();
+        let verifies = verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+            domain,
+            &srs,
+            &unpacked_constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        );
+        assert!(verifies);
+
+        // The sum of the partial sums on each row, hence the running sum, is
+        // the same with and without packing
+        let env = |mvlookups| {
+            let mut fq_sponge = BaseSponge::new(BN254G1Affine::other_curve_sponge_params());
+            Env::<BN254G1Affine, LookupTableIDs>::create::<OpeningProof, BaseSponge>(
+                mvlookups,
+                domain,
+                &mut fq_sponge,
+                &srs,
+                LookupAggregationMode::Committed,
+                AggregationDirection::Forward,
+                LookupAbsorptionOrder::ColumnsFirst,
+                None,
+                &PerfCounters::default(),
+                None,
+            )
+            .unwrap()
+        };
+        let unpacked_env = env(dual_table_inputs(domain_size).mvlookups);
+        let packed_env = env(packing
+            .apply_to_witness(dual_table_inputs(domain_size).mvlookups)
+            .unwrap());
+        assert_eq!(unpacked_env.beta, packed_env.beta);
+        assert_eq!(
+            unpacked_env.lookup_aggregation_poly_d1,
+            packed_env.lookup_aggregation_poly_d1
+        );
+        assert_eq!(
+            unpacked_env.lookup_counters_poly_d1,
+            packed_env.lookup_counters_poly_d1
+        );
+        assert_ne!(
+            unpacked_env.lookup_terms_poly_d1,
+            packed_env.lookup_terms_poly_d1
+        );
+    }
+
+    /// The columns are looked up alternately in `Custom(1)`, i.e. two
+    /// denominators for eleven lookups, in two chunks.
+    const DUPLICATED_LOOKUPS: [usize; 11] = [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0];
+
+    fn duplicated_lookups() -> BTreeMap<LookupTableIDs, Vec<MVLookup<E<Fp>, LookupTableIDs>>> {
+        let table_id = LookupTableIDs::Custom(1);
+        let lookups = DUPLICATED_LOOKUPS
+            .iter()
+            .map(|i| MVLookup::new(table_id, E::one(), &[curr_cell(Column::X(*i))]))
+            .collect();
+        BTreeMap::from([(table_id, lookups)])
+    }
+
+    fn duplicated_inputs(domain_size: usize) -> ProofInputs<N, BN254G1Affine, LookupTableIDs> {
+        let inputs = dual_table_inputs(domain_size);
+        let columns: Vec<Vec<Fp>> = DUPLICATED_LOOKUPS
+            .iter()
+            .map(|i| inputs.evaluations.cols[*i].clone())
+            .collect();
+        ProofInputs {
+            mvlookups: vec![range_table_witness(1, 16, &columns)],
+            evaluations: inputs.evaluations,
+        }
+    }
+
+    #[test]
+    fn test_lookup_grouping() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let lookups = duplicated_lookups();
+        let table_id = LookupTableIDs::Custom(1);
+
+        // The six lookups of X(0) fill the first chunk, and the five lookups
+        // of X(1) are with the table
+        let grouping = LookupGrouping::detect(&lookups);
+        assert_eq!(
+            grouping.order[&table_id],
+            vec![0, 2, 4, 6, 8, 10, 1, 3, 5, 7, 9]
+        );
+        let grouped_lookups = grouping.apply(&lookups);
+        assert_eq!(
+            LookupLayout::new(&grouped_lookups),
+            LookupLayout::new(&lookups)
+        );
+
+        // The denominators shared in a chunk are a factor once
+        let constraints = constraint_lookups(&lookups);
+        let grouped_constraints = constraint_lookups_with_config(
+            &lookups,
+            &LookupConstraintOptions {
+                grouping: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(constraints.len(), grouped_constraints.len());
+        assert_eq!(
+            LookupLayout::of_constraints(&grouped_constraints),
+            Some(LookupLayout::new(&lookups))
+        );
+        let degree = |constraint: &E<Fp>| constraint.degree(1, 0);
+        assert_eq!(degree(&constraints[0]), 7);
+        assert_eq!(degree(&grouped_constraints[0]), 2);
+        assert_eq!(degree(&constraints[1]), 7);
+        assert_eq!(degree(&grouped_constraints[1]), 3);
+
+        // The proofs verify with and without the grouping, with the witness
+        // in the order of the constraints
+        let mut grouped_inputs = duplicated_inputs(domain_size);
+        grouped_inputs.mvlookups = grouping.apply_to_witness(grouped_inputs.mvlookups).unwrap();
+        for (constraints, inputs) in [
+            (&constraints, duplicated_inputs(domain_size)),
+            (&grouped_constraints, grouped_inputs),
+        ] {
+            let proof =
+                prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                    domain,
+                    &srs,
+                    constraints,
+                    inputs,
+                    &mut rng,
+                )
+                .unwrap();
+            let verifies = verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+                domain,
+                &srs,
+                constraints,
+                &proof,
+                Witness::zero_vec(domain_size),
+            );
+            assert!(verifies);
+        }
+
+        // The witness of the grouped lookups is not accepted with the
+        // constraints in the original order
+        let mut grouped_inputs = duplicated_inputs(domain_size);
+        grouped_inputs.mvlookups = grouping.apply_to_witness(grouped_inputs.mvlookups).unwrap();
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            grouped_inputs,
+            &mut rng,
+        );
+        assert!(proof.is_err());
+
+        // The witness must have the lookups of the grouping
+        let witness =
+            range_table_witness(1, 16, &duplicated_inputs(domain_size).evaluations.cols[..]);
+        assert_eq!(
+            grouping.apply_to_witness(vec![witness]).unwrap_err(),
+            PackingError::LookupCountMismatch(table_id.to_u32())
+        );
+        assert_eq!(
+            grouping.apply_to_witness::<Fp>(vec![]).unwrap_err(),
+            PackingError::MissingTable(table_id.to_u32())
+        );
+    }
+
+    #[test]
+    fn test_lookup_grouping_shares_the_denominators() {
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let grouping = LookupGrouping::detect(&duplicated_lookups());
+
+        let env = |mvlookups, perf: &PerfCounters| {
+            let mut fq_sponge = BaseSponge::new(BN254G1Affine::other_curve_sponge_params());
+            Env::<BN254G1Affine, LookupTableIDs>::create::<OpeningProof, BaseSponge>(
+                mvlookups,
+                domain,
+                &mut fq_sponge,
+                &srs,
+                LookupAggregationMode::Committed,
+                AggregationDirection::Forward,
+                LookupAbsorptionOrder::ColumnsFirst,
+                None,
+                perf,
+                None,
+            )
+            .unwrap()
+        };
+        let perf = PerfCounters::default();
+        let ungrouped_env = env(duplicated_inputs(domain_size).mvlookups, &perf);
+        let ungrouped_stats = perf.stats();
+        let perf = PerfCounters::default();
+        let grouped_env = env(
+            grouping
+                .apply_to_witness(duplicated_inputs(domain_size).mvlookups)
+                .unwrap(),
+            &perf,
+        );
+        let grouped_stats = perf.stats();
+
+        // The running sum is the same, only the partial sums differ
+        assert_eq!(
+            ungrouped_env.lookup_aggregation_poly_d1,
+            grouped_env.lookup_aggregation_poly_d1
+        );
+        assert_ne!(
+            ungrouped_env.lookup_terms_poly_d1,
+            grouped_env.lookup_terms_poly_d1
+        );
+
+        // The adjacent lookups of the same value in a chunk share their
+        // inversion. On each row, X(0) and X(1) differ, and the table is
+        // inverted unless it equals the last lookup. There is one batch
+        // inversion per chunk.
+        if cfg!(feature = "perf-counters") {
+            let inputs = duplicated_inputs(domain_size);
+            let table_inversions = |last: usize| {
+                (0..domain_size)
+                    .filter(|i| {
+                        let table = Fp::from(if *i < 16 { *i as u64 } else { 0 });
+                        inputs.evaluations.cols[last][*i] != table
+                    })
+                    .count()
+            };
+            assert_eq!(ungrouped_stats.batch_inversions, 2);
+            assert_eq!(
+                ungrouped_stats.field_inversions,
+                11 * domain_size + table_inversions(0)
+            );
+            assert_eq!(grouped_stats.batch_inversions, 2);
+            assert_eq!(
+                grouped_stats.field_inversions,
+                2 * domain_size + table_inversions(1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_partial_sums_match_the_sequential_computation() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, _) = setup(domain_size);
+        let beta = Fp::rand(&mut rng);
+        let joint_combiner = Fp::rand(&mut rng);
+        // A witness of 8 chunks whose adjacent lookups are often equal,
+        // including across the chunks, witnesses of one and two chunks, and
+        // a witness whose last chunk is not full
+        let mut lookups = vec![
+            LookupWitness::random_with_table(domain, 0, 2, 47),
+            LookupWitness::random_with_table(domain, 1, 64, 5),
+            duplicated_inputs(domain_size).mvlookups.remove(0),
+            LookupWitness::random(domain),
+        ];
+        lookups.push(MVLookupWitness {
+            f: lookups[0].f[..13].to_vec(),
+            m: vec![],
+        });
+
+        let perf = PerfCounters::default();
+        let (partial_sums, fixed_tables) =
+            partial_sums(&lookups, beta, joint_combiner, domain_size, &perf);
+        let (expected_partial_sums, expected_fixed_tables) =
+            super::reference::partial_sums(&lookups, beta, joint_combiner, domain_size);
+        assert_eq!(partial_sums.len(), 8 + 1 + 2 + 1 + 3);
+        assert_eq!(partial_sums, expected_partial_sums);
+        assert_eq!(fixed_tables, expected_fixed_tables);
+        // The tables shared by several witnesses are in the witness order
+        assert_eq!(
+            fixed_tables[&LookupTableIDs::Custom(1)].len(),
+            2 * domain_size
+        );
+        // The partial sums of each row are the ones of the reference
+        for j in [0, 17, domain_size - 1] {
+            let row: Vec<_> = lookups[0].f.iter().map(|f_i| f_i[j].clone()).collect();
+            assert_eq!(
+                super::reference::row_partial_sums(beta, joint_combiner, &row),
+                partial_sums[..8].iter().map(|h| h[j]).collect::<Vec<_>>()
+            );
+        }
+        if cfg!(feature = "perf-counters") {
+            assert_eq!(perf.stats().batch_inversions, partial_sums.len());
+        }
+    }
+
+    #[test]
+    fn test_constant_one_numerators() {
+        use crate::{equivalence::constraints_equivalent, simplify::node_count};
+        use kimchi::circuits::expr::{ConstantExpr, ConstantTerm, ExprInner, Operations};
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        let table_id = LookupTableIDs::Custom(0);
+        let lookups = |numerator: E<Fp>| {
+            BTreeMap::from([(
+                table_id,
+                (0..5)
+                    .map(|i| MVLookup::new(table_id, numerator.clone(), &[curr_cell(Column::X(i))]))
+                    .collect::<Vec<_>>(),
+            )])
+        };
+        // The numerator one, but not as a literal, taking the general path
+        let one = ConstantExpr::from(ConstantTerm::Literal(Fp::one()));
+        let general_one = E::Atom(ExprInner::Constant(Operations::Mul(
+            Box::new(one.clone()),
+            Box::new(one),
+        )));
+        let constraints = constraint_lookups(&lookups(E::one()));
+        let general = constraint_lookups(&lookups(general_one));
+        assert!(constraints_equivalent(&constraints, &general, 20, &mut rng).is_equivalent());
+        // The factors one are elided, one per lookup of the partial sums
+        let nodes: usize = constraints.iter().map(node_count).sum();
+        let general_nodes: usize = general.iter().map(node_count).sum();
+        assert_eq!(general_nodes - nodes, 5 * 4);
+        for (constraint, general) in constraints.iter().zip(general.iter()) {
+            assert_eq!(constraint.degree(1, 0), general.degree(1, 0));
+        }
+
+        // The prover skips the multiplications by the numerator one, of the
+        // looked-up values, and multiplies by the multiplicities of the table
+        let domain_size = 1 << 8;
+        let (domain, _) = setup(domain_size);
+        let beta = Fp::rand(&mut rng);
+        let joint_combiner = Fp::rand(&mut rng);
+        let n_queries = 5;
+        let witness = vec![LookupWitness::random_with_table(domain, 0, 64, n_queries)];
+        let perf = PerfCounters::default();
+        let (partial_sums, _) = partial_sums(&witness, beta, joint_combiner, domain_size, &perf);
+        let (expected_partial_sums, _) =
+            super::reference::partial_sums(&witness, beta, joint_combiner, domain_size);
+        assert_eq!(partial_sums, expected_partial_sums);
+        if cfg!(feature = "perf-counters") {
+            let stats = perf.stats();
+            assert_eq!(stats.skipped_numerator_muls, n_queries * domain_size);
+            assert_eq!(stats.numerator_muls, domain_size);
+        }
+    }
+
+    #[test]
+    fn test_lookup_coalescing() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let lookups = duplicated_lookups();
+        let table_id = LookupTableIDs::Custom(1);
+
+        // The six lookups of X(0) and the five lookups of X(1) are coalesced
+        let coalescing = LookupCoalescing::detect(&lookups);
+        assert_eq!(
+            coalescing.groups[&table_id],
+            vec![vec![0, 2, 4, 6, 8, 10], vec![1, 3, 5, 7, 9]]
+        );
+        assert_eq!(coalescing.n_coalesced(table_id), 9);
+        assert!(!coalescing.is_trivial());
+        assert!(LookupCoalescing::detect(&dual_table_lookups()).is_trivial());
+        let coalesced_lookups = coalescing.apply(&lookups);
+        assert_eq!(coalesced_lookups[&table_id].len(), 2);
+        assert_eq!(coalesced_lookups[&table_id][0].numerator, E::from(6));
+        assert_eq!(coalesced_lookups[&table_id][1].numerator, E::from(5));
+
+        // The 12 terms of the table need two partial sums, and the three
+        // terms of the coalesced lookups only one
+        let constraints = constraint_lookups(&lookups);
+        let coalesced_constraints = constraint_lookups_with_config(
+            &lookups,
+            &LookupConstraintOptions {
+                coalescing: true,
+                ..Default::default()
+            },
+        );
+        let layout = LookupLayout::<LookupTableIDs>::of_constraints(&constraints).unwrap();
+        let coalesced_layout =
+            LookupLayout::<LookupTableIDs>::of_constraints(&coalesced_constraints).unwrap();
+        assert_eq!(layout.n_partial_sums(), 2);
+        assert_eq!(coalesced_layout.n_partial_sums(), 1);
+        assert_eq!(coalesced_layout, LookupLayout::new(&coalesced_lookups));
+
+        // The proofs verify with and without the coalescing. The
+        // multiplicities of the table are unchanged.
+        let inputs = duplicated_inputs(domain_size);
+        let mut coalesced_inputs = duplicated_inputs(domain_size);
+        coalesced_inputs.mvlookups = coalescing
+            .apply_to_witness(coalesced_inputs.mvlookups)
+            .unwrap();
+        assert_eq!(coalesced_inputs.mvlookups[0].f.len(), 3);
+        assert_eq!(coalesced_inputs.mvlookups[0].m, inputs.mvlookups[0].m);
+        for (constraints, inputs) in [
+            (&constraints, inputs),
+            (&coalesced_constraints, coalesced_inputs),
+        ] {
+            let proof =
+                prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                    domain,
+                    &srs,
+                    constraints,
+                    inputs,
+                    &mut rng,
+                )
+                .unwrap();
+            let verifies = verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+                domain,
+                &srs,
+                constraints,
+                &proof,
+                Witness::zero_vec(domain_size),
+            );
+            assert!(verifies);
+        }
+
+        // The witness must have the lookups of the coalescing
+        let witness =
+            range_table_witness(1, 16, &duplicated_inputs(domain_size).evaluations.cols[..]);
+        assert_eq!(
+            coalescing.apply_to_witness(vec![witness]).unwrap_err(),
+            PackingError::LookupCountMismatch(table_id.to_u32())
+        );
+        assert_eq!(
+            coalescing.apply_to_witness::<Fp>(vec![]).unwrap_err(),
+            PackingError::MissingTable(table_id.to_u32())
+        );
+    }
+
+    #[test]
+    fn test_proof_against_circuit_with_extra_table() {
+        use crate::{
+            columns::{PartialSumIdx, TableIdx},
+            verifier::try_verify,
+        };
+        use kimchi::circuits::expr::{ColumnEvaluations, ExprError};
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let custom = LookupTableIDs::Custom;
+
+        // The previous version of the circuit only uses the first table
+        let lookups = dual_table_lookups();
+        let old_lookups = BTreeMap::from([(custom(1), lookups[&custom(1)].clone())]);
+        let mut inputs = dual_table_inputs(domain_size);
+        inputs.mvlookups.truncate(1);
+        let old_constraints = constraint_lookups(&old_lookups);
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &old_constraints,
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+
+        let constraints = constraint_lookups(&lookups);
+        let res = try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            VerifierError::LookupTableSetMismatch {
+                columns: "multiplicity commitments",
+                missing: vec![custom(2).to_u32()],
+                unexpected: vec![],
+            }
+        );
+
+        // The evaluations of the missing columns are errors
+        let evals = &proof.proof_evals;
+        let table_idx = TableIdx::new(custom(2).to_u32());
+        for column in [
+            Column::LookupMultiplicity(table_idx),
+            Column::LookupFixedTable(table_idx),
+        ] {
+            assert!(matches!(
+                evals.evaluate(column),
+                Err(ExprError::MissingLookupTable(id)) if id == custom(2).to_u32()
+            ));
+        }
+        assert!(matches!(
+            evals.evaluate(Column::LookupPartialSum(PartialSumIdx::new(10))),
+            Err(ExprError::MissingEvaluation(..))
+        ));
+    }
+
+    /// A proof for the tables {1, 3}, verified against a circuit with the
+    /// tables {1, 2}: the multiplicities of the table 3 are not read by the
+    /// constraints, and the ones of the table 2 are missing
+    #[test]
+    fn test_proof_against_circuit_with_other_table() {
+        use crate::verifier::try_verify;
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let custom = LookupTableIDs::Custom;
+
+        let lookups = dual_table_lookups();
+        let mut other_lookups = lookups.clone();
+        let table_lookups = other_lookups.remove(&custom(2)).unwrap();
+        other_lookups.insert(
+            custom(3),
+            table_lookups
+                .into_iter()
+                .map(|lookup| MVLookup {
+                    table_id: custom(3),
+                    ..lookup
+                })
+                .collect(),
+        );
+        let mut inputs = dual_table_inputs(domain_size);
+        inputs.mvlookups[1] = range_table_witness(3, 32, &inputs.evaluations.cols[..]);
+        let packing = LookupPacking::detect(&other_lookups);
+        inputs.mvlookups = packing.apply_to_witness(inputs.mvlookups).unwrap();
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraint_lookups(&other_lookups),
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+        let lookup_evals = proof.proof_evals.mvlookup_evals.as_ref().unwrap();
+        assert_eq!(
+            lookup_evals.m.keys().collect::<Vec<_>>(),
+            vec![&custom(1), &custom(3)]
+        );
+
+        let res = try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraint_lookups(&lookups),
+            &proof,
+            Witness::zero_vec(domain_size),
+        );
+        let err = res.unwrap_err();
+        assert_eq!(
+            err,
+            VerifierError::LookupTableSetMismatch {
+                columns: "multiplicity commitments",
+                missing: vec![custom(2).to_u32()],
+                unexpected: vec![custom(3).to_u32()],
+            }
+        );
+        assert!(err.to_string().ends_with(&format!(
+            "missing [{}], unexpected [{}]",
+            custom(2).to_u32(),
+            custom(3).to_u32()
+        )));
+    }
+
+    #[test]
+    fn test_lookup_layout_hands_out_the_lookup_columns() {
+        let lookups = dual_table_lookups();
+        let layout = LookupLayout::new(&lookups);
+        let custom = LookupTableIDs::Custom;
+        assert_eq!(
+            layout.table_ids().collect::<Vec<_>>(),
+            vec![custom(1), custom(2)]
+        );
+        assert_eq!(layout.n_partial_sums(), 2);
+
+        // The constraints only use the columns of the layout
+        let columns: BTreeSet<Column> = constraint_lookups(&lookups)
+            .iter()
+            .flat_map(column_accesses)
+            .map(|(col, _)| col)
+            .filter(|col| !matches!(col, Column::X(_)))
+            .collect();
+        let mut expected: BTreeSet<Column> = layout.partial_sums().into_iter().collect();
+        for id in layout.table_ids() {
+            expected.insert(layout.multiplicity(id).unwrap());
+            expected.insert(layout.fixed_table(id).unwrap());
+        }
+        expected.insert(layout.aggregation());
+        assert_eq!(columns, expected);
+
+        // The columns out of the layout can not be obtained
+        assert_eq!(layout.partial_sum(1), layout.partial_sums().last().copied());
+        assert_eq!(layout.partial_sum(2), None);
+        assert_eq!(layout.multiplicity(custom(3)), None);
+        assert_eq!(layout.fixed_table(custom(3)), None);
+    }
+
+    #[test]
+    fn test_lookup_partial_sums_by_table() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let lookups = dual_table_lookups();
+        // Without packing, each table has its own partial sums
+        let packing = LookupPacking::default();
+        let layout = LookupLayout::with_packing(&lookups, &packing);
+        let constraints = constraint_lookups_with_config(
+            &lookups,
+            &LookupConstraintOptions {
+                packing: Some(&packing),
+                ..Default::default()
+            },
+        );
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                dual_table_inputs(domain_size),
+                &mut rng,
+            )
+            .unwrap();
+
+        let custom = LookupTableIDs::Custom;
+        let evals = proof.proof_evals.mvlookup_evals.as_ref().unwrap();
+        let comms = proof.proof_comms.mvlookup_comms.as_ref().unwrap();
+        assert_eq!(
+            evals.tables().collect::<Vec<_>>(),
+            vec![&custom(1), &custom(2)]
+        );
+        assert_eq!(
+            comms.tables().collect::<Vec<_>>(),
+            layout
+                .table_ids()
+                .collect::<Vec<_>>()
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        let mut i = 0;
+        for table_id in layout.table_ids() {
+            let mut chunk = 0;
+            while let Some(eval) = proof
+                .proof_evals
+                .lookup_partial_sum(table_id, chunk, &layout)
+            {
+                assert_eq!(eval.zeta, evals.h[i].zeta);
+                assert_eq!(eval.zeta_omega, evals.h[i].zeta_omega);
+                assert_eq!(
+                    proof
+                        .proof_comms
+                        .lookup_partial_sum_comm(table_id, chunk, &layout),
+                    Some(&comms.h[i])
+                );
+                assert_eq!(layout.partial_sum_index(table_id, chunk), Some(i));
+                chunk += 1;
+                i += 1;
+            }
+            assert!(chunk > 0);
+        }
+        assert_eq!(i, evals.h.len());
+
+        // Unknown tables have no partial sums
+        assert!(proof
+            .proof_evals
+            .lookup_partial_sum(custom(3), 0, &layout)
+            .is_none());
+        assert_eq!(
+            proof
+                .proof_comms
+                .lookup_partial_sum_comm(custom(3), 0, &layout),
+            None
+        );
+    }
+
+    #[test]
+    fn test_lookup_aggregation_modes() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let lookups = dual_table_lookups();
+        let packing = LookupPacking::detect(&lookups);
+        let inputs = || {
+            let mut inputs = dual_table_inputs(domain_size);
+            inputs.mvlookups = packing.apply_to_witness(inputs.mvlookups).unwrap();
+            inputs
+        };
+
+        let mut proofs = vec![];
+        for mode in [
+            LookupAggregationMode::Committed,
+            LookupAggregationMode::Claimed,
+        ] {
+            let config = ProtocolConfig {
+                lookup_aggregation: mode,
+                ..Default::default()
+            };
+            let constraints = constraint_lookups_with_config(
+                &lookups,
+                &LookupConstraintOptions {
+                    aggregation: mode,
+                    ..Default::default()
+                },
+            );
+            let ctx = ProverContext::new(domain, &srs, config, &constraints).unwrap();
+            let proof = prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs(), &mut rng)
+            .unwrap();
+            let res = VerifierContext::from_prover_context(&ctx)
+                .unwrap()
+                .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                    &proof,
+                    Witness::zero_vec(domain_size),
+                );
+            assert_eq!(res, Ok(()), "{mode:?}");
+            assert_eq!(
+                proof
+                    .proof_comms
+                    .mvlookup_comms
+                    .as_ref()
+                    .unwrap()
+                    .aggregation_mode(),
+                mode
+            );
+            proofs.push((constraints, proof));
+        }
+        let [(committed_constraints, committed_proof), (claimed_constraints, claimed_proof)]: [_;
+            2] = proofs.try_into().unwrap();
+
+        // The aggregation constraint is only given in the committed mode, and
+        // the lookups are satisfied, hence the claimed sum is zero.
+        assert_eq!(committed_constraints.len(), claimed_constraints.len() + 1);
+        assert_eq!(committed_proof.claimed_lookup_sum(), None);
+        assert_eq!(claimed_proof.claimed_lookup_sum(), Some(Fp::zero()));
+        assert_eq!(
+            (&committed_proof.proof_comms.mvlookup_comms.unwrap())
+                .into_iter()
+                .count(),
+            (&claimed_proof.proof_comms.mvlookup_comms.clone().unwrap())
+                .into_iter()
+                .count()
+                + 1
+        );
+
+        // The mode of the proof must be the one of the configuration
+        let res = VerifierContext::<_, OpeningProof, _>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &claimed_constraints,
+        )
+        .and_then(|ctx| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, _>(
+                &claimed_proof,
+                Witness::zero_vec(domain_size),
+            )
+        });
+        assert_eq!(
+            res,
+            Err(VerifierError::Config(
+                ConfigError::LookupAggregationMismatch
+            ))
+        );
+        let claimed_config = ProtocolConfig {
+            lookup_aggregation: LookupAggregationMode::Claimed,
+            ..Default::default()
+        };
+        assert_eq!(
+            claimed_config
+                .compile_constraints(committed_constraints.clone())
+                .unwrap_err(),
+            ConfigError::AggregationNotCommitted(committed_constraints.len() - 1)
+        );
+    }
+
+    #[test]
+    fn test_fixed_table_read_outside_lookups() {
+        let lookups = dual_table_lookups();
+        let layout = LookupLayout::new(&lookups);
+        let config = ProtocolConfig::default();
+
+        // The constraints of the lookup argument read the fixed tables
+        for constraints in [
+            constraint_lookups(&lookups),
+            constraint_lookups_with_config(
+                &lookups,
+                &LookupConstraintOptions {
+                    grouping: true,
+                    ..Default::default()
+                },
+            ),
+            constraint_lookups_with_config(
+                &lookups,
+                &LookupConstraintOptions {
+                    coalescing: true,
+                    ..Default::default()
+                },
+            ),
+            constraint_lookups_with_config(
+                &lookups,
+                &LookupConstraintOptions {
+                    aggregation: LookupAggregationMode::Claimed,
+                    ..Default::default()
+                },
+            ),
+        ] {
+            assert!(config.compile_constraints(constraints).is_ok());
+        }
+
+        // A user constraint reading the table 4 is rejected
+        let table = layout.fixed_table(LookupTableIDs::Custom(1)).unwrap();
+        let user_constraint = curr_cell::<Fp>(Column::X(0)) - curr_cell(table);
+        let mut constraints = constraint_lookups(&lookups);
+        constraints.insert(0, user_constraint.clone());
+        let err = config.compile_constraints(constraints.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError::FixedTableRead {
+                constraint: 0,
+                table_id: 4
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "the constraint 0 reads the column of the fixed table 4, which is internal to the \
+             lookup argument; add the constraint to ProtocolConfig::fixed_table_readers if this \
+             is intended"
+        );
+        // Even if it also reads a partial sum, with another table
+        let partial_sum = layout.partial_sum(0).unwrap();
+        let other_table = layout.fixed_table(LookupTableIDs::Custom(2)).unwrap();
+        assert_eq!(
+            fixed_table_read_outside_lookups(
+                &(user_constraint.clone() * curr_cell(partial_sum) - curr_cell(other_table))
+            ),
+            Some(4)
+        );
+
+        // Unless the constraint is allowed to
+        let config = ProtocolConfig {
+            fixed_table_readers: BTreeSet::from([0]),
+            ..ProtocolConfig::default()
+        };
+        assert!(config.compile_constraints(constraints).is_ok());
+    }
+
+    #[test]
+    fn test_combined_value_collisions() {
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let joint_combiner = Fp::rand(&mut o1_utils::tests::make_test_rng());
+        let mut witness = LookupWitness::<Fp>::random_with_table(domain, 1, 16, 2);
+        // The padding of the table is not reported
+        assert!(combined_value_collisions(&[witness.clone()], joint_combiner).is_empty());
+
+        let table = witness.f.last_mut().unwrap();
+        for i in [20, 21, 22, 23] {
+            table[i].numerator = -Fp::one();
+        }
+        // A duplicated row, and a row with a trailing zero
+        table[20].value = vec![Fp::from(3u64)];
+        table[21].value = vec![Fp::from(5u64), Fp::zero()];
+        // Two distinct rows which collide
+        table[22].value = vec![Fp::zero(), Fp::one()];
+        table[23].value = vec![joint_combiner];
+        for i in [3, 5] {
+            // Looked up at least once
+            table[i].numerator = -Fp::one();
+        }
+
+        let table_id = LookupTableIDs::Custom(1).to_u32();
+        let warnings = combined_value_collisions(&[witness], joint_combiner);
+        assert_eq!(
+            warnings,
+            vec![
+                (vec![3, 20], true),
+                (vec![5, 21], true),
+                (vec![22, 23], false)
+            ]
+            .into_iter()
+            .map(|(rows, structural)| ProverWarning::CombinedValueCollision {
+                table_id,
+                rows,
+                structural,
+            })
+            .collect::<Vec<_>>()
+        );
+    }
+
+    /// An implementation of [LookupTableID] with the bug `BUG`
+    #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    struct BrokenTableID<const BUG: u8>(u32);
+
+    impl<const BUG: u8> LookupTableID for BrokenTableID<BUG> {
+        fn to_u32(&self) -> u32 {
+            match BUG {
+                1 => self.0 % 2,
+                _ => self.0,
+            }
+        }
+
+        fn from_u32(value: u32) -> Self {
+            match BUG {
+                0 => BrokenTableID(value + 1),
+                _ => BrokenTableID(value),
+            }
+        }
+
+        fn to_field<F: ark_ff::Field>(&self) -> F {
+            match BUG {
+                2 => F::from(self.0 + 1),
+                _ => F::from(self.0),
+            }
+        }
+
+        fn to_constraint<F: ark_ff::Field>(&self) -> E<F> {
+            let f = match BUG {
+                4 => F::zero(),
+                _ => self.to_field(),
+            };
+            E::Atom(ExprInner::Constant(ConstantExpr::from(
+                ConstantTerm::Literal(f),
+            )))
+        }
+
+        fn is_fixed(&self) -> bool {
+            true
+        }
+
+        fn length(&self) -> usize {
+            match BUG {
+                3 => 0,
+                _ => 16,
+            }
+        }
+    }
+
+    crate::lookup_table_id! {
+        enum DerivedTableIDs {
+            Small = fixed(4),
+            Large = fixed(1 << 16),
+            Memory = ram,
+        }
+    }
+
+    #[test]
+    fn test_verify_table_id_impl() {
+        assert_eq!(
+            verify_table_id_impl(&[BrokenTableID::<0>(1)]),
+            Err(TableIdImplError::RoundTrip { id: 1, got: 2 })
+        );
+        assert_eq!(
+            verify_table_id_impl(&[BrokenTableID::<1>(1), BrokenTableID::<1>(3)]),
+            Err(TableIdImplError::NotInjective(1))
+        );
+        assert_eq!(
+            verify_table_id_impl(&[BrokenTableID::<2>(1)]),
+            Err(TableIdImplError::FieldMismatch(1))
+        );
+        assert_eq!(
+            verify_table_id_impl(&[BrokenTableID::<3>(1)]),
+            Err(TableIdImplError::EmptyFixedTable(1))
+        );
+        assert_eq!(
+            verify_table_id_impl(&[BrokenTableID::<4>(1)]),
+            Err(TableIdImplError::FieldMismatch(1))
+        );
+        assert_eq!(
+            verify_table_id_impl(&[BrokenTableID::<5>(1), BrokenTableID::<5>(MAX_TABLE_ID)]),
+            Err(TableIdImplError::IdTooLarge(MAX_TABLE_ID))
+        );
+        assert_eq!(
+            verify_table_id_impl(&[BrokenTableID::<5>(MAX_TABLE_ID - 1)]),
+            Ok(())
+        );
+
+        let ids = [
+            DerivedTableIDs::Small,
+            DerivedTableIDs::Large,
+            DerivedTableIDs::Memory,
+        ];
+        assert_eq!(verify_table_id_impl(&ids), Ok(()));
+        assert_eq!(
+            ids.map(|id| id.to_u32()),
+            [0, 1, 2],
+            "the tables are encoded with their index"
+        );
+        assert_eq!(ids.map(|id| id.is_fixed()), [true, true, false]);
+        assert_eq!(DerivedTableIDs::Small.length(), 4);
+
+        // The tables are checked when they are registered
+        let (domain, srs) = setup(1 << 4);
+        let table = |id| LookupTable {
+            table_id: BrokenTableID::<1>(id),
+            entries: vec![vec![Fp::zero()]],
+        };
+        assert_eq!(
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[table(0), table(2)])
+                .unwrap_err(),
+            TableIdImplError::NotInjective(0)
+        );
+        let empty_table = LookupTable {
+            table_id: DerivedTableIDs::Small,
+            entries: vec![],
+        };
+        assert_eq!(
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[empty_table]).unwrap_err(),
+            TableIdImplError::EmptyFixedTable(0)
+        );
+        let large_table = LookupTable {
+            table_id: BrokenTableID::<5>(u32::MAX),
+            entries: vec![vec![Fp::zero()]],
+        };
+        assert_eq!(
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[large_table]).unwrap_err(),
+            TableIdImplError::IdTooLarge(u32::MAX)
+        );
+    }
+
+    const NIBBLE_SBOX: [[u64; 2]; 16] = [
+        [0, 12],
+        [1, 5],
+        [2, 6],
+        [3, 11],
+        [4, 9],
+        [5, 0],
+        [6, 10],
+        [7, 13],
+        [8, 3],
+        [9, 14],
+        [10, 15],
+        [11, 8],
+        [12, 4],
+        [13, 7],
+        [14, 1],
+        [15, 2],
+    ];
+
+    crate::define_lookup_tables! {
+        enum MacroTables {
+            SBox = 3 => entries(NIBBLE_SBOX),
+            Double = 5 => generate(32, |i| [i, 2 * i]),
+        }
+    }
+
+    #[test]
+    fn test_define_lookup_tables() {
+        const N: usize = 4;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 6;
+        let (domain, srs) = setup(domain_size);
+        assert_eq!(verify_table_id_impl(MacroTables::ALL), Ok(()));
+        assert_eq!(
+            MacroTables::ALL
+                .iter()
+                .map(|id| id.to_u32())
+                .collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+        assert_eq!(MacroTables::SBox.length(), 16);
+        assert_eq!(MacroTables::Double.length(), 32);
+        assert_eq!(
+            MacroTables::Double.entries::<Fp>()[7],
+            vec![Fp::from(7u64), Fp::from(14u64)]
+        );
+
+        let tables = MacroTables::tables::<Fp>();
+        let global_tables =
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &tables).unwrap();
+        // Each row looks up a random entry of each table, the entries of the
+        // table `k` being the columns `2k` and `2k + 1`
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let lookups = tables
+            .iter()
+            .enumerate()
+            .map(|(k, table)| {
+                let lookup = MVLookup::new(table.table_id, E::one(), &[x(2 * k), x(2 * k + 1)]);
+                (table.table_id, vec![lookup])
+            })
+            .collect();
+        let constraints = constraint_lookups(&lookups);
+        let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+        let mvlookups = tables
+            .iter()
+            .enumerate()
+            .map(|(col, table)| {
+                let mut m = vec![Fp::zero(); domain_size];
+                let f = (0..domain_size)
+                    .map(|_| {
+                        let k = rand::Rng::gen_range(&mut rng, 0..table.entries.len());
+                        m[k] += Fp::one();
+                        cols[2 * col].push(table.entries[k][0]);
+                        cols[2 * col + 1].push(table.entries[k][1]);
+                        MVLookup::new(table.table_id, Fp::one(), &table.entries[k])
+                    })
+                    .collect();
+                let t = (0..domain_size)
+                    .map(|i| {
+                        let entry = table.entries.get(i).cloned().unwrap_or(vec![Fp::zero(); 2]);
+                        MVLookup::new(table.table_id, -m[i], &entry)
+                    })
+                    .collect();
+                MVLookupWitness { f: vec![f, t], m }
+            })
+            .collect();
+        let inputs: ProofInputs<N, BN254G1Affine, MacroTables> = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups,
+        };
+
+        let ctx = ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+            .unwrap()
+            .with_global_tables(global_tables);
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            MacroTables,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+        let res = VerifierContext::from_prover_context(&ctx)
+            .unwrap()
+            .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size),
+            );
+        assert_eq!(res, Ok(()));
+    }
+
+    #[test]
+    fn test_active_tables() {
+        const N: usize = 4;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 6;
+        let (domain, srs) = setup(domain_size);
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let lookups = BTreeMap::from([
+            (
+                MacroTables::SBox,
+                vec![MVLookup::new(MacroTables::SBox, E::one(), &[x(0), x(1)])],
+            ),
+            (
+                MacroTables::Double,
+                vec![MVLookup::new(MacroTables::Double, E::one(), &[x(2), x(3)])],
+            ),
+        ]);
+
+        // Each row looks up a random entry of each table
+        let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+        let mvlookups: Vec<_> = [(MacroTables::SBox, 0), (MacroTables::Double, 2)]
+            .into_iter()
+            .map(|(table_id, col)| {
+                let entries = table_id.entries::<Fp>();
+                let mut m = vec![Fp::zero(); domain_size];
+                let f = (0..domain_size)
+                    .map(|_| {
+                        let k = rand::Rng::gen_range(&mut rng, 0..entries.len());
+                        m[k] += Fp::one();
+                        cols[col].push(entries[k][0]);
+                        cols[col + 1].push(entries[k][1]);
+                        MVLookup::new(table_id, Fp::one(), &entries[k])
+                    })
+                    .collect();
+                let t = (0..domain_size)
+                    .map(|i| {
+                        let entry = entries.get(i).unwrap_or(&entries[0]);
+                        MVLookup::new(table_id, -m[i], entry)
+                    })
+                    .collect();
+                MVLookupWitness { f: vec![f, t], m }
+            })
+            .collect();
+        let inputs = |active: &[MacroTables]| -> ProofInputs<N, BN254G1Affine, MacroTables> {
+            ProofInputs {
+                evaluations: Witness {
+                    cols: Box::new(cols.clone()),
+                },
+                mvlookups: mvlookups
+                    .iter()
+                    .filter(|witness| active.contains(&witness.table_id().unwrap()))
+                    .cloned()
+                    .collect(),
+            }
+        };
 
-    pub struct QuotientPolynomialEnvironment<'a, F: FftField, ID: LookupTableID> {
-        pub lookup_terms_evals_d8: &'a Vec<Evaluations<F, D<F>>>,
-        pub lookup_aggregation_evals_d8: &'a Evaluations<F, D<F>>,
-        pub lookup_counters_evals_d8: &'a BTreeMap<ID, Evaluations<F, D<F>>>,
-        pub fixed_tables_evals_d8: &'a BTreeMap<ID, Evaluations<F, D<F>>>,
-    }
+        let policy = TablePolicy::new()
+            .mandatory(MacroTables::SBox)
+            .optional(MacroTables::Double);
+        // The constraints are the ones of the lookups into the active tables
+        let mut prove = |policy: &TablePolicy<MacroTables>, active: &[MacroTables]| {
+            let constraints = policy
+                .compile_constraints(&[], &lookups, &active.iter().copied().collect())
+                .map_err(ProverError::Config)?;
+            let ctx =
+                ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)?;
+            prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                MacroTables,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs(active), &mut rng)
+        };
+        let verify = |policy: &TablePolicy<MacroTables>, proof: &Proof<N, _, _, _>| {
+            let constraints = policy
+                .compile_constraints(&[], &lookups, &proof.active_tables())
+                .map_err(VerifierError::Config)?;
+            VerifierContext::<_, OpeningProof, _>::new(
+                domain,
+                &srs,
+                ProtocolConfig::unsimplified(),
+                &constraints,
+            )?
+            .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                proof,
+                Witness::zero_vec(domain_size),
+            )
+        };
 
-    pub struct Env<G: KimchiCurve, ID: LookupTableID> {
-        pub lookup_counters_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>>,
-        pub lookup_counters_comm_d1: BTreeMap<ID, PolyComm<G>>,
+        let full_proof = prove(&policy, MacroTables::ALL).unwrap();
+        assert_eq!(verify(&policy, &full_proof), Ok(()));
 
-        pub lookup_terms_poly_d1: Vec<DensePolynomial<G::ScalarField>>,
-        pub lookup_terms_comms_d1: Vec<PolyComm<G>>,
+        // The optional table is dropped, with its columns
+        let proof = prove(&policy, &[MacroTables::SBox]).unwrap();
+        assert_eq!(proof.active_tables(), BTreeSet::from([MacroTables::SBox]));
+        assert!(
+            rmp_serde::to_vec(&proof).unwrap().len()
+                < rmp_serde::to_vec(&full_proof).unwrap().len()
+        );
+        assert_eq!(verify(&policy, &proof), Ok(()));
 
-        pub lookup_aggregation_poly_d1: DensePolynomial<G::ScalarField>,
-        pub lookup_aggregation_comm_d1: PolyComm<G>,
+        // The mandatory table can not be dropped, by the prover
+        let inactive = ConfigError::MandatoryTableInactive(MacroTables::SBox.to_u32());
+        assert!(matches!(
+            prove(&policy, &[MacroTables::Double]),
+            Err(ProverError::Config(err)) if err == inactive
+        ));
+        // nor by a proof created with a more permissive policy
+        let permissive_policy = TablePolicy::new()
+            .optional(MacroTables::SBox)
+            .optional(MacroTables::Double);
+        let proof = prove(&permissive_policy, &[MacroTables::Double]).unwrap();
+        assert_eq!(verify(&permissive_policy, &proof), Ok(()));
+        assert_eq!(
+            verify(&policy, &proof),
+            Err(VerifierError::Config(inactive))
+        );
 
-        // Evaluating over d8 for the quotient polynomial
-        pub lookup_counters_evals_d8: BTreeMap<ID, Evaluations<G::ScalarField, D<G::ScalarField>>>,
-        pub lookup_terms_evals_d8: Vec<Evaluations<G::ScalarField, D<G::ScalarField>>>,
-        pub lookup_aggregation_evals_d8: Evaluations<G::ScalarField, D<G::ScalarField>>,
+        // The tables must be registered
+        let proof = prove(&permissive_policy, MacroTables::ALL).unwrap();
+        let partial_policy = TablePolicy::new().mandatory(MacroTables::SBox);
+        assert_eq!(
+            verify(&partial_policy, &proof),
+            Err(VerifierError::Config(ConfigError::UnregisteredTable(
+                MacroTables::Double.to_u32()
+            )))
+        );
+    }
 
-        pub fixed_lookup_tables_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>>,
-        pub fixed_lookup_tables_comms_d1: BTreeMap<ID, PolyComm<G>>,
-        pub fixed_lookup_tables_evals_d8:
-            BTreeMap<ID, Evaluations<G::ScalarField, D<G::ScalarField>>>,
+    #[test]
+    fn test_chunk_padding() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 6;
+        let (domain, srs) = setup(domain_size);
+        let chunk_size = crate::MAX_SUPPORTED_DEGREE - 2;
+        assert_eq!(chunk_padding(0), 0);
+        assert_eq!(chunk_padding(chunk_size - 1), 0);
+        assert_eq!(chunk_padding(chunk_size), 1);
+        assert_eq!(chunk_padding(chunk_size + 1), 0);
+        assert_eq!(chunk_padding(2 * chunk_size), 1);
 
-        /// The combiner used for vector lookups
-        pub joint_combiner: G::ScalarField,
+        // Each row looks up the same entry of the table in each lookup, the
+        // last partial sum only holding the term of the table
+        let n_lookups = chunk_size;
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let lookups = BTreeMap::from([(
+            MacroTables::SBox,
+            vec![MVLookup::new(MacroTables::SBox, E::one(), &[x(0), x(1)]); n_lookups],
+        )]);
+        let entries = MacroTables::SBox.entries::<Fp>();
+        let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+        let mut m = vec![Fp::zero(); domain_size];
+        let f: Vec<_> = (0..domain_size)
+            .map(|_| {
+                let k = rand::Rng::gen_range(&mut rng, 0..entries.len());
+                m[k] += Fp::from(n_lookups as u64);
+                cols[0].push(entries[k][0]);
+                cols[1].push(entries[k][1]);
+                MVLookup::new(MacroTables::SBox, Fp::one(), &entries[k])
+            })
+            .collect();
+        let t = (0..domain_size)
+            .map(|i| {
+                let entry = entries.get(i).unwrap_or(&entries[0]);
+                MVLookup::new(MacroTables::SBox, -m[i], entry)
+            })
+            .collect();
+        let mut f = vec![f; n_lookups];
+        f.push(t);
+        let witness = MVLookupWitness { f, m };
 
-        /// The evaluation point used for the lookup polynomials.
-        pub beta: G::ScalarField,
-    }
+        let shape = |lookups: &BTreeMap<MacroTables, Vec<MVLookup<E<Fp>, MacroTables>>>| {
+            let n_lookups = lookups
+                .iter()
+                .map(|(id, lookups)| (id.to_u32(), lookups.len()))
+                .collect();
+            CircuitShape::new(
+                domain_size as u64,
+                N,
+                0,
+                &constraint_lookups(lookups),
+                n_lookups,
+            )
+        };
+        let underfull = shape(&lookups).underfull_chunks();
+        assert_eq!(underfull.len(), 1);
+        assert_eq!(underfull[0].table_id, MacroTables::SBox.to_u32());
 
-    impl<G: KimchiCurve, ID: LookupTableID> Env<G, ID> {
-        /// Create an environment for the prover to create a proof for the MVLookup protocol.
-        /// The protocol does suppose that the individual lookup terms are
-        /// committed as part of the columns.
-        /// Therefore, the protocol only focus on commiting to the "grand
-        /// product sum" and the "row-accumulated" values.
-        pub fn create<
-            OpeningProof: OpenProof<G>,
-            Sponge: FqSponge<G::BaseField, G, G::ScalarField>,
-        >(
-            lookups: Vec<MVLookupWitness<G::ScalarField, ID>>,
-            domain: EvaluationDomains<G::ScalarField>,
-            fq_sponge: &mut Sponge,
-            srs: &OpeningProof::SRS,
-        ) -> Self
-        where
-            OpeningProof::SRS: Sync,
-        {
-            // Polynomial m(X)
-            // FIXME/IMPROVEME: m(X) is only for fixed table
-            let lookup_counters_evals_d1: BTreeMap<
-                ID,
-                Evaluations<G::ScalarField, D<G::ScalarField>>,
-            > = {
-                (&lookups)
-                    .into_par_iter()
-                    .filter(|lookup| {
-                        // FIXME: this is ugly.
-                        // Does not handle RAMLookup
-                        let table_id = lookup.f[0][0].table_id;
-                        table_id.is_fixed()
-                    })
-                    .map(|lookup| {
-                        let table_id = lookup.f[0][0].table_id;
-                        (
-                            table_id,
-                            Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
-                                lookup.m.to_vec(),
-                                domain.d1,
-                            ),
-                        )
-                    })
-                    .collect()
+        // The padded circuit is not reported, and has as many partial sums
+        let padded_lookups = pad_lookups(&lookups);
+        assert_eq!(padded_lookups[&MacroTables::SBox].len(), n_lookups + 1);
+        assert!(shape(&padded_lookups).underfull_chunks().is_empty());
+        assert_eq!(
+            shape(&padded_lookups).n_partial_sums(),
+            shape(&lookups).n_partial_sums()
+        );
+
+        // Both circuits verify, the no-op lookups adding nothing to the sums
+        for (lookups, witness) in [
+            (lookups.clone(), witness.clone()),
+            (padded_lookups, witness.pad()),
+        ] {
+            let constraints = constraint_lookups(&lookups);
+            let inputs = ProofInputs::<N, BN254G1Affine, MacroTables> {
+                evaluations: Witness {
+                    cols: Box::new(cols.clone()),
+                },
+                mvlookups: vec![witness],
             };
+            let proof =
+                prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, MacroTables>(
+                    domain,
+                    &srs,
+                    &constraints,
+                    inputs,
+                    &mut rng,
+                )
+                .unwrap();
+            let verifies = verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, MacroTables>(
+                domain,
+                &srs,
+                &constraints,
+                &proof,
+                Witness::zero_vec(domain_size),
+            );
+            assert!(verifies);
+        }
+    }
 
-            let lookup_counters_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>> =
-                (&lookup_counters_evals_d1)
-                    .into_par_iter()
-                    .map(|(id, evals)| (*id, evals.interpolate_by_ref()))
-                    .collect();
+    #[test]
+    fn test_accumulator_chain() {
+        const N: usize = 2;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 5;
+        let (domain, srs) = setup(domain_size);
+        let table_id = MacroTables::SBox;
+        let lookups = BTreeMap::from([(
+            table_id,
+            vec![MVLookup::new(
+                table_id,
+                E::one(),
+                &[curr_cell(Column::X(0)), curr_cell(Column::X(1))],
+            )],
+        )]);
+        let constraints = constraint_lookups_with_config(
+            &lookups,
+            &LookupConstraintOptions {
+                aggregation: LookupAggregationMode::Claimed,
+                ..Default::default()
+            },
+        );
+        let ctx =
+            ProverContext::new(domain, &srs, ProtocolConfig::default(), &constraints).unwrap();
+        let verifier = VerifierContext::from_prover_context(&ctx).unwrap();
 
-            let lookup_counters_evals_d8: BTreeMap<
-                ID,
-                Evaluations<G::ScalarField, D<G::ScalarField>>,
-            > = (&lookup_counters_poly_d1)
-                .into_par_iter()
-                .map(|(id, lookup)| (*id, lookup.evaluate_over_domain_by_ref(domain.d8)))
+        // Each row looks up a random entry of the table. Without the
+        // multiplicities, the lookups are only satisfied by another proof.
+        let inputs = |with_multiplicities: bool, rng: &mut rand::rngs::StdRng| {
+            let entries = table_id.entries::<Fp>();
+            let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+            let mut m = vec![Fp::zero(); domain_size];
+            let f = (0..domain_size)
+                .map(|_| {
+                    let k = rand::Rng::gen_range(rng, 0..entries.len());
+                    if with_multiplicities {
+                        m[k] += Fp::one();
+                    }
+                    cols[0].push(entries[k][0]);
+                    cols[1].push(entries[k][1]);
+                    MVLookup::new(table_id, Fp::one(), &entries[k])
+                })
                 .collect();
-
-            let lookup_counters_comm_d1: BTreeMap<ID, PolyComm<G>> = (&lookup_counters_evals_d1)
-                .into_par_iter()
-                .map(|(id, poly)| (*id, srs.commit_evaluations_non_hiding(domain.d1, poly)))
+            let t = (0..domain_size)
+                .map(|i| {
+                    let entry = entries.get(i).unwrap_or(&entries[0]);
+                    MVLookup::new(table_id, -m[i], entry)
+                })
                 .collect();
+            ProofInputs::<N, BN254G1Affine, MacroTables> {
+                evaluations: Witness {
+                    cols: Box::new(cols),
+                },
+                mvlookups: vec![MVLookupWitness { f: vec![f, t], m }],
+            }
+        };
+        let prove = |inputs, incoming, rng: &mut _| {
+            prove_with_context_and_accumulator::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                MacroTables,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs, incoming, rng)
+            .unwrap()
+        };
+        let verify = |proof: &Proof<N, BN254G1Affine, OpeningProof, MacroTables>, incoming| {
+            verifier
+                .verify_with_accumulator::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                    proof,
+                    Witness::zero_vec(domain_size),
+                    incoming,
+                )
+                .map(|boundary| (boundary.incoming, boundary.outgoing))
+        };
 
-            lookup_counters_comm_d1
-                .values()
-                .for_each(|comm| absorb_commitment(fq_sponge, comm));
-            // -- end of m(X)
+        // Three proofs, each one starting from the outgoing accumulator of
+        // the previous one
+        let mut incoming = Fp::zero();
+        let mut boundaries = vec![];
+        for _ in 0..3 {
+            let proof = prove(inputs(true, &mut rng), incoming, &mut rng);
+            let boundary = verify(&proof, incoming).unwrap();
+            incoming = boundary.1;
+            boundaries.push(boundary);
+        }
+        assert!(check_chain(&boundaries));
 
-            // -- start computing the row sums h(X)
-            // It will be used to compute the running sum in lookup_aggregation
-            // Coin a combiner to perform vector lookup.
-            // The row sums h are defined as
-            // --           n            1                    1
-            // h(ω^i) = ∑        -------------------- - --------------
-            //            j = 0    (β + f_{j}(ω^i))      (β + t(ω^i))
-            let vector_lookup_combiner = fq_sponge.challenge();
+        // A proof must start from the given incoming accumulator
+        let carry = Fp::rand(&mut rng);
+        let proof = prove(inputs(true, &mut rng), carry, &mut rng);
+        assert_eq!(
+            verify(&proof, Fp::zero()),
+            Err(VerifierError::IncomingAccumulatorMismatch)
+        );
+        // and its outgoing accumulator is bound to its running sum
+        let mut forged_proof = proof.clone();
+        forged_proof.accumulator.as_mut().unwrap().outgoing = carry + Fp::one();
+        assert!(verify(&forged_proof, carry).is_err());
 
-            // Coin an evaluation point for the rational functions
-            let beta = fq_sponge.challenge();
+        // A middle link starting from another accumulator breaks the chain
+        let middle = verify(&proof, carry).unwrap();
+        assert_eq!(middle, (carry, carry));
+        assert!(!check_chain(&[boundaries[0], middle, boundaries[2]]));
 
-            // Contain the evalations of the h_i. We divide the looked-up values
-            // in chunks of (MAX_SUPPORTED_DEGREE - 2)
-            let mut fixed_lookup_tables: BTreeMap<ID, Vec<G::ScalarField>> = BTreeMap::new();
+        // A proof with unsatisfied lookups has a non-zero outgoing
+        // accumulator, which can not end the chain
+        let proof = prove(inputs(false, &mut rng), Fp::zero(), &mut rng);
+        let last = verify(&proof, Fp::zero()).unwrap();
+        assert!(!last.1.is_zero());
+        assert!(!check_chain(&[boundaries[0], boundaries[1], last]));
 
-            let lookup_terms_evals: Vec<Vec<Vec<G::ScalarField>>> = lookups
-                .into_iter()
-                .map(|lookup| {
-                    let MVLookupWitness { f, m: _ } = lookup;
-                    // The number of functions to look up, including the fixed table.
-                    let n = f.len();
-                    let n_partial_sums = if n % (MAX_SUPPORTED_DEGREE - 2) == 0 {
-                        n / (MAX_SUPPORTED_DEGREE - 2)
-                    } else {
-                        n / (MAX_SUPPORTED_DEGREE - 2) + 1
-                    };
-                    let mut partial_sums =
-                        vec![
-                            Vec::<G::ScalarField>::with_capacity(domain.d1.size as usize);
-                            n_partial_sums
-                        ];
-
-                    // We compute first the denominators of all f_i and t. We gather them in
-                    // a vector to perform a batch inversion.
-                    let mut denominators = Vec::with_capacity(n * domain.d1.size as usize);
-                    // Iterate over the rows
-                    for j in 0..domain.d1.size {
-                        // Iterate over individual columns (i.e. f_i and t)
-                        for (i, f_i) in f.iter().enumerate() {
-                            let MVLookup {
-                                numerator: _,
-                                table_id,
-                                value,
-                            } = &f_i[j as usize];
-                            // Compute r * x_{1} + r^2 x_{2} + ... r^{N} x_{N}
-                            let combined_value: G::ScalarField =
-                                value.iter().rev().fold(G::ScalarField::zero(), |acc, y| {
-                                    acc * vector_lookup_combiner + y
-                                }) * vector_lookup_combiner;
-                            // add table id
-                            let combined_value =
-                                combined_value + table_id.to_field::<G::ScalarField>();
-
-                            // If last element and fixed lookup tables, we keep
-                            // the *combined* value of the table.
-                            if i == (n - 1) && table_id.is_fixed() {
-                                fixed_lookup_tables
-                                    .entry(*table_id)
-                                    .or_insert_with(Vec::new)
-                                    .push(combined_value);
-                            }
+        // The chain telescopes through any intermediate values
+        let (a, b) = (Fp::rand(&mut rng), Fp::rand(&mut rng));
+        assert!(check_chain(&[(Fp::zero(), a), (a, b), (b, Fp::zero())]));
+        assert!(!check_chain(&[(Fp::zero(), a), (b, Fp::zero())]));
+        assert!(!check_chain(&[(a, a)]));
+        assert!(check_chain::<Fp>(&[]));
+    }
 
-                            // β + a_{i}
-                            let lookup_denominator = beta + combined_value;
-                            denominators.push(lookup_denominator);
-                        }
-                    }
+    #[test]
+    fn test_table_term_sign() {
+        use crate::expr::{evaluate_on_assignment, Assignment};
+        use kimchi::circuits::{expr::Challenges, gate::CurrOrNext};
 
-                    ark_ff::fields::batch_inversion(&mut denominators);
-
-                    // Evals is the sum on the individual columns for each row
-                    let mut denominator_index = 0;
-
-                    // We only need to add the numerator now
-                    for j in 0..domain.d1.size {
-                        let mut partial_sum_idx = 0;
-                        let mut row_acc = G::ScalarField::zero();
-                        for f_i in f.iter() {
-                            let MVLookup {
-                                numerator,
-                                table_id: _,
-                                value: _,
-                            } = &f_i[j as usize];
-                            row_acc += *numerator * denominators[denominator_index];
-                            denominator_index += 1;
-                            // We split in chunks of (MAX_SUPPORTED_DEGREE - 2)
-                            // We reset the accumulator for the current partial
-                            // sum after keeping it.
-                            if denominator_index % (MAX_SUPPORTED_DEGREE - 2) == 0 {
-                                partial_sums[partial_sum_idx].push(row_acc);
-                                row_acc = G::ScalarField::zero();
-                                partial_sum_idx += 1;
-                            }
-                        }
-                        if denominator_index % (MAX_SUPPORTED_DEGREE - 2) != 0 {
-                            partial_sums[partial_sum_idx].push(row_acc);
-                        }
-                    }
-                    partial_sums
-                })
-                .collect::<Vec<_>>();
+        let mut rng = o1_utils::tests::make_test_rng();
+        // One row, looking up the entry (2, 1) of the table twice
+        let table_id = MacroTables::SBox;
+        let lookup = MVLookup::new(
+            table_id,
+            E::one(),
+            &[curr_cell(Column::X(0)), curr_cell(Column::X(1))],
+        );
+        let lookups = BTreeMap::from([(table_id, vec![lookup.clone(), lookup])]);
+        let layout = LookupLayout::new(&lookups);
+        let partial_sum = constraint_lookups(&lookups)[0].clone();
 
-            let lookup_terms_evals: Vec<Vec<G::ScalarField>> =
-                lookup_terms_evals.into_iter().flatten().collect();
+        let beta = Fp::rand(&mut rng);
+        let r = Fp::rand(&mut rng);
+        let f = Fp::from(3u64) + r * Fp::from(2u64) + r * r;
+        let evaluate = |m: Fp, h: Fp| {
+            let cell = |col| (col, CurrOrNext::Curr);
+            let assignment = Assignment {
+                cells: BTreeMap::from([
+                    (cell(Column::X(0)), Fp::from(2u64)),
+                    (cell(Column::X(1)), Fp::one()),
+                    (cell(layout.multiplicity(table_id).unwrap()), m),
+                    // The table column is already combined
+                    (cell(layout.fixed_table(table_id).unwrap()), f),
+                    (cell(layout.partial_sum(0).unwrap()), h),
+                ]),
+                challenges: Challenges {
+                    alpha: Fp::zero(),
+                    beta,
+                    gamma: Fp::zero(),
+                    joint_combiner: Some(r),
+                },
+                endo_coefficient: Fp::zero(),
+                mds: vec![],
+            };
+            evaluate_on_assignment(&partial_sum, &assignment).unwrap()
+        };
+        // h = 1 / (β + f) + 1 / (β + f) - 2 / (β + t), with t = f
+        assert_eq!(evaluate(Fp::from(2u64), Fp::zero()), Fp::zero());
+        // The multiplicities are subtracted: with the opposite sign, the
+        // partial sum is 4 / (β + f)
+        let h = Fp::from(4u64) / (beta + f);
+        assert_ne!(evaluate(-Fp::from(2u64), Fp::zero()), Fp::zero());
+        assert_eq!(evaluate(-Fp::from(2u64), h), Fp::zero());
+    }
 
-            // Sanity check to verify that the number of evaluations is correct
-            lookup_terms_evals
-                .iter()
-                .for_each(|evals| assert_eq!(evals.len(), domain.d1.size as usize));
+    #[test]
+    fn test_table_numerator_mismatch() {
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let mut witness = LookupWitness::random_with_table(domain, 1, 16, 2);
+        assert_eq!(witness.table_numerator_mismatch(), None);
+        witness.f.last_mut().unwrap()[5].numerator += Fp::one();
+        assert_eq!(witness.table_numerator_mismatch(), Some(5));
+    }
 
-            // Sanity check to verify that we have all the evaluations for the fixed lookup tables
-            fixed_lookup_tables
-                .values()
-                .for_each(|evals| assert_eq!(evals.len(), domain.d1.size as usize));
+    #[test]
+    fn test_aggregation_directions() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let lookups = dual_table_lookups();
+        let packing = LookupPacking::detect(&lookups);
+        let mvlookups = || {
+            packing
+                .apply_to_witness(dual_table_inputs(domain_size).mvlookups)
+                .unwrap()
+        };
 
-            let lookup_terms_evals_d1: Vec<Evaluations<G::ScalarField, D<G::ScalarField>>> =
-                lookup_terms_evals
-                    .into_par_iter()
-                    .map(|lte| {
-                        Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
-                            lte, domain.d1,
-                        )
-                    })
-                    .collect::<Vec<_>>();
+        let verify = |config: &ProtocolConfig,
+                      constraints: &[E<Fp>],
+                      proof: &crate::proof::Proof<N, _, _, _>| {
+            let ctx = VerifierContext::<_, OpeningProof, _>::new(
+                domain,
+                &srs,
+                config.clone(),
+                constraints,
+            )?;
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                proof,
+                Witness::zero_vec(domain_size),
+            )
+        };
+        let mut proofs = vec![];
+        for direction in [
+            AggregationDirection::Forward,
+            AggregationDirection::Backward,
+        ] {
+            let config = ProtocolConfig {
+                aggregation_direction: direction,
+                ..Default::default()
+            };
+            let constraints = constraint_lookups_with_config(
+                &lookups,
+                &LookupConstraintOptions::of_config(&config),
+            );
+            let mut inputs = dual_table_inputs(domain_size);
+            inputs.mvlookups = mvlookups();
+            let ctx = ProverContext::new(domain, &srs, config.clone(), &constraints).unwrap();
+            let proof = prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs, &mut rng)
+            .unwrap();
+            assert_eq!(
+                verify(&config, &constraints, &proof),
+                Ok(()),
+                "{direction:?}"
+            );
+            proofs.push((config, constraints, proof));
+        }
+        // The proofs do not verify with the constraints of the other direction
+        assert!(verify(&proofs[0].0, &proofs[1].1, &proofs[0].2).is_err());
+        assert!(verify(&proofs[1].0, &proofs[0].1, &proofs[1].2).is_err());
 
-            let fixed_lookup_tables_evals_d1: BTreeMap<
-                ID,
-                Evaluations<G::ScalarField, D<G::ScalarField>>,
-            > = fixed_lookup_tables
-                .into_iter()
-                .map(|(id, evals)| {
-                    (
-                        id,
-                        Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
-                            evals, domain.d1,
-                        ),
-                    )
-                })
-                .collect();
+        // The forward running sum is the sum of the rows before the current
+        // one, and the backward one the sum from the current row, which are
+        // opposite as the sum over the domain is zero.
+        let phi = |direction| {
+            let mut fq_sponge = BaseSponge::new(BN254G1Affine::other_curve_sponge_params());
+            let env = Env::<BN254G1Affine, LookupTableIDs>::create::<OpeningProof, BaseSponge>(
+                mvlookups(),
+                domain,
+                &mut fq_sponge,
+                &srs,
+                LookupAggregationMode::Committed,
+                direction,
+                LookupAbsorptionOrder::ColumnsFirst,
+                None,
+                &PerfCounters::default(),
+                None,
+            )
+            .unwrap();
+            env.lookup_aggregation_poly_d1
+                .unwrap()
+                .evaluate_over_domain(domain.d1)
+                .evals
+        };
+        let forward_phi = phi(AggregationDirection::Forward);
+        let backward_phi = phi(AggregationDirection::Backward);
+        assert_eq!(forward_phi[0], Fp::zero());
+        assert_ne!(forward_phi, backward_phi);
+        for (forward, backward) in forward_phi.iter().zip(backward_phi.iter()) {
+            assert_eq!(*forward, -*backward);
+        }
+    }
 
-            let lookup_terms_poly_d1: Vec<DensePolynomial<G::ScalarField>> =
-                (&lookup_terms_evals_d1)
-                    .into_par_iter()
-                    .map(|lte| lte.interpolate_by_ref())
-                    .collect::<Vec<_>>();
+    #[test]
+    fn test_external1_profile() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let global_tables =
+            generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[range_table(16)]).unwrap();
 
-            let fixed_lookup_tables_poly_d1: BTreeMap<ID, DensePolynomial<G::ScalarField>> =
-                (&fixed_lookup_tables_evals_d1)
-                    .into_par_iter()
-                    .map(|(id, evals)| (*id, evals.interpolate_by_ref()))
-                    .collect();
+        let config = ProtocolConfig::for_profile(Profile::External1);
+        assert_eq!(config.aggregation_direction, AggregationDirection::Backward);
+        assert_eq!(
+            config.lookup_absorption,
+            LookupAbsorptionOrder::AggregationFirst
+        );
+        assert!(config.profile.omits_fixed_tables());
+        assert_eq!(
+            ProtocolConfig::for_profile(Profile::Native),
+            ProtocolConfig::default()
+        );
 
-            let lookup_terms_evals_d8: Vec<Evaluations<G::ScalarField, D<G::ScalarField>>> =
-                (&lookup_terms_poly_d1)
-                    .into_par_iter()
-                    .map(|lte| lte.evaluate_over_domain_by_ref(domain.d8))
-                    .collect::<Vec<_>>();
+        let cols: [Vec<Fp>; N] = std::array::from_fn(|j| {
+            (0..domain_size)
+                .map(|i| Fp::from(((3 * i + j) % 16) as u64))
+                .collect()
+        });
+        let (lookups, lookup) = crate::test::column_lookups(domain, 1, 16, &cols, &[0, 1]);
+        let constraints = constraint_lookups_with_config(
+            &BTreeMap::from([(LookupTableIDs::Custom(1), lookups)]),
+            &LookupConstraintOptions::of_config(&config),
+        );
+        let inputs = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups: vec![lookup],
+        };
 
-            let fixed_lookup_tables_evals_d8: BTreeMap<
-                ID,
-                Evaluations<G::ScalarField, D<G::ScalarField>>,
-            > = (&fixed_lookup_tables_poly_d1)
-                .into_par_iter()
-                .map(|(id, poly)| (*id, poly.evaluate_over_domain_by_ref(domain.d8)))
-                .collect();
+        // The fixed tables are system parameters
+        let ctx =
+            ProverContext::<_, OpeningProof, _>::new(domain, &srs, config.clone(), &constraints)
+                .unwrap();
+        let res = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs.clone(), &mut rng);
+        assert!(matches!(
+            res,
+            Err(ProverError::Config(ConfigError::GlobalTablesRequired(
+                Profile::External1
+            )))
+        ));
 
-            let lookup_terms_comms_d1: Vec<PolyComm<G>> = (&lookup_terms_evals_d1)
-                .into_par_iter()
-                .map(|lte| srs.commit_evaluations_non_hiding(domain.d1, lte))
-                .collect::<Vec<_>>();
+        let ctx = ctx.with_global_tables(global_tables.clone());
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+        assert!(proof
+            .proof_comms
+            .mvlookup_comms
+            .as_ref()
+            .unwrap()
+            .fixed_tables
+            .is_empty());
+        let verify = |ctx: &VerifierContext<_, OpeningProof, LookupTableIDs>| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size),
+            )
+        };
+        assert_eq!(
+            verify(&VerifierContext::from_prover_context(&ctx).unwrap()),
+            Ok(())
+        );
 
-            let fixed_lookup_tables_comms_d1: BTreeMap<ID, PolyComm<G>> =
-                (&fixed_lookup_tables_evals_d1)
-                    .into_par_iter()
-                    .map(|(id, evals)| (*id, srs.commit_evaluations_non_hiding(domain.d1, evals)))
-                    .collect();
+        // The verifier must be given the global tables as well
+        assert_eq!(
+            verify(&VerifierContext::new(domain, &srs, config.clone(), &constraints).unwrap()),
+            Err(VerifierError::Config(ConfigError::GlobalTablesRequired(
+                Profile::External1
+            )))
+        );
 
-            lookup_terms_comms_d1
-                .iter()
-                .for_each(|comm| absorb_commitment(fq_sponge, comm));
+        // The transcript depends on the order of the absorptions
+        let columns_first = ProtocolConfig {
+            lookup_absorption: LookupAbsorptionOrder::ColumnsFirst,
+            ..config
+        };
+        let ctx = VerifierContext::new(domain, &srs, columns_first, &constraints)
+            .unwrap()
+            .with_global_tables(global_tables);
+        assert!(verify(&ctx).is_err());
+    }
 
-            fixed_lookup_tables_comms_d1
-                .values()
-                .for_each(|comm| absorb_commitment(fq_sponge, comm));
-            // -- end computing the row sums h
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct RowPartialSumsVectors {
+        name: String,
+        max_supported_degree: usize,
+        test_vectors: Vec<RowPartialSumsVector>,
+    }
 
-            // -- start computing the running sum in lookup_aggregation
-            // The running sum, φ, is defined recursively over the subgroup as followed:
-            // - φ(1) = 0
-            // - φ(ω^{j + 1}) = φ(ω^j) + \
-            //                         \sum_{i = 1}^{n} (1 / (β + f_i(ω^{j + 1}))) - \
-            //                         (m(ω^{j + 1}) / (β + t(ω^{j + 1})))
-            // - φ(ω^n) = 0
-            let lookup_aggregation_evals_d1 = {
-                let mut evals = Vec::with_capacity(domain.d1.size as usize);
-                let mut acc = G::ScalarField::zero();
-                for i in 0..domain.d1.size as usize {
-                    // φ(1) = 0
-                    evals.push(acc);
-                    for lte in lookup_terms_evals_d1.iter() {
-                        acc += lte[i]
-                    }
-                }
-                // Sanity check to verify that the accumulator ends up being zero.
-                // FIXME: This should be removed from runtime, and a constraint
-                // should be added. For now, the verifier accepts any proof.
-                // This will be fixed when constraints are added.
-                assert_eq!(acc, G::ScalarField::zero());
-                Evaluations::<G::ScalarField, D<G::ScalarField>>::from_vec_and_domain(
-                    evals, domain.d1,
-                )
-            };
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct RowPartialSumsVector {
+        beta: String,
+        joint_combiner: String,
+        row: Vec<RowPartialSumsLookup>,
+        output: Vec<String>,
+    }
 
-            let lookup_aggregation_poly_d1 = lookup_aggregation_evals_d1.interpolate_by_ref();
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct RowPartialSumsLookup {
+        table_id: u32,
+        numerator: String,
+        value: Vec<String>,
+    }
 
-            let lookup_aggregation_evals_d8 =
-                lookup_aggregation_poly_d1.evaluate_over_domain_by_ref(domain.d8);
+    const ROW_PARTIAL_SUMS_VECTORS: &str = "testdata/mvlookup_row_partial_sums.json";
 
-            let lookup_aggregation_comm_d1 =
-                srs.commit_evaluations_non_hiding(domain.d1, &lookup_aggregation_evals_d1);
+    fn random_lookup(rng: &mut impl rand::Rng) -> MVLookup<Fp, LookupTableIDs> {
+        let table_id = match rng.gen_range(0..3) {
+            0 => LookupTableIDs::RangeCheck16,
+            1 => LookupTableIDs::Bit,
+            _ => LookupTableIDs::Custom(rng.gen_range(0..4)),
+        };
+        let width = rng.gen_range(1..4);
+        let value: Vec<Fp> = (0..width).map(|_| Fp::rand(rng)).collect();
+        MVLookup::new(table_id, Fp::rand(rng), &value)
+    }
 
-            absorb_commitment(fq_sponge, &lookup_aggregation_comm_d1);
-            Self {
-                lookup_counters_poly_d1,
-                lookup_counters_comm_d1,
+    /// Run with `cargo test -p kimchi_msm -- --ignored
+    /// export_row_partial_sums_vectors` to write the test vectors of
+    /// [super::reference::row_partial_sums].
+    #[test]
+    #[ignore]
+    fn export_row_partial_sums_vectors() {
+        use o1_utils::FieldHelpers;
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::from_seed([0u8; 32]);
+        // Rows filling zero, one or several chunks, partially or not
+        let test_vectors = [1, 5, 6, 7, 12, 13]
+            .into_iter()
+            .map(|len| {
+                let beta = Fp::rand(&mut rng);
+                let joint_combiner = Fp::rand(&mut rng);
+                let row: Vec<_> = (0..len).map(|_| random_lookup(&mut rng)).collect();
+                let output = super::reference::row_partial_sums(beta, joint_combiner, &row);
+                RowPartialSumsVector {
+                    beta: beta.to_hex(),
+                    joint_combiner: joint_combiner.to_hex(),
+                    row: row
+                        .iter()
+                        .map(|lookup| RowPartialSumsLookup {
+                            table_id: lookup.table_id.to_u32(),
+                            numerator: lookup.numerator.to_hex(),
+                            value: lookup.value.iter().map(|x| x.to_hex()).collect(),
+                        })
+                        .collect(),
+                    output: output.iter().map(|x| x.to_hex()).collect(),
+                }
+            })
+            .collect();
+        let vectors = RowPartialSumsVectors {
+            name: "mvlookup_row_partial_sums".to_string(),
+            max_supported_degree: crate::MAX_SUPPORTED_DEGREE,
+            test_vectors,
+        };
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(ROW_PARTIAL_SUMS_VECTORS);
+        std::fs::write(path, serde_json::to_string_pretty(&vectors).unwrap() + "\n").unwrap();
+    }
 
-                lookup_terms_poly_d1,
-                lookup_terms_comms_d1,
+    #[test]
+    fn test_row_partial_sums_vectors() {
+        use o1_utils::FieldHelpers;
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(ROW_PARTIAL_SUMS_VECTORS);
+        let file = std::fs::File::open(path).expect("couldn't open the test vectors");
+        let vectors: RowPartialSumsVectors = serde_json::from_reader(file).unwrap();
+        assert_eq!(vectors.max_supported_degree, crate::MAX_SUPPORTED_DEGREE);
+        let from_hex = |x: &String| Fp::from_hex(x).unwrap();
+        for vector in vectors.test_vectors {
+            let row: Vec<_> = vector
+                .row
+                .iter()
+                .map(|lookup| {
+                    let value: Vec<Fp> = lookup.value.iter().map(from_hex).collect();
+                    MVLookup::new(
+                        LookupTableIDs::from_u32(lookup.table_id),
+                        from_hex(&lookup.numerator),
+                        &value,
+                    )
+                })
+                .collect();
+            let output: Vec<Fp> = vector.output.iter().map(from_hex).collect();
+            assert_eq!(
+                super::reference::row_partial_sums(
+                    from_hex(&vector.beta),
+                    from_hex(&vector.joint_combiner),
+                    &row
+                ),
+                output
+            );
+        }
+    }
 
-                lookup_aggregation_poly_d1,
-                lookup_aggregation_comm_d1,
+    #[test]
+    fn test_row_partial_sums_match_env() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 6;
+        let (domain, srs) = setup(domain_size);
+        // Two chunks with the table, and a lookup of vectors in one chunk,
+        // whose last column is a table of vectors
+        let mut f: Vec<Vec<_>> = (0..3)
+            .map(|_| (0..domain_size).map(|_| random_lookup(&mut rng)).collect())
+            .collect();
+        let m: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        for (lookup, m) in f[2].iter_mut().zip(m.iter()) {
+            lookup.table_id = LookupTableIDs::Custom(5);
+            lookup.numerator = -*m;
+        }
+        let lookups = vec![
+            LookupWitness::random_with_table(domain, 0, 20, 8),
+            MVLookupWitness { f, m },
+        ];
+        let mut fq_sponge = BaseSponge::new(BN254G1Affine::other_curve_sponge_params());
+        let env = Env::<BN254G1Affine, LookupTableIDs>::create::<OpeningProof, BaseSponge>(
+            lookups.clone(),
+            domain,
+            &mut fq_sponge,
+            &srs,
+            LookupAggregationMode::Claimed,
+            AggregationDirection::Forward,
+            LookupAbsorptionOrder::ColumnsFirst,
+            None,
+            &PerfCounters::default(),
+            None,
+        )
+        .unwrap();
+        let columns: Vec<Vec<Fp>> = env
+            .lookup_terms_poly_d1
+            .iter()
+            .map(|poly| poly.evaluate_over_domain_by_ref(domain.d1).evals)
+            .collect();
+        assert_eq!(columns.len(), 3);
+        for j in 0..domain_size {
+            let expected: Vec<Fp> = lookups
+                .iter()
+                .flat_map(|lookup| {
+                    let row: Vec<_> = lookup.f.iter().map(|f_i| f_i[j].clone()).collect();
+                    super::reference::row_partial_sums(env.beta, env.joint_combiner, &row)
+                })
+                .collect();
+            let got: Vec<Fp> = columns.iter().map(|column| column[j]).collect();
+            assert_eq!(got, expected, "row {j}");
+        }
+    }
 
-                lookup_counters_evals_d8,
-                lookup_terms_evals_d8,
-                lookup_aggregation_evals_d8,
+    #[test]
+    fn test_vector_combiner_matches_horner() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let joint_combiner = Fp::rand(&mut rng);
+        let mut combiner = VectorCombiner::new(joint_combiner);
+        // The powers are extended on the first lookup of a larger width, and
+        // reused for the smaller ones
+        let mut expected_muls = 0;
+        for width in [1, 3, 2, 6, 1, 4, 5, 6, 2, 1] {
+            let value: Vec<Fp> = (0..width).map(|_| Fp::rand(&mut rng)).collect();
+            let n_powers = std::cmp::max(combiner.powers.len(), width);
+            expected_muls += n_powers - combiner.powers.len() + width;
+            assert_eq!(
+                combiner.combine(&value),
+                super::reference::combine_values(joint_combiner, &value),
+                "width {width}"
+            );
+            assert_eq!(combiner.muls(), expected_muls, "width {width}");
+        }
+        assert_eq!(combiner.combine(&[]), Fp::zero());
+        // The padding values, with trailing zeroes
+        for width in 1..=6 {
+            let mut value = vec![Fp::zero(); width];
+            value[0] = Fp::rand(&mut rng);
+            assert_eq!(
+                combiner.combine(&value),
+                super::reference::combine_values(joint_combiner, &value)
+            );
+        }
+    }
 
-                fixed_lookup_tables_poly_d1,
-                fixed_lookup_tables_comms_d1,
-                fixed_lookup_tables_evals_d8,
+    #[cfg(feature = "perf-counters")]
+    #[test]
+    fn test_vector_combiner_muls_range_checks() {
+        let domain_size = 1 << 8;
+        let (domain, srs) = setup(domain_size);
+        let n_queries = 6;
+        let lookups = vec![LookupWitness::random_with_table(domain, 0, 64, n_queries)];
+        // The number of cells whose values are combined, i.e. which don't
+        // share the combined value of the previous column of the row in
+        // their chunk
+        let f = &lookups[0].f;
+        let n_combined = (0..domain_size)
+            .map(|j| {
+                1 + (1..f.len())
+                    .filter(|i| {
+                        i % (crate::MAX_SUPPORTED_DEGREE - 2) == 0
+                            || f[*i][j].value != f[i - 1][j].value
+                    })
+                    .count()
+            })
+            .sum::<usize>();
+        let perf = PerfCounters::default();
+        let mut fq_sponge = BaseSponge::new(BN254G1Affine::other_curve_sponge_params());
+        Env::<BN254G1Affine, LookupTableIDs>::create::<OpeningProof, BaseSponge>(
+            lookups,
+            domain,
+            &mut fq_sponge,
+            &srs,
+            LookupAggregationMode::Committed,
+            AggregationDirection::Forward,
+            LookupAbsorptionOrder::ColumnsFirst,
+            None,
+            &perf,
+            None,
+        )
+        .unwrap();
+        // One multiplication per combined value of width 1, instead of the
+        // two of the Horner scheme
+        assert_eq!(perf.stats().combiner_muls, n_combined);
+        assert!(n_combined <= (n_queries + 1) * domain_size);
+    }
 
-                joint_combiner: vector_lookup_combiner,
-                beta,
+    /// A lookup witness of a random shape, valid or not: a valid witness of
+    /// a random table, with random defects
+    fn arbitrary_witness(
+        rng: &mut impl rand::Rng,
+        domain_size: usize,
+    ) -> MVLookupWitness<Fp, LookupTableIDs> {
+        let ids = [
+            LookupTableIDs::RangeCheck16,
+            LookupTableIDs::Bit,
+            LookupTableIDs::Custom(0),
+            LookupTableIDs::Custom(1),
+        ];
+        let table_id = ids[rng.gen_range(0..ids.len())];
+        let mut m = vec![Fp::zero(); domain_size];
+        let mut f: Vec<Vec<MVLookup<Fp, LookupTableIDs>>> = (0..rng.gen_range(0..4))
+            .map(|_| {
+                (0..domain_size)
+                    .map(|_| {
+                        let v = rng.gen_range(0..4);
+                        m[v] += Fp::one();
+                        MVLookup::new(table_id, Fp::one(), &[Fp::from(v as u64)])
+                    })
+                    .collect()
+            })
+            .collect();
+        f.push(
+            (0..domain_size)
+                .map(|i| MVLookup::new(table_id, -m[i], &[Fp::from((i % 4) as u64)]))
+                .collect(),
+        );
+        let mut witness = MVLookupWitness { f, m };
+        for _ in 0..rng.gen_range(0..3) {
+            let n_columns = witness.f.len();
+            let column = rng.gen_range(0..n_columns.max(1));
+            let row = rng.gen_range(0..domain_size);
+            let cell = witness
+                .f
+                .get_mut(column)
+                .and_then(|column| column.get_mut(row));
+            match (rng.gen_range(0..10), cell) {
+                (0, _) => witness.f.clear(),
+                (1, _) => {
+                    witness.f.pop();
+                }
+                (2, _) => witness.f.push(vec![]),
+                (3, _) => witness.f.iter_mut().for_each(|column| column.truncate(row)),
+                (4, _) => witness.m.truncate(row),
+                (5, _) => witness.m.push(Fp::rand(rng)),
+                (6, Some(cell)) => cell.table_id = ids[rng.gen_range(0..ids.len())],
+                (7, Some(cell)) => cell.numerator = Fp::rand(rng),
+                (8, Some(cell)) => {
+                    cell.value = (0..rng.gen_range(0..3)).map(|_| Fp::rand(rng)).collect()
+                }
+                (9, _) => witness.m.iter_mut().for_each(|m| *m += Fp::one()),
+                _ => (),
+            }
+        }
+        witness
+    }
+
+    /// Env::create returns an error instead of panicking, whatever the
+    /// shapes and the values of the witnesses
+    #[test]
+    fn test_env_create_on_arbitrary_witnesses() {
+        use rand::Rng;
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 3;
+        let (domain, srs) = setup(domain_size);
+        let (mut n_ok, mut n_err) = (0, 0);
+        for _ in 0..300 {
+            let lookups: Vec<_> = (0..rng.gen_range(0..3))
+                .map(|_| arbitrary_witness(&mut rng, domain_size))
+                .collect();
+            let mut fq_sponge = BaseSponge::new(BN254G1Affine::other_curve_sponge_params());
+            let res = Env::<BN254G1Affine, LookupTableIDs>::create::<OpeningProof, BaseSponge>(
+                lookups.clone(),
+                domain,
+                &mut fq_sponge,
+                &srs,
+                LookupAggregationMode::Committed,
+                AggregationDirection::Forward,
+                LookupAbsorptionOrder::ColumnsFirst,
+                None,
+                &PerfCounters::default(),
+                None,
+            );
+            match res {
+                Ok(env) => {
+                    n_ok += 1;
+                    assert!(!env.lookup_terms_poly_d1.is_empty());
+                    assert!(env
+                        .lookup_terms_evals_d8
+                        .iter()
+                        .all(|evals| evals.evals.len() == domain.d8.size as usize));
+                    assert!(env
+                        .fixed_lookup_tables_comms_d1
+                        .keys()
+                        .eq(env.lookup_counters_comm_d1.keys()));
+                }
+                Err(err) => {
+                    n_err += 1;
+                    assert!(!matches!(err, ProverError::Internal(_)), "{err}");
+                }
             }
         }
+        // Both outcomes are exercised
+        assert!(n_ok > 10 && n_err > 10, "{n_ok} proofs, {n_err} errors");
     }
 }
@@ -0,0 +1,453 @@
+//! Compact encoding of the witness, for transport between machines.
+//!
+//! Most of the columns only hold small values, like limbs of 15 bits or
+//! booleans, but their canonical encodings use the full size of a field
+//! element. A [WitnessLayout] declares the range of each column, and
+//! [Witness::to_packed_bytes] packs the values of a column of `b` bits in `b`
+//! bits each. The decoding checks that the values fit in the declared range.
+//!
+//! The declaration is only checked by the encoding and the decoding. The
+//! lookups returned by [WitnessLayout::range_check_lookups] enforce it in the
+//! circuit too.
+
+use ark_ff::{BigInteger, One, PrimeField};
+use kimchi::{circuits::expr::ConstantTerm, curve::KimchiCurve};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::{
+    columns::Column,
+    expr::{curr_cell, E},
+    lookups::LookupTableIDs,
+    mvlookup::{LookupTableID, MVLookup},
+    proof::ProofInputs,
+    witness::Witness,
+};
+
+/// The range of the values of a witness column
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ColumnRange {
+    /// The values are in `[0, 2^b)`, with `b` between 1 and 64
+    Bits(u8),
+    /// The values are 0 or 1
+    Boolean,
+    /// Any field element
+    #[default]
+    FullField,
+}
+
+impl ColumnRange {
+    /// The number of bits of an encoded value, if it is not a field element
+    pub fn bits(&self) -> Option<u8> {
+        match self {
+            ColumnRange::Bits(bits) => Some(*bits),
+            ColumnRange::Boolean => Some(1),
+            ColumnRange::FullField => None,
+        }
+    }
+}
+
+/// Errors that can arise when packing or unpacking a witness
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PackingError {
+    #[error("the value at column {column}, row {row} does not fit in {range:?}")]
+    OutOfRange {
+        column: usize,
+        row: usize,
+        range: ColumnRange,
+    },
+
+    #[error("the value at column {column}, row {row} is not a canonical field element")]
+    NonCanonical { column: usize, row: usize },
+
+    #[error("the padding bits of the column {column} are not zero")]
+    NonZeroPadding { column: usize },
+
+    #[error("the encoding of the column {column} is truncated")]
+    Truncated { column: usize },
+
+    #[error("{0} bytes are left after the last column")]
+    TrailingBytes(usize),
+
+    #[error(
+        "the range {bits} bits of the column {column} can not be checked with the built-in tables"
+    )]
+    UnsupportedRangeCheck { column: usize, bits: u8 },
+
+    #[error("failed to decode the lookups: {0}")]
+    Deserialization(String),
+}
+
+/// The ranges of the N columns of a witness. All the columns are
+/// [ColumnRange::FullField] by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WitnessLayout<const N: usize> {
+    ranges: Box<[ColumnRange; N]>,
+}
+
+impl<const N: usize> Default for WitnessLayout<N> {
+    fn default() -> Self {
+        WitnessLayout {
+            ranges: Box::new([ColumnRange::FullField; N]),
+        }
+    }
+}
+
+impl<const N: usize> WitnessLayout<N> {
+    /// Declare the range of the column `column`
+    pub fn with_range(mut self, column: usize, range: ColumnRange) -> Self {
+        if let ColumnRange::Bits(bits) = range {
+            assert!(
+                (1..=64).contains(&bits),
+                "a column of {bits} bits must be declared as a full field column"
+            );
+        }
+        self.ranges[column] = range;
+        self
+    }
+
+    pub fn range(&self, column: usize) -> ColumnRange {
+        self.ranges[column]
+    }
+
+    /// Returns the lookups enforcing the declared ranges in the circuit, to
+    /// be given to [crate::mvlookup::constraint_lookups]. The boolean columns
+    /// are looked up in [LookupTableIDs::Bit]. A column of `b <= 16` bits is
+    /// looked up in [LookupTableIDs::RangeCheck16], and if `b < 16`, its value
+    /// multiplied by `2^(16 - b)` too. The two lookups imply that the value is
+    /// in `[0, 2^b)`, as the product can not wrap around the modulus. Wider
+    /// columns would need a decomposition and are rejected.
+    #[allow(clippy::type_complexity)]
+    pub fn range_check_lookups<F: PrimeField>(
+        &self,
+    ) -> Result<BTreeMap<LookupTableIDs, Vec<MVLookup<E<F>, LookupTableIDs>>>, PackingError> {
+        let mut lookups: BTreeMap<LookupTableIDs, Vec<_>> = BTreeMap::new();
+        for (column, range) in self.ranges.iter().enumerate() {
+            let x = curr_cell(Column::X(column));
+            match range.bits() {
+                None => (),
+                Some(1) => lookups
+                    .entry(LookupTableIDs::Bit)
+                    .or_default()
+                    .push(MVLookup::new(LookupTableIDs::Bit, E::one(), &[x])),
+                Some(bits) if bits <= 16 => {
+                    let table = lookups.entry(LookupTableIDs::RangeCheck16).or_default();
+                    if bits < 16 {
+                        let shift = E::from(ConstantTerm::Literal(F::from(1u64 << (16 - bits))));
+                        table.push(MVLookup::new(
+                            LookupTableIDs::RangeCheck16,
+                            E::one(),
+                            &[shift * x.clone()],
+                        ));
+                    }
+                    table.push(MVLookup::new(LookupTableIDs::RangeCheck16, E::one(), &[x]));
+                }
+                Some(bits) => return Err(PackingError::UnsupportedRangeCheck { column, bits }),
+            }
+        }
+        Ok(lookups)
+    }
+}
+
+/// Reads the little-endian u64 at the start of `bytes`
+fn read_u64(bytes: &[u8], column: usize) -> Result<(u64, &[u8]), PackingError> {
+    if bytes.len() < 8 {
+        return Err(PackingError::Truncated { column });
+    }
+    let (head, tail) = bytes.split_at(8);
+    Ok((u64::from_le_bytes(head.try_into().unwrap()), tail))
+}
+
+fn pack_column<F: PrimeField>(
+    column: usize,
+    values: &[F],
+    range: ColumnRange,
+    bytes: &mut Vec<u8>,
+) -> Result<(), PackingError> {
+    bytes.extend((values.len() as u64).to_le_bytes());
+    let Some(bits) = range.bits() else {
+        for x in values {
+            x.serialize(&mut *bytes).unwrap();
+        }
+        return Ok(());
+    };
+    let bits = bits as usize;
+    let start = bytes.len();
+    bytes.resize(start + (values.len() * bits + 7) / 8, 0);
+    for (row, x) in values.iter().enumerate() {
+        let repr = x.into_repr();
+        if repr.num_bits() as usize > bits {
+            return Err(PackingError::OutOfRange { column, row, range });
+        }
+        let value = repr.as_ref()[0];
+        for i in 0..bits {
+            if (value >> i) & 1 == 1 {
+                let pos = row * bits + i;
+                bytes[start + pos / 8] |= 1 << (pos % 8);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes a column at the start of `bytes`, and returns the remaining bytes
+fn unpack_column<F: PrimeField>(
+    column: usize,
+    bytes: &[u8],
+    range: ColumnRange,
+) -> Result<(Vec<F>, &[u8]), PackingError> {
+    let (len, bytes) = read_u64(bytes, column)?;
+    let len = len as usize;
+    let Some(bits) = range.bits() else {
+        let elem_size = F::zero().serialized_size();
+        if bytes.len() / elem_size < len {
+            return Err(PackingError::Truncated { column });
+        }
+        let (data, rest) = bytes.split_at(len * elem_size);
+        let values = data
+            .chunks(elem_size)
+            .enumerate()
+            .map(|(row, chunk)| {
+                F::deserialize(chunk).map_err(|_| PackingError::NonCanonical { column, row })
+            })
+            .collect::<Result<_, _>>()?;
+        return Ok((values, rest));
+    };
+    let bits = bits as usize;
+    let size = len
+        .checked_mul(bits)
+        .map(|n| (n + 7) / 8)
+        .filter(|size| *size <= bytes.len())
+        .ok_or(PackingError::Truncated { column })?;
+    let (data, rest) = bytes.split_at(size);
+    let values = (0..len)
+        .map(|row| {
+            let value = (0..bits).fold(0u64, |acc, i| {
+                let pos = row * bits + i;
+                acc | (((data[pos / 8] >> (pos % 8)) & 1) as u64) << i
+            });
+            F::from(value)
+        })
+        .collect();
+    // The unused bits of the last byte must be zero, so that the encoding of
+    // a witness is unique
+    let used = len * bits % 8;
+    if used != 0 && data[size - 1] >> used != 0 {
+        return Err(PackingError::NonZeroPadding { column });
+    }
+    Ok((values, rest))
+}
+
+fn unpack_witness<'a, const N: usize, F: PrimeField>(
+    layout: &WitnessLayout<N>,
+    mut bytes: &'a [u8],
+) -> Result<(Witness<N, Vec<F>>, &'a [u8]), PackingError> {
+    let mut cols: Box<[Vec<F>; N]> = Box::new(std::array::from_fn(|_| vec![]));
+    for (column, col) in cols.iter_mut().enumerate() {
+        (*col, bytes) = unpack_column(column, bytes, layout.range(column))?;
+    }
+    Ok((Witness { cols }, bytes))
+}
+
+impl<const N: usize, F: PrimeField> Witness<N, Vec<F>> {
+    /// Encode the columns with the ranges declared by `layout`. Each column
+    /// is prefixed by its length, as a little-endian u64. The values of the
+    /// columns of `b` bits are packed in `b` bits each, starting from the
+    /// least significant bit of the first byte. The other columns are the
+    /// concatenation of the canonical encodings of their values.
+    pub fn to_packed_bytes(&self, layout: &WitnessLayout<N>) -> Result<Vec<u8>, PackingError> {
+        let mut bytes = vec![];
+        for (column, col) in self.cols.iter().enumerate() {
+            pack_column(column, col, layout.range(column), &mut bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a witness encoded by [Witness::to_packed_bytes] with the same
+    /// layout.
+    pub fn from_packed_bytes(
+        layout: &WitnessLayout<N>,
+        bytes: &[u8],
+    ) -> Result<Self, PackingError> {
+        let (witness, rest) = unpack_witness(layout, bytes)?;
+        if !rest.is_empty() {
+            return Err(PackingError::TrailingBytes(rest.len()));
+        }
+        Ok(witness)
+    }
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofInputs<N, G, ID> {
+    /// Encode the witness with [Witness::to_packed_bytes], followed by the
+    /// lookups encoded with MessagePack.
+    pub fn to_packed_bytes(&self, layout: &WitnessLayout<N>) -> Result<Vec<u8>, PackingError> {
+        let mut bytes = self.evaluations.to_packed_bytes(layout)?;
+        bytes.extend(rmp_serde::to_vec(&self.mvlookups).unwrap());
+        Ok(bytes)
+    }
+
+    pub fn from_packed_bytes(
+        layout: &WitnessLayout<N>,
+        bytes: &[u8],
+    ) -> Result<Self, PackingError> {
+        let (evaluations, rest) = unpack_witness(layout, bytes)?;
+        let mvlookups = rmp_serde::from_slice(rest)
+            .map_err(|err| PackingError::Deserialization(err.to_string()))?;
+        Ok(ProofInputs {
+            evaluations,
+            mvlookups,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnRange, PackingError, WitnessLayout};
+    use crate::{
+        lookups::{LookupTableIDs, LookupWitness},
+        proof::ProofInputs,
+        witness::Witness,
+        BN254G1Affine, Fp,
+    };
+    use ark_ff::{One, UniformRand, Zero};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use rand::Rng;
+
+    fn layout() -> WitnessLayout<4> {
+        WitnessLayout::default()
+            .with_range(0, ColumnRange::Boolean)
+            .with_range(1, ColumnRange::Bits(15))
+            .with_range(3, ColumnRange::Bits(64))
+    }
+
+    fn random_witness(rows: usize) -> Witness<4, Vec<Fp>> {
+        let mut rng = o1_utils::tests::make_test_rng();
+        Witness {
+            cols: Box::new([
+                (0..rows).map(|_| Fp::from(rng.gen_bool(0.5))).collect(),
+                (0..rows)
+                    .map(|_| Fp::from(rng.gen_range(0..1 << 15)))
+                    .collect(),
+                (0..rows).map(|_| Fp::rand(&mut rng)).collect(),
+                (0..rows).map(|_| Fp::from(rng.gen::<u64>())).collect(),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_packed_witness_round_trip() {
+        let layout = layout();
+        // A number of rows which does not fill the last byte of the packed
+        // columns
+        let witness = random_witness(77);
+        let bytes = witness.to_packed_bytes(&layout).unwrap();
+        assert_eq!(
+            bytes.len(),
+            4 * 8 + (77 + 7) / 8 + (77 * 15 + 7) / 8 + 77 * 32 + 77 * 8
+        );
+        assert_eq!(Witness::from_packed_bytes(&layout, &bytes), Ok(witness));
+
+        // The default layout is the canonical encoding of the field elements
+        let witness = random_witness(8);
+        let bytes = witness.to_packed_bytes(&WitnessLayout::default()).unwrap();
+        assert_eq!(bytes.len(), 4 * (8 + 8 * 32));
+        assert_eq!(
+            Witness::from_packed_bytes(&WitnessLayout::default(), &bytes),
+            Ok(witness)
+        );
+    }
+
+    #[test]
+    fn test_packed_boolean_column_size() {
+        let rows = 1 << 14;
+        let layout = WitnessLayout::default().with_range(0, ColumnRange::Boolean);
+        let witness: Witness<1, Vec<Fp>> = Witness {
+            cols: Box::new([(0..rows).map(|i| Fp::from(i % 3 == 0)).collect()]),
+        };
+        let bytes = witness.to_packed_bytes(&layout).unwrap();
+        assert_eq!(bytes.len(), 8 + 2048);
+        assert_eq!(Witness::from_packed_bytes(&layout, &bytes), Ok(witness));
+    }
+
+    #[test]
+    fn test_packed_witness_rejects_out_of_range_values() {
+        let layout = layout();
+        let mut witness = random_witness(9);
+        witness.cols[1][4] = Fp::from(1u64 << 15);
+        assert_eq!(
+            witness.to_packed_bytes(&layout),
+            Err(PackingError::OutOfRange {
+                column: 1,
+                row: 4,
+                range: ColumnRange::Bits(15)
+            })
+        );
+        witness.cols[1][4] = Fp::zero();
+        witness.cols[0][8] = -Fp::one();
+        assert!(matches!(
+            witness.to_packed_bytes(&layout),
+            Err(PackingError::OutOfRange {
+                column: 0,
+                row: 8,
+                ..
+            })
+        ));
+
+        // A set padding bit of the boolean column would be a tenth value
+        witness.cols[0][8] = Fp::one();
+        let mut bytes = witness.to_packed_bytes(&layout).unwrap();
+        bytes[8 + 1] |= 2;
+        assert_eq!(
+            Witness::<4, Vec<Fp>>::from_packed_bytes(&layout, &bytes),
+            Err(PackingError::NonZeroPadding { column: 0 })
+        );
+
+        let bytes = witness.to_packed_bytes(&layout).unwrap();
+        assert_eq!(
+            Witness::<4, Vec<Fp>>::from_packed_bytes(&layout, &bytes[..bytes.len() - 1]),
+            Err(PackingError::Truncated { column: 3 })
+        );
+        assert_eq!(
+            Witness::<4, Vec<Fp>>::from_packed_bytes(&layout, &[bytes.as_slice(), &[0]].concat()),
+            Err(PackingError::TrailingBytes(1))
+        );
+    }
+
+    #[test]
+    fn test_packed_proof_inputs_round_trip() {
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let layout = layout();
+        let inputs: ProofInputs<4, BN254G1Affine, LookupTableIDs> = ProofInputs {
+            evaluations: random_witness(1 << 4),
+            mvlookups: vec![LookupWitness::random_with_table(domain, 0, 10, 2)],
+        };
+        let bytes = inputs.to_packed_bytes(&layout).unwrap();
+        let decoded =
+            ProofInputs::<4, BN254G1Affine, LookupTableIDs>::from_packed_bytes(&layout, &bytes)
+                .unwrap();
+        assert_eq!(decoded.evaluations, inputs.evaluations);
+        assert_eq!(
+            rmp_serde::to_vec(&decoded.mvlookups).unwrap(),
+            rmp_serde::to_vec(&inputs.mvlookups).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_range_check_lookups() {
+        let lookups = layout()
+            .with_range(3, ColumnRange::Bits(16))
+            .range_check_lookups::<Fp>()
+            .unwrap();
+        assert_eq!(lookups[&LookupTableIDs::Bit].len(), 1);
+        // The 15-bit column is looked up twice
+        assert_eq!(lookups[&LookupTableIDs::RangeCheck16].len(), 3);
+
+        assert!(matches!(
+            layout().range_check_lookups::<Fp>(),
+            Err(PackingError::UnsupportedRangeCheck {
+                column: 3,
+                bits: 64
+            })
+        ));
+    }
+}
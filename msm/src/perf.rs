@@ -0,0 +1,214 @@
+//! Counters of the expensive operations performed by the prover and the
+//! verifier, to check their algorithmic behaviour independently of the wall
+//! time.
+//! The operations are only counted when the feature `perf-counters` is
+//! enabled. Otherwise, [PerfCounters] is empty and recording is a no-op.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "perf-counters")]
+use std::sync::Mutex;
+
+/// The number of operations performed, see [PerfCounters].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PerfStats {
+    /// The number of FFTs (forward and inverse), indexed by the size of the
+    /// domain.
+    pub ffts: BTreeMap<u64, usize>,
+    /// The number of MSMs, indexed by the number of bases.
+    pub msms: BTreeMap<usize, usize>,
+    /// The number of batch inversions
+    pub batch_inversions: usize,
+    /// The total number of field elements inverted in the batch inversions
+    pub field_inversions: usize,
+    /// The size, in bytes, of the field elements which have been committed
+    pub bytes_committed: usize,
+    /// The number of single scalar multiplications of group elements
+    pub scalar_muls: usize,
+    /// The number of evaluations of polynomials copied by the verifier, see
+    /// [PerfCounters::record_evaluation_copies]
+    pub evaluation_copies: usize,
+    /// The number of lookups of the evaluations of a column in the proof by
+    /// the verifier, when evaluating the constraints, see
+    /// [crate::evaluation_layout::EvaluationLayout]
+    pub evaluation_lookups: usize,
+    /// The number of field multiplications performed by the prover to combine
+    /// the values of the vector lookups, see
+    /// [crate::mvlookup::VectorCombiner]
+    pub combiner_muls: usize,
+    /// The number of field multiplications by the numerators of the lookups
+    /// performed by the prover to compute the partial sums
+    pub numerator_muls: usize,
+    /// The number of multiplications by a numerator skipped by the prover,
+    /// the numerator being one, see [PerfCounters::record_numerator_muls]
+    pub skipped_numerator_muls: usize,
+    /// The number of placeholder commitments, computed without any MSM by a
+    /// dry run of the prover, see [crate::dry_run]
+    pub placeholder_commitments: usize,
+}
+
+impl PerfStats {
+    /// Returns the number of FFTs performed over a domain of size `size`
+    pub fn ffts_of_size(&self, size: u64) -> usize {
+        self.ffts.get(&size).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of MSMs performed with `size` bases
+    pub fn msms_of_size(&self, size: usize) -> usize {
+        self.msms.get(&size).copied().unwrap_or(0)
+    }
+}
+
+/// Accumulator of [PerfStats], shared by the threads of the prover or of the
+/// verifier.
+#[derive(Debug, Default)]
+pub struct PerfCounters {
+    #[cfg(feature = "perf-counters")]
+    stats: Mutex<PerfStats>,
+    #[cfg(feature = "perf-counters")]
+    placeholder_commitments: bool,
+}
+
+impl PerfCounters {
+    /// The counters of a prover whose commitments are placeholders, see
+    /// [crate::dry_run]: the commitments are counted as
+    /// [PerfStats::placeholder_commitments] instead of MSMs.
+    pub fn with_placeholder_commitments() -> Self {
+        #[cfg(feature = "perf-counters")]
+        {
+            PerfCounters {
+                stats: Mutex::default(),
+                placeholder_commitments: true,
+            }
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        PerfCounters::default()
+    }
+
+    /// Count an FFT over a domain of size `size`
+    #[inline]
+    pub fn record_fft(&self, size: u64) {
+        #[cfg(feature = "perf-counters")]
+        {
+            *self.stats.lock().unwrap().ffts.entry(size).or_insert(0) += 1;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = size;
+    }
+
+    /// Count `chunks` MSMs of `size` bases, committing to `size * chunks`
+    /// field elements of `elem_size` bytes each.
+    #[inline]
+    pub fn record_commitment(&self, size: usize, chunks: usize, elem_size: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            let mut stats = self.stats.lock().unwrap();
+            if self.placeholder_commitments {
+                stats.placeholder_commitments += chunks;
+            } else {
+                *stats.msms.entry(size).or_insert(0) += chunks;
+                stats.bytes_committed += size * chunks * elem_size;
+            }
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = (size, chunks, elem_size);
+    }
+
+    /// Count an MSM of `size` bases whose scalars are not committed field
+    /// elements, e.g. in the verifier.
+    #[inline]
+    pub fn record_msm(&self, size: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            *self.stats.lock().unwrap().msms.entry(size).or_insert(0) += 1;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = size;
+    }
+
+    /// Count `n` scalar multiplications of group elements
+    #[inline]
+    pub fn record_scalar_muls(&self, n: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            self.stats.lock().unwrap().scalar_muls += n;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = n;
+    }
+
+    /// Count `n` evaluations of polynomials copied by the verifier. The
+    /// evaluations of the proof are only copied once, into the batch of the
+    /// opening proof.
+    #[inline]
+    pub fn record_evaluation_copies(&self, n: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            self.stats.lock().unwrap().evaluation_copies += n;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = n;
+    }
+
+    /// Count `n` lookups of the evaluations of a column in the proof by the
+    /// verifier
+    #[inline]
+    pub fn record_evaluation_lookups(&self, n: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            self.stats.lock().unwrap().evaluation_lookups += n;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = n;
+    }
+
+    /// Count `n` field multiplications combining the values of the vector
+    /// lookups
+    #[inline]
+    pub fn record_combiner_muls(&self, n: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            self.stats.lock().unwrap().combiner_muls += n;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = n;
+    }
+
+    /// Count `performed` multiplications by the numerators of the lookups,
+    /// and `skipped` ones, whose numerator is one and whose inverted
+    /// denominator is added as it is
+    #[inline]
+    pub fn record_numerator_muls(&self, performed: usize, skipped: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.numerator_muls += performed;
+            stats.skipped_numerator_muls += skipped;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = (performed, skipped);
+    }
+
+    /// Count a batch inversion of `n` field elements
+    #[inline]
+    pub fn record_batch_inversion(&self, n: usize) {
+        #[cfg(feature = "perf-counters")]
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.batch_inversions += 1;
+            stats.field_inversions += n;
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = n;
+    }
+
+    /// Returns the operations counted so far. Without the feature
+    /// `perf-counters`, nothing is counted.
+    pub fn stats(&self) -> PerfStats {
+        #[cfg(feature = "perf-counters")]
+        {
+            self.stats.lock().unwrap().clone()
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        PerfStats::default()
+    }
+}
@@ -1,22 +1,41 @@
 use crate::{
+    config::{OpeningMode, SrsLabel},
+    expr::E,
+    fuzz::{Decode, DeserializeError},
+    lookup_engine::PlookupColumn,
     lookups::{LookupTableIDs, LookupWitness},
-    mvlookup::{LookupProof, LookupTableID},
-    witness::Witness,
-    MVLookupWitness, DOMAIN_SIZE,
+    mvlookup::{
+        AccumulatorBoundary, EncodedLookupProof, LookupLayout, LookupProof, LookupTableID, MVLookup,
+    },
+    shifts,
+    witness::{Witness, WitnessError},
+    MVLookupWitness, DOMAIN_SIZE, MAX_SUPPORTED_DEGREE,
 };
-use ark_ff::{UniformRand, Zero};
+use ark_ec::AffineCurve;
+use ark_ff::{FftField, Field, UniformRand, Zero};
+use ark_poly::{univariate::DensePolynomial, Polynomial};
+use blake2::{Blake2b512, Digest};
 use kimchi::{
     circuits::{
         domains::EvaluationDomains,
         expr::{ColumnEvaluations, ExprError},
+        gate::CurrOrNext,
     },
     curve::KimchiCurve,
     proof::PointEvaluations,
 };
 use poly_commitment::{commitment::PolyComm, OpenProof};
 use rand::thread_rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    bound = "",
+    into = "SerializableProofInputs<N, G::ScalarField, ID>",
+    from = "SerializableProofInputs<N, G::ScalarField, ID>"
+)]
 pub struct ProofInputs<const N: usize, G: KimchiCurve, ID: LookupTableID> {
     /// Actual values w_i of the witness columns. "Evaluations" as in
     /// evaluations of polynomial P_w that interpolates w_i.
@@ -43,6 +62,36 @@ impl<const N: usize, G: KimchiCurve> ProofInputs<N, G, LookupTableIDs> {
     }
 }
 
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofInputs<N, G, ID> {
+    /// Append `padding` rows to the witness, see
+    /// [crate::domain::domain_padding]. The witness columns are padded with
+    /// zeroes, and the lookups with the no-op lookups of [MVLookup::noop],
+    /// which add nothing to the lookup argument: the lookup constraints must
+    /// not query the padding rows, e.g. with a numerator which is zero on
+    /// these rows.
+    /// Fails if the witness has more than `max_rows` rows before the padding,
+    /// see [crate::config::ProtocolConfig::max_rows].
+    pub fn pad(mut self, padding: usize, max_rows: Option<usize>) -> Result<Self, WitnessError> {
+        if let Some(max_rows) = max_rows {
+            self.evaluations.check_max_rows(max_rows)?;
+        }
+        for column in self.evaluations.cols.iter_mut() {
+            column.resize(column.len() + padding, G::ScalarField::zero());
+        }
+        for lookup in self.mvlookups.iter_mut() {
+            for column in lookup.f.iter_mut() {
+                if let Some(table_id) = column.first().map(|lookup| lookup.table_id) {
+                    column.extend((0..padding).map(|_| MVLookup::noop(table_id)));
+                }
+            }
+            lookup
+                .m
+                .resize(lookup.m.len() + padding, G::ScalarField::zero());
+        }
+        Ok(self)
+    }
+}
+
 impl<const N: usize, G: KimchiCurve, ID: LookupTableID> Default for ProofInputs<N, G, ID> {
     /// Creates a default proof instance. Note that such an empty "zero" instance will not satisfy any constraint.
     /// E.g. some constraints that have constants inside of them (A - const = 0) cannot be satisfied by it.
@@ -58,82 +107,1136 @@ impl<const N: usize, G: KimchiCurve, ID: LookupTableID> Default for ProofInputs<
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct ProofEvaluations<const N: usize, F, ID: LookupTableID> {
+/// The evaluations of a polynomial sent by the prover, at the evaluation
+/// points given by an [OpeningMode].
+pub trait EvaluationContainer<F>: Clone + Send + Sync + std::fmt::Debug {
+    /// The opening mode the evaluations are given for.
+    const OPENING_MODE: OpeningMode;
+
+    /// Build the container from the evaluations at each evaluation point,
+    /// in the order of [OpeningMode::evaluation_points]. Returns `None` if the
+    /// number of evaluations does not match the opening mode.
+    fn from_points(evals: Vec<F>) -> Option<Self>;
+
+    /// Returns the evaluations at each evaluation point, in the order of
+    /// [OpeningMode::evaluation_points].
+    fn to_points(&self) -> Vec<F>;
+
+    /// Call `f` on the evaluation at each evaluation point, in the order of
+    /// [OpeningMode::evaluation_points], without copying them.
+    fn for_each_point(&self, f: impl FnMut(&F));
+
+    /// Returns the evaluations as expected by the expression framework.
+    /// The evaluation at ζω is zero if it is not opened. The constraints
+    /// referring to the next row are rejected by
+    /// [crate::config::ProtocolConfig::compile_constraints] in that case.
+    fn to_point_evaluations(&self) -> PointEvaluations<F>;
+
+    /// Evaluate `poly` at the evaluation points `points`.
+    fn evaluate(poly: &DensePolynomial<F>, points: &[F]) -> Self
+    where
+        F: Field,
+    {
+        let evals = points.iter().map(|point| poly.evaluate(point)).collect();
+        Self::from_points(evals).expect("The evaluation points must match the opening mode")
+    }
+}
+
+impl<F: Field> EvaluationContainer<F> for PointEvaluations<F> {
+    const OPENING_MODE: OpeningMode = OpeningMode::TwoPoints;
+
+    fn from_points(evals: Vec<F>) -> Option<Self> {
+        match evals.as_slice() {
+            [zeta, zeta_omega] => Some(PointEvaluations {
+                zeta: *zeta,
+                zeta_omega: *zeta_omega,
+            }),
+            _ => None,
+        }
+    }
+
+    fn to_points(&self) -> Vec<F> {
+        vec![self.zeta, self.zeta_omega]
+    }
+
+    fn for_each_point(&self, mut f: impl FnMut(&F)) {
+        f(&self.zeta);
+        f(&self.zeta_omega);
+    }
+
+    fn to_point_evaluations(&self) -> PointEvaluations<F> {
+        *self
+    }
+}
+
+/// The evaluation of a polynomial at ζ only, see [OpeningMode::SinglePoint].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SinglePointEvaluations<F> {
+    /// Evaluation at the challenge point ζ.
+    pub zeta: F,
+}
+
+impl<F: Field> EvaluationContainer<F> for SinglePointEvaluations<F> {
+    const OPENING_MODE: OpeningMode = OpeningMode::SinglePoint;
+
+    fn from_points(evals: Vec<F>) -> Option<Self> {
+        match evals.as_slice() {
+            [zeta] => Some(SinglePointEvaluations { zeta: *zeta }),
+            _ => None,
+        }
+    }
+
+    fn to_points(&self) -> Vec<F> {
+        vec![self.zeta]
+    }
+
+    fn for_each_point(&self, mut f: impl FnMut(&F)) {
+        f(&self.zeta);
+    }
+
+    fn to_point_evaluations(&self) -> PointEvaluations<F> {
+        PointEvaluations {
+            zeta: self.zeta,
+            zeta_omega: F::zero(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    bound = "F: Field + ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize",
+    into = "SerializableProofEvaluations<N, F>",
+    try_from = "SerializableProofEvaluations<N, F>"
+)]
+pub struct ProofEvaluations<
+    const N: usize,
+    F,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<F> = PointEvaluations<F>,
+> {
     /// Witness evaluations, including public inputs
-    pub(crate) witness_evals: Witness<N, PointEvaluations<F>>,
+    pub(crate) witness_evals: Witness<N, Eval>,
     /// MVLookup argument evaluations
-    pub(crate) mvlookup_evals: Option<LookupProof<PointEvaluations<F>, ID>>,
-    /// Evaluation of Z_H(ζ) (t_0(X) + ζ^n t_1(X) + ...) at ζω.
-    pub(crate) ft_eval1: F,
+    pub(crate) mvlookup_evals: Option<LookupProof<Eval, ID>>,
+    /// Evaluation of Z_H(ζ) (t_0(X) + ζ^n t_1(X) + ...) at ζω. It is not
+    /// given when ζω is not opened.
+    pub(crate) ft_eval1: Option<F>,
+    /// Evaluations at ζω² of the witness columns read two rows ahead, indexed
+    /// by their column, see [crate::shifts]
+    pub(crate) shifted_evals: BTreeMap<usize, F>,
 }
 
-/// The trait ColumnEvaluations is used by the verifier.
-/// It will return the evaluation of the corresponding column at the
-/// evaluation points coined by the verifier during the protocol.
-impl<const N: usize, F: Clone, ID: LookupTableID> ColumnEvaluations<F>
-    for ProofEvaluations<N, F, ID>
+impl<const N: usize, F, ID: LookupTableID, Eval: EvaluationContainer<F>>
+    ProofEvaluations<N, F, ID, Eval>
 {
-    type Column = crate::columns::Column;
+    /// The evaluations of the partial sum `chunk` of the table `table_id`,
+    /// see [LookupLayout::partial_sum_index]. Returns `None` when the proof
+    /// does not use lookups, or when the partial sum is not in `layout`.
+    pub fn lookup_partial_sum(
+        &self,
+        table_id: ID,
+        chunk: usize,
+        layout: &LookupLayout<ID>,
+    ) -> Option<&Eval> {
+        self.mvlookup_evals
+            .as_ref()?
+            .partial_sum(table_id, chunk, layout)
+    }
 
-    fn evaluate(&self, col: Self::Column) -> Result<PointEvaluations<F>, ExprError<Self::Column>> {
-        let res = match col {
-            Self::Column::X(i) => {
-                if i < N {
-                    self.witness_evals[i].clone()
-                } else {
-                    panic!("Index out of bounds")
+    /// The evaluations at ζω² of the witness columns read two rows ahead,
+    /// indexed by their column, see [crate::shifts]
+    pub fn shifted_evals(&self) -> &BTreeMap<usize, F> {
+        &self.shifted_evals
+    }
+}
+
+/// Serialization helper for the evaluations of one polynomial, as
+/// [PointEvaluations] can only be serialized for vectors of field elements.
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+struct SerializableEvaluations<F>(#[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")] Vec<F>);
+
+/// Serialization helper for [ProofInputs].
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+struct SerializableProofInputs<const N: usize, F, ID: LookupTableID> {
+    evaluations: Witness<N, SerializableEvaluations<F>>,
+    mvlookups: Vec<MVLookupWitness<F, ID>>,
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> From<ProofInputs<N, G, ID>>
+    for SerializableProofInputs<N, G::ScalarField, ID>
+{
+    fn from(inputs: ProofInputs<N, G, ID>) -> Self {
+        SerializableProofInputs {
+            evaluations: Witness {
+                cols: Box::new(inputs.evaluations.cols.map(SerializableEvaluations)),
+            },
+            mvlookups: inputs.mvlookups,
+        }
+    }
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID>
+    From<SerializableProofInputs<N, G::ScalarField, ID>> for ProofInputs<N, G, ID>
+{
+    fn from(inputs: SerializableProofInputs<N, G::ScalarField, ID>) -> Self {
+        ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(
+                    inputs
+                        .evaluations
+                        .cols
+                        .map(|SerializableEvaluations(col)| col),
+                ),
+            },
+            mvlookups: inputs.mvlookups,
+        }
+    }
+}
+
+/// Serialization helper for [ProofEvaluations], with the shifted
+/// evaluations kept as the list of their entries, see [crate::fuzz::Decode]
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+pub(crate) struct SerializableProofEvaluations<const N: usize, F> {
+    witness_evals: Witness<N, SerializableEvaluations<F>>,
+    mvlookup_evals: Option<EncodedLookupProof<SerializableEvaluations<F>>>,
+    #[serde_as(as = "Option<o1_utils::serialization::SerdeAs>")]
+    ft_eval1: Option<F>,
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serde_shifted_evals::serialize",
+        deserialize_with = "serde_shifted_evals::deserialize::<N, _, _>"
+    )]
+    shifted_evals: Vec<(usize, F)>,
+}
+
+/// Serialization of the shifted evaluations as a map indexed by their
+/// column. The entries are decoded in their order, and the columns are
+/// bounded by `N`, which bounds the length prefix of the map. The encoding
+/// is canonical, see [into_map].
+pub(crate) mod serde_shifted_evals {
+    use crate::fuzz::{check_canonical_map, DeserializeError};
+    use o1_utils::serialization::SerdeAs;
+    use serde::{
+        de::{Error, MapAccess, Visitor},
+        Deserializer, Serializer,
+    };
+    use serde_with::{de::DeserializeAsWrap, ser::SerializeAsWrap};
+    use std::{collections::BTreeMap, fmt, marker::PhantomData};
+
+    pub fn serialize<S: Serializer, F: ark_serialize::CanonicalSerialize>(
+        entries: &[(usize, F)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            entries
+                .iter()
+                .map(|(i, v)| (i, SerializeAsWrap::<F, SerdeAs>::new(v))),
+        )
+    }
+
+    /// The map of the decoded entries, whose duplicate or unsorted columns
+    /// are reported with [check_canonical_map]
+    pub(crate) fn into_map<F>(
+        entries: Vec<(usize, F)>,
+    ) -> Result<BTreeMap<usize, F>, DeserializeError> {
+        check_canonical_map(&entries)?;
+        Ok(entries.into_iter().collect())
+    }
+
+    struct ShiftedEvalsVisitor<const N: usize, F>(PhantomData<F>);
+
+    impl<'de, const N: usize, F: ark_serialize::CanonicalDeserialize> Visitor<'de>
+        for ShiftedEvalsVisitor<N, F>
+    {
+        type Value = Vec<(usize, F)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a map indexed by columns below {N}")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            if let Some(len) = access.size_hint() {
+                if len > N {
+                    return Err(A::Error::custom(format!(
+                        "{len} shifted evaluations are declared, above the {N} columns"
+                    )));
                 }
             }
-            Self::Column::LookupPartialSum(i) => {
-                if let Some(ref lookup) = self.mvlookup_evals {
-                    lookup.h[i].clone()
-                } else {
-                    panic!("No lookup provided")
+            let mut entries = vec![];
+            while let Some((i, v)) = access.next_entry::<usize, DeserializeAsWrap<F, SerdeAs>>()? {
+                if i >= N || entries.len() == N {
+                    return Err(A::Error::custom(format!(
+                        "the shifted column {i} is not below {N}"
+                    )));
                 }
+                entries.push((i, v.into_inner()));
             }
-            Self::Column::LookupAggregation => {
-                if let Some(ref lookup) = self.mvlookup_evals {
-                    lookup.sum.clone()
-                } else {
-                    panic!("No lookup provided")
+            Ok(entries)
+        }
+    }
+
+    pub fn deserialize<
+        'de,
+        const N: usize,
+        De: Deserializer<'de>,
+        F: ark_serialize::CanonicalDeserialize,
+    >(
+        deserializer: De,
+    ) -> Result<Vec<(usize, F)>, De::Error> {
+        deserializer.deserialize_map(ShiftedEvalsVisitor::<N, F>(PhantomData))
+    }
+}
+
+impl<const N: usize, F, ID: LookupTableID, Eval: EvaluationContainer<F>>
+    From<ProofEvaluations<N, F, ID, Eval>> for SerializableProofEvaluations<N, F>
+{
+    fn from(evals: ProofEvaluations<N, F, ID, Eval>) -> Self {
+        let to_points = |evals: Eval| SerializableEvaluations(evals.to_points());
+        SerializableProofEvaluations {
+            witness_evals: Witness {
+                cols: Box::new(evals.witness_evals.cols.map(to_points)),
+            },
+            mvlookup_evals: evals
+                .mvlookup_evals
+                .map(|evals| evals.map(to_points).into()),
+            ft_eval1: evals.ft_eval1,
+            shifted_evals: evals.shifted_evals.into_iter().collect(),
+        }
+    }
+}
+
+impl<const N: usize, F, ID: LookupTableID, Eval: EvaluationContainer<F>>
+    TryFrom<SerializableProofEvaluations<N, F>> for ProofEvaluations<N, F, ID, Eval>
+{
+    type Error = DeserializeError;
+
+    fn try_from(evals: SerializableProofEvaluations<N, F>) -> Result<Self, Self::Error> {
+        const ERROR: &str = "the number of evaluations does not match the opening mode";
+        let error = || DeserializeError::Decoding(ERROR.to_string());
+        let from_points =
+            |SerializableEvaluations(evals)| Eval::from_points(evals).ok_or_else(error);
+        let witness_evals: [_; N] = *evals.witness_evals.cols;
+        let witness_evals: Vec<Eval> = witness_evals
+            .into_iter()
+            .map(from_points)
+            .collect::<Result<_, _>>()?;
+        let mvlookup_evals = match evals.mvlookup_evals {
+            None => None,
+            Some(evals) => {
+                let evals = LookupProof::<_, ID>::try_from(evals)?.map(from_points);
+                if (&evals).into_iter().any(Result::is_err) {
+                    return Err(error());
                 }
+                Some(evals.map(Result::unwrap))
             }
-            Self::Column::LookupMultiplicity(id) => {
-                if let Some(ref lookup) = self.mvlookup_evals {
-                    lookup.m[&ID::from_u32(id)].clone()
-                } else {
-                    panic!("No lookup provided")
-                }
+        };
+        Ok(ProofEvaluations {
+            witness_evals: Witness {
+                cols: Box::new(witness_evals.try_into().map_err(|_| error())?),
+            },
+            mvlookup_evals,
+            ft_eval1: evals.ft_eval1,
+            shifted_evals: serde_shifted_evals::into_map(evals.shifted_evals)?,
+        })
+    }
+}
+
+/// Access to the evaluations of the columns without copying them, used by the
+/// verifier. [ColumnEvaluations], from kimchi, returns the evaluations by
+/// value, and is implemented on top of it.
+pub(crate) trait BorrowedColumnEvaluations<F, Eval> {
+    fn column_evaluations(
+        &self,
+        col: crate::columns::Column,
+    ) -> Result<&Eval, ExprError<crate::columns::Column>>;
+}
+
+impl<const N: usize, F, ID: LookupTableID, Eval: EvaluationContainer<F>>
+    BorrowedColumnEvaluations<F, Eval> for ProofEvaluations<N, F, ID, Eval>
+{
+    fn column_evaluations(
+        &self,
+        col: crate::columns::Column,
+    ) -> Result<&Eval, ExprError<crate::columns::Column>> {
+        use crate::columns::{Column, TableIdx};
+        // The proofs are untrusted, a column missing from them is an error. A
+        // table missing from them is named, as it is the case of a proof
+        // verified against a newer version of the circuit.
+        let missing = || ExprError::MissingEvaluation(col, CurrOrNext::Curr);
+        let lookup = || self.mvlookup_evals.as_ref().ok_or_else(missing);
+        let missing_table = |id: TableIdx| ExprError::MissingLookupTable(id.to_u32());
+        let table = |id: TableIdx| {
+            let table_id = ID::try_from_u32(id.to_u32()).ok_or_else(|| missing_table(id))?;
+            let lookup = self
+                .mvlookup_evals
+                .as_ref()
+                .ok_or_else(|| missing_table(id))?;
+            Ok((lookup, table_id))
+        };
+        match col {
+            Column::X(i) => self.witness_evals.cols.get(i).ok_or_else(missing),
+            Column::LookupPartialSum(i) => lookup()?.h.get(i.index()).ok_or_else(missing),
+            Column::LookupAggregation => lookup()?.sum.committed().ok_or_else(missing),
+            Column::LookupMultiplicity(id) => {
+                let (lookup, table_id) = table(id)?;
+                lookup.m.get(&table_id).ok_or_else(|| missing_table(id))
             }
-            Self::Column::LookupFixedTable(id) => {
-                if let Some(ref lookup) = self.mvlookup_evals {
-                    lookup.fixed_tables[&ID::from_u32(id)].clone()
-                } else {
-                    panic!("No lookup provided")
-                }
+            Column::LookupFixedTable(id) => {
+                let (lookup, table_id) = table(id)?;
+                lookup
+                    .fixed_tables
+                    .get(&table_id)
+                    .ok_or_else(|| missing_table(id))
             }
-        };
-        Ok(res)
+            Column::Plookup(id, column) => {
+                let (lookup, table_id) = table(id)?;
+                lookup
+                    .plookup
+                    .get(&table_id)
+                    .map(|columns| columns.get(column))
+                    .ok_or_else(|| missing_table(id))
+            }
+            // The extra challenges and the selectors are not part of the
+            // proof evaluations, and the evaluations at ζω² are not given for
+            // the opening mode
+            Column::ExtraChallenge(_)
+            | Column::SubdomainSelector(_)
+            | Column::BoundarySelector(_)
+            | Column::XShift2(_) => Err(missing()),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+/// The trait ColumnEvaluations is used by the verifier.
+/// It will return the evaluation of the corresponding column at the
+/// evaluation points coined by the verifier during the protocol.
+impl<const N: usize, F: Copy + Zero, ID: LookupTableID, Eval: EvaluationContainer<F>>
+    ColumnEvaluations<F> for ProofEvaluations<N, F, ID, Eval>
+{
+    type Column = crate::columns::Column;
+
+    fn evaluate(&self, col: Self::Column) -> Result<PointEvaluations<F>, ExprError<Self::Column>> {
+        match col {
+            crate::columns::Column::XShift2(i) => {
+                shifts::shifted_point_evaluations(&self.shifted_evals, i)
+                    .ok_or(ExprError::MissingEvaluation(col, CurrOrNext::Curr))
+            }
+            _ => Ok(self.column_evaluations(col)?.to_point_evaluations()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    bound = "G: ark_serialize::CanonicalDeserialize + ark_serialize::CanonicalSerialize",
+    try_from = "EncodedProofCommitments<N, G>"
+)]
 pub struct ProofCommitments<const N: usize, G: KimchiCurve, ID: LookupTableID> {
     /// Commitments to the N columns of the circuits, also called the 'witnesses'.
     /// If some columns are considered as public inputs, it is counted in the witness.
     pub(crate) witness_comms: Witness<N, PolyComm<G>>,
     /// Commitments to the polynomials used by the lookup argument.
     /// The values contains the chunked polynomials.
-    pub(crate) mvlookup_comms: Option<LookupProof<PolyComm<G>, ID>>,
+    pub(crate) mvlookup_comms: Option<LookupProof<PolyComm<G>, ID, G::ScalarField>>,
     /// Commitments to the quotient polynomial.
-    /// The value contains the chunked polynomials.
+    /// The value contains the chunked polynomials, and the number of chunks
+    /// must be [expected_quotient_chunks].
     pub(crate) t_comm: PolyComm<G>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Proof<const N: usize, G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID> {
+/// The encoding of [ProofCommitments], with the maps of the lookup argument
+/// kept as the list of their entries, see [Decode]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "G: ark_serialize::CanonicalDeserialize + ark_serialize::CanonicalSerialize")]
+pub(crate) struct EncodedProofCommitments<const N: usize, G: KimchiCurve> {
+    witness_comms: Witness<N, PolyComm<G>>,
+    mvlookup_comms: Option<EncodedLookupProof<PolyComm<G>, G::ScalarField>>,
+    t_comm: PolyComm<G>,
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> From<ProofCommitments<N, G, ID>>
+    for EncodedProofCommitments<N, G>
+{
+    fn from(comms: ProofCommitments<N, G, ID>) -> Self {
+        EncodedProofCommitments {
+            witness_comms: comms.witness_comms,
+            mvlookup_comms: comms.mvlookup_comms.map(Into::into),
+            t_comm: comms.t_comm,
+        }
+    }
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> TryFrom<EncodedProofCommitments<N, G>>
+    for ProofCommitments<N, G, ID>
+{
+    type Error = DeserializeError;
+
+    fn try_from(comms: EncodedProofCommitments<N, G>) -> Result<Self, Self::Error> {
+        Ok(ProofCommitments {
+            witness_comms: comms.witness_comms,
+            mvlookup_comms: comms.mvlookup_comms.map(TryInto::try_into).transpose()?,
+            t_comm: comms.t_comm,
+        })
+    }
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofCommitments<N, G, ID> {
+    /// The commitments of the proof with their number of chunks, in the
+    /// order they are absorbed by the transcript with
+    /// [crate::mvlookup::LookupAbsorptionOrder::ColumnsFirst]. Each chunk of
+    /// a commitment is absorbed. The fixed tables are the ones of the proof,
+    /// not the ones derived from global tables by the verifier. The columns
+    /// of the plookup engine committed before the challenges of the lookup
+    /// argument follow the multiplicities, and the grand products come last.
+    pub fn chunk_schedule(&self) -> Vec<(CommitmentLabel, usize)> {
+        let mut schedule: Vec<(CommitmentLabel, usize)> = (&self.witness_comms)
+            .into_iter()
+            .enumerate()
+            .map(|(i, comm)| (CommitmentLabel::Witness(i), comm.elems.len()))
+            .collect();
+        if let Some(comms) = &self.mvlookup_comms {
+            schedule.extend(comms.m.iter().map(|(id, comm)| {
+                (
+                    CommitmentLabel::LookupMultiplicity(id.to_u32()),
+                    comm.elems.len(),
+                )
+            }));
+            let plookup = |columns: &'static [PlookupColumn]| {
+                comms.plookup.iter().flat_map(move |(id, comms)| {
+                    columns.iter().map(move |column| {
+                        (
+                            CommitmentLabel::Plookup(id.to_u32(), *column),
+                            comms.get(*column).elems.len(),
+                        )
+                    })
+                })
+            };
+            schedule.extend(plookup(&PlookupColumn::FIRST_PHASE));
+            schedule.extend(
+                comms
+                    .h
+                    .iter()
+                    .enumerate()
+                    .map(|(i, comm)| (CommitmentLabel::LookupPartialSum(i), comm.elems.len())),
+            );
+            schedule.extend(comms.fixed_tables.iter().map(|(id, comm)| {
+                (
+                    CommitmentLabel::LookupFixedTable(id.to_u32()),
+                    comm.elems.len(),
+                )
+            }));
+            schedule.extend(
+                comms
+                    .sum
+                    .committed()
+                    .map(|comm| (CommitmentLabel::LookupAggregation, comm.elems.len())),
+            );
+            schedule.extend(plookup(&[PlookupColumn::Product]));
+        }
+        schedule.push((CommitmentLabel::Quotient, self.t_comm.elems.len()));
+        schedule
+    }
+
+    /// The commitment to the partial sum `chunk` of the table `table_id`,
+    /// see [ProofEvaluations::lookup_partial_sum]
+    pub fn lookup_partial_sum_comm(
+        &self,
+        table_id: ID,
+        chunk: usize,
+        layout: &LookupLayout<ID>,
+    ) -> Option<&PolyComm<G>> {
+        self.mvlookup_comms
+            .as_ref()?
+            .partial_sum(table_id, chunk, layout)
+    }
+}
+
+/// Returns the maximum degree of the constraint polynomial.
+/// When the lookup argument has partial sums, the degree is always
+/// [MAX_SUPPORTED_DEGREE], as the lookup constraints are split into partial
+/// sums up to this degree. The constraints of the plookup engine are counted
+/// with the other ones, see [crate::lookup_engine::PlookupEngine].
+pub fn max_constraint_degree<F: Field>(constraints: &[E<F>], with_lookups: bool) -> u64 {
+    if with_lookups {
+        MAX_SUPPORTED_DEGREE as u64
+    } else {
+        constraints
+            .iter()
+            .map(|expr| expr.degree(1, 0))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Returns the number of chunks of the commitment to the quotient polynomial
+/// `t(X)` for constraints of degree `max_degree` over `domain`.
+/// The constraint polynomial `C(X)` has degree at most `max_degree * (n - 1)`
+/// where `n` is the size of `domain.d1`, therefore `t(X) = C(X) / Z_H(X)` has
+/// degree strictly less than `(max_degree - 1) * n` and it is committed in
+/// chunks of size `n`. At least one chunk is always committed.
+pub fn expected_quotient_chunks<F: FftField>(
+    max_degree: u64,
+    domain: EvaluationDomains<F>,
+) -> usize {
+    let n = domain.d1.size as usize;
+    let quotient_size = std::cmp::max(1, max_degree.saturating_sub(1) as usize) * n;
+    (quotient_size + n - 1) / n
+}
+
+/// Returns the number of chunks of the commitment to a column over a domain
+/// of size `domain_size` with an SRS of size `srs_size`, i.e. the number of
+/// SRS-sized pieces of a polynomial of degree less than `domain_size`.
+/// The chunks of the quotient polynomial, see [expected_quotient_chunks], are
+/// columns in that sense.
+pub fn expected_column_chunks(domain_size: usize, srs_size: usize) -> usize {
+    std::cmp::max(1, (domain_size + srs_size - 1) / srs_size)
+}
+
+/// A commitment of a proof, see [ProofCommitments::chunk_schedule]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentLabel {
+    Witness(usize),
+    LookupMultiplicity(u32),
+    LookupPartialSum(usize),
+    LookupAggregation,
+    LookupFixedTable(u32),
+    Plookup(u32, PlookupColumn),
+    Quotient,
+}
+
+impl std::fmt::Display for CommitmentLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitmentLabel::Witness(i) => write!(f, "witness column {i}"),
+            CommitmentLabel::LookupMultiplicity(id) => {
+                write!(f, "multiplicities of the table {id}")
+            }
+            CommitmentLabel::LookupPartialSum(i) => write!(f, "partial sum {i}"),
+            CommitmentLabel::LookupAggregation => write!(f, "lookup aggregation"),
+            CommitmentLabel::LookupFixedTable(id) => write!(f, "fixed table {id}"),
+            CommitmentLabel::Plookup(id, column) => write!(f, "{column} of the table {id}"),
+            CommitmentLabel::Quotient => write!(f, "quotient"),
+        }
+    }
+}
+
+/// The first message of the prover, i.e. the commitments to the witness
+/// columns and to the multiplicities of the lookup argument, see
+/// [crate::prover::prove_first_round].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "G: ark_serialize::CanonicalDeserialize + ark_serialize::CanonicalSerialize")]
+pub struct FirstRoundMessage<G: KimchiCurve, ID: LookupTableID> {
+    /// Commitments to the witness columns
+    pub witness_comms: Vec<PolyComm<G>>,
+    /// Commitments to the multiplicities, for each fixed lookup table
+    #[serde(with = "crate::mvlookup::serde_table_map")]
+    pub lookup_counters_comms: BTreeMap<ID, PolyComm<G>>,
+}
+
+/// A digest of the canonical serialization of a value, for the proof types
+/// whose field and group elements can not be hashed with [std::hash::Hash].
+/// Two values are equal if and only if their stable hashes are, up to the
+/// collisions of Blake2b, and the digest does not depend on the platform.
+pub trait StableHash: Serialize {
+    /// The first 32 bytes of the Blake2b digest of the MessagePack of the
+    /// value, which encodes the field and group elements canonically
+    fn stable_hash(&self) -> [u8; 32] {
+        let bytes = rmp_serde::to_vec(self).expect("The proof types can always be serialized");
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&Blake2b512::digest(bytes)[..32]);
+        digest
+    }
+}
+
+impl<const N: usize, T> StableHash for Witness<N, T> where Self: Serialize {}
+
+impl<T, ID: LookupTableID, C> StableHash for LookupProof<T, ID, C> where Self: Serialize {}
+
+impl<const N: usize, F, ID: LookupTableID, Eval: EvaluationContainer<F>> StableHash
+    for ProofEvaluations<N, F, ID, Eval>
+where
+    Self: Serialize,
+{
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> StableHash for ProofCommitments<N, G, ID> {}
+
+/// The values of the output cells of the witness, see
+/// [crate::config::ProtocolConfig::output_cells], in the order of the cells
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+pub struct PublicOutputs<F>(#[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")] pub Vec<F>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    bound(
+        serialize = "OpeningProof: Serialize",
+        deserialize = "OpeningProof: Deserialize<'de>"
+    ),
+    try_from = "EncodedProof<N, G, OpeningProof>"
+)]
+pub struct Proof<
+    const N: usize,
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField> = PointEvaluations<<G as AffineCurve>::ScalarField>,
+> {
     pub(crate) proof_comms: ProofCommitments<N, G, ID>,
-    pub(crate) proof_evals: ProofEvaluations<N, G::ScalarField, ID>,
+    pub(crate) proof_evals: ProofEvaluations<N, G::ScalarField, ID, Eval>,
     pub(crate) opening_proof: OpeningProof,
+    /// The label of the SRS, if it is part of the transcript, see
+    /// [crate::config::ProtocolConfig::srs_label]
+    pub(crate) srs_label: Option<SrsLabel>,
+    /// The values of the output cells, bound to the witness by the verifier
+    pub(crate) public_outputs: PublicOutputs<G::ScalarField>,
+    /// The boundary of the running sum of the lookup argument, when the proof
+    /// is chained with others, see
+    /// [crate::prover::prove_with_context_and_accumulator]
+    pub(crate) accumulator: Option<AccumulatorBoundary<G::ScalarField>>,
+    /// The opening proof of the evaluations at ζω² of the columns read two
+    /// rows ahead, if any, see [crate::shifts]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) shifted_opening: Option<OpeningProof>,
+}
+
+/// The encoding of a [Proof], with its maps kept as the list of their
+/// entries, see [Decode]. The fields added to the proof after its first
+/// version are optional.
+#[derive(Deserialize)]
+#[serde(bound = "OpeningProof: Deserialize<'de>")]
+pub(crate) struct EncodedProof<const N: usize, G: KimchiCurve, OpeningProof> {
+    proof_comms: EncodedProofCommitments<N, G>,
+    proof_evals: SerializableProofEvaluations<N, G::ScalarField>,
+    opening_proof: OpeningProof,
+    #[serde(default)]
+    srs_label: Option<SrsLabel>,
+    #[serde(default)]
+    public_outputs: PublicOutputs<G::ScalarField>,
+    #[serde(default)]
+    accumulator: Option<AccumulatorBoundary<G::ScalarField>>,
+    #[serde(default)]
+    shifted_opening: Option<OpeningProof>,
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > TryFrom<EncodedProof<N, G, OpeningProof>> for Proof<N, G, OpeningProof, ID, Eval>
+{
+    type Error = DeserializeError;
+
+    fn try_from(proof: EncodedProof<N, G, OpeningProof>) -> Result<Self, Self::Error> {
+        Ok(Proof {
+            proof_comms: proof.proof_comms.try_into()?,
+            proof_evals: proof.proof_evals.try_into()?,
+            opening_proof: proof.opening_proof,
+            srs_label: proof.srs_label,
+            public_outputs: proof.public_outputs,
+            accumulator: proof.accumulator,
+            shifted_opening: proof.shifted_opening,
+        })
+    }
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G> + DeserializeOwned,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > Decode for Proof<N, G, OpeningProof, ID, Eval>
+{
+    type Encoded = EncodedProof<N, G, OpeningProof>;
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > StableHash for Proof<N, G, OpeningProof, ID, Eval>
+where
+    Self: Serialize,
+{
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G>,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > Proof<N, G, OpeningProof, ID, Eval>
+{
+    /// The claimed sum of the partial sums of the lookup argument, when the
+    /// proof is created in the [crate::mvlookup::LookupAggregationMode::Claimed]
+    /// mode. The verifier does not check it, see
+    /// [crate::mvlookup::LookupAggregation].
+    pub fn claimed_lookup_sum(&self) -> Option<G::ScalarField> {
+        self.proof_comms
+            .mvlookup_comms
+            .as_ref()
+            .and_then(|comms| comms.claimed_sum().copied())
+    }
+
+    /// The label of the SRS the proof is created with, if it is part of the
+    /// transcript
+    pub fn srs_label(&self) -> Option<&SrsLabel> {
+        self.srs_label.as_ref()
+    }
+
+    /// The values of the output cells of the configuration the proof is
+    /// created with
+    pub fn public_outputs(&self) -> &PublicOutputs<G::ScalarField> {
+        &self.public_outputs
+    }
+
+    /// The boundary of the running sum of the lookup argument, when the proof
+    /// is chained with others
+    pub fn accumulator(&self) -> Option<&AccumulatorBoundary<G::ScalarField>> {
+        self.accumulator.as_ref()
+    }
+
+    /// Check that the lookup argument is given both in the commitments and
+    /// in the evaluations, for the same columns. The commitments to the fixed
+    /// tables are omitted with global tables, see
+    /// [crate::prover::ProverContext::with_global_tables].
+    pub(crate) fn check_lookup_shape(&self) -> Result<(), &'static str> {
+        match (
+            &self.proof_comms.mvlookup_comms,
+            &self.proof_evals.mvlookup_evals,
+        ) {
+            (None, None) => Ok(()),
+            (Some(comms), Some(evals)) => {
+                if comms.m.keys().eq(evals.m.keys())
+                    && comms.h.len() == evals.h.len()
+                    && comms.aggregation_mode() == evals.aggregation_mode()
+                    && (comms.fixed_tables.is_empty()
+                        || comms.fixed_tables.keys().eq(evals.fixed_tables.keys()))
+                    && comms.plookup.keys().eq(evals.plookup.keys())
+                {
+                    Ok(())
+                } else {
+                    Err("the commitments and the evaluations of the lookup argument have different shapes")
+                }
+            }
+            _ => Err("the lookup argument is only given in the commitments or in the evaluations"),
+        }
+    }
+
+    /// The fixed tables which are active in the proof, i.e. whose
+    /// multiplicities are committed, see [crate::mvlookup::TablePolicy]
+    pub fn active_tables(&self) -> BTreeSet<ID> {
+        self.proof_comms
+            .mvlookup_comms
+            .as_ref()
+            .map(|comms| comms.m.keys().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serde_shifted_evals, Proof, ProofEvaluations, StableHash};
+    use crate::{
+        columns::{Column, PartialSumIdx, TableIdx},
+        config::SrsLabel,
+        fixtures::{FixtureConfig, FixtureTables, N_COLUMNS},
+        fuzz::{decode, Decode, DeserializeError},
+        lookups::LookupTableIDs,
+        mvlookup::{AccumulatorBoundary, LookupAggregation, LookupProof},
+        witness::Witness,
+        BN254G1Affine, Fp, OpeningProof,
+    };
+    use ark_ff::One;
+    use kimchi::{
+        circuits::{
+            expr::{ColumnEvaluations, ExprError},
+            gate::CurrOrNext,
+        },
+        proof::PointEvaluations,
+    };
+    use o1_utils::serialization::SerdeAs;
+    use serde::{Deserialize, Serialize, Serializer};
+    use serde_with::ser::SerializeAsWrap;
+    use std::{collections::BTreeMap, marker::PhantomData, ops::Neg};
+
+    type FixtureProof = Proof<N_COLUMNS, BN254G1Affine, OpeningProof, FixtureTables>;
+    type Mutation = fn(&mut FixtureProof);
+
+    #[test]
+    fn test_proof_equality_and_stable_hash() {
+        let fixture = FixtureConfig::TwoTables.generate().unwrap();
+        let proof: FixtureProof = fixture.decode_proof().unwrap();
+
+        // Serialization round trip
+        let bytes = rmp_serde::to_vec(&proof).unwrap();
+        let decoded: FixtureProof = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert_eq!(decoded.stable_hash(), proof.stable_hash());
+        assert_eq!(
+            decoded.proof_comms.stable_hash(),
+            proof.proof_comms.stable_hash()
+        );
+        assert_eq!(
+            decoded.proof_evals.stable_hash(),
+            proof.proof_evals.stable_hash()
+        );
+
+        let one = Fp::one();
+        let mutations: Vec<(&str, Mutation)> = vec![
+            ("witness commitment", |proof| {
+                let comm = &mut proof.proof_comms.witness_comms.cols[1];
+                comm.elems[0] = comm.elems[0].neg();
+            }),
+            ("multiplicity commitment", |proof| {
+                let comms = proof.proof_comms.mvlookup_comms.as_mut().unwrap();
+                let comm = comms.m.values_mut().next().unwrap();
+                comm.elems[0] = comm.elems[0].neg();
+            }),
+            ("partial sum commitment", |proof| {
+                let comms = proof.proof_comms.mvlookup_comms.as_mut().unwrap();
+                comms.h[0].elems[0] = comms.h[0].elems[0].neg();
+            }),
+            ("aggregation commitment", |proof| {
+                let comms = proof.proof_comms.mvlookup_comms.as_mut().unwrap();
+                let LookupAggregation::Committed(comm) = &mut comms.sum else {
+                    panic!("the fixture commits to the aggregation")
+                };
+                comm.elems[0] = comm.elems[0].neg();
+            }),
+            ("chunk of the quotient commitment", |proof| {
+                let comm = &mut proof.proof_comms.t_comm;
+                comm.elems.push(comm.elems[0]);
+            }),
+            ("witness evaluation", |proof| {
+                proof.proof_evals.witness_evals.cols[0].zeta_omega += Fp::one();
+            }),
+            ("multiplicity evaluation", |proof| {
+                let evals = proof.proof_evals.mvlookup_evals.as_mut().unwrap();
+                evals.m.values_mut().next().unwrap().zeta += Fp::one();
+            }),
+            ("fixed table evaluation", |proof| {
+                let evals = proof.proof_evals.mvlookup_evals.as_mut().unwrap();
+                evals.fixed_tables.values_mut().next().unwrap().zeta += Fp::one();
+            }),
+            ("quotient evaluation", |proof| {
+                *proof.proof_evals.ft_eval1.as_mut().unwrap() += Fp::one();
+            }),
+            ("opening quotient", |proof| {
+                proof.opening_proof.quotient = proof.opening_proof.quotient.neg();
+            }),
+            ("opening blinding", |proof| {
+                proof.opening_proof.blinding += Fp::one();
+            }),
+            ("SRS label", |proof| {
+                proof.srs_label = Some(SrsLabel(vec![0]));
+            }),
+            ("public outputs", |proof| {
+                proof.public_outputs.0.push(Fp::one());
+            }),
+            ("accumulator", |proof| {
+                proof.accumulator = Some(AccumulatorBoundary {
+                    incoming: Fp::one(),
+                    outgoing: -Fp::one(),
+                });
+            }),
+        ];
+        for (field, mutate) in mutations {
+            let mut mutated = proof.clone();
+            mutate(&mut mutated);
+            assert_ne!(mutated, proof, "{field}");
+            assert_ne!(mutated.stable_hash(), proof.stable_hash(), "{field}");
+        }
+
+        // The hashes of the components
+        let mut mutated = proof.clone();
+        mutated.proof_comms.witness_comms.cols[0].elems[0] =
+            mutated.proof_comms.witness_comms.cols[0].elems[0].neg();
+        assert_ne!(
+            mutated.proof_comms.witness_comms.stable_hash(),
+            proof.proof_comms.witness_comms.stable_hash()
+        );
+        assert_ne!(
+            mutated.proof_comms.stable_hash(),
+            proof.proof_comms.stable_hash()
+        );
+        let mut mutated = proof.clone();
+        let comms = mutated.proof_comms.mvlookup_comms.as_mut().unwrap();
+        comms.h[0].elems[0] = comms.h[0].elems[0].neg();
+        assert_ne!(
+            mutated.proof_comms.mvlookup_comms.unwrap().stable_hash(),
+            proof
+                .proof_comms
+                .mvlookup_comms
+                .as_ref()
+                .unwrap()
+                .stable_hash()
+        );
+        let mut mutated = proof.clone();
+        mutated.proof_evals.mvlookup_evals.as_mut().unwrap().h[0].zeta += one;
+        assert_ne!(
+            mutated.proof_evals.stable_hash(),
+            proof.proof_evals.stable_hash()
+        );
+    }
+
+    /// The shifted evaluations of 4 columns
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(try_from = "EncodedShiftedEvals")]
+    struct ShiftedEvals(BTreeMap<usize, Fp>);
+
+    #[derive(Deserialize)]
+    struct EncodedShiftedEvals(
+        #[serde(deserialize_with = "serde_shifted_evals::deserialize::<4, _, _>")] Vec<(usize, Fp)>,
+    );
+
+    impl Serialize for ShiftedEvals {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let entries: Vec<(usize, Fp)> = self.0.clone().into_iter().collect();
+            serde_shifted_evals::serialize(&entries, serializer)
+        }
+    }
+
+    impl TryFrom<EncodedShiftedEvals> for ShiftedEvals {
+        type Error = DeserializeError;
+
+        fn try_from(
+            EncodedShiftedEvals(entries): EncodedShiftedEvals,
+        ) -> Result<Self, Self::Error> {
+            serde_shifted_evals::into_map(entries).map(ShiftedEvals)
+        }
+    }
+
+    impl Decode for ShiftedEvals {
+        type Encoded = EncodedShiftedEvals;
+    }
+
+    /// The entries of a map, encoded in their order, duplicates included
+    struct MapEntries(Vec<(usize, Fp)>);
+
+    impl Serialize for MapEntries {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(
+                self.0
+                    .iter()
+                    .map(|(i, v)| (i, SerializeAsWrap::<Fp, SerdeAs>::new(v))),
+            )
+        }
+    }
+
+    #[test]
+    fn test_non_canonical_maps() {
+        type Lookups = LookupProof<u64, LookupTableIDs>;
+        let decode_lookups = |m: Vec<(u32, u64)>| {
+            let sum: LookupAggregation<u64, PhantomData<()>> = LookupAggregation::Committed(0);
+            let bytes = rmp_serde::to_vec(&(m, vec![0u64], sum, vec![(1u32, 0u64)])).unwrap();
+            decode::<Lookups>(bytes.as_slice())
+        };
+
+        // The canonical encoding round trips
+        let lookups = decode_lookups(vec![(1, 5), (3, 6)]).unwrap();
+        assert_eq!(
+            lookups.m,
+            BTreeMap::from([
+                (LookupTableIDs::RangeCheck16, 5),
+                (LookupTableIDs::Custom(0), 6)
+            ])
+        );
+        let bytes = rmp_serde::to_vec(&lookups).unwrap();
+        assert_eq!(rmp_serde::from_slice::<Lookups>(&bytes).unwrap(), lookups);
+        // Duplicate and unsorted table IDs
+        for m in [vec![(1, 5), (1, 6)], vec![(3, 6), (1, 5)]] {
+            assert!(matches!(
+                decode_lookups(m),
+                Err(DeserializeError::NonCanonicalMap(_))
+            ));
+        }
+        // The table IDs are bounded
+        assert!(matches!(
+            decode_lookups(vec![(1, 5), (crate::mvlookup::MAX_TABLE_ID, 6)]),
+            Err(DeserializeError::Decoding(_))
+        ));
+
+        let decode_shifted = |entries: Vec<(usize, Fp)>| {
+            let bytes = rmp_serde::to_vec(&MapEntries(entries)).unwrap();
+            decode::<ShiftedEvals>(bytes.as_slice())
+        };
+        let one = Fp::one();
+        let shifted = decode_shifted(vec![(0, one), (3, -one)]).unwrap();
+        assert_eq!(shifted.0, BTreeMap::from([(0, one), (3, -one)]));
+        let bytes = rmp_serde::to_vec(&shifted).unwrap();
+        assert_eq!(
+            rmp_serde::from_slice::<ShiftedEvals>(&bytes).unwrap(),
+            shifted
+        );
+        for entries in [vec![(1, one), (1, -one)], vec![(2, one), (0, -one)]] {
+            assert!(matches!(
+                decode_shifted(entries),
+                Err(DeserializeError::NonCanonicalMap(_))
+            ));
+        }
+        // The columns are bounded by the number of columns, as is the length
+        // of the map
+        assert!(matches!(
+            decode_shifted(vec![(4, one)]),
+            Err(DeserializeError::Decoding(_))
+        ));
+        assert!(matches!(
+            decode_shifted((0..5).map(|i| (i, one)).collect()),
+            Err(DeserializeError::Decoding(_))
+        ));
+    }
+
+    /// The columns missing from the evaluations of a proof without lookups
+    /// are errors for the verifier
+    #[test]
+    fn test_evaluate_missing_columns() {
+        let point = PointEvaluations {
+            zeta: Fp::one(),
+            zeta_omega: -Fp::one(),
+        };
+        let evals: ProofEvaluations<2, Fp, LookupTableIDs> = ProofEvaluations {
+            witness_evals: Witness {
+                cols: Box::new([point, point]),
+            },
+            mvlookup_evals: None,
+            ft_eval1: None,
+            shifted_evals: BTreeMap::new(),
+        };
+        assert_eq!(evals.evaluate(Column::X(1)).unwrap(), point);
+        for column in [
+            Column::X(2),
+            Column::LookupAggregation,
+            Column::LookupPartialSum(PartialSumIdx::new(0)),
+            Column::XShift2(0),
+        ] {
+            assert!(matches!(
+                evals.evaluate(column),
+                Err(ExprError::MissingEvaluation(col, CurrOrNext::Curr)) if col == column
+            ));
+        }
+        let table = TableIdx::new(1);
+        for column in [
+            Column::LookupMultiplicity(table),
+            Column::LookupFixedTable(table),
+        ] {
+            assert!(matches!(
+                evals.evaluate(column),
+                Err(ExprError::MissingLookupTable(1))
+            ));
+        }
+    }
 }
@@ -0,0 +1,376 @@
+//! A header prepended to the serialized proofs, describing the circuit they
+//! prove, for the tooling to route the proofs to their [VerifierContext]
+//! without deserializing them, see [peek_header].
+//!
+//! The header, written by [Proof::serialize_with_header], is made of:
+//! - the magic bytes [HEADER_MAGIC],
+//! - the version of the header, as a little-endian `u16`,
+//! - the number of witness columns `N`, as a little-endian `u32`,
+//! - the base 2 logarithm of the size of the domain, as a byte,
+//! - the number of lookup tables, as a little-endian `u32`, followed by their
+//!   IDs, see [LookupTableID::to_u32], in increasing order,
+//! - the digest of the circuit, see [VerifierContext::digest].
+//!
+//! The proof follows, in MessagePack.
+//!
+//! The header is untrusted: [VerifierContext::verify_with_header] checks it
+//! against the context before decoding the proof.
+
+use crate::{
+    mvlookup::LookupTableID,
+    proof::{EvaluationContainer, Proof},
+    verifier::{VerifierContext, VerifierError},
+    witness::Witness,
+};
+use kimchi::{curve::KimchiCurve, plonk_sponge::FrSponge};
+use mina_poseidon::FqSponge;
+use poly_commitment::OpenProof;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+/// The bytes starting the proofs serialized with a header
+pub const HEADER_MAGIC: [u8; 4] = *b"MSMP";
+
+/// The version of the header written by [Proof::serialize_with_header]
+pub const HEADER_VERSION: u16 = 1;
+
+/// Errors that can arise when reading the header of a proof, see
+/// [peek_header]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    #[error("the proof does not start with a header")]
+    MissingMagic,
+
+    #[error("the version {0} of the header is not supported")]
+    UnsupportedVersion(u16),
+
+    #[error("the header is truncated")]
+    Truncated,
+
+    #[error("the lookup tables of the header are not in increasing order")]
+    UnsortedTables,
+}
+
+/// The description of the circuit of a proof, see the
+/// [module documentation](crate::proof_header)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofHeader {
+    pub version: u16,
+    /// The number of witness columns
+    pub n_columns: usize,
+    /// The base 2 logarithm of the size of the domain
+    pub domain_log_size: u8,
+    /// The IDs of the lookup tables of the proof, in increasing order
+    pub table_ids: Vec<u32>,
+    /// The digest of the circuit, see [VerifierContext::digest]
+    pub circuit_digest: [u8; 32],
+}
+
+impl ProofHeader {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(47 + 4 * self.table_ids.len());
+        bytes.extend(HEADER_MAGIC);
+        bytes.extend(self.version.to_le_bytes());
+        bytes.extend((self.n_columns as u32).to_le_bytes());
+        bytes.push(self.domain_log_size);
+        bytes.extend((self.table_ids.len() as u32).to_le_bytes());
+        self.table_ids
+            .iter()
+            .for_each(|id| bytes.extend(id.to_le_bytes()));
+        bytes.extend(self.circuit_digest);
+        bytes
+    }
+
+    /// Decode the header at the start of `bytes`, and return it with the
+    /// rest of the bytes
+    fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), HeaderError> {
+        fn take<const K: usize>(bytes: &mut &[u8]) -> Result<[u8; K], HeaderError> {
+            if bytes.len() < K {
+                return Err(HeaderError::Truncated);
+            }
+            let (value, rest) = bytes.split_at(K);
+            *bytes = rest;
+            Ok(value.try_into().unwrap())
+        }
+
+        let mut bytes = bytes;
+        if bytes.len() < HEADER_MAGIC.len() || take(&mut bytes)? != HEADER_MAGIC {
+            return Err(HeaderError::MissingMagic);
+        }
+        let version = u16::from_le_bytes(take(&mut bytes)?);
+        if version != HEADER_VERSION {
+            return Err(HeaderError::UnsupportedVersion(version));
+        }
+        let n_columns = u32::from_le_bytes(take(&mut bytes)?) as usize;
+        let [domain_log_size] = take(&mut bytes)?;
+        let n_tables = u32::from_le_bytes(take(&mut bytes)?) as usize;
+        // The number of tables is untrusted, and bounded by the size of the
+        // input before allocating
+        if bytes.len() / 4 < n_tables {
+            return Err(HeaderError::Truncated);
+        }
+        let table_ids = (0..n_tables)
+            .map(|_| take(&mut bytes).map(u32::from_le_bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+        if table_ids.windows(2).any(|ids| ids[0] >= ids[1]) {
+            return Err(HeaderError::UnsortedTables);
+        }
+        let circuit_digest = take(&mut bytes)?;
+        let header = ProofHeader {
+            version,
+            n_columns,
+            domain_log_size,
+            table_ids,
+            circuit_digest,
+        };
+        Ok((header, bytes))
+    }
+
+    /// Check the header against the context `ctx` the proof is verified with,
+    /// and the number of witness columns `n_columns` of the verifier
+    pub fn check<G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID>(
+        &self,
+        ctx: &VerifierContext<G, OpeningProof, ID>,
+        n_columns: usize,
+    ) -> Result<(), VerifierError>
+    where
+        OpeningProof::SRS: Sync,
+    {
+        if self.n_columns != n_columns {
+            return Err(VerifierError::HeaderMismatch("number of columns"));
+        }
+        if u32::from(self.domain_log_size) != ctx.domain().d1.log_size_of_group {
+            return Err(VerifierError::HeaderMismatch("domain size"));
+        }
+        let mut table_ids: Vec<u32> = ctx.lookup_layout().map_or(vec![], |layout| {
+            layout.table_ids().map(|id| id.to_u32()).collect()
+        });
+        table_ids.sort();
+        if self.table_ids != table_ids {
+            return Err(VerifierError::HeaderMismatch("lookup tables"));
+        }
+        if self.circuit_digest != ctx.digest() {
+            return Err(VerifierError::HeaderMismatch("circuit digest"));
+        }
+        Ok(())
+    }
+}
+
+/// Read the header of a proof serialized with
+/// [Proof::serialize_with_header], without decoding the proof
+pub fn peek_header(bytes: &[u8]) -> Result<ProofHeader, HeaderError> {
+    ProofHeader::decode(bytes).map(|(header, _)| header)
+}
+
+impl<
+        const N: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G> + Serialize,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    > Proof<N, G, OpeningProof, ID, Eval>
+{
+    /// Serialize the proof in MessagePack, after a header describing the
+    /// circuit of `ctx`, see the [module documentation](crate::proof_header)
+    pub fn serialize_with_header(&self, ctx: &VerifierContext<G, OpeningProof, ID>) -> Vec<u8>
+    where
+        OpeningProof::SRS: Sync,
+    {
+        let mut table_ids: Vec<u32> = self
+            .proof_evals
+            .mvlookup_evals
+            .as_ref()
+            .map_or(vec![], |lookup| {
+                lookup.tables().map(|id| id.to_u32()).collect()
+            });
+        table_ids.sort();
+        let header = ProofHeader {
+            version: HEADER_VERSION,
+            n_columns: N,
+            domain_log_size: ctx.domain().d1.log_size_of_group as u8,
+            table_ids,
+            circuit_digest: ctx.digest(),
+        };
+        let mut bytes = header.encode();
+        bytes.extend(rmp_serde::to_vec(self).expect("The proof can always be serialized"));
+        bytes
+    }
+}
+
+impl<'a, G: KimchiCurve, OpeningProof: OpenProof<G> + DeserializeOwned, ID: LookupTableID>
+    VerifierContext<'a, G, OpeningProof, ID>
+where
+    OpeningProof::SRS: Sync,
+{
+    /// Verify a proof serialized with [Proof::serialize_with_header], like
+    /// [VerifierContext::verify]. The header is checked against the context
+    /// before the proof is decoded, see [ProofHeader::check].
+    pub fn verify_with_header<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        &self,
+        bytes: &[u8],
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    ) -> Result<(), VerifierError> {
+        let deserialization = VerifierError::Deserialization;
+        let (header, body) =
+            ProofHeader::decode(bytes).map_err(|err| deserialization(err.to_string()))?;
+        header.check(self, N)?;
+        let proof: Proof<N, G, OpeningProof, ID, Eval> =
+            rmp_serde::from_slice(body).map_err(|err| deserialization(err.to_string()))?;
+        self.verify::<EFqSponge, EFrSponge, N, NPUB, Eval>(&proof, public_inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        columns::Column,
+        config::ProtocolConfig,
+        expr::{curr_cell, E},
+        lookups::LookupTableIDs,
+        prover::prove,
+        test::random_lookup_circuit,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{Field, UniformRand};
+    use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+    use poly_commitment::pairing_proof::PairingSRS;
+    use std::collections::BTreeMap;
+
+    const N: usize = 4;
+
+    type Ctx<'a> = VerifierContext<'a, BN254G1Affine, OpeningProof, LookupTableIDs>;
+
+    fn verify(ctx: &Ctx, bytes: &[u8]) -> Result<(), VerifierError> {
+        ctx.verify_with_header::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            bytes,
+            Witness::zero_vec(1),
+        )
+    }
+
+    #[test]
+    fn test_proof_header_routing() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let mut srs: PairingSRS<BN254> =
+            PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        // Two circuits of the same number of columns: one looking up its
+        // first two columns, and one with X(1) = X(0)^2
+        let (lookup_constraints, lookup_inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 8, 2, &mut rng);
+        let (mut square_constraints, mut square_inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 8, 0, &mut rng);
+        square_constraints.clear();
+        let x0 = curr_cell::<Fp>(Column::X(0));
+        square_constraints.push(curr_cell::<Fp>(Column::X(1)) - x0.clone() * x0);
+        let cols = &mut square_inputs.evaluations.cols;
+        cols[1] = cols[0].iter().map(|x| x.square()).collect();
+        square_inputs.mvlookups.clear();
+
+        let circuits: Vec<(Vec<E<Fp>>, _)> = vec![
+            (lookup_constraints, lookup_inputs),
+            (square_constraints, square_inputs),
+        ];
+        let contexts: Vec<Ctx> = circuits
+            .iter()
+            .map(|(constraints, _)| {
+                Ctx::new(domain, &srs, ProtocolConfig::default(), constraints).unwrap()
+            })
+            .collect();
+        let registry: BTreeMap<[u8; 32], &Ctx> =
+            contexts.iter().map(|ctx| (ctx.digest(), ctx)).collect();
+        assert_eq!(registry.len(), 2);
+
+        let proofs: Vec<Vec<u8>> = circuits
+            .into_iter()
+            .zip(&contexts)
+            .map(|((constraints, inputs), ctx)| {
+                let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+                    domain,
+                    &srs,
+                    &constraints,
+                    inputs,
+                    &mut rng,
+                )
+                .unwrap();
+                proof.serialize_with_header(ctx)
+            })
+            .collect();
+
+        let expected_tables = [vec![LookupTableIDs::Custom(1).to_u32()], vec![]];
+        for (bytes, expected_tables) in proofs.iter().zip(expected_tables) {
+            // The header is read without decoding the proof, and routes it to
+            // its context
+            let header = peek_header(bytes).unwrap();
+            assert_eq!(header.n_columns, N);
+            assert_eq!(header.domain_log_size, 4);
+            assert_eq!(header.table_ids, expected_tables);
+            verify(registry[&header.circuit_digest], bytes).unwrap();
+        }
+
+        // The header is checked against the context it is verified with
+        assert_eq!(
+            verify(&contexts[1], &proofs[0]),
+            Err(VerifierError::HeaderMismatch("lookup tables"))
+        );
+
+        // A header claiming the wrong digest is rejected before the proof is
+        // decoded, here from bytes which are not a proof
+        let (mut header, _) = ProofHeader::decode(&proofs[1]).unwrap();
+        header.circuit_digest[0] ^= 1;
+        let mut forged = header.encode();
+        forged.extend(b"not a proof");
+        assert_eq!(
+            verify(&contexts[1], &forged),
+            Err(VerifierError::HeaderMismatch("circuit digest"))
+        );
+    }
+
+    #[test]
+    fn test_peek_header_errors() {
+        let header = ProofHeader {
+            version: HEADER_VERSION,
+            n_columns: N,
+            domain_log_size: 4,
+            table_ids: vec![1, 4],
+            circuit_digest: [7; 32],
+        };
+        let bytes = header.encode();
+        assert_eq!(peek_header(&bytes), Ok(header.clone()));
+
+        assert_eq!(peek_header(b"MSM"), Err(HeaderError::MissingMagic));
+        assert_eq!(peek_header(b"\x93\x01"), Err(HeaderError::MissingMagic));
+        assert_eq!(
+            peek_header(&bytes[..bytes.len() - 1]),
+            Err(HeaderError::Truncated)
+        );
+        let unsupported = ProofHeader {
+            version: HEADER_VERSION + 1,
+            ..header.clone()
+        };
+        assert_eq!(
+            peek_header(&unsupported.encode()),
+            Err(HeaderError::UnsupportedVersion(HEADER_VERSION + 1))
+        );
+        let unsorted = ProofHeader {
+            table_ids: vec![4, 1],
+            ..header.clone()
+        };
+        assert_eq!(
+            peek_header(&unsorted.encode()),
+            Err(HeaderError::UnsortedTables)
+        );
+        // The number of tables is bounded by the input
+        let mut oversized = header.encode();
+        oversized[11..15].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(peek_header(&oversized), Err(HeaderError::Truncated));
+    }
+}
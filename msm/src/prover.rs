@@ -1,16 +1,49 @@
 use crate::{
-    column_env::ColumnEnvironment,
-    expr::E,
+    access_patterns::analyze_access_patterns,
+    audit::{AuditError, ProofAuditor},
+    boundary,
+    checkpoint::{self, CheckpointError, SpongeSnapshot},
+    column_env::{divide_by_vanishing_poly_on_coset, evaluate_over_coset, ColumnEnvironment},
+    column_permutation::{ColumnPermutation, PermutationError},
+    columns::Column,
+    committer::{ColumnCommitter, ColumnCommitters, ColumnDescriptor},
+    config::{
+        check_joint_combiner, ConfigError, DegeneratePointRule, OpeningMode, ProtocolConfig,
+        SrsLabel,
+    },
+    constant_columns::ConstantColumns,
+    cost::TranscriptLayout,
+    expr::{column_accesses, NamedConstraint, E},
+    lookup_engine::{LookupEngineError, LookupEngineKind, LookupEngines},
+    lookup_stats::{LookupStats, LookupStatsObserver, ProofCounts},
     mvlookup,
-    mvlookup::{prover::Env, LookupProof, LookupTableID},
-    proof::{Proof, ProofCommitments, ProofEvaluations, ProofInputs},
+    mvlookup::{
+        prover::Env, AggregationDirection, GlobalTableCommitments, LookupAbsorptionOrder,
+        LookupAggregation, LookupAggregationMode, LookupProof, LookupQueryError, LookupTableID,
+        MVLookup, MVLookupWitness, TableIdImplError,
+    },
+    perf::PerfCounters,
+    proof::{
+        expected_column_chunks, expected_quotient_chunks, max_constraint_degree,
+        EvaluationContainer, FirstRoundMessage, Proof, ProofCommitments, ProofEvaluations,
+        ProofInputs, PublicOutputs,
+    },
+    shifts::{self, EvaluationShifts},
+    subdomain,
+    subdomain::SubdomainLayout,
+    transcript::{
+        CountingFrSponge, CountingSponge, Transcript, TranscriptBackend, TranscriptCounter,
+        TranscriptProfile, STRICT_TRANSCRIPT,
+    },
+    verifier::VerifierContext,
     witness::Witness,
     MAX_SUPPORTED_DEGREE,
 };
-use ark_ff::{Field, One, Zero};
+use ark_ff::{Field, One, PrimeField, Zero};
 use ark_poly::{
     univariate::DensePolynomial, Evaluations, Polynomial, Radix2EvaluationDomain as R2D,
 };
+use ark_serialize::CanonicalSerialize;
 use kimchi::{
     circuits::{
         domains::EvaluationDomains,
@@ -19,7 +52,6 @@ use kimchi::{
     curve::KimchiCurve,
     groupmap::GroupMap,
     plonk_sponge::FrSponge,
-    proof::PointEvaluations,
 };
 use mina_poseidon::{sponge::ScalarChallenge, FqSponge};
 use o1_utils::ExtendedDensePolynomial;
@@ -28,25 +60,1602 @@ use poly_commitment::{
     evaluation_proof::DensePolynomialOrEvaluations,
     OpenProof, SRS,
 };
-use rand::{CryptoRng, RngCore};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rayon::{
+    iter::{
+        IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+    },
+    ThreadPool,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+    path::Path,
+    sync::Arc,
+};
 use thiserror::Error;
 
-/// Errors that can arise when creating a proof
-#[derive(Error, Debug, Clone)]
-pub enum ProverError {
-    #[error("the proof could not be constructed: {0}")]
-    Generic(&'static str),
+/// Errors that can arise when creating a proof
+#[derive(Error, Debug, Clone)]
+pub enum ProverError {
+    #[error("the proof could not be constructed: {0}")]
+    Generic(&'static str),
+
+    #[error("the provided (witness) constraints was not satisfied: {0}")]
+    ConstraintNotSatisfied(String),
+
+    #[error("the provided (witness) constraint has degree {0} > allowed {1}; expr: {2}")]
+    ConstraintDegreeTooHigh(u64, u64, String),
+
+    #[error("the fixed lookup table {0} is not part of the global table commitments")]
+    MissingGlobalTable(u32),
+
+    #[error("the fixed lookup table {0} does not match the global table commitments")]
+    GlobalTableMismatch(u32),
+
+    #[error("the witness column {0} differs from the one committed in the first phase")]
+    FirstPhaseColumnMismatch(usize),
+
+    #[error("the first round message does not match the state of the prover")]
+    FirstRoundMismatch,
+
+    #[error("the circuit has neither constraints nor lookups")]
+    EmptyCircuit,
+
+    #[error("the inputs have {0} lookup witnesses, but the constraints do not use lookups")]
+    UnusedLookups(usize),
+
+    #[error("the circuit can not be proven with the protocol configuration: {0}")]
+    Config(ConfigError),
+
+    #[error("the column {0} is declared constant but is not")]
+    NonConstantColumn(usize),
+
+    #[error("the column {0} does not have the size of its domain")]
+    ColumnSizeMismatch(usize),
+
+    #[error("the implementation of the table IDs is inconsistent: {0}")]
+    TableId(TableIdImplError),
+
+    #[error("the lookups by reference can not be read out of the witness: {0}")]
+    LookupQuery(LookupQueryError),
+
+    #[error("the evaluation point is zero or in the domain")]
+    DegenerateEvaluationPoint,
+
+    #[error("the proof is not released: {0}")]
+    Audit(AuditError),
+
+    #[error("the witness can not be permuted: {0}")]
+    ColumnPermutation(PermutationError),
+
+    #[error(
+        "the lookup column {column} of the table {table_id} has {got} rows instead of {expected}"
+    )]
+    LookupColumnSizeMismatch {
+        table_id: u32,
+        column: usize,
+        expected: usize,
+        got: usize,
+    },
+
+    #[error("the multiplicities of the table {table_id} have {got} rows instead of {expected}")]
+    MultiplicitiesSizeMismatch {
+        table_id: u32,
+        expected: usize,
+        got: usize,
+    },
+
+    #[error("the witness of the fixed table {0} does not end with the column of the table")]
+    MissingTableColumn(u32),
+
+    #[error("the table {0} has several lookup witnesses")]
+    DuplicateLookupWitness(u32),
+
+    #[error("the table {0} has a lookup witness but is not in the lookup layout")]
+    UnregisteredTable(u32),
+
+    #[error("the table {0} is in the lookup layout but has no lookup witness")]
+    MissingLookupWitness(u32),
+
+    #[error(
+        "the lookups of the table {table_id} need {got} partial sums, the layout has {expected}"
+    )]
+    PartialSumsMismatch {
+        table_id: u32,
+        expected: usize,
+        got: usize,
+    },
+
+    #[error("the transcript does not follow the protocol: {got:?} instead of {expected:?}")]
+    TranscriptProfileMismatch {
+        expected: TranscriptProfile,
+        got: TranscriptProfile,
+    },
+
+    #[error("a value looked up with the plookup engine is not in its table")]
+    UnbalancedLookups,
+
+    /// The lookups of the table `table_id` do not sum to zero over the
+    /// domain. The `residual` is their sum, written as the decimal integer in
+    /// `[0, p)` of its canonical representation, `p` the scalar field modulus.
+    #[error("the running sum of the lookup argument does not end at zero: the lookups of the table {table_id} sum to {residual} over the domain, a value looked up is not in the table or its multiplicity is wrong")]
+    LookupAggregationNonZero { table_id: u32, residual: String },
+
+    #[error("the lookups can not be proven with the engines of their tables: {0}")]
+    LookupEngine(LookupEngineError),
+
+    #[error("an invariant of the validated inputs does not hold: {0}")]
+    Internal(&'static str),
+}
+
+/// Suspicious inputs which do not prevent the creation of a proof, but are
+/// likely to make it fail. They are logged by the prover.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ProverWarning {
+    /// The rows `rows` of the fixed table `table_id` have the same combined
+    /// value. If `structural`, the rows have the same values, up to trailing
+    /// zeroes, i.e. the table has duplicated rows or rows of different widths.
+    /// Otherwise, the joint combiner made distinct rows collide, which is
+    /// astronomically unlikely.
+    #[error("the rows {rows:?} of the table {table_id} have the same combined value (duplicated rows: {structural})")]
+    CombinedValueCollision {
+        table_id: u32,
+        rows: Vec<usize>,
+        structural: bool,
+    },
+}
+
+/// Check the structure of the lookup witnesses `lookups` over a domain of
+/// `domain_size` rows: each witness has a column, a single witness per table,
+/// all the columns and the multiplicities have the size of the domain, and the
+/// witness of a fixed table ends with the column of the table, whose cells
+/// have the ID of the table and the numerators `-m`.
+/// This is the validation of the witnesses given to
+/// [mvlookup::prover::Env::create], which relies on these invariants, and
+/// which is called by [preflight] as well.
+/// It only performs O(rows) comparisons, without MSMs nor FFTs.
+pub fn validate_lookup_witnesses<F: PrimeField, ID: LookupTableID>(
+    lookups: &[MVLookupWitness<F, ID>],
+    domain_size: usize,
+) -> Result<(), ProverError> {
+    let mut table_ids = BTreeSet::new();
+    for lookup in lookups.iter() {
+        let table_id = lookup
+            .table_id()
+            .ok_or(ProverError::Generic("a lookup witness has no column"))?;
+        if !table_ids.insert(table_id) {
+            return Err(ProverError::DuplicateLookupWitness(table_id.to_u32()));
+        }
+        if let Some((column, col)) = lookup
+            .f
+            .iter()
+            .enumerate()
+            .find(|(_, col)| col.len() != domain_size)
+        {
+            return Err(ProverError::LookupColumnSizeMismatch {
+                table_id: table_id.to_u32(),
+                column,
+                expected: domain_size,
+                got: col.len(),
+            });
+        }
+        if lookup.m.len() != domain_size {
+            return Err(ProverError::MultiplicitiesSizeMismatch {
+                table_id: table_id.to_u32(),
+                expected: domain_size,
+                got: lookup.m.len(),
+            });
+        }
+        if table_id.is_fixed() {
+            let is_table_column = lookup.f.last().map_or(false, |table| {
+                table.iter().all(|entry| entry.table_id == table_id)
+            }) && lookup.table_numerator_mismatch().is_none();
+            if !is_table_column {
+                return Err(ProverError::MissingTableColumn(table_id.to_u32()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the structure of `inputs` against the lookup columns of `layout`
+/// before any commitment: the sizes of the witness columns, the lookup
+/// witnesses with [validate_lookup_witnesses], and the tables and numbers of
+/// partial sums of the layout, including the range checks of the
+/// multiplicities added by the prover. An empty layout, e.g. the one of
+/// constraints without the lookup argument, does not check the tables.
+/// It only performs O(rows) comparisons, without MSMs nor FFTs.
+pub fn preflight<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: &ProofInputs<N, G, ID>,
+    layout: &mvlookup::LookupLayout<ID>,
+    domain: EvaluationDomains<G::ScalarField>,
+) -> Result<(), ProverError> {
+    preflight_with_engines(inputs, layout, &LookupEngines::default(), domain)
+}
+
+/// [preflight], where the tables of the plookup engine in `engines` are not
+/// checked against the partial sums of `layout`
+pub(crate) fn preflight_with_engines<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: &ProofInputs<N, G, ID>,
+    layout: &mvlookup::LookupLayout<ID>,
+    engines: &LookupEngines<ID>,
+    domain: EvaluationDomains<G::ScalarField>,
+) -> Result<(), ProverError> {
+    let domain_size = domain.d1.size as usize;
+    if let Some(i) = inputs
+        .evaluations
+        .cols
+        .iter()
+        .position(|col| col.len() != domain_size)
+    {
+        return Err(ProverError::ColumnSizeMismatch(i));
+    }
+    validate_lookup_witnesses(&inputs.mvlookups, domain_size)?;
+
+    // The number of terms of each table, including its column
+    let mut n_terms: BTreeMap<ID, usize> = inputs
+        .mvlookups
+        .iter()
+        .filter_map(|lookup| Some((lookup.table_id()?, lookup.f.len())))
+        .filter(|(table_id, _)| engines.engine(*table_id) == LookupEngineKind::MVLookup)
+        .collect();
+    // The range checks of the multiplicities, see
+    // [mvlookup::add_multiplicity_range_checks]
+    let bounds: Vec<ID> = n_terms
+        .keys()
+        .filter_map(|table_id| table_id.multiplicity_bound())
+        .collect();
+    for bound in bounds {
+        *n_terms.entry(bound).or_insert(1) += 1;
+    }
+
+    if layout.n_partial_sums() == 0 {
+        return Ok(());
+    }
+    let chunk_size = MAX_SUPPORTED_DEGREE - 2;
+    for (table_id, n) in n_terms.iter() {
+        let expected = layout
+            .n_table_partial_sums(*table_id)
+            .ok_or(ProverError::UnregisteredTable(table_id.to_u32()))?;
+        let got = (n + chunk_size - 1) / chunk_size;
+        if got != expected {
+            return Err(ProverError::PartialSumsMismatch {
+                table_id: table_id.to_u32(),
+                expected,
+                got,
+            });
+        }
+    }
+    if let Some(table_id) = layout.table_ids().find(|id| !n_terms.contains_key(id)) {
+        return Err(ProverError::MissingLookupWitness(table_id.to_u32()));
+    }
+    Ok(())
+}
+
+/// The checks of [prove] on `inputs` before any commitment: the witness
+/// columns which are not read by `constraints` are logged, and the inputs are
+/// checked against the lookup columns of the constraints with [preflight].
+pub(crate) fn check_inputs<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    constraints: &[E<G::ScalarField>],
+    inputs: &ProofInputs<N, G, ID>,
+    domain: EvaluationDomains<G::ScalarField>,
+) -> Result<(), ProverError> {
+    // Committing to a column which is not read is usually a bug
+    let unread_columns = analyze_access_patterns(&NamedConstraint::from_indexed(constraints))
+        .unread_witness_columns(N);
+    if !unread_columns.is_empty() {
+        log::warn!("The witness columns {unread_columns:?} are not read by any constraint");
+    }
+    if let Some(layout) = mvlookup::LookupLayout::of_constraints(constraints) {
+        let engines = LookupEngines::of_constraints(constraints);
+        preflight_with_engines(inputs, &layout, &engines, domain)?;
+    }
+    Ok(())
+}
+
+pub fn prove<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    check_inputs(constraints, &inputs, domain)?;
+    prove_internal::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, 0, ID, _>(
+        domain,
+        srs,
+        constraints,
+        vec![],
+        |_| inputs,
+        ProverOptions::default(),
+        rng,
+    )
+}
+
+/// Same as [prove], with the randomness of the prover derived from `seed`, so
+/// that the proof only depends on its inputs. It is used to produce
+/// reproducible proofs, e.g. the test vectors of [crate::fixtures]. The seed
+/// must be kept secret for the proof to be zero-knowledge.
+pub fn prove_deterministic<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    inputs: ProofInputs<N, G, ID>,
+    seed: [u8; 32],
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let mut rng = rand::rngs::StdRng::from_seed(seed);
+    prove::<G, OpeningProof, EFqSponge, EFrSponge, Column, _, N, ID>(
+        domain,
+        srs,
+        constraints,
+        inputs,
+        &mut rng,
+    )
+}
+
+/// Same as [prove], with only the fixed tables of the lookup witnesses of
+/// `inputs` being active, see [mvlookup::TablePolicy]. The constraints are
+/// `constraints` and the ones of the lookups of `lookups_map` into the active
+/// tables.
+#[deprecated(
+    note = "compile the constraints with crate::mvlookup::TablePolicy::compile_constraints, and use prove_with_context"
+)]
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+pub fn prove_with_active_tables<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    policy: &mvlookup::TablePolicy<ID>,
+    constraints: &[E<G::ScalarField>],
+    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<G::ScalarField>, ID>>>,
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let active = inputs
+        .mvlookups
+        .iter()
+        .filter_map(|witness| witness.table_id())
+        .collect();
+    let constraints = policy
+        .compile_constraints(constraints, lookups_map, &active)
+        .map_err(ProverError::Config)?;
+    check_inputs(&constraints, &inputs, domain)?;
+    let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), &constraints)?;
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, _>(
+        &ctx, inputs, None, None, rng,
+    )
+}
+
+/// Same as [prove], with the lookups given by [mvlookup::MVLookupQueries],
+/// i.e. possibly by reference to the witness columns. The looked-up values of
+/// the columns are read out of `evaluations` by the prover, see
+/// [mvlookup::LookupQuery::Column].
+#[deprecated(
+    note = "resolve the lookups with crate::mvlookup::MVLookupQueries::resolve, and use prove_with_context"
+)]
+pub fn prove_with_lookup_queries<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    evaluations: Witness<N, Vec<G::ScalarField>>,
+    lookups: Vec<mvlookup::MVLookupQueries<G::ScalarField, ID>>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let mvlookups = lookups
+        .into_iter()
+        .map(|lookup| lookup.resolve(&evaluations))
+        .collect::<Result<_, _>>()
+        .map_err(ProverError::LookupQuery)?;
+    let inputs = ProofInputs {
+        evaluations,
+        mvlookups,
+    };
+    check_inputs(constraints, &inputs, domain)?;
+    let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?;
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, _>(
+        &ctx, inputs, None, None, rng,
+    )
+}
+
+/// Same as [prove], counting the expensive operations performed by the prover
+/// in `perf`, see [PerfCounters]. The operations are only counted when the
+/// feature `perf-counters` is enabled.
+#[deprecated(note = "use prove_with_context, with ProverContext::with_perf_counters")]
+pub fn prove_with_perf_counters<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    inputs: ProofInputs<N, G, ID>,
+    perf: &PerfCounters,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    check_inputs(constraints, &inputs, domain)?;
+    let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?;
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, _>(
+        &ctx,
+        inputs,
+        None,
+        Some(perf),
+        rng,
+    )
+}
+
+/// Create a proof for a circuit with columns bound to a sub-domain of d1, see
+/// [crate::subdomain]. The columns of `layout` must have the size of the
+/// sub-domain, and the constraints referring to them must be restricted with
+/// [SubdomainLayout::restrict]. The proof is checked by [crate::verifier::verify].
+#[deprecated(note = "use prove_with_context, with ProverContext::with_subdomain")]
+pub fn prove_with_subdomain<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    layout: &SubdomainLayout,
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?
+        .with_subdomain(layout.clone());
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, _>(
+        &ctx, inputs, None, None, rng,
+    )
+}
+
+/// Create a proof using fixed lookup tables shared with other circuits and
+/// published out-of-band, see [GlobalTableCommitments].
+/// The prover checks that the fixed tables of the witness are the ones
+/// committed in `global_tables`, and omits the commitments to the fixed
+/// tables from the proof. The verifier must use
+/// [crate::verifier::VerifierContext::with_global_tables].
+#[deprecated(note = "use prove_with_context, with ProverContext::with_global_tables")]
+pub fn prove_with_global_tables<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    inputs: ProofInputs<N, G, ID>,
+    global_tables: &GlobalTableCommitments<G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?
+        .with_global_tables(global_tables.clone());
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, _>(
+        &ctx, inputs, None, None, rng,
+    )
+}
+
+/// Create a proof following the protocol configuration `config`, which
+/// determines the evaluation points opened by the prover. The evaluation
+/// container `Eval` must match the opening mode of the configuration, and the
+/// constraints are checked with [ProtocolConfig::compile_constraints].
+/// The profile of the configuration must not omit the fixed tables, see
+/// [ProverContext::with_global_tables] otherwise.
+/// The verifier must use [crate::verifier::VerifierContext].
+#[deprecated(note = "use prove_with_context")]
+pub fn prove_with_config<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    config: &ProtocolConfig,
+    constraints: &[E<G::ScalarField>],
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let ctx = ProverContext::new(domain, srs, config.clone(), constraints)?;
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, Eval>(
+        &ctx, inputs, None, None, rng,
+    )
+}
+
+/// Same as [prove_with_config], for a proof whose lookups are only satisfied
+/// across a chain of proofs, see [prove_with_context_and_accumulator]. The
+/// running sum of the lookup argument starts from `incoming`.
+#[deprecated(note = "use prove_with_context_and_accumulator")]
+pub fn prove_with_accumulator<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    config: &ProtocolConfig,
+    constraints: &[E<G::ScalarField>],
+    inputs: ProofInputs<N, G, ID>,
+    incoming: G::ScalarField,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let ctx = ProverContext::new(domain, srs, config.clone(), constraints)?;
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, Eval>(
+        &ctx,
+        inputs,
+        Some(incoming),
+        None,
+        rng,
+    )
+}
+
+/// Returns the constraints extended with the ones binding the output cells of
+/// `config` to their values in `inputs`, and these values
+#[allow(clippy::type_complexity)]
+fn bind_public_outputs<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    config: &ProtocolConfig,
+    mut constraints: Vec<E<G::ScalarField>>,
+    inputs: &ProofInputs<N, G, ID>,
+    domain: EvaluationDomains<G::ScalarField>,
+) -> Result<(Vec<E<G::ScalarField>>, PublicOutputs<G::ScalarField>), ProverError> {
+    let public_outputs = config
+        .public_outputs(inputs.evaluations.cols.as_slice(), domain.d1.size as usize)
+        .map_err(ProverError::Config)?;
+    constraints.extend(
+        config
+            .output_constraints(&public_outputs)
+            .map_err(ProverError::Config)?,
+    );
+    Ok((constraints, public_outputs))
+}
+
+/// Create a proof omitting the `N - M` columns of the witness declared in
+/// `constant_columns`, see [ConstantColumns::detect]. The references to these
+/// columns are replaced by their values in the constraints, and the resulting
+/// proof has `M` columns. The prover fails if a declared column is not
+/// constant. The verifier must use the same substitution.
+#[deprecated(
+    note = "reduce the inputs with ConstantColumns::reduce_inputs, substitute the constraints with ConstantColumns::substitute, and use prove_with_context"
+)]
+pub fn prove_with_constant_columns<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    const M: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    constant_columns: &ConstantColumns<G::ScalarField>,
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<M, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let inputs = constant_columns.reduce_inputs::<N, M, G, ID>(inputs)?;
+    let constraints: Vec<_> = constraints
+        .iter()
+        .map(|constraint| constant_columns.substitute(constraint))
+        .collect();
+    let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), &constraints)?;
+    prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, RNG, M, ID, _>(
+        &ctx, inputs, None, None, rng,
+    )
+}
+
+/// Create a proof for constraints using `K` extra challenges, see
+/// [prove_with_context_and_extra_challenges].
+#[deprecated(note = "use prove_with_context_and_extra_challenges")]
+pub fn prove_with_extra_challenges<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    const K: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    first_phase: Vec<Vec<G::ScalarField>>,
+    second_phase: impl FnOnce(&[G::ScalarField; K]) -> ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?;
+    prove_in_phases::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, K, ID, _>(
+        &ctx,
+        first_phase,
+        second_phase,
+        None,
+        rng,
+    )
+}
+
+/// The state of the prover after the first round, see [prove_first_round].
+/// It can be serialized to resume the proof in a different process with
+/// [resume_proving].
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "F: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize")]
+pub struct FirstRoundState<F, ID: LookupTableID> {
+    #[serde_as(as = "Vec<Vec<o1_utils::serialization::SerdeAs>>")]
+    witness: Vec<Vec<F>>,
+    mvlookups: Vec<MVLookupWitness<F, ID>>,
+}
+
+/// Compute only the first message of the prover, i.e. the commitments to the
+/// witness columns and to the multiplicities of the lookup argument.
+/// The proof can be finished later with [resume_proving], using the returned
+/// state. The transcript is the same as the one of [prove].
+pub fn prove_first_round<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    inputs: ProofInputs<N, G, ID>,
+) -> (
+    FirstRoundMessage<G, ID>,
+    FirstRoundState<G::ScalarField, ID>,
+)
+where
+    OpeningProof::SRS: Sync,
+{
+    let witness_comms = (&inputs.evaluations)
+        .into_par_iter()
+        .map(|evals| {
+            let poly = Evaluations::<G::ScalarField, R2D<G::ScalarField>>::from_vec_and_domain(
+                evals.clone(),
+                domain.d1,
+            )
+            .interpolate();
+            commit_column::<G, OpeningProof>(srs, &poly)
+        })
+        .collect();
+    // The multiplicities of the bounding tables include the range checks
+    let mvlookups =
+        mvlookup::add_multiplicity_range_checks(inputs.mvlookups.clone(), domain.d1.size as usize);
+    let lookup_counters_comms = mvlookup::prover::commit_lookup_counters::<G, OpeningProof, ID>(
+        &mvlookup::prover::lookup_counters_evals_d1(&mvlookups, domain),
+        domain,
+        srs,
+    );
+    let message = FirstRoundMessage {
+        witness_comms,
+        lookup_counters_comms,
+    };
+    let state = FirstRoundState {
+        witness: inputs.evaluations.into_iter().collect(),
+        mvlookups: inputs.mvlookups,
+    };
+    (message, state)
+}
+
+/// Finish a proof started with [prove_first_round].
+/// The commitments are recomputed from `state`, and the prover fails with
+/// [ProverError::FirstRoundMismatch] if they are not the ones of
+/// `first_round`.
+pub fn resume_proving<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    Column,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    state: FirstRoundState<G::ScalarField, ID>,
+    first_round: &FirstRoundMessage<G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let FirstRoundState { witness, mvlookups } = state;
+    let cols: Box<[Vec<G::ScalarField>; N]> = witness.try_into().map_err(|_| {
+        ProverError::Generic("the first round state does not have the expected number of columns")
+    })?;
+    let inputs = ProofInputs {
+        evaluations: Witness { cols },
+        mvlookups,
+    };
+    prove_internal::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, 0, ID, _>(
+        domain,
+        srs,
+        constraints,
+        vec![],
+        |_| inputs,
+        ProverOptions {
+            first_round: Some(first_round),
+            ..Default::default()
+        },
+        rng,
+    )
+}
+
+/// Whether the prover skips the work which is not needed for the values of
+/// the witness, see [ProverContext::with_proving_mode].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProvingMode {
+    /// The prover takes the shortcuts allowed by the values of the witness:
+    /// the columns of small values are committed with the specialized
+    /// committers of [ColumnCommitters], the lookups of the same value and
+    /// table in consecutive columns of a row share their denominator, see
+    /// [mvlookup::LookupGrouping], and the numerators equal to one are not
+    /// multiplied, see [PerfCounters::record_numerator_muls].
+    #[default]
+    Optimized,
+    /// The prover performs the same operations for all the witnesses of a
+    /// circuit, whatever their values, e.g. in a prover service shared by
+    /// several tenants, where the running time of a proof must not leak the
+    /// sparsity of its witness. Every row and column is processed
+    /// unconditionally, with the generic commitments, one denominator per
+    /// lookup and one multiplication per numerator. The columns and their
+    /// commitments are the same as in [ProvingMode::Optimized], and the
+    /// verifier is unchanged.
+    ///
+    /// The remaining operations depending on the values are:
+    /// - the field arithmetic, which is not constant time, e.g. the inversion
+    ///   of a batch inversion, whose single inversion is amortized over the
+    ///   whole batch, and the final subtraction of the Montgomery
+    ///   multiplication,
+    /// - the MSMs of the commitments, whose bucket additions skip the zero
+    ///   digits of the scalars,
+    /// - the sorting of the columns of the plookup engine, see
+    ///   [crate::lookup_engine::PlookupEngine],
+    /// - the checks of the witness, which stop at the first invalid value. A
+    ///   valid witness passes all of them.
+    ConstantTrace,
+}
+
+/// The parameters shared by the proofs of the instances of a circuit: the
+/// SRS, the compiled constraints, the commitments to the fixed tables and the
+/// layout of the columns.
+/// The context is never modified by the prover, each proof using its own
+/// sponges and scratch space, hence it is `Send + Sync` when the SRS is, and
+/// it can be shared by the threads proving independent instances, see
+/// [prove_many].
+pub struct ProverContext<'a, G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID> {
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &'a OpeningProof::SRS,
+    config: ProtocolConfig,
+    constraints: Vec<E<G::ScalarField>>,
+    global_tables: Option<GlobalTableCommitments<G, ID>>,
+    subdomain: Option<SubdomainLayout>,
+    memory_budget: Option<usize>,
+    committers: ColumnCommitters<G>,
+    lookup_observer: Option<LookupStatsObserver>,
+    column_permutation: Option<ColumnPermutation>,
+    thread_pool: Option<Arc<ThreadPool>>,
+    proving_mode: ProvingMode,
+    perf: Option<Arc<PerfCounters>>,
+}
+
+impl<'a, G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID>
+    ProverContext<'a, G, OpeningProof, ID>
+{
+    /// Compile `constraints` for the configuration `config`, see
+    /// [ProtocolConfig::compile_constraints].
+    pub fn new(
+        domain: EvaluationDomains<G::ScalarField>,
+        srs: &'a OpeningProof::SRS,
+        config: ProtocolConfig,
+        constraints: &[E<G::ScalarField>],
+    ) -> Result<Self, ProverError> {
+        let constraints = config
+            .compile_constraints(constraints.to_vec())
+            .map_err(ProverError::Config)?;
+        Ok(ProverContext {
+            domain,
+            srs,
+            config,
+            constraints,
+            global_tables: None,
+            subdomain: None,
+            memory_budget: None,
+            committers: ColumnCommitters::default(),
+            lookup_observer: None,
+            column_permutation: None,
+            thread_pool: None,
+            proving_mode: ProvingMode::default(),
+            perf: None,
+        })
+    }
+
+    /// Check the fixed tables of the witness against `global_tables`, and omit
+    /// the commitments to them from the proofs, see [GlobalTableCommitments]
+    pub fn with_global_tables(mut self, global_tables: GlobalTableCommitments<G, ID>) -> Self {
+        self.global_tables = Some(global_tables);
+        self
+    }
+
+    /// Bind the columns of `layout` to a sub-domain of d1, see
+    /// [crate::subdomain]. The columns of `layout` must have the size of the
+    /// sub-domain, and the constraints referring to them must be restricted
+    /// with [SubdomainLayout::restrict].
+    pub fn with_subdomain(mut self, layout: SubdomainLayout) -> Self {
+        self.subdomain = Some(layout);
+        self
+    }
+
+    /// Bound the memory used by [prove_many] to about `bytes`
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Describe the values of the witness column `column`, to commit to it
+    /// with the committer of `descriptor`, see [ColumnCommitters]
+    pub fn with_column_descriptor(mut self, column: usize, descriptor: ColumnDescriptor) -> Self {
+        self.committers = self.committers.with_column(column, descriptor);
+        self
+    }
+
+    /// Commit to the witness columns of `descriptor` with `committer`,
+    /// instead of the built-in committer if any
+    pub fn with_column_committer(
+        mut self,
+        descriptor: ColumnDescriptor,
+        committer: Arc<dyn ColumnCommitter<G>>,
+    ) -> Self {
+        self.committers = self.committers.with_committer(descriptor, committer);
+        self
+    }
+
+    /// Call `observer` with the [LookupStats] of each proof once it is
+    /// finished, for telemetry. The statistics are computed while building
+    /// the lookup argument, and are empty for the proofs without lookups.
+    pub fn with_lookup_observer(mut self, observer: LookupStatsObserver) -> Self {
+        self.lookup_observer = Some(observer);
+        self
+    }
+
+    /// Reorder the witness columns with `permutation`, e.g. computed with
+    /// [ColumnPermutation::from_access_report]. The compiled constraints and
+    /// the output cells of the configuration are renumbered, and the witness
+    /// of each proof is permuted. The indices of the columns given to the
+    /// other methods of the context are the ones of the permuted witness.
+    pub fn with_column_permutation(mut self, permutation: ColumnPermutation) -> Self {
+        self.constraints = self
+            .constraints
+            .iter()
+            .map(|constraint| permutation.apply_to_constraint(constraint))
+            .collect();
+        self.config = permutation.apply_to_config(self.config);
+        self.column_permutation = Some(permutation);
+        self
+    }
+
+    /// Run the parallel sections of the prover on `pool` instead of the
+    /// global rayon thread pool, e.g. when the application uses the global
+    /// pool for other work. The proofs are the same.
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Prove with `mode`, e.g. [ProvingMode::ConstantTrace] to perform the
+    /// same operations for all the witnesses
+    pub fn with_proving_mode(mut self, mode: ProvingMode) -> Self {
+        self.proving_mode = mode;
+        self
+    }
+
+    /// Count the expensive operations of the proofs of the context in
+    /// `perf`, see [PerfCounters]. The operations are only counted when the
+    /// feature `perf-counters` is enabled.
+    pub fn with_perf_counters(mut self, perf: Arc<PerfCounters>) -> Self {
+        self.perf = Some(perf);
+        self
+    }
+
+    pub fn domain(&self) -> EvaluationDomains<G::ScalarField> {
+        self.domain
+    }
+
+    pub fn srs(&self) -> &'a OpeningProof::SRS {
+        self.srs
+    }
+
+    pub fn config(&self) -> &ProtocolConfig {
+        &self.config
+    }
+
+    /// The compiled constraints
+    pub fn constraints(&self) -> &[E<G::ScalarField>] {
+        &self.constraints
+    }
+
+    pub fn global_tables(&self) -> Option<&GlobalTableCommitments<G, ID>> {
+        self.global_tables.as_ref()
+    }
+
+    pub fn column_permutation(&self) -> Option<&ColumnPermutation> {
+        self.column_permutation.as_ref()
+    }
+
+    pub fn proving_mode(&self) -> ProvingMode {
+        self.proving_mode
+    }
+
+    /// The options of the prover for the proofs of the context, counting the
+    /// operations in `perf` instead of the counters of the context if any
+    fn options<'b>(&'b self, perf: Option<&'b PerfCounters>) -> ProverOptions<'b, G, ID> {
+        ProverOptions {
+            global_tables: self.global_tables.as_ref(),
+            subdomain: self.subdomain.as_ref(),
+            committers: Some(&self.committers),
+            lookup_observer: self.lookup_observer.as_ref(),
+            proving_mode: self.proving_mode,
+            perf: perf.or(self.perf.as_deref()),
+            ..ProverOptions::of_config(&self.config)
+        }
+    }
+
+    /// An estimate of the memory used to prove an instance with `n_columns`
+    /// witness columns and `n_lookups` instances of the lookup argument: the
+    /// polynomials, and their evaluations over d8.
+    pub fn memory_per_proof(&self, n_columns: usize, n_lookups: usize) -> usize {
+        let elem_size = G::ScalarField::zero().serialized_size();
+        // The multiplicities, a partial sum, and the running sum per lookup,
+        // and the quotient
+        let n_polys = n_columns + 3 * n_lookups + 1;
+        n_polys * (self.domain.d1.size + self.domain.d8.size) as usize * elem_size
+    }
+}
+
+/// Create a proof with the parameters shared in `ctx`. The verifier must use
+/// [VerifierContext], with the global tables if `ctx` has some. The proof is
+/// computed on the thread pool of `ctx` if any, see
+/// [ProverContext::with_thread_pool].
+pub fn prove_with_context<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    OpeningProof: Send,
+    Eval: Send,
+    RNG: RngCore + CryptoRng,
+{
+    match &ctx.thread_pool {
+        // The caller's generator may not be sendable to the threads of the
+        // pool, so that the prover uses a generator seeded from it.
+        Some(pool) => {
+            let mut pool_rng = seed_pool_rng(rng)?;
+            pool.install(move || {
+                prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, _, N, ID, Eval>(
+                    ctx,
+                    inputs,
+                    None,
+                    None,
+                    &mut pool_rng,
+                )
+            })
+        }
+        None => prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, _, N, ID, Eval>(
+            ctx, inputs, None, None, rng,
+        ),
+    }
+}
+
+/// Same as [prove_with_context], for a proof whose lookups are only satisfied
+/// across a chain of proofs, see [mvlookup::AccumulatorBoundary]. The running
+/// sum of the lookup argument starts from `incoming`, and the outgoing
+/// accumulator is given with the proof. The lookup constraints must be built
+/// without the aggregation constraint, with
+/// [mvlookup::constraint_lookups_with_config] in the
+/// [LookupAggregationMode::Claimed] mode, and the configuration of `ctx` must
+/// aggregate the lookups in the committed mode and in the forward direction.
+/// The verifier must use [VerifierContext::verify_with_accumulator].
+pub fn prove_with_context_and_accumulator<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    inputs: ProofInputs<N, G, ID>,
+    incoming: G::ScalarField,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    OpeningProof: Send,
+    Eval: Send,
+    RNG: RngCore + CryptoRng,
+{
+    match &ctx.thread_pool {
+        Some(pool) => {
+            let mut pool_rng = seed_pool_rng(rng)?;
+            pool.install(move || {
+                prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, _, N, ID, Eval>(
+                    ctx,
+                    inputs,
+                    Some(incoming),
+                    None,
+                    &mut pool_rng,
+                )
+            })
+        }
+        None => prove_instance::<G, OpeningProof, EFqSponge, EFrSponge, _, N, ID, Eval>(
+            ctx,
+            inputs,
+            Some(incoming),
+            None,
+            rng,
+        ),
+    }
+}
+
+/// Create a proof for constraints using `K` extra challenges, accessible in
+/// the constraints through [crate::columns::Column::ExtraChallenge], with the
+/// parameters shared in `ctx`.
+/// The columns of `first_phase` are committed first, and are the first
+/// columns of the witness. The extra challenges are then coined, and given to
+/// `second_phase` to build the whole witness, including the lookups. The
+/// first columns of the witness returned by `second_phase` must be the ones
+/// of `first_phase`.
+/// The context must not have output cells nor a column permutation. The
+/// verifier must use [VerifierContext::verify_with_extra_challenges] with the
+/// same number of columns in the first phase.
+pub fn prove_with_context_and_extra_challenges<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    const K: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    first_phase: Vec<Vec<G::ScalarField>>,
+    second_phase: impl FnOnce(&[G::ScalarField; K]) -> ProofInputs<N, G, ID> + Send,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    OpeningProof: Send,
+    Eval: Send,
+    RNG: RngCore + CryptoRng,
+{
+    match &ctx.thread_pool {
+        Some(pool) => {
+            let mut pool_rng = seed_pool_rng(rng)?;
+            pool.install(move || {
+                prove_in_phases::<G, OpeningProof, EFqSponge, EFrSponge, _, N, K, ID, Eval>(
+                    ctx,
+                    first_phase,
+                    second_phase,
+                    None,
+                    &mut pool_rng,
+                )
+            })
+        }
+        None => prove_in_phases::<G, OpeningProof, EFqSponge, EFrSponge, _, N, K, ID, Eval>(
+            ctx,
+            first_phase,
+            second_phase,
+            None,
+            rng,
+        ),
+    }
+}
+
+/// The generator of the prover on the thread pool of a context, seeded from
+/// the caller's generator `rng`
+fn seed_pool_rng(rng: &mut impl RngCore) -> Result<rand::rngs::StdRng, ProverError> {
+    rand::rngs::StdRng::from_rng(rng)
+        .map_err(|_| ProverError::Generic("failed to seed the generator of the thread pool"))
+}
+
+/// The proof of [prove_with_context], on the current thread, whose running sum
+/// of the lookup argument starts from `incoming` if any, counting the
+/// operations in `perf` instead of the counters of `ctx` if any
+fn prove_instance<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    inputs: ProofInputs<N, G, ID>,
+    incoming: Option<G::ScalarField>,
+    perf: Option<&PerfCounters>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    if ctx.config.opening_mode != Eval::OPENING_MODE {
+        return Err(ProverError::Config(ConfigError::OpeningModeMismatch));
+    }
+    ctx.config
+        .check_global_tables(ctx.global_tables.is_some())
+        .map_err(ProverError::Config)?;
+    if incoming.is_some()
+        && (ctx.config.lookup_aggregation != LookupAggregationMode::Committed
+            || ctx.config.aggregation_direction != AggregationDirection::Forward
+            || inputs.mvlookups.is_empty())
+    {
+        return Err(ProverError::Config(
+            ConfigError::LookupAccumulatorNotSupported,
+        ));
+    }
+    let inputs = match &ctx.column_permutation {
+        Some(permutation) => ProofInputs {
+            evaluations: permutation
+                .apply_to_witness(inputs.evaluations)
+                .map_err(ProverError::ColumnPermutation)?,
+            mvlookups: inputs.mvlookups,
+        },
+        None => inputs,
+    };
+    let (constraints, public_outputs) =
+        bind_public_outputs(&ctx.config, ctx.constraints.clone(), &inputs, ctx.domain)?;
+    prove_internal::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, 0, ID, Eval>(
+        ctx.domain,
+        ctx.srs,
+        &constraints,
+        vec![],
+        |_| inputs,
+        ProverOptions {
+            incoming,
+            public_outputs,
+            ..ctx.options(perf)
+        },
+        rng,
+    )
+}
+
+/// The proof of [prove_with_context_and_extra_challenges], on the current
+/// thread, counting the operations in `perf` instead of the counters of `ctx`
+/// if any
+fn prove_in_phases<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    const K: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    first_phase: Vec<Vec<G::ScalarField>>,
+    second_phase: impl FnOnce(&[G::ScalarField; K]) -> ProofInputs<N, G, ID>,
+    perf: Option<&PerfCounters>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    if ctx.config.opening_mode != Eval::OPENING_MODE {
+        return Err(ProverError::Config(ConfigError::OpeningModeMismatch));
+    }
+    ctx.config
+        .check_global_tables(ctx.global_tables.is_some())
+        .map_err(ProverError::Config)?;
+    // The outputs and the permutation apply to the whole witness, which is
+    // only known after the first phase
+    if !ctx.config.output_cells.is_empty() || ctx.column_permutation.is_some() {
+        return Err(ProverError::Generic(
+            "the extra challenges do not support output cells nor column permutations",
+        ));
+    }
+    prove_internal::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, K, ID, Eval>(
+        ctx.domain,
+        ctx.srs,
+        &ctx.constraints,
+        first_phase,
+        second_phase,
+        ctx.options(perf),
+        rng,
+    )
+}
+
+/// Create a proof with the parameters shared in `ctx`, like
+/// [prove_with_context], and release it only if each of `auditors` accepts
+/// it, in order. The auditors are given the [VerifierContext] of `ctx`, see
+/// [crate::audit].
+pub fn prove_audited<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    auditors: &[&dyn ProofAuditor<N, G, OpeningProof, ID, Eval>],
+    inputs: ProofInputs<N, G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    OpeningProof: Send,
+    Eval: Send,
+    RNG: RngCore + CryptoRng,
+{
+    prove_audited_with_hook::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, Eval>(
+        ctx,
+        auditors,
+        inputs,
+        |_| (),
+        rng,
+    )
+}
+
+/// [prove_audited], with a `hook` modifying the proof before it is audited,
+/// to simulate the bugs of the prover in the tests
+pub(crate) fn prove_audited_with_hook<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    auditors: &[&dyn ProofAuditor<N, G, OpeningProof, ID, Eval>],
+    inputs: ProofInputs<N, G, ID>,
+    hook: impl FnOnce(&mut Proof<N, G, OpeningProof, ID, Eval>),
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    OpeningProof: Send,
+    Eval: Send,
+    RNG: RngCore + CryptoRng,
+{
+    let verifier_ctx = VerifierContext::from_prover_context(ctx)
+        .map_err(|err| ProverError::Audit(AuditError::Verification(err)))?;
+    let mut proof = prove_with_context::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, Eval>(
+        ctx, inputs, rng,
+    )?;
+    hook(&mut proof);
+    for auditor in auditors {
+        auditor
+            .audit(&proof, &verifier_ctx)
+            .map_err(ProverError::Audit)?;
+    }
+    Ok(proof)
+}
+
+/// Prove independent instances in parallel with the parameters shared in
+/// `ctx`, see [prove_with_context]. The results are in the order of `inputs`.
+/// The instances are proven in waves, whose size is bounded by the memory
+/// budget of the context if any, and by the number of threads of the thread
+/// pool of the context, or of the global rayon thread pool, otherwise.
+pub fn prove_many<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    ctx: &ProverContext<G, OpeningProof, ID>,
+    inputs: Vec<ProofInputs<N, G, ID>>,
+) -> Vec<Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>>
+where
+    OpeningProof::SRS: Sync,
+    OpeningProof: Send,
+    Eval: Send,
+{
+    let wave_size = match ctx.memory_budget {
+        Some(budget) => {
+            let n_lookups = inputs.iter().map(|inputs| inputs.mvlookups.len()).max();
+            let per_proof = ctx.memory_per_proof(N, n_lookups.unwrap_or(0));
+            std::cmp::max(1, budget / per_proof)
+        }
+        None => ctx
+            .thread_pool
+            .as_ref()
+            .map_or_else(rayon::current_num_threads, |pool| {
+                pool.current_num_threads()
+            }),
+    };
+    let prove = move || {
+        let mut proofs = Vec::with_capacity(inputs.len());
+        let mut inputs = inputs.into_iter().peekable();
+        while inputs.peek().is_some() {
+            let wave: Vec<_> = inputs.by_ref().take(wave_size).collect();
+            let wave_proofs: Vec<_> = wave
+                .into_par_iter()
+                .map(|inputs| {
+                    prove_with_context::<G, OpeningProof, EFqSponge, EFrSponge, _, N, ID, Eval>(
+                        ctx,
+                        inputs,
+                        &mut rand::thread_rng(),
+                    )
+                })
+                .collect();
+            proofs.extend(wave_proofs);
+        }
+        proofs
+    };
+    match &ctx.thread_pool {
+        Some(pool) => pool.install(prove),
+        None => prove(),
+    }
+}
+
+/// The state of the prover once the witness columns and the polynomials of
+/// the lookup argument are committed, i.e. once the environment of the lookup
+/// argument is built, see [prove_until_checkpoint].
+/// It can be saved, and the proof finished in a different process with
+/// [resume_from_checkpoint].
+pub struct ProverCheckpoint<const N: usize, G: KimchiCurve, ID: LookupTableID, EFqSponge> {
+    committed: CommittedColumns<N, G, ID, EFqSponge>,
+}
 
-    #[error("the provided (witness) constraints was not satisfied: {0}")]
-    ConstraintNotSatisfied(String),
+/// Serialization helper for [ProverCheckpoint]
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(
+    bound = "G: ark_serialize::CanonicalSerialize + ark_serialize::CanonicalDeserialize, \
+                 S: Serialize + serde::de::DeserializeOwned"
+)]
+struct SerializableCheckpoint<G: KimchiCurve, S> {
+    #[serde_as(as = "Vec<o1_utils::serialization::SerdeAs>")]
+    witness_polys: Vec<DensePolynomial<G::ScalarField>>,
+    witness_comms: Vec<PolyComm<G>>,
+    /// The environment encoded with [Env::to_bytes]
+    lookup_env: Option<Vec<u8>>,
+    fq_sponge: S,
+}
 
-    #[error("the provided (witness) constraint has degree {0} > allowed {1}; expr: {2}")]
-    ConstraintDegreeTooHigh(u64, u64, String),
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID, EFqSponge>
+    ProverCheckpoint<N, G, ID, EFqSponge>
+where
+    EFqSponge: SpongeSnapshot<G::BaseField>,
+{
+    /// The environment of the lookup argument, if the circuit uses lookups
+    pub fn lookup_env(&self) -> Option<&Env<G, ID>> {
+        self.committed.lookup_env.as_ref()
+    }
+
+    /// Save the checkpoint in the file `path`, with the digest of its
+    /// content, see [crate::checkpoint].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let CommittedColumns {
+            fq_sponge,
+            witness_polys,
+            witness_comms,
+            lookup_env,
+        } = &self.committed;
+        checkpoint::save(
+            &SerializableCheckpoint::<G, _> {
+                witness_polys: witness_polys.cols.to_vec(),
+                witness_comms: witness_comms.cols.to_vec(),
+                lookup_env: lookup_env.as_ref().map(Env::to_bytes).transpose()?,
+                fq_sponge: fq_sponge.snapshot(),
+            },
+            path,
+        )
+    }
+
+    /// Load a checkpoint saved with [Self::save] over `domain`.
+    pub fn load(
+        path: impl AsRef<Path>,
+        domain: EvaluationDomains<G::ScalarField>,
+    ) -> Result<Self, CheckpointError> {
+        let checkpoint: SerializableCheckpoint<G, EFqSponge::Snapshot> = checkpoint::load(path)?;
+        let n_columns_error =
+            || CheckpointError::Encoding(format!("the checkpoint does not have {N} columns"));
+        let witness_polys: Box<[_; N]> = checkpoint
+            .witness_polys
+            .try_into()
+            .map_err(|_| n_columns_error())?;
+        let witness_comms: Box<[_; N]> = checkpoint
+            .witness_comms
+            .try_into()
+            .map_err(|_| n_columns_error())?;
+        Ok(ProverCheckpoint {
+            committed: CommittedColumns {
+                fq_sponge: EFqSponge::restore(checkpoint.fq_sponge, G::other_curve_sponge_params()),
+                witness_polys: Witness {
+                    cols: witness_polys,
+                },
+                witness_comms: Witness {
+                    cols: witness_comms,
+                },
+                lookup_env: checkpoint
+                    .lookup_env
+                    .map(|bytes| Env::from_bytes(&bytes, domain))
+                    .transpose()?,
+            },
+        })
+    }
 }
 
-#[allow(unreachable_code)]
-pub fn prove<
+/// Commit to the witness columns and build the environment of the lookup
+/// argument, like [prove] does, and return the state of the prover.
+/// The proof can be finished later with [resume_from_checkpoint], possibly
+/// after saving and loading the checkpoint. The transcript is the same as the
+/// one of [prove].
+pub fn prove_until_checkpoint<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    const N: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    inputs: ProofInputs<N, G, ID>,
+) -> Result<ProverCheckpoint<N, G, ID, EFqSponge>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let (committed, _) = commit_columns::<G, OpeningProof, EFqSponge, N, 0, ID>(
+        EFqSponge::new(G::other_curve_sponge_params()),
+        domain,
+        srs,
+        constraints,
+        vec![],
+        |_| inputs,
+        &ProverOptions::default(),
+        OpeningMode::TwoPoints,
+        None,
+        &PerfCounters::default(),
+    )?;
+    Ok(ProverCheckpoint { committed })
+}
+
+/// Finish a proof started with [prove_until_checkpoint]. The constraints must
+/// be the ones given to [prove_until_checkpoint].
+pub fn resume_from_checkpoint<
     G: KimchiCurve,
     OpeningProof: OpenProof<G>,
     EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
@@ -59,99 +1668,589 @@ pub fn prove<
     domain: EvaluationDomains<G::ScalarField>,
     srs: &OpeningProof::SRS,
     constraints: &Vec<E<G::ScalarField>>,
-    inputs: ProofInputs<N, G, ID>,
+    checkpoint: ProverCheckpoint<N, G, ID, EFqSponge>,
     rng: &mut RNG,
 ) -> Result<Proof<N, G, OpeningProof, ID>, ProverError>
 where
     OpeningProof::SRS: Sync,
     RNG: RngCore + CryptoRng,
 {
+    finish_proving::<G, OpeningProof, EFqSponge, EFrSponge, RNG, N, ID, _>(
+        domain,
+        srs,
+        constraints,
+        checkpoint.committed,
+        &[],
+        None,
+        None,
+        DegeneratePointRule::default(),
+        None,
+        &PerfCounters::default(),
+        rng,
+    )
+}
+
+/// Commit to a witness column
+fn commit_column<G: KimchiCurve, OpeningProof: OpenProof<G>>(
+    srs: &OpeningProof::SRS,
+    poly: &DensePolynomial<G::ScalarField>,
+) -> PolyComm<G> {
+    mask_column_commitment::<G, OpeningProof>(srs, srs.commit_non_hiding(poly, 1))
+}
+
+/// Mask the commitment `comm` to a witness column
+fn mask_column_commitment<G: KimchiCurve, OpeningProof: OpenProof<G>>(
+    srs: &OpeningProof::SRS,
+    comm: PolyComm<G>,
+) -> PolyComm<G> {
+    // In case the column polynomial is all zeroes, we want to mask the commitment
+    srs.mask_custom(comm.clone(), &comm.map(|_| G::ScalarField::one()))
+        .unwrap()
+        .commitment
+}
+
+/// The state of the prover once the witness columns and the polynomials of
+/// the lookup argument are committed, and their commitments absorbed by the
+/// sponge.
+struct CommittedColumns<const N: usize, G: KimchiCurve, ID: LookupTableID, EFqSponge> {
+    fq_sponge: EFqSponge,
+    witness_polys: Witness<N, DensePolynomial<G::ScalarField>>,
+    witness_comms: Witness<N, PolyComm<G>>,
+    lookup_env: Option<Env<G, ID>>,
+}
+
+/// The options of [prove_internal], whose default values are the ones of
+/// [prove]
+struct ProverOptions<'a, G: KimchiCurve, ID: LookupTableID> {
+    global_tables: Option<&'a GlobalTableCommitments<G, ID>>,
+    first_round: Option<&'a FirstRoundMessage<G, ID>>,
+    subdomain: Option<&'a SubdomainLayout>,
+    lookup_aggregation: LookupAggregationMode,
+    aggregation_direction: AggregationDirection,
+    lookup_absorption: LookupAbsorptionOrder,
+    incoming: Option<G::ScalarField>,
+    srs_label: Option<&'a SrsLabel>,
+    public_outputs: PublicOutputs<G::ScalarField>,
+    degenerate_point_rule: DegeneratePointRule,
+    transcript: TranscriptBackend,
+    committers: Option<&'a ColumnCommitters<G>>,
+    lookup_observer: Option<&'a LookupStatsObserver>,
+    proving_mode: ProvingMode,
+    perf: Option<&'a PerfCounters>,
+}
+
+impl<'a, G: KimchiCurve, ID: LookupTableID> Default for ProverOptions<'a, G, ID> {
+    fn default() -> Self {
+        ProverOptions {
+            global_tables: None,
+            first_round: None,
+            subdomain: None,
+            lookup_aggregation: LookupAggregationMode::Committed,
+            aggregation_direction: AggregationDirection::Forward,
+            lookup_absorption: LookupAbsorptionOrder::ColumnsFirst,
+            incoming: None,
+            srs_label: None,
+            public_outputs: PublicOutputs::default(),
+            degenerate_point_rule: DegeneratePointRule::default(),
+            transcript: TranscriptBackend::FqSponge,
+            committers: None,
+            lookup_observer: None,
+            proving_mode: ProvingMode::Optimized,
+            perf: None,
+        }
+    }
+}
+
+impl<'a, G: KimchiCurve, ID: LookupTableID> ProverOptions<'a, G, ID> {
+    /// The options following the protocol configuration `config`
+    fn of_config(config: &'a ProtocolConfig) -> Self {
+        ProverOptions {
+            lookup_aggregation: config.lookup_aggregation,
+            aggregation_direction: config.aggregation_direction,
+            lookup_absorption: config.lookup_absorption,
+            srs_label: config.srs_label.as_ref(),
+            degenerate_point_rule: config.degenerate_point_rule,
+            transcript: config.transcript,
+            ..Default::default()
+        }
+    }
+}
+
+fn prove_internal<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    const K: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    first_phase: Vec<Vec<G::ScalarField>>,
+    second_phase: impl FnOnce(&[G::ScalarField; K]) -> ProofInputs<N, G, ID>,
+    options: ProverOptions<G, ID>,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let default_perf = PerfCounters::default();
+    let perf = options.perf.unwrap_or(&default_perf);
+    let mut lookup_stats = options.lookup_observer.map(|_| LookupStats::default());
+    let transcript_counter = TranscriptCounter::default();
+    let (committed, extra_challenges) =
+        commit_columns::<G, OpeningProof, CountingSponge<Transcript<G, EFqSponge>>, N, K, ID>(
+            CountingSponge::new(
+                Transcript::with_backend(options.transcript),
+                transcript_counter.clone(),
+            ),
+            domain,
+            srs,
+            constraints,
+            first_phase,
+            second_phase,
+            &options,
+            Eval::OPENING_MODE,
+            lookup_stats.as_mut(),
+            perf,
+        )?;
+    // The running sum of a chained proof is constrained by its boundary
+    let accumulator = committed
+        .lookup_env
+        .as_ref()
+        .and_then(|lookup_env| lookup_env.accumulator);
+    let constraints = match &accumulator {
+        None => Cow::Borrowed(constraints),
+        Some(boundary) => {
+            let layout = mvlookup::LookupLayout::<ID>::of_constraints(constraints).ok_or(
+                ProverError::Config(ConfigError::LookupAccumulatorNotSupported),
+            )?;
+            let mut constraints = constraints.clone();
+            constraints.extend(mvlookup::accumulator_constraints(
+                &layout, domain.d1, boundary,
+            ));
+            Cow::Owned(constraints)
+        }
+    };
+    let proof = finish_proving::<
+        G,
+        OpeningProof,
+        CountingSponge<Transcript<G, EFqSponge>>,
+        EFrSponge,
+        RNG,
+        N,
+        ID,
+        Eval,
+    >(
+        domain,
+        srs,
+        &constraints,
+        committed,
+        &extra_challenges,
+        options.global_tables,
+        options.subdomain,
+        options.degenerate_point_rule,
+        Some(&transcript_counter),
+        perf,
+        rng,
+    )?;
+    let proof = Proof {
+        srs_label: options.srs_label.cloned(),
+        public_outputs: options.public_outputs,
+        accumulator,
+        ..proof
+    };
+    if STRICT_TRANSCRIPT {
+        let expected =
+            TranscriptLayout::of_proof(&proof, K, options.global_tables.is_some()).profile();
+        let got = transcript_counter.profile();
+        if got != expected {
+            return Err(ProverError::TranscriptProfileMismatch { expected, got });
+        }
+    }
+    if let (Some(observer), Some(mut stats)) = (options.lookup_observer, lookup_stats) {
+        stats.proof = Some(ProofCounts::of_proof(&proof));
+        observer(&stats);
+    }
+    Ok(proof)
+}
+
+/// Commit to the witness columns, coining the extra challenges after the
+/// first phase, and build the environment of the lookup argument.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+fn commit_columns<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    const N: usize,
+    const K: usize,
+    ID: LookupTableID,
+>(
+    mut fq_sponge: EFqSponge,
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    first_phase: Vec<Vec<G::ScalarField>>,
+    second_phase: impl FnOnce(&[G::ScalarField; K]) -> ProofInputs<N, G, ID>,
+    options: &ProverOptions<G, ID>,
+    opening_mode: OpeningMode,
+    lookup_stats: Option<&mut LookupStats>,
+    perf: &PerfCounters,
+) -> Result<(CommittedColumns<N, G, ID, EFqSponge>, [G::ScalarField; K]), ProverError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let ProverOptions {
+        global_tables,
+        first_round,
+        subdomain,
+        lookup_aggregation,
+        aggregation_direction,
+        lookup_absorption,
+        incoming,
+        srs_label,
+        committers,
+        proving_mode,
+        ..
+    } = *options;
+    let public_outputs = options.public_outputs.0.as_slice();
+
     ////////////////////////////////////////////////////////////////////////////
     // Setting up the protocol
     ////////////////////////////////////////////////////////////////////////////
 
-    let group_map = G::Map::setup();
+    if first_phase.len() > N {
+        return Err(ProverError::Generic(
+            "the first phase has more columns than the witness",
+        ));
+    }
+    // The proof has one evaluation per polynomial and per point, which is
+    // the one of an unchunked commitment
+    let srs_size = srs.max_poly_size();
+    if expected_column_chunks(domain.d1.size as usize, srs_size) > 1 {
+        return Err(ProverError::Config(ConfigError::ChunkedCommitments {
+            domain_size: domain.d1.size as usize,
+            srs_size,
+        }));
+    }
 
     ////////////////////////////////////////////////////////////////////////////
     // Round 1: Creating and absorbing column commitments
     ////////////////////////////////////////////////////////////////////////////
 
-    let mut fq_sponge = EFqSponge::new(G::other_curve_sponge_params());
+    // The proof is bound to the SRS before any commitment
+    if let Some(label) = srs_label {
+        fq_sponge.absorb_fr(&[label.to_field()]);
+    }
+    // The outputs are bound to the transcript before the witness
+    if !public_outputs.is_empty() {
+        fq_sponge.absorb_fr(public_outputs);
+    }
+
+    let elem_size = G::ScalarField::zero().serialized_size();
 
-    // Interpolate all columns on d1, using trait Into.
-    let witness_evals: Witness<N, Evaluations<G::ScalarField, R2D<G::ScalarField>>> = inputs
-        .evaluations
-        .into_par_iter()
-        .map(|evals| {
-            Evaluations::<G::ScalarField, R2D<G::ScalarField>>::from_vec_and_domain(
-                evals, domain.d1,
-            )
-        })
-        .collect::<Witness<N, Evaluations<G::ScalarField, R2D<G::ScalarField>>>>();
+    // The columns bound to the sub-domain are interpolated and committed over
+    // it, the other ones over d1.
+    let subdomain_domain = match subdomain {
+        Some(layout) => Some(layout.domain(domain).ok_or(ProverError::Generic(
+            "the sub-domain is not a proper sub-domain of d1",
+        ))?),
+        None => None,
+    };
+    let column_domain = |i: usize| match (subdomain, subdomain_domain) {
+        (Some(layout), Some(sub_domain)) if layout.columns.contains(&i) => sub_domain,
+        _ => domain.d1,
+    };
+    if let Some(layout) = subdomain {
+        if let Some(i) = layout.columns.iter().find(|i| **i >= N) {
+            return Err(ProverError::ColumnSizeMismatch(*i));
+        }
+    }
+    let check_sizes = |columns: &[Vec<G::ScalarField>]| match subdomain {
+        Some(layout) => layout
+            .columns
+            .iter()
+            .filter(|i| **i < columns.len())
+            .try_for_each(|i| {
+                if columns[*i].len() != layout.size() {
+                    Err(ProverError::ColumnSizeMismatch(*i))
+                } else {
+                    Ok(())
+                }
+            }),
+        None => Ok(()),
+    };
+    check_sizes(&first_phase)?;
 
-    let witness_polys: Witness<N, DensePolynomial<G::ScalarField>> = {
-        let interpolate =
-            |evals: Evaluations<G::ScalarField, R2D<G::ScalarField>>| evals.interpolate();
-        witness_evals
-            .into_par_iter()
-            .map(interpolate)
-            .collect::<Witness<N, DensePolynomial<G::ScalarField>>>()
+    // Interpolate the columns on their domain
+    let interpolate = |(i, evals): (usize, Vec<G::ScalarField>)| {
+        let column_domain = column_domain(i);
+        perf.record_fft(column_domain.size);
+        Evaluations::<G::ScalarField, R2D<G::ScalarField>>::from_vec_and_domain(
+            evals,
+            column_domain,
+        )
+        .interpolate()
     };
 
-    let witness_comms: Witness<N, PolyComm<G>> = {
-        let comm = {
-            |poly: &DensePolynomial<G::ScalarField>| {
-                let mut comm = srs.commit_non_hiding(poly, 1);
-                // In case the column polynomial is all zeroes, we want to mask the commitment
-                comm = srs
-                    .mask_custom(comm.clone(), &comm.map(|_| G::ScalarField::one()))
-                    .unwrap()
-                    .commitment;
-                comm
+    // The columns with a descriptor are committed from their values, with
+    // the same commitment, see [ColumnCommitters]. Whether a column has the
+    // values of its descriptor depends on the witness, and the generic
+    // commitment is used in the constant trace mode.
+    let committers = committers.filter(|_| proving_mode == ProvingMode::Optimized);
+    let specialized_comm = |(i, evals): (usize, &Vec<G::ScalarField>)| {
+        if column_domain(i) != domain.d1 {
+            return None;
+        }
+        committers?.commit(srs, domain, i, evals)
+    };
+    let comm =
+        |(i, poly, specialized): (usize, &DensePolynomial<G::ScalarField>, _)| match specialized {
+            Some(comm) => mask_column_commitment::<G, OpeningProof>(srs, comm),
+            None => {
+                perf.record_commitment(column_domain(i).size as usize, 1, elem_size);
+                commit_column::<G, OpeningProof>(srs, poly)
             }
         };
-        (&witness_polys)
-            .into_par_iter()
-            .map(comm)
-            .collect::<Witness<N, PolyComm<G>>>()
-    };
+
+    // The columns of the first phase are committed before coining the extra
+    // challenges.
+    let first_phase_specialized: Vec<Option<PolyComm<G>>> = first_phase
+        .par_iter()
+        .enumerate()
+        .map(specialized_comm)
+        .collect();
+    let first_phase_polys: Vec<DensePolynomial<G::ScalarField>> = first_phase
+        .clone()
+        .into_par_iter()
+        .enumerate()
+        .map(interpolate)
+        .collect();
+    let first_phase_comms: Vec<PolyComm<G>> = first_phase_polys
+        .par_iter()
+        .zip(first_phase_specialized)
+        .enumerate()
+        .map(|(i, (poly, specialized))| comm((i, poly, specialized)))
+        .collect();
 
     // Do not use parallelism
-    (&witness_comms)
-        .into_iter()
+    first_phase_comms
+        .iter()
+        .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
+
+    let extra_challenges: [G::ScalarField; K] = std::array::from_fn(|_| fq_sponge.challenge());
+
+    let mut inputs = second_phase(&extra_challenges);
+
+    // A circuit whose constraints do not read any lookup column has no lookup
+    // argument, and the verifier rejects its proofs carrying one
+    if !inputs.mvlookups.is_empty() && !mvlookup::uses_lookups(constraints) {
+        return Err(ProverError::UnusedLookups(inputs.mvlookups.len()));
+    }
+    inputs.mvlookups =
+        mvlookup::add_multiplicity_range_checks(inputs.mvlookups, domain.d1.size as usize);
+
+    // The joint combiner is only coined with the lookup argument
+    check_joint_combiner(constraints).map_err(ProverError::Config)?;
+
+    // Without constraints nor lookups, there is nothing to prove
+    if constraints.is_empty() && inputs.mvlookups.is_empty() {
+        return Err(ProverError::EmptyCircuit);
+    }
+
+    // The lookup argument refers to the next row
+    if opening_mode == OpeningMode::SinglePoint && !inputs.mvlookups.is_empty() {
+        return Err(ProverError::Config(ConfigError::LookupsNotSupported));
+    }
+
+    check_sizes(inputs.evaluations.cols.as_slice())?;
+
+    for (i, col) in first_phase.iter().enumerate() {
+        if inputs.evaluations.cols[i] != *col {
+            return Err(ProverError::FirstPhaseColumnMismatch(i));
+        }
+    }
+
+    // Checking the fixed tables against the global ones before doing any more
+    // work
+    if let Some(global_tables) = global_tables {
+        let local_tables: Vec<_> = inputs
+            .mvlookups
+            .iter()
+            .filter_map(|lookup| lookup.fixed_table())
+            .collect();
+        let local_tables =
+            mvlookup::generate_global_tables::<G, OpeningProof, ID>(srs, domain, &local_tables)
+                .map_err(ProverError::TableId)?;
+        for (table_id, comms) in local_tables.tables.iter() {
+            match global_tables.tables.get(table_id) {
+                None => return Err(ProverError::MissingGlobalTable(table_id.to_u32())),
+                Some(global_comms) if global_comms != comms => {
+                    return Err(ProverError::GlobalTableMismatch(table_id.to_u32()))
+                }
+                Some(_) => (),
+            }
+        }
+    }
+
+    // The remaining columns are committed after the extra challenges.
+    let n_first_phase = first_phase.len();
+    let second_phase_specialized: Vec<Option<PolyComm<G>>> = inputs.evaluations.cols
+        [n_first_phase..]
+        .par_iter()
+        .enumerate()
+        .map(|(i, evals)| specialized_comm((n_first_phase + i, evals)))
+        .collect();
+    let second_phase_polys: Vec<DensePolynomial<G::ScalarField>> = inputs
+        .evaluations
+        .into_par_iter()
+        .enumerate()
+        .skip(n_first_phase)
+        .map(interpolate)
+        .collect();
+    let second_phase_comms: Vec<PolyComm<G>> = second_phase_polys
+        .par_iter()
+        .zip(second_phase_specialized)
+        .enumerate()
+        .map(|(i, (poly, specialized))| comm((n_first_phase + i, poly, specialized)))
+        .collect();
+
+    // Do not use parallelism
+    second_phase_comms
+        .iter()
         .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
 
+    let witness_polys: Witness<N, DensePolynomial<G::ScalarField>> = first_phase_polys
+        .into_par_iter()
+        .chain(second_phase_polys)
+        .collect();
+    let witness_comms: Witness<N, PolyComm<G>> = first_phase_comms
+        .into_par_iter()
+        .chain(second_phase_comms)
+        .collect();
+
     // -- Start MVLookup
+    // The tables whose columns of the plookup engine are read by the
+    // constraints are proven with it, the other ones with MVLookup
+    let lookup_engines = LookupEngines::of_constraints(constraints);
     let lookup_env = if !inputs.mvlookups.is_empty() {
-        Some(Env::create::<OpeningProof, EFqSponge>(
+        Some(Env::create_with_engines::<OpeningProof, EFqSponge>(
             inputs.mvlookups,
             domain,
             &mut fq_sponge,
             srs,
-        ))
+            lookup_aggregation,
+            aggregation_direction,
+            lookup_absorption,
+            incoming,
+            perf,
+            lookup_stats,
+            &lookup_engines,
+            proving_mode,
+        )?)
     } else {
         None
     };
 
-    let max_degree = {
-        if lookup_env.is_none() {
-            constraints
-                .iter()
-                .map(|expr| expr.degree(1, 0))
-                .max()
-                .unwrap_or(0)
-        } else {
-            8
+    // When resuming, the commitments must be the ones sent in the first round
+    if let Some(first_round) = first_round {
+        let lookup_counters_comms = Option::map(lookup_env.as_ref(), |lookup_env| {
+            lookup_env.lookup_counters_comm_d1.clone()
+        })
+        .unwrap_or_default();
+        // The first round does not know the engines, and commits to the
+        // multiplicities of the tables of the plookup engine as well
+        let first_round_counters_comms: BTreeMap<ID, PolyComm<G>> = first_round
+            .lookup_counters_comms
+            .iter()
+            .filter(|(id, _)| lookup_engines.engine(**id) == LookupEngineKind::MVLookup)
+            .map(|(id, comm)| (*id, comm.clone()))
+            .collect();
+        if first_round.witness_comms.as_slice() != witness_comms.cols.as_slice()
+            || first_round_counters_comms != lookup_counters_comms
+        {
+            return Err(ProverError::FirstRoundMismatch);
         }
-    };
+    }
+
+    Ok((
+        CommittedColumns {
+            fq_sponge,
+            witness_polys,
+            witness_comms,
+            lookup_env,
+        },
+        extra_challenges,
+    ))
+}
+
+/// Finish the proof from the committed columns, i.e. create and commit to the
+/// quotient polynomial, and open all the polynomials.
+#[allow(clippy::too_many_arguments)]
+fn finish_proving<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    RNG,
+    const N: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    committed: CommittedColumns<N, G, ID, EFqSponge>,
+    extra_challenges: &[G::ScalarField],
+    global_tables: Option<&GlobalTableCommitments<G, ID>>,
+    subdomain: Option<&SubdomainLayout>,
+    degenerate_point_rule: DegeneratePointRule,
+    transcript_counter: Option<&TranscriptCounter>,
+    perf: &PerfCounters,
+    rng: &mut RNG,
+) -> Result<Proof<N, G, OpeningProof, ID, Eval>, ProverError>
+where
+    OpeningProof::SRS: Sync,
+    RNG: RngCore + CryptoRng,
+{
+    let CommittedColumns {
+        mut fq_sponge,
+        witness_polys,
+        witness_comms,
+        lookup_env,
+    } = committed;
+    let group_map = G::Map::setup();
+    let elem_size = G::ScalarField::zero().serialized_size();
+
+    // The constraints of the plookup engine have a low degree, the degree is
+    // only raised for the partial sums of MVLookup
+    let max_degree = max_constraint_degree(
+        constraints,
+        lookup_env.as_ref().map_or(false, |lookup_env| {
+            !lookup_env.lookup_terms_poly_d1.is_empty()
+        }),
+    );
 
     // Don't need to be absorbed. Already absorbed in mvlookup::prover::Env::create
     // FIXME: remove clone
     let mvlookup_comms = Option::map(lookup_env.as_ref(), |lookup_env| LookupProof {
         m: lookup_env.lookup_counters_comm_d1.clone(),
         h: lookup_env.lookup_terms_comms_d1.clone(),
-        sum: lookup_env.lookup_aggregation_comm_d1.clone(),
-        fixed_tables: lookup_env.fixed_lookup_tables_comms_d1.clone(),
+        sum: lookup_env.lookup_aggregation.clone(),
+        // When using global tables, the verifier derives the commitments
+        // itself.
+        fixed_tables: if global_tables.is_some() {
+            BTreeMap::new()
+        } else {
+            lookup_env.fixed_lookup_tables_comms_d1.clone()
+        },
+        plookup: lookup_env.plookup_comms_d1.clone(),
     });
 
     // -- end computing the running sum in lookup_aggregation
@@ -167,7 +2266,10 @@ where
         };
         (&witness_polys)
             .into_par_iter()
-            .map(|evals| evals.evaluate_over_domain_by_ref(domain_eval))
+            .map(|evals| {
+                perf.record_fft(domain_eval.size);
+                evaluate_over_coset(evals, domain_eval)
+            })
             .collect::<Witness<N, Evaluations<G::ScalarField, R2D<G::ScalarField>>>>()
     };
 
@@ -186,6 +2288,48 @@ where
     // TODO These should be evaluations of fixed coefficient polys
     let coefficient_evals_env: Vec<Evaluations<G::ScalarField, R2D<G::ScalarField>>> = vec![];
 
+    let extra_challenges_evals: Vec<Evaluations<G::ScalarField, R2D<G::ScalarField>>> =
+        extra_challenges
+            .iter()
+            .map(|chal| {
+                Evaluations::from_vec_and_domain(vec![*chal; domain.d8.size as usize], domain.d8)
+            })
+            .collect();
+
+    let subdomain_selector_evals = subdomain.map(|layout| {
+        perf.record_fft(domain.d8.size);
+        subdomain::selector_evals_d8(layout.log_size, domain)
+    });
+
+    // The boundary selectors read by the constraints
+    let boundary_selectors_evals: BTreeMap<
+        usize,
+        Evaluations<G::ScalarField, R2D<G::ScalarField>>,
+    > = constraints
+        .iter()
+        .flat_map(column_accesses)
+        .filter_map(|(column, _)| match column {
+            Column::BoundarySelector(max_rows) => Some(max_rows),
+            _ => None,
+        })
+        .collect::<BTreeSet<usize>>()
+        .into_iter()
+        .map(|max_rows| {
+            perf.record_fft(domain.d1.size);
+            perf.record_fft(domain.d8.size);
+            (max_rows, boundary::selector_evals_d8(max_rows, domain))
+        })
+        .collect();
+
+    // The witness columns read two rows ahead, shifted by two rows
+    let shifts = EvaluationShifts::from_constraints(constraints);
+    let shifted_witness_evals: BTreeMap<usize, Evaluations<G::ScalarField, R2D<G::ScalarField>>> =
+        shifts
+            .shifted_columns()
+            .iter()
+            .map(|i| (*i, shifts::shift_evals(&witness_evals[*i], domain.d1)))
+            .collect();
+
     let zk_rows = 0;
     let column_env = {
         let challenges = Challenges {
@@ -193,7 +2337,9 @@ where
             // NB: as there is on permutation argument, we do use the beta
             // field instead of a new one for the evaluation point.
             beta: Option::map(lookup_env.as_ref(), |x| x.beta).unwrap_or(G::ScalarField::zero()),
-            gamma: G::ScalarField::zero(),
+            // The shift of the tables of the plookup engine
+            gamma: Option::and_then(lookup_env.as_ref(), |x| x.gamma)
+                .unwrap_or(G::ScalarField::zero()),
             joint_combiner: Option::map(lookup_env.as_ref(), |x| x.joint_combiner),
         };
         ColumnEnvironment {
@@ -209,29 +2355,32 @@ where
             lookup: Option::map(lookup_env.as_ref(), |lookup_env| {
                 mvlookup::prover::QuotientPolynomialEnvironment {
                     lookup_terms_evals_d8: &lookup_env.lookup_terms_evals_d8,
-                    lookup_aggregation_evals_d8: &lookup_env.lookup_aggregation_evals_d8,
+                    lookup_aggregation_evals_d8: lookup_env.lookup_aggregation_evals_d8.as_ref(),
                     lookup_counters_evals_d8: &lookup_env.lookup_counters_evals_d8,
                     fixed_tables_evals_d8: &lookup_env.fixed_lookup_tables_evals_d8,
+                    plookup_evals_d8: &lookup_env.plookup_evals_d8,
                 }
             }),
             domain,
+            extra_challenges: &extra_challenges_evals,
+            subdomain_selector: Option::zip(subdomain, subdomain_selector_evals.as_ref())
+                .map(|(layout, evals)| (layout.log_size, evals)),
+            boundary_selectors: &boundary_selectors_evals,
+            shifted_witness: &shifted_witness_evals,
         }
     };
 
     let quotient_poly: DensePolynomial<G::ScalarField> = {
-        // Only for debugging purposes
+        // The constraints are evaluated over a coset of their evaluation
+        // domain, where Z_H(X) does not vanish, see [ColumnEnvironment]. A
+        // constraint which is not satisfied on H is caught by the degree of
+        // its quotient, and attributed to it.
         for expr in constraints.iter() {
-            let fail_q_division =
-                ProverError::ConstraintNotSatisfied(format!("Unsatisfied expression: {:}", expr));
-            // Check this expression are witness satisfied
-            let (_, res) = expr
-                .evaluations(&column_env)
-                .interpolate_by_ref()
-                .divide_by_vanishing_poly(domain.d1)
-                .ok_or(fail_q_division.clone())?;
-            if !res.is_zero() {
-                return Err(fail_q_division);
-            }
+            let evaluations = expr.evaluations(&column_env);
+            perf.record_fft(evaluations.domain().size);
+            divide_by_vanishing_poly_on_coset(evaluations, domain.d1).ok_or_else(|| {
+                ProverError::ConstraintNotSatisfied(format!("Unsatisfied expression: {:}", expr))
+            })?;
         }
 
         // Compute ∑ α^i constraint_i as an expression
@@ -253,121 +2402,117 @@ where
         let expr_evaluation: Evaluations<G::ScalarField, R2D<G::ScalarField>> =
             combined_expr.evaluations(&column_env);
 
-        // And we interpolate using the evaluations
-        let expr_evaluation_interpolated = expr_evaluation.interpolate();
-
-        let fail_final_q_division = || {
-            panic!("Division by vanishing poly must not fail at this point, we checked it before")
-        };
-        // We compute the polynomial t(X) by dividing the constraints polynomial
-        // by the vanishing polynomial, i.e. Z_H(X).
-        let (quotient, res) = expr_evaluation_interpolated
-            .divide_by_vanishing_poly(domain.d1)
-            .unwrap_or_else(fail_final_q_division);
-        // As the constraints must be verified on H, the rest of the division
-        // must be equal to 0 as the constraints polynomial and Z_H(X) are both
-        // equal on H.
-        if !res.is_zero() {
-            fail_final_q_division();
-        }
-
-        quotient
+        // We compute the polynomial t(X) by dividing the constraints
+        // polynomial by the vanishing polynomial, i.e. Z_H(X), pointwise over
+        // the coset, and we interpolate the result.
+        perf.record_fft(expr_evaluation.domain().size);
+        divide_by_vanishing_poly_on_coset(expr_evaluation, domain.d1).expect(
+            "The constraint polynomial must vanish on H at this point, we checked the constraints before",
+        )
     };
 
-    let num_chunks: usize = if max_degree == 1 {
-        1
-    } else {
-        (max_degree - 1) as usize
-    };
+    // The verifier expects the commitment to t(X) to have exactly this number
+    // of chunks, see [expected_quotient_chunks]. The degree bound must hold by
+    // construction of the constraint polynomial.
+    let num_chunks: usize = expected_quotient_chunks(max_degree, domain);
+    assert!(
+        quotient_poly.coeffs.len() <= num_chunks * domain.d1.size as usize,
+        "The quotient polynomial does not fit in {num_chunks} chunks"
+    );
 
     //~ 1. commit to the quotient polynomial $t$.
+    perf.record_commitment(domain.d1.size as usize, num_chunks, elem_size);
     let t_comm = srs.commit_non_hiding(&quotient_poly, num_chunks);
 
     ////////////////////////////////////////////////////////////////////////////
-    // Round 3: Evaluations at zeta and zeta_omega
+    // Round 3: Evaluations at zeta and zeta_omega, when opened
     ////////////////////////////////////////////////////////////////////////////
 
     //~ 1. Absorb the the commitment of the quotient polynomial with the Fq-Sponge.
     absorb_commitment(&mut fq_sponge, &t_comm);
 
     //~ 1. Sample $\zeta'$ with the Fq-Sponge.
-    let zeta_chal = ScalarChallenge(fq_sponge.challenge());
-
     //~ 1. Derive $\zeta$ from $\zeta'$ using the endomorphism (TODO: specify)
-    let zeta = zeta_chal.to_field(endo_r);
+    //~ 1. Check that $\zeta$ is not zero or in the domain, following the
+    //~    [DegeneratePointRule] of the configuration.
+    let zeta = degenerate_point_rule
+        .derive_evaluation_point(domain, || {
+            ScalarChallenge(fq_sponge.challenge()).to_field(endo_r)
+        })
+        .ok_or(ProverError::DegenerateEvaluationPoint)?;
 
-    let omega = domain.d1.group_gen;
-    // We will also evaluate at ζω as lookups do require to go to the next row.
-    let zeta_omega = zeta * omega;
+    // We will also evaluate at ζω as lookups do require to go to the next row,
+    // unless the opening mode is single-point.
+    let evaluation_points = Eval::OPENING_MODE.evaluation_points(zeta, domain);
 
-    // Evaluate the polynomials at ζ and ζω -- Columns
-    let witness_evals: Witness<N, PointEvaluations<_>> = {
-        let eval = |p: &DensePolynomial<_>| PointEvaluations {
-            zeta: p.evaluate(&zeta),
-            zeta_omega: p.evaluate(&zeta_omega),
-        };
-        (&witness_polys)
-            .into_par_iter()
-            .map(eval)
-            .collect::<Witness<N, PointEvaluations<_>>>()
-    };
+    // Evaluate the polynomials at the evaluation points -- Columns
+    let eval = |p: &DensePolynomial<_>| Eval::evaluate(p, &evaluation_points);
+    let witness_evals: Witness<N, Eval> = (&witness_polys)
+        .into_par_iter()
+        .map(eval)
+        .collect::<Witness<N, Eval>>();
 
     // IMPROVEME: move this into the mvlookup module
     let mvlookup_evals = lookup_env.as_ref().map(|lookup_env| LookupProof {
         m: lookup_env
             .lookup_counters_poly_d1
             .iter()
-            .map(|(id, poly)| {
-                let zeta = poly.evaluate(&zeta);
-                let zeta_omega = poly.evaluate(&zeta_omega);
-                (*id, PointEvaluations { zeta, zeta_omega })
-            })
+            .map(|(id, poly)| (*id, eval(poly)))
             .collect(),
         h: (&lookup_env.lookup_terms_poly_d1)
             .into_par_iter()
-            .map(|poly| PointEvaluations {
-                zeta: poly.evaluate(&zeta),
-                zeta_omega: poly.evaluate(&zeta_omega),
-            })
+            .map(eval)
             .collect(),
-        sum: PointEvaluations {
-            zeta: lookup_env.lookup_aggregation_poly_d1.evaluate(&zeta),
-            zeta_omega: lookup_env.lookup_aggregation_poly_d1.evaluate(&zeta_omega),
+        sum: match lookup_env.lookup_aggregation_poly_d1 {
+            Some(ref poly) => LookupAggregation::Committed(eval(poly)),
+            None => LookupAggregation::Claimed(PhantomData),
         },
         fixed_tables: {
             lookup_env
                 .fixed_lookup_tables_poly_d1
                 .iter()
-                .map(|(id, poly)| {
-                    let zeta = poly.evaluate(&zeta);
-                    let zeta_omega = poly.evaluate(&zeta_omega);
-                    (*id, PointEvaluations { zeta, zeta_omega })
-                })
+                .map(|(id, poly)| (*id, eval(poly)))
                 .collect()
         },
+        plookup: lookup_env
+            .plookup_poly_d1
+            .iter()
+            .map(|(id, polys)| (*id, polys.as_ref().map(eval)))
+            .collect(),
     });
 
+    // Evaluate the columns read two rows ahead at ζω²
+    let shifted_point = shifts::shifted_point(zeta, domain.d1);
+    let shifted_evals: BTreeMap<usize, G::ScalarField> = shifts
+        .shifted_columns()
+        .iter()
+        .map(|i| (*i, witness_polys[*i].evaluate(&shifted_point)))
+        .collect();
+
     ////////////////////////////////////////////////////////////////////////////
     // Round 4: Opening proof w/o linearization polynomial
     ////////////////////////////////////////////////////////////////////////////
 
     // Fiat Shamir - absorbing evaluations
     let fq_sponge_before_evaluations = fq_sponge.clone();
-    let mut fr_sponge = EFrSponge::new(G::sponge_params());
+    let mut fr_sponge = CountingFrSponge::new(
+        EFrSponge::new(G::sponge_params()),
+        transcript_counter.cloned().unwrap_or_default(),
+    );
     fr_sponge.absorb(&fq_sponge.digest());
 
-    for PointEvaluations { zeta, zeta_omega } in (&witness_evals).into_iter() {
-        fr_sponge.absorb(zeta);
-        fr_sponge.absorb(zeta_omega);
+    for evals in (&witness_evals).into_iter() {
+        evals.to_points().iter().for_each(|x| fr_sponge.absorb(x));
     }
 
     if lookup_env.is_some() {
-        for PointEvaluations { zeta, zeta_omega } in mvlookup_evals.as_ref().unwrap().into_iter() {
-            fr_sponge.absorb(zeta);
-            fr_sponge.absorb(zeta_omega);
+        for evals in mvlookup_evals.as_ref().unwrap().into_iter() {
+            evals.to_points().iter().for_each(|x| fr_sponge.absorb(x));
         }
     }
 
+    shifted_evals.values().for_each(|x| fr_sponge.absorb(x));
+
     // Compute ft(X) = \
     //   (1 - ζ^n) \
     //    (t_0(X) + ζ^n t_1(X) + ... + ζ^{kn} t_{k}(X))
@@ -391,16 +2536,24 @@ where
     // witness columns because ft(X) is the constraint polynomial, built from
     // the public constraints.
     // We evaluate at ζω because the lookup argument requires to compute
-    // \phi(Xω) - \phi(X).
-    let ft_eval1 = ft.evaluate(&zeta_omega);
+    // \phi(Xω) - \phi(X). It is not required in single-point mode.
+    let ft_eval1 = evaluation_points
+        .get(1)
+        .map(|zeta_omega| ft.evaluate(zeta_omega));
 
     // Absorb ft(ζω)
-    fr_sponge.absorb(&ft_eval1);
+    if let Some(ft_eval1) = &ft_eval1 {
+        fr_sponge.absorb(ft_eval1);
+    }
 
     let v_chal = fr_sponge.challenge();
     let v = v_chal.to_field(endo_r);
     let u_chal = fr_sponge.challenge();
     let u = u_chal.to_field(endo_r);
+    // The opening proof is not part of the transcript profile
+    if let Some(counter) = transcript_counter {
+        counter.stop();
+    }
 
     let coefficients_form = DensePolynomialOrEvaluations::DensePolynomial;
     let non_hiding = |d1_size| PolyComm {
@@ -433,11 +2586,10 @@ where
                 .map(|poly| (coefficients_form(poly), non_hiding(1)))
                 .collect::<Vec<_>>(),
         );
-        // -- after that the running sum
-        polynomials.push((
-            coefficients_form(&lookup_env.lookup_aggregation_poly_d1),
-            non_hiding(1),
-        ));
+        // -- after that the running sum, if it is committed
+        if let Some(ref poly) = lookup_env.lookup_aggregation_poly_d1 {
+            polynomials.push((coefficients_form(poly), non_hiding(1)));
+        }
         // -- Adding fixed lookup tables
         polynomials.extend(
             lookup_env
@@ -446,25 +2598,55 @@ where
                 .map(|poly| (coefficients_form(poly), non_hiding(1)))
                 .collect::<Vec<_>>(),
         );
+        // -- Adding the columns of the plookup engine, table by table
+        polynomials.extend(
+            lookup_env
+                .plookup_poly_d1
+                .values()
+                .flat_map(|polys| polys.iter())
+                .map(|poly| (coefficients_form(poly), non_hiding(1)))
+                .collect::<Vec<_>>(),
+        );
     }
     polynomials.push((coefficients_form(&ft), non_hiding(1)));
 
+    // The columns read two rows ahead are opened at ζω² by a second opening
+    // proof, with the same challenges
+    let shifted_opening = (!shifts.is_empty()).then(|| {
+        let shifted_polynomials: Vec<_> = shifts
+            .shifted_columns()
+            .iter()
+            .map(|i| (coefficients_form(&witness_polys[*i]), hiding(1)))
+            .collect();
+        OpenProof::open::<_, _, R2D<G::ScalarField>>(
+            srs,
+            &group_map,
+            shifted_polynomials.as_slice(),
+            &[shifted_point],
+            v,
+            u,
+            fq_sponge_before_evaluations.clone(),
+            rng,
+        )
+    });
+
     let opening_proof = OpenProof::open::<_, _, R2D<G::ScalarField>>(
         srs,
         &group_map,
         polynomials.as_slice(),
-        &[zeta, zeta_omega],
+        &evaluation_points,
         v,
         u,
         fq_sponge_before_evaluations,
         rng,
     );
 
-    let proof_evals: ProofEvaluations<N, G::ScalarField, ID> = {
+    let proof_evals: ProofEvaluations<N, G::ScalarField, ID, Eval> = {
         ProofEvaluations {
             witness_evals,
             mvlookup_evals,
             ft_eval1,
+            shifted_evals,
         }
     };
 
@@ -476,5 +2658,9 @@ where
         },
         proof_evals,
         opening_proof,
+        srs_label: None,
+        public_outputs: PublicOutputs::default(),
+        accumulator: None,
+        shifted_opening,
     })
 }
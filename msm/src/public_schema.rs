@@ -0,0 +1,588 @@
+//! Typed public inputs, see [PublicSchema].
+//!
+//! The public inputs are the first columns of the witness, which the verifier
+//! commits to itself, see [crate::verifier::verify]. Instead of filling these
+//! columns by hand, a circuit declares the named fields of its public inputs
+//! and their types, and each field is assigned to some cells of the public
+//! columns by the layout of the schema. The prover sets the values of the
+//! fields with [ProofInputs::set_public], and the verifier derives the
+//! public columns it expects from the claimed values with
+//! [PublicSchema::public_columns].
+//!
+//! The cells are assigned in the order of the fields, row by row: the cell
+//! `k` is in the column `k % n_columns` and in the row `k / n_columns`. The
+//! other cells of the public columns are zero.
+
+use ark_ff::PrimeField;
+use kimchi::curve::KimchiCurve;
+use num_bigint::BigUint;
+use o1_utils::FieldHelpers;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::{mvlookup::LookupTableID, proof::ProofInputs, witness::Witness};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PublicSchemaError {
+    #[error("the schema has fields but no public column")]
+    NoPublicColumn,
+
+    #[error("the field {0} is declared twice")]
+    DuplicateField(String),
+
+    #[error("the field {name} has limbs of {limb_bits} bits, which is not supported")]
+    InvalidLimbs { name: String, limb_bits: u32 },
+
+    #[error("the field {0} is not in the schema")]
+    UnknownField(String),
+
+    #[error("the field {0} is given twice")]
+    DuplicateValue(String),
+
+    #[error("the field {0} is not given")]
+    MissingField(String),
+
+    #[error("the value of the field {0} does not have the type of the field")]
+    TypeMismatch(String),
+
+    #[error("the value of the field {0} does not fit in its limbs")]
+    ValueTooLarge(String),
+
+    #[error("the schema has {expected} public columns, got {got}")]
+    ColumnCountMismatch { expected: usize, got: usize },
+
+    #[error("the schema has {n_public} public columns, more than the {n_columns} columns of the witness")]
+    TooManyColumns { n_public: usize, n_columns: usize },
+
+    #[error("the schema needs {rows} rows, more than the {domain_size} rows of the domain")]
+    TooManyRows { rows: usize, domain_size: usize },
+}
+
+/// The type of a field of a [PublicSchema], which gives the cells it takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PublicFieldType {
+    /// An element of the native field, in one cell
+    Scalar,
+    /// An integer of 64 bits, in limbs of `limb_bits` bits, the least
+    /// significant limb first
+    U64 { limb_bits: u32 },
+    /// An element of a foreign field, in `n_limbs` limbs of `limb_bits`
+    /// bits, the least significant limb first
+    ForeignField { limb_bits: u32, n_limbs: usize },
+}
+
+impl PublicFieldType {
+    /// The number of cells taken by a field of this type
+    pub fn n_cells(&self) -> usize {
+        match self {
+            PublicFieldType::Scalar => 1,
+            PublicFieldType::U64 { limb_bits } => {
+                (64 + *limb_bits as usize - 1) / *limb_bits as usize
+            }
+            PublicFieldType::ForeignField { n_limbs, .. } => *n_limbs,
+        }
+    }
+
+    fn limb_bits(&self) -> Option<u32> {
+        match self {
+            PublicFieldType::Scalar => None,
+            PublicFieldType::U64 { limb_bits } => Some(*limb_bits),
+            PublicFieldType::ForeignField { limb_bits, .. } => Some(*limb_bits),
+        }
+    }
+}
+
+/// The value of a field of a [PublicSchema], encoded according to the type
+/// of the field, see [PublicSchema::encode]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicValue<F> {
+    Scalar(F),
+    U64(u64),
+    ForeignField(BigUint),
+}
+
+impl<F> PublicValue<F> {
+    /// The value of the element `x` of a foreign field
+    pub fn foreign<Ff: PrimeField>(x: &Ff) -> Self {
+        PublicValue::ForeignField(x.to_biguint())
+    }
+}
+
+/// A named field of a [PublicSchema]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublicField {
+    pub name: String,
+    pub field_type: PublicFieldType,
+    /// The index of the first cell of the field
+    first_cell: usize,
+}
+
+/// The named and typed fields of the public inputs of a circuit, laid out in
+/// the first `n_columns` columns of the witness, see the [module
+/// documentation](crate::public_schema).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PublicSchema {
+    n_columns: usize,
+    fields: Vec<PublicField>,
+}
+
+impl PublicSchema {
+    /// The schema of the fields `fields`, laid out in this order in the
+    /// first `n_columns` columns of the witness. The limbs of the fields must
+    /// have at least one bit, and at most 64 bits for the integers.
+    pub fn new(
+        n_columns: usize,
+        fields: Vec<(&str, PublicFieldType)>,
+    ) -> Result<Self, PublicSchemaError> {
+        if n_columns == 0 && !fields.is_empty() {
+            return Err(PublicSchemaError::NoPublicColumn);
+        }
+        let mut schema = PublicSchema {
+            n_columns,
+            fields: Vec::with_capacity(fields.len()),
+        };
+        let mut first_cell = 0;
+        for (name, field_type) in fields {
+            if schema.field(name).is_some() {
+                return Err(PublicSchemaError::DuplicateField(name.to_string()));
+            }
+            let valid_limbs = match field_type {
+                PublicFieldType::Scalar => true,
+                PublicFieldType::U64 { limb_bits } => (1..=64).contains(&limb_bits),
+                PublicFieldType::ForeignField { limb_bits, .. } => limb_bits >= 1,
+            };
+            if !valid_limbs {
+                return Err(PublicSchemaError::InvalidLimbs {
+                    name: name.to_string(),
+                    limb_bits: field_type.limb_bits().unwrap_or(0),
+                });
+            }
+            schema.fields.push(PublicField {
+                name: name.to_string(),
+                field_type,
+                first_cell,
+            });
+            first_cell += field_type.n_cells();
+        }
+        Ok(schema)
+    }
+
+    /// The number of public columns, the first columns of the witness
+    pub fn n_columns(&self) -> usize {
+        self.n_columns
+    }
+
+    pub fn fields(&self) -> &[PublicField] {
+        &self.fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&PublicField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Whether the schema has neither fields nor public columns
+    pub fn is_empty(&self) -> bool {
+        self.n_columns == 0 && self.fields.is_empty()
+    }
+
+    /// The number of cells taken by the fields
+    pub fn n_cells(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|field| field.field_type.n_cells())
+            .sum()
+    }
+
+    /// The number of rows of the public columns taken by the fields
+    pub fn n_rows(&self) -> usize {
+        if self.n_columns == 0 {
+            0
+        } else {
+            (self.n_cells() + self.n_columns - 1) / self.n_columns
+        }
+    }
+
+    /// The cells `(column, row)` of the field `name`, the least significant
+    /// limb first
+    pub fn cells(&self, name: &str) -> Option<Vec<(usize, usize)>> {
+        let field = self.field(name)?;
+        Some(
+            (field.first_cell..field.first_cell + field.field_type.n_cells())
+                .map(|cell| (cell % self.n_columns, cell / self.n_columns))
+                .collect(),
+        )
+    }
+
+    /// Encode `values`, which give the value of each field of the schema, as
+    /// the assignments `(column, row, value)` of the cells of the fields
+    pub fn encode<F: PrimeField>(
+        &self,
+        values: &[(&str, PublicValue<F>)],
+    ) -> Result<Vec<(usize, usize, F)>, PublicSchemaError> {
+        let mut given: BTreeMap<&str, &PublicValue<F>> = BTreeMap::new();
+        for (name, value) in values {
+            if self.field(name).is_none() {
+                return Err(PublicSchemaError::UnknownField(name.to_string()));
+            }
+            if given.insert(name, value).is_some() {
+                return Err(PublicSchemaError::DuplicateValue(name.to_string()));
+            }
+        }
+
+        let mut assignments = Vec::with_capacity(self.n_cells());
+        for field in self.fields.iter() {
+            let name = || field.name.clone();
+            let value = given
+                .get(field.name.as_str())
+                .ok_or_else(|| PublicSchemaError::MissingField(name()))?;
+            let limbs = match (field.field_type, value) {
+                (PublicFieldType::Scalar, PublicValue::Scalar(x)) => vec![*x],
+                (PublicFieldType::U64 { limb_bits }, PublicValue::U64(x)) => {
+                    limbs::<F>(&BigUint::from(*x), limb_bits, field.field_type.n_cells())
+                        .ok_or_else(|| PublicSchemaError::InvalidLimbs {
+                            name: name(),
+                            limb_bits,
+                        })?
+                }
+                (
+                    PublicFieldType::ForeignField { limb_bits, n_limbs },
+                    PublicValue::ForeignField(x),
+                ) => {
+                    if x.bits() > limb_bits as u64 * n_limbs as u64 {
+                        return Err(PublicSchemaError::ValueTooLarge(name()));
+                    }
+                    limbs::<F>(x, limb_bits, n_limbs).ok_or_else(|| {
+                        PublicSchemaError::InvalidLimbs {
+                            name: name(),
+                            limb_bits,
+                        }
+                    })?
+                }
+                _ => return Err(PublicSchemaError::TypeMismatch(name())),
+            };
+            assignments.extend(limbs.into_iter().enumerate().map(|(i, limb)| {
+                let cell = field.first_cell + i;
+                (cell % self.n_columns, cell / self.n_columns, limb)
+            }));
+        }
+        Ok(assignments)
+    }
+
+    /// The public columns of `domain_size` rows holding `values`, the other
+    /// cells being zero
+    fn columns<F: PrimeField>(
+        &self,
+        values: &[(&str, PublicValue<F>)],
+        domain_size: usize,
+    ) -> Result<Vec<Vec<F>>, PublicSchemaError> {
+        let rows = self.n_rows();
+        if rows > domain_size {
+            return Err(PublicSchemaError::TooManyRows { rows, domain_size });
+        }
+        let mut columns = vec![vec![F::zero(); domain_size]; self.n_columns];
+        for (column, row, value) in self.encode(values)? {
+            columns[column][row] = value;
+        }
+        Ok(columns)
+    }
+
+    /// The public columns the verifier expects for the claimed `values`, to
+    /// be given as the public inputs to the verifier. `NPUB` must be the
+    /// number of public columns of the schema.
+    pub fn public_columns<F: PrimeField, const NPUB: usize>(
+        &self,
+        values: &[(&str, PublicValue<F>)],
+        domain_size: usize,
+    ) -> Result<Witness<NPUB, Vec<F>>, PublicSchemaError> {
+        if NPUB != self.n_columns {
+            return Err(PublicSchemaError::ColumnCountMismatch {
+                expected: self.n_columns,
+                got: NPUB,
+            });
+        }
+        let columns = self.columns(values, domain_size)?;
+        Ok(Witness {
+            cols: Box::new(
+                columns
+                    .try_into()
+                    .expect("the schema has NPUB public columns"),
+            ),
+        })
+    }
+}
+
+/// The `n_limbs` limbs of `bits` bits of `x`, the least significant limb
+/// first, or `None` if the limbs do not fit in `F`
+fn limbs<F: PrimeField>(x: &BigUint, bits: u32, n_limbs: usize) -> Option<Vec<F>> {
+    if bits >= F::size_in_bits() as u32 {
+        return None;
+    }
+    let mask = (BigUint::from(1u64) << bits) - 1u64;
+    (0..n_limbs)
+        .map(|i| F::from_biguint(&((x >> (i * bits as usize)) & &mask)).ok())
+        .collect()
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofInputs<N, G, ID> {
+    /// Set the public columns of the witness, the first
+    /// [PublicSchema::n_columns] columns, to `values` laid out by `schema`.
+    /// The public columns only hold the public values: their other cells are
+    /// set to zero.
+    pub fn set_public(
+        &mut self,
+        schema: &PublicSchema,
+        values: &[(&str, PublicValue<G::ScalarField>)],
+    ) -> Result<(), PublicSchemaError> {
+        if schema.n_columns() > N {
+            return Err(PublicSchemaError::TooManyColumns {
+                n_public: schema.n_columns(),
+                n_columns: N,
+            });
+        }
+        let domain_size = self.evaluations.cols.first().map_or(0, Vec::len);
+        let columns = schema.columns(values, domain_size)?;
+        for (column, values) in self.evaluations.cols.iter_mut().zip(columns) {
+            *column = values;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PublicFieldType, PublicSchema, PublicSchemaError, PublicValue};
+    use crate::{
+        circuit::{prove_circuit, verify_circuit, verify_circuit_with_public, CircuitDefinition},
+        columns::Column,
+        config::ProtocolConfig,
+        expr::{curr_cell, NamedConstraint},
+        lookups::LookupTableIDs,
+        proof::ProofInputs,
+        verifier::VerifierError,
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Ff1, Fp, OpeningProof, ScalarSponge, BN254, LIMB_BITSIZE,
+        N_LIMBS,
+    };
+    use ark_ff::UniformRand;
+    use kimchi::circuits::domains::EvaluationDomains;
+    use num_bigint::BigUint;
+    use o1_utils::FieldHelpers;
+    use poly_commitment::pairing_proof::PairingSRS;
+
+    const N: usize = 4;
+    const NPUB: usize = 2;
+
+    /// A scalar, an integer in 4 limbs of 16 bits and an element of [Ff1] in
+    /// the limbs of the foreign field circuits, in 22 cells
+    fn mixed_schema() -> PublicSchema {
+        PublicSchema::new(
+            NPUB,
+            vec![
+                ("root", PublicFieldType::Scalar),
+                ("nonce", PublicFieldType::U64 { limb_bits: 16 }),
+                (
+                    "point",
+                    PublicFieldType::ForeignField {
+                        limb_bits: LIMB_BITSIZE as u32,
+                        n_limbs: N_LIMBS,
+                    },
+                ),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn mixed_values(root: Fp, nonce: u64, point: &Ff1) -> Vec<(&'static str, PublicValue<Fp>)> {
+        vec![
+            ("root", PublicValue::Scalar(root)),
+            ("nonce", PublicValue::U64(nonce)),
+            ("point", PublicValue::foreign(point)),
+        ]
+    }
+
+    #[test]
+    fn test_mixed_schema_layout() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let schema = mixed_schema();
+        assert_eq!(schema.n_cells(), 1 + 4 + N_LIMBS);
+        assert_eq!(schema.n_rows(), 11);
+        assert_eq!(schema.cells("root").unwrap(), vec![(0, 0)]);
+        assert_eq!(
+            schema.cells("nonce").unwrap(),
+            vec![(1, 0), (0, 1), (1, 1), (0, 2)]
+        );
+        assert_eq!(schema.cells("point").unwrap()[0], (1, 2));
+        assert_eq!(schema.cells("other"), None);
+
+        let root = Fp::rand(&mut rng);
+        let nonce = 0x0123_4567_89ab_cdef;
+        let point = Ff1::rand(&mut rng);
+        let columns = schema
+            .public_columns::<Fp, NPUB>(&mixed_values(root, nonce, &point), 1 << 4)
+            .unwrap();
+        let cell = |(column, row): (usize, usize)| columns.cols[column][row];
+        let recompose = |name: &str, bits: usize| {
+            schema
+                .cells(name)
+                .unwrap()
+                .into_iter()
+                .enumerate()
+                .fold(BigUint::from(0u64), |acc, (i, c)| {
+                    acc + (cell(c).to_biguint() << (i * bits))
+                })
+        };
+        assert_eq!(cell((0, 0)), root);
+        assert_eq!(recompose("nonce", 16), BigUint::from(nonce));
+        assert_eq!(recompose("point", LIMB_BITSIZE), point.to_biguint());
+        // The cells after the fields are zero
+        assert!(columns.cols[1][11..].iter().all(|x| *x == Fp::from(0u64)));
+        assert!(columns.cols[0][11..].iter().all(|x| *x == Fp::from(0u64)));
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let scalar = PublicFieldType::Scalar;
+        assert_eq!(
+            PublicSchema::new(0, vec![("x", scalar)]).unwrap_err(),
+            PublicSchemaError::NoPublicColumn
+        );
+        assert_eq!(
+            PublicSchema::new(1, vec![("x", scalar), ("x", scalar)]).unwrap_err(),
+            PublicSchemaError::DuplicateField("x".to_string())
+        );
+        assert_eq!(
+            PublicSchema::new(1, vec![("x", PublicFieldType::U64 { limb_bits: 0 })]).unwrap_err(),
+            PublicSchemaError::InvalidLimbs {
+                name: "x".to_string(),
+                limb_bits: 0
+            }
+        );
+
+        let schema = mixed_schema();
+        let mut rng = o1_utils::tests::make_test_rng();
+        let values = mixed_values(Fp::rand(&mut rng), 1, &Ff1::rand(&mut rng));
+        let encode = |values: &[(&str, PublicValue<Fp>)]| schema.encode(values).unwrap_err();
+        assert_eq!(
+            encode(&values[1..]),
+            PublicSchemaError::MissingField("root".to_string())
+        );
+        assert_eq!(
+            encode(&[values.clone(), vec![("other", PublicValue::U64(0))]].concat()),
+            PublicSchemaError::UnknownField("other".to_string())
+        );
+        assert_eq!(
+            encode(&[values.clone(), vec![values[0].clone()]].concat()),
+            PublicSchemaError::DuplicateValue("root".to_string())
+        );
+        assert_eq!(
+            encode(&[vec![("root", PublicValue::U64(0))], values[1..].to_vec()].concat()),
+            PublicSchemaError::TypeMismatch("root".to_string())
+        );
+        let too_large = PublicValue::ForeignField(BigUint::from(1u64) << (LIMB_BITSIZE * N_LIMBS));
+        assert_eq!(
+            encode(&[values[..2].to_vec(), vec![("point", too_large)]].concat()),
+            PublicSchemaError::ValueTooLarge("point".to_string())
+        );
+        assert_eq!(
+            schema.public_columns::<Fp, 3>(&values, 1 << 4).unwrap_err(),
+            PublicSchemaError::ColumnCountMismatch {
+                expected: NPUB,
+                got: 3
+            }
+        );
+        assert_eq!(
+            schema.public_columns::<Fp, NPUB>(&values, 8).unwrap_err(),
+            PublicSchemaError::TooManyRows {
+                rows: 11,
+                domain_size: 8
+            }
+        );
+    }
+
+    #[test]
+    fn test_public_schema_end_to_end() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 5;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        // The private column 2 is the sum of the public columns
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let constraints = vec![NamedConstraint::new("sum", x(2) - x(0) - x(1))];
+        let circuit = CircuitDefinition::<Fp, LookupTableIDs, N>::new(
+            domain,
+            ProtocolConfig::default(),
+            constraints,
+            vec![],
+        )
+        .unwrap();
+        let digest = circuit.digest();
+        let circuit = circuit.with_public_schema(mixed_schema()).unwrap();
+        assert_ne!(circuit.digest(), digest);
+
+        let root = Fp::rand(&mut rng);
+        let nonce = u64::rand(&mut rng);
+        let point = Ff1::rand(&mut rng);
+        let values = mixed_values(root, nonce, &point);
+        let mut inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new(std::array::from_fn(|_| {
+                    (0..domain_size).map(|_| Fp::rand(&mut rng)).collect()
+                })),
+            },
+            mvlookups: vec![],
+        };
+        inputs.set_public(circuit.public_schema(), &values).unwrap();
+        let sums: Vec<Fp> = (0..domain_size)
+            .map(|i| inputs.evaluations.cols[0][i] + inputs.evaluations.cols[1][i])
+            .collect();
+        inputs.evaluations.cols[2] = sums;
+
+        let prover_ctx = circuit.prover_context::<_, OpeningProof>(&srs).unwrap();
+        let verifier_ctx = circuit.verifier_context::<_, OpeningProof>(&srs).unwrap();
+        let proof = prove_circuit::<_, OpeningProof, BaseSponge, ScalarSponge, _, _, N>(
+            &prover_ctx,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        let verify = |values: &[(&str, PublicValue<Fp>)]| {
+            verify_circuit_with_public::<_, OpeningProof, BaseSponge, ScalarSponge, _, N, NPUB>(
+                &verifier_ctx,
+                &proof,
+                values,
+            )
+        };
+        verify(&values).unwrap();
+
+        // A falsified public value changes the public columns of the verifier
+        let falsified = mixed_values(root, nonce ^ 1, &point);
+        assert_eq!(
+            verify(&falsified).unwrap_err(),
+            VerifierError::PublicInputMismatch(1)
+        );
+        let falsified = mixed_values(root, nonce, &(point + Ff1::from(1u64)));
+        assert!(matches!(
+            verify(&falsified).unwrap_err(),
+            VerifierError::PublicInputMismatch(_)
+        ));
+        assert_eq!(
+            verify(&values[1..]).unwrap_err(),
+            VerifierError::PublicSchema(PublicSchemaError::MissingField("root".to_string()))
+        );
+        // The public inputs are not optional
+        assert_eq!(
+            verify_circuit::<_, OpeningProof, BaseSponge, ScalarSponge, _, N>(
+                &verifier_ctx,
+                &proof
+            )
+            .unwrap_err(),
+            VerifierError::PublicInputCountMismatch {
+                expected: NPUB,
+                got: 0
+            }
+        );
+    }
+}
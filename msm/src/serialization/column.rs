@@ -13,7 +13,8 @@ pub enum SerializationColumn {
     ChalKimchi(usize),
     /// N_INTERMEDIATE_LIMBS intermediate values, 4 bits long. Represent parts of the IPA challenge.
     ChalIntermediate(usize),
-    /// N_LIMBS values, representing the converted IPA challenge.
+    /// N_LIMBS values, representing the converted IPA challenge, in the order
+    /// of the [super::LimbConvention].
     ChalConverted(usize),
     /// Previous coefficient C_j, this one is looked up. For the row i, the expected
     /// value is (C_i >> 1).
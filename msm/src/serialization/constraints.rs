@@ -10,7 +10,7 @@ use crate::{columns::Column, expr::E};
 use super::{interpreter::InterpreterEnv, Lookup, LookupTable};
 use crate::{
     columns::ColumnIndexer, mvlookup::constraint_lookups,
-    serialization::column::SerializationColumn,
+    serialization::column::SerializationColumn, N_LIMBS,
 };
 
 pub struct Env<Fp> {
@@ -86,6 +86,14 @@ impl<F: PrimeField> InterpreterEnv<F> for Env<F> {
         self.add_lookup(LookupTable::RangeCheck4, value);
     }
 
+    fn range_check_top_limb(&mut self, value: &Self::Variable, bits: u32) {
+        self.add_lookup(LookupTable::TopLimb(bits), value);
+    }
+
+    fn range_check16(&mut self, value: &Self::Variable) {
+        self.add_lookup(LookupTable::RangeCheck16, value);
+    }
+
     fn constant(value: F) -> Self::Variable {
         let cst_expr_inner = ConstantExpr::from(ConstantTerm::Literal(value));
         Expr::Atom(ExprInner::Constant(cst_expr_inner))
@@ -133,13 +141,18 @@ impl<F: PrimeField> Env<F> {
             .collect();
         constraints.extend(relation_constraints);
 
-        assert!(self.lookups[&LookupTable::RangeCheck15].len() == 17);
+        // The limbs of 15 bits, and the top limb if it is checked with its
+        // own table
+        let n_limbs: usize = self
+            .lookups
+            .iter()
+            .filter(|(table_id, _)| **table_id != LookupTable::RangeCheck4)
+            .map(|(_, lookups)| lookups.len())
+            .sum();
+        assert!(n_limbs == N_LIMBS);
         assert!(self.lookups[&LookupTable::RangeCheck4].len() == 20);
 
-        let _lookup_constraint = constraint_lookups(&self.lookups);
-        // FIXME: it seems the constraints are not correctly checked.
-        // Activate lookup constraints after by decommenting the following line
-        // constraints.extend(_lookup_constraint);
+        constraints.extend(constraint_lookups(&self.lookups));
         constraints
     }
 }
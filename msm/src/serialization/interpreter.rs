@@ -4,7 +4,10 @@ use num_integer::Integer;
 use num_traits::{sign::Signed, Euclid};
 
 use crate::{
-    serialization::{column::SerializationColumn, N_INTERMEDIATE_LIMBS},
+    serialization::{
+        column::SerializationColumn, LimbConvention, LimbConventionError, LookupTable,
+        RangeCheckError, U64Decomposition, N_INTERMEDIATE_LIMBS,
+    },
     LIMB_BITSIZE, N_LIMBS,
 };
 use o1_utils::{field_helpers::FieldHelpers, foreign_field::ForeignElement};
@@ -43,6 +46,14 @@ pub trait InterpreterEnv<Fp: PrimeField> {
     /// Check that the value is in the range [0, 2^4-1]
     fn range_check4(&mut self, _value: &Self::Variable);
 
+    /// Check that the value is in the range [0, 2^bits-1], with the table
+    /// [LookupTable::TopLimb]
+    fn range_check_top_limb(&mut self, value: &Self::Variable, bits: u32);
+
+    /// Check that the value is in the range [0, 2^16-1], with the table
+    /// [LookupTable::RangeCheck16]
+    fn range_check16(&mut self, value: &Self::Variable);
+
     fn constant(value: Fp) -> Self::Variable;
 
     /// Extract the bits from the variable `x` between `highest_bit` and `lowest_bit`, and store
@@ -57,6 +68,52 @@ pub trait InterpreterEnv<Fp: PrimeField> {
         position: Self::Position,
     ) -> Self::Variable;
 
+    /// Check that the value in the column `value` is in the range
+    /// [0, 2^64-1]. The value is decomposed into the limbs of `decomposition`,
+    /// written in the columns `limbs` from the least significant one, which
+    /// must recompose to the value and are looked up in their table.
+    /// The value is expected to be encoded in big-endian, see [Self::bitmask_be].
+    /// Returns an error, without writing anything, if the number of columns
+    /// does not match the number of limbs.
+    fn range_check_u64(
+        &mut self,
+        value: Self::Position,
+        limbs: Vec<Self::Position>,
+        decomposition: U64Decomposition,
+    ) -> Result<(), RangeCheckError> {
+        if limbs.len() != decomposition.n_limbs() {
+            return Err(RangeCheckError::LimbCount {
+                expected: decomposition.n_limbs(),
+                got: limbs.len(),
+            });
+        }
+        let value = self.read_column(value);
+        let limb_vars: Vec<_> = limbs
+            .into_iter()
+            .enumerate()
+            .map(|(i, position)| {
+                let (lowest, highest, _) = decomposition.limb(i);
+                self.bitmask_be(&value, highest, lowest, position)
+            })
+            .collect();
+
+        let constraint = limb_vars.iter().enumerate().fold(value, |acc, (i, limb)| {
+            let (lowest, _, _) = decomposition.limb(i);
+            acc - limb.clone() * Self::constant(Fp::from(1u64 << lowest))
+        });
+        self.add_constraint(constraint);
+
+        limb_vars
+            .iter()
+            .enumerate()
+            .for_each(|(i, limb)| match decomposition.limb(i).2 {
+                LookupTable::RangeCheck16 => self.range_check16(limb),
+                LookupTable::RangeCheck4 => self.range_check4(limb),
+                _ => self.range_check15(limb),
+            });
+        Ok(())
+    }
+
     // Helper
     // @volhovm I think we could just use indexer directly without Position.
     fn read_column_direct(&self, pos: SerializationColumn) -> Self::Variable {
@@ -66,7 +123,8 @@ pub trait InterpreterEnv<Fp: PrimeField> {
 
 /// Deserialize a field element of the scalar field of Vesta or Pallas given as
 /// a sequence of 3 limbs of 88 bits.
-/// It will deserialize into limbs of 15 bits.
+/// It will deserialize into limbs of 15 bits, following
+/// [LimbConvention::LITTLE_ENDIAN_255].
 /// Given a scalar field element of Vesta or Pallas, here the decomposition:
 /// ```text
 /// limbs = [limbs0, limbs1, limbs2]
@@ -87,6 +145,25 @@ pub fn deserialize_field_element<Fp: PrimeField, Env: InterpreterEnv<Fp>>(
     env: &mut Env,
     limbs: [u128; 3],
 ) {
+    deserialize_field_element_with_convention(env, limbs, LimbConvention::LITTLE_ENDIAN_255)
+        .expect("the convention of the MSM circuit is supported")
+}
+
+/// Deserialize a field element given as a sequence of 3 limbs of 88 bits into
+/// the limbs of `convention`, see [deserialize_field_element] for the
+/// decomposition. The limb `c_i` of significance `i` is written in the column
+/// given by [LimbConvention::column], and the recomposition constraints are
+/// built with the same positions. The top limb `c16` covers the bits
+/// `64..64 + top_limb_bits` of `limbs2`, which must be smaller than
+/// `2^(64 + top_limb_bits)`.
+/// Returns an error, before writing to `env`, if the convention is not
+/// supported, see [LimbConvention::validate].
+pub fn deserialize_field_element_with_convention<Fp: PrimeField, Env: InterpreterEnv<Fp>>(
+    env: &mut Env,
+    limbs: [u128; 3],
+    convention: LimbConvention,
+) -> Result<(), LimbConventionError> {
+    convention.validate()?;
     // Use this to constrain later
     let kimchi_limbs0 = Env::get_column(SerializationColumn::ChalKimchi(0));
     let kimchi_limbs1 = Env::get_column(SerializationColumn::ChalKimchi(1));
@@ -99,7 +176,7 @@ pub fn deserialize_field_element<Fp: PrimeField, Env: InterpreterEnv<Fp>>(
     // FIXME: should we assert this in the circuit?
     assert!(limbs[0] < 2u128.pow(88));
     assert!(limbs[1] < 2u128.pow(88));
-    assert!(limbs[2] < 2u128.pow(79));
+    assert!(limbs[2] < 2u128.pow((convention.total_bits() - 2 * 88) as u32));
 
     let limb0_var = env.copy(&input_limb0, kimchi_limbs0);
     let limb1_var = env.copy(&input_limb1, kimchi_limbs1);
@@ -122,119 +199,41 @@ pub fn deserialize_field_element<Fp: PrimeField, Env: InterpreterEnv<Fp>>(
     // Range check on each limb
     limb2_vars.iter().for_each(|v| env.range_check4(v));
 
-    let mut fifteen_bits_vars = vec![];
-    {
-        let c0 = Env::get_column(SerializationColumn::ChalConverted(0));
-        let c0_var = env.bitmask_be(&input_limb0, 15, 0, c0);
-        fifteen_bits_vars.push(c0_var)
-    }
-
-    {
-        let c1 = Env::get_column(SerializationColumn::ChalConverted(1));
-        let c1_var = env.bitmask_be(&input_limb0, 30, 15, c1);
-        fifteen_bits_vars.push(c1_var);
-    }
-
-    {
-        let c2 = Env::get_column(SerializationColumn::ChalConverted(2));
-        let c2_var = env.bitmask_be(&input_limb0, 45, 30, c2);
-        fifteen_bits_vars.push(c2_var);
-    }
-
-    {
-        let c3 = Env::get_column(SerializationColumn::ChalConverted(3));
-        let c3_var = env.bitmask_be(&input_limb0, 60, 45, c3);
-        fifteen_bits_vars.push(c3_var)
-    }
-
-    {
-        let c4 = Env::get_column(SerializationColumn::ChalConverted(4));
-        let c4_var = env.bitmask_be(&input_limb0, 75, 60, c4);
-        fifteen_bits_vars.push(c4_var);
-    }
-
-    {
-        let c5 = Env::get_column(SerializationColumn::ChalConverted(5));
-        let res = (limbs[0] >> 75) & ((1 << (88 - 75)) - 1);
-        let res_prime = limbs[1] & ((1 << 2) - 1);
-        let res = res + (res_prime << (15 - 2));
-        let res = Env::constant(Fp::from(res));
-        let c5_var = env.copy(&res, c5);
-        fifteen_bits_vars.push(c5_var);
-    }
-
-    {
-        let c6 = Env::get_column(SerializationColumn::ChalConverted(6));
-        let c6_var = env.bitmask_be(&input_limb1, 17, 2, c6);
-        fifteen_bits_vars.push(c6_var);
-    }
-
-    {
-        let c7 = Env::get_column(SerializationColumn::ChalConverted(7));
-        let c7_var = env.bitmask_be(&input_limb1, 32, 17, c7);
-        fifteen_bits_vars.push(c7_var);
-    }
-
-    {
-        let c8 = Env::get_column(SerializationColumn::ChalConverted(8));
-        let c8_var = env.bitmask_be(&input_limb1, 47, 32, c8);
-        fifteen_bits_vars.push(c8_var);
-    }
-
-    {
-        let c9 = Env::get_column(SerializationColumn::ChalConverted(9));
-        let c9_var = env.bitmask_be(&input_limb1, 62, 47, c9);
-        fifteen_bits_vars.push(c9_var);
-    }
-
-    {
-        let c10 = Env::get_column(SerializationColumn::ChalConverted(10));
-        let c10_var = env.bitmask_be(&input_limb1, 77, 62, c10);
-        fifteen_bits_vars.push(c10_var);
-    }
-
-    {
-        let c11 = Env::get_column(SerializationColumn::ChalConverted(11));
-        let res = (limbs[1] >> 77) & ((1 << (88 - 77)) - 1);
-        let res_prime = limbs[2] & ((1 << 4) - 1);
-        let res = res + (res_prime << (15 - 4));
-        let res = Env::constant(res.into());
-        let c11_var = env.copy(&res, c11);
-        fifteen_bits_vars.push(c11_var);
-    }
-
-    {
-        let c12 = Env::get_column(SerializationColumn::ChalConverted(12));
-        let c12_var = env.bitmask_be(&input_limb2, 19, 4, c12);
-        fifteen_bits_vars.push(c12_var);
-    }
-
-    {
-        let c13 = Env::get_column(SerializationColumn::ChalConverted(13));
-        let c13_var = env.bitmask_be(&input_limb2, 34, 19, c13);
-        fifteen_bits_vars.push(c13_var);
-    }
-
-    {
-        let c14 = Env::get_column(SerializationColumn::ChalConverted(14));
-        let c14_var = env.bitmask_be(&input_limb2, 49, 34, c14);
-        fifteen_bits_vars.push(c14_var);
-    }
-
-    {
-        let c15 = Env::get_column(SerializationColumn::ChalConverted(15));
-        let c15_var = env.bitmask_be(&input_limb2, 64, 49, c15);
-        fifteen_bits_vars.push(c15_var);
-    }
-
-    {
-        let c16 = Env::get_column(SerializationColumn::ChalConverted(16));
-        let c16_var = env.bitmask_be(&input_limb2, 79, 64, c16);
-        fifteen_bits_vars.push(c16_var);
-    }
+    // The limbs of the convention, by significance
+    let input_limbs = [input_limb0, input_limb1, input_limb2];
+    let fifteen_bits_vars: Vec<_> = (0..N_LIMBS)
+        .map(|i| {
+            let (lowest, highest) = convention.limb_range(i);
+            let position =
+                Env::get_column(SerializationColumn::ChalConverted(convention.column(i)));
+            // The input limb holding the lowest bit
+            let k = lowest / 88;
+            if (highest - 1) / 88 == k {
+                env.bitmask_be(
+                    &input_limbs[k],
+                    (highest - 88 * k) as u32,
+                    (lowest - 88 * k) as u32,
+                    position,
+                )
+            } else {
+                // The limb spans two input limbs, e.g. c5 and c11
+                let split = 88 * (k + 1) - lowest;
+                let res = (limbs[k] >> (lowest - 88 * k)) & ((1 << split) - 1);
+                let res_prime = limbs[k + 1] & ((1 << (highest - 88 * (k + 1))) - 1);
+                let res = res + (res_prime << split);
+                env.copy(&Env::constant(Fp::from(res)), position)
+            }
+        })
+        .collect();
 
     // Range check on each limb
-    fifteen_bits_vars.iter().for_each(|v| env.range_check15(v));
+    fifteen_bits_vars
+        .iter()
+        .enumerate()
+        .for_each(|(i, v)| match convention.table(i) {
+            LookupTable::TopLimb(bits) => env.range_check_top_limb(v, bits),
+            _ => env.range_check15(v),
+        });
 
     let shl_88_var = Env::constant(Fp::from(1u128 << 88u128));
     let shl_15_var = Env::constant(Fp::from(1u128 << 15u128));
@@ -276,6 +275,7 @@ pub fn deserialize_field_element<Fp: PrimeField, Env: InterpreterEnv<Fp>>(
         });
         env.add_constraint(constraint);
     }
+    Ok(())
 }
 
 /// Alias for LIMB_BITSIZE, used for convenience.
@@ -1,4 +1,5 @@
-use crate::{mvlookup::LookupTableID, MVLookup};
+use crate::{mvlookup::LookupTableID, MVLookup, LIMB_BITSIZE, N_LIMBS};
+use thiserror::Error;
 
 pub mod column;
 pub mod constraints;
@@ -12,6 +13,14 @@ pub const N_INTERMEDIATE_LIMBS: usize = 20;
 pub enum LookupTable {
     RangeCheck15,
     RangeCheck4,
+    /// The table `[0, 2^16)`, used by the decomposition
+    /// [U64Decomposition::Limbs16]. It requires a domain of size at least
+    /// `2^16`.
+    RangeCheck16,
+    /// The table `[0, 2^bits)`, checking the top limb of a decomposition
+    /// narrower than the other limbs, see [LimbConvention]. The number of bits
+    /// is in `[1, 15)`.
+    TopLimb(u32),
 }
 
 impl LookupTableID for LookupTable {
@@ -19,14 +28,22 @@ impl LookupTableID for LookupTable {
         match self {
             Self::RangeCheck15 => 1,
             Self::RangeCheck4 => 2,
+            Self::RangeCheck16 => 3,
+            Self::TopLimb(bits) => 16 + bits,
         }
     }
 
     fn from_u32(value: u32) -> Self {
+        Self::try_from_u32(value).expect("Invalid lookup table id")
+    }
+
+    fn try_from_u32(value: u32) -> Option<Self> {
         match value {
-            1 => Self::RangeCheck15,
-            2 => Self::RangeCheck4,
-            _ => panic!("Invalid lookup table id"),
+            1 => Some(Self::RangeCheck15),
+            2 => Some(Self::RangeCheck4),
+            3 => Some(Self::RangeCheck16),
+            17..=30 => Some(Self::TopLimb(value - 16)),
+            _ => None,
         }
     }
 
@@ -39,6 +56,172 @@ impl LookupTableID for LookupTable {
         match self {
             Self::RangeCheck15 => 1 << 15,
             Self::RangeCheck4 => 1 << 4,
+            Self::RangeCheck16 => 1 << 16,
+            Self::TopLimb(bits) => 1 << bits,
+        }
+    }
+}
+
+/// The order of the limbs of a decomposition in the columns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    /// The least significant limb is in the first column
+    Little,
+    /// The most significant limb is in the first column
+    Big,
+}
+
+/// Errors returned when a [LimbConvention] is not supported by the circuit
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LimbConventionError {
+    #[error(
+        "the limbs of {0} bits are not supported, the circuit uses limbs of {LIMB_BITSIZE} bits"
+    )]
+    UnsupportedLimbSize(usize),
+
+    #[error("the top limb of {top_limb_bits} bits is not in [1, {limb_bits}]")]
+    InvalidTopLimb {
+        limb_bits: usize,
+        top_limb_bits: usize,
+    },
+}
+
+/// Errors returned when the columns given to
+/// [interpreter::InterpreterEnv::range_check_u64] do not match the
+/// [U64Decomposition]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RangeCheckError {
+    #[error("the decomposition has {expected} limbs, but {got} columns are given")]
+    LimbCount { expected: usize, got: usize },
+}
+
+/// The encoding of the [N_LIMBS] limbs a field element is decomposed into by
+/// [interpreter::deserialize_field_element_with_convention], in the columns
+/// [column::SerializationColumn::ChalConverted].
+/// All the limbs have `limb_bits` bits but the most significant one, which
+/// has `top_limb_bits` bits, and is range checked with the smaller table
+/// [LookupTable::TopLimb] when it is narrower than the other limbs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LimbConvention {
+    pub order: Endianness,
+    pub limb_bits: usize,
+    pub top_limb_bits: usize,
+}
+
+impl LimbConvention {
+    /// The decomposition used by the MSM circuit, into little-endian limbs of
+    /// 15 bits covering 255 bits
+    pub const LITTLE_ENDIAN_255: Self = LimbConvention {
+        order: Endianness::Little,
+        limb_bits: LIMB_BITSIZE,
+        top_limb_bits: LIMB_BITSIZE,
+    };
+
+    /// The decomposition of the external specification, into big-endian
+    /// limbs of 15 bits, whose top limb has 14 bits to cover exactly the 254
+    /// bits of the scalar field of BN254
+    pub const BIG_ENDIAN_254: Self = LimbConvention {
+        order: Endianness::Big,
+        limb_bits: LIMB_BITSIZE,
+        top_limb_bits: LIMB_BITSIZE - 1,
+    };
+
+    /// Check that the circuit supports the convention. The size of the limbs
+    /// is fixed by the tables and the recomposition constraints, which split
+    /// the value at the bit 180.
+    pub fn validate(&self) -> Result<(), LimbConventionError> {
+        if self.limb_bits != LIMB_BITSIZE {
+            return Err(LimbConventionError::UnsupportedLimbSize(self.limb_bits));
+        }
+        if self.top_limb_bits == 0 || self.top_limb_bits > self.limb_bits {
+            return Err(LimbConventionError::InvalidTopLimb {
+                limb_bits: self.limb_bits,
+                top_limb_bits: self.top_limb_bits,
+            });
+        }
+        Ok(())
+    }
+
+    /// The number of bits covered by the limbs
+    pub fn total_bits(&self) -> usize {
+        (N_LIMBS - 1) * self.limb_bits + self.top_limb_bits
+    }
+
+    /// The bits `[lowest, highest)` of the value held by the limb of
+    /// significance `i`, i.e. the `i`-th least significant limb
+    pub fn limb_range(&self, i: usize) -> (usize, usize) {
+        assert!(i < N_LIMBS);
+        let lowest = i * self.limb_bits;
+        if i == N_LIMBS - 1 {
+            (lowest, lowest + self.top_limb_bits)
+        } else {
+            (lowest, lowest + self.limb_bits)
+        }
+    }
+
+    /// The index of the column [column::SerializationColumn::ChalConverted]
+    /// holding the limb of significance `i`
+    pub fn column(&self, i: usize) -> usize {
+        assert!(i < N_LIMBS);
+        match self.order {
+            Endianness::Little => i,
+            Endianness::Big => N_LIMBS - 1 - i,
+        }
+    }
+
+    /// The table range checking the limb of significance `i`
+    pub fn table(&self, i: usize) -> LookupTable {
+        if i == N_LIMBS - 1 && self.top_limb_bits < self.limb_bits {
+            LookupTable::TopLimb(self.top_limb_bits as u32)
+        } else {
+            LookupTable::RangeCheck15
+        }
+    }
+}
+
+/// The decomposition of a 64-bits value into limbs by
+/// [interpreter::InterpreterEnv::range_check_u64]. The limbs are little-endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum U64Decomposition {
+    /// Four limbs of 16 bits, checked with [LookupTable::RangeCheck16]
+    Limbs16,
+    /// Four limbs of 15 bits, checked with [LookupTable::RangeCheck15], and a
+    /// top limb of 4 bits, checked with [LookupTable::RangeCheck4]. It is used
+    /// when the table of 16 bits does not fit in the domain.
+    Limbs15,
+}
+
+impl U64Decomposition {
+    /// The decomposition whose tables fit in a domain of size `domain_size`,
+    /// preferring the limbs of 16 bits. Returns `None` if the domain is
+    /// smaller than `2^15`.
+    pub fn for_domain(domain_size: usize) -> Option<Self> {
+        if domain_size >= LookupTable::RangeCheck16.length() {
+            Some(Self::Limbs16)
+        } else if domain_size >= LookupTable::RangeCheck15.length() {
+            Some(Self::Limbs15)
+        } else {
+            None
+        }
+    }
+
+    /// The number of limbs, i.e. of columns allocated for the limbs
+    pub fn n_limbs(&self) -> usize {
+        match self {
+            Self::Limbs16 => 4,
+            Self::Limbs15 => 5,
+        }
+    }
+
+    /// The bits `[lowest, highest)` of the value held by the limb of
+    /// significance `i`, and the table range checking it
+    pub fn limb(&self, i: usize) -> (u32, u32, LookupTable) {
+        assert!(i < self.n_limbs());
+        let i = i as u32;
+        match self {
+            Self::Limbs16 => (16 * i, 16 * (i + 1), LookupTable::RangeCheck16),
+            Self::Limbs15 if i == 4 => (60, 64, LookupTable::RangeCheck4),
+            Self::Limbs15 => (15 * i, 15 * (i + 1), LookupTable::RangeCheck15),
         }
     }
 }
@@ -47,47 +230,66 @@ pub type Lookup<F> = MVLookup<F, LookupTable>;
 
 #[cfg(test)]
 mod tests {
-    use kimchi::circuits::domains::EvaluationDomains;
+    use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+    use num_bigint::BigUint;
+    use o1_utils::FieldHelpers;
     use poly_commitment::pairing_proof::PairingSRS;
-    use rand::Rng as _;
+    use rand::{rngs::StdRng, Rng as _};
 
-    use super::{Lookup, LookupTable};
+    use super::{
+        Endianness, LimbConvention, LimbConventionError, Lookup, LookupTable, RangeCheckError,
+        U64Decomposition,
+    };
 
     use crate::{
         columns::Column,
-        mvlookup::MVLookupWitness,
+        config::ProtocolConfig,
+        cost::CircuitShape,
+        mvlookup::{
+            check_chain, constraint_lookups, constraint_lookups_with_config, LookupAggregationMode,
+            LookupConstraintOptions, LookupTableID, MVLookupWitness,
+        },
         precomputed_srs::get_bn254_srs,
         proof::ProofInputs,
-        prover::prove,
+        prover::{prove, prove_with_context_and_accumulator, ProverContext, ProverError},
         serialization::{
-            column::SER_N_COLUMNS, constraints, interpreter::deserialize_field_element, witness,
-            N_INTERMEDIATE_LIMBS,
+            column::{SerializationColumn, SER_N_COLUMNS},
+            constraints,
+            interpreter::{deserialize_field_element_with_convention, InterpreterEnv},
+            witness, N_INTERMEDIATE_LIMBS,
         },
-        verifier::verify,
+        verifier::{verify, VerifierContext},
         witness::Witness,
-        BaseSponge, Fp, OpeningProof, ScalarSponge, BN254, N_LIMBS,
+        BaseSponge, Fp, OpeningProof, ScalarSponge, BN254, LIMB_BITSIZE, N_LIMBS,
     };
 
-    use ark_ff::FftField;
+    use ark_ff::{FftField, Field, One, UniformRand, Zero};
+
+    // Must be at least 1 << 15 to support rangecheck15
+    const DOMAIN_SIZE: usize = 1 << 15;
 
     impl LookupTable {
         fn entries<F: FftField>(&self, domain: EvaluationDomains<F>) -> Vec<F> {
             assert!(domain.d1.size >= (1 << 15));
-            match self {
-                Self::RangeCheck15 => (0..domain.d1.size).map(|i| F::from(i)).collect(),
-                Self::RangeCheck4 => (0..domain.d1.size)
-                    .map(|i| if i < (1 << 4) { F::from(i) } else { F::zero() })
-                    .collect(),
-            }
+            let length = match self {
+                Self::RangeCheck15 => domain.d1.size,
+                Self::RangeCheck4 => 1 << 4,
+                Self::RangeCheck16 => 1 << 16,
+                Self::TopLimb(bits) => 1 << bits,
+            };
+            (0..domain.d1.size)
+                .map(|i| if i < length { F::from(i) } else { F::zero() })
+                .collect()
         }
     }
 
-    #[test]
-    fn test_completeness() {
-        let mut rng = o1_utils::tests::make_test_rng();
-        // Must be at least 1 << 15 to support rangecheck15
-        const DOMAIN_SIZE: usize = 1 << 15;
-
+    /// Decompose the same 3 limbs of 88 bits on each row with `convention`,
+    /// and prove and verify the decomposition. Returns the witness.
+    fn prove_decomposition(
+        convention: LimbConvention,
+        limbs: [u128; 3],
+        rng: &mut StdRng,
+    ) -> Box<Witness<SER_N_COLUMNS, Vec<Fp>>> {
         let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
 
         let srs: PairingSRS<BN254> = get_bn254_srs(domain);
@@ -98,51 +300,29 @@ mod tests {
             cols: Box::new(std::array::from_fn(|_| Vec::with_capacity(DOMAIN_SIZE))),
         });
 
-        // Boxing to avoid stack overflow
-        let mut field_elements = vec![];
+        // The looked-up columns of each table, and a column for the fixed
+        // table
+        let mut lookups: std::collections::BTreeMap<LookupTable, Vec<Vec<Lookup<Fp>>>> =
+            Default::default();
+
         // FIXME: we do use always the same values here, because we have a
         // constant check (X - c), different for each row. And there is no
         // constant support/public input yet in the quotient polynomial.
-        let (x, y, z) = (
-            rng.gen_range(0..1000000),
-            rng.gen_range(0..1000000),
-            rng.gen_range(0..1000000),
-        );
         for _ in 0..DOMAIN_SIZE {
-            field_elements.push([x, y, z])
-        }
-
-        // Adding one for the fixed table.
-        let mut rangecheck15: [Vec<Lookup<Fp>>; N_LIMBS + 1] = std::array::from_fn(|_| vec![]);
-        let mut rangecheck4: [Vec<Lookup<Fp>>; N_INTERMEDIATE_LIMBS + 1] =
-            std::array::from_fn(|_| vec![]);
-
-        for (_i, limbs) in field_elements.iter().enumerate() {
             // Witness
-            deserialize_field_element(&mut witness_env, *limbs);
+            deserialize_field_element_with_convention(&mut witness_env, limbs, convention).unwrap();
             // Filling actually used rows
             for j in 0..SER_N_COLUMNS {
                 witness.cols[j].push(witness_env.witness.cols[j]);
             }
 
-            for (j, lookup) in witness_env
-                .lookups
-                .get(&LookupTable::RangeCheck4)
-                .unwrap()
-                .iter()
-                .enumerate()
-            {
-                rangecheck4[j].push(lookup.clone())
-            }
-
-            for (j, lookup) in witness_env
-                .lookups
-                .get(&LookupTable::RangeCheck15)
-                .unwrap()
-                .iter()
-                .enumerate()
-            {
-                rangecheck15[j].push(lookup.clone())
+            for (table_id, row_lookups) in witness_env.lookups.iter() {
+                let columns = lookups
+                    .entry(*table_id)
+                    .or_insert_with(|| vec![vec![]; row_lookups.len()]);
+                for (j, lookup) in row_lookups.iter().enumerate() {
+                    columns[j].push(lookup.clone())
+                }
             }
 
             witness_env.reset()
@@ -150,51 +330,33 @@ mod tests {
 
         let constraints = {
             let mut constraints_env = constraints::Env::<Fp>::create();
-            deserialize_field_element(&mut constraints_env, field_elements[0]);
+            deserialize_field_element_with_convention(&mut constraints_env, limbs, convention)
+                .unwrap();
             constraints_env.get_constraints()
         };
 
-        let rangecheck15_m = witness_env.get_rangecheck15_multipliticies(domain);
-        let rangecheck15_t = LookupTable::RangeCheck15
-            .entries(domain)
+        let mvlookups = lookups
             .into_iter()
-            .enumerate()
-            .map(|(i, v)| Lookup {
-                table_id: LookupTable::RangeCheck15,
-                numerator: -rangecheck15_m[i],
-                value: vec![v],
-            });
-        rangecheck15[N_LIMBS] = rangecheck15_t.collect();
-
-        let rangecheck4_m = witness_env.get_rangecheck4_multipliticies(domain);
-        let rangecheck4_t = LookupTable::RangeCheck4
-            .entries(domain)
-            .into_iter()
-            .enumerate()
-            .map(|(i, v)| Lookup {
-                table_id: LookupTable::RangeCheck4,
-                numerator: -rangecheck4_m[i],
-                value: vec![v],
-            });
-        rangecheck4[N_INTERMEDIATE_LIMBS] = rangecheck4_t.collect();
-
-        let lookup_witness_rangecheck4: MVLookupWitness<Fp, LookupTable> = {
-            MVLookupWitness {
-                f: rangecheck4.to_vec(),
-                m: rangecheck4_m,
-            }
-        };
-
-        let lookup_witness_rangecheck15: MVLookupWitness<Fp, LookupTable> = {
-            MVLookupWitness {
-                f: rangecheck15.to_vec(),
-                m: rangecheck15_m,
-            }
-        };
+            .filter(|(_, columns)| !columns.is_empty())
+            .map(|(table_id, mut f)| {
+                let m = witness_env.get_multiplicities(table_id, domain);
+                let t = table_id
+                    .entries(domain)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| Lookup {
+                        table_id,
+                        numerator: -m[i],
+                        value: vec![v],
+                    });
+                f.push(t.collect());
+                MVLookupWitness { f, m }
+            })
+            .collect();
 
         let proof_inputs = ProofInputs {
-            evaluations: *witness,
-            mvlookups: vec![lookup_witness_rangecheck15, lookup_witness_rangecheck4],
+            evaluations: *witness.clone(),
+            mvlookups,
         };
 
         let proof = prove::<
@@ -206,7 +368,7 @@ mod tests {
             _,
             SER_N_COLUMNS,
             LookupTable,
-        >(domain, &srs, &constraints, proof_inputs, &mut rng)
+        >(domain, &srs, &constraints, proof_inputs, rng)
         .unwrap();
 
         let verifies =
@@ -217,6 +379,402 @@ mod tests {
                 &proof,
                 Witness::zero_vec(DOMAIN_SIZE),
             );
-        assert!(verifies)
+        assert!(verifies);
+        witness
+    }
+
+    #[test]
+    fn test_completeness() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let limbs = [
+            rng.gen_range(0..1000000),
+            rng.gen_range(0..1000000),
+            rng.gen_range(0..1000000),
+        ];
+        prove_decomposition(LimbConvention::LITTLE_ENDIAN_255, limbs, &mut rng);
+    }
+
+    #[test]
+    fn test_limb_conventions() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        // A scalar of BN254, on 254 bits
+        let x: BigUint = Fp::rand(&mut rng).to_biguint();
+        let mask_88 = (BigUint::from(1u8) << 88) - 1u8;
+        let limbs: [u128; 3] =
+            std::array::from_fn(|i| ((&x >> (88 * i)) & &mask_88).try_into().unwrap());
+
+        // The limb of significance `i`, of 15 bits, or of `top_limb_bits`
+        // bits for the last one
+        let limb = |i: usize, top_limb_bits: usize| {
+            let bits = if i == N_LIMBS - 1 {
+                top_limb_bits
+            } else {
+                LIMB_BITSIZE
+            };
+            let limb = (&x >> (LIMB_BITSIZE * i)) & ((BigUint::from(1u8) << bits) - 1u8);
+            Fp::from(limb)
+        };
+        let converted = |witness: &Witness<SER_N_COLUMNS, Vec<Fp>>, j: usize| {
+            let Column::X(col) =
+                crate::columns::ColumnIndexer::to_column(SerializationColumn::ChalConverted(j))
+            else {
+                unreachable!()
+            };
+            witness.cols[col][0]
+        };
+
+        let little_endian = prove_decomposition(LimbConvention::LITTLE_ENDIAN_255, limbs, &mut rng);
+        for j in 0..N_LIMBS {
+            assert_eq!(converted(&little_endian, j), limb(j, LIMB_BITSIZE));
+        }
+
+        // The most significant limb comes first, and is checked with its own
+        // table
+        let convention = LimbConvention::BIG_ENDIAN_254;
+        assert_eq!(convention.total_bits(), 254);
+        assert_eq!(convention.table(N_LIMBS - 1), LookupTable::TopLimb(14));
+        assert_eq!(convention.table(0), LookupTable::RangeCheck15);
+        let big_endian = prove_decomposition(convention, limbs, &mut rng);
+        for j in 0..N_LIMBS {
+            assert_eq!(converted(&big_endian, j), limb(N_LIMBS - 1 - j, 14));
+        }
+        // The other columns do not depend on the convention
+        for col in 0..3 + N_INTERMEDIATE_LIMBS {
+            assert_eq!(big_endian.cols[col], little_endian.cols[col]);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_limb_conventions() {
+        let convention = LimbConvention {
+            order: Endianness::Big,
+            limb_bits: 16,
+            top_limb_bits: 14,
+        };
+        assert_eq!(
+            convention.validate(),
+            Err(LimbConventionError::UnsupportedLimbSize(16))
+        );
+        let convention = LimbConvention {
+            top_limb_bits: 0,
+            ..LimbConvention::BIG_ENDIAN_254
+        };
+        assert_eq!(
+            convention.validate(),
+            Err(LimbConventionError::InvalidTopLimb {
+                limb_bits: 15,
+                top_limb_bits: 0
+            })
+        );
+
+        // The interpreter returns the error without writing the witness
+        let mut witness_env = witness::Env::<Fp>::create();
+        assert_eq!(
+            deserialize_field_element_with_convention(&mut witness_env, [1, 2, 3], convention),
+            Err(LimbConventionError::InvalidTopLimb {
+                limb_bits: 15,
+                top_limb_bits: 0
+            })
+        );
+        assert!(witness_env
+            .lookups
+            .values()
+            .all(|lookups| lookups.is_empty()));
+    }
+
+    /// The witness of [InterpreterEnv::range_check_u64] of `values`, one per
+    /// row: the value in the first column, followed by its limbs
+    fn range_check_u64_witness<const N: usize>(
+        decomposition: U64Decomposition,
+        values: &[u64],
+    ) -> Witness<N, Vec<Fp>> {
+        assert_eq!(N, decomposition.n_limbs() + 1);
+        let mut witness_env = witness::Env::<Fp>::create();
+        let mut witness: Witness<N, Vec<Fp>> = Witness {
+            cols: Box::new(std::array::from_fn(|_| Vec::with_capacity(values.len()))),
+        };
+        for x in values {
+            witness_env.write_column(Column::X(0), Fp::from(*x));
+            witness_env
+                .range_check_u64(Column::X(0), (1..N).map(Column::X).collect(), decomposition)
+                .unwrap();
+            for j in 0..N {
+                witness.cols[j].push(witness_env.witness.cols[j]);
+            }
+            witness_env.reset()
+        }
+        witness
+    }
+
+    /// The lookup witnesses of the limbs of `witness`, built from its columns:
+    /// the values out of their table are looked up, but not counted in the
+    /// multiplicities
+    fn range_check_u64_lookups<const N: usize>(
+        domain: EvaluationDomains<Fp>,
+        decomposition: U64Decomposition,
+        witness: &Witness<N, Vec<Fp>>,
+    ) -> Vec<MVLookupWitness<Fp, LookupTable>> {
+        let mut lookups: std::collections::BTreeMap<LookupTable, Vec<Vec<Lookup<Fp>>>> =
+            Default::default();
+        for i in 0..decomposition.n_limbs() {
+            let (_, _, table_id) = decomposition.limb(i);
+            let column = witness.cols[i + 1]
+                .iter()
+                .map(|value| Lookup {
+                    table_id,
+                    numerator: Fp::one(),
+                    value: vec![*value],
+                })
+                .collect();
+            lookups.entry(table_id).or_default().push(column);
+        }
+        lookups
+            .into_iter()
+            .map(|(table_id, mut f)| {
+                let mut m = vec![Fp::zero(); domain.d1.size as usize];
+                for lookup in f.iter().flatten() {
+                    let value = lookup.value[0].to_biguint();
+                    if value < BigUint::from(table_id.length()) {
+                        let index: usize = value.try_into().unwrap();
+                        m[index] += Fp::one();
+                    }
+                }
+                let t = table_id
+                    .entries(domain)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| Lookup {
+                        table_id,
+                        numerator: -m[i],
+                        value: vec![v],
+                    });
+                f.push(t.collect());
+                MVLookupWitness { f, m }
+            })
+            .collect()
+    }
+
+    /// Prove and verify the range check of random 64-bits values with
+    /// `decomposition`, over a domain of size `domain_size`. Then move `2^b`
+    /// from the second limb to the first one, of `b` bits, which keeps the
+    /// recomposition but puts the first limb out of its table, and check that
+    /// the lookup argument rejects the witness. Same with the top limb, by
+    /// adding `2^64` to the value, and check that the verifier rejects a proof
+    /// of this witness.
+    fn prove_range_check_u64<const N: usize>(domain_size: usize, decomposition: U64Decomposition) {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let mut constraints_env = constraints::Env::<Fp>::create();
+        constraints_env
+            .range_check_u64(Column::X(0), (1..N).map(Column::X).collect(), decomposition)
+            .unwrap();
+        let recomposition: Vec<_> = constraints_env
+            .constraints
+            .iter()
+            .map(|(_, cst)| cst.clone())
+            .collect();
+        // The recomposition, and the constraints of the lookups
+        assert_eq!(recomposition.len(), 1);
+        let mut constraints = recomposition.clone();
+        constraints.extend(constraint_lookups(&constraints_env.lookups));
+
+        let n_lookups = constraints_env
+            .lookups
+            .iter()
+            .map(|(table_id, lookups)| (table_id.to_u32(), lookups.len()))
+            .collect();
+        let shape = CircuitShape::new(domain.d1.size, N, 0, &constraints, n_lookups);
+        assert_eq!(shape.n_columns, decomposition.n_limbs() + 1);
+        let expected_lookups = match decomposition {
+            // Four limbs of 16 bits
+            U64Decomposition::Limbs16 => vec![(LookupTable::RangeCheck16.to_u32(), 4)],
+            // Four limbs of 15 bits, and a top limb of 4 bits
+            U64Decomposition::Limbs15 => vec![
+                (LookupTable::RangeCheck15.to_u32(), 4),
+                (LookupTable::RangeCheck4.to_u32(), 1),
+            ],
+        };
+        assert_eq!(shape.lookups, expected_lookups.into_iter().collect());
+
+        // The first limb of the first value is 5, the second one is 1
+        let (_, bits, first_table) = decomposition.limb(0);
+        let values: Vec<u64> = std::iter::once((1 << bits) + 5)
+            .chain((1..domain_size).map(|_| rng.gen()))
+            .collect();
+        let witness = range_check_u64_witness::<N>(decomposition, &values);
+        let valid = ProofInputs {
+            mvlookups: range_check_u64_lookups(domain, decomposition, &witness),
+            evaluations: witness,
+        };
+
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTable>(
+            domain,
+            &srs,
+            &constraints,
+            valid.clone(),
+            &mut rng,
+        )
+        .unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTable,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        ));
+
+        // The lookup witnesses are rebuilt from the manipulated columns, so
+        // the failure comes from the range check of the first limb
+        let mut manipulated = valid.evaluations.clone();
+        manipulated.cols[1][0] += Fp::from(1u64 << bits);
+        manipulated.cols[2][0] -= Fp::one();
+        let manipulated = ProofInputs {
+            mvlookups: range_check_u64_lookups(domain, decomposition, &manipulated),
+            evaluations: manipulated,
+        };
+        let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTable>(
+            domain,
+            &srs,
+            &constraints,
+            manipulated,
+            &mut rng,
+        );
+        assert!(matches!(
+            res,
+            Err(ProverError::LookupAggregationNonZero { table_id, .. })
+                if table_id == first_table.to_u32()
+        ));
+
+        // Adding `2^b` to the top limb, of `b` bits, adds `2^64` to the
+        // recomposition, so the value is not a 64-bits value anymore. The
+        // failure comes from the range check of the top limb.
+        let top = decomposition.n_limbs() - 1;
+        let (lowest, highest, top_table) = decomposition.limb(top);
+        let mut manipulated = valid.evaluations;
+        manipulated.cols[top + 1][0] += Fp::from(1u64 << (highest - lowest));
+        manipulated.cols[0][0] += Fp::from(2u64).pow([64]);
+        let manipulated = ProofInputs {
+            mvlookups: range_check_u64_lookups(domain, decomposition, &manipulated),
+            evaluations: manipulated,
+        };
+        let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTable>(
+            domain,
+            &srs,
+            &constraints,
+            manipulated.clone(),
+            &mut rng,
+        );
+        assert!(matches!(
+            res,
+            Err(ProverError::LookupAggregationNonZero { table_id, .. })
+                if table_id == top_table.to_u32()
+        ));
+
+        // The prover of a chain of proofs does not check that the lookups are
+        // balanced, and proves the witness anyway. The running sum of the
+        // proof starts from zero, but does not end at zero, so the verifier
+        // rejects the proof as a whole chain, and as a standalone proof.
+        let mut chained_constraints = recomposition;
+        chained_constraints.extend(constraint_lookups_with_config(
+            &constraints_env.lookups,
+            &LookupConstraintOptions {
+                aggregation: LookupAggregationMode::Claimed,
+                ..Default::default()
+            },
+        ));
+        let ctx = ProverContext::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &chained_constraints,
+        )
+        .unwrap();
+        let proof = prove_with_context_and_accumulator::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTable,
+            PointEvaluations<Fp>,
+        >(&ctx, manipulated, Fp::zero(), &mut rng)
+        .unwrap();
+        let boundary = VerifierContext::from_prover_context(&ctx)
+            .unwrap()
+            .verify_with_accumulator::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size),
+                Fp::zero(),
+            )
+            .unwrap();
+        assert!(!boundary.outgoing.is_zero());
+        assert!(!check_chain(&[(boundary.incoming, boundary.outgoing)]));
+        assert!(!verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTable,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        ));
+    }
+
+    #[test]
+    fn test_range_check_u64() {
+        // The table of 16 bits does not fit in the domain
+        assert_eq!(
+            U64Decomposition::for_domain(1 << 16),
+            Some(U64Decomposition::Limbs16)
+        );
+        assert_eq!(U64Decomposition::for_domain(1 << 14), None);
+        let decomposition = U64Decomposition::for_domain(DOMAIN_SIZE).unwrap();
+        assert_eq!(decomposition, U64Decomposition::Limbs15);
+
+        // The columns must match the limbs of the decomposition
+        let mut witness_env = witness::Env::<Fp>::create();
+        witness_env.write_column(Column::X(0), Fp::from(u64::MAX));
+        assert_eq!(
+            witness_env.range_check_u64(
+                Column::X(0),
+                (1..5).map(Column::X).collect(),
+                decomposition
+            ),
+            Err(RangeCheckError::LimbCount {
+                expected: 5,
+                got: 4
+            })
+        );
+        assert!(witness_env
+            .lookups
+            .values()
+            .all(|lookups| lookups.is_empty()));
+
+        prove_range_check_u64::<6>(DOMAIN_SIZE, decomposition);
+    }
+
+    #[test]
+    fn test_range_check_u64_limbs16() {
+        let decomposition = U64Decomposition::for_domain(1 << 16).unwrap();
+        assert_eq!(decomposition, U64Decomposition::Limbs16);
+        prove_range_check_u64::<5>(1 << 16, decomposition);
     }
 }
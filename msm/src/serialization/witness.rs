@@ -4,6 +4,7 @@ use o1_utils::FieldHelpers;
 
 use crate::{
     columns::{Column, ColumnIndexer},
+    mvlookup::LookupTableID,
     serialization::{
         column::{SerializationColumn, SER_N_COLUMNS},
         interpreter::InterpreterEnv,
@@ -118,6 +119,38 @@ impl<F: PrimeField> InterpreterEnv<F> for Env<F> {
             })
     }
 
+    fn range_check_top_limb(&mut self, value: &Self::Variable, bits: u32) {
+        let value_biguint = value.to_biguint();
+        assert!(value_biguint < BigUint::from(2u128.pow(bits)));
+        // Adding multiplicities
+        let table_id = LookupTable::TopLimb(bits);
+        let value_usize: usize = value_biguint.try_into().unwrap();
+        self.lookup_multiplicities
+            .entry(table_id)
+            .or_insert_with(|| vec![F::zero(); table_id.length()])[value_usize] += F::one();
+        self.lookups.entry(table_id).or_default().push(Lookup {
+            table_id,
+            numerator: F::one(),
+            value: vec![*value],
+        })
+    }
+
+    fn range_check16(&mut self, value: &Self::Variable) {
+        let value_biguint = value.to_biguint();
+        assert!(value_biguint < BigUint::from(2u128.pow(16)));
+        // Adding multiplicities
+        let table_id = LookupTable::RangeCheck16;
+        let value_usize: usize = value_biguint.try_into().unwrap();
+        self.lookup_multiplicities
+            .entry(table_id)
+            .or_insert_with(|| vec![F::zero(); table_id.length()])[value_usize] += F::one();
+        self.lookups.entry(table_id).or_default().push(Lookup {
+            table_id,
+            numerator: F::one(),
+            value: vec![*value],
+        })
+    }
+
     fn copy(&mut self, x: &Self::Variable, position: Self::Position) -> Self::Variable {
         self.write_column(position, *x);
         *x
@@ -172,12 +205,37 @@ impl<Fp: PrimeField> Env<Fp> {
                 supposed to write only in witness columns"
                 );
             }
+            Column::Plookup(..) => {
+                panic!(
+                    "This is a lookup related column. The environment is
+                supposed to write only in witness columns"
+                );
+            }
+            Column::ExtraChallenge(_) => {
+                panic!(
+                    "This is a challenge, coined by the verifier. The environment is
+                supposed to write only in witness columns"
+                );
+            }
+            Column::SubdomainSelector(_) | Column::BoundarySelector(_) => {
+                panic!(
+                    "This is a selector, computed by the verifier. The environment is
+                supposed to write only in witness columns"
+                );
+            }
+            Column::XShift2(_) => {
+                panic!(
+                    "This is a witness column two rows ahead. The environment is
+                supposed to write only in the current row"
+                );
+            }
         }
     }
 
     pub fn reset(&mut self) {
-        *self.lookups.get_mut(&LookupTable::RangeCheck4).unwrap() = Vec::new();
-        *self.lookups.get_mut(&LookupTable::RangeCheck15).unwrap() = Vec::new();
+        self.lookups
+            .values_mut()
+            .for_each(|lookups| *lookups = Vec::new());
     }
 
     pub fn get_rangecheck4_multipliticies(&self, domain: EvaluationDomains<Fp>) -> Vec<Fp> {
@@ -194,6 +252,24 @@ impl<Fp: PrimeField> Env<Fp> {
         assert_eq!(domain.d1.size, 1 << 15);
         self.lookup_multiplicities[&LookupTable::RangeCheck15].to_vec()
     }
+
+    /// The multiplicities of the table `table_id`, padded with zeroes up to
+    /// the size of the domain. They are zero if the table is not used.
+    pub fn get_multiplicities(
+        &self,
+        table_id: LookupTable,
+        domain: EvaluationDomains<Fp>,
+    ) -> Vec<Fp> {
+        let size = domain.d1.size as usize;
+        assert!(table_id.length() <= size);
+        let mut m = self
+            .lookup_multiplicities
+            .get(&table_id)
+            .cloned()
+            .unwrap_or_default();
+        m.resize(size, Fp::zero());
+        m
+    }
 }
 
 impl<Fp: PrimeField> Env<Fp> {
@@ -0,0 +1,306 @@
+//! The evaluations of the witness columns two rows after the current row, for
+//! the constraints reading three consecutive rows, e.g. a permutation argument
+//! alongside the lookup argument.
+//!
+//! The constraints read the column `X(i)` two rows ahead with
+//! [crate::expr::shift2_cell], i.e. the column [Column::XShift2]. Each column
+//! is evaluated at the points of the [OpeningMode], and the columns read two
+//! rows ahead are also evaluated at `ζω²`, see [EvaluationShifts::shifts].
+//! These additional evaluations are opened by a second opening proof, at the
+//! single point `ζω²`, with the same challenges as the opening proof of the
+//! other evaluations: the columns which are not read two rows ahead only carry
+//! the evaluations of the opening mode.
+//! The prover evaluates the constraints over a coset of d4 or d8, where the
+//! column shifted by two rows is the column rotated by twice the ratio of the
+//! sizes of the domains, see [shift_evals].
+
+use ark_ff::{FftField, Field, Zero};
+use ark_poly::{EvaluationDomain, Evaluations, Radix2EvaluationDomain as R2D};
+use kimchi::{
+    circuits::{
+        expr::{ColumnEvaluations, ExprError},
+        gate::CurrOrNext,
+    },
+    proof::PointEvaluations,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    columns::Column,
+    config::OpeningMode,
+    expr::{column_accesses, E},
+};
+
+/// The number of rows the columns of [Column::XShift2] are shifted by
+pub const SHIFT: u64 = 2;
+
+/// The shifts of the evaluation points of each witness column, i.e. the
+/// powers of ω the challenge ζ is multiplied by, as defined by the
+/// constraints of the circuit
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EvaluationShifts {
+    /// The witness columns read two rows ahead
+    shifted_columns: BTreeSet<usize>,
+}
+
+impl EvaluationShifts {
+    /// The shifts of the columns read by `constraints`
+    pub fn from_constraints<F>(constraints: &[E<F>]) -> Self {
+        let shifted_columns = constraints
+            .iter()
+            .flat_map(column_accesses)
+            .filter_map(|(column, _)| match column {
+                Column::XShift2(i) => Some(i),
+                _ => None,
+            })
+            .collect();
+        EvaluationShifts { shifted_columns }
+    }
+
+    /// The witness columns read two rows ahead, in increasing order
+    pub fn shifted_columns(&self) -> &BTreeSet<usize> {
+        &self.shifted_columns
+    }
+
+    /// Whether no column is read two rows ahead, i.e. whether the proofs
+    /// only carry the evaluations of the opening mode
+    pub fn is_empty(&self) -> bool {
+        self.shifted_columns.is_empty()
+    }
+
+    /// The shifts of the evaluation points of the witness column `col`: the
+    /// ones of `opening_mode`, i.e. `[0, 1]` by default, followed by [SHIFT]
+    /// if the column is read two rows ahead
+    pub fn shifts(&self, col: usize, opening_mode: OpeningMode) -> Vec<u64> {
+        let mut shifts: Vec<u64> = (0..opening_mode.n_points() as u64).collect();
+        if self.shifted_columns.contains(&col) {
+            shifts.push(SHIFT);
+        }
+        shifts
+    }
+}
+
+/// The point `ζω²` the columns read two rows ahead are evaluated at
+pub fn shifted_point<F: FftField>(zeta: F, domain: R2D<F>) -> F {
+    zeta * domain.group_gen.square()
+}
+
+/// The evaluations over a coset of the column with the evaluations `evals`
+/// over the same coset, shifted by two rows of `d1`
+pub fn shift_evals<F: FftField>(
+    evals: &Evaluations<F, R2D<F>>,
+    d1: R2D<F>,
+) -> Evaluations<F, R2D<F>> {
+    let domain = evals.domain();
+    let rotation = (SHIFT * (domain.size / d1.size)) as usize;
+    let mut shifted = evals.evals.clone();
+    shifted.rotate_left(rotation % domain.size());
+    Evaluations::from_vec_and_domain(shifted, domain)
+}
+
+/// An adapter of the evaluations `inner` of the columns at ζ and ζω, adding
+/// the evaluations at ζω² of the columns read two rows ahead
+pub struct ShiftedEvaluations<'a, F, C> {
+    inner: &'a C,
+    /// The evaluations at ζω², indexed by the witness column
+    shifted: &'a BTreeMap<usize, F>,
+}
+
+impl<'a, F: Field, C: ColumnEvaluations<F, Column = Column>> ShiftedEvaluations<'a, F, C> {
+    pub fn new(inner: &'a C, shifted: &'a BTreeMap<usize, F>) -> Self {
+        ShiftedEvaluations { inner, shifted }
+    }
+
+    /// The evaluation of `col` at ζω^shift, for the shifts `0` and `1` of the
+    /// opening mode, and the shift [SHIFT] of the witness columns read two
+    /// rows ahead
+    pub fn evaluate_shift(&self, col: Column, shift: u64) -> Result<F, ExprError<Column>> {
+        match (col, shift) {
+            (_, 0) => Ok(self.inner.evaluate(col)?.zeta),
+            (_, 1) => Ok(self.inner.evaluate(col)?.zeta_omega),
+            (Column::X(i), SHIFT) => Ok(self.evaluate(Column::XShift2(i))?.zeta),
+            _ => Err(ExprError::MissingEvaluation(col, CurrOrNext::Next)),
+        }
+    }
+}
+
+impl<'a, F: Field, C: ColumnEvaluations<F, Column = Column>> ColumnEvaluations<F>
+    for ShiftedEvaluations<'a, F, C>
+{
+    type Column = Column;
+
+    fn evaluate(&self, col: Self::Column) -> Result<PointEvaluations<F>, ExprError<Self::Column>> {
+        match col {
+            Column::XShift2(i) => shifted_point_evaluations(self.shifted, i)
+                .ok_or(ExprError::MissingEvaluation(col, CurrOrNext::Curr)),
+            col => self.inner.evaluate(col),
+        }
+    }
+}
+
+/// The evaluations of [Column::XShift2] for the column `i`, as expected by the
+/// expression framework. The column is only read at the current row, and its
+/// evaluation at ζω is zero.
+pub(crate) fn shifted_point_evaluations<F: Copy + Zero>(
+    shifted: &BTreeMap<usize, F>,
+    i: usize,
+) -> Option<PointEvaluations<F>> {
+    shifted.get(&i).map(|zeta| PointEvaluations {
+        zeta: *zeta,
+        zeta_omega: F::zero(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ProtocolConfig,
+        expr::{curr_cell, next_cell, shift2_cell},
+        lookups::LookupTableIDs,
+        proof::{EvaluationContainer, Proof, ProofInputs},
+        prover::{prove, ProverError},
+        verifier::{VerifierContext, VerifierError},
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::UniformRand;
+    use kimchi::circuits::domains::EvaluationDomains;
+    use poly_commitment::pairing_proof::PairingSRS;
+
+    const N: usize = 4;
+
+    type Ctx<'a> = VerifierContext<'a, BN254G1Affine, OpeningProof, LookupTableIDs>;
+
+    fn verify(
+        ctx: &Ctx,
+        proof: &Proof<N, BN254G1Affine, OpeningProof, LookupTableIDs>,
+    ) -> Result<(), VerifierError> {
+        ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            proof,
+            Witness::zero_vec(1),
+        )
+    }
+
+    #[test]
+    fn test_three_consecutive_rows() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let mut srs: PairingSRS<BN254> =
+            PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        let n = domain.d1.size as usize;
+
+        // X(0) alternates between a and b, and X(1) is the product of two
+        // consecutive rows of X(0): X(0)[i] X(0)[i + 1] and
+        // X(0)[i + 1] X(0)[i + 2] are both X(1)[i]
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let constraints = vec![
+            x(0) * next_cell(Column::X(0)) - x(1),
+            next_cell(Column::X(0)) * shift2_cell(Column::X(0)) - x(1),
+        ];
+        let (a, b) = (Fp::rand(&mut rng), Fp::rand(&mut rng));
+        let mut cols: [Vec<Fp>; N] =
+            std::array::from_fn(|_| (0..n).map(|_| Fp::rand(&mut rng)).collect());
+        cols[0] = (0..n).map(|i| if i % 2 == 0 { a } else { b }).collect();
+        cols[1] = vec![a * b; n];
+        let inputs = |cols: [Vec<Fp>; N]| ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups: vec![],
+        };
+
+        let shifts = EvaluationShifts::from_constraints(&constraints);
+        assert_eq!(shifts.shifted_columns(), &BTreeSet::from([0]));
+        assert_eq!(shifts.shifts(0, OpeningMode::TwoPoints), vec![0, 1, 2]);
+        assert_eq!(shifts.shifts(2, OpeningMode::TwoPoints), vec![0, 1]);
+
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs(cols.clone()),
+            &mut rng,
+        )
+        .unwrap();
+        let ctx = Ctx::new(domain, &srs, ProtocolConfig::default(), &constraints).unwrap();
+        verify(&ctx, &proof).unwrap();
+
+        // Only the column read two rows ahead is evaluated at ζω², the others,
+        // read or not, carry the two evaluations of the opening mode
+        let evals = &proof.proof_evals;
+        assert_eq!(
+            evals.shifted_evals().keys().copied().collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert!(proof.shifted_opening.is_some());
+        assert!((&evals.witness_evals)
+            .into_iter()
+            .all(|evals| evals.to_points().len() == 2));
+        let adapter = ShiftedEvaluations::new(evals, evals.shifted_evals());
+        assert_eq!(
+            adapter.evaluate_shift(Column::X(0), SHIFT).unwrap(),
+            evals.shifted_evals()[&0]
+        );
+        assert_eq!(
+            adapter.evaluate_shift(Column::X(0), 1).unwrap(),
+            evals.witness_evals[0].zeta_omega
+        );
+        assert!(adapter.evaluate_shift(Column::X(2), SHIFT).is_err());
+
+        // The proofs are serialized with their evaluations at ζω²
+        let bytes = rmp_serde::to_vec(&proof).unwrap();
+        let decoded: Proof<N, BN254G1Affine, OpeningProof, LookupTableIDs> =
+            rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+
+        // The evaluations at ζω² are opened
+        let mut tampered = proof.clone();
+        *tampered.proof_evals.shifted_evals.get_mut(&0).unwrap() += Fp::from(1u64);
+        assert!(verify(&ctx, &tampered).is_err());
+
+        // And they are required
+        let mut missing = proof.clone();
+        missing.proof_evals.shifted_evals.clear();
+        assert!(matches!(
+            verify(&ctx, &missing),
+            Err(VerifierError::MalformedProof(_))
+        ));
+        let missing = Proof {
+            shifted_opening: None,
+            ..proof
+        };
+        assert!(matches!(
+            verify(&ctx, &missing),
+            Err(VerifierError::MalformedProof(_))
+        ));
+
+        // The witness must satisfy the constraint on the third row
+        cols[0][3] = a;
+        let result = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs(cols),
+            &mut rng,
+        );
+        assert!(matches!(
+            result,
+            Err(ProverError::ConstraintNotSatisfied(_))
+        ));
+    }
+
+    #[test]
+    fn test_shifted_column_at_next_row() {
+        let shifted = shift2_cell::<Fp>(Column::X(0));
+        let next = next_cell::<Fp>(Column::XShift2(0));
+        assert!(ProtocolConfig::default()
+            .compile_constraints(vec![shifted])
+            .is_ok());
+        assert_eq!(
+            ProtocolConfig::default().compile_constraints(vec![next]),
+            Err(crate::config::ConfigError::ShiftedColumnAtNextRow(0))
+        );
+    }
+}
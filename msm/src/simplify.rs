@@ -0,0 +1,404 @@
+//! Simplification of the constraints before they are proven, see
+//! [simplify].
+//!
+//! The interpreters of the gadgets build the constraints term by term, e.g.
+//! multiplying the limbs by powers of two, and the expressions keep the
+//! nested products of literals and the additions of zero that the operators
+//! of [kimchi::circuits::expr::Expr] do not fold across several levels.
+//! The simplified constraints have the same value on every assignment of the
+//! cells and of the challenges, and the same degree, which gives the number
+//! of chunks of the quotient polynomial.
+
+use ark_ff::{Field, One};
+use kimchi::circuits::expr::{ConstantExpr, ExprInner, Literal, Operations};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+use crate::expr::E;
+
+/// Whether [crate::config::ProtocolConfig::compile_constraints] simplifies
+/// the constraints, see [simplify]. Both ways give the same proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConstraintSimplification {
+    #[default]
+    Enabled,
+    /// The constraints are proven as they are given
+    Disabled,
+}
+
+/// Simplify `expr`:
+/// - the operations on literals are folded, in the constants as in the
+///   expression,
+/// - the literal terms of each sum are added, and the zero sum is removed,
+/// - the literal factors of each product are multiplied, and the factor one
+///   is removed. A product by zero is kept, as removing it would lower the
+///   degree of the expression,
+/// - the terms of the sums and the factors of the products are sorted, for
+///   the equal subexpressions of different constraints to be written the
+///   same way.
+///
+/// The cached subexpressions and the features are kept as they are.
+pub fn simplify<F: Field>(expr: E<F>) -> E<F> {
+    simplify_operations(expr, &|atom| match atom {
+        ExprInner::Constant(c) => ExprInner::Constant(simplify_constant(c)),
+        atom => atom,
+    })
+}
+
+/// The number of nodes of `expr`, counting the operations and the atoms of
+/// the expression and of its constants
+pub fn node_count<F>(expr: &E<F>) -> usize {
+    count_operations(expr, &|atom| match atom {
+        ExprInner::Constant(c) => count_operations(c, &|_| 1),
+        _ => 1,
+    })
+}
+
+fn simplify_constant<F: Field>(c: ConstantExpr<F>) -> ConstantExpr<F> {
+    simplify_operations(c, &|atom| atom)
+}
+
+fn count_operations<T>(op: &Operations<T>, atom: &impl Fn(&T) -> usize) -> usize {
+    let rec = |x: &Operations<T>| count_operations(x, atom);
+    match op {
+        Operations::Atom(x) => atom(x),
+        Operations::Add(x, y) | Operations::Sub(x, y) | Operations::Mul(x, y) => {
+            1 + rec(x) + rec(y)
+        }
+        Operations::Double(x)
+        | Operations::Square(x)
+        | Operations::Pow(x, _)
+        | Operations::Cache(_, x) => 1 + rec(x),
+        Operations::IfFeature(_, x, y) => 1 + rec(x) + rec(y),
+    }
+}
+
+fn literal<T: Literal + Clone>(x: T::F) -> Operations<T> {
+    <Operations<T> as Literal>::literal(x)
+}
+
+/// The key of the canonical order of the terms and of the factors
+fn order_key<T: Debug>(op: &Operations<T>) -> String {
+    format!("{op:?}")
+}
+
+fn simplify_operations<T, F>(op: Operations<T>, atom: &impl Fn(T) -> T) -> Operations<T>
+where
+    T: Literal<F = F> + Clone + PartialEq + Debug,
+    F: Field,
+{
+    match op {
+        Operations::Atom(x) => Operations::Atom(atom(x)),
+        Operations::Add(_, _) | Operations::Sub(_, _) => simplify_sum(op, atom),
+        Operations::Mul(_, _) => {
+            let (coefficient, factors) = simplify_product(op, atom);
+            rebuild_product(coefficient, factors)
+        }
+        Operations::Double(x) => {
+            let x = simplify_operations(*x, atom);
+            match x.to_literal_ref() {
+                Some(x) => literal(x.double()),
+                None => Operations::Double(Box::new(x)),
+            }
+        }
+        Operations::Square(x) => {
+            let x = simplify_operations(*x, atom);
+            match x.to_literal_ref() {
+                Some(x) => literal(x.square()),
+                None => Operations::Square(Box::new(x)),
+            }
+        }
+        Operations::Pow(x, n) => {
+            let x = simplify_operations(*x, atom);
+            match x.to_literal_ref() {
+                Some(x) => literal(x.pow([n])),
+                None if n == 1 => x,
+                None => Operations::Pow(Box::new(x), n),
+            }
+        }
+        op @ (Operations::Cache(_, _) | Operations::IfFeature(_, _, _)) => op,
+    }
+}
+
+/// Add the terms of the sum `op` to `terms`, with their sign
+fn flatten_sum<T>(op: Operations<T>, negated: bool, terms: &mut Vec<(bool, Operations<T>)>) {
+    match op {
+        Operations::Add(x, y) => {
+            flatten_sum(*x, negated, terms);
+            flatten_sum(*y, negated, terms);
+        }
+        Operations::Sub(x, y) => {
+            flatten_sum(*x, negated, terms);
+            flatten_sum(*y, !negated, terms);
+        }
+        op => terms.push((negated, op)),
+    }
+}
+
+fn simplify_sum<T, F>(op: Operations<T>, atom: &impl Fn(T) -> T) -> Operations<T>
+where
+    T: Literal<F = F> + Clone + PartialEq + Debug,
+    F: Field,
+{
+    let mut flattened = vec![];
+    flatten_sum(op, false, &mut flattened);
+    let mut constant = F::zero();
+    let mut terms = vec![];
+    while let Some((negated, term)) = flattened.pop() {
+        let (negated, term) = match term {
+            // The products by -1 are subtracted
+            Operations::Mul(_, _) => {
+                let (coefficient, factors) = simplify_product(term, atom);
+                if coefficient == -F::one() && !factors.is_empty() {
+                    (!negated, rebuild_product(F::one(), factors))
+                } else {
+                    (negated, rebuild_product(coefficient, factors))
+                }
+            }
+            term => (negated, simplify_operations(term, atom)),
+        };
+        // The term may be a sum itself once simplified, e.g. a product by one
+        if matches!(term, Operations::Add(_, _) | Operations::Sub(_, _)) {
+            flatten_sum(term, negated, &mut flattened);
+            continue;
+        }
+        match term.to_literal_ref() {
+            Some(x) if negated => constant -= x,
+            Some(x) => constant += x,
+            None => terms.push((negated, order_key(&term), term)),
+        }
+    }
+    // The added terms come first, then the subtracted ones
+    terms.sort_by(|(n1, k1, _), (n2, k2, _)| n1.cmp(n2).then_with(|| k1.cmp(k2)));
+    let mut terms = terms.into_iter().map(|(negated, _, term)| (negated, term));
+    let mut res = match terms.next() {
+        None => return literal(constant),
+        Some((false, term)) => term,
+        Some((true, term)) if constant.is_zero() => {
+            let negation = Operations::Mul(Box::new(literal(-F::one())), Box::new(term));
+            let (coefficient, factors) = simplify_product(negation, atom);
+            rebuild_product(coefficient, factors)
+        }
+        Some((true, term)) => {
+            let res = Operations::Sub(Box::new(literal(constant)), Box::new(term));
+            constant = F::zero();
+            res
+        }
+    };
+    for (negated, term) in terms {
+        res = if negated {
+            Operations::Sub(Box::new(res), Box::new(term))
+        } else {
+            Operations::Add(Box::new(res), Box::new(term))
+        };
+    }
+    if !constant.is_zero() {
+        res = Operations::Add(Box::new(res), Box::new(literal(constant)));
+    }
+    res
+}
+
+/// Add the factors of the product `op` to `factors`
+fn flatten_product<T>(op: Operations<T>, factors: &mut Vec<Operations<T>>) {
+    match op {
+        Operations::Mul(x, y) => {
+            flatten_product(*x, factors);
+            flatten_product(*y, factors);
+        }
+        op => factors.push(op),
+    }
+}
+
+/// The product of the literal factors of `op`, and its other factors,
+/// simplified and sorted
+fn simplify_product<T, F>(op: Operations<T>, atom: &impl Fn(T) -> T) -> (F, Vec<Operations<T>>)
+where
+    T: Literal<F = F> + Clone + PartialEq + Debug,
+    F: Field,
+{
+    let mut flattened = vec![];
+    flatten_product(op, &mut flattened);
+    let mut coefficient = F::one();
+    let mut factors = vec![];
+    for factor in flattened {
+        let factor = simplify_operations(factor, atom);
+        match factor.to_literal_ref() {
+            Some(x) => coefficient *= x,
+            None => {
+                // The factor may be a product itself once simplified
+                let mut inner = vec![];
+                flatten_product(factor, &mut inner);
+                for factor in inner {
+                    match factor.to_literal_ref() {
+                        Some(x) => coefficient *= x,
+                        None => factors.push((order_key(&factor), factor)),
+                    }
+                }
+            }
+        }
+    }
+    factors.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    (
+        coefficient,
+        factors.into_iter().map(|(_, factor)| factor).collect(),
+    )
+}
+
+/// The product of the literal `coefficient` and of `factors`, the literal
+/// being the first factor unless it is one
+fn rebuild_product<T: Literal>(coefficient: T::F, factors: Vec<Operations<T>>) -> Operations<T>
+where
+    T::F: Field,
+{
+    let mut factors = factors.into_iter();
+    let Some(first) = factors.next() else {
+        return literal(coefficient);
+    };
+    let first = if coefficient.is_one() {
+        first
+    } else {
+        Operations::Mul(Box::new(literal(coefficient)), Box::new(first))
+    };
+    factors.fold(first, |acc, factor| {
+        Operations::Mul(Box::new(acc), Box::new(factor))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{node_count, simplify, ConstraintSimplification};
+    use crate::{
+        columns::Column,
+        config::ProtocolConfig,
+        equivalence::constraints_equivalent,
+        expr::{curr_cell, next_cell, E},
+        fec::{
+            constraint::ConstraintBuilderEnv, interpreter as fec_interpreter,
+            interpreter::FECInterpreterEnv,
+        },
+        Ff1, Fp,
+    };
+    use ark_ff::Zero;
+    use kimchi::circuits::expr::{
+        ChallengeTerm, ConstantExpr, ConstantTerm, ExprInner, Operations,
+    };
+
+    type BinaryOp = fn(Box<E<Fp>>, Box<E<Fp>>) -> E<Fp>;
+
+    fn literal(x: u64) -> E<Fp> {
+        E::Atom(ExprInner::Constant(ConstantExpr::from(
+            ConstantTerm::Literal(Fp::from(x)),
+        )))
+    }
+
+    /// The constraints of the foreign field multiplications of the addition of
+    /// points
+    fn fec_constraints() -> Vec<E<Fp>> {
+        let mut env = ConstraintBuilderEnv::<Fp>::empty();
+        fec_interpreter::constrain_ec_addition::<Fp, Ff1, _>(&mut env, 0);
+        env.constraints
+    }
+
+    #[test]
+    fn test_simplify_folds_literals() {
+        let x = curr_cell::<Fp>(Column::X(0));
+        let y = next_cell::<Fp>(Column::X(1));
+        // Built without the operators, which would fold some of the literals
+        let raw = |op: BinaryOp, a: E<Fp>, b: E<Fp>| op(Box::new(a), Box::new(b));
+        let expr = raw(
+            Operations::Add,
+            raw(
+                Operations::Mul,
+                raw(Operations::Mul, literal(3), x.clone()),
+                literal(5),
+            ),
+            raw(
+                Operations::Sub,
+                raw(Operations::Add, y.clone(), literal(0)),
+                raw(Operations::Mul, literal(1), literal(2)),
+            ),
+        );
+        let simplified = simplify(expr.clone());
+        // y + 15 * x + (-2)
+        assert_eq!(node_count(&expr), 13);
+        assert_eq!(node_count(&simplified), 7);
+        assert_eq!(simplified.degree(1, 0), expr.degree(1, 0));
+        assert_eq!(
+            simplified,
+            simplify(y.clone() - literal(2) + literal(15) * x.clone())
+        );
+
+        // The constant subexpressions are folded too
+        let constant_literal = |x: u64| ConstantExpr::from(ConstantTerm::Literal(Fp::from(x)));
+        let constant = E::Atom(ExprInner::Constant(Operations::Mul(
+            Box::new(Operations::Mul(
+                Box::new(constant_literal(2)),
+                Box::new(constant_literal(3)),
+            )),
+            Box::new(ConstantExpr::from(ChallengeTerm::Alpha)),
+        )));
+        assert_eq!(
+            simplify(constant),
+            E::Atom(ExprInner::Constant(
+                constant_literal(6) * ConstantExpr::from(ChallengeTerm::Alpha)
+            ))
+        );
+
+        // A product by zero keeps the degree of its factors
+        let zero = raw(Operations::Mul, literal(0), x.clone() * y.clone());
+        let simplified = simplify(zero.clone());
+        assert_eq!(simplified.degree(1, 0), 2);
+        assert!(!simplified.is_zero());
+    }
+
+    #[test]
+    fn test_simplify_canonical_order() {
+        let x = curr_cell::<Fp>(Column::X(0));
+        let y = curr_cell::<Fp>(Column::X(1));
+        let z = curr_cell::<Fp>(Column::X(2));
+        assert_eq!(
+            simplify(x.clone() * y.clone() - z.clone() + literal(1)),
+            simplify(literal(1) - z.clone() + y.clone() * x.clone())
+        );
+        assert_eq!(
+            simplify(-(x.clone() * z.clone()) + y.clone()),
+            simplify(y.clone() - z.clone() * x.clone())
+        );
+    }
+
+    #[test]
+    fn test_simplify_fec_constraints() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let constraints = fec_constraints();
+        let simplified: Vec<E<Fp>> = constraints.iter().cloned().map(simplify).collect();
+
+        let before: usize = constraints.iter().map(node_count).sum();
+        let after: usize = simplified.iter().map(node_count).sum();
+        assert!(after < before, "{after} nodes after, {before} before");
+        for (constraint, simplified) in constraints.iter().zip(simplified.iter()) {
+            assert_eq!(constraint.degree(1, 0), simplified.degree(1, 0));
+        }
+        assert!(constraints_equivalent(&constraints, &simplified, 100, &mut rng).is_equivalent());
+        // The pass is idempotent
+        let twice: Vec<E<Fp>> = simplified.iter().cloned().map(simplify).collect();
+        assert_eq!(twice, simplified);
+    }
+
+    #[test]
+    fn test_compile_constraints_simplification() {
+        let constraints = fec_constraints();
+        let simplified: Vec<E<Fp>> = constraints.iter().cloned().map(simplify).collect();
+        assert_eq!(
+            ProtocolConfig::default().compile_constraints(constraints.clone()),
+            Ok(simplified)
+        );
+        let config = ProtocolConfig {
+            simplification: ConstraintSimplification::Disabled,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.compile_constraints(constraints.clone()),
+            Ok(constraints)
+        );
+    }
+}
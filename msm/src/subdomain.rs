@@ -0,0 +1,114 @@
+//! Columns bound to a sub-domain of d1, for sub-circuits using much fewer rows
+//! than the rest of the circuit.
+//! Let `n` be the size of d1 and `n'` the size of the sub-domain `H'`, with
+//! `k = n / n'`. `H'` is the subgroup of d1 generated by `ω^k`, i.e. the value
+//! `i` of a sub-domain column is at row `k * i` of the circuit.
+//! The prover interpolates and commits to the sub-domain columns over `H'`,
+//! which costs an MSM of size `n'` instead of `n`. The polynomials are opened
+//! at the same points as the other columns, as they are only of lower degree.
+//!
+//! The constraints referring to the sub-domain columns must only be enforced on
+//! the rows of `H'`. They are multiplied by the selector
+//! `s(X) = 1/k ∑_{t < k} X^{n' t}`, which is `1` on `H'` and `0` on the other
+//! rows of d1, see [SubdomainLayout::restrict]. The selector is not
+//! committed: the verifier evaluates it itself.
+//! As the next row of d1 is not the next row of `H'`, these constraints must
+//! only refer to the current row.
+
+use ark_ff::{FftField, Field};
+use ark_poly::{
+    univariate::DensePolynomial, EvaluationDomain, Evaluations, Radix2EvaluationDomain as R2D,
+};
+use kimchi::circuits::domains::EvaluationDomains;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::{
+    column_env::evaluate_over_coset,
+    columns::Column,
+    expr::{curr_cell, E},
+};
+
+/// The witness columns which are bound to a sub-domain of d1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubdomainLayout {
+    /// The base two logarithm of the size of the sub-domain
+    pub log_size: u32,
+    /// The indices of the columns defined over the sub-domain. The other
+    /// columns are defined over d1.
+    pub columns: BTreeSet<usize>,
+}
+
+impl SubdomainLayout {
+    /// The size of the sub-domain
+    pub fn size(&self) -> usize {
+        1 << self.log_size
+    }
+
+    /// The size of the domain of the column `i`, given the size of d1
+    pub fn column_size(&self, i: usize, d1_size: usize) -> usize {
+        if self.columns.contains(&i) {
+            self.size()
+        } else {
+            d1_size
+        }
+    }
+
+    /// The sub-domain, or `None` if it is not a proper sub-domain of d1
+    pub fn domain<F: FftField>(&self, domain: EvaluationDomains<F>) -> Option<R2D<F>> {
+        if self.size() >= domain.d1.size as usize {
+            return None;
+        }
+        R2D::new(self.size())
+    }
+
+    /// The selector of the rows of the sub-domain
+    pub fn selector<F: Field>(&self) -> E<F> {
+        curr_cell(Column::SubdomainSelector(self.log_size))
+    }
+
+    /// Enforce `constraint` on the rows of the sub-domain only
+    pub fn restrict<F: Field>(&self, constraint: E<F>) -> E<F> {
+        self.selector() * constraint
+    }
+}
+
+/// The selector polynomial `s(X) = 1/k ∑_{t < k} X^{n' t}` of the sub-domain of
+/// size `2^log_size`.
+pub fn selector_polynomial<F: FftField>(
+    log_size: u32,
+    domain: EvaluationDomains<F>,
+) -> DensePolynomial<F> {
+    let n = domain.d1.size as usize;
+    let sub_size = 1 << log_size;
+    let k = n / sub_size;
+    let k_inv = F::from(k as u64).inverse().unwrap();
+    let mut coeffs = vec![F::zero(); n - sub_size + 1];
+    for t in 0..k {
+        coeffs[t * sub_size] = k_inv;
+    }
+    DensePolynomial { coeffs }
+}
+
+/// The evaluations of the selector of the sub-domain of size `2^log_size` over
+/// the coset of d8 the constraints are evaluated over, see
+/// [selector_polynomial] and [crate::column_env::ColumnEnvironment].
+pub fn selector_evals_d8<F: FftField>(
+    log_size: u32,
+    domain: EvaluationDomains<F>,
+) -> Evaluations<F, R2D<F>> {
+    evaluate_over_coset(&selector_polynomial(log_size, domain), domain.d8)
+}
+
+/// Evaluate the selector of the sub-domain of size `2^log_size` at `x`, when
+/// d1 has size `d1_size`.
+pub fn evaluate_selector<F: Field>(log_size: u32, d1_size: u64, x: F) -> F {
+    let k = d1_size >> log_size;
+    // With y = x^{n'}, s(x) = 1/k ∑_{t < k} y^t = (y^k - 1) / (k (y - 1))
+    let y = x.pow([1u64 << log_size]);
+    if y.is_one() {
+        return F::one();
+    }
+    let k = F::from(k);
+    (y.pow([d1_size >> log_size]) - F::one()) / (k * (y - F::one()))
+}
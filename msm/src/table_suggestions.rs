@@ -0,0 +1,249 @@
+//! Offline analysis of a witness, suggesting groups of columns which could be
+//! checked with a lookup table instead of constraints.
+//!
+//! A group of columns whose rows only take a few distinct values can be
+//! replaced by a lookup of the row in a fixed table of these values. The
+//! constraints only involving the columns of the group are then redundant,
+//! but the lookup argument adds the table, its multiplicities and the partial
+//! sums, see [CircuitShape::n_partial_sums]. [suggest_lookup_tables] reports
+//! both sides, for the circuit author to decide.
+
+use ark_ff::FftField;
+use kimchi::{circuits::gate::CurrOrNext, curve::KimchiCurve};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    columns::Column,
+    config::ProtocolConfig,
+    cost::{estimate_verifier_cost, CircuitShape, VerifierCost},
+    expr::{column_accesses, E},
+    mvlookup::LookupTableID,
+    proof::ProofInputs,
+};
+
+/// The key of the suggested table in [TableSuggestion::shape_after], which
+/// does not have an ID yet
+pub const SUGGESTED_TABLE: u32 = u32::MAX;
+
+/// The outcome of replacing the constraints of a group of columns by a lookup
+/// table, see [suggest_lookup_tables]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSuggestion {
+    /// The indices of the witness columns of the group
+    pub columns: Vec<usize>,
+    /// The number of distinct rows of the group, i.e. the size of the table
+    pub distinct_tuples: usize,
+    /// The number of constraints only involving the columns of the group on
+    /// the current row, which the lookup makes redundant
+    pub constraints_removed: usize,
+    /// The number of columns added by the lookup argument: the table, its
+    /// multiplicities and the new partial sums
+    pub columns_added: usize,
+    /// The shape of the circuit before the change
+    pub shape_before: CircuitShape,
+    /// The shape of the circuit once the constraints are replaced by a lookup
+    /// in the table [SUGGESTED_TABLE]
+    pub shape_after: CircuitShape,
+}
+
+impl TableSuggestion {
+    /// Whether the table fits in the domain, which is required for a fixed
+    /// table
+    pub fn fits_in_domain(&self) -> bool {
+        self.distinct_tuples as u64 <= self.shape_before.domain_size
+    }
+
+    /// The cost of the verifier before and after the change, with the
+    /// configuration `config`
+    pub fn verifier_costs(&self, config: &ProtocolConfig) -> (VerifierCost, VerifierCost) {
+        (
+            estimate_verifier_cost(&self.shape_before, config),
+            estimate_verifier_cost(&self.shape_after, config),
+        )
+    }
+}
+
+impl std::fmt::Display for TableSuggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "columns {:?}: {} distinct rows{}, {} constraints removed, {} columns added, {} -> {} quotient chunks",
+            self.columns,
+            self.distinct_tuples,
+            if self.fits_in_domain() {
+                ""
+            } else {
+                " (larger than the domain)"
+            },
+            self.constraints_removed,
+            self.columns_added,
+            self.shape_before.quotient_chunks(),
+            self.shape_after.quotient_chunks(),
+        )
+    }
+}
+
+/// Whether `constraint` only refers to the columns `columns` on the current
+/// row
+fn only_involves<F>(constraint: &E<F>, columns: &BTreeSet<usize>) -> bool {
+    column_accesses(constraint).iter().all(|(col, row)| {
+        *row == CurrOrNext::Curr && matches!(col, Column::X(i) if columns.contains(i))
+    })
+}
+
+/// For each group of `column_groups`, count the distinct rows of the columns
+/// of the group in `inputs`, and estimate the cost of checking the group with
+/// a lookup table of these rows instead of the `constraints` only involving
+/// the group. The suggestions are ranked from the most promising: the tables
+/// fitting in the domain first, by increasing size, then by decreasing number
+/// of constraints removed.
+pub fn suggest_lookup_tables<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: &ProofInputs<N, G, ID>,
+    column_groups: &[Vec<usize>],
+    constraints: &[E<G::ScalarField>],
+) -> Vec<TableSuggestion> {
+    let domain_size = inputs.evaluations.cols.first().map_or(0, |col| col.len());
+    // The values looked up per row in each table, the table excepted
+    let mut lookups: BTreeMap<u32, usize> = BTreeMap::new();
+    for lookup in &inputs.mvlookups {
+        if let Some(row) = lookup.f.first().and_then(|f| f.first()) {
+            *lookups.entry(row.table_id.to_u32()).or_default() += lookup.f.len() - 1;
+        }
+    }
+    let shape_before = CircuitShape::new(domain_size as u64, N, 0, constraints, lookups.clone());
+
+    let mut suggestions: Vec<TableSuggestion> = column_groups
+        .iter()
+        .map(|columns| {
+            let distinct_tuples = (0..domain_size)
+                .map(|row| {
+                    columns
+                        .iter()
+                        .map(|i| inputs.evaluations.cols[*i][row])
+                        .collect::<Vec<_>>()
+                })
+                .collect::<BTreeSet<_>>()
+                .len();
+            let group: BTreeSet<usize> = columns.iter().copied().collect();
+            let remaining: Vec<E<G::ScalarField>> = constraints
+                .iter()
+                .filter(|constraint| !only_involves(constraint, &group))
+                .cloned()
+                .collect();
+            let shape_after = suggested_shape(&shape_before, &remaining, &lookups);
+            TableSuggestion {
+                columns: columns.clone(),
+                distinct_tuples,
+                constraints_removed: constraints.len() - remaining.len(),
+                // The table and the multiplicities
+                columns_added: 2 + shape_after.n_partial_sums() - shape_before.n_partial_sums(),
+                shape_before: shape_before.clone(),
+                shape_after,
+            }
+        })
+        .collect();
+    suggestions.sort_by_key(|suggestion| {
+        (
+            !suggestion.fits_in_domain(),
+            suggestion.distinct_tuples,
+            std::cmp::Reverse(suggestion.constraints_removed),
+        )
+    });
+    suggestions
+}
+
+fn suggested_shape<F: FftField>(
+    shape_before: &CircuitShape,
+    constraints: &[E<F>],
+    lookups: &BTreeMap<u32, usize>,
+) -> CircuitShape {
+    let mut lookups = lookups.clone();
+    lookups.insert(SUGGESTED_TABLE, 1);
+    CircuitShape::new(
+        shape_before.domain_size,
+        shape_before.n_columns,
+        shape_before.n_public_inputs,
+        constraints,
+        lookups,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{suggest_lookup_tables, SUGGESTED_TABLE};
+    use crate::{
+        columns::Column,
+        expr::{curr_cell, next_cell, E},
+        lookups::{LookupTableIDs, LookupWitness},
+        proof::ProofInputs,
+        witness::Witness,
+        BN254G1Affine, Fp,
+    };
+    use ark_ff::{Field, One, UniformRand};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use rand::Rng;
+
+    #[test]
+    fn test_suggest_lookup_tables() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 8;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        // The columns 0, 1 and 2 are (x, x^2, x + 1) for x < 16, the other
+        // ones are random
+        let xs: Vec<Fp> = (0..domain_size)
+            .map(|_| Fp::from(rng.gen_range(0..16u64)))
+            .collect();
+        let random = |rng: &mut rand::rngs::StdRng| -> Vec<Fp> {
+            (0..domain_size).map(|_| Fp::rand(rng)).collect()
+        };
+        let inputs: ProofInputs<5, BN254G1Affine, LookupTableIDs> = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new([
+                    xs.clone(),
+                    xs.iter().map(|x| x.square()).collect(),
+                    xs.iter().map(|x| *x + Fp::one()).collect(),
+                    random(&mut rng),
+                    random(&mut rng),
+                ]),
+            },
+            mvlookups: vec![LookupWitness::random_with_table(domain, 1, 16, 2)],
+        };
+        let x = |i| curr_cell::<Fp>(Column::X(i));
+        let constraints: Vec<E<Fp>> = vec![
+            x(1) - x(0) * x(0),
+            x(2) - x(0) - E::one(),
+            // Refers to the next row, and is kept
+            next_cell(Column::X(0)) - x(0),
+            x(3) * x(4) * x(4),
+        ];
+
+        let suggestions = suggest_lookup_tables(
+            &inputs,
+            &[vec![3, 4], vec![0, 1, 2], vec![0, 3]],
+            &constraints,
+        );
+        assert_eq!(suggestions.len(), 3);
+        let best = &suggestions[0];
+        assert_eq!(best.columns, vec![0, 1, 2]);
+        assert_eq!(best.distinct_tuples, 16);
+        assert_eq!(best.constraints_removed, 2);
+        assert!(best.fits_in_domain());
+        // The existing table has 2 lookups per row, i.e. one partial sum, and
+        // the new table adds another one
+        assert_eq!(best.shape_before.n_partial_sums(), 1);
+        assert_eq!(best.shape_after.n_partial_sums(), 2);
+        assert_eq!(best.columns_added, 3);
+        assert_eq!(best.shape_after.lookups[&SUGGESTED_TABLE], 1);
+
+        // Random columns are not worth a table
+        assert!(suggestions[1..]
+            .iter()
+            .all(|suggestion| suggestion.distinct_tuples > 200));
+        assert_eq!(suggestions[1].columns, vec![3, 4]);
+        assert_eq!(suggestions[1].constraints_removed, 1);
+        assert_eq!(suggestions[2].constraints_removed, 0);
+        assert!(best
+            .to_string()
+            .starts_with("columns [0, 1, 2]: 16 distinct rows"));
+    }
+}
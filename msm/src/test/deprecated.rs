@@ -0,0 +1,838 @@
+//! The deprecated entry points of the prover and of the verifier, which only
+//! wrap [ProverContext] and [VerifierContext]. Each one is checked against the
+//! contexts: the proofs of a deprecated prover are accepted by the verifier
+//! context, and the deprecated verifiers accept the proofs of the prover
+//! context.
+//! The deprecated entry points without a configuration use
+//! [ProtocolConfig::unsimplified].
+
+use super::{column_lookups, random_lookup_circuit};
+use crate::{
+    columns::Column,
+    config::ProtocolConfig,
+    constant_columns::ConstantColumns,
+    expr::{curr_cell, next_cell, E},
+    lookups::LookupTableIDs,
+    mvlookup::{
+        constraint_lookups, constraint_lookups_with_config, generate_global_tables,
+        LookupAggregationMode, LookupConstraintOptions, LookupQuery, LookupTable, MVLookupQueries,
+        TablePolicy,
+    },
+    perf::PerfCounters,
+    proof::{Proof, ProofInputs},
+    prover::{
+        self, prove_with_context, prove_with_context_and_accumulator,
+        prove_with_context_and_extra_challenges, ProverContext,
+    },
+    subdomain::SubdomainLayout,
+    verification_cache::LruVerificationCache,
+    verifier::{self, VerifierContext},
+    witness::Witness,
+    BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+};
+use ark_ff::{One, UniformRand};
+use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+use poly_commitment::pairing_proof::PairingSRS;
+use rand::Rng;
+use std::{collections::BTreeMap, sync::Arc};
+
+const N: usize = 2;
+
+const DOMAIN_SIZE: usize = 1 << 6;
+
+const TABLE_ID: LookupTableIDs = LookupTableIDs::Custom(1);
+
+fn setup() -> (EvaluationDomains<Fp>, PairingSRS<BN254>) {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+    let x = Fp::rand(&mut rng);
+    let mut srs: PairingSRS<BN254> = PairingSRS::create(x, DOMAIN_SIZE);
+    srs.full_srs.add_lagrange_basis(domain.d1);
+    (domain, srs)
+}
+
+/// The lookups of both columns of a random witness into the table of
+/// [random_lookup_circuit], and the witness
+#[allow(clippy::type_complexity)]
+fn lookup_circuit(
+    domain: EvaluationDomains<Fp>,
+) -> (
+    BTreeMap<LookupTableIDs, Vec<crate::mvlookup::MVLookup<E<Fp>, LookupTableIDs>>>,
+    ProofInputs<N, BN254G1Affine, LookupTableIDs>,
+) {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let cols: [Vec<Fp>; N] = std::array::from_fn(|_| {
+        (0..DOMAIN_SIZE)
+            .map(|_| Fp::from(rng.gen_range(0..16u64)))
+            .collect()
+    });
+    let (lookups, lookup) = column_lookups(domain, 1, 16, &cols, &[0, 1]);
+    let inputs = ProofInputs {
+        evaluations: Witness {
+            cols: Box::new(cols),
+        },
+        mvlookups: vec![lookup],
+    };
+    (BTreeMap::from([(TABLE_ID, lookups)]), inputs)
+}
+
+fn prove_in_context(
+    ctx: &ProverContext<BN254G1Affine, OpeningProof, LookupTableIDs>,
+    inputs: ProofInputs<N, BN254G1Affine, LookupTableIDs>,
+) -> Proof<N, BN254G1Affine, OpeningProof, LookupTableIDs> {
+    let mut rng = o1_utils::tests::make_test_rng();
+    prove_with_context::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        _,
+        N,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+    >(ctx, inputs, &mut rng)
+    .unwrap()
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_config() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let config = ProtocolConfig::default();
+    let proof = prover::prove_with_config::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        N,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+    >(domain, &srs, &config, &constraints, inputs, &mut rng)
+    .unwrap();
+    let res = VerifierContext::new(domain, &srs, config, &constraints)
+        .unwrap()
+        .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_config() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let config = ProtocolConfig::default();
+    let ctx = ProverContext::new(domain, &srs, config.clone(), &constraints).unwrap();
+    let proof = prove_in_context(&ctx, inputs);
+    let res = verifier::verify_with_config::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+    >(
+        domain,
+        &srs,
+        &config,
+        &constraints,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+    );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_challenges() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let ctx =
+        ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints).unwrap();
+    let proof = prove_in_context(&ctx, inputs);
+    let challenges = verifier::verify_with_challenges::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+    )
+    .unwrap();
+    let expected = VerifierContext::from_prover_context(&ctx)
+        .unwrap()
+        .verify_with_challenges::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        )
+        .unwrap();
+    assert_eq!(challenges, expected);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_perf_counters() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let perf = PerfCounters::default();
+    let proof = prover::prove_with_perf_counters::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        N,
+        LookupTableIDs,
+    >(domain, &srs, &constraints, inputs.clone(), &perf, &mut rng)
+    .unwrap();
+    let ctx =
+        ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints).unwrap();
+    let res = VerifierContext::from_prover_context(&ctx)
+        .unwrap()
+        .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        );
+    assert_eq!(res, Ok(()));
+
+    // The counters are the ones of the context
+    let ctx_perf = Arc::new(PerfCounters::default());
+    prove_in_context(&ctx.with_perf_counters(ctx_perf.clone()), inputs);
+    assert_eq!(perf.stats(), ctx_perf.stats());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_perf_counters() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let ctx =
+        ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints).unwrap();
+    let proof = prove_in_context(&ctx, inputs);
+    let perf = PerfCounters::default();
+    let res = verifier::verify_with_perf_counters::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+        &perf,
+    );
+    assert_eq!(res, Ok(()));
+
+    // The counters are the ones of the context
+    let ctx_perf = PerfCounters::default();
+    VerifierContext::from_prover_context(&ctx)
+        .unwrap()
+        .verify_with_perf_counters::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+            &ctx_perf,
+        )
+        .unwrap();
+    assert_eq!(perf.stats(), ctx_perf.stats());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_cache() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let config = ProtocolConfig::default();
+    let ctx = ProverContext::new(domain, &srs, config.clone(), &constraints).unwrap();
+    let proof = prove_in_context(&ctx, inputs);
+    let cache = LruVerificationCache::new(4);
+    let res = verifier::verify_with_cache::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+        _,
+    >(
+        domain,
+        &srs,
+        &config,
+        &constraints,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+        &cache,
+        &PerfCounters::default(),
+    );
+    assert_eq!(res, Ok(()));
+    assert_eq!(cache.len(), 1);
+
+    // The outcome is cached for the context of the same circuit
+    let res = VerifierContext::from_prover_context(&ctx)
+        .unwrap()
+        .verify_with_cache::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>, _>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+            &cache,
+            &PerfCounters::default(),
+        );
+    assert_eq!(res, Ok(()));
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_global_tables() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let table = LookupTable {
+        table_id: TABLE_ID,
+        entries: (0..16u64).map(|v| vec![Fp::from(v)]).collect(),
+    };
+    let global_tables =
+        generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[table]).unwrap();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let proof = prover::prove_with_global_tables::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        N,
+        LookupTableIDs,
+    >(domain, &srs, &constraints, inputs, &global_tables, &mut rng)
+    .unwrap();
+    let res = VerifierContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+        .unwrap()
+        .with_global_tables(global_tables)
+        .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_global_tables() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let table = LookupTable {
+        table_id: TABLE_ID,
+        entries: (0..16u64).map(|v| vec![Fp::from(v)]).collect(),
+    };
+    let global_tables =
+        generate_global_tables::<_, OpeningProof, _>(&srs, domain, &[table]).unwrap();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, N, &mut rng);
+    let ctx = ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+        .unwrap()
+        .with_global_tables(global_tables.clone());
+    let proof = prove_in_context(&ctx, inputs);
+    assert!(verifier::verify_with_global_tables::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+        &global_tables,
+    ));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_active_tables() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (lookups, inputs) = lookup_circuit(domain);
+    let policy = TablePolicy::new().mandatory(TABLE_ID);
+    let proof = prover::prove_with_active_tables::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        N,
+        LookupTableIDs,
+    >(domain, &srs, &policy, &[], &lookups, inputs, &mut rng)
+    .unwrap();
+    let constraints = policy
+        .compile_constraints(&[], &lookups, &proof.active_tables())
+        .unwrap();
+    assert_eq!(constraints, constraint_lookups(&lookups));
+    let res = VerifierContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+        .unwrap()
+        .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_active_tables() {
+    let (domain, srs) = setup();
+    let (lookups, inputs) = lookup_circuit(domain);
+    let policy = TablePolicy::new().mandatory(TABLE_ID);
+    let ctx = ProverContext::new(
+        domain,
+        &srs,
+        ProtocolConfig::unsimplified(),
+        &constraint_lookups(&lookups),
+    )
+    .unwrap();
+    let proof = prove_in_context(&ctx, inputs);
+    let res = verifier::verify_with_active_tables::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &policy,
+        &[],
+        &lookups,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+    );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_lookup_queries() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (lookups, inputs) = lookup_circuit(domain);
+    let constraints = constraint_lookups(&lookups);
+    let lookup = &inputs.mvlookups[0];
+    // The first column is given by reference, the second one by value
+    let queries = MVLookupQueries {
+        f: vec![
+            LookupQuery::Column(Column::X(0)),
+            LookupQuery::Value(lookup.f[1].clone()),
+            LookupQuery::Value(lookup.f[2].clone()),
+        ],
+        m: lookup.m.clone(),
+    };
+    let proof = prover::prove_with_lookup_queries::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        N,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        inputs.evaluations,
+        vec![queries],
+        &mut rng,
+    )
+    .unwrap();
+    let res = VerifierContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+        .unwrap()
+        .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_subdomain() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    // X1 = X0^2 on the rows of a sub-domain of size 8, i.e. every 8 rows
+    let layout = SubdomainLayout {
+        log_size: 3,
+        columns: [1].into(),
+    };
+    let ratio = DOMAIN_SIZE / layout.size();
+    let constraints = vec![layout.restrict(
+        curr_cell::<Fp>(Column::X(1)) - curr_cell(Column::X(0)) * curr_cell(Column::X(0)),
+    )];
+    let x0: Vec<Fp> = (0..DOMAIN_SIZE).map(|_| Fp::rand(&mut rng)).collect();
+    let x1 = (0..layout.size())
+        .map(|i| x0[i * ratio] * x0[i * ratio])
+        .collect();
+    let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+        evaluations: Witness {
+            cols: Box::new([x0, x1]),
+        },
+        mvlookups: vec![],
+    };
+    let proof = prover::prove_with_subdomain::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        N,
+        LookupTableIDs,
+    >(domain, &srs, &constraints, &layout, inputs, &mut rng)
+    .unwrap();
+    let res = VerifierContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+        .unwrap()
+        .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        );
+    assert_eq!(res, Ok(()));
+}
+
+/// The constraints and the witness of a claimed lookup argument, which is
+/// satisfied across a chain of proofs
+#[allow(clippy::type_complexity)]
+fn accumulator_circuit(
+    domain: EvaluationDomains<Fp>,
+) -> (Vec<E<Fp>>, ProofInputs<N, BN254G1Affine, LookupTableIDs>) {
+    let (lookups, inputs) = lookup_circuit(domain);
+    let constraints = constraint_lookups_with_config(
+        &lookups,
+        &LookupConstraintOptions {
+            aggregation: LookupAggregationMode::Claimed,
+            ..Default::default()
+        },
+    );
+    (constraints, inputs)
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_accumulator() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) = accumulator_circuit(domain);
+    let config = ProtocolConfig::default();
+    let incoming = Fp::rand(&mut rng);
+    let proof = prover::prove_with_accumulator::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        N,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+    >(
+        domain,
+        &srs,
+        &config,
+        &constraints,
+        inputs,
+        incoming,
+        &mut rng,
+    )
+    .unwrap();
+    let boundary = VerifierContext::new(domain, &srs, config, &constraints)
+        .unwrap()
+        .verify_with_accumulator::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+            incoming,
+        )
+        .unwrap();
+    // The lookups are satisfied by the proof itself
+    assert_eq!((boundary.incoming, boundary.outgoing), (incoming, incoming));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_accumulator() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) = accumulator_circuit(domain);
+    let config = ProtocolConfig::default();
+    let ctx = ProverContext::new(domain, &srs, config.clone(), &constraints).unwrap();
+    let incoming = Fp::rand(&mut rng);
+    let proof = prove_with_context_and_accumulator::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        _,
+        N,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+    >(&ctx, inputs, incoming, &mut rng)
+    .unwrap();
+    let boundary = verifier::verify_with_accumulator::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+    >(
+        domain,
+        &srs,
+        &config,
+        &constraints,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+        incoming,
+    )
+    .unwrap();
+    assert_eq!((boundary.incoming, boundary.outgoing), (incoming, incoming));
+}
+
+/// A circuit with a constant column: X2 = X0 * X1, where X1 is constant
+fn constant_column_circuit() -> (Vec<E<Fp>>, ProofInputs<3, BN254G1Affine, LookupTableIDs>) {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let x = |i| curr_cell::<Fp>(Column::X(i));
+    let constraints = vec![x(2) - x(0) * x(1)];
+    let c = Fp::rand(&mut rng);
+    let x0: Vec<Fp> = (0..DOMAIN_SIZE).map(|_| Fp::rand(&mut rng)).collect();
+    let x2 = x0.iter().map(|a| *a * c).collect();
+    let inputs = ProofInputs {
+        evaluations: Witness {
+            cols: Box::new([x0, vec![c; DOMAIN_SIZE], x2]),
+        },
+        mvlookups: vec![],
+    };
+    (constraints, inputs)
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_constant_columns() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, inputs) = constant_column_circuit();
+    let constant_columns = ConstantColumns::detect(&inputs.evaluations);
+    let proof = prover::prove_with_constant_columns::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        3,
+        N,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        &constant_columns,
+        inputs,
+        &mut rng,
+    )
+    .unwrap();
+    let constraints: Vec<_> = constraints
+        .iter()
+        .map(|constraint| constant_columns.substitute(constraint))
+        .collect();
+    let res = VerifierContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+        .unwrap()
+        .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_constant_columns() {
+    let (domain, srs) = setup();
+    let (constraints, inputs) = constant_column_circuit();
+    let constant_columns = ConstantColumns::detect(&inputs.evaluations);
+    let reduced_constraints: Vec<_> = constraints
+        .iter()
+        .map(|constraint| constant_columns.substitute(constraint))
+        .collect();
+    let ctx = ProverContext::new(
+        domain,
+        &srs,
+        ProtocolConfig::unsimplified(),
+        &reduced_constraints,
+    )
+    .unwrap();
+    let inputs = constant_columns
+        .reduce_inputs::<3, N, _, _>(inputs)
+        .unwrap();
+    let proof = prove_in_context(&ctx, inputs);
+    let res = verifier::verify_with_constant_columns::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        N,
+        0,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        &constant_columns,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+    );
+    assert_eq!(res, Ok(()));
+}
+
+/// A grand product over an extra challenge γ, checking that the second
+/// column is a permutation of the first one: the first phase is the two
+/// columns, the second phase the running product
+#[allow(clippy::type_complexity)]
+fn grand_product_circuit() -> (
+    Vec<E<Fp>>,
+    Vec<Vec<Fp>>,
+    impl FnOnce(&[Fp; 1]) -> ProofInputs<3, BN254G1Affine, LookupTableIDs> + Send,
+) {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let x = |i| curr_cell::<Fp>(Column::X(i));
+    let gamma = curr_cell::<Fp>(Column::ExtraChallenge(0));
+    let constraints =
+        vec![next_cell(Column::X(2)) * (x(1) + gamma.clone()) - x(2) * (x(0) + gamma)];
+    let a: Vec<Fp> = (0..DOMAIN_SIZE).map(|_| Fp::rand(&mut rng)).collect();
+    let b: Vec<Fp> = a.iter().rev().cloned().collect();
+    let first_phase = vec![a.clone(), b.clone()];
+    let second_phase = move |[gamma]: &[Fp; 1]| {
+        let mut z = Vec::with_capacity(DOMAIN_SIZE);
+        z.push(Fp::one());
+        for i in 0..DOMAIN_SIZE - 1 {
+            z.push(z[i] * (a[i] + gamma) / (b[i] + gamma));
+        }
+        ProofInputs {
+            evaluations: Witness {
+                cols: Box::new([a, b, z]),
+            },
+            mvlookups: vec![],
+        }
+    };
+    (constraints, first_phase, second_phase)
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_prove_with_extra_challenges() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, first_phase, second_phase) = grand_product_circuit();
+    let proof = prover::prove_with_extra_challenges::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        Column,
+        _,
+        3,
+        1,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        first_phase,
+        second_phase,
+        &mut rng,
+    )
+    .unwrap();
+    let res = VerifierContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+        .unwrap()
+        .verify_with_extra_challenges::<BaseSponge, ScalarSponge, 3, 0, 1, PointEvaluations<Fp>>(
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+            2,
+        );
+    assert_eq!(res, Ok(()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_verify_with_extra_challenges() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let (domain, srs) = setup();
+    let (constraints, first_phase, second_phase) = grand_product_circuit();
+    let ctx =
+        ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints).unwrap();
+    let proof = prove_with_context_and_extra_challenges::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        _,
+        3,
+        1,
+        LookupTableIDs,
+        PointEvaluations<Fp>,
+    >(&ctx, first_phase, second_phase, &mut rng)
+    .unwrap();
+    assert!(verifier::verify_with_extra_challenges::<
+        _,
+        OpeningProof,
+        BaseSponge,
+        ScalarSponge,
+        3,
+        0,
+        1,
+        LookupTableIDs,
+    >(
+        domain,
+        &srs,
+        &constraints,
+        2,
+        &proof,
+        Witness::zero_vec(DOMAIN_SIZE),
+    ));
+}
@@ -0,0 +1,254 @@
+//! The minimal configuration of the lookup argument: a single fixed table,
+//! and a single value looked up per row. The only chunk of the table holds
+//! two terms, the looked-up value and the term of the table, and is committed
+//! in a single partial sum.
+
+use super::random_lookup_circuit;
+use crate::{
+    columns::Column,
+    cost::CircuitShape,
+    expr::{curr_cell, E},
+    lookup_stats::LookupStats,
+    lookups::LookupTableIDs,
+    mvlookup::{chunk_padding, combine_lookups, LookupLayout, LookupTableID, MVLookup},
+    prover::{prove, ProverError},
+    verifier::{try_verify, VerifierError},
+    witness::Witness,
+    BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+};
+use ark_ff::{Field, One, UniformRand, Zero};
+use kimchi::{
+    circuits::{
+        domains::EvaluationDomains,
+        expr::{Challenges, ColumnEvaluations, Constants, ExprError, PolishToken},
+    },
+    curve::KimchiCurve,
+    proof::PointEvaluations,
+};
+use poly_commitment::pairing_proof::PairingSRS;
+use std::collections::BTreeMap;
+
+/// The single column of the circuit, which is looked up
+const N: usize = 1;
+
+const TABLE_ID: LookupTableIDs = LookupTableIDs::Custom(1);
+
+const TABLE_SIZE: u64 = 4;
+
+/// The values of the columns at ζ. The constraints are evaluated at the
+/// current row only.
+struct Cells(BTreeMap<Column, Fp>);
+
+impl ColumnEvaluations<Fp> for Cells {
+    type Column = Column;
+
+    fn evaluate(&self, col: Column) -> Result<PointEvaluations<Fp>, ExprError<Column>> {
+        let value = *self.0.get(&col).ok_or(ExprError::MissingEvaluation(
+            col,
+            kimchi::circuits::gate::CurrOrNext::Curr,
+        ))?;
+        Ok(PointEvaluations {
+            zeta: value,
+            zeta_omega: Fp::zero(),
+        })
+    }
+}
+
+/// Evaluate `constraint` on `cells`, with the challenges `β` and `r`
+fn evaluate(constraint: &E<Fp>, cells: &Cells, beta: Fp, joint_combiner: Fp) -> Fp {
+    let domain = EvaluationDomains::<Fp>::create(1 << 3).unwrap();
+    PolishToken::evaluate(
+        constraint.to_polish().as_slice(),
+        domain.d1,
+        Fp::from(7u64),
+        cells,
+        &Constants {
+            endo_coefficient: Fp::zero(),
+            mds: &BN254G1Affine::sponge_params().mds,
+            zk_rows: 0,
+        },
+        &Challenges {
+            alpha: Fp::zero(),
+            beta,
+            gamma: Fp::zero(),
+            joint_combiner: Some(joint_combiner),
+        },
+    )
+    .unwrap()
+}
+
+fn setup_srs(domain: EvaluationDomains<Fp>) -> PairingSRS<BN254> {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let mut srs: PairingSRS<BN254> =
+        PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+    srs.full_srs.add_lagrange_basis(domain.d1);
+    srs
+}
+
+/// The example `i = 2` of [combine_lookups]:
+/// ```text
+/// h(X) * (β + f_2(X)) * (β + f_1(X)) = m_1(X) * (β + f_2(X)) + m_2(X) * (β + f_{1}(X))
+/// ```
+/// where `f_i(X) = table_id + r * x_i(X)` is the combination of the value
+/// looked up.
+#[test]
+fn test_combine_lookups_docstring_example() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let [x_1, x_2, m_1, m_2, h] = [0, 1, 2, 3, 4].map(Column::X);
+    let lookups = vec![
+        MVLookup::new(TABLE_ID, curr_cell(m_1), &[curr_cell(x_1)]),
+        MVLookup::new(TABLE_ID, curr_cell(m_2), &[curr_cell(x_2)]),
+    ];
+    let constraint = combine_lookups(h, lookups);
+
+    let [beta, r] = [(); 2].map(|_| Fp::rand(&mut rng));
+    let mut cells = Cells(
+        [x_1, x_2, m_1, m_2, h]
+            .map(|col| (col, Fp::rand(&mut rng)))
+            .into(),
+    );
+    let f_1 = TABLE_ID.to_field::<Fp>() + r * cells.0[&x_1];
+    let f_2 = TABLE_ID.to_field::<Fp>() + r * cells.0[&x_2];
+    let (m_1, m_2) = (cells.0[&m_1], cells.0[&m_2]);
+    let lhs = cells.0[&h] * (beta + f_2) * (beta + f_1);
+    let rhs = m_1 * (beta + f_2) + m_2 * (beta + f_1);
+    assert_eq!(evaluate(&constraint, &cells, beta, r), lhs - rhs);
+
+    // The constraint holds for h(X) = m_1(X) / (β + f_1(X)) + m_2(X) / (β + f_2(X))
+    let sum = m_1 * (beta + f_1).inverse().unwrap() + m_2 * (beta + f_2).inverse().unwrap();
+    cells.0.insert(h, sum);
+    assert_eq!(evaluate(&constraint, &cells, beta, r), Fp::zero());
+}
+
+/// The constraint of the single partial sum is the one of the example of the
+/// table of [combine_lookups]:
+/// ```text
+/// h(X) * (β + t(X)) * (β + f(X)) = (β + t(X)) - m(X) * (β + f(X))
+/// ```
+#[test]
+fn test_minimal_constraint() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(1 << 3).unwrap();
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, TABLE_SIZE, 1, &mut rng);
+
+    // A single partial sum, and the aggregation
+    let layout = LookupLayout::<LookupTableIDs>::of_constraints(&constraints).unwrap();
+    assert_eq!(layout.n_partial_sums(), 1);
+    assert_eq!(layout.n_table_partial_sums(TABLE_ID), Some(1));
+    assert_eq!(layout.table_ids().collect::<Vec<_>>(), vec![TABLE_ID]);
+    assert_eq!(constraints.len(), 2);
+
+    // The formulas of the number of partial sums agree
+    assert_eq!(chunk_padding(1), 0);
+    let shape = CircuitShape::new(
+        domain.d1.size,
+        N,
+        0,
+        &constraints,
+        BTreeMap::from([(TABLE_ID.to_u32(), 1)]),
+    );
+    assert_eq!(shape.n_partial_sums(), 1);
+    assert!(shape.underfull_chunks().is_empty());
+    let stats = LookupStats::from_witness(&inputs.mvlookups);
+    assert_eq!(stats.tables.len(), 1);
+    assert_eq!(stats.tables[0].partial_sums, 1);
+
+    let h = layout.partial_sum(0).unwrap();
+    let m = layout.multiplicity(TABLE_ID).unwrap();
+    let t = layout.fixed_table(TABLE_ID).unwrap();
+    let x = Column::X(0);
+    let [beta, r] = [(); 2].map(|_| Fp::rand(&mut rng));
+    let cells = Cells([x, h, m, t].map(|col| (col, Fp::rand(&mut rng))).into());
+    // The table is already combined with the joint combiner and its ID
+    let f = TABLE_ID.to_field::<Fp>() + r * cells.0[&x];
+    let t = cells.0[&t];
+    let lhs = cells.0[&h] * (beta + t) * (beta + f);
+    let rhs = (beta + t) - cells.0[&m] * (beta + f);
+    assert_eq!(evaluate(&constraints[0], &cells, beta, r), lhs - rhs);
+}
+
+#[test]
+fn test_minimal_completeness() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    for log_size in [2, 3, 5, 8] {
+        let domain = EvaluationDomains::<Fp>::create(1 << log_size).unwrap();
+        let srs = setup_srs(domain);
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, TABLE_SIZE, 1, &mut rng);
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        let lookup_evals = proof.proof_evals.mvlookup_evals.as_ref().unwrap();
+        assert_eq!(lookup_evals.h.len(), 1);
+        assert_eq!(lookup_evals.m.len(), 1);
+        try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, _>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(0),
+        )
+        .unwrap_or_else(|err| panic!("domain of size 2^{log_size}: {err}"));
+    }
+}
+
+/// A value missing from the table is looked up, with the multiplicities of
+/// the other values: the running sum does not end up being zero
+#[test]
+fn test_minimal_value_missing_from_table() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(1 << 3).unwrap();
+    let srs = setup_srs(domain);
+    let (constraints, mut inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, TABLE_SIZE, 1, &mut rng);
+    let missing = Fp::from(TABLE_SIZE);
+    inputs.evaluations.cols[0][1] = missing;
+    inputs.mvlookups[0].f[0][1].value = vec![missing];
+    let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+        domain,
+        &srs,
+        &constraints,
+        inputs,
+        &mut rng,
+    );
+    assert!(matches!(
+        res,
+        Err(ProverError::LookupAggregationNonZero { table_id, .. })
+            if table_id == LookupTableIDs::Custom(1).to_u32()
+    ));
+}
+
+/// The evaluation of the single partial sum does not satisfy its constraint
+#[test]
+fn test_minimal_wrong_partial_sum() {
+    let mut rng = o1_utils::tests::make_test_rng();
+    let domain = EvaluationDomains::<Fp>::create(1 << 3).unwrap();
+    let srs = setup_srs(domain);
+    let (constraints, inputs) =
+        random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, TABLE_SIZE, 1, &mut rng);
+    let mut proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, _>(
+        domain,
+        &srs,
+        &constraints,
+        inputs,
+        &mut rng,
+    )
+    .unwrap();
+    proof.proof_evals.mvlookup_evals.as_mut().unwrap().h[0].zeta += Fp::one();
+    assert_eq!(
+        try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, _>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(0),
+        ),
+        Err(VerifierError::OpeningProofFailed)
+    );
+}
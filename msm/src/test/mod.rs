@@ -3,14 +3,27 @@ pub mod constraint;
 pub mod interpreter;
 pub mod witness;
 
+#[cfg(test)]
+mod deprecated;
+#[cfg(test)]
+mod minimal_lookup;
+
 use crate::{
-    columns::Column, expr::E, lookups::LookupTableIDs, proof::ProofInputs, prover::prove,
-    verifier::verify, witness::Witness, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    columns::Column,
+    expr::{curr_cell, E},
+    lookups::{LookupTableIDs, LookupWitness},
+    mvlookup::{constraint_lookups, MVLookup},
+    proof::ProofInputs,
+    prover::prove,
+    verifier::verify,
+    witness::Witness,
+    BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
 };
-use ark_ff::{UniformRand, Zero};
-use kimchi::circuits::domains::EvaluationDomains;
+use ark_ff::{One, PrimeField, UniformRand, Zero};
+use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
 use poly_commitment::pairing_proof::PairingSRS;
-use rand::{CryptoRng, RngCore};
+use rand::{CryptoRng, Rng, RngCore};
+use std::collections::BTreeMap;
 
 // Generic function to test with different circuits with the generic prover/verifier.
 // It doesn't use the interpreter to build the witness and compute the constraints.
@@ -91,6 +104,98 @@ pub fn test_completeness_generic<const N: usize, RNG>(
     assert!(verifies)
 }
 
+/// The lookups of the witness columns `queried` into the table
+/// `Custom(table_id)` of the values `[0, table_size)`, padded with zeroes up
+/// to the domain size, and their witness given the values `cols` of the
+/// columns. The looked-up values must be in the table.
+pub fn column_lookups<F: PrimeField>(
+    domain: EvaluationDomains<F>,
+    table_id: u32,
+    table_size: u64,
+    cols: &[Vec<F>],
+    queried: &[usize],
+) -> (Vec<MVLookup<E<F>, LookupTableIDs>>, LookupWitness<F>) {
+    let domain_size = domain.d1.size as usize;
+    assert!(table_size as usize <= domain_size);
+    let table_id = LookupTableIDs::Custom(table_id);
+    let table: Vec<F> = (0..domain_size as u64)
+        .map(|i| {
+            if i < table_size {
+                F::from(i)
+            } else {
+                F::zero()
+            }
+        })
+        .collect();
+    let mut m = vec![F::zero(); domain_size];
+    let mut f: Vec<Vec<MVLookup<F, LookupTableIDs>>> = queried
+        .iter()
+        .map(|i| {
+            cols[*i]
+                .iter()
+                .map(|x| {
+                    let k = table[..table_size as usize]
+                        .iter()
+                        .position(|v| v == x)
+                        .expect("the looked-up values must be in the table");
+                    m[k] += F::one();
+                    MVLookup::new(table_id, F::one(), &[*x])
+                })
+                .collect()
+        })
+        .collect();
+    f.push(
+        table
+            .iter()
+            .zip(m.iter())
+            .map(|(v, m)| MVLookup::new(table_id, -*m, &[*v]))
+            .collect(),
+    );
+    let lookups = queried
+        .iter()
+        .map(|i| MVLookup::new(table_id, E::one(), &[curr_cell(Column::X(*i))]))
+        .collect();
+    (lookups, LookupWitness { f, m })
+}
+
+/// A circuit looking up the values of its first `n_queries` columns, random
+/// values in `[0, table_size)`, into the table `Custom(table_id)`, see
+/// [column_lookups]. The other columns are random. Returns the constraints of
+/// the lookups, see [constraint_lookups], and the inputs of a proof.
+pub fn random_lookup_circuit<const N: usize, G: KimchiCurve, RNG: RngCore + CryptoRng>(
+    domain: EvaluationDomains<G::ScalarField>,
+    table_id: u32,
+    table_size: u64,
+    n_queries: usize,
+    rng: &mut RNG,
+) -> (Vec<E<G::ScalarField>>, ProofInputs<N, G, LookupTableIDs>) {
+    assert!(n_queries <= N, "the looked-up values are witness columns");
+    let cols: [Vec<G::ScalarField>; N] = std::array::from_fn(|i| {
+        (0..domain.d1.size)
+            .map(|_| {
+                if i < n_queries {
+                    G::ScalarField::from(rng.gen_range(0..table_size))
+                } else {
+                    G::ScalarField::rand(rng)
+                }
+            })
+            .collect()
+    });
+    let queried: Vec<usize> = (0..n_queries).collect();
+    let (lookups, lookup) = column_lookups(domain, table_id, table_size, &cols, &queried);
+    let constraints = constraint_lookups(&BTreeMap::from([(
+        LookupTableIDs::Custom(table_id),
+        lookups,
+    )]));
+    let inputs = ProofInputs {
+        evaluations: Witness {
+            cols: Box::new(cols),
+        },
+        mvlookups: vec![lookup],
+    };
+    (constraints, inputs)
+}
+
 // TODO: move tests from src/lib.rs into this file
 // TODO: use interpreter/witness/constraint files to define witness/cosntraints
 
@@ -99,11 +204,38 @@ mod tests {
 
     use super::*;
     use crate::{
+        checkpoint::CheckpointError,
         columns::Column,
+        config::{ConfigError, DegeneratePointRule, OpeningMode, ProtocolConfig, SrsLabel},
+        constant_columns::ConstantColumns,
         expr::{self, E},
+        lookups::{booleanity, LookupWitness},
+        mvlookup::{self, constraint_lookups},
+        proof::{FirstRoundMessage, Proof, SinglePointEvaluations},
+        prover::{
+            prove_first_round, prove_many, prove_until_checkpoint, prove_with_context,
+            prove_with_context_and_extra_challenges, resume_from_checkpoint, resume_proving,
+            FirstRoundState, ProverCheckpoint, ProverContext, ProverError,
+        },
+        subdomain::SubdomainLayout,
+        transcript::TranscriptBackend,
+        verifier::{try_verify, verify_with_srs_set, VerifierContext, VerifierError},
+        BN254G1Affine, SpongeParams,
     };
+    use ark_bn254::Fq;
     use ark_ff::{Field, One, UniformRand};
-    use kimchi::circuits::expr::{ConstantExpr, ConstantTerm};
+    use kimchi::{
+        circuits::expr::{ConstantExpr, ConstantTerm},
+        plonk_sponge::FrSponge,
+        proof::PointEvaluations,
+    };
+    use mina_poseidon::{
+        constants::SpongeConstants,
+        poseidon::{ArithmeticSpongeParams, SpongeState},
+        sponge::{FqSponge, ScalarChallenge},
+    };
+    use rand::Rng;
+    use std::collections::{BTreeMap, BTreeSet};
 
     #[cfg(dead_code)]
     fn test_soundness_generic<const N: usize, RNG>(
@@ -380,6 +512,45 @@ mod tests {
         // test_soundness_generic(constraints, witness, domain_size, &mut rng);
     }
 
+    #[test]
+    // The witness of X_{0}^3 + X_{1} AND X_{2}^2 - 3 X_{3} is broken on a
+    // single row of the second constraint
+    fn test_unsatisfied_constraint_is_attributed() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 4;
+        let domain_size = 1 << 8;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let x = |i| expr::curr_cell::<Fp>(Column::X(i));
+        let three = E::constant(ConstantExpr::from(ConstantTerm::Literal(Fp::from(3))));
+        let constraints = vec![x(0) * x(0) * x(0) + x(1), x(2) * x(2) - three * x(3)];
+        let x0s: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let x1s = x0s.iter().map(|x0| -*x0 * x0 * x0).collect();
+        let x2s: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let mut x3s: Vec<Fp> = x2s.iter().map(|x2| *x2 * x2 / Fp::from(3)).collect();
+        x3s[domain_size / 3] += Fp::one();
+        let inputs = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new([x0s, x1s, x2s, x3s]),
+            },
+            mvlookups: vec![],
+        };
+
+        let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+            &mut rng,
+        );
+        let Err(ProverError::ConstraintNotSatisfied(msg)) = res else {
+            panic!("the broken witness must be rejected by the constraint check")
+        };
+        assert_eq!(msg, format!("Unsatisfied expression: {}", constraints[1]));
+    }
+
     #[test]
     // X_{0}^6 + X_{1}^4 - X_{2}^3 - 2 X_{3}
     fn test_completeness_degree_six() {
@@ -489,4 +660,3610 @@ mod tests {
         // TODO: Refactorize code in prover to handle a degug or add an adversarial prover.
         // test_soundness_generic(constraints, witness, domain_size, &mut rng);
     }
+
+    #[test]
+    fn test_truncated_quotient_commitment_is_rejected() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 8;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+
+        let mut srs: PairingSRS<BN254> = {
+            // Trusted setup toxic waste
+            let x = Fp::rand(&mut rng);
+            PairingSRS::create(x, domain.d1.size as usize)
+        };
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        // X_{0}^3 - X_{1}, the quotient polynomial is split in two chunks
+        let constraints = {
+            let x0 = expr::curr_cell::<Fp>(Column::X(0));
+            let x1 = expr::curr_cell::<Fp>(Column::X(1));
+            vec![x0.clone() * x0.clone() * x0.clone() - x1.clone()]
+        };
+        let random_x0s: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let exp_x1 = random_x0s.iter().map(|x0| *x0 * *x0 * *x0).collect();
+        let proof_inputs = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new([random_x0s, exp_x1]),
+            },
+            mvlookups: vec![],
+        };
+
+        let mut proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                proof_inputs,
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(proof.proof_comms.t_comm.len(), 2);
+        proof.proof_comms.t_comm.elems.truncate(1);
+
+        let res = try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        );
+        assert_eq!(
+            res,
+            Err(VerifierError::QuotientChunkCountMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+
+    // Grand product argument between two columns A and B, using one extra
+    // challenge γ: Z(ωX) (B(X) + γ) - Z(X) (A(X) + γ)
+    #[test]
+    fn test_completeness_extra_challenge_grand_product() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 3;
+        let domain_size = 1 << 8;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+
+        let mut srs: PairingSRS<BN254> = {
+            // Trusted setup toxic waste
+            let x = Fp::rand(&mut rng);
+            PairingSRS::create(x, domain.d1.size as usize)
+        };
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let constraints = {
+            let a = expr::curr_cell::<Fp>(Column::X(0));
+            let b = expr::curr_cell::<Fp>(Column::X(1));
+            let z = expr::curr_cell::<Fp>(Column::X(2));
+            let z_next = expr::next_cell::<Fp>(Column::X(2));
+            let gamma = expr::curr_cell::<Fp>(Column::ExtraChallenge(0));
+            vec![z_next * (b + gamma.clone()) - z * (a + gamma)]
+        };
+
+        // B is a permutation of A, so the product wraps around to one.
+        let a: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let b: Vec<Fp> = a.iter().rev().cloned().collect();
+        let first_phase = vec![a.clone(), b.clone()];
+
+        let second_phase = |[gamma]: &[Fp; 1]| {
+            let mut z = Vec::with_capacity(domain_size);
+            z.push(Fp::one());
+            for i in 0..domain_size - 1 {
+                z.push(z[i] * (a[i] + gamma) / (b[i] + gamma));
+            }
+            ProofInputs {
+                evaluations: Witness {
+                    cols: Box::new([a.clone(), b.clone(), z]),
+                },
+                mvlookups: vec![],
+            }
+        };
+
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::unsimplified(),
+            &constraints,
+        )
+        .unwrap();
+        let proof = prove_with_context_and_extra_challenges::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            1,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, first_phase, second_phase, &mut rng)
+        .unwrap();
+
+        let res = VerifierContext::from_prover_context(&ctx)
+            .unwrap()
+            .verify_with_extra_challenges::<BaseSponge, ScalarSponge, N, 0, 1, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size),
+                2,
+            );
+        assert_eq!(res, Ok(()));
+    }
+
+    #[test]
+    fn test_split_proving_is_identical_to_one_shot_proving() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 8;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+
+        let mut srs: PairingSRS<BN254> = {
+            // Trusted setup toxic waste
+            let x = Fp::rand(&mut rng);
+            PairingSRS::create(x, domain.d1.size as usize)
+        };
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+
+        let one_shot_proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs.clone(),
+                &mut o1_utils::tests::make_test_rng(),
+            )
+            .unwrap();
+
+        let (first_round, state) =
+            prove_first_round::<_, OpeningProof, N, LookupTableIDs>(domain, &srs, inputs);
+        // The state and the message can be sent to a different process
+        let first_round: FirstRoundMessage<BN254G1Affine, LookupTableIDs> =
+            rmp_serde::from_slice(&rmp_serde::to_vec(&first_round).unwrap()).unwrap();
+        let state: FirstRoundState<Fp, LookupTableIDs> =
+            rmp_serde::from_slice(&rmp_serde::to_vec(&state).unwrap()).unwrap();
+
+        let split_proof = resume_proving::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            Column,
+            _,
+            N,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            state.clone(),
+            &first_round,
+            &mut o1_utils::tests::make_test_rng(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rmp_serde::to_vec(&one_shot_proof).unwrap(),
+            rmp_serde::to_vec(&split_proof).unwrap()
+        );
+
+        // Resuming with a tampered message fails
+        let mut tampered = first_round.clone();
+        tampered.witness_comms.swap(0, 1);
+        let res = resume_proving::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            Column,
+            _,
+            N,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            state,
+            &tampered,
+            &mut o1_utils::tests::make_test_rng(),
+        );
+        assert!(matches!(res, Err(ProverError::FirstRoundMismatch)));
+    }
+
+    #[test]
+    fn test_checkpoint_restart() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 8;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let srs = setup_srs(domain);
+
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let one_shot_proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs.clone(),
+                &mut o1_utils::tests::make_test_rng(),
+            )
+            .unwrap();
+
+        let checkpoint = prove_until_checkpoint::<_, OpeningProof, BaseSponge, N, LookupTableIDs>(
+            domain,
+            &srs,
+            &constraints,
+            inputs,
+        )
+        .unwrap();
+        assert!(checkpoint.lookup_env().is_some());
+        let path =
+            std::env::temp_dir().join(format!("kimchi_msm_checkpoint_{}.bin", std::process::id()));
+        checkpoint.save(&path).unwrap();
+        drop(checkpoint);
+
+        // The checkpoint is created over a different domain
+        let other_domain = EvaluationDomains::<Fp>::create(domain_size << 1).unwrap();
+        let res = ProverCheckpoint::<N, BN254G1Affine, LookupTableIDs, BaseSponge>::load(
+            &path,
+            other_domain,
+        );
+        assert!(matches!(res, Err(CheckpointError::DomainMismatch { .. })));
+
+        // A fresh prover resumes from the file
+        let checkpoint =
+            ProverCheckpoint::<N, BN254G1Affine, LookupTableIDs, BaseSponge>::load(&path, domain)
+                .unwrap();
+        let proof = resume_from_checkpoint::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            Column,
+            _,
+            N,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            checkpoint,
+            &mut o1_utils::tests::make_test_rng(),
+        )
+        .unwrap();
+        assert_eq!(
+            rmp_serde::to_vec(&one_shot_proof).unwrap(),
+            rmp_serde::to_vec(&proof).unwrap()
+        );
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain.d1.size as usize),
+        ));
+
+        // A corrupted checkpoint is rejected
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        let res =
+            ProverCheckpoint::<N, BN254G1Affine, LookupTableIDs, BaseSponge>::load(&path, domain);
+        assert!(matches!(res, Err(CheckpointError::DigestMismatch)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_prove_many_in_parallel() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ProverContext<BN254G1Affine, OpeningProof, LookupTableIDs>>();
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let srs = setup_srs(domain);
+        // The circuits only differ by their witness
+        let (circuits, inputs): (Vec<_>, Vec<ProofInputs<N, BN254G1Affine, LookupTableIDs>>) = (0
+            ..64)
+            .map(|_| random_lookup_circuit(domain, 1, 16, 2, &mut rng))
+            .unzip();
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &circuits[0],
+        )
+        .unwrap();
+        // Waves of 4 instances
+        let ctx = {
+            let memory_budget = 4 * ctx.memory_per_proof(N, 1);
+            ctx.with_memory_budget(memory_budget)
+        };
+
+        let proofs = prove_many::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs);
+        assert_eq!(proofs.len(), 64);
+        proofs.into_iter().for_each(|proof| {
+            let res = VerifierContext::from_prover_context(&ctx)
+                .unwrap()
+                .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                    &proof.unwrap(),
+                    Witness::zero_vec(domain_size),
+                );
+            assert_eq!(res, Ok(()));
+        });
+    }
+
+    #[test]
+    fn test_column_committers() {
+        use crate::{committer::ColumnDescriptor, prover::prove_with_context};
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 3;
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let srs = setup_srs(domain);
+        // The first column is boolean
+        let x_0 = expr::curr_cell::<Fp>(Column::X(0));
+        let constraints = vec![x_0.clone() * (x_0 - E::one())];
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap();
+        // The third column is not boolean, and is committed with the generic
+        // commitment
+        let described_ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap()
+        .with_column_descriptor(0, ColumnDescriptor::Boolean)
+        .with_column_descriptor(1, ColumnDescriptor::Bits(15))
+        .with_column_descriptor(2, ColumnDescriptor::Boolean);
+
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new([
+                    (0..domain_size)
+                        .map(|_| Fp::from(rng.gen_range(0..2u64)))
+                        .collect(),
+                    (0..domain_size)
+                        .map(|_| Fp::from(rng.gen_range(0..1u64 << 15)))
+                        .collect(),
+                    (0..domain_size).map(|_| Fp::rand(&mut rng)).collect(),
+                ]),
+            },
+            mvlookups: vec![],
+        };
+        let mut prove = |ctx: &ProverContext<_, _, _>| {
+            let proof = prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(ctx, inputs.clone(), &mut rng)
+            .unwrap();
+            let res = VerifierContext::from_prover_context(ctx)
+                .unwrap()
+                .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                    &proof,
+                    Witness::zero_vec(domain_size),
+                );
+            assert_eq!(res, Ok(()));
+            proof
+        };
+        let proof = prove(&ctx);
+        let described_proof = prove(&described_ctx);
+        assert_eq!(
+            proof.proof_comms.witness_comms,
+            described_proof.proof_comms.witness_comms
+        );
+    }
+
+    #[test]
+    fn test_verifier_context_distribution() {
+        use crate::{prover::prove_with_context, verifier::VerifierContext};
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 4;
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        // The SRS of a trusted setup, which each machine loads on its own
+        let srs = |x: u64| {
+            let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::from(x), domain_size);
+            srs.full_srs.add_lagrange_basis(domain.d1);
+            srs
+        };
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+
+        let (bytes, digest, proof) = {
+            let prover_srs = srs(42);
+            let prover_ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &prover_srs,
+                ProtocolConfig::default(),
+                &constraints,
+            )
+            .unwrap();
+            let proof = prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&prover_ctx, inputs, &mut rng)
+            .unwrap();
+            let ctx = VerifierContext::from_prover_context(&prover_ctx).unwrap();
+            assert_eq!(ctx.constraints(), prover_ctx.constraints());
+            // The context only depends on the circuit of the prover
+            let rebuilt = VerifierContext::from_prover_context(&prover_ctx).unwrap();
+            assert_eq!(rebuilt.to_bytes(), ctx.to_bytes());
+            (ctx.to_bytes(), ctx.digest(), proof)
+        };
+
+        let verifier_srs = srs(42);
+        let ctx =
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::from_bytes(&bytes, &verifier_srs)
+                .unwrap();
+        assert_eq!(ctx.digest(), digest);
+        assert_eq!(ctx.to_bytes(), bytes);
+        assert!(ctx.lookup_layout().is_some());
+        assert_eq!(
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness { cols: Box::new([]) }
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            ctx.verify::<BaseSponge, ScalarSponge, N, 1, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size)
+            ),
+            Err(VerifierError::PublicInputCountMismatch {
+                expected: 0,
+                got: 1
+            })
+        );
+
+        // The context is bound to the SRS
+        let other_srs = srs(43);
+        assert!(matches!(
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::from_bytes(&bytes, &other_srs),
+            Err(VerifierError::SrsFingerprintMismatch)
+        ));
+        assert!(matches!(
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::from_bytes(
+                &bytes[..bytes.len() - 1],
+                &verifier_srs
+            ),
+            Err(VerifierError::Deserialization(_))
+        ));
+
+        // A modified circuit has another digest
+        let x = expr::curr_cell::<Fp>(Column::X(N - 1));
+        let mut modified = constraints.clone();
+        modified.push(x.clone() * x.clone() - x);
+        let modified_ctx = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &verifier_srs,
+            ProtocolConfig::default(),
+            &modified,
+        )
+        .unwrap();
+        assert_ne!(modified_ctx.digest(), digest);
+        let unmodified_ctx = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &verifier_srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap();
+        assert_eq!(unmodified_ctx.digest(), digest);
+    }
+
+    #[test]
+    fn test_verifier_context_unknown_partial_sum() {
+        use crate::{columns::PartialSumIdx, verifier::VerifierContext};
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 4;
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let srs = setup_srs(domain);
+        let (constraints, _) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let ctx = |constraints: &[E<Fp>]| {
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                ProtocolConfig::default(),
+                constraints,
+            )
+            .unwrap()
+            .to_bytes()
+        };
+        let n_partial_sums = VerifierContext::<_, OpeningProof, LookupTableIDs>::from_bytes(
+            &ctx(&constraints),
+            &srs,
+        )
+        .unwrap()
+        .lookup_layout()
+        .unwrap()
+        .n_partial_sums();
+
+        // The index of a partial sum can not be built from outside of the
+        // crate, but a serialized context can carry any index. It is checked
+        // against the layout when the context is loaded.
+        let x = expr::curr_cell::<Fp>(Column::X(0));
+        let mut unknown_partial_sum = constraints.clone();
+        unknown_partial_sum.push(
+            expr::curr_cell(Column::LookupPartialSum(PartialSumIdx::new(n_partial_sums))) * x,
+        );
+        assert_eq!(
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::from_bytes(
+                &ctx(&unknown_partial_sum),
+                &srs
+            )
+            .err(),
+            Some(VerifierError::Deserialization(format!(
+                "unknown lookup partial sum {n_partial_sums}"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_prove_audited() {
+        use crate::{
+            audit::{AuditError, MaxSizeAuditor, ProofAuditor, SelfVerifyAuditor},
+            prover::{prove_audited, prove_audited_with_hook},
+        };
+
+        type Auditor<'a> = &'a dyn ProofAuditor<
+            N,
+            BN254G1Affine,
+            OpeningProof,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >;
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 4;
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let srs = setup_srs(domain);
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap();
+        let self_verify = SelfVerifyAuditor::<BaseSponge, ScalarSponge>::new();
+        let prove = |auditors: &[Auditor], rng: &mut _| {
+            prove_audited::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, auditors, inputs.clone(), rng)
+        };
+
+        // The proof is released when the auditors accept it
+        let proof = prove(&[&self_verify, &MaxSizeAuditor::new(usize::MAX)], &mut rng).unwrap();
+        let size = rmp_serde::to_vec(&proof).unwrap().len();
+        assert!(prove(&[&MaxSizeAuditor::new(size)], &mut rng).is_ok());
+
+        // A configuration whose proofs are too large is blocked
+        assert!(matches!(
+            prove(&[&self_verify, &MaxSizeAuditor::new(size - 1)], &mut rng),
+            Err(ProverError::Audit(AuditError::TooLarge { max_size, .. })) if max_size == size - 1
+        ));
+
+        // A bug of the prover is caught by the self-verification
+        let auditors: [Auditor; 2] = [&MaxSizeAuditor::new(usize::MAX), &self_verify];
+        let result = prove_audited_with_hook::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(
+            &ctx,
+            &auditors,
+            inputs.clone(),
+            |proof| proof.proof_evals.witness_evals.cols[0].zeta += Fp::one(),
+            &mut rng,
+        );
+        assert!(matches!(
+            result,
+            Err(ProverError::Audit(AuditError::Verification(_)))
+        ));
+    }
+
+    #[test]
+    fn test_column_permutation() {
+        use crate::{
+            access_patterns::analyze_access_patterns, column_permutation::ColumnPermutation,
+            expr::NamedConstraint, prover::prove_with_context, verifier::VerifierContext,
+        };
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 8;
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        // x[5] = x[3] * x[7] and x[6] = x[1]
+        let cell = |i| expr::curr_cell::<Fp>(Column::X(i));
+        let constraints = vec![cell(5) - cell(3) * cell(7), cell(6) - cell(1)];
+        let mut evaluations = Witness::<N, Vec<Fp>>::zero_vec(domain_size);
+        for i in 0..N {
+            evaluations.cols[i] = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        }
+        for j in 0..domain_size {
+            evaluations.cols[5][j] = evaluations.cols[3][j] * evaluations.cols[7][j];
+            evaluations.cols[6][j] = evaluations.cols[1][j];
+        }
+        let inputs = ProofInputs {
+            evaluations,
+            mvlookups: vec![],
+        };
+
+        // The columns of each constraint are adjacent
+        let report = analyze_access_patterns(&NamedConstraint::from_indexed(&constraints));
+        let permutation = ColumnPermutation::from_access_report(&report, N, 0);
+        assert_eq!(
+            (0..N).map(|i| permutation.position(i)).collect::<Vec<_>>(),
+            vec![5, 3, 6, 0, 7, 1, 4, 2]
+        );
+
+        let plain_ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap();
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap()
+        .with_column_permutation(permutation.clone());
+        // The constraints are renumbered with the recorded permutation
+        let permuted: Vec<_> = plain_ctx
+            .constraints()
+            .iter()
+            .map(|constraint| permutation.apply_to_constraint(constraint))
+            .collect();
+        assert_eq!(ctx.constraints(), permuted.as_slice());
+        assert_ne!(ctx.constraints(), plain_ctx.constraints());
+        let inverse = permutation.inverse();
+        let restored: Vec<_> = ctx
+            .constraints()
+            .iter()
+            .map(|constraint| inverse.apply_to_constraint(constraint))
+            .collect();
+        assert_eq!(restored.as_slice(), plain_ctx.constraints());
+
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+
+        // The permutation is part of the distributed context
+        let verifier_ctx = VerifierContext::from_prover_context(&ctx).unwrap();
+        let plain_verifier_ctx = VerifierContext::from_prover_context(&plain_ctx).unwrap();
+        assert_ne!(verifier_ctx.digest(), plain_verifier_ctx.digest());
+        let verifier_ctx = VerifierContext::<_, OpeningProof, LookupTableIDs>::from_bytes(
+            &verifier_ctx.to_bytes(),
+            &srs,
+        )
+        .unwrap();
+        assert_eq!(verifier_ctx.column_permutation(), Some(&permutation));
+        let verify = |ctx: &VerifierContext<_, OpeningProof, LookupTableIDs>| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness { cols: Box::new([]) },
+            )
+        };
+        assert_eq!(verify(&verifier_ctx), Ok(()));
+        assert!(verify(&plain_verifier_ctx).is_err());
+        let rebuilt = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap()
+        .with_column_permutation(permutation);
+        assert_eq!(rebuilt.digest(), verifier_ctx.digest());
+
+        // The evaluations map back to the columns of the circuit
+        assert_eq!(
+            verifier_ctx.witness_evaluations(&proof, 6),
+            verifier_ctx.witness_evaluations(&proof, 1)
+        );
+        assert_ne!(
+            plain_verifier_ctx.witness_evaluations(&proof, 6),
+            plain_verifier_ctx.witness_evaluations(&proof, 1)
+        );
+    }
+
+    #[test]
+    fn test_column_permutation_shifted_columns() {
+        use crate::{
+            column_permutation::ColumnPermutation, prover::prove_with_context,
+            verifier::VerifierContext,
+        };
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 4;
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        // x[0] alternates between a and b, x[1] is a * b, read two rows
+        // ahead through x[0], and x[3] = x[2]
+        let cell = |i| expr::curr_cell::<Fp>(Column::X(i));
+        let constraints = vec![
+            expr::next_cell(Column::X(0)) * expr::shift2_cell(Column::X(0)) - cell(1),
+            cell(3) - cell(2),
+        ];
+        let (a, b) = (Fp::rand(&mut rng), Fp::rand(&mut rng));
+        let x2: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new([
+                    (0..domain_size)
+                        .map(|i| if i % 2 == 0 { a } else { b })
+                        .collect(),
+                    vec![a * b; domain_size],
+                    x2.clone(),
+                    x2,
+                ]),
+            },
+            mvlookups: vec![],
+        };
+
+        // The shifted column is renumbered with the others, and moves onto
+        // a column which does not satisfy the constraint
+        let permutation = ColumnPermutation::from_positions(vec![2, 3, 0, 1]).unwrap();
+        assert_eq!(
+            permutation.apply_to_constraint(&constraints[0]),
+            expr::next_cell(Column::X(2)) * expr::shift2_cell(Column::X(2)) - cell(3)
+        );
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .unwrap()
+        .with_column_permutation(permutation);
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+        assert_eq!(
+            proof.proof_evals.shifted_evals().keys().collect::<Vec<_>>(),
+            vec![&2]
+        );
+        let verifier_ctx = VerifierContext::from_prover_context(&ctx).unwrap();
+        assert_eq!(
+            verifier_ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness { cols: Box::new([]) },
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_constant_columns_elision_shifted_columns() {
+        const N: usize = 4;
+        const M: usize = 3;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        // x[0] alternates between a and b, x[1] is the constant a * b, read
+        // two rows ahead, and x[3] = x[2]
+        let cell = |i| expr::curr_cell::<Fp>(Column::X(i));
+        let constraints = vec![
+            expr::next_cell(Column::X(0)) * expr::shift2_cell(Column::X(0))
+                - expr::shift2_cell(Column::X(1)),
+            expr::shift2_cell(Column::X(3)) - expr::shift2_cell(Column::X(2)),
+            cell(3) - cell(2),
+        ];
+        let (a, b) = (Fp::rand(&mut rng), Fp::rand(&mut rng));
+        let x2: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new([
+                    (0..domain_size)
+                        .map(|i| if i % 2 == 0 { a } else { b })
+                        .collect(),
+                    vec![a * b; domain_size],
+                    x2.clone(),
+                    x2,
+                ]),
+            },
+            mvlookups: vec![],
+        };
+        let constant_columns = ConstantColumns::detect(&inputs.evaluations);
+        assert_eq!(constant_columns.columns, BTreeMap::from([(1, a * b)]));
+        // The shifted references are replaced or renumbered too
+        assert_eq!(
+            constant_columns.substitute(&constraints[1]),
+            expr::shift2_cell(Column::X(2)) - expr::shift2_cell(Column::X(1))
+        );
+
+        let proof = prove_without_constant_columns::<N, M>(
+            domain,
+            &srs,
+            &constraints,
+            &constant_columns,
+            inputs,
+            &mut rng,
+        )
+        .unwrap();
+        let res =
+            verify_without_constant_columns(domain, &srs, &constraints, &constant_columns, &proof);
+        assert_eq!(res, Ok(()));
+    }
+
+    #[test]
+    fn test_max_rows_boundary_selector() {
+        use crate::{
+            config::ConfigError,
+            domain::domain_padding,
+            prover::prove_with_context,
+            verifier::{VerifierContext, VerifierError},
+            witness::WitnessError,
+        };
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let max_rows = 10;
+        let domain = EvaluationDomains::<Fp>::create(1 << 4).unwrap();
+        let srs = setup_srs(domain);
+        let config = ProtocolConfig {
+            max_rows: Some(max_rows),
+            ..ProtocolConfig::default()
+        };
+        // x[1] = x[0]^2 + 1 on the rows of the circuit only, as the padding
+        // rows are zero
+        let cell = |i| expr::curr_cell::<Fp>(Column::X(i));
+        let constraint = cell(1) - cell(0) * cell(0) - E::one();
+        let constraints = vec![config.boundary_selector().unwrap() * constraint.clone()];
+        let mut trace = |rows: usize| {
+            let x: Vec<Fp> = (0..rows).map(|_| Fp::rand(&mut rng)).collect();
+            let y = x.iter().map(|x| *x * x + Fp::one()).collect();
+            ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+                evaluations: Witness {
+                    cols: Box::new([x, y]),
+                },
+                mvlookups: vec![],
+            }
+        };
+
+        // A witness one row over the limit is rejected
+        assert_eq!(
+            trace(max_rows + 1)
+                .pad(domain_padding(&domain, max_rows + 1), config.max_rows)
+                .err(),
+            Some(WitnessError::TooManyRows {
+                max: max_rows,
+                got: max_rows + 1
+            })
+        );
+
+        // The trace is shorter than the domain, and the padding rows are not
+        // selected
+        let inputs = trace(max_rows)
+            .pad(domain_padding(&domain, max_rows), config.max_rows)
+            .unwrap();
+        let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            config.clone(),
+            &constraints,
+        )
+        .unwrap();
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs.clone(), &mut rng)
+        .unwrap();
+        let verifier_ctx = VerifierContext::<_, OpeningProof, LookupTableIDs>::from_bytes(
+            &VerifierContext::from_prover_context(&ctx)
+                .unwrap()
+                .to_bytes(),
+            &srs,
+        )
+        .unwrap();
+        assert_eq!(verifier_ctx.max_rows(), Some(max_rows));
+        assert_eq!(
+            verifier_ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness { cols: Box::new([]) },
+            ),
+            Ok(())
+        );
+
+        // Without the selector, the padding rows do not satisfy the constraint
+        let unrestricted_ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            config.clone(),
+            &[constraint],
+        )
+        .unwrap();
+        assert!(prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&unrestricted_ctx, inputs, &mut rng)
+        .is_err());
+
+        // The maximum number of rows is part of the digest, and the selector
+        // must be the one of the configuration
+        let context = |max_rows, constraints: &[E<Fp>]| {
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                ProtocolConfig {
+                    max_rows,
+                    ..ProtocolConfig::default()
+                },
+                constraints,
+            )
+        };
+        assert_ne!(
+            context(Some(max_rows), &[]).unwrap().digest(),
+            context(Some(max_rows + 1), &[]).unwrap().digest()
+        );
+        assert_eq!(
+            context(Some(max_rows + 1), &constraints).err(),
+            Some(VerifierError::Config(
+                ConfigError::BoundarySelectorMismatch(0)
+            ))
+        );
+        assert_eq!(
+            context(None, &constraints).err(),
+            Some(VerifierError::Config(
+                ConfigError::BoundarySelectorMismatch(0)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_lookup_observer() {
+        use crate::{
+            lookup_stats::{LookupStats, ProofCounts},
+            prover::prove_with_context,
+        };
+        use std::sync::{Arc, Mutex};
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let srs = setup_srs(domain);
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let observed = Arc::new(Mutex::new(vec![]));
+        let ctx = {
+            let observed = observed.clone();
+            ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                ProtocolConfig::default(),
+                &constraints,
+            )
+            .unwrap()
+            .with_lookup_observer(Arc::new(move |stats: &LookupStats| {
+                observed.lock().unwrap().push(stats.clone())
+            }))
+        };
+        let expected = LookupStats::from_witness(&inputs.mvlookups);
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(observed.len(), 1);
+        assert_eq!(observed[0].tables, expected.tables);
+        let table = &observed[0].tables[0];
+        assert_eq!(table.queries, 2 * domain_size);
+        assert_eq!(table.entries, 16);
+        assert_eq!(table.partial_sums, 1);
+        // The 2 witness columns, the multiplicities, the partial sum, the
+        // running sum, the table and the quotient. All but the quotient are
+        // evaluated at ζ and ζω, and the quotient at ζω.
+        let counts = ProofCounts {
+            commitments: 7,
+            evaluations: 13,
+        };
+        assert_eq!(observed[0].proof, Some(counts));
+        assert_eq!(ProofCounts::of_proof(&proof), counts);
+    }
+
+    #[test]
+    fn test_prove_in_injected_thread_pool() {
+        use crate::{
+            committer::{ColumnCommitter, ColumnDescriptor},
+            prover::prove_with_context,
+            verifier::VerifierContext,
+        };
+        use ark_ec::AffineCurve;
+        use std::sync::{Arc, Mutex};
+
+        /// Records the names of the threads committing to the columns, which
+        /// are then committed with the generic commitment
+        #[derive(Default)]
+        struct ThreadRecorder(Mutex<BTreeSet<String>>);
+
+        impl ColumnCommitter<BN254G1Affine> for ThreadRecorder {
+            fn commit(
+                &self,
+                _bases: &[BN254G1Affine],
+                _evals: &[Fp],
+            ) -> Option<<BN254G1Affine as AffineCurve>::Projective> {
+                let name = std::thread::current().name().unwrap_or("").to_string();
+                self.0.lock().unwrap().insert(name);
+                None
+            }
+        }
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let srs = setup_srs(domain);
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .thread_name(|i| format!("injected-prover-{i}"))
+                .build()
+                .unwrap(),
+        );
+
+        let prove = |pool: Option<&Arc<rayon::ThreadPool>>, rng: &mut _| {
+            let recorder = Arc::new(ThreadRecorder::default());
+            let mut ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                ProtocolConfig::default(),
+                &constraints,
+            )
+            .unwrap()
+            .with_column_descriptor(0, ColumnDescriptor::Bits(4))
+            .with_column_descriptor(1, ColumnDescriptor::Bits(4))
+            .with_column_committer(ColumnDescriptor::Bits(4), recorder.clone());
+            if let Some(pool) = pool {
+                ctx = ctx.with_thread_pool(pool.clone());
+            }
+            let proof = prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs.clone(), rng)
+            .unwrap();
+            let verifies = VerifierContext::from_prover_context(&ctx)
+                .unwrap()
+                .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                    &proof,
+                    Witness { cols: Box::new([]) },
+                );
+            assert_eq!(verifies, Ok(()));
+            let threads = recorder.0.lock().unwrap().clone();
+            threads
+        };
+
+        // Both columns are committed on the threads of the injected pool
+        let threads = prove(Some(&pool), &mut rng);
+        assert!(!threads.is_empty());
+        assert!(threads
+            .iter()
+            .all(|name| name.starts_with("injected-prover-")));
+
+        // The global pool is used otherwise
+        let threads = prove(None, &mut rng);
+        assert!(!threads.is_empty());
+        assert!(threads
+            .iter()
+            .all(|name| !name.starts_with("injected-prover-")));
+    }
+
+    #[test]
+    fn test_verify_with_srs_set() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let old_srs = setup_srs(domain);
+        let new_srs = {
+            let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+            srs.full_srs.add_lagrange_basis(domain.d1);
+            srs
+        };
+        let old_label = SrsLabel::new("old");
+        let new_label = SrsLabel::fingerprint::<BN254G1Affine, OpeningProof>(&new_srs);
+        assert_ne!(
+            new_label,
+            SrsLabel::fingerprint::<BN254G1Affine, OpeningProof>(&old_srs)
+        );
+
+        let (constraints, _) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let mut prove = |srs: &PairingSRS<BN254>, srs_label: Option<SrsLabel>| {
+            let config = ProtocolConfig {
+                srs_label,
+                ..Default::default()
+            };
+            let (_, inputs) = random_lookup_circuit(domain, 1, 16, 2, &mut rng);
+            ProverContext::new(domain, srs, config.clone(), &constraints)
+                .and_then(|ctx| {
+                    prove_with_context::<
+                        _,
+                        OpeningProof,
+                        BaseSponge,
+                        ScalarSponge,
+                        _,
+                        N,
+                        LookupTableIDs,
+                        PointEvaluations<Fp>,
+                    >(&ctx, inputs, &mut rng)
+                })
+                .unwrap()
+        };
+        let old_proof = prove(&old_srs, Some(old_label.clone()));
+        let new_proof = prove(&new_srs, Some(new_label.clone()));
+        let unlabeled_proof = prove(&new_srs, None);
+        assert_eq!(new_proof.srs_label(), Some(&new_label));
+
+        let verify = |candidates: &[(SrsLabel, &PairingSRS<BN254>)]| {
+            verify_with_srs_set::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                N,
+                0,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(
+                domain,
+                candidates,
+                &ProtocolConfig::default(),
+                &constraints,
+                &[
+                    (&old_proof, Witness::zero_vec(domain_size)),
+                    (&new_proof, Witness::zero_vec(domain_size)),
+                    (&unlabeled_proof, Witness::zero_vec(domain_size)),
+                ],
+            )
+        };
+        assert_eq!(
+            verify(&[(old_label.clone(), &old_srs), (new_label.clone(), &new_srs)]),
+            vec![Ok(()), Ok(()), Err(VerifierError::UnknownSrs(None))]
+        );
+        // The new SRS is not in the set
+        assert_eq!(
+            verify(&[(old_label.clone(), &old_srs)])[1],
+            Err(VerifierError::UnknownSrs(Some(new_label.clone())))
+        );
+        // The label must designate the SRS the proof is created with
+        assert_eq!(
+            verify(&[(old_label, &new_srs)])[0],
+            Err(VerifierError::OpeningProofFailed)
+        );
+
+        // The label is part of the statement
+        let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &new_srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .and_then(|ctx| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &new_proof,
+                Witness::zero_vec(domain_size),
+            )
+        });
+        assert_eq!(
+            res,
+            Err(VerifierError::Config(ConfigError::SrsLabelMismatch))
+        );
+    }
+
+    #[test]
+    fn test_chunked_commitments() {
+        use crate::{mvlookup::LookupTableID, proof::CommitmentLabel};
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain_size = 1 << 6;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let srs = setup_srs(domain);
+        // The commitments over the domain have two chunks with this SRS
+        let half_srs = {
+            let mut srs: PairingSRS<BN254> =
+                PairingSRS::create(Fp::rand(&mut rng), domain_size / 2);
+            srs.full_srs.add_lagrange_basis(domain.d1);
+            srs
+        };
+        let chunked = ConfigError::ChunkedCommitments {
+            domain_size,
+            srs_size: domain_size / 2,
+        };
+        let config = ProtocolConfig::default();
+
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let prove = |srs: &PairingSRS<BN254>, rng: &mut _| {
+            ProverContext::new(domain, srs, config.clone(), &constraints).and_then(|ctx| {
+                prove_with_context::<
+                    _,
+                    OpeningProof,
+                    BaseSponge,
+                    ScalarSponge,
+                    _,
+                    N,
+                    LookupTableIDs,
+                    PointEvaluations<Fp>,
+                >(&ctx, inputs.clone(), rng)
+            })
+        };
+        let verify = |srs: &PairingSRS<BN254>, proof: &Proof<N, _, _, _, _>| {
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                srs,
+                config.clone(),
+                &constraints,
+            )
+            .and_then(|ctx| {
+                ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                    proof,
+                    Witness::zero_vec(domain_size),
+                )
+            })
+        };
+        assert!(matches!(
+            prove(&half_srs, &mut rng),
+            Err(ProverError::Config(err)) if err == chunked
+        ));
+
+        // Each commitment of the lookup argument has a single chunk, in the
+        // order of the transcript
+        let proof = prove(&srs, &mut rng).unwrap();
+        let schedule = proof.proof_comms.chunk_schedule();
+        let labels: Vec<CommitmentLabel> = schedule.iter().map(|(label, _)| *label).collect();
+        assert_eq!(
+            labels,
+            vec![
+                CommitmentLabel::Witness(0),
+                CommitmentLabel::Witness(1),
+                CommitmentLabel::LookupMultiplicity(LookupTableIDs::Custom(1).to_u32()),
+                CommitmentLabel::LookupPartialSum(0),
+                CommitmentLabel::LookupFixedTable(LookupTableIDs::Custom(1).to_u32()),
+                CommitmentLabel::LookupAggregation,
+                CommitmentLabel::Quotient,
+            ]
+        );
+        assert!(schedule
+            .iter()
+            .all(|(label, chunks)| *chunks == 1 || *label == CommitmentLabel::Quotient));
+        assert_eq!(verify(&srs, &proof), Ok(()));
+        assert_eq!(
+            verify(&half_srs, &proof),
+            Err(VerifierError::Config(chunked))
+        );
+
+        // A chunk added to a commitment of the lookup argument is rejected
+        // before the transcript absorbs it
+        let mut tampered = proof.clone();
+        let comms = tampered.proof_comms.mvlookup_comms.as_mut().unwrap();
+        let chunk = comms.h[0].elems[0];
+        comms.h[0].elems.push(chunk);
+        assert_eq!(
+            verify(&srs, &tampered),
+            Err(VerifierError::CommitmentChunkCountMismatch {
+                commitment: CommitmentLabel::LookupPartialSum(0),
+                expected: 1,
+                got: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_degenerate_evaluation_point() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let omega = domain.d1.group_gen;
+        let zeta = Fp::rand(&mut rng);
+        assert!(DegeneratePointRule::is_degenerate(Fp::zero(), domain));
+        assert!(DegeneratePointRule::is_degenerate(Fp::one(), domain));
+        assert!(DegeneratePointRule::is_degenerate(omega.pow([3]), domain));
+        assert!(!DegeneratePointRule::is_degenerate(zeta, domain));
+
+        // A sponge stub squeezing the given challenges, and counting the
+        // squeezes
+        let stub = |challenges: Vec<Fp>| {
+            let mut challenges = challenges.into_iter();
+            let mut squeezes = 0;
+            move || {
+                squeezes += 1;
+                (challenges.next().unwrap(), squeezes)
+            }
+        };
+        for rule in [DegeneratePointRule::Abort, DegeneratePointRule::Rederive] {
+            let mut squeeze = stub(vec![zeta]);
+            assert_eq!(
+                rule.derive_evaluation_point(domain, || squeeze().0),
+                Some(zeta)
+            );
+        }
+        let mut squeeze = stub(vec![omega.pow([3]), zeta]);
+        assert_eq!(
+            DegeneratePointRule::Abort.derive_evaluation_point(domain, || squeeze().0),
+            None
+        );
+        let mut squeezes = 0;
+        let mut squeeze = stub(vec![omega.pow([3]), Fp::zero(), zeta]);
+        assert_eq!(
+            DegeneratePointRule::Rederive.derive_evaluation_point(domain, || {
+                let (challenge, n) = squeeze();
+                squeezes = n;
+                challenge
+            }),
+            Some(zeta)
+        );
+        assert_eq!(squeezes, 3);
+        // The number of new challenges is bounded
+        let mut squeeze = stub(vec![omega; 1 + DegeneratePointRule::MAX_REDERIVATIONS]);
+        assert_eq!(
+            DegeneratePointRule::Rederive.derive_evaluation_point(domain, || squeeze().0),
+            None
+        );
+
+        // The prover and the verifier follow the rule of the configuration
+        const N: usize = 2;
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        let config = ProtocolConfig {
+            degenerate_point_rule: DegeneratePointRule::Rederive,
+            ..Default::default()
+        };
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let proof = ProverContext::new(domain, &srs, config.clone(), &constraints)
+            .and_then(|ctx| {
+                prove_with_context::<
+                    _,
+                    OpeningProof,
+                    BaseSponge,
+                    ScalarSponge,
+                    _,
+                    N,
+                    LookupTableIDs,
+                    PointEvaluations<Fp>,
+                >(&ctx, inputs, &mut rng)
+            })
+            .unwrap();
+        let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            config.clone(),
+            &constraints,
+        )
+        .and_then(|ctx| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size),
+            )
+        });
+        assert_eq!(res, Ok(()));
+    }
+
+    #[test]
+    fn test_transcript_backends() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        let configs =
+            [TranscriptBackend::FqSponge, TranscriptBackend::ScalarSponge].map(|transcript| {
+                ProtocolConfig {
+                    transcript,
+                    ..Default::default()
+                }
+            });
+        let (constraints, _) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let proofs = configs.clone().map(|config| {
+            let (_, inputs) = random_lookup_circuit(domain, 1, 16, 2, &mut rng);
+            ProverContext::new(domain, &srs, config.clone(), &constraints)
+                .and_then(|ctx| {
+                    prove_with_context::<
+                        _,
+                        OpeningProof,
+                        BaseSponge,
+                        ScalarSponge,
+                        _,
+                        N,
+                        LookupTableIDs,
+                        PointEvaluations<Fp>,
+                    >(&ctx, inputs, &mut rng)
+                })
+                .unwrap()
+        });
+        for (i, config) in configs.iter().enumerate() {
+            for (j, proof) in proofs.iter().enumerate() {
+                let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                    domain,
+                    &srs,
+                    config.clone(),
+                    &constraints,
+                )
+                .and_then(|ctx| {
+                    ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                        proof,
+                        Witness::zero_vec(domain_size),
+                    )
+                });
+                assert_eq!(res.is_ok(), i == j, "{:?}", config.transcript);
+            }
+        }
+
+        // The default backend is the sponge given to the prover
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                random_lookup_circuit(domain, 1, 16, 2, &mut rng).1,
+                &mut rng,
+            )
+            .unwrap();
+        let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            configs[0].clone(),
+            &constraints,
+        )
+        .and_then(|ctx| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(domain_size),
+            )
+        });
+        assert_eq!(res, Ok(()));
+    }
+
+    fn setup_srs(domain: EvaluationDomains<Fp>) -> PairingSRS<BN254> {
+        let mut rng = o1_utils::tests::make_test_rng();
+        // Trusted setup toxic waste
+        let x = Fp::rand(&mut rng);
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(x, domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        srs
+    }
+
+    fn prove_and_verify<const N: usize>(
+        domain: EvaluationDomains<Fp>,
+        srs: &PairingSRS<BN254>,
+        constraints: &Vec<E<Fp>>,
+        inputs: ProofInputs<N, BN254G1Affine, LookupTableIDs>,
+    ) -> Result<bool, ProverError> {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+            domain,
+            srs,
+            constraints,
+            inputs,
+            &mut rng,
+        )?;
+        Ok(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTableIDs,
+        >(
+            domain,
+            srs,
+            constraints,
+            &proof,
+            Witness::zero_vec(domain.d1.size as usize),
+        ))
+    }
+
+    /// Create a proof omitting the columns of `constant_columns`, whose
+    /// references are replaced by their values in the constraints
+    fn prove_without_constant_columns<const N: usize, const M: usize>(
+        domain: EvaluationDomains<Fp>,
+        srs: &PairingSRS<BN254>,
+        constraints: &[E<Fp>],
+        constant_columns: &ConstantColumns<Fp>,
+        inputs: ProofInputs<N, BN254G1Affine, LookupTableIDs>,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Proof<M, BN254G1Affine, OpeningProof, LookupTableIDs>, ProverError> {
+        let inputs = constant_columns.reduce_inputs::<N, M, _, _>(inputs)?;
+        let constraints: Vec<_> = constraints
+            .iter()
+            .map(|constraint| constant_columns.substitute(constraint))
+            .collect();
+        let ctx = ProverContext::new(domain, srs, ProtocolConfig::unsimplified(), &constraints)?;
+        prove_with_context::<_, OpeningProof, BaseSponge, ScalarSponge, _, M, LookupTableIDs, _>(
+            &ctx, inputs, rng,
+        )
+    }
+
+    /// Verify a proof created by [prove_without_constant_columns]
+    fn verify_without_constant_columns<const M: usize>(
+        domain: EvaluationDomains<Fp>,
+        srs: &PairingSRS<BN254>,
+        constraints: &[E<Fp>],
+        constant_columns: &ConstantColumns<Fp>,
+        proof: &Proof<M, BN254G1Affine, OpeningProof, LookupTableIDs>,
+    ) -> Result<(), VerifierError> {
+        let constraints: Vec<_> = constraints
+            .iter()
+            .map(|constraint| constant_columns.substitute(constraint))
+            .collect();
+        VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), &constraints)?
+            .verify::<BaseSponge, ScalarSponge, M, 0, _>(
+                proof,
+                Witness::zero_vec(domain.d1.size as usize),
+            )
+    }
+
+    #[test]
+    fn test_degenerate_no_column_only_lookups() {
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+        let inputs: ProofInputs<0, BN254G1Affine, LookupTableIDs> = ProofInputs {
+            evaluations: Witness::zero_vec(domain.d1.size as usize),
+            mvlookups: vec![LookupWitness::random_with_table(domain, 1, 16, 2)],
+        };
+        // Without constraints, the lookups are not used, see
+        // mvlookup::uses_lookups
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &vec![], inputs),
+            Err(ProverError::UnusedLookups(1))
+        ));
+    }
+
+    #[test]
+    fn test_degenerate_no_constraint_with_lookups() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+        let inputs: ProofInputs<N, BN254G1Affine, LookupTableIDs> = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(std::array::from_fn(|_| {
+                    (0..domain.d1.size).map(|_| Fp::rand(&mut rng)).collect()
+                })),
+            },
+            mvlookups: vec![LookupWitness::random_with_table(domain, 1, 16, 2)],
+        };
+        // Without constraints, the lookups are not used, see
+        // mvlookup::uses_lookups
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &vec![], inputs),
+            Err(ProverError::UnusedLookups(1))
+        ));
+    }
+
+    #[test]
+    fn test_degenerate_no_constraint_no_lookup() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        const N: usize = 2;
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+        let inputs: ProofInputs<N, BN254G1Affine, LookupTableIDs> = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(std::array::from_fn(|_| {
+                    (0..domain.d1.size).map(|_| Fp::rand(&mut rng)).collect()
+                })),
+            },
+            mvlookups: vec![],
+        };
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &vec![], inputs),
+            Err(ProverError::EmptyCircuit)
+        ));
+    }
+
+    #[test]
+    fn test_degenerate_no_column_no_constraint_no_lookup() {
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+        let inputs: ProofInputs<0, BN254G1Affine, LookupTableIDs> = ProofInputs {
+            evaluations: Witness::zero_vec(domain.d1.size as usize),
+            mvlookups: vec![],
+        };
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &vec![], inputs),
+            Err(ProverError::EmptyCircuit)
+        ));
+    }
+
+    fn booleanity_inputs<const N: usize>(
+        domain: EvaluationDomains<Fp>,
+        with_lookups: bool,
+    ) -> ProofInputs<N, BN254G1Affine, LookupTableIDs> {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let cols: [Vec<Fp>; N] = std::array::from_fn(|_| {
+            (0..domain.d1.size)
+                .map(|_| Fp::from(rng.gen_range(0..2u64)))
+                .collect()
+        });
+        let mvlookups = if with_lookups {
+            vec![booleanity::witness(domain, &cols)]
+        } else {
+            vec![]
+        };
+        ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups,
+        }
+    }
+
+    fn booleanity_lookup_constraints(columns: &[Column]) -> Vec<E<Fp>> {
+        let lookups = BTreeMap::from([(LookupTableIDs::Bit, booleanity::lookups(columns))]);
+        constraint_lookups(&lookups)
+    }
+
+    #[test]
+    fn test_booleanity_constraints_and_lookups() {
+        // More columns than terms in a partial sum
+        const N: usize = 8;
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+        let columns: Vec<Column> = (0..N).map(Column::X).collect();
+
+        let shape = booleanity::Shape::new(N);
+        assert_eq!(
+            shape.to_string(),
+            "booleanity of 8 columns:
+  constraints: 0 additional columns, 8 constraints of degree 2
+  lookups: 5 additional columns, 3 constraints of degree up to 8"
+        );
+        assert_eq!(shape.n_lookup_columns, 5);
+        assert_eq!(shape.n_lookup_constraints, 3);
+
+        // With one constraint per column
+        let constraints: Vec<E<Fp>> = columns
+            .iter()
+            .map(|col| {
+                let x = expr::curr_cell::<Fp>(*col);
+                x.clone() * x.clone() - x
+            })
+            .collect();
+        assert_eq!(constraints.len(), shape.n_constraints);
+        let inputs = booleanity_inputs::<N>(domain, false);
+        assert!(prove_and_verify(domain, &srs, &constraints, inputs).unwrap());
+
+        // With lookups into the bit table
+        let constraints = booleanity_lookup_constraints(&columns);
+        assert_eq!(constraints.len(), shape.n_lookup_constraints);
+        let inputs = booleanity_inputs::<N>(domain, true);
+        assert!(prove_and_verify(domain, &srs, &constraints, inputs).unwrap());
+    }
+
+    #[test]
+    fn test_lookups_by_reference() {
+        use crate::{
+            lookup_fingerprint::WitnessFingerprint,
+            mvlookup::{LookupQuery, LookupQueryError, MVLookupQueries},
+            prover::prove_with_context,
+        };
+
+        const N: usize = 4;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        let table_id = LookupTableIDs::Custom(1);
+
+        // X(3) is range checked in [0, 16), and so is the computed value
+        // 15 - X(3), which is not a witness column
+        let cols: [Vec<Fp>; N] = std::array::from_fn(|i| {
+            (0..domain_size)
+                .map(|_| {
+                    if i == 3 {
+                        Fp::from(rng.gen_range(0..16u64))
+                    } else {
+                        Fp::rand(&mut rng)
+                    }
+                })
+                .collect()
+        });
+        let computed: Vec<Fp> = cols[3].iter().map(|x| Fp::from(15u64) - x).collect();
+        let (mut lookups, by_value) =
+            column_lookups(domain, 1, 16, &[cols[3].clone(), computed], &[0, 1]);
+        lookups[0] = MVLookup::new(table_id, E::one(), &[expr::curr_cell(Column::X(3))]);
+        lookups[1] = MVLookup::new(
+            table_id,
+            E::one(),
+            &[E::from(15) - expr::curr_cell(Column::X(3))],
+        );
+        let constraints = constraint_lookups(&BTreeMap::from([(table_id, lookups)]));
+        let queries = MVLookupQueries {
+            f: vec![
+                LookupQuery::Column(Column::X(3)),
+                LookupQuery::Value(by_value.f[1].clone()),
+                LookupQuery::Value(by_value.f[2].clone()),
+            ],
+            m: by_value.m.clone(),
+        };
+        let witness = Witness {
+            cols: Box::new(cols),
+        };
+
+        // The values read out of the witness are the ones given by value
+        let resolved = queries.clone().resolve(&witness).unwrap();
+        assert_eq!(
+            WitnessFingerprint::from(&resolved),
+            WitnessFingerprint::from(&by_value)
+        );
+
+        // The prover resolves the lookups before proving
+        let ctx =
+            ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints).unwrap();
+        let prove = |queries: MVLookupQueries<Fp, LookupTableIDs>, rng: &mut _| {
+            let mvlookups = vec![queries
+                .resolve(&witness)
+                .map_err(ProverError::LookupQuery)?];
+            let inputs = ProofInputs {
+                evaluations: witness.clone(),
+                mvlookups,
+            };
+            prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs, rng)
+        };
+        let proof = prove(queries.clone(), &mut rng).unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        ));
+
+        // Only the witness columns can be given by reference
+        for column in [Column::X(N), Column::LookupAggregation] {
+            let mut queries = queries.clone();
+            queries.f[0] = LookupQuery::Column(column);
+            assert!(matches!(
+                prove(queries, &mut rng),
+                Err(ProverError::LookupQuery(LookupQueryError::NotAWitnessColumn(c))) if c == column
+            ));
+        }
+        // The table is given by value
+        let mut queries = queries;
+        queries.f.pop();
+        queries.f.push(LookupQuery::Column(Column::X(3)));
+        assert!(matches!(
+            prove(queries, &mut rng),
+            Err(ProverError::LookupQuery(
+                LookupQueryError::MissingTableColumn
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_lookup_witnesses_in_any_order() {
+        const N: usize = 2;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+
+        // X(0) is looked up into the table of ID 1, and X(1) into the table
+        // of ID 2
+        let cols: [Vec<Fp>; N] = std::array::from_fn(|_| {
+            (0..domain_size)
+                .map(|_| Fp::from(rng.gen_range(0..16u64)))
+                .collect()
+        });
+        let (lookups_1, witness_1) = column_lookups(domain, 1, 16, &cols, &[0]);
+        let (lookups_2, witness_2) = column_lookups(domain, 2, 16, &cols, &[1]);
+        let constraints = constraint_lookups(&BTreeMap::from([
+            (LookupTableIDs::Custom(1), lookups_1),
+            (LookupTableIDs::Custom(2), lookups_2),
+        ]));
+
+        // The witnesses are given in the reverse order of their table IDs
+        let inputs = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups: vec![witness_2, witness_1],
+        };
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        ));
+    }
+
+    #[test]
+    fn test_multiplicity_padding() {
+        const N: usize = 2;
+        const TABLE_SIZE: usize = 16;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let srs = setup_srs(domain);
+        let table_id = LookupTableIDs::Custom(1);
+
+        // The table is padded with zeroes after its 16 first rows
+        let (mut constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, TABLE_SIZE as u64, 2, &mut rng);
+        let layout = mvlookup::LookupLayout::of_constraints(&constraints).unwrap();
+        assert_eq!(
+            layout.multiplicities(),
+            vec![layout.multiplicity(table_id).unwrap()]
+        );
+        assert!(mvlookup::constrain_multiplicity_padding::<Fp, _>(
+            &layout,
+            LookupTableIDs::Custom(2),
+            TABLE_SIZE
+        )
+        .is_none());
+        constraints
+            .push(mvlookup::constrain_multiplicity_padding(&layout, table_id, TABLE_SIZE).unwrap());
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &constraints, inputs.clone()),
+            Ok(true)
+        ));
+
+        // The lookups of zero are counted on a padding row instead of the
+        // first row: the sum of the lookups is still zero
+        let mut padded = inputs;
+        let m = padded.mvlookups[0].m[0];
+        assert!(!m.is_zero());
+        padded.mvlookups[0].m[0] = Fp::zero();
+        padded.mvlookups[0].m[TABLE_SIZE] = m;
+        let table = padded.mvlookups[0].f.last_mut().unwrap();
+        table[0].numerator = Fp::zero();
+        table[TABLE_SIZE].numerator = -m;
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &constraints, padded),
+            Err(ProverError::ConstraintNotSatisfied(_))
+        ));
+    }
+
+    #[test]
+    fn test_lookup_free_fast_path() {
+        const N: usize = 2;
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let srs = setup_srs(domain);
+        let columns: Vec<Column> = (0..N).map(Column::X).collect();
+        let with_lookups = booleanity_lookup_constraints(&columns);
+        let lookup_free: Vec<E<Fp>> = columns
+            .iter()
+            .map(|col| {
+                let x = expr::curr_cell::<Fp>(*col);
+                x.clone() * x.clone() - x
+            })
+            .collect();
+        assert!(mvlookup::uses_lookups(&with_lookups));
+        assert!(!mvlookup::uses_lookups(&lookup_free));
+
+        let mut rng = o1_utils::tests::make_test_rng();
+        let prove = |constraints: &Vec<E<Fp>>, with_lookup_data: bool, rng: &mut _| {
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                constraints,
+                booleanity_inputs::<N>(domain, with_lookup_data),
+                rng,
+            )
+        };
+        let verify =
+            |constraints: &Vec<E<Fp>>,
+             proof: &Proof<N, BN254G1Affine, OpeningProof, LookupTableIDs>| {
+                try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+                    domain,
+                    &srs,
+                    constraints,
+                    proof,
+                    Witness::zero_vec(domain.d1.size as usize),
+                )
+            };
+
+        // A circuit with lookups, and a proof with lookup data
+        let proof = prove(&with_lookups, true, &mut rng).unwrap();
+        assert!(proof.proof_comms.mvlookup_comms.is_some());
+        assert_eq!(verify(&with_lookups, &proof), Ok(()));
+
+        // A circuit with lookups, and a proof without lookup data
+        let mut stripped = proof.clone();
+        stripped.proof_comms.mvlookup_comms = None;
+        stripped.proof_evals.mvlookup_evals = None;
+        assert_eq!(
+            verify(&with_lookups, &stripped),
+            Err(VerifierError::MissingLookupTable(
+                mvlookup::LookupTableID::to_u32(&LookupTableIDs::Bit)
+            ))
+        );
+
+        // A lookup-free circuit, and a proof without lookup data. The prover
+        // rejects the lookup witnesses, which would not be constrained.
+        assert!(matches!(
+            prove(&lookup_free, true, &mut rng),
+            Err(ProverError::UnusedLookups(1))
+        ));
+        let lookup_free_proof = prove(&lookup_free, false, &mut rng).unwrap();
+        assert!(lookup_free_proof.proof_comms.mvlookup_comms.is_none());
+        assert!(lookup_free_proof.proof_evals.mvlookup_evals.is_none());
+        assert_eq!(verify(&lookup_free, &lookup_free_proof), Ok(()));
+
+        // A lookup-free circuit, and a proof with lookup data
+        let mut injected = lookup_free_proof;
+        injected.proof_comms.mvlookup_comms = proof.proof_comms.mvlookup_comms.clone();
+        assert_eq!(
+            verify(&lookup_free, &injected),
+            Err(VerifierError::UnexpectedLookupData)
+        );
+        injected.proof_evals.mvlookup_evals = proof.proof_evals.mvlookup_evals;
+        assert_eq!(
+            verify(&lookup_free, &injected),
+            Err(VerifierError::UnexpectedLookupData)
+        );
+    }
+
+    /// A constraint checking two equalities with one random linear
+    /// combination by the joint combiner, see [mvlookup::joint_combiner]
+    #[test]
+    fn test_joint_combiner_in_constraints() {
+        const N: usize = 5;
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let srs = setup_srs(domain);
+        let mut rng = o1_utils::tests::make_test_rng();
+        let x = |i| expr::curr_cell::<Fp>(Column::X(i));
+        let rlc = (x(1) - x(2)) + mvlookup::joint_combiner() * (x(3) - x(4));
+
+        // With lookups, the combiner is coined after the witness columns
+        let (mut constraints, mut inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 1, &mut rng);
+        constraints.push(rlc.clone());
+        inputs.evaluations.cols[2] = inputs.evaluations.cols[1].clone();
+        inputs.evaluations.cols[4] = inputs.evaluations.cols[3].clone();
+        assert!(ProtocolConfig::default()
+            .compile_constraints(constraints.clone())
+            .is_ok());
+        assert!(prove_and_verify(domain, &srs, &constraints, inputs.clone()).unwrap());
+        // One of the equalities does not hold
+        inputs.evaluations.cols[4][7] += Fp::one();
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &constraints, inputs),
+            Err(ProverError::ConstraintNotSatisfied(_))
+        ));
+
+        // Without lookups, the combiner is not coined
+        let lookup_free = vec![x(1) - x(2), rlc];
+        let expected = ConfigError::JointCombinerWithoutLookups(1);
+        assert_eq!(
+            ProtocolConfig::default().compile_constraints(lookup_free.clone()),
+            Err(expected.clone())
+        );
+        let cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![Fp::one(); domain.d1.size as usize]);
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups: vec![],
+        };
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &lookup_free, inputs.clone()),
+            Err(ProverError::Config(err)) if err == expected
+        ));
+        // The verifier rejects the circuit as well, for the proof of its
+        // constraints without the combiner
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &lookup_free[..1].to_vec(),
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(
+            try_verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+                domain,
+                &srs,
+                &lookup_free,
+                &proof,
+                Witness::zero_vec(domain.d1.size as usize),
+            ),
+            Err(VerifierError::Config(expected))
+        );
+    }
+
+    #[test]
+    fn test_booleanity_lookups_non_boolean_value() {
+        const N: usize = 2;
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+        let columns: Vec<Column> = (0..N).map(Column::X).collect();
+        let constraints = booleanity_lookup_constraints(&columns);
+        let mut inputs = booleanity_inputs::<N>(domain, false);
+        inputs.evaluations.cols[1][3] = Fp::from(2u64);
+        inputs.mvlookups = vec![booleanity::witness(domain, &inputs.evaluations.cols[..])];
+        assert!(matches!(
+            prove_and_verify(domain, &srs, &constraints, inputs),
+            Err(ProverError::LookupAggregationNonZero { table_id, .. })
+                if table_id == mvlookup::LookupTableID::to_u32(&LookupTableIDs::Bit)
+        ));
+    }
+
+    #[cfg(feature = "perf-counters")]
+    #[test]
+    fn test_preflight() {
+        use crate::{
+            mvlookup::{LookupLayout, LookupTableID},
+            perf::{PerfCounters, PerfStats},
+            prover::{check_inputs, preflight, prove_with_context},
+        };
+        use std::sync::Arc;
+
+        const N: usize = 8;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let srs = setup_srs(domain);
+        let columns: Vec<Column> = (0..N).map(Column::X).collect();
+        let constraints = booleanity_lookup_constraints(&columns);
+        let layout = LookupLayout::of_constraints(&constraints).unwrap();
+        assert_eq!(
+            layout,
+            LookupLayout::new(&BTreeMap::from([(
+                LookupTableIDs::Bit,
+                booleanity::lookups::<Fp>(&columns)
+            )]))
+        );
+        preflight(&booleanity_inputs::<N>(domain, true), &layout, domain).unwrap();
+
+        // Each defect is reported before committing to any column
+        let check =
+            |inputs: ProofInputs<N, BN254G1Affine, LookupTableIDs>, rng: &mut _| -> ProverError {
+                let perf = Arc::new(PerfCounters::default());
+                let ctx =
+                    ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+                        .unwrap()
+                        .with_perf_counters(perf.clone());
+                let err = check_inputs(&constraints, &inputs, domain)
+                    .and_then(|()| {
+                        prove_with_context::<
+                            _,
+                            OpeningProof,
+                            BaseSponge,
+                            ScalarSponge,
+                            _,
+                            N,
+                            LookupTableIDs,
+                            PointEvaluations<Fp>,
+                        >(&ctx, inputs, rng)
+                    })
+                    .unwrap_err();
+                assert_eq!(perf.stats(), PerfStats::default());
+                err
+            };
+        let bit = LookupTableIDs::Bit.to_u32();
+
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs.evaluations.cols[2].pop();
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::ColumnSizeMismatch(2)
+        ));
+
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs.mvlookups[0].m.pop();
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::MultiplicitiesSizeMismatch { table_id, .. } if table_id == bit
+        ));
+
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs.mvlookups[0].f[1].pop();
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::LookupColumnSizeMismatch { column: 1, .. }
+        ));
+
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs.mvlookups[0].f.pop();
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::MissingTableColumn(table_id) if table_id == bit
+        ));
+
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs.mvlookups.push(inputs.mvlookups[0].clone());
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::DuplicateLookupWitness(table_id) if table_id == bit
+        ));
+
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs
+            .mvlookups
+            .push(LookupWitness::random_with_table(domain, 1, 16, 1));
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::UnregisteredTable(table_id)
+                if table_id == LookupTableIDs::Custom(1).to_u32()
+        ));
+
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs.mvlookups.clear();
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::MissingLookupWitness(table_id) if table_id == bit
+        ));
+
+        // Looking up a single column, with a single partial sum
+        let mut inputs = booleanity_inputs::<N>(domain, true);
+        inputs.mvlookups = vec![booleanity::witness(domain, &inputs.evaluations.cols[..1])];
+        assert!(matches!(
+            check(inputs, &mut rng),
+            ProverError::PartialSumsMismatch {
+                expected: 2,
+                got: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_single_point_opening_mode() {
+        const N: usize = 3;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+
+        // X2 = X0 * X1, without any reference to the next row
+        let constraints = {
+            let x0 = expr::curr_cell::<Fp>(Column::X(0));
+            let x1 = expr::curr_cell::<Fp>(Column::X(1));
+            let x2 = expr::curr_cell::<Fp>(Column::X(2));
+            vec![x2 - x0 * x1]
+        };
+        let inputs = || {
+            let mut rng = o1_utils::tests::make_test_rng();
+            let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+            for _ in 0..domain.d1.size {
+                let (a, b) = (Fp::rand(&mut rng), Fp::rand(&mut rng));
+                cols[0].push(a);
+                cols[1].push(b);
+                cols[2].push(a * b);
+            }
+            ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+                evaluations: Witness {
+                    cols: Box::new(cols),
+                },
+                mvlookups: vec![],
+            }
+        };
+
+        let single_point_config = ProtocolConfig {
+            opening_mode: OpeningMode::SinglePoint,
+            ..Default::default()
+        };
+        let constraints = single_point_config
+            .compile_constraints(constraints)
+            .unwrap();
+
+        let single_point_proof =
+            ProverContext::new(domain, &srs, single_point_config.clone(), &constraints)
+                .and_then(|ctx| {
+                    prove_with_context::<
+                        _,
+                        OpeningProof,
+                        BaseSponge,
+                        ScalarSponge,
+                        _,
+                        N,
+                        LookupTableIDs,
+                        SinglePointEvaluations<Fp>,
+                    >(&ctx, inputs(), &mut rng)
+                })
+                .unwrap();
+        let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            single_point_config.clone(),
+            &constraints,
+        )
+        .and_then(|ctx| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, SinglePointEvaluations<Fp>>(
+                &single_point_proof,
+                Witness::zero_vec(domain.d1.size as usize),
+            )
+        });
+        assert_eq!(res, Ok(()));
+
+        // The same circuit in the default mode
+        let config = ProtocolConfig::default();
+        let proof = ProverContext::new(domain, &srs, config.clone(), &constraints)
+            .and_then(|ctx| {
+                prove_with_context::<
+                    _,
+                    OpeningProof,
+                    BaseSponge,
+                    ScalarSponge,
+                    _,
+                    N,
+                    LookupTableIDs,
+                    PointEvaluations<Fp>,
+                >(&ctx, inputs(), &mut rng)
+            })
+            .unwrap();
+
+        // Only the evaluations at ζω, including the one of ft, are saved
+        let single_point_size = rmp_serde::to_vec(&single_point_proof).unwrap().len();
+        let size = rmp_serde::to_vec(&proof).unwrap().len();
+        assert!(single_point_size < size, "{single_point_size} >= {size}");
+
+        // The proof can not be verified with another configuration
+        let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            config.clone(),
+            &constraints,
+        )
+        .and_then(|ctx| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, SinglePointEvaluations<Fp>>(
+                &single_point_proof,
+                Witness::zero_vec(domain.d1.size as usize),
+            )
+        });
+        assert_eq!(
+            res,
+            Err(VerifierError::Config(ConfigError::OpeningModeMismatch))
+        );
+    }
+
+    #[test]
+    fn test_single_point_opening_mode_rejects_next_row() {
+        // Z_next = Z + X0
+        let constraints = vec![
+            expr::curr_cell::<Fp>(Column::X(0)),
+            expr::next_cell::<Fp>(Column::X(1))
+                - expr::curr_cell::<Fp>(Column::X(1))
+                - expr::curr_cell::<Fp>(Column::X(0)),
+        ];
+        let config = ProtocolConfig {
+            opening_mode: OpeningMode::SinglePoint,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.compile_constraints(constraints.clone()).unwrap_err(),
+            ConfigError::NextRowNotOpened(1)
+        );
+        assert!(ProtocolConfig::default()
+            .compile_constraints(constraints)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_constant_columns_elision() {
+        const N: usize = 4;
+        const M: usize = 2;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+
+        // X1 = 0 and X2 = 1 are constant, X3 = X0 * X2 + X1
+        let constraints = {
+            let x = |i| expr::curr_cell::<Fp>(Column::X(i));
+            vec![
+                x(3) - x(0) * x(2) - x(1),
+                // The constant columns are also constant on the next row
+                expr::next_cell::<Fp>(Column::X(2)) * x(3) - x(0) * x(2),
+            ]
+        };
+        let x0: Vec<Fp> = (0..domain.d1.size).map(|_| Fp::rand(&mut rng)).collect();
+        let inputs = |x2: Vec<Fp>| ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new([
+                    x0.clone(),
+                    vec![Fp::zero(); domain.d1.size as usize],
+                    x2,
+                    x0.clone(),
+                ]),
+            },
+            mvlookups: vec![],
+        };
+        let ones = vec![Fp::one(); domain.d1.size as usize];
+
+        let constant_columns = ConstantColumns::detect(&inputs(ones.clone()).evaluations);
+        assert_eq!(
+            constant_columns.columns,
+            BTreeMap::from([(1, Fp::zero()), (2, Fp::one())])
+        );
+
+        let proof = prove_without_constant_columns::<N, M>(
+            domain,
+            &srs,
+            &constraints,
+            &constant_columns,
+            inputs(ones.clone()),
+            &mut rng,
+        )
+        .unwrap();
+        let res =
+            verify_without_constant_columns(domain, &srs, &constraints, &constant_columns, &proof);
+        assert_eq!(res, Ok(()));
+
+        // The full proof is bigger
+        let full_proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs(ones.clone()),
+                &mut rng,
+            )
+            .unwrap();
+        let size = rmp_serde::to_vec(&proof).unwrap().len();
+        let full_size = rmp_serde::to_vec(&full_proof).unwrap().len();
+        assert!(size < full_size, "{size} >= {full_size}");
+
+        // The verifier must use the same constant columns
+        let other_constant_columns = ConstantColumns {
+            columns: BTreeMap::from([(1, Fp::one()), (2, Fp::one())]),
+        };
+        let res = verify_without_constant_columns(
+            domain,
+            &srs,
+            &constraints,
+            &other_constant_columns,
+            &proof,
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_constant_columns_elision_non_constant_column() {
+        const N: usize = 3;
+        const M: usize = 2;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 8).unwrap();
+        let srs = setup_srs(domain);
+
+        // X2 = X0 * X1
+        let constraints = {
+            let x = |i| expr::curr_cell::<Fp>(Column::X(i));
+            vec![x(2) - x(0) * x(1)]
+        };
+        // X1 is one, except on the last row
+        let mut x1 = vec![Fp::one(); domain.d1.size as usize];
+        *x1.last_mut().unwrap() = Fp::from(2u64);
+        let x0: Vec<Fp> = (0..domain.d1.size).map(|_| Fp::rand(&mut rng)).collect();
+        let x2 = x0.iter().zip(x1.iter()).map(|(a, b)| *a * b).collect();
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new([x0, x1, x2]),
+            },
+            mvlookups: vec![],
+        };
+        assert!(ConstantColumns::detect(&inputs.evaluations)
+            .columns
+            .is_empty());
+
+        // Claiming that X1 is constant
+        let constant_columns = ConstantColumns {
+            columns: BTreeMap::from([(1, Fp::one())]),
+        };
+        let res = prove_without_constant_columns::<N, M>(
+            domain,
+            &srs,
+            &constraints,
+            &constant_columns,
+            inputs,
+            &mut rng,
+        );
+        assert!(matches!(res, Err(ProverError::NonConstantColumn(1))));
+    }
+
+    #[test]
+    fn test_dry_run_agrees_with_the_prover() {
+        use crate::{
+            dry_run::dry_run_prove,
+            perf::PerfCounters,
+            prover::{check_inputs, prove_with_context},
+        };
+        use std::sync::Arc;
+
+        const N: usize = 11;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let srs = setup_srs(domain);
+        let (lookup_constraints, valid) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 5, &mut rng);
+        // x[10] = x[8] * x[9], besides the lookups of the first columns
+        let cell = |i| expr::curr_cell::<Fp>(Column::X(i));
+        let mut constraints = lookup_constraints.clone();
+        constraints.push(cell(10) - cell(8) * cell(9));
+        let mut valid = valid;
+        for j in 0..domain.d1.size as usize {
+            valid.evaluations.cols[10][j] =
+                valid.evaluations.cols[8][j] * valid.evaluations.cols[9][j];
+        }
+
+        let mut product_defect = valid.clone();
+        product_defect.evaluations.cols[10][7] += Fp::one();
+        // The looked-up column differs from the values of the lookups
+        let mut lookup_defect = valid.clone();
+        lookup_defect.evaluations.cols[2][3] += Fp::one();
+        let mut multiplicity_defect = valid.clone();
+        multiplicity_defect.mvlookups[0].m[0] += Fp::one();
+        let mut short_column = valid.clone();
+        short_column.evaluations.cols[4].pop();
+
+        for (name, inputs, accepted) in [
+            ("valid", valid, true),
+            ("product", product_defect, false),
+            ("lookup", lookup_defect, false),
+            ("multiplicity", multiplicity_defect, false),
+            ("short column", short_column, false),
+        ] {
+            let perf = Arc::new(PerfCounters::default());
+            let ctx =
+                ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+                    .unwrap()
+                    .with_perf_counters(perf.clone());
+            // The inputs are checked before any commitment, like in the dry
+            // run
+            let proof = check_inputs(&constraints, &inputs, domain).and_then(|()| {
+                prove_with_context::<
+                    _,
+                    OpeningProof,
+                    BaseSponge,
+                    ScalarSponge,
+                    _,
+                    N,
+                    LookupTableIDs,
+                    PointEvaluations<Fp>,
+                >(&ctx, inputs.clone(), &mut rng)
+            });
+            let report = dry_run_prove::<_, BaseSponge, ScalarSponge, N, LookupTableIDs>(
+                inputs,
+                &constraints,
+                domain,
+            );
+            assert_eq!(proof.is_ok(), accepted, "{name}");
+            match (proof, report) {
+                (Ok(proof), Ok(report)) => {
+                    assert_eq!(
+                        report.n_partial_sums,
+                        proof.proof_comms.mvlookup_comms.unwrap().h.len()
+                    );
+                    assert_eq!(report.quotient_chunks, proof.proof_comms.t_comm.elems.len());
+                    // The dry run does not perform any MSM
+                    if cfg!(feature = "perf-counters") {
+                        assert!(!perf.stats().msms.is_empty());
+                        assert!(report.stats.msms.is_empty());
+                        assert_eq!(
+                            report.stats.placeholder_commitments,
+                            perf.stats().msms.values().sum::<usize>()
+                        );
+                        assert_eq!(report.stats.ffts, perf.stats().ffts);
+                    }
+                }
+                (Err(err), Err(dry_run_err)) => {
+                    assert_eq!(err.to_string(), dry_run_err.to_string(), "{name}")
+                }
+                (proof, report) => panic!(
+                    "{name}: the prover returns {:?}, the dry run {:?}",
+                    proof.err(),
+                    report.err()
+                ),
+            }
+        }
+    }
+
+    #[cfg(feature = "perf-counters")]
+    #[test]
+    fn test_perf_counters_lookups() {
+        use crate::{
+            perf::PerfCounters,
+            proof::{expected_quotient_chunks, max_constraint_degree},
+            prover::prove_with_context,
+            MAX_SUPPORTED_DEGREE,
+        };
+        use std::sync::Arc;
+
+        // The looked-up values are witness columns
+        const N: usize = 11;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+
+        for n_queries in [1, 4, 5, 6, 11] {
+            let (constraints, inputs) =
+                random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, n_queries, &mut rng);
+            // The cells with the same value as the previous column of the
+            // row share its inversion, within a chunk
+            let f = &inputs.mvlookups[0].f;
+            let n_inversions = (0..domain_size)
+                .map(|j| {
+                    1 + (1..f.len())
+                        .filter(|i| {
+                            i % (MAX_SUPPORTED_DEGREE - 2) == 0
+                                || f[*i][j].value != f[i - 1][j].value
+                        })
+                        .count()
+                })
+                .sum::<usize>();
+            let perf = Arc::new(PerfCounters::default());
+            let ctx =
+                ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+                    .unwrap()
+                    .with_perf_counters(perf.clone());
+            prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs, &mut rng)
+            .unwrap();
+            let stats = perf.stats();
+
+            // The looked-up values and the table are split in chunks of
+            // MAX_SUPPORTED_DEGREE - 2 terms, one partial sum per chunk
+            let n_partial_sums =
+                (n_queries + MAX_SUPPORTED_DEGREE - 2) / (MAX_SUPPORTED_DEGREE - 2);
+            // The multiplicities, the partial sums, the fixed table and the
+            // running sum.
+            let n_lookup_columns = n_partial_sums + 3;
+
+            // One batch inversion per chunk, of its denominators
+            assert_eq!(stats.batch_inversions, n_partial_sums);
+            assert_eq!(stats.field_inversions, n_inversions);
+            assert!(n_inversions <= (n_queries + 1) * domain_size);
+
+            // The witness and lookup columns are interpolated once, and
+            // evaluated once over d8. The constraints are interpolated over
+            // the smallest domain containing their degree.
+            assert!(
+                stats.ffts_of_size(domain.d8.size) >= N + n_lookup_columns,
+                "n_queries = {n_queries}"
+            );
+            assert!(
+                stats.ffts_of_size(domain.d1.size) >= N + n_lookup_columns,
+                "n_queries = {n_queries}"
+            );
+            let domain_sizes = [
+                domain.d1.size,
+                domain.d2.size,
+                domain.d4.size,
+                domain.d8.size,
+            ];
+            assert!(stats.ffts.keys().all(|size| domain_sizes.contains(size)));
+
+            // One commitment per column, and one per chunk of the quotient
+            let quotient_chunks =
+                expected_quotient_chunks(max_constraint_degree(&constraints, true), domain);
+            assert_eq!(
+                stats.msms,
+                BTreeMap::from([(domain_size, N + n_lookup_columns + quotient_chunks)])
+            );
+            assert_eq!(
+                stats.bytes_committed,
+                (N + n_lookup_columns + quotient_chunks) * domain_size * 32
+            );
+        }
+    }
+
+    #[cfg(feature = "perf-counters")]
+    #[test]
+    fn test_verification_cache() {
+        use crate::{
+            perf::PerfCounters, verification_cache::LruVerificationCache, verifier::VerifierContext,
+        };
+
+        const N: usize = 2;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        let config = ProtocolConfig::default();
+        let (constraints, inputs) =
+            random_lookup_circuit::<N, BN254G1Affine, _>(domain, 1, 16, 2, &mut rng);
+        let labeled = ProtocolConfig {
+            srs_label: Some(SrsLabel::new("label")),
+            ..Default::default()
+        };
+        let mut prove = |config: &ProtocolConfig| {
+            ProverContext::new(domain, &srs, config.clone(), &constraints)
+                .and_then(|ctx| {
+                    prove_with_context::<
+                        _,
+                        OpeningProof,
+                        BaseSponge,
+                        ScalarSponge,
+                        _,
+                        N,
+                        LookupTableIDs,
+                        PointEvaluations<Fp>,
+                    >(&ctx, inputs.clone(), &mut rng)
+                })
+                .unwrap()
+        };
+        let proof = prove(&config);
+        // Another proof of the same witness, with another transcript
+        let other_proof = prove(&labeled);
+
+        let cache = LruVerificationCache::new(16);
+        let verify = |proof, config: &ProtocolConfig| {
+            let perf = PerfCounters::default();
+            let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                config.clone(),
+                &constraints,
+            )
+            .and_then(|ctx| {
+                ctx.verify_with_cache::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>, _>(
+                    proof,
+                    Witness::zero_vec(domain_size),
+                    &cache,
+                    &perf,
+                )
+            });
+            (res, perf.stats().msms.is_empty())
+        };
+        // The second verification is served by the cache
+        assert_eq!(verify(&proof, &config), (Ok(()), false));
+        assert_eq!(verify(&proof, &config), (Ok(()), true));
+        assert_eq!(cache.len(), 1);
+
+        // The other proof is verified, and the failures are cached too
+        let rejected = Err(VerifierError::Config(ConfigError::SrsLabelMismatch));
+        assert_eq!(verify(&other_proof, &config).0, rejected);
+        assert_eq!(verify(&other_proof, &config), (rejected, true));
+        assert_eq!(verify(&other_proof, &labeled), (Ok(()), false));
+        assert_eq!(cache.len(), 3);
+
+        // The public inputs are part of the key
+        let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            config.clone(),
+            &constraints,
+        )
+        .and_then(|ctx| {
+            ctx.with_public_inputs(1)
+                .verify_with_cache::<BaseSponge, ScalarSponge, N, 1, PointEvaluations<Fp>, _>(
+                    &proof,
+                    Witness::zero_vec(domain_size),
+                    &cache,
+                    &PerfCounters::default(),
+                )
+        });
+        assert_eq!(res, Err(VerifierError::PublicInputMismatch(0)));
+        assert_eq!(cache.len(), 4);
+
+        // The circuit is part of the key: the context of another circuit
+        // sharing the cache verifies the proof, and rejects it
+        let mut other_constraints = constraints.clone();
+        other_constraints.push(expr::curr_cell(Column::X(0)));
+        let other_ctx = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            config.clone(),
+            &other_constraints,
+        )
+        .unwrap();
+        let res = other_ctx
+            .verify_with_cache::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>, _>(
+                &proof,
+                Witness::zero_vec(domain_size),
+                &cache,
+                &PerfCounters::default(),
+            );
+        assert!(res.is_err());
+        assert_eq!(cache.len(), 5);
+    }
+
+    #[test]
+    fn test_subdomain_column() {
+        const N: usize = 4;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 14).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        // X2 is defined over a sub-domain of size 2^10, i.e. every 16 rows
+        let layout = SubdomainLayout {
+            log_size: 10,
+            columns: [2].into(),
+        };
+        let ratio = domain_size / layout.size();
+
+        // X3 = X0 + X1 on every row, X2 = X0 * X1 on the rows of the sub-domain
+        let constraints = {
+            let x = |i| expr::curr_cell::<Fp>(Column::X(i));
+            vec![x(3) - x(0) - x(1), layout.restrict(x(2) - x(0) * x(1))]
+        };
+        let x0: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let x1: Vec<Fp> = (0..domain_size).map(|_| Fp::rand(&mut rng)).collect();
+        let x2: Vec<Fp> = (0..layout.size())
+            .map(|i| x0[i * ratio] * x1[i * ratio])
+            .collect();
+        let x3: Vec<Fp> = x0.iter().zip(x1.iter()).map(|(a, b)| *a + b).collect();
+        let inputs = |x2: Vec<Fp>| ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new([x0.clone(), x1.clone(), x2, x3.clone()]),
+            },
+            mvlookups: vec![],
+        };
+        let ctx = ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints)
+            .unwrap()
+            .with_subdomain(layout.clone());
+        let prove = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >;
+
+        let proof = prove(&ctx, inputs(x2.clone()), &mut rng).unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            LookupTableIDs,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(domain_size),
+        ));
+
+        // The constraint is enforced on the rows of the sub-domain
+        let mut tampered = x2.clone();
+        tampered[3] += Fp::one();
+        let res = prove(&ctx, inputs(tampered), &mut rng);
+        assert!(matches!(res, Err(ProverError::ConstraintNotSatisfied(_))));
+
+        // The column must have the size of the sub-domain
+        let res = prove(&ctx, inputs(x3.clone()), &mut rng);
+        assert!(matches!(res, Err(ProverError::ColumnSizeMismatch(2))));
+
+        // The column of the sub-domain is committed with an MSM of the size
+        // of the sub-domain, the other ones over d1
+        #[cfg(feature = "perf-counters")]
+        {
+            use crate::{perf::PerfCounters, prover::prove_with_context};
+            use std::sync::Arc;
+
+            let perf = Arc::new(PerfCounters::default());
+            let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                ProtocolConfig::default(),
+                &constraints,
+            )
+            .unwrap()
+            .with_subdomain(layout.clone())
+            .with_perf_counters(perf.clone());
+            prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs(x2), &mut rng)
+            .unwrap();
+            let stats = perf.stats();
+            assert_eq!(stats.msms_of_size(layout.size()), 1);
+            assert!(stats.msms_of_size(domain_size) >= N - 1);
+            assert_eq!(
+                stats.msms.keys().copied().collect::<Vec<_>>(),
+                vec![layout.size(), domain_size]
+            );
+        }
+    }
+
+    thread_local! {
+        static FQ_PERMUTATIONS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+        static FR_PERMUTATIONS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+    }
+
+    /// The number of permutations performed by an [ArithmeticSponge] which
+    /// went from `before` to `after` by absorbing `n` elements
+    fn absorb_permutations(before: &SpongeState, n: usize, after: &SpongeState) -> usize {
+        let filled = match before {
+            SpongeState::Absorbed(k) => *k,
+            SpongeState::Squeezed(_) => 0,
+        };
+        match after {
+            SpongeState::Absorbed(k) => (filled + n - k) / SpongeParams::SPONGE_RATE,
+            SpongeState::Squeezed(_) => panic!("the sponge did not absorb"),
+        }
+    }
+
+    /// The number of permutations performed by an [ArithmeticSponge] which
+    /// went from `before` to `after` by squeezing at most one element
+    fn squeeze_permutations(before: &SpongeState, after: &SpongeState) -> usize {
+        match (before, after) {
+            (SpongeState::Squeezed(1), SpongeState::Squeezed(1)) => 0,
+            (_, SpongeState::Squeezed(1)) => 1,
+            _ => 0,
+        }
+    }
+
+    /// The number of permutations performed by an [ArithmeticSponge] in the
+    /// state `before` to squeeze one element
+    fn digest_permutations(before: &SpongeState) -> usize {
+        match before {
+            SpongeState::Squeezed(k) if *k < SpongeParams::SPONGE_RATE => 0,
+            _ => 1,
+        }
+    }
+
+    /// [BaseSponge], counting its permutations in [FQ_PERMUTATIONS]
+    #[derive(Clone)]
+    struct CountingFqSponge(BaseSponge);
+
+    impl CountingFqSponge {
+        fn count<T>(&mut self, op: impl FnOnce(&mut BaseSponge) -> T, absorbed: usize) -> T {
+            let before = self.0.sponge.sponge_state.clone();
+            let res = op(&mut self.0);
+            let after = &self.0.sponge.sponge_state;
+            let permutations = if absorbed > 0 {
+                absorb_permutations(&before, absorbed, after)
+            } else {
+                squeeze_permutations(&before, after)
+            };
+            FQ_PERMUTATIONS.with(|n| n.set(n.get() + permutations));
+            res
+        }
+    }
+
+    impl FqSponge<Fq, BN254G1Affine, Fp> for CountingFqSponge {
+        fn new(p: &'static ArithmeticSpongeParams<Fq>) -> Self {
+            CountingFqSponge(BaseSponge::new(p))
+        }
+
+        fn absorb_fq(&mut self, x: &[Fq]) {
+            self.count(|sponge| sponge.absorb_fq(x), x.len())
+        }
+
+        fn absorb_g(&mut self, g: &[BN254G1Affine]) {
+            self.count(|sponge| sponge.absorb_g(g), 2 * g.len())
+        }
+
+        // The scalar field of BN254 is smaller than its base field, a scalar
+        // is absorbed as a single element.
+        fn absorb_fr(&mut self, x: &[Fp]) {
+            self.count(|sponge| sponge.absorb_fr(x), x.len())
+        }
+
+        fn challenge_fq(&mut self) -> Fq {
+            self.count(|sponge| sponge.challenge_fq(), 0)
+        }
+
+        fn challenge(&mut self) -> Fp {
+            self.count(|sponge| sponge.challenge(), 0)
+        }
+
+        fn digest_fq(self) -> Fq {
+            let permutations = digest_permutations(&self.0.sponge.sponge_state);
+            FQ_PERMUTATIONS.with(|n| n.set(n.get() + permutations));
+            self.0.digest_fq()
+        }
+
+        fn digest(self) -> Fp {
+            let permutations = digest_permutations(&self.0.sponge.sponge_state);
+            FQ_PERMUTATIONS.with(|n| n.set(n.get() + permutations));
+            self.0.digest()
+        }
+    }
+
+    /// [ScalarSponge], counting its permutations in [FR_PERMUTATIONS]
+    struct CountingFrSponge(ScalarSponge);
+
+    impl CountingFrSponge {
+        fn count<T>(&mut self, op: impl FnOnce(&mut ScalarSponge) -> T, absorbed: usize) -> T {
+            let before = self.0.sponge.sponge_state.clone();
+            let res = op(&mut self.0);
+            let after = &self.0.sponge.sponge_state;
+            let permutations = if absorbed > 0 {
+                absorb_permutations(&before, absorbed, after)
+            } else {
+                squeeze_permutations(&before, after)
+            };
+            FR_PERMUTATIONS.with(|n| n.set(n.get() + permutations));
+            res
+        }
+    }
+
+    impl FrSponge<Fp> for CountingFrSponge {
+        fn new(p: &'static ArithmeticSpongeParams<Fp>) -> Self {
+            CountingFrSponge(<ScalarSponge as FrSponge<Fp>>::new(p))
+        }
+
+        fn absorb(&mut self, x: &Fp) {
+            self.count(|sponge| sponge.absorb(x), 1)
+        }
+
+        fn absorb_multiple(&mut self, x: &[Fp]) {
+            self.count(|sponge| sponge.absorb_multiple(x), x.len())
+        }
+
+        fn challenge(&mut self) -> ScalarChallenge<Fp> {
+            self.count(|sponge| sponge.challenge(), 0)
+        }
+
+        fn digest(self) -> Fp {
+            let permutations = digest_permutations(&self.0.sponge.sponge_state);
+            FR_PERMUTATIONS.with(|n| n.set(n.get() + permutations));
+            self.0.digest()
+        }
+
+        fn absorb_evaluations(
+            &mut self,
+            _e: &kimchi::proof::ProofEvaluations<PointEvaluations<Vec<Fp>>>,
+        ) {
+            unimplemented!("the evaluations of kimchi proofs are not absorbed")
+        }
+    }
+
+    /// Returns the number of permutations of the sponges performed by `f`
+    fn count_sponge_permutations<T>(f: impl FnOnce() -> T) -> (T, usize, usize) {
+        FQ_PERMUTATIONS.with(|n| n.set(0));
+        FR_PERMUTATIONS.with(|n| n.set(0));
+        let res = f();
+        (
+            res,
+            FQ_PERMUTATIONS.with(|n| n.get()),
+            FR_PERMUTATIONS.with(|n| n.get()),
+        )
+    }
+
+    #[cfg(feature = "perf-counters")]
+    #[test]
+    fn test_constant_trace() {
+        use crate::{
+            committer::ColumnDescriptor,
+            perf::PerfCounters,
+            prover::{prove_with_context, ProvingMode},
+        };
+        use std::sync::Arc;
+
+        const N: usize = 4;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+        // Each column is looked up into the table of [0, 16)
+        let queried: Vec<usize> = (0..N).collect();
+        let sparse: [Vec<Fp>; N] = std::array::from_fn(|_| vec![Fp::from(0u64); domain_size]);
+        let dense: [Vec<Fp>; N] = std::array::from_fn(|_| {
+            (0..domain_size)
+                .map(|_| Fp::from(rng.gen_range(1..16u64)))
+                .collect()
+        });
+        let (lookups, _) = column_lookups(domain, 0, 16, &sparse, &queried);
+        let constraints =
+            constraint_lookups(&BTreeMap::from([(LookupTableIDs::Custom(0), lookups)]));
+
+        let mut prove = |mode, cols: &[Vec<Fp>; N]| {
+            let (_, witness) = column_lookups(domain, 0, 16, cols, &queried);
+            let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+                evaluations: Witness {
+                    cols: Box::new(cols.clone()),
+                },
+                mvlookups: vec![witness],
+            };
+            let perf = Arc::new(PerfCounters::default());
+            // The first column is boolean in the sparse witness only
+            let ctx = ProverContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                ProtocolConfig::default(),
+                &constraints,
+            )
+            .unwrap()
+            .with_column_descriptor(0, ColumnDescriptor::Boolean)
+            .with_proving_mode(mode)
+            .with_perf_counters(perf.clone());
+            assert_eq!(ctx.proving_mode(), mode);
+            let proof = prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                LookupTableIDs,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs, &mut rng)
+            .unwrap();
+            let res = VerifierContext::from_prover_context(&ctx)
+                .unwrap()
+                .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                    &proof,
+                    Witness::zero_vec(domain_size),
+                );
+            assert_eq!(res, Ok(()));
+            (perf.stats(), proof.proof_comms.witness_comms)
+        };
+
+        // The optimized prover skips some work on the sparse witness: the
+        // specialized commitment of the boolean column, and the denominators
+        // shared by the columns of a row
+        let (sparse_stats, sparse_comms) = prove(ProvingMode::Optimized, &sparse);
+        let (dense_stats, _) = prove(ProvingMode::Optimized, &dense);
+        assert_ne!(sparse_stats.msms, dense_stats.msms);
+        assert!(sparse_stats.field_inversions < dense_stats.field_inversions);
+        assert!(sparse_stats.skipped_numerator_muls > 0);
+
+        // In the constant trace mode, the operations do not depend on the
+        // values, and the commitments are the same
+        let (constant_sparse_stats, constant_sparse_comms) =
+            prove(ProvingMode::ConstantTrace, &sparse);
+        let (constant_dense_stats, _) = prove(ProvingMode::ConstantTrace, &dense);
+        assert_eq!(constant_sparse_stats, constant_dense_stats);
+        assert_eq!(constant_sparse_stats.skipped_numerator_muls, 0);
+        assert_eq!(constant_sparse_comms, sparse_comms);
+    }
+
+    #[cfg(feature = "perf-counters")]
+    #[test]
+    fn test_estimate_verifier_cost() {
+        use crate::{
+            cost::{estimate_verifier_cost, CircuitShape, FieldOps},
+            mvlookup::LookupTableID,
+            perf::PerfCounters,
+            verifier::VerifierContext,
+        };
+
+        const N: usize = 3;
+        const NPUB: usize = 1;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+
+        // X2 = X0 * X1, and X0 is public
+        let relation = {
+            let x0 = expr::curr_cell::<Fp>(Column::X(0));
+            let x1 = expr::curr_cell::<Fp>(Column::X(1));
+            let x2 = expr::curr_cell::<Fp>(Column::X(2));
+            vec![x2 - x0 * x1]
+        };
+        // The factors are small enough for the three columns to be looked up
+        // in a table of 16 values
+        let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+        for _ in 0..domain_size {
+            let (a, b) = (rng.gen_range(0..4u64), rng.gen_range(0..4u64));
+            cols[0].push(Fp::from(a));
+            cols[1].push(Fp::from(b));
+            cols[2].push(Fp::from(a * b));
+        }
+
+        for (table_id, n_queries) in [(None, 0), (Some(1), 1), (Some(2), 6), (Some(1), 11)] {
+            // The columns are looked up in turn
+            let (constraints, mvlookups) = match table_id {
+                None => (relation.clone(), vec![]),
+                Some(id) => {
+                    let queried: Vec<usize> = (0..n_queries).map(|i| i % N).collect();
+                    let (lookups, lookup) = column_lookups(domain, id, 16, &cols, &queried);
+                    let constraints = relation
+                        .iter()
+                        .cloned()
+                        .chain(constraint_lookups(&BTreeMap::from([(
+                            LookupTableIDs::Custom(id),
+                            lookups,
+                        )])))
+                        .collect();
+                    (constraints, vec![lookup])
+                }
+            };
+            let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+                evaluations: Witness {
+                    cols: Box::new(cols.clone()),
+                },
+                mvlookups,
+            };
+            let proof =
+                prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                    domain,
+                    &srs,
+                    &constraints,
+                    inputs,
+                    &mut rng,
+                )
+                .unwrap();
+
+            let ctx = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                ProtocolConfig::unsimplified(),
+                &constraints,
+            )
+            .unwrap()
+            .with_public_inputs(NPUB);
+            let perf = PerfCounters::default();
+            let (res, fq_permutations, fr_permutations) = count_sponge_permutations(|| {
+                ctx.verify_with_perf_counters::<
+                    CountingFqSponge,
+                    CountingFrSponge,
+                    N,
+                    NPUB,
+                    PointEvaluations<Fp>,
+                >(
+                    &proof,
+                    Witness {
+                        cols: Box::new([cols[0].clone()]),
+                    },
+                    &perf,
+                )
+            });
+            assert_eq!(res, Ok(()));
+
+            let lookups = table_id
+                .map(|id| BTreeMap::from([(LookupTableIDs::Custom(id).to_u32(), n_queries)]))
+                .unwrap_or_default();
+            let shape = CircuitShape::new(domain.d1.size, N, NPUB, &constraints, lookups);
+            let cost = estimate_verifier_cost(&shape, &ProtocolConfig::default());
+            let stats = perf.stats();
+            assert_eq!(cost.fq_sponge_permutations, fq_permutations, "{n_queries}");
+            assert_eq!(cost.fr_sponge_permutations, fr_permutations, "{n_queries}");
+            assert_eq!(cost.msms, stats.msms, "{n_queries}");
+            assert_eq!(cost.scalar_muls, stats.scalar_muls, "{n_queries}");
+            // The evaluations of the witness and of the lookup argument are
+            // borrowed, and only copied into the batch of the opening proof
+            let n_lookup_evals = proof
+                .proof_evals
+                .mvlookup_evals
+                .as_ref()
+                .map_or(0, |evals| evals.into_iter().count());
+            assert_eq!(
+                stats.evaluation_copies,
+                2 * (N + n_lookup_evals),
+                "{n_queries}"
+            );
+            // The combination with α^0 = 1 is folded
+            if table_id.is_none() {
+                assert_eq!(
+                    cost.constraint_ops,
+                    FieldOps {
+                        additions: 1,
+                        multiplications: 1,
+                        inversions: 0
+                    }
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_estimate_verifier_cost_single_point() {
+        use crate::cost::{estimate_verifier_cost, CircuitShape};
+
+        const N: usize = 3;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+
+        // X2 = X0 * X1
+        let constraints = {
+            let x0 = expr::curr_cell::<Fp>(Column::X(0));
+            let x1 = expr::curr_cell::<Fp>(Column::X(1));
+            let x2 = expr::curr_cell::<Fp>(Column::X(2));
+            vec![x2 - x0 * x1]
+        };
+        let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+        for _ in 0..domain_size {
+            let (a, b) = (Fp::rand(&mut rng), Fp::rand(&mut rng));
+            cols[0].push(a);
+            cols[1].push(b);
+            cols[2].push(a * b);
+        }
+
+        let config = ProtocolConfig {
+            opening_mode: OpeningMode::SinglePoint,
+            ..Default::default()
+        };
+        let proof = ProverContext::new(domain, &srs, config.clone(), &constraints)
+            .and_then(|ctx| {
+                prove_with_context::<
+                    _,
+                    OpeningProof,
+                    BaseSponge,
+                    ScalarSponge,
+                    _,
+                    N,
+                    LookupTableIDs,
+                    SinglePointEvaluations<Fp>,
+                >(
+                    &ctx,
+                    ProofInputs {
+                        evaluations: Witness {
+                            cols: Box::new(cols),
+                        },
+                        mvlookups: vec![],
+                    },
+                    &mut rng,
+                )
+            })
+            .unwrap();
+        let (res, fq_permutations, fr_permutations) = count_sponge_permutations(|| {
+            VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                domain,
+                &srs,
+                config.clone(),
+                &constraints,
+            )
+            .and_then(|ctx| {
+                ctx.verify::<CountingFqSponge, CountingFrSponge, N, 0, SinglePointEvaluations<Fp>>(
+                    &proof,
+                    Witness::zero_vec(domain_size),
+                )
+            })
+        });
+        assert_eq!(res, Ok(()));
+        let shape = CircuitShape::new(domain.d1.size, N, 0, &constraints, BTreeMap::new());
+        let cost = estimate_verifier_cost(&shape, &config);
+        assert_eq!(cost.fq_sponge_permutations, fq_permutations);
+        assert_eq!(cost.fr_sponge_permutations, fr_permutations);
+    }
+
+    #[test]
+    fn test_transcript_profile() {
+        use crate::{
+            cost::{transcript_profile, CircuitShape},
+            mvlookup::LookupTableID,
+            transcript::{test_hooks, TranscriptProfile},
+            verifier::VerifierError,
+        };
+
+        const N: usize = 3;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+
+        // X2 = X0 * X1, with factors small enough for the three columns to be
+        // looked up twice in a table of 16 values
+        let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+        for _ in 0..domain_size {
+            let (a, b) = (rng.gen_range(0..4u64), rng.gen_range(0..4u64));
+            cols[0].push(Fp::from(a));
+            cols[1].push(Fp::from(b));
+            cols[2].push(Fp::from(a * b));
+        }
+        let (lookups, lookup) = column_lookups(domain, 1, 16, &cols, &[0, 1, 2, 0, 1, 2]);
+        let constraints = {
+            let x0 = expr::curr_cell::<Fp>(Column::X(0));
+            let x1 = expr::curr_cell::<Fp>(Column::X(1));
+            let x2 = expr::curr_cell::<Fp>(Column::X(2));
+            let mut constraints = vec![x2 - x0 * x1];
+            constraints.extend(constraint_lookups(&BTreeMap::from([(
+                LookupTableIDs::Custom(1),
+                lookups,
+            )])));
+            constraints
+        };
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new(cols),
+            },
+            mvlookups: vec![lookup],
+        };
+        // The prover checks its own transcript against the profile
+        let proof =
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs,
+                &mut rng,
+            )
+            .unwrap();
+
+        // The profile is part of the specification of the protocol, any
+        // change to it must be deliberate
+        let lookups = BTreeMap::from([(LookupTableIDs::Custom(1).to_u32(), 6)]);
+        let shape = CircuitShape::new(domain.d1.size, N, 0, &constraints, lookups);
+        assert_eq!(
+            transcript_profile(&shape, &ProtocolConfig::default()),
+            TranscriptProfile {
+                fq_points: 15,
+                fq_scalars: 0,
+                fq_squeezes: 5,
+                fr_scalars: 18,
+                fr_squeezes: 2,
+            }
+        );
+
+        let verify_proof = || {
+            verify::<_, OpeningProof, BaseSponge, ScalarSponge, N, 0, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                &proof,
+                Witness::zero_vec(domain_size),
+            )
+        };
+        assert!(verify_proof());
+
+        // A verifier absorbing one more scalar than the protocol prescribes
+        // is caught before the opening proof is checked
+        test_hooks::set_extra_absorption(true);
+        let res = VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+            domain,
+            &srs,
+            ProtocolConfig::default(),
+            &constraints,
+        )
+        .and_then(|ctx| {
+            ctx.verify::<BaseSponge, ScalarSponge, N, 0, _>(&proof, Witness::zero_vec(domain_size))
+        });
+        test_hooks::set_extra_absorption(false);
+        match res {
+            Err(VerifierError::TranscriptProfileMismatch { expected, got }) => {
+                assert_eq!(got.fq_scalars, expected.fq_scalars + 1);
+                assert_eq!(
+                    TranscriptProfile {
+                        fq_scalars: expected.fq_scalars,
+                        ..got
+                    },
+                    expected
+                );
+            }
+            res => panic!("unexpected result {res:?}"),
+        }
+        assert!(verify_proof());
+    }
+
+    #[test]
+    fn test_estimate_verifier_cost_claimed_aggregation() {
+        use crate::{
+            cost::{estimate_verifier_cost, CircuitShape},
+            mvlookup::{
+                constraint_lookups_with_config, LookupAggregationMode, LookupConstraintOptions,
+                LookupTableID,
+            },
+        };
+
+        // The last 6 columns are looked up
+        const N: usize = 9;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(1 << 6).unwrap();
+        let domain_size = domain.d1.size as usize;
+        let srs = setup_srs(domain);
+
+        // X2 = X0 * X1
+        let constraints = {
+            let x0 = expr::curr_cell::<Fp>(Column::X(0));
+            let x1 = expr::curr_cell::<Fp>(Column::X(1));
+            let x2 = expr::curr_cell::<Fp>(Column::X(2));
+            vec![x2 - x0 * x1]
+        };
+        let mut cols: [Vec<Fp>; N] = std::array::from_fn(|_| vec![]);
+        for _ in 0..domain_size {
+            let (a, b) = (Fp::rand(&mut rng), Fp::rand(&mut rng));
+            cols[0].push(a);
+            cols[1].push(b);
+            cols[2].push(a * b);
+            for col in cols[3..].iter_mut() {
+                col.push(Fp::from(rng.gen_range(0..16u64)));
+            }
+        }
+
+        let config = ProtocolConfig {
+            lookup_aggregation: LookupAggregationMode::Claimed,
+            ..Default::default()
+        };
+        for n_queries in [1, 6] {
+            let queried: Vec<usize> = (3..3 + n_queries).collect();
+            let (lookups, lookup) = column_lookups(domain, 1, 16, &cols, &queried);
+            let constraints: Vec<E<Fp>> = constraints
+                .iter()
+                .cloned()
+                .chain(constraint_lookups_with_config(
+                    &BTreeMap::from([(LookupTableIDs::Custom(1), lookups)]),
+                    &LookupConstraintOptions::of_config(&config),
+                ))
+                .collect();
+            let proof = ProverContext::new(domain, &srs, config.clone(), &constraints)
+                .and_then(|ctx| {
+                    prove_with_context::<
+                        _,
+                        OpeningProof,
+                        BaseSponge,
+                        ScalarSponge,
+                        _,
+                        N,
+                        LookupTableIDs,
+                        PointEvaluations<Fp>,
+                    >(
+                        &ctx,
+                        ProofInputs {
+                            evaluations: Witness {
+                                cols: Box::new(cols.clone()),
+                            },
+                            mvlookups: vec![lookup],
+                        },
+                        &mut rng,
+                    )
+                })
+                .unwrap();
+            let (res, fq_permutations, fr_permutations) = count_sponge_permutations(|| {
+                VerifierContext::<_, OpeningProof, LookupTableIDs>::new(
+                    domain,
+                    &srs,
+                    config.clone(),
+                    &constraints,
+                )
+                .and_then(|ctx| {
+                    ctx.verify::<CountingFqSponge, CountingFrSponge, N, 0, PointEvaluations<Fp>>(
+                        &proof,
+                        Witness::zero_vec(domain_size),
+                    )
+                })
+            });
+            assert_eq!(res, Ok(()));
+            let lookups = BTreeMap::from([(LookupTableIDs::Custom(1).to_u32(), n_queries)]);
+            let shape = CircuitShape::new(domain.d1.size, N, 0, &constraints, lookups);
+            let cost = estimate_verifier_cost(&shape, &config);
+            assert_eq!(cost.fq_sponge_permutations, fq_permutations, "{n_queries}");
+            assert_eq!(cost.fr_sponge_permutations, fr_permutations, "{n_queries}");
+        }
+    }
 }
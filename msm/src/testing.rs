@@ -0,0 +1,342 @@
+//! Tools for the tests of the circuits built with this crate.
+//!
+//! [shrink_failing] reduces the [ProofInputs] of a failing randomized test to
+//! a minimal witness on which the test still fails, which can be kept as a
+//! fixture with [save_inputs] and read back with [load_inputs].
+
+use ark_ff::{PrimeField, Zero};
+use kimchi::curve::KimchiCurve;
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    fixtures::{decode, encode, FixtureError},
+    mvlookup::{LookupTableID, MVLookup, MVLookupWitness},
+    proof::ProofInputs,
+};
+
+/// Reduce `inputs`, on which `predicate` holds, e.g. "the prover fails with
+/// this error", to smaller inputs on which it still holds. The reductions
+/// are tried in a fixed order, until none of them applies:
+/// - the witnesses of the lookup tables are removed;
+/// - the columns of looked-up values are removed;
+/// - the rows are blanked, by halves of the rows which are not blank yet;
+/// - the witness columns are set to zero.
+///
+/// A blank row is a padding row, like the rows added by [ProofInputs::pad]:
+/// its witness cells are zero, and its lookups are the no-op lookups of
+/// [MVLookup::noop]. The multiplicities of the removed lookups are removed
+/// from their fixed tables, so that the lookup witness stays consistent.
+/// The size of the witness does not change.
+///
+/// The reduction is deterministic, and `predicate` is called a number of
+/// times logarithmic in the number of rows when the failure comes from a
+/// single row.
+///
+/// # Panics
+///
+/// Panics if `predicate` does not hold on `inputs`.
+pub fn shrink_failing<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: ProofInputs<N, G, ID>,
+    mut predicate: impl FnMut(&ProofInputs<N, G, ID>) -> bool,
+) -> ProofInputs<N, G, ID> {
+    assert!(
+        predicate(&inputs),
+        "the predicate does not hold on the inputs to shrink"
+    );
+    let mut current = inputs;
+    let mut try_candidate = |current: &mut ProofInputs<N, G, ID>, candidate| {
+        let holds = predicate(&candidate);
+        if holds {
+            *current = candidate;
+        }
+        holds
+    };
+    loop {
+        let mut progress = false;
+
+        let mut i = 0;
+        while i < current.mvlookups.len() {
+            let mut candidate = current.clone();
+            candidate.mvlookups.remove(i);
+            if try_candidate(&mut current, candidate) {
+                progress = true;
+            } else {
+                i += 1;
+            }
+        }
+
+        for i in 0..current.mvlookups.len() {
+            // The last column is the column of the table
+            let mut column = 0;
+            while column + 1 < current.mvlookups[i].f.len() {
+                let mut candidate = current.clone();
+                remove_lookup_column(&mut candidate.mvlookups[i], column);
+                if try_candidate(&mut current, candidate) {
+                    progress = true;
+                } else {
+                    column += 1;
+                }
+            }
+        }
+
+        let n_rows = current.evaluations.cols.first().map_or(0, Vec::len);
+        let mut size = n_rows.next_power_of_two() / 2;
+        while size > 0 {
+            for start in (0..n_rows).step_by(size) {
+                let rows: Vec<usize> = (start..(start + size).min(n_rows))
+                    .filter(|j| !is_blank(&current, *j))
+                    .collect();
+                if rows.is_empty() {
+                    continue;
+                }
+                let mut candidate = current.clone();
+                blank_rows(&mut candidate, &rows);
+                progress |= try_candidate(&mut current, candidate);
+            }
+            size /= 2;
+        }
+
+        for i in 0..N {
+            if current.evaluations.cols[i].iter().all(Zero::is_zero) {
+                continue;
+            }
+            let mut candidate = current.clone();
+            candidate.evaluations.cols[i]
+                .iter_mut()
+                .for_each(|x| *x = G::ScalarField::zero());
+            progress |= try_candidate(&mut current, candidate);
+        }
+
+        if !progress {
+            return current;
+        }
+    }
+}
+
+/// Returns the rows of `inputs` which are not blank, see [shrink_failing]
+pub fn active_rows<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: &ProofInputs<N, G, ID>,
+) -> Vec<usize> {
+    let n_rows = inputs.evaluations.cols.first().map_or(0, Vec::len);
+    (0..n_rows).filter(|j| !is_blank(inputs, *j)).collect()
+}
+
+/// Write `inputs` to `path`, hex-encoded MessagePack like the inputs of the
+/// [crate::fixtures]
+pub fn save_inputs<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: &ProofInputs<N, G, ID>,
+    path: impl AsRef<Path>,
+) -> Result<(), FixtureError> {
+    std::fs::write(path, encode(inputs)? + "\n").map_err(|err| FixtureError::Io(err.to_string()))
+}
+
+/// Read the inputs written by [save_inputs]
+pub fn load_inputs<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    path: impl AsRef<Path>,
+) -> Result<ProofInputs<N, G, ID>, FixtureError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|err| FixtureError::Io(err.to_string()))?;
+    decode(contents.trim())
+}
+
+fn is_blank<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: &ProofInputs<N, G, ID>,
+    row: usize,
+) -> bool {
+    inputs
+        .evaluations
+        .cols
+        .iter()
+        .all(|column| column[row].is_zero())
+        && inputs.mvlookups.iter().all(|lookup| {
+            let queries = &lookup.f[..lookup.f.len().saturating_sub(1)];
+            queries.iter().all(|column| column[row].numerator.is_zero())
+        })
+}
+
+fn blank_rows<const N: usize, G: KimchiCurve, ID: LookupTableID>(
+    inputs: &mut ProofInputs<N, G, ID>,
+    rows: &[usize],
+) {
+    for column in inputs.evaluations.cols.iter_mut() {
+        for row in rows {
+            column[*row] = G::ScalarField::zero();
+        }
+    }
+    for lookup in inputs.mvlookups.iter_mut() {
+        for column in 0..lookup.f.len().saturating_sub(1) {
+            remove_lookups(lookup, column, rows.iter().copied());
+        }
+    }
+}
+
+fn remove_lookup_column<F: PrimeField, ID: LookupTableID>(
+    lookup: &mut MVLookupWitness<F, ID>,
+    column: usize,
+) {
+    remove_lookups(lookup, column, 0..lookup.f[column].len());
+    lookup.f.remove(column);
+}
+
+/// Replace the lookups of the rows `rows` of the column `column` of
+/// `lookup` with no-op lookups, removing their multiplicities from the fixed
+/// table of `lookup`, its last column
+fn remove_lookups<F: PrimeField, ID: LookupTableID>(
+    lookup: &mut MVLookupWitness<F, ID>,
+    column: usize,
+    rows: impl Iterator<Item = usize>,
+) {
+    let n_columns = lookup.f.len();
+    let (queries, table) = lookup.f.split_at_mut(n_columns - 1);
+    let table = &mut table[0];
+    let is_fixed = table
+        .first()
+        .map_or(false, |entry| entry.table_id.is_fixed());
+    // The rows of the table of each entry. A duplicated entry is removed
+    // from the rows where it is looked up first.
+    let mut table_rows: BTreeMap<(ID, &[F]), Vec<usize>> = BTreeMap::new();
+    let entries: Vec<(ID, Vec<F>)> = table
+        .iter()
+        .map(|entry| (entry.table_id, entry.value.clone()))
+        .collect();
+    if is_fixed {
+        for (i, (table_id, value)) in entries.iter().enumerate() {
+            table_rows.entry((*table_id, value)).or_default().push(i);
+        }
+    }
+    for j in rows {
+        let query = &mut queries[column][j];
+        if query.numerator.is_zero() {
+            continue;
+        }
+        let table_row = table_rows
+            .get(&(query.table_id, &query.value[..]))
+            .and_then(|rows| {
+                rows.iter()
+                    .find(|i| !lookup.m[**i].is_zero())
+                    .or(rows.first())
+                    .copied()
+            });
+        // A value out of the table does not have a multiplicity
+        if let Some(i) = table_row {
+            lookup.m[i] -= query.numerator;
+            table[i].numerator += query.numerator;
+        }
+        *query = MVLookup::noop(query.table_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        columns::Column, expr::curr_cell, lookups::LookupTableIDs, mvlookup::constraint_lookups,
+        prover::prove, witness::Witness, BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge,
+        BN254,
+    };
+    use ark_ff::{One, UniformRand};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+
+    #[test]
+    fn test_shrink_failing() {
+        // x[0] is the selector of the rows, x[1] and x[2] are looked up in
+        // the table of [0, 16), and x[3] = x[1] * x[2]
+        const N: usize = 4;
+        const TABLE_SIZE: usize = 16;
+        const DEFECT: usize = 700;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain_size = 1 << 10;
+        let domain = EvaluationDomains::<Fp>::create(domain_size).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), domain_size);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let table_id = LookupTableIDs::Custom(0);
+        let cell = |i| curr_cell::<Fp>(Column::X(i));
+        let lookups = BTreeMap::from([(
+            table_id,
+            vec![
+                MVLookup::new(table_id, cell(0), &[cell(1)]),
+                MVLookup::new(table_id, cell(0), &[cell(2)]),
+            ],
+        )]);
+        // The lookups are checked first
+        let mut constraints = constraint_lookups(&lookups);
+        constraints.push(cell(3) - cell(1) * cell(2));
+
+        let values: Vec<[u64; 2]> = (0..domain_size)
+            .map(|_| [rng.gen_range(0..16), rng.gen_range(0..16)])
+            .collect();
+        let mut m = vec![Fp::zero(); domain_size];
+        values
+            .iter()
+            .flatten()
+            .for_each(|x| m[*x as usize] += Fp::one());
+        let query = |k: usize| {
+            values
+                .iter()
+                .map(|value| MVLookup::new(table_id, Fp::one(), &[Fp::from(value[k])]))
+                .collect()
+        };
+        let table = (0..domain_size)
+            .map(|i| {
+                let value = if i < TABLE_SIZE { i as u64 } else { 0 };
+                MVLookup::new(table_id, -m[i], &[Fp::from(value)])
+            })
+            .collect();
+        let mut cols = vec![vec![Fp::one(); domain_size]];
+        cols.extend((0..2).map(|k| values.iter().map(|value| Fp::from(value[k])).collect()));
+        cols.push(
+            values
+                .iter()
+                .map(|value| Fp::from(value[0] * value[1]))
+                .collect(),
+        );
+        cols[3][DEFECT] += Fp::one();
+        let inputs = ProofInputs::<N, BN254G1Affine, LookupTableIDs> {
+            evaluations: Witness {
+                cols: Box::new(cols.try_into().unwrap()),
+            },
+            mvlookups: vec![MVLookupWitness {
+                f: vec![query(0), query(1), table],
+                m,
+            }],
+        };
+
+        let mut prove_rng = o1_utils::tests::make_test_rng();
+        let mut prover_error = |inputs: &ProofInputs<N, BN254G1Affine, LookupTableIDs>| {
+            prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, LookupTableIDs>(
+                domain,
+                &srs,
+                &constraints,
+                inputs.clone(),
+                &mut prove_rng,
+            )
+            .err()
+            .map(|err| err.to_string())
+        };
+        let error = prover_error(&inputs).unwrap();
+        assert!(error.contains("x[3]"), "{error}");
+
+        let mut n_calls = 0;
+        let shrunk = shrink_failing(inputs, |inputs| {
+            n_calls += 1;
+            prover_error(inputs).as_ref() == Some(&error)
+        });
+        // Only the row of the defect is left, with its lookups, whose
+        // multiplicities are the only ones left in the table
+        assert_eq!(active_rows(&shrunk), vec![DEFECT]);
+        let cols = &shrunk.evaluations.cols;
+        assert_ne!(cols[3][DEFECT], cols[1][DEFECT] * cols[2][DEFECT]);
+        assert_eq!(shrunk.mvlookups[0].m.iter().sum::<Fp>(), Fp::from(2u64));
+        assert!(n_calls < 100, "{n_calls}");
+        assert_eq!(prover_error(&shrunk), Some(error));
+
+        let path = std::env::temp_dir().join("kimchi_msm_shrunk_inputs.hex");
+        save_inputs(&shrunk, &path).unwrap();
+        let loaded: ProofInputs<N, BN254G1Affine, LookupTableIDs> = load_inputs(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(encode(&loaded).unwrap(), encode(&shrunk).unwrap());
+    }
+}
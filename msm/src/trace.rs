@@ -0,0 +1,419 @@
+//! Generation of the proof inputs from an execution trace, one row at a time.
+//!
+//! An interpreter loop, e.g. a VM executing one step per row, fills the
+//! witness columns and the lookups of each row through a [RowWriter] given to
+//! a callback, see [ProofInputs::from_step_fn]. The caller never holds the
+//! whole trace: the writer only holds the current row, and the values are
+//! moved into the columns of the proof inputs as soon as the row is complete.
+//! The cells which are not written are zero.
+//!
+//! The lookups go into the fixed tables of a [TraceLayout], with a fixed
+//! number of lookups per row and per table. The multiplicities of the tables
+//! are computed along the way.
+
+use ark_ff::{One, Zero};
+use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::{
+    mvlookup::{LookupTable, LookupTableID, MVLookup, MVLookupWitness},
+    proof::ProofInputs,
+    witness::Witness,
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TraceError {
+    #[error("the column {column} is written twice in the row {row}")]
+    DoubleWrite { row: usize, column: usize },
+
+    #[error("the row {row} writes the column {column}, which is not in the witness")]
+    UnknownColumn { row: usize, column: usize },
+
+    #[error("the row {row} looks up into the table {table_id}, which is not in the layout")]
+    UnknownTable { row: usize, table_id: u32 },
+
+    #[error("the row {row} has more lookups into the table {table_id} than the layout allows")]
+    TooManyLookups { row: usize, table_id: u32 },
+
+    #[error("the row {row} looks up a value which is not in the table {table_id}")]
+    NotInTable { row: usize, table_id: u32 },
+
+    #[error("the table {0} is declared twice in the layout")]
+    DuplicateTable(u32),
+
+    #[error("the table {0} has more entries than the domain has rows")]
+    TableTooLarge(u32),
+}
+
+/// The fixed lookup tables a trace looks up into, with the number of lookups
+/// into each of them per row.
+#[derive(Debug, Clone)]
+pub struct TraceLayout<F, ID: LookupTableID> {
+    tables: Vec<(LookupTable<F, ID>, usize)>,
+}
+
+impl<F, ID: LookupTableID> Default for TraceLayout<F, ID> {
+    fn default() -> Self {
+        TraceLayout { tables: vec![] }
+    }
+}
+
+impl<F, ID: LookupTableID> TraceLayout<F, ID> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `table`, with at most `lookups_per_row` lookups into it per
+    /// row. The lookups a row does not make are no-op lookups, see
+    /// [MVLookup::noop]: the lookup constraints must not query them, e.g.
+    /// with a numerator which is zero on these rows.
+    pub fn with_table(mut self, table: LookupTable<F, ID>, lookups_per_row: usize) -> Self {
+        self.tables.push((table, lookups_per_row));
+        self
+    }
+}
+
+/// The cells and the lookups of the current row of a trace, see
+/// [ProofInputs::from_step_fn]. The first error is recorded and returned once
+/// the row is complete, such that the step function does not have to handle
+/// it.
+pub struct RowWriter<F, ID: LookupTableID> {
+    row: usize,
+    cells: Vec<Option<F>>,
+    /// The tables of the layout, with the values looked up into them in the
+    /// current row
+    lookups: Vec<(ID, usize, Vec<Vec<F>>)>,
+    error: Option<TraceError>,
+}
+
+impl<F: Copy, ID: LookupTableID> RowWriter<F, ID> {
+    fn new(n_columns: usize, layout: &TraceLayout<F, ID>) -> Self {
+        RowWriter {
+            row: 0,
+            cells: vec![None; n_columns],
+            lookups: layout
+                .tables
+                .iter()
+                .map(|(table, lookups_per_row)| {
+                    (
+                        table.table_id,
+                        *lookups_per_row,
+                        Vec::with_capacity(*lookups_per_row),
+                    )
+                })
+                .collect(),
+            error: None,
+        }
+    }
+
+    /// Start the row `row`, all the cells being unset
+    fn reset(&mut self, row: usize) {
+        self.row = row;
+        self.cells.iter_mut().for_each(|cell| *cell = None);
+        self.lookups
+            .iter_mut()
+            .for_each(|(_, _, values)| values.clear());
+    }
+
+    fn fail(&mut self, error: TraceError) {
+        self.error.get_or_insert(error);
+    }
+
+    /// The index of the current row
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Set the cell of the column `col` in the current row to `value`
+    pub fn set(&mut self, col: usize, value: F) {
+        let row = self.row;
+        match self.cells.get_mut(col) {
+            Some(Some(_)) => self.fail(TraceError::DoubleWrite { row, column: col }),
+            Some(cell) => *cell = Some(value),
+            None => self.fail(TraceError::UnknownColumn { row, column: col }),
+        }
+    }
+
+    /// Look up `values` into the table `table` in the current row
+    pub fn lookup(&mut self, table: ID, values: &[F]) {
+        let row = self.row;
+        let table_id = table.to_u32();
+        match self.lookups.iter_mut().find(|(id, _, _)| *id == table) {
+            Some((_, lookups_per_row, row_values)) if row_values.len() < *lookups_per_row => {
+                row_values.push(values.to_vec())
+            }
+            Some(_) => self.fail(TraceError::TooManyLookups { row, table_id }),
+            None => self.fail(TraceError::UnknownTable { row, table_id }),
+        }
+    }
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofInputs<N, G, ID> {
+    /// Build the proof inputs of the trace produced by `step`, which is
+    /// called on each row of the domain in order, see the [module
+    /// documentation](crate::trace). Fails on the first row which writes a
+    /// cell twice, or whose lookups do not match `layout`.
+    pub fn from_step_fn(
+        domain: EvaluationDomains<G::ScalarField>,
+        layout: &TraceLayout<G::ScalarField, ID>,
+        mut step: impl FnMut(usize, &mut RowWriter<G::ScalarField, ID>),
+    ) -> Result<Self, TraceError> {
+        let domain_size = domain.d1.size as usize;
+        // The row of the first occurrence of each entry, per table
+        let mut indices: Vec<HashMap<&[G::ScalarField], usize>> =
+            Vec::with_capacity(layout.tables.len());
+        for (i, (table, _)) in layout.tables.iter().enumerate() {
+            let table_id = table.table_id.to_u32();
+            if layout.tables[..i]
+                .iter()
+                .any(|(other, _)| other.table_id == table.table_id)
+            {
+                return Err(TraceError::DuplicateTable(table_id));
+            }
+            if table.entries.len() > domain_size {
+                return Err(TraceError::TableTooLarge(table_id));
+            }
+            let mut index = HashMap::with_capacity(table.entries.len());
+            for (row, entry) in table.entries.iter().enumerate() {
+                index.entry(entry.as_slice()).or_insert(row);
+            }
+            indices.push(index);
+        }
+
+        let mut cols: Box<[Vec<G::ScalarField>; N]> =
+            Box::new(std::array::from_fn(|_| Vec::with_capacity(domain_size)));
+        let mut mvlookups: Vec<MVLookupWitness<G::ScalarField, ID>> = layout
+            .tables
+            .iter()
+            .map(|(_, lookups_per_row)| MVLookupWitness {
+                f: (0..*lookups_per_row)
+                    .map(|_| Vec::with_capacity(domain_size))
+                    .collect(),
+                m: vec![G::ScalarField::zero(); domain_size],
+            })
+            .collect();
+
+        let mut writer = RowWriter::new(N, layout);
+        for row in 0..domain_size {
+            writer.reset(row);
+            step(row, &mut writer);
+            if let Some(error) = writer.error.take() {
+                return Err(error);
+            }
+            for (col, cell) in cols.iter_mut().zip(writer.cells.iter()) {
+                col.push(cell.unwrap_or_else(G::ScalarField::zero));
+            }
+            for ((witness, index), (table_id, _, values)) in mvlookups
+                .iter_mut()
+                .zip(indices.iter())
+                .zip(writer.lookups.iter_mut())
+            {
+                let mut values = values.drain(..);
+                for column in witness.f.iter_mut() {
+                    match values.next() {
+                        Some(value) => {
+                            let i = *index.get(value.as_slice()).ok_or(TraceError::NotInTable {
+                                row,
+                                table_id: table_id.to_u32(),
+                            })?;
+                            witness.m[i] += G::ScalarField::one();
+                            column.push(MVLookup::new(*table_id, G::ScalarField::one(), &value));
+                        }
+                        None => column.push(MVLookup::noop(*table_id)),
+                    }
+                }
+            }
+        }
+
+        // The tables, with a negative sign, are the last column of each
+        // lookup witness. They are padded with zero entries, which are not
+        // looked up.
+        for (witness, (table, _)) in mvlookups.iter_mut().zip(layout.tables.iter()) {
+            let width = table.entries.first().map_or(1, |entry| entry.len());
+            let padding = vec![G::ScalarField::zero(); width];
+            witness.f.push(
+                (0..domain_size)
+                    .map(|i| {
+                        let entry = table.entries.get(i).unwrap_or(&padding);
+                        MVLookup::new(table.table_id, -witness.m[i], entry)
+                    })
+                    .collect(),
+            );
+        }
+
+        Ok(ProofInputs {
+            evaluations: Witness { cols },
+            mvlookups,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TraceError, TraceLayout};
+    use crate::{
+        columns::Column,
+        config::ProtocolConfig,
+        expr::{curr_cell, E},
+        lookups::LookupTableIDs,
+        mvlookup::{constraint_lookups, LookupTable, MVLookup},
+        proof::ProofInputs,
+        prover::{prove_with_context, ProverContext, ProverError},
+        verifier::VerifierContext,
+        witness::Witness,
+        BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand};
+    use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+    use std::collections::BTreeMap;
+
+    const DOMAIN_SIZE: usize = 1 << 5;
+    const N: usize = 3;
+
+    /// A counter, incremented by a bit at each step. Each row holds the
+    /// counter, the increment, which is looked up in the table of the bits,
+    /// and the next counter.
+    fn counter_vm() -> (TraceLayout<Fp, LookupTableIDs>, Vec<E<Fp>>) {
+        let layout = TraceLayout::new().with_table(
+            LookupTable {
+                table_id: LookupTableIDs::Bit,
+                entries: vec![vec![Fp::from(0u64)], vec![Fp::from(1u64)]],
+            },
+            1,
+        );
+        let mut constraints =
+            vec![curr_cell(Column::X(2)) - curr_cell(Column::X(0)) - curr_cell(Column::X(1))];
+        constraints.extend(constraint_lookups(&BTreeMap::from([(
+            LookupTableIDs::Bit,
+            vec![MVLookup {
+                table_id: LookupTableIDs::Bit,
+                numerator: E::one(),
+                value: vec![curr_cell(Column::X(1))],
+            }],
+        )])));
+        (layout, constraints)
+    }
+
+    #[test]
+    fn test_trace_counter_vm() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), DOMAIN_SIZE);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        let (layout, constraints) = counter_vm();
+        let config = ProtocolConfig::default();
+
+        // The state of the VM is only the counter
+        let mut counter = 0u64;
+        let mut increments = rng.clone();
+        let inputs: ProofInputs<N, _, LookupTableIDs> =
+            ProofInputs::from_step_fn(domain, &layout, |_, row| {
+                let increment = increments.gen_range(0..2u64);
+                row.set(0, Fp::from(counter));
+                row.set(1, Fp::from(increment));
+                row.lookup(LookupTableIDs::Bit, &[Fp::from(increment)]);
+                counter += increment;
+                row.set(2, Fp::from(counter));
+            })
+            .unwrap();
+        assert_eq!(
+            inputs.evaluations.cols[2][DOMAIN_SIZE - 1],
+            Fp::from(counter)
+        );
+
+        let ctx = ProverContext::new(domain, &srs, config, &constraints).unwrap();
+        let proof = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng)
+        .unwrap();
+        let res = VerifierContext::from_prover_context(&ctx)
+            .unwrap()
+            .verify::<BaseSponge, ScalarSponge, N, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(DOMAIN_SIZE),
+            );
+        assert!(res.is_ok(), "{res:?}");
+
+        // A step which does not increment the counter in the last column
+        let inputs: ProofInputs<N, _, LookupTableIDs> =
+            ProofInputs::from_step_fn(domain, &layout, |i, row| {
+                row.set(0, Fp::from(i as u64));
+                row.set(1, Fp::one());
+                row.lookup(LookupTableIDs::Bit, &[Fp::one()]);
+            })
+            .unwrap();
+        let res = prove_with_context::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            _,
+            N,
+            LookupTableIDs,
+            PointEvaluations<Fp>,
+        >(&ctx, inputs, &mut rng);
+        assert!(matches!(res, Err(ProverError::ConstraintNotSatisfied(_))));
+    }
+
+    #[test]
+    fn test_trace_errors() {
+        let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+        let (layout, _) = counter_vm();
+        let from_step_fn = |step: &dyn Fn(usize, &mut super::RowWriter<Fp, LookupTableIDs>)| {
+            ProofInputs::<N, crate::BN254G1Affine, LookupTableIDs>::from_step_fn(
+                domain, &layout, step,
+            )
+            .map(|_| ())
+        };
+
+        assert_eq!(
+            from_step_fn(&|i, row| {
+                row.set(0, Fp::one());
+                if i == 3 {
+                    row.set(0, Fp::one());
+                }
+            }),
+            Err(TraceError::DoubleWrite { row: 3, column: 0 })
+        );
+        assert_eq!(
+            from_step_fn(&|_, row| row.set(N, Fp::one())),
+            Err(TraceError::UnknownColumn { row: 0, column: N })
+        );
+        assert_eq!(
+            from_step_fn(&|_, row| {
+                row.lookup(LookupTableIDs::Bit, &[Fp::one()]);
+                row.lookup(LookupTableIDs::Bit, &[Fp::one()]);
+            }),
+            Err(TraceError::TooManyLookups {
+                row: 0,
+                table_id: 2
+            })
+        );
+        assert_eq!(
+            from_step_fn(&|_, row| row.lookup(LookupTableIDs::RangeCheck16, &[Fp::one()])),
+            Err(TraceError::UnknownTable {
+                row: 0,
+                table_id: 1
+            })
+        );
+        assert_eq!(
+            from_step_fn(&|i, row| row.lookup(LookupTableIDs::Bit, &[Fp::from(i as u64)])),
+            Err(TraceError::NotInTable {
+                row: 2,
+                table_id: 2
+            })
+        );
+        // The rows which do not write a cell or make a lookup are valid
+        assert_eq!(from_step_fn(&|_, _| {}), Ok(()));
+    }
+}
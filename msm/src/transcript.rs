@@ -0,0 +1,392 @@
+//! The Fiat-Shamir transcript of the prover and of the verifier.
+//!
+//! By default, the challenges are coined by a sponge over the base field of
+//! the curve, the `EFqSponge` given to the prover and to the verifier. A
+//! recursive verifier working over the scalar field can instead select
+//! [TranscriptBackend::ScalarSponge] in the
+//! [crate::config::ProtocolConfig]: the commitments are then hashed, using
+//! their compressed canonical serialization, into a Poseidon sponge over the
+//! scalar field.
+//!
+//! Both backends are wrapped in a [Transcript], which implements [FqSponge].
+//! The prover and the verifier absorb the same messages in the same order
+//! whatever the backend is, as the absorption schedule is written once
+//! against the [FqSponge] interface.
+//!
+//! The number of messages absorbed and of challenges squeezed is part of the
+//! protocol, a recursive verifier replaying the transcript in a circuit, see
+//! [TranscriptProfile]. The prover and the verifier count them with
+//! [CountingSponge] and [CountingFrSponge], and check them against the
+//! profile expected from the shape of the proof in strict mode, i.e. in the
+//! debug builds or with the feature `strict-transcript`.
+
+use ark_ff::{Field, PrimeField};
+use ark_serialize::CanonicalSerialize;
+use kimchi::{curve::KimchiCurve, plonk_sponge::FrSponge, proof::ProofEvaluations};
+use mina_poseidon::{
+    constants::PlonkSpongeConstantsKimchi,
+    poseidon::{ArithmeticSponge, ArithmeticSpongeParams, Sponge},
+    sponge::{FqSponge, ScalarChallenge, CHALLENGE_LENGTH_IN_LIMBS},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// The sponge coining the challenges of the protocol, see [Transcript].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TranscriptBackend {
+    /// The `EFqSponge` given to the prover and to the verifier, over the base
+    /// field of the curve.
+    #[default]
+    FqSponge,
+    /// A Poseidon sponge over the scalar field. The points are absorbed as
+    /// their compressed canonical serialization, packed into scalar field
+    /// elements.
+    ScalarSponge,
+}
+
+/// A Poseidon sponge over the scalar field `F`, absorbing the points as
+/// bytes, see [TranscriptBackend::ScalarSponge].
+#[derive(Clone)]
+pub struct ScalarFieldSponge<F: PrimeField> {
+    sponge: ArithmeticSponge<F, PlonkSpongeConstantsKimchi>,
+    last_squeezed: Vec<u64>,
+}
+
+impl<F: PrimeField> ScalarFieldSponge<F> {
+    pub fn new(params: &'static ArithmeticSpongeParams<F>) -> Self {
+        ScalarFieldSponge {
+            sponge: ArithmeticSponge::new(params),
+            last_squeezed: vec![],
+        }
+    }
+
+    pub fn absorb(&mut self, x: &[F]) {
+        self.last_squeezed = vec![];
+        self.sponge.absorb(x);
+    }
+
+    /// Absorb `bytes`, packed in chunks small enough to be elements of `F`.
+    /// The messages of the protocol have a fixed size, hence the length is
+    /// not absorbed.
+    pub fn absorb_bytes(&mut self, bytes: &[u8]) {
+        let chunk_size = (F::size_in_bits() - 1) / 8;
+        let elems: Vec<F> = bytes
+            .chunks(chunk_size)
+            .map(F::from_le_bytes_mod_order)
+            .collect();
+        self.absorb(&elems);
+    }
+
+    /// Absorb the canonical serialization of `x`, compressed for points.
+    pub fn absorb_serializable<T: CanonicalSerialize>(&mut self, x: &T) {
+        let mut bytes = vec![];
+        x.serialize(&mut bytes)
+            .expect("the serialization in a vector does not fail");
+        self.absorb_bytes(&bytes);
+    }
+
+    /// Squeeze a challenge of `CHALLENGE_LENGTH_IN_LIMBS` limbs, like the
+    /// challenges of the base field sponge.
+    pub fn challenge(&mut self) -> F {
+        if self.last_squeezed.len() < CHALLENGE_LENGTH_IN_LIMBS {
+            let x = self.sponge.squeeze().into_repr();
+            self.last_squeezed
+                .extend(&x.as_ref()[0..CHALLENGE_LENGTH_IN_LIMBS]);
+        }
+        let mut repr = F::BigInt::default();
+        repr.as_mut()[0..CHALLENGE_LENGTH_IN_LIMBS]
+            .copy_from_slice(&self.last_squeezed[0..CHALLENGE_LENGTH_IN_LIMBS]);
+        self.last_squeezed.drain(0..CHALLENGE_LENGTH_IN_LIMBS);
+        F::from_repr(repr).expect("a challenge is smaller than the modulus")
+    }
+
+    /// Squeeze a full field element
+    pub fn squeeze(&mut self) -> F {
+        self.last_squeezed = vec![];
+        self.sponge.squeeze()
+    }
+}
+
+/// Reduce an element of the scalar field into the base field
+fn to_base_field<Fr: PrimeField, Fq: Field>(x: Fr) -> Fq {
+    let shift = Fq::from(1u128 << 64);
+    x.into_repr()
+        .as_ref()
+        .iter()
+        .rev()
+        .fold(Fq::zero(), |acc, limb| acc * shift + Fq::from(*limb))
+}
+
+/// The sponge of the transcript, selected by a [TranscriptBackend].
+/// [FqSponge::new] creates a [TranscriptBackend::FqSponge] transcript, which
+/// is the same as using `EFqSponge` directly.
+#[derive(Clone)]
+pub enum Transcript<G: KimchiCurve, EFqSponge> {
+    FqSponge(EFqSponge),
+    ScalarSponge(ScalarFieldSponge<G::ScalarField>),
+}
+
+impl<G: KimchiCurve, EFqSponge: FqSponge<G::BaseField, G, G::ScalarField>>
+    Transcript<G, EFqSponge>
+{
+    /// A new transcript using `backend`
+    pub fn with_backend(backend: TranscriptBackend) -> Self {
+        match backend {
+            TranscriptBackend::FqSponge => {
+                Transcript::FqSponge(EFqSponge::new(G::other_curve_sponge_params()))
+            }
+            TranscriptBackend::ScalarSponge => {
+                Transcript::ScalarSponge(ScalarFieldSponge::new(G::sponge_params()))
+            }
+        }
+    }
+
+    pub fn backend(&self) -> TranscriptBackend {
+        match self {
+            Transcript::FqSponge(_) => TranscriptBackend::FqSponge,
+            Transcript::ScalarSponge(_) => TranscriptBackend::ScalarSponge,
+        }
+    }
+}
+
+impl<G: KimchiCurve, EFqSponge: FqSponge<G::BaseField, G, G::ScalarField>>
+    FqSponge<G::BaseField, G, G::ScalarField> for Transcript<G, EFqSponge>
+{
+    fn new(params: &'static ArithmeticSpongeParams<G::BaseField>) -> Self {
+        Transcript::FqSponge(EFqSponge::new(params))
+    }
+
+    fn absorb_fq(&mut self, x: &[G::BaseField]) {
+        match self {
+            Transcript::FqSponge(sponge) => sponge.absorb_fq(x),
+            Transcript::ScalarSponge(sponge) => {
+                x.iter().for_each(|x| sponge.absorb_serializable(x))
+            }
+        }
+    }
+
+    fn absorb_g(&mut self, g: &[G]) {
+        match self {
+            Transcript::FqSponge(sponge) => sponge.absorb_g(g),
+            Transcript::ScalarSponge(sponge) => {
+                g.iter().for_each(|g| sponge.absorb_serializable(g))
+            }
+        }
+    }
+
+    fn absorb_fr(&mut self, x: &[G::ScalarField]) {
+        match self {
+            Transcript::FqSponge(sponge) => sponge.absorb_fr(x),
+            Transcript::ScalarSponge(sponge) => sponge.absorb(x),
+        }
+    }
+
+    fn challenge_fq(&mut self) -> G::BaseField {
+        match self {
+            Transcript::FqSponge(sponge) => sponge.challenge_fq(),
+            Transcript::ScalarSponge(sponge) => to_base_field(sponge.squeeze()),
+        }
+    }
+
+    fn challenge(&mut self) -> G::ScalarField {
+        match self {
+            Transcript::FqSponge(sponge) => sponge.challenge(),
+            Transcript::ScalarSponge(sponge) => sponge.challenge(),
+        }
+    }
+
+    fn digest_fq(self) -> G::BaseField {
+        match self {
+            Transcript::FqSponge(sponge) => sponge.digest_fq(),
+            Transcript::ScalarSponge(mut sponge) => to_base_field(sponge.squeeze()),
+        }
+    }
+
+    fn digest(self) -> G::ScalarField {
+        match self {
+            Transcript::FqSponge(sponge) => sponge.digest(),
+            Transcript::ScalarSponge(mut sponge) => sponge.squeeze(),
+        }
+    }
+}
+
+/// The number of operations performed on the sponges of the transcript, up
+/// to the opening proof, as seen through the [FqSponge] and [FrSponge]
+/// interfaces. The opening proof depends on the polynomial commitment scheme,
+/// and is not covered.
+/// The profile of a circuit is given by [crate::cost::transcript_profile].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptProfile {
+    /// The number of points absorbed by the sponge over the base field, one
+    /// per chunk of each commitment
+    pub fq_points: usize,
+    /// The number of scalars absorbed by the sponge over the base field
+    pub fq_scalars: usize,
+    /// The number of challenges squeezed from the sponge over the base field,
+    /// including its digest, given to the sponge over the scalar field
+    pub fq_squeezes: usize,
+    /// The number of scalars absorbed by the sponge over the scalar field
+    pub fr_scalars: usize,
+    /// The number of challenges squeezed from the sponge over the scalar
+    /// field
+    pub fr_squeezes: usize,
+}
+
+/// Whether the prover and the verifier check the operations performed on
+/// their sponges against the expected [TranscriptProfile]
+pub(crate) const STRICT_TRANSCRIPT: bool =
+    cfg!(any(debug_assertions, test, feature = "strict-transcript"));
+
+/// The counts of a [TranscriptProfile], shared by the sponges of a transcript
+/// and their clones. The counter is stopped before the opening proof, see
+/// [TranscriptCounter::stop].
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptCounter(Arc<Mutex<(TranscriptProfile, bool)>>);
+
+impl TranscriptCounter {
+    fn record(&self, f: impl FnOnce(&mut TranscriptProfile)) {
+        let mut counter = self.0.lock().unwrap();
+        if !counter.1 {
+            f(&mut counter.0)
+        }
+    }
+
+    /// Stop counting the operations, e.g. the ones of the opening proof
+    pub fn stop(&self) {
+        self.0.lock().unwrap().1 = true;
+    }
+
+    /// The operations counted so far
+    pub fn profile(&self) -> TranscriptProfile {
+        self.0.lock().unwrap().0
+    }
+}
+
+/// A sponge over the base field counting its operations in a
+/// [TranscriptCounter]
+#[derive(Clone)]
+pub struct CountingSponge<S> {
+    sponge: S,
+    counter: TranscriptCounter,
+}
+
+impl<S> CountingSponge<S> {
+    pub fn new(sponge: S, counter: TranscriptCounter) -> Self {
+        CountingSponge { sponge, counter }
+    }
+
+    pub fn counter(&self) -> &TranscriptCounter {
+        &self.counter
+    }
+}
+
+impl<G: KimchiCurve, S: FqSponge<G::BaseField, G, G::ScalarField>>
+    FqSponge<G::BaseField, G, G::ScalarField> for CountingSponge<S>
+{
+    fn new(params: &'static ArithmeticSpongeParams<G::BaseField>) -> Self {
+        CountingSponge::new(S::new(params), TranscriptCounter::default())
+    }
+
+    fn absorb_fq(&mut self, x: &[G::BaseField]) {
+        self.counter.record(|profile| profile.fq_scalars += x.len());
+        self.sponge.absorb_fq(x)
+    }
+
+    fn absorb_g(&mut self, g: &[G]) {
+        self.counter.record(|profile| profile.fq_points += g.len());
+        self.sponge.absorb_g(g)
+    }
+
+    fn absorb_fr(&mut self, x: &[G::ScalarField]) {
+        self.counter.record(|profile| profile.fq_scalars += x.len());
+        self.sponge.absorb_fr(x)
+    }
+
+    fn challenge_fq(&mut self) -> G::BaseField {
+        self.counter.record(|profile| profile.fq_squeezes += 1);
+        self.sponge.challenge_fq()
+    }
+
+    fn challenge(&mut self) -> G::ScalarField {
+        self.counter.record(|profile| profile.fq_squeezes += 1);
+        self.sponge.challenge()
+    }
+
+    fn digest_fq(self) -> G::BaseField {
+        self.counter.record(|profile| profile.fq_squeezes += 1);
+        self.sponge.digest_fq()
+    }
+
+    fn digest(self) -> G::ScalarField {
+        self.counter.record(|profile| profile.fq_squeezes += 1);
+        self.sponge.digest()
+    }
+}
+
+/// A sponge over the scalar field counting its operations in a
+/// [TranscriptCounter]
+pub struct CountingFrSponge<S> {
+    sponge: S,
+    counter: TranscriptCounter,
+}
+
+impl<S> CountingFrSponge<S> {
+    pub fn new(sponge: S, counter: TranscriptCounter) -> Self {
+        CountingFrSponge { sponge, counter }
+    }
+}
+
+impl<F: Field, S: FrSponge<F>> FrSponge<F> for CountingFrSponge<S> {
+    fn new(params: &'static ArithmeticSpongeParams<F>) -> Self {
+        CountingFrSponge::new(S::new(params), TranscriptCounter::default())
+    }
+
+    fn absorb(&mut self, x: &F) {
+        self.counter.record(|profile| profile.fr_scalars += 1);
+        self.sponge.absorb(x)
+    }
+
+    fn absorb_multiple(&mut self, x: &[F]) {
+        self.counter.record(|profile| profile.fr_scalars += x.len());
+        self.sponge.absorb_multiple(x)
+    }
+
+    fn challenge(&mut self) -> ScalarChallenge<F> {
+        self.counter.record(|profile| profile.fr_squeezes += 1);
+        self.sponge.challenge()
+    }
+
+    fn digest(self) -> F {
+        self.counter.record(|profile| profile.fr_squeezes += 1);
+        self.sponge.digest()
+    }
+
+    fn absorb_evaluations(
+        &mut self,
+        e: &ProofEvaluations<kimchi::proof::PointEvaluations<Vec<F>>>,
+    ) {
+        // Not used by the protocol, the evaluations are absorbed one by one
+        self.sponge.absorb_evaluations(e)
+    }
+}
+
+/// The hooks deviating from the protocol, to test the checks of the
+/// transcript
+#[cfg(test)]
+pub(crate) mod test_hooks {
+    use std::cell::Cell;
+
+    thread_local! {
+        static EXTRA_ABSORPTION: Cell<bool> = Cell::new(false);
+    }
+
+    /// Make the verifier of the current thread absorb an extra scalar before
+    /// coining `α`
+    pub fn set_extra_absorption(enabled: bool) {
+        EXTRA_ABSORPTION.with(|extra| extra.set(enabled))
+    }
+
+    pub fn extra_absorption() -> bool {
+        EXTRA_ABSORPTION.with(|extra| extra.get())
+    }
+}
@@ -0,0 +1,191 @@
+//! Caching of the outcome of the verification of proofs, for verifiers
+//! receiving the same proofs several times, e.g. on retries.
+//!
+//! The outcome is indexed by a [CacheKey], made of digests of the circuit, of
+//! the public inputs and of the whole proof. The digest of the circuit is the
+//! one of its verifier context, see
+//! [crate::verifier::VerifierContext::digest], which covers the domain, the
+//! protocol configuration, the constraints, the global tables and the SRS,
+//! so that a cache can be shared by the verifiers of several circuits. The
+//! digest of the proof is computed on its serialization, which encodes the
+//! field and group elements canonically, and covers the commitments, the
+//! evaluations and the opening proof. An outcome is only inserted by
+//! [crate::verifier::VerifierContext::verify_with_cache] after the proof has
+//! been verified, it can not be given by the sender of the proof.
+
+use ark_ff::PrimeField;
+use blake2::{Blake2b512, Digest};
+use kimchi::curve::KimchiCurve;
+use poly_commitment::OpenProof;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use crate::{
+    mvlookup::LookupTableID,
+    proof::{EvaluationContainer, Proof, StableHash},
+    verifier::VerifierError,
+    witness::Witness,
+};
+
+/// The outcome of the verification of a proof
+pub type VerificationOutcome = Result<(), VerifierError>;
+
+/// The index of the outcome of a verification, see the module documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CacheKey {
+    /// The digest of the circuit, see [crate::verifier::VerifierContext::digest]
+    pub circuit: [u8; 32],
+    pub public_inputs: [u8; 32],
+    pub proof: [u8; 32],
+}
+
+fn digest(bytes: impl AsRef<[u8]>) -> [u8; 32] {
+    let mut digest = [0; 32];
+    digest.copy_from_slice(&Blake2b512::digest(bytes.as_ref())[..32]);
+    digest
+}
+
+/// The digest of the columns of public inputs, each one preceded by its
+/// length
+fn digest_public_inputs<F: PrimeField, const NPUB: usize>(
+    public_inputs: &Witness<NPUB, Vec<F>>,
+) -> [u8; 32] {
+    let mut bytes = vec![];
+    for column in public_inputs.cols.iter() {
+        bytes.extend((column.len() as u64).to_le_bytes());
+        for x in column {
+            x.serialize(&mut bytes).unwrap();
+        }
+    }
+    digest(bytes)
+}
+
+impl CacheKey {
+    pub fn new<
+        const N: usize,
+        const NPUB: usize,
+        G: KimchiCurve,
+        OpeningProof: OpenProof<G> + Serialize,
+        ID: LookupTableID,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        circuit: [u8; 32],
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: &Witness<NPUB, Vec<G::ScalarField>>,
+    ) -> Self {
+        CacheKey {
+            circuit,
+            public_inputs: digest_public_inputs(public_inputs),
+            proof: proof.stable_hash(),
+        }
+    }
+}
+
+/// A store of the outcomes of verifications. The methods take `&self` so that
+/// the cache can be shared by the threads of a verifier.
+pub trait VerificationCache {
+    /// Returns the outcome of the verification indexed by `key`, if known
+    fn get(&self, key: &CacheKey) -> Option<VerificationOutcome>;
+
+    /// Record the outcome of a complete verification
+    fn insert(&self, key: CacheKey, outcome: VerificationOutcome);
+}
+
+/// A [VerificationCache] in memory, keeping the `capacity` most recently used
+/// outcomes.
+#[derive(Debug)]
+pub struct LruVerificationCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    /// The outcomes, with the time of their last use
+    outcomes: HashMap<CacheKey, (u64, VerificationOutcome)>,
+    /// The keys, by time of last use
+    recency: BTreeMap<u64, CacheKey>,
+    time: u64,
+}
+
+impl LruState {
+    fn touch(&mut self, key: &CacheKey) -> Option<VerificationOutcome> {
+        let time = self.time;
+        let (last_use, outcome) = self.outcomes.get_mut(key)?;
+        self.recency.remove(last_use);
+        self.recency.insert(time, *key);
+        *last_use = time;
+        self.time += 1;
+        Some(outcome.clone())
+    }
+}
+
+impl LruVerificationCache {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "the cache must hold at least one outcome");
+        LruVerificationCache {
+            capacity,
+            state: Mutex::new(LruState::default()),
+        }
+    }
+
+    /// The number of outcomes in the cache
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().outcomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl VerificationCache for LruVerificationCache {
+    fn get(&self, key: &CacheKey) -> Option<VerificationOutcome> {
+        self.state.lock().unwrap().touch(key)
+    }
+
+    fn insert(&self, key: CacheKey, outcome: VerificationOutcome) {
+        let mut state = self.state.lock().unwrap();
+        if state.touch(&key).is_some() {
+            // The outcome of a verification is deterministic
+            return;
+        }
+        if state.outcomes.len() == self.capacity {
+            if let Some((_, oldest)) = state.recency.pop_first() {
+                state.outcomes.remove(&oldest);
+            }
+        }
+        let time = state.time;
+        state.outcomes.insert(key, (time, outcome));
+        state.recency.insert(time, key);
+        state.time += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheKey, LruVerificationCache, VerificationCache};
+    use crate::verifier::VerifierError;
+
+    #[test]
+    fn test_lru_eviction() {
+        let key = |i: u8| CacheKey {
+            circuit: [0; 32],
+            public_inputs: [0; 32],
+            proof: [i; 32],
+        };
+        let cache = LruVerificationCache::new(2);
+        cache.insert(key(0), Ok(()));
+        cache.insert(key(1), Err(VerifierError::OpeningProofFailed));
+        // The first outcome is used again, the second one is evicted
+        assert_eq!(cache.get(&key(0)), Some(Ok(())));
+        cache.insert(key(2), Ok(()));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key(1)), None);
+        assert_eq!(cache.get(&key(0)), Some(Ok(())));
+        assert_eq!(cache.get(&key(2)), Some(Ok(())));
+    }
+}
@@ -1,5 +1,9 @@
-use crate::mvlookup::LookupTableID;
-use ark_ff::{Field, One, Zero};
+use crate::mvlookup::{
+    accumulator_constraints, AccumulatorBoundary, AggregationDirection, GlobalTableCommitments,
+    LookupAbsorptionOrder, LookupAggregation, LookupAggregationMode, LookupLayout, LookupProof,
+    LookupTableID, MVLookup, TablePolicy,
+};
+use ark_ff::{FftField, Field, One, Zero};
 use ark_poly::{univariate::DensePolynomial, Evaluations, Radix2EvaluationDomain as R2D};
 use rand::thread_rng;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
@@ -7,7 +11,8 @@ use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use kimchi::{
     circuits::{
         domains::EvaluationDomains,
-        expr::{Challenges, Constants, Expr, PolishToken},
+        expr::{Challenges, ColumnEvaluations, Constants, Expr, ExprError, PolishToken},
+        gate::CurrOrNext,
     },
     curve::KimchiCurve,
     groupmap::GroupMap,
@@ -16,13 +21,158 @@ use kimchi::{
 };
 use mina_poseidon::{sponge::ScalarChallenge, FqSponge};
 use poly_commitment::{
-    commitment::{
-        absorb_commitment, combined_inner_product, BatchEvaluationProof, Evaluation, PolyComm,
-    },
+    commitment::{absorb_commitment, BatchEvaluationProof, Evaluation, PolyComm},
     OpenProof, SRS,
 };
 
-use crate::{expr::E, proof::Proof, witness::Witness};
+use crate::{
+    boundary,
+    column_permutation::ColumnPermutation,
+    columns::Column,
+    config::{
+        check_joint_combiner, ConfigError, DegeneratePointRule, OpeningMode, ProtocolConfig,
+        SrsLabel,
+    },
+    constant_columns::ConstantColumns,
+    cost::TranscriptLayout,
+    domain::create_domain,
+    evaluation_layout::{EvaluationLayout, FlatEvaluations},
+    expr::{column_accesses, decode_expr, deserialize_expr_tokens, encode_expr, ExprToken, E},
+    perf::PerfCounters,
+    proof::{
+        expected_column_chunks, expected_quotient_chunks, max_constraint_degree,
+        BorrowedColumnEvaluations, CommitmentLabel, EvaluationContainer, Proof, ProofEvaluations,
+    },
+    prover::ProverContext,
+    public_schema::PublicSchemaError,
+    shifts::{self, EvaluationShifts, ShiftedEvaluations},
+    subdomain,
+    transcript::{
+        CountingFrSponge, CountingSponge, Transcript, TranscriptBackend, TranscriptCounter,
+        TranscriptProfile, STRICT_TRANSCRIPT,
+    },
+    verification_cache::{CacheKey, VerificationCache},
+    witness::Witness,
+};
+use blake2::{Blake2b512, Digest};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::OnceLock,
+};
+use thiserror::Error;
+
+/// Errors that can arise when verifying a proof
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VerifierError {
+    #[error("the commitment to the quotient polynomial has {got} chunks, expected {expected}")]
+    QuotientChunkCountMismatch { expected: usize, got: usize },
+
+    #[error("the commitment to the {commitment} has {got} chunks, expected {expected}")]
+    CommitmentChunkCountMismatch {
+        commitment: CommitmentLabel,
+        expected: usize,
+        got: usize,
+    },
+
+    #[error("the commitment to the public input column {0} does not match the proof")]
+    PublicInputMismatch(usize),
+
+    #[error("the fixed lookup table {0} is not part of the global table commitments")]
+    MissingGlobalTable(u32),
+
+    #[error("the table {0} of the plookup engine does not match the global table commitments")]
+    PlookupTableMismatch(u32),
+
+    #[error("the opening proof could not be verified")]
+    OpeningProofFailed,
+
+    #[error("the proof does not follow the protocol configuration: {0}")]
+    Config(ConfigError),
+
+    #[error("the proof could not be deserialized: {0}")]
+    Deserialization(String),
+
+    #[error("the verifier is not configured for proofs over the curve {0}")]
+    UnsupportedCurve(&'static str),
+
+    #[error("the proof was created with an SRS which is not in the set of the verifier: {0:?}")]
+    UnknownSrs(Option<SrsLabel>),
+
+    #[error("the evaluation point is zero or in the domain")]
+    DegenerateEvaluationPoint,
+
+    #[error("the running sum of the lookup argument does not start from the incoming accumulator")]
+    IncomingAccumulatorMismatch,
+
+    #[error("the proof is malformed: {0}")]
+    MalformedProof(&'static str),
+
+    #[error("the constraints could not be evaluated on the proof: {0}")]
+    ConstraintEvaluation(String),
+
+    #[error("the constraints refer to the lookup table {0}, which is not in the proof")]
+    MissingLookupTable(u32),
+
+    #[error(
+        "the {columns} of the lookup argument are not given for the tables of the constraints: \
+         missing {missing:?}, unexpected {unexpected:?}"
+    )]
+    LookupTableSetMismatch {
+        columns: &'static str,
+        missing: Vec<u32>,
+        unexpected: Vec<u32>,
+    },
+
+    #[error("the proof carries lookup data, but the constraints do not use lookups")]
+    UnexpectedLookupData,
+
+    #[error("the verifier context could not be encoded: {0}")]
+    ContextEncoding(String),
+
+    #[error("the SRS is not the one of the verifier context")]
+    SrsFingerprintMismatch,
+
+    #[error("expected {expected} public input columns, got {got}")]
+    PublicInputCountMismatch { expected: usize, got: usize },
+
+    #[error("the public values do not match the public schema: {0}")]
+    PublicSchema(PublicSchemaError),
+
+    #[error("the column permutation has {got} columns, the proof has {expected}")]
+    ColumnPermutationMismatch { expected: usize, got: usize },
+
+    #[error("the header of the proof does not match the verifier context: {0}")]
+    HeaderMismatch(&'static str),
+
+    #[error("the transcript does not follow the protocol: {got:?} instead of {expected:?}")]
+    TranscriptProfileMismatch {
+        expected: TranscriptProfile,
+        got: TranscriptProfile,
+    },
+}
+
+/// The challenges coined by the verifier while replaying the transcript of a
+/// proof, see [VerifierContext::verify_with_challenges]. They are the same as the ones of the
+/// prover, and let other implementations of the verifier check that they
+/// follow the same transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifierChallenges<F> {
+    /// The combiner of the values of a lookup, only given with lookups
+    pub joint_combiner: Option<F>,
+    /// The challenge of the lookup argument, zero without lookups
+    pub beta: F,
+    /// The shift of the tables of the plookup engine, zero without them
+    pub gamma: F,
+    /// The combiner of the constraints
+    pub alpha: F,
+    /// The evaluation point
+    pub zeta: F,
+    /// The combiner of the polynomials of the opening proof
+    pub v: F,
+    /// The combiner of the evaluation points of the opening proof
+    pub u: F,
+}
 
 pub fn verify<
     G: KimchiCurve,
@@ -39,6 +189,1147 @@ pub fn verify<
     proof: &Proof<N, G, OpeningProof, ID>,
     public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
 ) -> bool
+where
+    OpeningProof::SRS: Sync,
+{
+    try_verify::<G, OpeningProof, EFqSponge, EFrSponge, N, NPUB, ID>(
+        domain,
+        srs,
+        constraints,
+        proof,
+        public_inputs,
+    )
+    .is_ok()
+}
+
+/// Same as [verify], but returns the reason why the proof has been rejected.
+pub fn try_verify<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    proof: &Proof<N, G, OpeningProof, ID>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    verify_internal::<G, OpeningProof, EFqSponge, EFrSponge, N, NPUB, 0, ID, _>(
+        domain,
+        srs,
+        constraints,
+        0,
+        proof,
+        public_inputs,
+        None,
+        LookupAggregationMode::Committed,
+        LookupAbsorptionOrder::ColumnsFirst,
+        None,
+        DegeneratePointRule::default(),
+        TranscriptBackend::FqSponge,
+        EvaluationLayout::default(),
+        &PerfCounters::default(),
+    )
+    .map(|_| ())
+}
+
+/// Same as [try_verify], also returning the challenges of the transcript when
+/// the proof is accepted.
+#[deprecated(note = "use VerifierContext::verify_with_challenges")]
+pub fn verify_with_challenges<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    proof: &Proof<N, G, OpeningProof, ID>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+) -> Result<VerifierChallenges<G::ScalarField>, VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?
+        .with_public_inputs(NPUB)
+        .verify_with_challenges::<EFqSponge, EFrSponge, N, NPUB, _>(proof, public_inputs)
+}
+
+/// Same as [try_verify], counting the expensive operations performed by the
+/// verifier in `perf`, see [PerfCounters]. The operations are only counted when
+/// the feature `perf-counters` is enabled. The multi-scalar multiplication of
+/// the opening proof is counted, but not the other operations of the
+/// polynomial commitment scheme.
+#[deprecated(note = "use VerifierContext::verify_with_perf_counters")]
+pub fn verify_with_perf_counters<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    proof: &Proof<N, G, OpeningProof, ID>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    perf: &PerfCounters,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)?
+        .with_public_inputs(NPUB)
+        .verify_with_perf_counters::<EFqSponge, EFrSponge, N, NPUB, _>(proof, public_inputs, perf)
+}
+
+/// Verify a proof whose constraints are the ones of the active tables of the
+/// proof, see [crate::mvlookup::TablePolicy]. The active tables of the proof
+/// must be acceptable for `policy`, see [crate::mvlookup::TablePolicy::check],
+/// and the lookups into the inactive tables are not checked.
+#[deprecated(
+    note = "compile the constraints with crate::mvlookup::TablePolicy::compile_constraints, and use VerifierContext::verify"
+)]
+#[allow(clippy::type_complexity)]
+pub fn verify_with_active_tables<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    policy: &TablePolicy<ID>,
+    constraints: &[E<G::ScalarField>],
+    lookups_map: &BTreeMap<ID, Vec<MVLookup<E<G::ScalarField>, ID>>>,
+    proof: &Proof<N, G, OpeningProof, ID>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let constraints = policy
+        .compile_constraints(constraints, lookups_map, &proof.active_tables())
+        .map_err(VerifierError::Config)?;
+    VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), &constraints)?
+        .with_public_inputs(NPUB)
+        .verify::<EFqSponge, EFrSponge, N, NPUB, _>(proof, public_inputs)
+}
+
+/// Verify a proof whose fixed lookup tables are shared with other circuits,
+/// see [GlobalTableCommitments]. The commitments to the fixed lookup tables
+/// are derived from `global_tables`, and the ones possibly contained in the
+/// proof are ignored.
+#[deprecated(note = "use VerifierContext::verify, with VerifierContext::with_global_tables")]
+pub fn verify_with_global_tables<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    proof: &Proof<N, G, OpeningProof, ID>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    global_tables: &GlobalTableCommitments<G, ID>,
+) -> bool
+where
+    OpeningProof::SRS: Sync,
+{
+    VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)
+        .and_then(|ctx| {
+            ctx.with_global_tables(global_tables.clone())
+                .with_public_inputs(NPUB)
+                .verify::<EFqSponge, EFrSponge, N, NPUB, _>(proof, public_inputs)
+        })
+        .is_ok()
+}
+
+/// Verify a proof created with `K` extra challenges, see
+/// [VerifierContext::verify_with_extra_challenges].
+#[deprecated(note = "use VerifierContext::verify_with_extra_challenges")]
+pub fn verify_with_extra_challenges<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    const K: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    n_first_phase: usize,
+    proof: &Proof<N, G, OpeningProof, ID>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+) -> bool
+where
+    OpeningProof::SRS: Sync,
+{
+    VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), constraints)
+        .and_then(|ctx| {
+            ctx.with_public_inputs(NPUB)
+                .verify_with_extra_challenges::<EFqSponge, EFrSponge, N, NPUB, K, _>(
+                    proof,
+                    public_inputs,
+                    n_first_phase,
+                )
+        })
+        .is_ok()
+}
+
+/// Verify a proof created with the protocol configuration `config`. The
+/// evaluation container `Eval` must match the opening mode of `config`.
+#[deprecated(note = "use VerifierContext::verify")]
+pub fn verify_with_config<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    config: &ProtocolConfig,
+    constraints: &[E<G::ScalarField>],
+    proof: &Proof<N, G, OpeningProof, ID, Eval>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    VerifierContext::new(domain, srs, config.clone(), constraints)?
+        .with_public_inputs(NPUB)
+        .verify::<EFqSponge, EFrSponge, N, NPUB, Eval>(proof, public_inputs)
+}
+
+/// Same as [verify_with_config], looking up the outcome of the verification
+/// in `cache` first, see [VerifierContext::verify_with_cache].
+#[deprecated(note = "use VerifierContext::verify_with_cache")]
+#[allow(clippy::too_many_arguments)]
+pub fn verify_with_cache<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G> + Serialize,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+    Cache: VerificationCache,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    config: &ProtocolConfig,
+    constraints: &[E<G::ScalarField>],
+    proof: &Proof<N, G, OpeningProof, ID, Eval>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    cache: &Cache,
+    perf: &PerfCounters,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    VerifierContext::new(domain, srs, config.clone(), constraints)?
+        .with_public_inputs(NPUB)
+        .verify_with_cache::<EFqSponge, EFrSponge, N, NPUB, Eval, Cache>(
+            proof,
+            public_inputs,
+            cache,
+            perf,
+        )
+}
+
+/// Same as [verify_with_config], for a proof whose running sum of the lookup
+/// argument starts from `incoming`, see
+/// [VerifierContext::verify_with_accumulator].
+#[deprecated(note = "use VerifierContext::verify_with_accumulator")]
+pub fn verify_with_accumulator<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    config: &ProtocolConfig,
+    constraints: &[E<G::ScalarField>],
+    proof: &Proof<N, G, OpeningProof, ID, Eval>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    incoming: G::ScalarField,
+) -> Result<AccumulatorBoundary<G::ScalarField>, VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    VerifierContext::new(domain, srs, config.clone(), constraints)?
+        .with_public_inputs(NPUB)
+        .verify_with_accumulator::<EFqSponge, EFrSponge, N, NPUB, Eval>(
+            proof,
+            public_inputs,
+            incoming,
+        )
+}
+
+/// The circuit and the parameters the proofs of a circuit are verified
+/// against: the compiled constraints and the layout of their lookup columns,
+/// the configuration of the protocol, the commitments to the global fixed
+/// tables if any, the fingerprint of the SRS, and the number of public input
+/// columns. The constraints are compiled once with
+/// [ProtocolConfig::compile_constraints] when creating the context, instead of
+/// once per proof.
+/// The context is distributed to the verifiers with [VerifierContext::to_bytes],
+/// without the SRS, and is registered with its [VerifierContext::digest].
+pub struct VerifierContext<'a, G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID> {
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &'a OpeningProof::SRS,
+    config: ProtocolConfig,
+    constraints: Vec<E<G::ScalarField>>,
+    lookup_layout: Option<LookupLayout<ID>>,
+    global_tables: Option<GlobalTableCommitments<G, ID>>,
+    /// Computed when it is first needed, as it costs an MSM of the size of
+    /// the SRS
+    srs_fingerprint: OnceLock<SrsLabel>,
+    n_public_inputs: usize,
+    column_permutation: Option<ColumnPermutation>,
+    evaluation_layout: EvaluationLayout,
+}
+
+/// The serialization of a [VerifierContext]. The layout of the lookup columns
+/// is derived from the constraints, and is not serialized.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct SerializedVerifierContext<G: KimchiCurve, ID: LookupTableID> {
+    domain_size: u64,
+    config: ProtocolConfig,
+    #[serde(deserialize_with = "deserialize_expr_tokens")]
+    constraints: Vec<Vec<ExprToken<G::ScalarField>>>,
+    global_tables: Option<GlobalTableCommitments<G, ID>>,
+    srs_fingerprint: SrsLabel,
+    n_public_inputs: usize,
+    column_permutation: Option<ColumnPermutation>,
+}
+
+impl<'a, G: KimchiCurve, OpeningProof: OpenProof<G>, ID: LookupTableID>
+    VerifierContext<'a, G, OpeningProof, ID>
+where
+    OpeningProof::SRS: Sync,
+{
+    /// Create the context of the circuit given by `constraints`, which do not
+    /// include the ones added by `config`. Fails if the constraints are not
+    /// supported by `config`, or can not be serialized, see [encode_expr].
+    pub fn new(
+        domain: EvaluationDomains<G::ScalarField>,
+        srs: &'a OpeningProof::SRS,
+        config: ProtocolConfig,
+        constraints: &[E<G::ScalarField>],
+    ) -> Result<Self, VerifierError> {
+        let constraints = config
+            .compile_constraints(constraints.to_vec())
+            .map_err(VerifierError::Config)?;
+        Self::compiled(domain, srs, config, constraints, None, None)
+    }
+
+    /// The context of the proofs created with `ctx`, see
+    /// [crate::prover::prove_with_context]. The circuit is the one of `ctx`,
+    /// whose constraints are already compiled, and the context only depends
+    /// on `ctx`.
+    pub fn from_prover_context(
+        ctx: &ProverContext<'a, G, OpeningProof, ID>,
+    ) -> Result<Self, VerifierError> {
+        Self::compiled(
+            ctx.domain(),
+            ctx.srs(),
+            ctx.config().clone(),
+            ctx.constraints().to_vec(),
+            ctx.global_tables().cloned(),
+            ctx.column_permutation().cloned(),
+        )
+    }
+
+    fn compiled(
+        domain: EvaluationDomains<G::ScalarField>,
+        srs: &'a OpeningProof::SRS,
+        config: ProtocolConfig,
+        constraints: Vec<E<G::ScalarField>>,
+        global_tables: Option<GlobalTableCommitments<G, ID>>,
+        column_permutation: Option<ColumnPermutation>,
+    ) -> Result<Self, VerifierError> {
+        // The context must be serializable to be distributed
+        for constraint in constraints.iter() {
+            encode_expr(constraint)
+                .map_err(|err| VerifierError::ContextEncoding(err.to_string()))?;
+        }
+        Ok(VerifierContext {
+            domain,
+            srs,
+            config,
+            lookup_layout: LookupLayout::of_constraints(&constraints),
+            constraints,
+            global_tables,
+            srs_fingerprint: OnceLock::new(),
+            n_public_inputs: 0,
+            column_permutation,
+            evaluation_layout: EvaluationLayout::default(),
+        })
+    }
+
+    /// Derive the commitments to the fixed tables from `global_tables`, see
+    /// [GlobalTableCommitments]. The ones possibly contained in the proofs are
+    /// ignored.
+    pub fn with_global_tables(mut self, global_tables: GlobalTableCommitments<G, ID>) -> Self {
+        self.global_tables = Some(global_tables);
+        self
+    }
+
+    /// Expect `n_public_inputs` public input columns, the first columns of
+    /// the witness
+    pub fn with_public_inputs(mut self, n_public_inputs: usize) -> Self {
+        self.n_public_inputs = n_public_inputs;
+        self
+    }
+
+    /// Expect the witness columns to be reordered with `permutation`, like
+    /// [crate::prover::ProverContext::with_column_permutation]. The
+    /// constraints of the context are renumbered.
+    pub fn with_column_permutation(mut self, permutation: ColumnPermutation) -> Self {
+        self.constraints = self
+            .constraints
+            .iter()
+            .map(|constraint| permutation.apply_to_constraint(constraint))
+            .collect();
+        self.config = permutation.apply_to_config(self.config);
+        self.column_permutation = Some(permutation);
+        self
+    }
+
+    /// Read the evaluations of the proofs with `layout` when evaluating the
+    /// constraints. The layout is local to the verifier, and is not part of
+    /// the serialization of the context.
+    pub fn with_evaluation_layout(mut self, layout: EvaluationLayout) -> Self {
+        self.evaluation_layout = layout;
+        self
+    }
+
+    pub fn domain(&self) -> EvaluationDomains<G::ScalarField> {
+        self.domain
+    }
+
+    pub fn config(&self) -> &ProtocolConfig {
+        &self.config
+    }
+
+    /// The compiled constraints
+    pub fn constraints(&self) -> &[E<G::ScalarField>] {
+        &self.constraints
+    }
+
+    /// The layout of the lookup columns of the constraints, `None` without
+    /// lookups
+    pub fn lookup_layout(&self) -> Option<&LookupLayout<ID>> {
+        self.lookup_layout.as_ref()
+    }
+
+    pub fn srs_fingerprint(&self) -> &SrsLabel {
+        self.srs_fingerprint
+            .get_or_init(|| SrsLabel::fingerprint::<G, OpeningProof>(self.srs))
+    }
+
+    pub fn n_public_inputs(&self) -> usize {
+        self.n_public_inputs
+    }
+
+    /// The maximum number of rows of the circuit, see
+    /// [ProtocolConfig::max_rows]
+    pub fn max_rows(&self) -> Option<usize> {
+        self.config.max_rows
+    }
+
+    /// The permutation of the witness columns of the proofs, `None` if the
+    /// columns are in the order of the circuit
+    pub fn column_permutation(&self) -> Option<&ColumnPermutation> {
+        self.column_permutation.as_ref()
+    }
+
+    pub fn evaluation_layout(&self) -> EvaluationLayout {
+        self.evaluation_layout
+    }
+
+    /// The evaluations in `proof` of the witness column `column`, in the
+    /// order of the circuit, i.e. before the permutation of the columns
+    pub fn witness_evaluations<'b, const N: usize, Eval: EvaluationContainer<G::ScalarField>>(
+        &self,
+        proof: &'b Proof<N, G, OpeningProof, ID, Eval>,
+        column: usize,
+    ) -> &'b Eval {
+        let position = self
+            .column_permutation
+            .as_ref()
+            .map_or(column, |permutation| permutation.position(column));
+        &proof.proof_evals.witness_evals.cols[position]
+    }
+
+    fn serialized(&self) -> SerializedVerifierContext<G, ID> {
+        SerializedVerifierContext {
+            domain_size: self.domain.d1.size,
+            config: self.config.clone(),
+            constraints: self
+                .constraints
+                .iter()
+                .map(|constraint| {
+                    encode_expr(constraint).expect("the constraints are checked on creation")
+                })
+                .collect(),
+            global_tables: self.global_tables.clone(),
+            srs_fingerprint: self.srs_fingerprint().clone(),
+            n_public_inputs: self.n_public_inputs,
+            column_permutation: self.column_permutation.clone(),
+        }
+    }
+
+    /// The canonical serialization of the context, in MessagePack, without
+    /// the SRS
+    pub fn to_bytes(&self) -> Vec<u8> {
+        rmp_serde::to_vec(&self.serialized()).expect("The context can always be serialized")
+    }
+
+    /// Load a context serialized with [VerifierContext::to_bytes], with the
+    /// SRS `srs` of the verifier, which must be the one the context was
+    /// created with, see [SrsLabel::fingerprint]. The SRS is checked once,
+    /// when loading the context.
+    pub fn from_bytes(bytes: &[u8], srs: &'a OpeningProof::SRS) -> Result<Self, VerifierError> {
+        let deserialization = VerifierError::Deserialization;
+        let serialized: SerializedVerifierContext<G, ID> =
+            rmp_serde::from_slice(bytes).map_err(|err| deserialization(err.to_string()))?;
+        let domain = create_domain(serialized.domain_size as usize)
+            .ok()
+            .filter(|domain| domain.d1.size == serialized.domain_size)
+            .ok_or_else(|| deserialization("the size of the domain is invalid".to_string()))?;
+        let constraints = serialized
+            .constraints
+            .into_iter()
+            .map(decode_expr)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| deserialization(err.to_string()))?;
+        // The layout decodes the IDs of the tables, which must be checked
+        // first as the context is untrusted
+        let table_ids = constraints
+            .iter()
+            .flat_map(column_accesses)
+            .filter_map(|(column, _)| match column {
+                Column::LookupMultiplicity(idx) | Column::LookupFixedTable(idx) => Some(idx),
+                _ => None,
+            });
+        for idx in table_ids {
+            if ID::try_from_u32(idx.to_u32()).is_none() {
+                return Err(deserialization(format!(
+                    "unknown lookup table {}",
+                    idx.to_u32()
+                )));
+            }
+        }
+        // The partial sums are the only lookup columns whose index is not an
+        // ID of table, they must be ones of the layout
+        let lookup_layout = LookupLayout::of_constraints(&constraints);
+        let n_partial_sums = lookup_layout
+            .as_ref()
+            .map_or(0, |layout| layout.n_partial_sums());
+        let partial_sums =
+            constraints
+                .iter()
+                .flat_map(column_accesses)
+                .filter_map(|(column, _)| match column {
+                    Column::LookupPartialSum(idx) => Some(idx),
+                    _ => None,
+                });
+        for idx in partial_sums {
+            if idx.index() >= n_partial_sums {
+                return Err(deserialization(format!(
+                    "unknown lookup partial sum {}",
+                    idx.index()
+                )));
+            }
+        }
+        if SrsLabel::fingerprint::<G, OpeningProof>(srs) != serialized.srs_fingerprint {
+            return Err(VerifierError::SrsFingerprintMismatch);
+        }
+        Ok(VerifierContext {
+            domain,
+            srs,
+            config: serialized.config,
+            lookup_layout,
+            constraints,
+            global_tables: serialized.global_tables,
+            srs_fingerprint: OnceLock::from(serialized.srs_fingerprint),
+            n_public_inputs: serialized.n_public_inputs,
+            column_permutation: serialized.column_permutation,
+            evaluation_layout: EvaluationLayout::default(),
+        })
+    }
+
+    /// The first 32 bytes of the Blake2b digest of [VerifierContext::to_bytes],
+    /// identifying the circuit, e.g. to register it on-chain
+    pub fn digest(&self) -> [u8; 32] {
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&Blake2b512::digest(self.to_bytes())[..32]);
+        digest
+    }
+
+    /// Verify a proof of the circuit. The evaluation container `Eval` must
+    /// match the opening mode of the configuration, and the number of public
+    /// input columns must be the one of the context.
+    pub fn verify<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    ) -> Result<(), VerifierError> {
+        self.verify_with_perf_counters::<EFqSponge, EFrSponge, N, NPUB, Eval>(
+            proof,
+            public_inputs,
+            &PerfCounters::default(),
+        )
+    }
+
+    /// Same as [VerifierContext::verify], counting the expensive operations
+    /// performed by the verifier in `perf`, see [PerfCounters]. The operations
+    /// are only counted when the feature `perf-counters` is enabled. The
+    /// multi-scalar multiplication of the opening proof is counted, but not
+    /// the other operations of the polynomial commitment scheme.
+    pub fn verify_with_perf_counters<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+        perf: &PerfCounters,
+    ) -> Result<(), VerifierError> {
+        self.verify_checked::<EFqSponge, EFrSponge, N, NPUB, 0, Eval>(
+            proof,
+            public_inputs,
+            0,
+            vec![],
+            perf,
+        )
+        .map(|_| ())
+    }
+
+    /// Same as [VerifierContext::verify], also returning the challenges of
+    /// the transcript when the proof is accepted
+    pub fn verify_with_challenges<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    ) -> Result<VerifierChallenges<G::ScalarField>, VerifierError> {
+        self.verify_checked::<EFqSponge, EFrSponge, N, NPUB, 0, Eval>(
+            proof,
+            public_inputs,
+            0,
+            vec![],
+            &PerfCounters::default(),
+        )
+    }
+
+    /// Verify a proof created with
+    /// [crate::prover::prove_with_context_and_extra_challenges]. The `K` extra
+    /// challenges are coined after absorbing the commitments to the first
+    /// `n_first_phase` columns of the witness.
+    pub fn verify_with_extra_challenges<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        const K: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+        n_first_phase: usize,
+    ) -> Result<(), VerifierError> {
+        self.verify_checked::<EFqSponge, EFrSponge, N, NPUB, K, Eval>(
+            proof,
+            public_inputs,
+            n_first_phase,
+            vec![],
+            &PerfCounters::default(),
+        )
+        .map(|_| ())
+    }
+
+    /// Verify a proof created with
+    /// [crate::prover::prove_with_context_and_accumulator], whose running sum
+    /// of the lookup argument starts from `incoming`. The boundary of the
+    /// proof is returned, to check the chain of proofs with
+    /// [crate::mvlookup::check_chain].
+    pub fn verify_with_accumulator<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+        incoming: G::ScalarField,
+    ) -> Result<AccumulatorBoundary<G::ScalarField>, VerifierError> {
+        if self.config.lookup_aggregation != LookupAggregationMode::Committed
+            || self.config.aggregation_direction != AggregationDirection::Forward
+        {
+            return Err(VerifierError::Config(
+                ConfigError::LookupAccumulatorNotSupported,
+            ));
+        }
+        let boundary = match proof.accumulator() {
+            Some(boundary) if boundary.incoming == incoming => *boundary,
+            _ => return Err(VerifierError::IncomingAccumulatorMismatch),
+        };
+        let layout = self.lookup_layout.as_ref().ok_or(VerifierError::Config(
+            ConfigError::LookupAccumulatorNotSupported,
+        ))?;
+        self.verify_checked::<EFqSponge, EFrSponge, N, NPUB, 0, Eval>(
+            proof,
+            public_inputs,
+            0,
+            accumulator_constraints(layout, self.domain.d1, &boundary),
+            &PerfCounters::default(),
+        )?;
+        Ok(boundary)
+    }
+
+    /// Same as [VerifierContext::verify_with_perf_counters], looking up the
+    /// outcome of the verification in `cache` first, and recording it once
+    /// the proof has been verified. A proof found in the cache is not
+    /// verified again, and the operations of the verifier are only counted in
+    /// `perf` otherwise.
+    /// The outcome is indexed by the digest of the context, see
+    /// [VerifierContext::digest], the public inputs and the proof, see
+    /// [CacheKey], so that `cache` can be shared with the contexts of other
+    /// circuits.
+    pub fn verify_with_cache<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+        Cache: VerificationCache,
+    >(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+        cache: &Cache,
+        perf: &PerfCounters,
+    ) -> Result<(), VerifierError>
+    where
+        OpeningProof: Serialize,
+    {
+        let key = CacheKey::new(self.digest(), proof, &public_inputs);
+        if let Some(outcome) = cache.get(&key) {
+            return outcome;
+        }
+        let outcome = self.verify_with_perf_counters::<EFqSponge, EFrSponge, N, NPUB, Eval>(
+            proof,
+            public_inputs,
+            perf,
+        );
+        cache.insert(key, outcome.clone());
+        outcome
+    }
+
+    /// Check `proof` against the context, and verify it with the constraints
+    /// of the context extended with `extra_constraints`, and `K` extra
+    /// challenges coined after the first `n_first_phase` columns
+    fn verify_checked<
+        EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+        EFrSponge: FrSponge<G::ScalarField>,
+        const N: usize,
+        const NPUB: usize,
+        const K: usize,
+        Eval: EvaluationContainer<G::ScalarField>,
+    >(
+        &self,
+        proof: &Proof<N, G, OpeningProof, ID, Eval>,
+        public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+        n_first_phase: usize,
+        extra_constraints: Vec<E<G::ScalarField>>,
+        perf: &PerfCounters,
+    ) -> Result<VerifierChallenges<G::ScalarField>, VerifierError> {
+        if self.config.opening_mode != Eval::OPENING_MODE {
+            return Err(VerifierError::Config(ConfigError::OpeningModeMismatch));
+        }
+        if NPUB != self.n_public_inputs {
+            return Err(VerifierError::PublicInputCountMismatch {
+                expected: self.n_public_inputs,
+                got: NPUB,
+            });
+        }
+        if let Some(permutation) = &self.column_permutation {
+            if permutation.len() != N {
+                return Err(VerifierError::ColumnPermutationMismatch {
+                    expected: N,
+                    got: permutation.len(),
+                });
+            }
+        }
+        self.config
+            .check_global_tables(self.global_tables.is_some())
+            .map_err(VerifierError::Config)?;
+        let mut constraints = self.constraints.clone();
+        constraints.extend(extra_constraints);
+        // The outputs claimed by the prover are bound to the witness
+        constraints.extend(
+            self.config
+                .output_constraints(proof.public_outputs())
+                .map_err(VerifierError::Config)?,
+        );
+        verify_internal::<G, OpeningProof, EFqSponge, EFrSponge, N, NPUB, K, ID, Eval>(
+            self.domain,
+            self.srs,
+            &constraints,
+            n_first_phase,
+            proof,
+            public_inputs,
+            self.global_tables.as_ref(),
+            self.config.lookup_aggregation,
+            self.config.lookup_absorption,
+            self.config.srs_label.as_ref(),
+            self.config.degenerate_point_rule,
+            self.config.transcript,
+            self.evaluation_layout,
+            perf,
+        )
+    }
+}
+
+/// Verify proofs created under any of the SRS of `srs_candidates`, e.g.
+/// during the migration to a new SRS. Each proof is verified like with
+/// [VerifierContext::verify], with the SRS whose label is the one recorded in the
+/// proof, see [ProtocolConfig::srs_label], and fails with
+/// [VerifierError::UnknownSrs] if there is none. The label of `config` is
+/// ignored. The results are in the order of `proofs`.
+#[allow(clippy::type_complexity)]
+pub fn verify_with_srs_set<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs_candidates: &[(SrsLabel, &OpeningProof::SRS)],
+    config: &ProtocolConfig,
+    constraints: &[E<G::ScalarField>],
+    proofs: &[(
+        &Proof<N, G, OpeningProof, ID, Eval>,
+        Witness<NPUB, Vec<G::ScalarField>>,
+    )],
+) -> Vec<Result<(), VerifierError>>
+where
+    OpeningProof::SRS: Sync,
+{
+    proofs
+        .iter()
+        .map(|(proof, public_inputs)| {
+            let (label, srs) = proof
+                .srs_label()
+                .and_then(|label| srs_candidates.iter().find(|(l, _)| l == label))
+                .ok_or_else(|| VerifierError::UnknownSrs(proof.srs_label().cloned()))?;
+            let config = ProtocolConfig {
+                srs_label: Some(label.clone()),
+                ..config.clone()
+            };
+            VerifierContext::new(domain, *srs, config, constraints)?
+                .with_public_inputs(NPUB)
+                .verify::<EFqSponge, EFrSponge, N, NPUB, Eval>(proof, public_inputs.clone())
+        })
+        .collect()
+}
+
+/// Verify a proof created with [crate::prover::prove_with_constant_columns].
+/// The references to the constant columns are replaced by their values in the
+/// constraints. The public inputs are the first columns of the witness without
+/// the constant columns.
+#[deprecated(
+    note = "substitute the constraints with ConstantColumns::substitute, and use VerifierContext::verify"
+)]
+pub fn verify_with_constant_columns<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const M: usize,
+    const NPUB: usize,
+    ID: LookupTableID,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &[E<G::ScalarField>],
+    constant_columns: &ConstantColumns<G::ScalarField>,
+    proof: &Proof<M, G, OpeningProof, ID>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+) -> Result<(), VerifierError>
+where
+    OpeningProof::SRS: Sync,
+{
+    let constraints: Vec<_> = constraints
+        .iter()
+        .map(|constraint| constant_columns.substitute(constraint))
+        .collect();
+    VerifierContext::new(domain, srs, ProtocolConfig::unsimplified(), &constraints)?
+        .with_public_inputs(NPUB)
+        .verify::<EFqSponge, EFrSponge, M, NPUB, _>(proof, public_inputs)
+}
+
+/// The evaluations of the proof, together with the extra challenges, which
+/// are constant columns from the point of view of the constraints, and the
+/// sub-domain and boundary selectors, which the verifier evaluates itself.
+struct EvaluationsWithChallenges<
+    'a,
+    const N: usize,
+    F: FftField,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<F>,
+> {
+    proof_evals: &'a ProofEvaluations<N, F, ID, Eval>,
+    /// The evaluations of the proof in the [EvaluationLayout::Flat] layout
+    flat_evals: Option<&'a FlatEvaluations<F>>,
+    extra_challenges: &'a [F],
+    zeta: F,
+    domain: R2D<F>,
+    perf: &'a PerfCounters,
+}
+
+impl<'a, const N: usize, F: FftField, ID: LookupTableID, Eval: EvaluationContainer<F>>
+    ColumnEvaluations<F> for EvaluationsWithChallenges<'a, N, F, ID, Eval>
+{
+    type Column = Column;
+
+    fn evaluate(&self, col: Self::Column) -> Result<PointEvaluations<F>, ExprError<Self::Column>> {
+        match col {
+            Column::ExtraChallenge(i) => {
+                let chal = self.extra_challenges[i];
+                Ok(PointEvaluations {
+                    zeta: chal,
+                    zeta_omega: chal,
+                })
+            }
+            Column::SubdomainSelector(log_size) => {
+                let selector = |x| subdomain::evaluate_selector(log_size, self.domain.size, x);
+                Ok(PointEvaluations {
+                    zeta: selector(self.zeta),
+                    zeta_omega: selector(self.zeta * self.domain.group_gen),
+                })
+            }
+            Column::BoundarySelector(max_rows) => {
+                let selector = |x| boundary::evaluate_selector(max_rows, self.domain, x);
+                Ok(PointEvaluations {
+                    zeta: selector(self.zeta),
+                    zeta_omega: selector(self.zeta * self.domain.group_gen),
+                })
+            }
+            // The columns missing from the flat layout are looked up in the
+            // proof, for the error to be the same with both layouts
+            _ => match self.flat_evals.and_then(|flat_evals| flat_evals.get(col)) {
+                Some(evals) => Ok(evals),
+                // The evaluations are borrowed, and only the ones read are
+                // copied on the stack
+                None => {
+                    self.perf.record_evaluation_lookups(1);
+                    Ok(self
+                        .proof_evals
+                        .column_evaluations(col)?
+                        .to_point_evaluations())
+                }
+            },
+        }
+    }
+}
+
+/// The combined inner product of the evaluations of the batch, like
+/// [poly_commitment::commitment::combined_inner_product], without copying them: the sum of the evaluations
+/// of the chunks, combined with the powers of `evalscale` over the evaluation
+/// points, and with the powers of `polyscale` over the chunks.
+pub(crate) fn borrowed_combined_inner_product<G: KimchiCurve>(
+    polyscale: G::ScalarField,
+    evalscale: G::ScalarField,
+    batch: &[Evaluation<G>],
+) -> G::ScalarField {
+    let mut res = G::ScalarField::zero();
+    let mut xi_i = G::ScalarField::one();
+    for Evaluation { evaluations, .. } in batch {
+        let n_chunks = evaluations.first().map_or(0, |evals| evals.len());
+        for chunk in 0..n_chunks {
+            let term = evaluations
+                .iter()
+                .rev()
+                .fold(G::ScalarField::zero(), |acc, evals| {
+                    acc * evalscale + evals[chunk]
+                });
+            res += xi_i * term;
+            xi_i *= polyscale;
+        }
+    }
+    res
+}
+
+/// Check that the tables of `given`, the keys of a part of the lookup
+/// argument of the proof, are exactly the tables of the constraints
+fn check_table_set<'a, ID: LookupTableID + 'a>(
+    columns: &'static str,
+    tables: &BTreeSet<u32>,
+    given: impl Iterator<Item = &'a ID>,
+) -> Result<(), VerifierError> {
+    let given: BTreeSet<u32> = given.map(ID::to_u32).collect();
+    if given == *tables {
+        return Ok(());
+    }
+    Err(VerifierError::LookupTableSetMismatch {
+        columns,
+        missing: tables.difference(&given).copied().collect(),
+        unexpected: given.difference(tables).copied().collect(),
+    })
+}
+
+/// Check that the lookup columns among `columns`, the columns read by the
+/// constraints, are given in the proof: the multiplicities and the fixed
+/// table of each table, and the partial sums.
+/// The multiplicities and the fixed tables are given for exactly the tables
+/// of the constraints, both in the commitments and in the evaluations, as
+/// they are paired by table afterwards. The commitments to the fixed tables
+/// are omitted from the proofs verified against global tables.
+/// The columns of the plookup engine are given for exactly the tables whose
+/// columns of the engine are read.
+fn check_lookup_columns<G: KimchiCurve, ID: LookupTableID, Eval>(
+    columns: &BTreeSet<Column>,
+    mvlookup_comms: Option<&LookupProof<PolyComm<G>, ID, G::ScalarField>>,
+    mvlookup_evals: Option<&LookupProof<Eval, ID>>,
+) -> Result<(), VerifierError> {
+    let tables: BTreeSet<u32> = columns
+        .iter()
+        .filter_map(|column| match column {
+            Column::LookupMultiplicity(idx) | Column::LookupFixedTable(idx) => Some(idx.to_u32()),
+            _ => None,
+        })
+        .collect();
+    let plookup_tables: BTreeSet<u32> = columns
+        .iter()
+        .filter_map(|column| match column {
+            Column::Plookup(idx, _) => Some(idx.to_u32()),
+            _ => None,
+        })
+        .collect();
+    // The missing tables are reported first, as they shift the partial sums
+    match (mvlookup_comms, mvlookup_evals) {
+        (Some(comms), Some(evals)) => {
+            check_table_set("multiplicity commitments", &tables, comms.m.keys())?;
+            check_table_set("multiplicity evaluations", &tables, evals.m.keys())?;
+            if !comms.fixed_tables.is_empty() {
+                check_table_set(
+                    "fixed table commitments",
+                    &tables,
+                    comms.fixed_tables.keys(),
+                )?;
+            }
+            check_table_set(
+                "fixed table evaluations",
+                &tables,
+                evals.fixed_tables.keys(),
+            )?;
+            check_table_set("plookup commitments", &plookup_tables, comms.plookup.keys())?;
+            check_table_set("plookup evaluations", &plookup_tables, evals.plookup.keys())?;
+        }
+        _ => {
+            if let Some(id) = tables.first().or(plookup_tables.first()) {
+                return Err(VerifierError::MissingLookupTable(*id));
+            }
+        }
+    }
+    let n_partial_sums = mvlookup_comms.map_or(0, |comms| comms.h.len());
+    if columns
+        .iter()
+        .any(|column| matches!(column, Column::LookupPartialSum(i) if i.index() >= n_partial_sums))
+    {
+        return Err(VerifierError::MalformedProof(
+            "the constraints refer to a partial sum which is not in the proof",
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_internal<
+    G: KimchiCurve,
+    OpeningProof: OpenProof<G>,
+    EFqSponge: Clone + FqSponge<G::BaseField, G, G::ScalarField>,
+    EFrSponge: FrSponge<G::ScalarField>,
+    const N: usize,
+    const NPUB: usize,
+    const K: usize,
+    ID: LookupTableID,
+    Eval: EvaluationContainer<G::ScalarField>,
+>(
+    domain: EvaluationDomains<G::ScalarField>,
+    srs: &OpeningProof::SRS,
+    constraints: &Vec<E<G::ScalarField>>,
+    n_first_phase: usize,
+    proof: &Proof<N, G, OpeningProof, ID, Eval>,
+    public_inputs: Witness<NPUB, Vec<G::ScalarField>>,
+    global_tables: Option<&GlobalTableCommitments<G, ID>>,
+    lookup_aggregation: LookupAggregationMode,
+    lookup_absorption: LookupAbsorptionOrder,
+    srs_label: Option<&SrsLabel>,
+    degenerate_point_rule: DegeneratePointRule,
+    transcript: TranscriptBackend,
+    evaluation_layout: EvaluationLayout,
+    perf: &PerfCounters,
+) -> Result<VerifierChallenges<G::ScalarField>, VerifierError>
 where
     OpeningProof::SRS: Sync,
 {
@@ -46,8 +1337,146 @@ where
         proof_comms,
         proof_evals,
         opening_proof,
+        srs_label: proof_srs_label,
+        public_outputs,
+        accumulator,
+        shifted_opening,
     } = proof;
 
+    if proof_srs_label.as_ref() != srs_label {
+        return Err(VerifierError::Config(ConfigError::SrsLabelMismatch));
+    }
+
+    // A circuit whose constraints do not read any lookup column has no lookup
+    // argument, and its proofs must not carry one: the prover rejects its
+    // lookup witnesses, see crate::mvlookup::uses_lookups, and the verifier
+    // skips it.
+    let columns: BTreeSet<Column> = constraints
+        .iter()
+        .flat_map(column_accesses)
+        .map(|(column, _)| column)
+        .collect();
+    let uses_lookups = columns.iter().any(Column::is_lookup);
+    let has_lookup_data =
+        proof_comms.mvlookup_comms.is_some() || proof_evals.mvlookup_evals.is_some();
+    if !uses_lookups && has_lookup_data {
+        return Err(VerifierError::UnexpectedLookupData);
+    }
+    // The joint combiner is only coined with the lookup argument
+    check_joint_combiner(constraints).map_err(VerifierError::Config)?;
+
+    if uses_lookups {
+        // The commitments and the evaluations of the lookup argument are
+        // paired below, and the proof is untrusted
+        proof
+            .check_lookup_shape()
+            .map_err(VerifierError::MalformedProof)?;
+
+        // The lookup columns of the constraints must be in the proof, which
+        // is not the case of a proof verified against a newer version of the
+        // circuit with more tables, and the proof must not give other tables
+        check_lookup_columns::<G, ID, Eval>(
+            &columns,
+            proof_comms.mvlookup_comms.as_ref(),
+            proof_evals.mvlookup_evals.as_ref(),
+        )?;
+
+        // The lookup argument refers to the next row
+        if Eval::OPENING_MODE == OpeningMode::SinglePoint && proof_comms.mvlookup_comms.is_some() {
+            return Err(VerifierError::Config(ConfigError::LookupsNotSupported));
+        }
+
+        // The aggregation of the lookup argument must be given as expected,
+        // both in the commitments and in the evaluations
+        let aggregation_modes = [
+            Option::map(proof_comms.mvlookup_comms.as_ref(), |comms| {
+                comms.aggregation_mode()
+            }),
+            Option::map(proof_evals.mvlookup_evals.as_ref(), |evals| {
+                evals.aggregation_mode()
+            }),
+        ];
+        if aggregation_modes
+            .iter()
+            .flatten()
+            .any(|mode| *mode != lookup_aggregation)
+        {
+            return Err(VerifierError::Config(
+                ConfigError::LookupAggregationMismatch,
+            ));
+        }
+    }
+
+    // The columns read two rows ahead are evaluated at ζω², and only them, and
+    // these evaluations are opened by their own opening proof
+    let shifts = EvaluationShifts::from_constraints(constraints);
+    let shifted_comms: Vec<(&PolyComm<G>, G::ScalarField)> = shifts
+        .shifted_columns()
+        .iter()
+        .map(|i| {
+            let comm = proof_comms.witness_comms.cols.get(*i).ok_or_else(|| {
+                let err = ExprError::MissingEvaluation(Column::XShift2(*i), CurrOrNext::Curr);
+                VerifierError::ConstraintEvaluation(err.to_string())
+            })?;
+            let eval = proof_evals
+                .shifted_evals
+                .get(i)
+                .ok_or(VerifierError::MalformedProof(
+                    "the evaluations at ζω² do not match the columns read two rows ahead",
+                ))?;
+            Ok((comm, *eval))
+        })
+        .collect::<Result<_, VerifierError>>()?;
+    if proof_evals.shifted_evals.len() != shifted_comms.len()
+        || shifted_opening.is_some() == shifts.is_empty()
+    {
+        return Err(VerifierError::MalformedProof(
+            "the evaluations at ζω² do not match the columns read two rows ahead",
+        ));
+    }
+
+    // The number of chunks of each commitment is fixed by the size of the
+    // SRS, and by the constraints for the quotient polynomial. The transcript
+    // absorbs all the chunks, check them before doing any work.
+    {
+        let domain_size = domain.d1.size as usize;
+        let srs_size = srs.max_poly_size();
+        let column_chunks = expected_column_chunks(domain_size, srs_size);
+        // The evaluations of the chunks are not part of the proof
+        if column_chunks > 1 {
+            return Err(VerifierError::Config(ConfigError::ChunkedCommitments {
+                domain_size,
+                srs_size,
+            }));
+        }
+        let max_degree = max_constraint_degree(
+            constraints,
+            Option::map_or(proof_comms.mvlookup_comms.as_ref(), false, |comms| {
+                !comms.h.is_empty()
+            }),
+        );
+        let quotient_chunks = expected_quotient_chunks(max_degree, domain) * column_chunks;
+        for (commitment, got) in proof_comms.chunk_schedule() {
+            match commitment {
+                CommitmentLabel::Quotient if got != quotient_chunks => {
+                    return Err(VerifierError::QuotientChunkCountMismatch {
+                        expected: quotient_chunks,
+                        got,
+                    })
+                }
+                CommitmentLabel::Quotient => (),
+                _ if got != column_chunks => {
+                    return Err(VerifierError::CommitmentChunkCountMismatch {
+                        commitment,
+                        expected: column_chunks,
+                        got,
+                    })
+                }
+                _ => (),
+            }
+        }
+    }
+
     ////////////////////////////////////////////////////////////////////////////
     // Re-evaluating public inputs
     ////////////////////////////////////////////////////////////////////////////
@@ -73,7 +1502,18 @@ where
     };
 
     let public_input_comms: Witness<NPUB, PolyComm<G>> = {
-        let comm = |poly: &DensePolynomial<G::ScalarField>| srs.commit_non_hiding(poly, 1);
+        // The witness columns are masked with the blinder one, see
+        // crate::prover::commit_column
+        let comm = |poly: &DensePolynomial<G::ScalarField>| {
+            if !poly.is_zero() {
+                perf.record_msm(poly.coeffs.len());
+            }
+            perf.record_scalar_muls(1);
+            let comm = srs.commit_non_hiding(poly, 1);
+            srs.mask_custom(comm.clone(), &comm.map(|_| G::ScalarField::one()))
+                .unwrap()
+                .commitment
+        };
         (&public_input_polys)
             .into_par_iter()
             .map(comm)
@@ -85,53 +1525,177 @@ where
         "Number of public inputs exceeds number of witness columns"
     );
     for i in 0..NPUB {
-        assert!(public_input_comms.cols[i] == proof_comms.witness_comms.cols[i]);
+        if public_input_comms.cols[i] != proof_comms.witness_comms.cols[i] {
+            return Err(VerifierError::PublicInputMismatch(i));
+        }
     }
 
     ////////////////////////////////////////////////////////////////////////////
     // Absorbing all the commitments to the columns
     ////////////////////////////////////////////////////////////////////////////
 
-    let mut fq_sponge = EFqSponge::new(G::other_curve_sponge_params());
+    assert!(
+        n_first_phase <= N,
+        "Number of columns in the first phase exceeds number of witness columns"
+    );
+
+    let transcript_counter = TranscriptCounter::default();
+    let mut fq_sponge = CountingSponge::new(
+        Transcript::<G, EFqSponge>::with_backend(transcript),
+        transcript_counter.clone(),
+    );
+    if let Some(label) = srs_label {
+        fq_sponge.absorb_fr(&[label.to_field()]);
+    }
+    if !public_outputs.0.is_empty() {
+        fq_sponge.absorb_fr(&public_outputs.0);
+    }
+    // The extra challenges are coined after the first phase columns
+    (&proof_comms.witness_comms)
+        .into_iter()
+        .take(n_first_phase)
+        .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
+    let extra_challenges: [G::ScalarField; K] = std::array::from_fn(|_| fq_sponge.challenge());
     (&proof_comms.witness_comms)
         .into_iter()
+        .skip(n_first_phase)
         .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
 
     ////////////////////////////////////////////////////////////////////////////
     // MVLookup
     ////////////////////////////////////////////////////////////////////////////
 
-    let (joint_combiner, beta) = {
-        if let Some(mvlookup_comms) = &proof_comms.mvlookup_comms {
+    // Nothing is absorbed nor squeezed for a lookup-free circuit
+    let (joint_combiner, beta, gamma, mvlookup_comms) = {
+        if let (true, Some(mvlookup_comms), Some(mvlookup_evals)) = (
+            uses_lookups,
+            &proof_comms.mvlookup_comms,
+            &proof_evals.mvlookup_evals,
+        ) {
             // First, we absorb the multiplicity polynomials
             mvlookup_comms
                 .m
                 .values()
                 .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
+            // Then the columns of the plookup engine committed before the
+            // challenges
+            mvlookup_comms
+                .plookup
+                .values()
+                .flat_map(|comms| comms.first_phase())
+                .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
 
             // To generate the challenges
             let joint_combiner = fq_sponge.challenge();
             let beta = fq_sponge.challenge();
+            let gamma = if mvlookup_comms.plookup.is_empty() {
+                G::ScalarField::zero()
+            } else {
+                fq_sponge.challenge()
+            };
+
+            // When global tables are used, the tables of the plookup engine
+            // must be the first column of their global table
+            if let Some(global_tables) = global_tables {
+                for (id, comms) in mvlookup_comms.plookup.iter() {
+                    match global_tables.tables.get(id) {
+                        None => return Err(VerifierError::MissingGlobalTable(id.to_u32())),
+                        Some(columns) if columns.first() != Some(&comms.table) => {
+                            return Err(VerifierError::PlookupTableMismatch(id.to_u32()))
+                        }
+                        Some(_) => (),
+                    }
+                }
+            }
+
+            // When global tables are used, the commitments to the fixed
+            // tables are derived from them, for each table with multiplicities.
+            let mvlookup_comms = match global_tables {
+                None => mvlookup_comms.clone(),
+                Some(global_tables) => {
+                    let unit_comm = srs.commit_evaluations_non_hiding(
+                        domain.d1,
+                        &Evaluations::from_vec_and_domain(
+                            vec![G::ScalarField::one(); domain.d1.size as usize],
+                            domain.d1,
+                        ),
+                    );
+                    // A table not being part of the global set is a failure
+                    let fixed_tables = mvlookup_comms
+                        .m
+                        .keys()
+                        .map(|id| {
+                            global_tables
+                                .combined_commitment(id, joint_combiner, &unit_comm)
+                                .map(|comm| (*id, comm))
+                                .ok_or(VerifierError::MissingGlobalTable(id.to_u32()))
+                        })
+                        .collect::<Result<_, _>>()?;
+                    LookupProof {
+                        fixed_tables,
+                        ..mvlookup_comms.clone()
+                    }
+                }
+            };
 
             // And now, we absorb the commitments to the other polynomials
-            mvlookup_comms
-                .h
-                .iter()
-                .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
+            let absorb_columns = |fq_sponge: &mut CountingSponge<Transcript<G, EFqSponge>>| {
+                mvlookup_comms
+                    .h
+                    .iter()
+                    .for_each(|comm| absorb_commitment(fq_sponge, comm));
+
+                mvlookup_comms
+                    .fixed_tables
+                    .values()
+                    .for_each(|comm| absorb_commitment(fq_sponge, comm));
+            };
+            if lookup_absorption == LookupAbsorptionOrder::ColumnsFirst {
+                absorb_columns(&mut fq_sponge);
+            }
 
+            // And at the end, the aggregation, or the claimed sum which is
+            // checked outside of the proof, unless it is absorbed first
+            match &mvlookup_comms.sum {
+                LookupAggregation::Committed(comm) => {
+                    absorb_commitment(&mut fq_sponge, comm);
+                    if let Some(boundary) = accumulator {
+                        fq_sponge.absorb_fr(&[boundary.incoming, boundary.outgoing]);
+                    }
+                }
+                LookupAggregation::Claimed(sum) => fq_sponge.absorb_fr(&[*sum]),
+            }
+            if lookup_absorption == LookupAbsorptionOrder::AggregationFirst {
+                absorb_columns(&mut fq_sponge);
+            }
+            // The grand products of the plookup engine come last
             mvlookup_comms
-                .fixed_tables
+                .plookup
                 .values()
-                .for_each(|comm| absorb_commitment(&mut fq_sponge, comm));
-
-            // And at the end, the aggregation
-            absorb_commitment(&mut fq_sponge, &mvlookup_comms.sum);
-            (Some(joint_combiner), beta)
+                .for_each(|comms| absorb_commitment(&mut fq_sponge, &comms.product));
+            // With global tables, the evaluations must be given for the
+            // fixed table of each table with multiplicities
+            if !mvlookup_comms.has_shape_of(mvlookup_evals) {
+                return Err(VerifierError::MalformedProof(
+                    "the evaluations of the fixed tables do not match their commitments",
+                ));
+            }
+            (
+                Some(joint_combiner),
+                beta,
+                gamma,
+                Some((mvlookup_comms, mvlookup_evals)),
+            )
         } else {
-            (None, G::ScalarField::zero())
+            (None, G::ScalarField::zero(), G::ScalarField::zero(), None)
         }
     };
 
+    #[cfg(test)]
+    if crate::transcript::test_hooks::extra_absorption() {
+        fq_sponge.absorb_fr(&[G::ScalarField::zero()]);
+    }
+
     //~ 1. Sample $\alpha'$ with the Fq-Sponge.
     let alpha_chal = ScalarChallenge(fq_sponge.challenge());
     let (_, endo_r) = G::endos();
@@ -144,11 +1708,22 @@ where
     absorb_commitment(&mut fq_sponge, &proof_comms.t_comm);
 
     // -- Preparing for opening proof verification
-    let zeta_chal = ScalarChallenge(fq_sponge.challenge());
-    let (_, endo_r) = G::endos();
-    let zeta: G::ScalarField = zeta_chal.to_field(endo_r);
-    let omega = domain.d1.group_gen;
-    let zeta_omega = zeta * omega;
+    // The constraints are evaluated at ζ by dividing by the vanishing
+    // polynomial and by ζ - ω^i for the Lagrange bases, which are zero if ζ is
+    // in the domain
+    let zeta: G::ScalarField = degenerate_point_rule
+        .derive_evaluation_point(domain, || {
+            ScalarChallenge(fq_sponge.challenge()).to_field(endo_r)
+        })
+        .ok_or(VerifierError::DegenerateEvaluationPoint)?;
+    let evaluation_points = Eval::OPENING_MODE.evaluation_points(zeta, domain);
+    // The only copy of the evaluations, into the batch of the opening proof
+    let to_evaluations = |evals: &Eval| {
+        let mut evaluations = Vec::with_capacity(evaluation_points.len());
+        evals.for_each_point(|x| evaluations.push(vec![*x]));
+        perf.record_evaluation_copies(evaluations.len());
+        evaluations
+    };
 
     let mut coms_and_evaluations: Vec<Evaluation<_>> = vec![];
 
@@ -156,20 +1731,20 @@ where
         (&proof_comms.witness_comms)
             .into_iter()
             .zip(&proof_evals.witness_evals)
-            .map(|(commitment, point_eval)| Evaluation {
+            .map(|(commitment, evals)| Evaluation {
                 commitment: commitment.clone(),
-                evaluations: vec![vec![point_eval.zeta], vec![point_eval.zeta_omega]],
+                evaluations: to_evaluations(evals),
             }),
     );
 
-    if let Some(mvlookup_comms) = &proof_comms.mvlookup_comms {
+    if let Some((mvlookup_comms, mvlookup_evals)) = &mvlookup_comms {
         coms_and_evaluations.extend(
             mvlookup_comms
                 .into_iter()
-                .zip(proof_evals.mvlookup_evals.as_ref().unwrap())
-                .map(|(commitment, point_eval)| Evaluation {
+                .zip(*mvlookup_evals)
+                .map(|(commitment, evals)| Evaluation {
                     commitment: commitment.clone(),
-                    evaluations: vec![vec![point_eval.zeta], vec![point_eval.zeta_omega]],
+                    evaluations: to_evaluations(evals),
                 })
                 .collect::<Vec<_>>(),
         );
@@ -177,28 +1752,33 @@ where
 
     // -- Absorb all coms_and_evaluations
     let fq_sponge_before_coms_and_evaluations = fq_sponge.clone();
-    let mut fr_sponge = EFrSponge::new(G::sponge_params());
+    let mut fr_sponge = CountingFrSponge::new(
+        EFrSponge::new(G::sponge_params()),
+        transcript_counter.clone(),
+    );
     fr_sponge.absorb(&fq_sponge.digest());
 
-    for PointEvaluations { zeta, zeta_omega } in (&proof_evals.witness_evals).into_iter() {
-        fr_sponge.absorb(zeta);
-        fr_sponge.absorb(zeta_omega);
+    for evals in (&proof_evals.witness_evals).into_iter() {
+        evals.for_each_point(|x| fr_sponge.absorb(x));
     }
-    if proof_comms.mvlookup_comms.is_some() {
+    if let Some((_, mvlookup_evals)) = &mvlookup_comms {
         // MVLookup FS
-        for PointEvaluations { zeta, zeta_omega } in
-            proof_evals.mvlookup_evals.as_ref().unwrap().into_iter()
-        {
-            fr_sponge.absorb(zeta);
-            fr_sponge.absorb(zeta_omega);
+        for evals in mvlookup_evals.into_iter() {
+            evals.for_each_point(|x| fr_sponge.absorb(x));
         }
     };
+    proof_evals
+        .shifted_evals
+        .values()
+        .for_each(|x| fr_sponge.absorb(x));
 
     // Compute [ft(X)] = \
     //   (1 - ζ^n) \
     //    ([t_0(X)] + ζ^n [t_1(X)] + ... + ζ^{kn} [t_{k}(X)])
     let ft_comm = {
         let evaluation_point_to_domain_size = zeta.pow([domain.d1.size]);
+        // One scalar multiplication per chunk, and one for the scaling
+        perf.record_scalar_muls(proof_comms.t_comm.elems.len() + 1);
         let chunked_t_comm = proof_comms
             .t_comm
             .chunk_commitment(evaluation_point_to_domain_size);
@@ -208,7 +1788,7 @@ where
     let challenges = Challenges {
         alpha,
         beta,
-        gamma: G::ScalarField::zero(),
+        gamma,
         joint_combiner,
     };
 
@@ -220,22 +1800,46 @@ where
 
     let combined_expr =
         Expr::combine_constraints(0..(constraints.len() as u32), constraints.clone());
+    let flat_evals = match evaluation_layout {
+        EvaluationLayout::PerColumn => None,
+        EvaluationLayout::Flat => Some(FlatEvaluations::new(proof_evals, perf)),
+    };
     let ft_eval0 = -PolishToken::evaluate(
         combined_expr.to_polish().as_slice(),
         domain.d1,
         zeta,
-        proof_evals,
+        &ShiftedEvaluations::new(
+            &EvaluationsWithChallenges {
+                proof_evals,
+                flat_evals: flat_evals.as_ref(),
+                extra_challenges: &extra_challenges,
+                zeta,
+                domain: domain.d1,
+                perf,
+            },
+            &proof_evals.shifted_evals,
+        ),
         &constants,
         &challenges,
     )
-    .unwrap();
+    .map_err(|err| match err {
+        ExprError::MissingLookupTable(id) => VerifierError::MissingLookupTable(id),
+        err => VerifierError::ConstraintEvaluation(err.to_string()),
+    })?;
 
+    // The evaluation of ft at ζω is given if and only if ζω is opened
+    let ft_evals = match (evaluation_points.len(), proof_evals.ft_eval1) {
+        (1, None) => vec![vec![ft_eval0]],
+        (2, Some(ft_eval1)) => {
+            fr_sponge.absorb(&ft_eval1);
+            vec![vec![ft_eval0], vec![ft_eval1]]
+        }
+        _ => return Err(VerifierError::Config(ConfigError::OpeningModeMismatch)),
+    };
     coms_and_evaluations.push(Evaluation {
         commitment: ft_comm,
-        evaluations: vec![vec![ft_eval0], vec![proof_evals.ft_eval1]],
+        evaluations: ft_evals,
     });
-
-    fr_sponge.absorb(&proof_evals.ft_eval1);
     // -- End absorb all coms_and_evaluations
 
     let v_chal = fr_sponge.challenge();
@@ -243,25 +1847,70 @@ where
     let u_chal = fr_sponge.challenge();
     let u = u_chal.to_field(endo_r);
 
-    let combined_inner_product = {
-        let es: Vec<_> = coms_and_evaluations
-            .iter()
-            .map(|Evaluation { evaluations, .. }| evaluations.clone())
-            .collect();
+    // The opening proof is not part of the transcript profile
+    transcript_counter.stop();
+    if STRICT_TRANSCRIPT {
+        let expected = TranscriptLayout::of_proof(proof, K, global_tables.is_some()).profile();
+        let got = transcript_counter.profile();
+        if got != expected {
+            return Err(VerifierError::TranscriptProfileMismatch { expected, got });
+        }
+    }
 
-        combined_inner_product(&v, &u, es.as_slice())
-    };
+    let combined_inner_product = borrowed_combined_inner_product(v, u, &coms_and_evaluations);
 
-    let batch = BatchEvaluationProof {
+    // The opening proof combines the commitments in a single MSM
+    perf.record_msm(
+        coms_and_evaluations
+            .iter()
+            .map(|eval| eval.commitment.elems.len())
+            .sum(),
+    );
+
+    let mut batches = vec![];
+    // The columns read two rows ahead are opened at ζω² with the same
+    // challenges, see [crate::shifts]
+    if let Some(shifted_opening) = shifted_opening {
+        let shifted_evaluations: Vec<Evaluation<_>> = shifted_comms
+            .into_iter()
+            .map(|(commitment, eval)| Evaluation {
+                commitment: commitment.clone(),
+                evaluations: vec![vec![eval]],
+            })
+            .collect();
+        perf.record_msm(shifted_evaluations.len());
+        batches.push(BatchEvaluationProof {
+            sponge: fq_sponge_before_coms_and_evaluations.clone(),
+            combined_inner_product: borrowed_combined_inner_product(v, u, &shifted_evaluations),
+            evaluations: shifted_evaluations,
+            evaluation_points: vec![shifts::shifted_point(zeta, domain.d1)],
+            polyscale: v,
+            evalscale: u,
+            opening: shifted_opening,
+        });
+    }
+    batches.push(BatchEvaluationProof {
         sponge: fq_sponge_before_coms_and_evaluations,
         evaluations: coms_and_evaluations,
-        evaluation_points: vec![zeta, zeta_omega],
+        evaluation_points,
         polyscale: v,
         evalscale: u,
         opening: opening_proof,
         combined_inner_product,
-    };
+    });
 
     let group_map = G::Map::setup();
-    OpeningProof::verify(srs, &group_map, &mut [batch], &mut thread_rng())
+    if OpeningProof::verify(srs, &group_map, &mut batches, &mut thread_rng()) {
+        Ok(VerifierChallenges {
+            joint_combiner,
+            beta,
+            gamma,
+            alpha,
+            zeta,
+            v,
+            u,
+        })
+    } else {
+        Err(VerifierError::OpeningProofFailed)
+    }
 }
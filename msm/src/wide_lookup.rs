@@ -0,0 +1,396 @@
+//! Lookups of wide tuples split into several narrower lookups.
+//!
+//! A lookup of a tuple of `w` values combines them with `w` powers of the
+//! joint combiner. [LookupSplit] splits the tuple into parts of at most
+//! `max_width` values, each part being looked up in a sub-table: the
+//! projection of the original table on the columns of the part. The rows of
+//! the sub-tables are prefixed with their index in the original table, and the
+//! lookups of the parts with the same index column, which links the parts to
+//! the same row of the original table.
+//!
+//! The sub-tables must be registered under their own IDs, derived from the
+//! ID of the original table, see [LookupSplit::new]. They are padded to the
+//! domain size with the index of the row and zeroes, so that the padding rows
+//! of the parts can only be linked to each other, as for a table padded with
+//! zeroes.
+//! A split handles one lookup of the original table per row, with one index
+//! column.
+
+use ark_ff::{Field, PrimeField};
+use std::{collections::BTreeMap, ops::Range};
+use thiserror::Error;
+
+use crate::{
+    columns::Column,
+    expr::{curr_cell, E},
+    mvlookup::{LookupTable, LookupTableID, MVLookup, MVLookupWitness},
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SplitError {
+    #[error("the lookup has {got} values, but the table has {expected} columns")]
+    WidthMismatch { expected: usize, got: usize },
+
+    #[error("the lookup is not into the table {0}")]
+    TableMismatch(u32),
+
+    #[error("the tuple of the row {0} is not in the table")]
+    NotInTable(usize),
+
+    #[error("the table has {got} rows, more than the domain size {domain_size}")]
+    TableTooLarge { domain_size: usize, got: usize },
+}
+
+/// The lookups of the parts of a lookup, by sub-table
+pub type SplitLookups<F, ID> = BTreeMap<ID, Vec<MVLookup<E<F>, ID>>>;
+
+/// The witness built by [LookupSplit::witness]
+#[derive(Debug, Clone)]
+pub struct SplitWitness<F, ID: LookupTableID> {
+    /// The index column
+    pub index: Vec<F>,
+    /// The columns of the looked-up tuples
+    pub values: Vec<Vec<F>>,
+    /// The witnesses of the lookups of the parts, in order
+    pub mvlookups: Vec<MVLookupWitness<F, ID>>,
+}
+
+/// The split of the lookups of a table of `width` columns, see the module
+/// documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupSplit<ID> {
+    table_id: ID,
+    width: usize,
+    index_column: usize,
+    /// The sub-tables, with the columns of the original table they contain
+    parts: Vec<(ID, Range<usize>)>,
+}
+
+impl<ID: LookupTableID> LookupSplit<ID> {
+    /// Split the table `table_id` of `width` columns in parts of at most
+    /// `max_width` columns, the part `k` being registered under the ID
+    /// `derive_id(k)`. The index of the rows is in the column
+    /// `X(index_column)`.
+    pub fn new(
+        table_id: ID,
+        width: usize,
+        max_width: usize,
+        index_column: usize,
+        derive_id: impl Fn(usize) -> ID,
+    ) -> Self {
+        assert!(max_width > 0, "the parts can not be empty");
+        let parts = (0..width)
+            .step_by(max_width)
+            .enumerate()
+            .map(|(k, start)| (derive_id(k), start..std::cmp::min(start + max_width, width)))
+            .collect();
+        LookupSplit {
+            table_id,
+            width,
+            index_column,
+            parts,
+        }
+    }
+
+    /// The IDs of the sub-tables, in order
+    pub fn part_ids(&self) -> Vec<ID> {
+        self.parts.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Returns the lookups of the parts of `lookup`, which must be a lookup
+    /// into the original table
+    pub fn split_lookup<F: Field>(
+        &self,
+        lookup: &MVLookup<E<F>, ID>,
+    ) -> Result<SplitLookups<F, ID>, SplitError> {
+        if lookup.table_id != self.table_id {
+            return Err(SplitError::TableMismatch(lookup.table_id.to_u32()));
+        }
+        if lookup.value.len() != self.width {
+            return Err(SplitError::WidthMismatch {
+                expected: self.width,
+                got: lookup.value.len(),
+            });
+        }
+        Ok(self
+            .parts
+            .iter()
+            .map(|(id, range)| {
+                let mut value = vec![curr_cell(Column::X(self.index_column))];
+                value.extend_from_slice(&lookup.value[range.clone()]);
+                (
+                    *id,
+                    vec![MVLookup::new(*id, lookup.numerator.clone(), &value)],
+                )
+            })
+            .collect())
+    }
+
+    /// Returns the sub-tables of `table`, without padding
+    pub fn sub_tables<F: Field>(&self, table: &LookupTable<F, ID>) -> Vec<LookupTable<F, ID>> {
+        self.parts
+            .iter()
+            .map(|(id, range)| LookupTable {
+                table_id: *id,
+                entries: table
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| sub_row(i, &entry[range.clone()]))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Returns the row of `table` of each of the `tuples`
+    pub fn rows_of<F: PrimeField>(
+        &self,
+        table: &LookupTable<F, ID>,
+        tuples: &[Vec<F>],
+    ) -> Result<Vec<usize>, SplitError> {
+        let rows: BTreeMap<&Vec<F>, usize> = table
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry, i))
+            .collect();
+        tuples
+            .iter()
+            .enumerate()
+            .map(|(j, tuple)| rows.get(tuple).copied().ok_or(SplitError::NotInTable(j)))
+            .collect()
+    }
+
+    /// Returns the index column, the looked-up tuples and the lookups of the
+    /// parts, each row `j` of the domain looking up the row `rows[j]` of
+    /// `table` with the numerator one
+    pub fn witness<F: PrimeField>(
+        &self,
+        table: &LookupTable<F, ID>,
+        rows: &[usize],
+    ) -> Result<SplitWitness<F, ID>, SplitError> {
+        let domain_size = rows.len();
+        if table.entries.len() > domain_size {
+            return Err(SplitError::TableTooLarge {
+                domain_size,
+                got: table.entries.len(),
+            });
+        }
+        if let Some(j) = rows.iter().position(|row| *row >= table.entries.len()) {
+            return Err(SplitError::NotInTable(j));
+        }
+        let mut m = vec![F::zero(); domain_size];
+        for row in rows {
+            m[*row] += F::one();
+        }
+        let index = rows.iter().map(|row| F::from(*row as u64)).collect();
+        let values = (0..self.width)
+            .map(|i| rows.iter().map(|row| table.entries[*row][i]).collect())
+            .collect();
+        let zeroes = vec![F::zero(); self.width];
+        let mvlookups = self
+            .parts
+            .iter()
+            .map(|(id, range)| {
+                let lookups = rows
+                    .iter()
+                    .map(|row| {
+                        MVLookup::new(
+                            *id,
+                            F::one(),
+                            &sub_row(*row, &table.entries[*row][range.clone()]),
+                        )
+                    })
+                    .collect();
+                let sub_table = (0..domain_size)
+                    .map(|i| {
+                        let entry = table.entries.get(i).unwrap_or(&zeroes);
+                        MVLookup::new(*id, -m[i], &sub_row(i, &entry[range.clone()]))
+                    })
+                    .collect();
+                MVLookupWitness {
+                    f: vec![lookups, sub_table],
+                    m: m.clone(),
+                }
+            })
+            .collect();
+        Ok(SplitWitness {
+            index,
+            values,
+            mvlookups,
+        })
+    }
+}
+
+/// The row `i` of a sub-table, made of its index and the values `values`
+fn sub_row<F: Field>(i: usize, values: &[F]) -> Vec<F> {
+    let mut row = vec![F::from(i as u64)];
+    row.extend_from_slice(values);
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LookupSplit, SplitError};
+    use crate::{
+        columns::Column,
+        expr::{curr_cell, E},
+        mvlookup::{constraint_lookups, LookupTable, LookupTableID, MVLookup},
+        proof::ProofInputs,
+        prover::{prove, ProverError},
+        verifier::verify,
+        witness::Witness,
+        BN254G1Affine, BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{One, UniformRand};
+    use kimchi::circuits::domains::EvaluationDomains;
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::Rng;
+
+    const WIDTH: usize = 10;
+    const N: usize = WIDTH + 1;
+    const DOMAIN_SIZE: usize = 1 << 5;
+
+    /// A wide table, and its parts
+    #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    enum Tables {
+        Wide,
+        Part(u32),
+    }
+
+    impl LookupTableID for Tables {
+        fn to_u32(&self) -> u32 {
+            match self {
+                Tables::Wide => 1,
+                Tables::Part(k) => 2 + k,
+            }
+        }
+
+        fn from_u32(value: u32) -> Self {
+            match value {
+                1 => Tables::Wide,
+                k => Tables::Part(k - 2),
+            }
+        }
+
+        fn is_fixed(&self) -> bool {
+            true
+        }
+
+        fn length(&self) -> usize {
+            20
+        }
+    }
+
+    #[test]
+    fn test_split_wide_lookup() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+        let mut srs: PairingSRS<BN254> =
+            PairingSRS::create(Fp::rand(&mut rng), domain.d1.size as usize);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+
+        let table = LookupTable {
+            table_id: Tables::Wide,
+            entries: (0..Tables::Wide.length())
+                .map(|_| (0..WIDTH).map(|_| Fp::rand(&mut rng)).collect())
+                .collect(),
+        };
+        let split = LookupSplit::new(Tables::Wide, WIDTH, 5, WIDTH, |k| Tables::Part(k as u32));
+        assert_eq!(split.part_ids(), vec![Tables::Part(0), Tables::Part(1)]);
+        let sub_tables = split.sub_tables(&table);
+        assert_eq!(sub_tables[1].entries[3][0], Fp::from(3u64));
+        assert_eq!(sub_tables[1].entries[3][1..], table.entries[3][5..]);
+
+        assert_eq!(
+            split
+                .split_lookup::<Fp>(&MVLookup::new(Tables::Wide, E::one(), &[]))
+                .unwrap_err(),
+            SplitError::WidthMismatch {
+                expected: WIDTH,
+                got: 0
+            }
+        );
+        let lookup = MVLookup::new(
+            Tables::Wide,
+            E::one(),
+            &(0..WIDTH)
+                .map(|i| curr_cell(Column::X(i)))
+                .collect::<Vec<_>>(),
+        );
+        let constraints = constraint_lookups(&split.split_lookup(&lookup).unwrap());
+
+        let rows: Vec<usize> = (0..DOMAIN_SIZE)
+            .map(|_| rng.gen_range(0..table.entries.len()))
+            .collect();
+        let tuples: Vec<Vec<Fp>> = rows.iter().map(|row| table.entries[*row].clone()).collect();
+        assert_eq!(split.rows_of(&table, &tuples), Ok(rows.clone()));
+        let witness = split.witness(&table, &rows).unwrap();
+        let inputs = |index: Vec<Fp>, values: Vec<Vec<Fp>>, mvlookups| {
+            let mut cols = values;
+            cols.push(index);
+            ProofInputs::<N, BN254G1Affine, Tables> {
+                evaluations: Witness {
+                    cols: Box::new(cols.try_into().unwrap()),
+                },
+                mvlookups,
+            }
+        };
+        let proof = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, Tables>(
+            domain,
+            &srs,
+            &constraints,
+            inputs(
+                witness.index.clone(),
+                witness.values.clone(),
+                witness.mvlookups.clone(),
+            ),
+            &mut rng,
+        )
+        .unwrap();
+        assert!(verify::<
+            _,
+            OpeningProof,
+            BaseSponge,
+            ScalarSponge,
+            N,
+            0,
+            Tables,
+        >(
+            domain,
+            &srs,
+            &constraints,
+            &proof,
+            Witness::zero_vec(DOMAIN_SIZE),
+        ));
+
+        // The first part of the tuple of the row 3 comes from another row of
+        // the table than the second part. Both parts are in their sub-table,
+        // but they are not linked by the index.
+        let mut other_rows = rows.clone();
+        other_rows[3] = (rows[3] + 1) % table.entries.len();
+        let mut mixed_tuple = table.entries[rows[3]][..5].to_vec();
+        mixed_tuple.extend_from_slice(&table.entries[other_rows[3]][5..]);
+        let mut tuples = tuples;
+        tuples[3] = mixed_tuple;
+        assert_eq!(
+            split.rows_of(&table, &tuples),
+            Err(SplitError::NotInTable(3))
+        );
+        let other_witness = split.witness(&table, &other_rows).unwrap();
+        let mut values = witness.values;
+        values[5..].clone_from_slice(&other_witness.values[5..]);
+        let mvlookups = vec![
+            witness.mvlookups[0].clone(),
+            other_witness.mvlookups[1].clone(),
+        ];
+        let res = prove::<_, OpeningProof, BaseSponge, ScalarSponge, Column, _, N, Tables>(
+            domain,
+            &srs,
+            &constraints,
+            inputs(witness.index, values, mvlookups),
+            &mut rng,
+        );
+        assert!(matches!(res, Err(ProverError::ConstraintNotSatisfied(_))));
+    }
+}
@@ -0,0 +1,372 @@
+//! Proof inputs restricted to a window of rows, to prove a region of a large
+//! witness on its own, e.g. to bisect the rows breaking a constraint with
+//! [crate::dry_run::dry_run_prove], see [ProofInputs::window].
+//!
+//! The rows of the window are moved to the first rows of a smaller domain,
+//! and the rows after them are padding rows, like the rows added by
+//! [ProofInputs::pad]: the witness cells are zero, and the lookups are the
+//! no-op lookups of [MVLookup::noop].
+//! The domain must have more rows than the window: the next row of the last
+//! row of the window is a padding row, and not the first row of the window.
+//! A constraint reading the next row is then evaluated on the last row of the
+//! window with the padding as its next row, and may fail there even if the
+//! rows of the window are correct in the original witness.
+//!
+//! The lookups issued outside of the window are dropped, and the
+//! multiplicities of the fixed tables are computed again from the lookups of
+//! the window. The fixed tables keep their rows, which are not rows of the
+//! trace: their first rows must fit in the smaller domain. The lookups into
+//! the other tables are restricted to the window like the witness columns.
+
+use ark_ff::{PrimeField, Zero};
+use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve};
+use std::{collections::HashMap, ops::Range};
+use thiserror::Error;
+
+use crate::{
+    mvlookup::{LookupTableID, MVLookup, MVLookupWitness},
+    proof::ProofInputs,
+    witness::Witness,
+};
+
+/// Errors that can arise when restricting proof inputs to a window, see
+/// [ProofInputs::window]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum WindowError {
+    #[error("the window {start}..{end} is empty")]
+    EmptyWindow { start: usize, end: usize },
+
+    #[error("the window ends at row {end}, after the {rows} rows of the witness")]
+    OutOfBounds { end: usize, rows: usize },
+
+    #[error(
+        "the window of {rows} rows needs a domain of more than {rows} rows, got {domain_size}"
+    )]
+    DomainTooSmall { rows: usize, domain_size: usize },
+
+    #[error("the row {row} of the table {table_id} is looked up in the window, and does not fit in the domain of {domain_size} rows")]
+    TableTooLarge {
+        table_id: u32,
+        row: usize,
+        domain_size: usize,
+    },
+}
+
+impl<const N: usize, G: KimchiCurve, ID: LookupTableID> ProofInputs<N, G, ID> {
+    /// The inputs made of the rows `range` of `self`, padded to the size of
+    /// `domain`, see the [module documentation](crate::window)
+    pub fn window(
+        &self,
+        range: Range<usize>,
+        domain: EvaluationDomains<G::ScalarField>,
+    ) -> Result<ProofInputs<N, G, ID>, WindowError> {
+        self.window_with_changes(range, domain)
+            .map(|(inputs, _)| inputs)
+    }
+
+    /// Like [ProofInputs::window], and return as well the fixed tables whose
+    /// multiplicities differ from the ones of `self`, i.e. the tables looked
+    /// up outside of the window
+    pub fn window_with_changes(
+        &self,
+        range: Range<usize>,
+        domain: EvaluationDomains<G::ScalarField>,
+    ) -> Result<(ProofInputs<N, G, ID>, Vec<ID>), WindowError> {
+        let domain_size = domain.d1.size as usize;
+        if range.is_empty() {
+            return Err(WindowError::EmptyWindow {
+                start: range.start,
+                end: range.end,
+            });
+        }
+        let rows = self
+            .evaluations
+            .cols
+            .iter()
+            .map(Vec::len)
+            .min()
+            .unwrap_or(0);
+        if range.end > rows {
+            return Err(WindowError::OutOfBounds {
+                end: range.end,
+                rows,
+            });
+        }
+        if range.len() >= domain_size {
+            return Err(WindowError::DomainTooSmall {
+                rows: range.len(),
+                domain_size,
+            });
+        }
+
+        let cols: [Vec<G::ScalarField>; N] = std::array::from_fn(|i| {
+            let mut column = self.evaluations.cols[i][range.clone()].to_vec();
+            column.resize(domain_size, G::ScalarField::zero());
+            column
+        });
+        let mut changed = vec![];
+        let mvlookups = self
+            .mvlookups
+            .iter()
+            .map(|lookup| {
+                let (windowed, table_id) = window_lookup(lookup, &range, domain_size)?;
+                if let Some(table_id) = table_id {
+                    changed.push(table_id);
+                }
+                Ok(windowed)
+            })
+            .collect::<Result<_, WindowError>>()?;
+        Ok((
+            ProofInputs {
+                evaluations: Witness {
+                    cols: Box::new(cols),
+                },
+                mvlookups,
+            },
+            changed,
+        ))
+    }
+}
+
+/// The rows `range` of the column `column`, padded with no-op lookups to
+/// `domain_size` rows
+fn window_column<F: PrimeField, ID: LookupTableID>(
+    column: &[MVLookup<F, ID>],
+    range: &Range<usize>,
+    domain_size: usize,
+) -> Result<Vec<MVLookup<F, ID>>, WindowError> {
+    let Some(table_id) = column.first().map(|lookup| lookup.table_id) else {
+        return Ok(vec![]);
+    };
+    let rows = column.get(range.clone()).ok_or(WindowError::OutOfBounds {
+        end: range.end,
+        rows: column.len(),
+    })?;
+    let mut windowed = rows.to_vec();
+    windowed.resize(domain_size, MVLookup::noop(table_id));
+    Ok(windowed)
+}
+
+/// Restrict `lookup` to the rows `range`, and return the ID of its table if
+/// it is a fixed table whose multiplicities have changed
+fn window_lookup<F: PrimeField, ID: LookupTableID>(
+    lookup: &MVLookupWitness<F, ID>,
+    range: &Range<usize>,
+    domain_size: usize,
+) -> Result<(MVLookupWitness<F, ID>, Option<ID>), WindowError> {
+    // The last column is the column of the table
+    let Some((table, queries)) = lookup.f.split_last() else {
+        return Ok((lookup.clone(), None));
+    };
+    let table_id = table.first().map(|entry| entry.table_id);
+    let mut f = queries
+        .iter()
+        .map(|column| window_column(column, range, domain_size))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let Some(table_id) = table_id.filter(ID::is_fixed) else {
+        f.push(window_column(table, range, domain_size)?);
+        let mut m = lookup.m.get(range.clone()).unwrap_or_default().to_vec();
+        m.resize(domain_size, F::zero());
+        return Ok((MVLookupWitness { f, m }, None));
+    };
+
+    // The multiplicities of the lookups of the window. A value in several
+    // rows of the table is counted in its first row, and a value out of the
+    // table does not have a multiplicity.
+    let mut table_rows: HashMap<&[F], usize> = HashMap::new();
+    for (i, entry) in table.iter().enumerate().rev() {
+        table_rows.insert(&entry.value, i);
+    }
+    let mut m = vec![F::zero(); domain_size];
+    for query in f.iter().flatten() {
+        if query.numerator.is_zero() {
+            continue;
+        }
+        if let Some(&row) = table_rows.get(&query.value[..]) {
+            let multiplicity = m.get_mut(row).ok_or(WindowError::TableTooLarge {
+                table_id: table_id.to_u32(),
+                row,
+                domain_size,
+            })?;
+            *multiplicity += query.numerator;
+        }
+    }
+    let mut windowed_table: Vec<MVLookup<F, ID>> =
+        table.iter().take(domain_size).cloned().collect();
+    windowed_table.resize(domain_size, MVLookup::noop(table_id));
+    for (entry, multiplicity) in windowed_table.iter_mut().zip(m.iter()) {
+        entry.numerator = -*multiplicity;
+    }
+    f.push(windowed_table);
+
+    let changed = (0..domain_size.max(lookup.m.len())).any(|i| {
+        lookup.m.get(i).copied().unwrap_or_default() != m.get(i).copied().unwrap_or_default()
+    });
+    Ok((MVLookupWitness { f, m }, changed.then_some(table_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        columns::Column, dry_run::dry_run_prove, expr::curr_cell, lookups::LookupTableIDs,
+        mvlookup::constraint_lookups, BN254G1Affine, BaseSponge, Fp, ScalarSponge,
+    };
+    use ark_ff::One;
+    use std::collections::BTreeMap;
+
+    // x[0] is the selector of the rows, x[1] and x[2] are looked up in the
+    // table of [0, 16), and x[3] = x[1] * x[2]
+    const N: usize = 4;
+    const TABLE_SIZE: usize = 16;
+    const DOMAIN_SIZE: usize = 1 << 8;
+    const TABLE_ID: LookupTableIDs = LookupTableIDs::Custom(0);
+
+    type Inputs = ProofInputs<N, BN254G1Affine, LookupTableIDs>;
+
+    fn circuit(defect: usize) -> (Vec<crate::expr::E<Fp>>, Inputs) {
+        let cell = |i| curr_cell::<Fp>(Column::X(i));
+        let lookups = BTreeMap::from([(
+            TABLE_ID,
+            vec![
+                MVLookup::new(TABLE_ID, cell(0), &[cell(1)]),
+                MVLookup::new(TABLE_ID, cell(0), &[cell(2)]),
+            ],
+        )]);
+        let mut constraints = constraint_lookups(&lookups);
+        constraints.push(cell(3) - cell(1) * cell(2));
+
+        let values: Vec<[u64; 2]> = (0..DOMAIN_SIZE as u64)
+            .map(|i| [(3 * i + 1) % 16, (5 * i + 7) % 16])
+            .collect();
+        let mut m = vec![Fp::zero(); DOMAIN_SIZE];
+        values
+            .iter()
+            .flatten()
+            .for_each(|x| m[*x as usize] += Fp::one());
+        let query = |k: usize| {
+            values
+                .iter()
+                .map(|value| MVLookup::new(TABLE_ID, Fp::one(), &[Fp::from(value[k])]))
+                .collect()
+        };
+        let table = (0..DOMAIN_SIZE)
+            .map(|i| {
+                let value = if i < TABLE_SIZE { i as u64 } else { 0 };
+                MVLookup::new(TABLE_ID, -m[i], &[Fp::from(value)])
+            })
+            .collect();
+        let mut cols = vec![vec![Fp::one(); DOMAIN_SIZE]];
+        cols.extend((0..2).map(|k| values.iter().map(|value| Fp::from(value[k])).collect()));
+        cols.push(
+            values
+                .iter()
+                .map(|value| Fp::from(value[0] * value[1]))
+                .collect(),
+        );
+        cols[3][defect] += Fp::one();
+        let inputs = ProofInputs {
+            evaluations: Witness {
+                cols: Box::new(cols.try_into().unwrap()),
+            },
+            mvlookups: vec![MVLookupWitness {
+                f: vec![query(0), query(1), table],
+                m,
+            }],
+        };
+        (constraints, inputs)
+    }
+
+    /// The domain of the windows of `rows` rows, with at least one padding row
+    fn window_domain(rows: usize) -> EvaluationDomains<Fp> {
+        EvaluationDomains::<Fp>::create(rows + 1).unwrap()
+    }
+
+    #[test]
+    fn test_window_bisects_a_defect() {
+        const DEFECT: usize = 137;
+        let (constraints, inputs) = circuit(DEFECT);
+        let is_broken = |inputs: Inputs, domain| {
+            dry_run_prove::<_, BaseSponge, ScalarSponge, N, LookupTableIDs>(
+                inputs,
+                &constraints,
+                domain,
+            )
+            .is_err()
+        };
+        let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+        assert!(is_broken(inputs.clone(), domain));
+
+        // The half of the rows which is broken is the one of the defect. The
+        // lookups of the other half are dropped, and the lookups of each
+        // window are balanced with the multiplicities computed again.
+        let mut range = 0..DOMAIN_SIZE;
+        while range.len() > 16 {
+            let middle = range.start + range.len() / 2;
+            let [left, right] = [range.start..middle, middle..range.end];
+            let domain = window_domain(left.len());
+            let broken = [&left, &right]
+                .map(|range| is_broken(inputs.window(range.clone(), domain).unwrap(), domain));
+            assert_eq!(broken, [left.contains(&DEFECT), right.contains(&DEFECT)]);
+            range = if broken[0] { left } else { right };
+        }
+        assert_eq!(range, 128..144);
+
+        let domain = window_domain(range.len());
+        let (window, changed) = inputs.window_with_changes(range.clone(), domain).unwrap();
+        assert_eq!(changed, vec![TABLE_ID]);
+        assert_eq!(domain.d1.size, 32);
+        let cols = &window.evaluations.cols;
+        assert_eq!(
+            cols[3][DEFECT - range.start],
+            inputs.evaluations.cols[3][DEFECT]
+        );
+        // The rows after the window are padding rows
+        assert!(cols
+            .iter()
+            .all(|column| column[16..].iter().all(Zero::is_zero)));
+        let lookup = &window.mvlookups[0];
+        assert!(lookup.f[..2]
+            .iter()
+            .flat_map(|column| &column[16..])
+            .all(|query| query.table_id == TABLE_ID && query.numerator.is_zero()));
+        assert_eq!(lookup.m.iter().sum::<Fp>(), Fp::from(32u64));
+    }
+
+    #[test]
+    fn test_window_errors() {
+        let (_, inputs) = circuit(0);
+        let domain = window_domain(16);
+        assert_eq!(
+            inputs.window(8..8, domain).unwrap_err(),
+            WindowError::EmptyWindow { start: 8, end: 8 }
+        );
+        assert_eq!(
+            inputs
+                .window(DOMAIN_SIZE - 8..DOMAIN_SIZE + 8, domain)
+                .unwrap_err(),
+            WindowError::OutOfBounds {
+                end: DOMAIN_SIZE + 8,
+                rows: DOMAIN_SIZE,
+            }
+        );
+        assert_eq!(
+            inputs.window(0..32, domain).unwrap_err(),
+            WindowError::DomainTooSmall {
+                rows: 32,
+                domain_size: 32,
+            }
+        );
+        // The value 10 is looked up at the row 3, and is in the row 10 of the
+        // table
+        assert_eq!(
+            inputs.window(0..4, window_domain(4)).unwrap_err(),
+            WindowError::TableTooLarge {
+                table_id: TABLE_ID.to_u32(),
+                row: 10,
+                domain_size: 8,
+            }
+        );
+    }
+}
@@ -1,6 +1,25 @@
-use ark_ff::Zero;
+use ark_ff::{PrimeField, Zero};
 use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 use std::ops::Index;
+use thiserror::Error;
+
+/// Errors that can arise when ingesting witness values
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum WitnessError {
+    #[error("the value at column {column}, row {row} is not a canonical field element")]
+    NonCanonical { column: usize, row: usize },
+
+    #[error("the encoding of the column {column} is not a multiple of {elem_size} bytes")]
+    TruncatedColumn { column: usize, elem_size: usize },
+
+    #[error("expected {expected} columns, got {got}")]
+    ColumnCountMismatch { expected: usize, got: usize },
+
+    #[error("the witness has {got} rows, more than the maximum of {max} rows of the circuit")]
+    TooManyRows { max: usize, got: usize },
+}
 
 /// The witness columns used by a gate of the MSM circuits.
 /// It is generic over the number of columns, N, and the type of the witness, T.
@@ -8,10 +27,13 @@ use std::ops::Index;
 /// - `Vec<G::ScalarField>` for the evaluations
 /// - `PolyComm<G>` for the commitments
 /// It can be used to represent the different subcircuits used by the project.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
 pub struct Witness<const N: usize, T> {
     /// A witness row is represented by an array of N witness columns
     /// When T is a vector, then the witness describes the rows of the circuit.
+    #[serde_as(as = "Box<[_; N]>")]
     pub cols: Box<[T; N]>,
 }
 
@@ -61,6 +83,67 @@ impl<const N: usize, T: Zero + Clone> Witness<N, Vec<T>> {
     }
 }
 
+impl<const N: usize, F: PrimeField> Witness<N, Vec<F>> {
+    /// Decode the witness from the encodings of its columns. Each column is
+    /// the concatenation of the canonical encodings of its values, as given by
+    /// [ark_serialize::CanonicalSerialize]. The values which are not reduced
+    /// modulo the field modulus are rejected, instead of being wrapped.
+    pub fn from_bytes(columns: &[&[u8]]) -> Result<Self, WitnessError> {
+        if columns.len() != N {
+            return Err(WitnessError::ColumnCountMismatch {
+                expected: N,
+                got: columns.len(),
+            });
+        }
+        let elem_size = F::zero().serialized_size();
+        let mut cols: [Vec<F>; N] = std::array::from_fn(|_| vec![]);
+        for (column, (bytes, col)) in columns.iter().zip(cols.iter_mut()).enumerate() {
+            if bytes.len() % elem_size != 0 {
+                return Err(WitnessError::TruncatedColumn { column, elem_size });
+            }
+            *col = bytes
+                .chunks(elem_size)
+                .enumerate()
+                .map(|(row, chunk)| {
+                    F::deserialize(chunk).map_err(|_| WitnessError::NonCanonical { column, row })
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(Witness {
+            cols: Box::new(cols),
+        })
+    }
+
+    /// Check that the witness has at most `max_rows` rows, see
+    /// [crate::config::ProtocolConfig::max_rows]
+    pub fn check_max_rows(&self, max_rows: usize) -> Result<(), WitnessError> {
+        let rows = self.cols.iter().map(|col| col.len()).max().unwrap_or(0);
+        if rows > max_rows {
+            return Err(WitnessError::TooManyRows {
+                max: max_rows,
+                got: rows,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the values of the witness are canonical field elements.
+    /// It is only useful for the witnesses built from the internal
+    /// representation of the field elements, as the arithmetic operations and
+    /// the decoding functions always return canonical values.
+    pub fn validate_canonical(&self) -> Result<(), WitnessError> {
+        for (column, col) in self.cols.iter().enumerate() {
+            if let Some(row) = col
+                .iter()
+                .position(|x| F::from_repr(x.into_repr()) != Some(*x))
+            {
+                return Err(WitnessError::NonCanonical { column, row });
+            }
+        }
+        Ok(())
+    }
+}
+
 // IMPLEMENTATION OF ITERATORS FOR THE WITNESS STRUCTURE
 
 impl<'lt, const N: usize, G> IntoIterator for &'lt Witness<N, G> {
@@ -143,3 +226,53 @@ where
         iter_contents.into_par_iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Witness, WitnessError};
+    use crate::Fp;
+    use ark_ff::{BigInteger, FpParameters, One, PrimeField, Zero};
+
+    type Params = <Fp as PrimeField>::Params;
+
+    #[test]
+    fn test_from_bytes_rejects_the_modulus() {
+        let zero = vec![0u8; 32];
+        let mut modulus_minus_one = Params::MODULUS;
+        modulus_minus_one.sub_noborrow(&Fp::one().into_repr());
+
+        // Just below the modulus
+        let bytes = [zero.clone(), modulus_minus_one.to_bytes_le()].concat();
+        let witness = Witness::<2, Vec<Fp>>::from_bytes(&[&zero, &bytes]).unwrap();
+        assert_eq!(witness.cols[1], vec![Fp::zero(), -Fp::one()]);
+
+        // The modulus itself
+        let bytes = [zero.clone(), Params::MODULUS.to_bytes_le()].concat();
+        let res = Witness::<2, Vec<Fp>>::from_bytes(&[&zero, &bytes]);
+        assert_eq!(res, Err(WitnessError::NonCanonical { column: 1, row: 1 }));
+
+        let res = Witness::<2, Vec<Fp>>::from_bytes(&[&zero, &bytes[..40]]);
+        assert_eq!(
+            res,
+            Err(WitnessError::TruncatedColumn {
+                column: 1,
+                elem_size: 32
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_canonical() {
+        let mut witness = Witness::<2, Vec<Fp>> {
+            cols: Box::new([vec![Fp::zero(), -Fp::one()], vec![Fp::one(); 2]]),
+        };
+        assert_eq!(witness.validate_canonical(), Ok(()));
+
+        // The internal representation is not reduced
+        witness.cols[1][0] = Fp::new(Params::MODULUS);
+        assert_eq!(
+            witness.validate_canonical(),
+            Err(WitnessError::NonCanonical { column: 1, row: 0 })
+        );
+    }
+}
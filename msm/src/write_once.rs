@@ -0,0 +1,523 @@
+//! Write-once enforcement for the keys of a dynamic table, i.e. each key (or
+//! address) of the table is written at most once.
+//!
+//! The keys are given in a witness column, one entry per row of the domain.
+//! Two methods are provided, see [WriteOnceMode]:
+//! - [WriteOnceMode::Sorted]: the prover commits to a sorted copy of the keys,
+//!   and the consecutive sorted keys are constrained to be strictly
+//!   increasing by range checking their differences minus one into an
+//!   existing table. The sorted column is a permutation of the keys, which is
+//!   checked with the running sum of `1 / (γ + key) - 1 / (γ + sorted)` over
+//!   the domain, `γ` being an extra challenge coined after the keys and the
+//!   sorted copy are committed. The difference of the last row, which wraps
+//!   around, is not constrained. It adds three columns, two constraints of
+//!   degree 3, and one lookup, of degree 1, per row.
+//! - [WriteOnceMode::Lookup]: the keys are looked up in a table of all the
+//!   possible keys, whose multiplicities are bounded by a table of two values,
+//!   i.e. each key is looked up at most once, see
+//!   [LookupTableID::multiplicity_bound]. It does not add any column or
+//!   constraint, but the possible keys must fit in the domain, and the table
+//!   can not be used by other lookups.
+//!
+//! The witness builders do not check the keys, and the proof of duplicated
+//! keys fails. Use [WriteOnce::check_keys] to find them beforehand.
+
+use ark_ff::{FftField, One, PrimeField};
+use kimchi::circuits::{
+    domains::EvaluationDomains,
+    expr::{ConstantExpr, ConstantTerm, ExprInner, RowOffset},
+};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::{
+    columns::Column,
+    expr::{curr_cell, next_cell, E},
+    mvlookup::{multiplicity_index, LookupTableID, MVLookup, MVLookupWitness},
+};
+
+/// How the keys are checked, see the module documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOnceMode<ID> {
+    Sorted {
+        /// The column of the sorted keys
+        sorted: usize,
+        /// The column of the differences of the consecutive sorted keys,
+        /// minus one
+        difference: usize,
+        /// The column of the running sum checking the permutation. It must
+        /// be committed after the extra challenge is coined.
+        accumulator: usize,
+        /// The index of the extra challenge `γ`
+        challenge: usize,
+        /// The table the differences are looked up into, containing
+        /// `[0, length)`
+        range_table: ID,
+    },
+    Lookup {
+        /// The table of the possible keys `[0, length)`, whose multiplicities
+        /// are bounded by a table of length 2
+        table_id: ID,
+    },
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum WriteOnceError {
+    #[error("the multiplicities of the table {0} are not bounded by a table of length 2")]
+    UnboundedTable(u32),
+
+    #[error("the rows {0} and {1} write the same key")]
+    DuplicateKey(usize, usize),
+
+    #[error("the key of the row {0} is not in the table of the possible keys")]
+    UnknownKey(usize),
+
+    #[error("the gap between the sorted keys {0} and {1} is too large for the range table")]
+    GapTooLarge(usize, usize),
+}
+
+/// The witness built by [WriteOnce::witness]
+#[derive(Debug, Clone)]
+pub struct WriteOnceWitness<F, ID: LookupTableID> {
+    /// The values of the columns added by the gadget, by index, without the
+    /// accumulator, see [WriteOnce::accumulator]
+    pub columns: BTreeMap<usize, Vec<F>>,
+    /// The witnesses of the lookups of the gadget
+    pub mvlookups: Vec<MVLookupWitness<F, ID>>,
+}
+
+/// A gadget enforcing that the keys of the column `X(key)` are pairwise
+/// distinct
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOnce<ID> {
+    key: usize,
+    mode: WriteOnceMode<ID>,
+}
+
+impl<ID: LookupTableID> WriteOnce<ID> {
+    pub fn new(key: usize, mode: WriteOnceMode<ID>) -> Result<Self, WriteOnceError> {
+        if let WriteOnceMode::Lookup { table_id } = mode {
+            if table_id.multiplicity_bound().map(|bound| bound.length()) != Some(2) {
+                return Err(WriteOnceError::UnboundedTable(table_id.to_u32()));
+            }
+        }
+        Ok(WriteOnce { key, mode })
+    }
+
+    pub fn mode(&self) -> &WriteOnceMode<ID> {
+        &self.mode
+    }
+
+    /// The lookups of the gadget, to be given to
+    /// [crate::mvlookup::constraint_lookups] with the other lookups of the
+    /// circuit
+    pub fn lookups<F: PrimeField>(&self) -> BTreeMap<ID, Vec<MVLookup<E<F>, ID>>> {
+        let (table_id, column) = match self.mode {
+            WriteOnceMode::Sorted {
+                difference,
+                range_table,
+                ..
+            } => (range_table, difference),
+            WriteOnceMode::Lookup { table_id } => (table_id, self.key),
+        };
+        BTreeMap::from([(
+            table_id,
+            vec![MVLookup::new(
+                table_id,
+                E::one(),
+                &[curr_cell(Column::X(column))],
+            )],
+        )])
+    }
+
+    /// The constraints of the gadget over `domain`, besides the lookups
+    pub fn constraints<F: FftField>(&self, domain: EvaluationDomains<F>) -> Vec<E<F>> {
+        let WriteOnceMode::Sorted {
+            sorted,
+            difference,
+            accumulator,
+            challenge,
+            ..
+        } = self.mode
+        else {
+            return vec![];
+        };
+        let literal = |x: F| {
+            E::Atom(ExprInner::Constant(ConstantExpr::from(
+                ConstantTerm::Literal(x),
+            )))
+        };
+        let key = curr_cell(Column::X(self.key));
+        let sorted_curr = curr_cell(Column::X(sorted));
+        let gamma = curr_cell(Column::ExtraChallenge(challenge));
+
+        // The unnormalized Lagrange polynomial of the last row is `n ω` on the
+        // last row, and zero on the other ones
+        let last_row = E::Atom(ExprInner::UnnormalizedLagrangeBasis(RowOffset {
+            zk_rows: false,
+            offset: -1,
+        }));
+        let scaling = (F::from(domain.d1.size) * domain.d1.group_gen)
+            .inverse()
+            .unwrap();
+        let not_last_row = E::one() - literal(scaling) * last_row;
+        let difference = not_last_row
+            * (next_cell(Column::X(sorted))
+                - sorted_curr.clone()
+                - E::one()
+                - curr_cell(Column::X(difference)));
+
+        // (φ(ωX) - φ(X)) (γ + key) (γ + sorted) = (γ + sorted) - (γ + key)
+        let permutation = (next_cell(Column::X(accumulator)) - curr_cell(Column::X(accumulator)))
+            * (gamma.clone() + key.clone())
+            * (gamma + sorted_curr.clone())
+            - sorted_curr
+            + key;
+        vec![difference, permutation]
+    }
+
+    /// Returns the first pair of rows writing the same key, or the first key
+    /// which the gadget can not handle
+    pub fn check_keys<F: PrimeField>(&self, keys: &[F]) -> Result<(), WriteOnceError> {
+        let mut rows: Vec<usize> = (0..keys.len()).collect();
+        rows.sort_by_key(|i| keys[*i]);
+        if let Some(pair) = rows.windows(2).find(|pair| keys[pair[0]] == keys[pair[1]]) {
+            let (i, j) = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+            return Err(WriteOnceError::DuplicateKey(i, j));
+        }
+        match self.mode {
+            WriteOnceMode::Sorted { range_table, .. } => {
+                match rows.windows(2).find(|pair| {
+                    let gap = keys[pair[1]] - keys[pair[0]] - F::one();
+                    multiplicity_index(&gap, range_table.length()).is_none()
+                }) {
+                    Some(pair) => Err(WriteOnceError::GapTooLarge(pair[0], pair[1])),
+                    None => Ok(()),
+                }
+            }
+            WriteOnceMode::Lookup { table_id } => {
+                match (0..keys.len())
+                    .find(|i| multiplicity_index(&keys[*i], table_id.length()).is_none())
+                {
+                    Some(i) => Err(WriteOnceError::UnknownKey(i)),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Returns the columns and the lookups of the gadget for the keys `keys`,
+    /// given for each row of `domain`
+    pub fn witness<F: PrimeField>(
+        &self,
+        domain: EvaluationDomains<F>,
+        keys: &[F],
+    ) -> WriteOnceWitness<F, ID> {
+        let domain_size = domain.d1.size as usize;
+        assert_eq!(keys.len(), domain_size);
+        // The lookups of `column` into the table `[0, length)`, padded with
+        // zeroes
+        let lookups = |table_id: ID, column: &[F]| {
+            let mut m = vec![F::zero(); domain_size];
+            let values = column
+                .iter()
+                .map(|x| {
+                    if let Some(i) = multiplicity_index(x, table_id.length()) {
+                        m[i] += F::one();
+                    }
+                    MVLookup::new(table_id, F::one(), &[*x])
+                })
+                .collect();
+            let table = (0..domain_size)
+                .map(|i| {
+                    let value = if i < table_id.length() { i as u64 } else { 0 };
+                    MVLookup::new(table_id, -m[i], &[F::from(value)])
+                })
+                .collect();
+            MVLookupWitness {
+                f: vec![values, table],
+                m,
+            }
+        };
+        match self.mode {
+            WriteOnceMode::Sorted {
+                sorted,
+                difference,
+                range_table,
+                ..
+            } => {
+                let mut sorted_keys = keys.to_vec();
+                sorted_keys.sort();
+                let mut differences: Vec<F> = sorted_keys
+                    .windows(2)
+                    .map(|pair| pair[1] - pair[0] - F::one())
+                    .collect();
+                // The last row is not constrained
+                differences.push(F::zero());
+                let mvlookups = vec![lookups(range_table, &differences)];
+                WriteOnceWitness {
+                    columns: BTreeMap::from([(sorted, sorted_keys), (difference, differences)]),
+                    mvlookups,
+                }
+            }
+            // The range checks of the multiplicities are added by the prover
+            WriteOnceMode::Lookup { table_id } => WriteOnceWitness {
+                columns: BTreeMap::new(),
+                mvlookups: vec![lookups(table_id, keys)],
+            },
+        }
+    }
+
+    /// Returns the running sum checking that `sorted` is a permutation of
+    /// `keys`, for the value `gamma` of the extra challenge. It starts at
+    /// zero, and wraps around to zero if and only if the columns are
+    /// permutations of each other.
+    pub fn accumulator<F: PrimeField>(&self, keys: &[F], sorted: &[F], gamma: F) -> Vec<F> {
+        let mut terms: Vec<F> = keys
+            .iter()
+            .zip(sorted)
+            .flat_map(|(key, sorted)| [gamma + key, gamma + sorted])
+            .collect();
+        ark_ff::fields::batch_inversion(&mut terms);
+        let mut accumulator = Vec::with_capacity(keys.len());
+        let mut acc = F::zero();
+        for pair in terms.chunks(2) {
+            accumulator.push(acc);
+            acc += pair[0] - pair[1];
+        }
+        accumulator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriteOnce, WriteOnceError, WriteOnceMode};
+    use crate::{
+        config::ProtocolConfig,
+        mvlookup::{
+            constraint_lookups, constraint_lookups_with_config, LookupAggregationMode,
+            LookupConstraintOptions, LookupTableID,
+        },
+        proof::ProofInputs,
+        prover::{
+            prove_with_context, prove_with_context_and_extra_challenges, ProverContext, ProverError,
+        },
+        verifier::VerifierContext,
+        witness::Witness,
+        BaseSponge, Fp, OpeningProof, ScalarSponge, BN254,
+    };
+    use ark_ff::{UniformRand, Zero};
+    use kimchi::{circuits::domains::EvaluationDomains, proof::PointEvaluations};
+    use poly_commitment::pairing_proof::PairingSRS;
+    use rand::seq::SliceRandom;
+
+    const DOMAIN_SIZE: usize = 1 << 5;
+
+    /// The possible keys, each written at most once, and the tables of the
+    /// differences of the sorted keys and of the multiplicities
+    #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    enum Tables {
+        Keys,
+        Once,
+        Gaps,
+    }
+
+    impl LookupTableID for Tables {
+        fn to_u32(&self) -> u32 {
+            *self as u32 + 1
+        }
+
+        fn from_u32(value: u32) -> Self {
+            Self::try_from_u32(value).unwrap_or_else(|| panic!("Invalid table ID {value}"))
+        }
+
+        fn try_from_u32(value: u32) -> Option<Self> {
+            match value {
+                1 => Some(Tables::Keys),
+                2 => Some(Tables::Once),
+                3 => Some(Tables::Gaps),
+                _ => None,
+            }
+        }
+
+        fn is_fixed(&self) -> bool {
+            true
+        }
+
+        fn length(&self) -> usize {
+            match self {
+                Tables::Keys => DOMAIN_SIZE,
+                Tables::Once => 2,
+                Tables::Gaps => 16,
+            }
+        }
+
+        fn multiplicity_bound(&self) -> Option<Self> {
+            match self {
+                Tables::Keys => Some(Tables::Once),
+                _ => None,
+            }
+        }
+    }
+
+    fn setup() -> (EvaluationDomains<Fp>, PairingSRS<BN254>) {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let domain = EvaluationDomains::<Fp>::create(DOMAIN_SIZE).unwrap();
+        let mut srs: PairingSRS<BN254> = PairingSRS::create(Fp::rand(&mut rng), DOMAIN_SIZE);
+        srs.full_srs.add_lagrange_basis(domain.d1);
+        (domain, srs)
+    }
+
+    #[test]
+    fn test_write_once_lookup() {
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, srs) = setup();
+        let gadget = WriteOnce::new(
+            0,
+            WriteOnceMode::Lookup {
+                table_id: Tables::Keys,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            WriteOnce::new(
+                0,
+                WriteOnceMode::Lookup {
+                    table_id: Tables::Gaps
+                }
+            )
+            .unwrap_err(),
+            WriteOnceError::UnboundedTable(3)
+        );
+        // The proof is created with a claimed sum of the lookup argument, as
+        // the prover only creates proofs with a committed running sum if the
+        // sum vanishes
+        let config = ProtocolConfig {
+            lookup_aggregation: LookupAggregationMode::Claimed,
+            ..Default::default()
+        };
+        let constraints = constraint_lookups_with_config(
+            &gadget.lookups(),
+            &LookupConstraintOptions::of_config(&config),
+        );
+
+        let ctx = ProverContext::new(domain, &srs, config, &constraints).unwrap();
+        let verifier = VerifierContext::from_prover_context(&ctx).unwrap();
+
+        let mut keys: Vec<Fp> = (0..DOMAIN_SIZE as u64).map(Fp::from).collect();
+        keys.shuffle(&mut rng);
+        let verify_keys = |keys: Vec<Fp>, rng: &mut rand::rngs::StdRng| {
+            let witness = gadget.witness(domain, &keys);
+            let inputs: ProofInputs<1, _, Tables> = ProofInputs {
+                evaluations: Witness {
+                    cols: Box::new([keys]),
+                },
+                mvlookups: witness.mvlookups,
+            };
+            let proof = prove_with_context::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                1,
+                Tables,
+                PointEvaluations<Fp>,
+            >(&ctx, inputs, rng)
+            .unwrap();
+            let res = verifier.verify::<BaseSponge, ScalarSponge, 1, 0, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(DOMAIN_SIZE),
+            );
+            res.is_ok() && proof.claimed_lookup_sum() == Some(Fp::zero())
+        };
+        assert_eq!(gadget.check_keys(&keys), Ok(()));
+        assert!(verify_keys(keys.clone(), &mut rng));
+
+        // Two entries with the same key: the multiplicity of the key is not
+        // in the bounding table
+        keys[7] = keys[3];
+        assert_eq!(
+            gadget.check_keys(&keys),
+            Err(WriteOnceError::DuplicateKey(3, 7))
+        );
+        assert!(!verify_keys(keys, &mut rng));
+    }
+
+    #[test]
+    fn test_write_once_sorted() {
+        const N: usize = 4;
+        let mut rng = o1_utils::tests::make_test_rng();
+        let (domain, srs) = setup();
+        let gadget = WriteOnce::new(
+            0,
+            WriteOnceMode::Sorted {
+                sorted: 1,
+                difference: 2,
+                accumulator: 3,
+                challenge: 0,
+                range_table: Tables::Gaps,
+            },
+        )
+        .unwrap();
+        let mut constraints = gadget.constraints(domain);
+        constraints.extend(constraint_lookups(&gadget.lookups()));
+        let ctx =
+            ProverContext::new(domain, &srs, ProtocolConfig::unsimplified(), &constraints).unwrap();
+
+        // Sparse keys, with gaps smaller than the range table
+        let mut keys: Vec<Fp> = (0..DOMAIN_SIZE as u64)
+            .map(|i| Fp::from(1000 + 7 * i))
+            .collect();
+        keys.shuffle(&mut rng);
+        // The witness of the gadget is built for `honest_keys`
+        let prove_keys = |keys: Vec<Fp>, honest_keys: &[Fp], rng: &mut rand::rngs::StdRng| {
+            let witness = gadget.witness(domain, honest_keys);
+            let sorted = witness.columns[&1].clone();
+            let first_phase = vec![keys.clone(), sorted.clone(), witness.columns[&2].clone()];
+            let second_phase = |[gamma]: &[Fp; 1]| {
+                let mut cols = first_phase.clone();
+                cols.push(gadget.accumulator(&keys, &sorted, *gamma));
+                ProofInputs {
+                    evaluations: Witness {
+                        cols: Box::new(cols.try_into().unwrap()),
+                    },
+                    mvlookups: witness.mvlookups.clone(),
+                }
+            };
+            prove_with_context_and_extra_challenges::<
+                _,
+                OpeningProof,
+                BaseSponge,
+                ScalarSponge,
+                _,
+                N,
+                1,
+                Tables,
+                PointEvaluations<Fp>,
+            >(&ctx, first_phase.clone(), second_phase, rng)
+        };
+        assert_eq!(gadget.check_keys(&keys), Ok(()));
+        let proof = prove_keys(keys.clone(), &keys, &mut rng).unwrap();
+        assert!(VerifierContext::from_prover_context(&ctx)
+            .unwrap()
+            .verify_with_extra_challenges::<BaseSponge, ScalarSponge, N, 0, 1, PointEvaluations<Fp>>(
+                &proof,
+                Witness::zero_vec(DOMAIN_SIZE),
+                3,
+            )
+            .is_ok());
+
+        // Two entries with the same key. The differences of the sorted keys
+        // can not be range checked, and the prover can only give the sorted
+        // keys without the duplicate, which are not a permutation of the keys
+        let honest_keys = keys.clone();
+        keys[7] = keys[3];
+        assert_eq!(
+            gadget.check_keys(&keys),
+            Err(WriteOnceError::DuplicateKey(3, 7))
+        );
+        let res = prove_keys(keys, &honest_keys, &mut rng);
+        assert!(matches!(res, Err(ProverError::ConstraintNotSatisfied(_))));
+    }
+}
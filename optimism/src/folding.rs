@@ -6,7 +6,7 @@ use kimchi::{
     circuits::{expr::ChallengeTerm, gate::CurrOrNext},
     folding::{Alphas, FoldingEnv, Instance, Side, Witness},
 };
-use kimchi_msm::witness::Witness as GenericWitness;
+use kimchi_msm::api::Witness as GenericWitness;
 use std::{array, ops::Index};
 
 use crate::DOMAIN_SIZE;
@@ -183,7 +183,7 @@ mod tests {
     #[derive(Clone, Debug, PartialEq, Eq, Hash)]
     struct TestConfig;
 
-    type TestWitness<T> = kimchi_msm::witness::Witness<3, T>;
+    type TestWitness<T> = kimchi_msm::api::Witness<3, T>;
     type TestFoldingWitness = FoldingWitness<3>;
     type TestFoldingInstance = FoldingInstance<3>;
     type TestFoldingEnvironment = FoldingEnvironment<3, TestStructure>;
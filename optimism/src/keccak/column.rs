@@ -13,10 +13,7 @@ use kimchi::circuits::polynomials::keccak::constants::{
     THETA_EXPAND_ROT_C_LEN, THETA_EXPAND_ROT_C_OFF, THETA_QUOTIENT_C_LEN, THETA_QUOTIENT_C_OFF,
     THETA_REMAINDER_C_LEN, THETA_REMAINDER_C_OFF, THETA_SHIFTS_C_LEN, THETA_SHIFTS_C_OFF,
 };
-use kimchi_msm::{
-    columns::{Column, ColumnIndexer},
-    witness::Witness,
-};
+use kimchi_msm::api::{Column, ColumnIndexer, Witness};
 use std::ops::{Index, IndexMut};
 
 /// The maximum total number of witness columns used by the Keccak circuit.
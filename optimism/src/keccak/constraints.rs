@@ -21,7 +21,7 @@ use kimchi::{
     },
     o1_utils::Two,
 };
-use kimchi_msm::columns::ColumnIndexer;
+use kimchi_msm::api::ColumnIndexer;
 
 /// This struct contains all that needs to be kept track of during the execution of the Keccak step interpreter
 #[derive(Clone, Debug)]
@@ -15,7 +15,7 @@ use ark_ff::Field;
 use kimchi::circuits::polynomials::keccak::constants::{
     DIM, KECCAK_COLS, QUARTERS, RATE_IN_BYTES, STATE_LEN,
 };
-use kimchi_msm::witness::Witness;
+use kimchi_msm::api::Witness;
 
 use self::{column::ZKVM_KECCAK_COLS, environment::KeccakEnv};
 
@@ -18,7 +18,7 @@ use crate::{
 };
 use ark_ff::Field;
 use kimchi::o1_utils::Two;
-use kimchi_msm::LookupTableID;
+use kimchi_msm::api::LookupTableID;
 
 /// This struct contains all that needs to be kept track of during the execution of the Keccak step interpreter
 // TODO: the fixed tables information should be inferred from the general environment
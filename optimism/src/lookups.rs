@@ -10,7 +10,7 @@ use kimchi::{
     },
     o1_utils::{FieldHelpers, Two},
 };
-use kimchi_msm::{LookupTableID, MVLookupTable};
+use kimchi_msm::api::{LookupTable as MVLookupTable, LookupTableID};
 
 /// All of the possible lookup table IDs used in the zkVM
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -47,18 +47,22 @@ impl LookupTableID for LookupTableIDs {
     }
 
     fn from_u32(value: u32) -> Self {
+        Self::try_from_u32(value).expect("Invalid table ID")
+    }
+
+    fn try_from_u32(value: u32) -> Option<Self> {
         match value {
-            0 => PadLookup,
-            1 => RoundConstantsLookup,
-            2 => ByteLookup,
-            3 => RangeCheck16Lookup,
-            4 => SparseLookup,
-            5 => ResetLookup,
-            6 => MemoryLookup,
-            7 => RegisterLookup,
-            8 => SyscallLookup,
-            9 => KeccakStepLookup,
-            _ => panic!("Invalid table ID"),
+            0 => Some(PadLookup),
+            1 => Some(RoundConstantsLookup),
+            2 => Some(ByteLookup),
+            3 => Some(RangeCheck16Lookup),
+            4 => Some(SparseLookup),
+            5 => Some(ResetLookup),
+            6 => Some(MemoryLookup),
+            7 => Some(RegisterLookup),
+            8 => Some(SyscallLookup),
+            9 => Some(KeccakStepLookup),
+            _ => None,
         }
     }
 
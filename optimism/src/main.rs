@@ -1,9 +1,7 @@
 use ark_ec::bn::Bn;
 use ark_ff::UniformRand;
 use kimchi::o1_utils;
-use kimchi_msm::{
-    columns::Column, proof::ProofInputs, prover::prove, verifier::verify, witness::Witness,
-};
+use kimchi_msm::api::{prove, verify, Column, ProofInputs, Witness};
 use kimchi_optimism::{
     cannon::{self, Meta, Start, State},
     cannon_cli,
@@ -1,10 +1,7 @@
 use std::ops::{Index, IndexMut};
 
 use super::witness::SCRATCH_SIZE;
-use kimchi_msm::{
-    columns::{Column, ColumnIndexer},
-    witness::Witness,
-};
+use kimchi_msm::api::{Column, ColumnIndexer, Witness};
 
 pub(crate) const MIPS_HASH_COUNTER_OFFSET: usize = 80;
 pub(crate) const MIPS_IS_SYSCALL_OFFSET: usize = 81;
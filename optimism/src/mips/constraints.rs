@@ -16,7 +16,7 @@ use kimchi::circuits::{
     expr::{ConstantExpr, Expr, ExprInner, Variable},
     gate::CurrOrNext,
 };
-use kimchi_msm::columns::{Column, ColumnIndexer as _};
+use kimchi_msm::api::{Column, ColumnIndexer as _};
 use std::array;
 
 /// The environment keeping the constraints between the different polynomials
@@ -18,7 +18,7 @@
 use std::collections::HashMap;
 
 use ark_ff::Field;
-use kimchi_msm::witness::Witness;
+use kimchi_msm::api::Witness;
 use strum::IntoEnumIterator;
 
 use crate::{
@@ -1,7 +1,7 @@
 use crate::lookups::LookupTableIDs;
 use ark_poly::{Evaluations, Radix2EvaluationDomain as D};
 use kimchi::{circuits::domains::EvaluationDomains, curve::KimchiCurve, plonk_sponge::FrSponge};
-use kimchi_msm::{proof::ProofInputs, witness::Witness};
+use kimchi_msm::api::{ProofInputs, Witness};
 use mina_poseidon::{sponge::ScalarChallenge, FqSponge};
 use poly_commitment::{commitment::absorb_commitment, OpenProof, SRS as _};
 use rayon::iter::{
@@ -1,5 +1,5 @@
 use ark_ff::{Field, One, Zero};
-use kimchi_msm::{LookupTableID, MVLookup};
+use kimchi_msm::api::{LookupTableID, MVLookup};
 
 /// Enum representing the two different modes of a RAMLookup
 #[derive(Copy, Clone, Debug)]
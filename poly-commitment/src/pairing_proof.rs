@@ -43,6 +43,14 @@ impl<Pair: PairingEngine> Clone for PairingProof<Pair> {
     }
 }
 
+impl<Pair: PairingEngine> PartialEq for PairingProof<Pair> {
+    fn eq(&self, other: &Self) -> bool {
+        self.quotient == other.quotient && self.blinding == other.blinding
+    }
+}
+
+impl<Pair: PairingEngine> Eq for PairingProof<Pair> {}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PairingSRS<Pair: PairingEngine> {
     pub full_srs: SRS<Pair::G1Affine>,
@@ -219,42 +227,51 @@ impl<
 }
 
 /// The polynomial that evaluates to each of `evals` for the respective `elm`s.
+/// The points must be distinct.
 fn eval_polynomial<F: PrimeField>(elm: &[F], evals: &[F]) -> DensePolynomial<F> {
     assert_eq!(elm.len(), evals.len());
-    let (zeta, zeta_omega) = if elm.len() == 2 {
-        (elm[0], elm[1])
-    } else {
-        todo!()
-    };
-    let (eval_zeta, eval_zeta_omega) = if evals.len() == 2 {
-        (evals[0], evals[1])
-    } else {
-        todo!()
-    };
-
-    // The polynomial that evaluates to `p(zeta)` at `zeta` and `p(zeta_omega)` at
-    // `zeta_omega`.
-    // We write `p(x) = a + bx`, which gives
+    // The Lagrange interpolation of the evaluations, i.e. the sum over `i` of
     // ```text
-    // p(zeta) = a + b * zeta
-    // p(zeta_omega) = a + b * zeta_omega
+    // evals[i] * prod_{j != i} (x - elm[j]) / (elm[i] - elm[j])
     // ```
-    // and so
-    // ```text
-    // b = (p(zeta_omega) - p(zeta)) / (zeta_omega - zeta)
-    // a = p(zeta) - b * zeta
-    // ```
-    let b = (eval_zeta_omega - eval_zeta) / (zeta_omega - zeta);
-    let a = eval_zeta - b * zeta;
-    DensePolynomial::from_coefficients_slice(&[a, b])
+    // which is the constant `evals[0]` for a single point, and the zero
+    // polynomial without any point.
+    elm.iter()
+        .zip(evals)
+        .enumerate()
+        .map(|(i, (x_i, eval_i))| {
+            let others: Vec<F> = elm
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, x_j)| *x_j)
+                .collect();
+            let denominator: F = others.iter().map(|x_j| *x_i - x_j).product();
+            let scale = *eval_i / denominator;
+            let basis = divisor_polynomial(&others);
+            DensePolynomial::from_coefficients_vec(
+                basis.coeffs.iter().map(|c| *c * scale).collect(),
+            )
+        })
+        .fold(DensePolynomial::zero(), |acc, term| &acc + &term)
 }
 
-/// The polynomial that evaluates to `0` at the evaluation points.
+/// The polynomial that evaluates to `0` at the evaluation points, i.e. the
+/// constant `1` without any point.
 fn divisor_polynomial<F: PrimeField>(elm: &[F]) -> DensePolynomial<F> {
     elm.iter()
         .map(|value| DensePolynomial::from_coefficients_slice(&[-(*value), F::one()]))
-        .reduce(|poly1, poly2| &poly1 * &poly2)
-        .unwrap()
+        .fold(
+            DensePolynomial::from_coefficients_slice(&[F::one()]),
+            |poly1, poly2| &poly1 * &poly2,
+        )
+}
+
+/// Whether a proof can be made at the evaluation points, i.e. there is at
+/// least one, and they are pairwise distinct as required to interpolate the
+/// evaluations, see [eval_polynomial].
+fn valid_points<F: PrimeField>(elm: &[F]) -> bool {
+    !elm.is_empty() && elm.iter().enumerate().all(|(i, x)| !elm[..i].contains(x))
 }
 
 impl<
@@ -270,6 +287,9 @@ impl<
         elm: &[F],                         // vector of evaluation points
         polyscale: F,                      // scaling factor for polynoms
     ) -> Option<Self> {
+        if !valid_points(elm) {
+            return None;
+        }
         let (p, blinding_factor) = combine_polys::<G, D>(plnms, polyscale, srs.full_srs.g.len());
         let evals: Vec<_> = elm.iter().map(|pt| p.evaluate(pt)).collect();
 
@@ -301,6 +321,9 @@ impl<
         polyscale: F,                     // scaling factor for polynoms
         elm: &[F],                        // vector of evaluation points
     ) -> bool {
+        if !valid_points(elm) {
+            return false;
+        }
         let poly_commitment = {
             let mut scalars: Vec<F> = Vec::new();
             let mut points = Vec::new();
@@ -339,13 +362,13 @@ impl<
 
 #[cfg(test)]
 mod tests {
-    use super::{PairingProof, PairingSRS};
+    use super::{divisor_polynomial, eval_polynomial, valid_points, PairingProof, PairingSRS};
     use crate::{
         commitment::Evaluation, evaluation_proof::DensePolynomialOrEvaluations, srs::SRS, SRS as _,
     };
     use ark_bn254::{Fr as ScalarField, G1Affine as G1, G2Affine as G2, Parameters};
     use ark_ec::bn::Bn;
-    use ark_ff::UniformRand;
+    use ark_ff::{UniformRand, Zero};
     use ark_poly::{
         univariate::DensePolynomial, EvaluationDomain, Polynomial, Radix2EvaluationDomain as D,
         UVPolynomial,
@@ -353,8 +376,10 @@ mod tests {
 
     use rand::{rngs::StdRng, SeedableRng};
 
-    #[test]
-    fn test_pairing_proof() {
+    /// Create and verify a pairing proof of 4 random polynomials at
+    /// `n_points` random points. Returns `None` if the proof can not be
+    /// created.
+    fn prove_and_verify(n_points: usize) -> Option<bool> {
         let n = 64;
         let domain = D::<ScalarField>::new(n).unwrap();
 
@@ -363,7 +388,8 @@ mod tests {
         let x = ScalarField::rand(rng);
 
         let mut srs = SRS::<G1>::create_trusted_setup(x, n);
-        let verifier_srs = SRS::<G2>::create_trusted_setup(x, 3);
+        // The divisor polynomial has degree `n_points`
+        let verifier_srs = SRS::<G2>::create_trusted_setup(x, std::cmp::max(3, n_points + 1));
         srs.add_lagrange_basis(domain);
 
         let srs = PairingSRS {
@@ -392,7 +418,7 @@ mod tests {
             })
             .collect();
 
-        let evaluation_points = vec![ScalarField::rand(rng), ScalarField::rand(rng)];
+        let evaluation_points: Vec<_> = (0..n_points).map(|_| ScalarField::rand(rng)).collect();
 
         let evaluations: Vec<_> = polynomials
             .iter()
@@ -419,10 +445,45 @@ mod tests {
             polynomials_and_blinders.as_slice(),
             &evaluation_points,
             polyscale,
-        )
-        .unwrap();
+        )?;
+
+        Some(pairing_proof.verify(&srs, &evaluations, polyscale, &evaluation_points))
+    }
+
+    #[test]
+    fn test_pairing_proof() {
+        assert_eq!(prove_and_verify(2), Some(true));
+    }
 
-        let res = pairing_proof.verify(&srs, &evaluations, polyscale, &evaluation_points);
-        assert!(res);
+    #[test]
+    fn test_pairing_proof_any_number_of_points() {
+        for n_points in [1, 3] {
+            assert_eq!(prove_and_verify(n_points), Some(true), "{n_points} points");
+        }
+        // There is nothing to open without any point
+        assert_eq!(prove_and_verify(0), None);
+    }
+
+    #[test]
+    fn test_eval_polynomial() {
+        let rng = &mut StdRng::from_seed([0u8; 32]);
+        for n_points in 0..5 {
+            let elm: Vec<_> = (0..n_points).map(|_| ScalarField::rand(rng)).collect();
+            let evals: Vec<_> = (0..n_points).map(|_| ScalarField::rand(rng)).collect();
+            let p = eval_polynomial(&elm, &evals);
+            assert!(p.degree() < std::cmp::max(n_points, 1));
+            for (x, eval) in elm.iter().zip(evals.iter()) {
+                assert_eq!(p.evaluate(x), *eval);
+                assert!(divisor_polynomial(&elm).evaluate(x).is_zero());
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_evaluation_points() {
+        let x = ScalarField::from(42u64);
+        assert!(valid_points(&[x, ScalarField::from(1u64)]));
+        assert!(!valid_points(&[x, ScalarField::from(1u64), x]));
+        assert!(!valid_points::<ScalarField>(&[]));
     }
 }